@@ -0,0 +1,233 @@
+//! Fill-in and factorisation time of each ordering against the natural one,
+//! on MNA matrices shaped like post-layout RC parasitics.
+//!
+//! Run with `cargo bench -p nim_spice --bench ordering`.
+
+use nim_spice::maths::sparse::{Lu, LuOptions, Ordering, Pattern, SparseMatrix, Statistics};
+use std::time::{Duration, Instant};
+
+const REFACTORS: u32 = 20;
+
+/// Conductance stamps and extra entries of one benchmark matrix.
+struct Circuit {
+    size: usize,
+    entries: Vec<(usize, usize, f64)>,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Circuit {
+            size: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn node(&mut self) -> usize {
+        self.size += 1;
+        self.size - 1
+    }
+
+    /// A conductance between two nodes.
+    fn resistor(&mut self, a: usize, b: usize, g: f64) {
+        self.entries.push((a, a, g));
+        self.entries.push((b, b, g));
+        self.entries.push((a, b, -g));
+        self.entries.push((b, a, -g));
+    }
+
+    /// A conductance from `a` to ground.
+    fn load(&mut self, a: usize, g: f64) {
+        self.entries.push((a, a, g));
+    }
+
+    /// A voltage source from `node` to ground, with a branch row.
+    fn source(&mut self, node: usize) {
+        let branch = self.node();
+        self.entries.push((node, branch, 1.0));
+        self.entries.push((branch, node, 1.0));
+    }
+
+    /// A transconductance driving `output` from `input`.
+    fn vccs(&mut self, output: usize, input: usize, gm: f64) {
+        self.entries.push((output, input, gm));
+    }
+
+    /// A `width` x `height` grid of wire segments, returning its nodes.
+    /// The capacitance to ground shows up as the small conductance of its
+    /// transient companion.
+    fn mesh(
+        &mut self,
+        width: usize,
+        height: usize,
+        random: &mut impl FnMut() -> f64,
+    ) -> Vec<usize> {
+        let nodes: Vec<usize> = (0..width * height).map(|_| self.node()).collect();
+        for y in 0..height {
+            for x in 0..width {
+                let node = nodes[y * width + x];
+                self.load(node, 1e-6 * (1.0 + random()));
+                if x + 1 < width {
+                    self.resistor(node, nodes[y * width + x + 1], 1e-2 * (1.0 + random()));
+                }
+                if y + 1 < height {
+                    self.resistor(node, nodes[(y + 1) * width + x], 1e-2 * (1.0 + random()));
+                }
+            }
+        }
+        nodes
+    }
+
+    fn matrix(&self) -> SparseMatrix {
+        let mut pattern = Pattern::new(self.size);
+        let handles: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(r, c, _)| pattern.handle(*r, *c))
+            .collect();
+        let mut matrix = pattern.matrix();
+        for (handle, (_, _, value)) in handles.iter().zip(&self.entries) {
+            matrix.add(*handle, *value);
+        }
+        matrix
+    }
+}
+
+fn lcg(mut seed: u64) -> impl FnMut() -> f64 {
+    move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as f64 / (1u64 << 31) as f64
+    }
+}
+
+/// One large driven power-grid-like mesh: symmetric apart from the source.
+fn grid() -> Circuit {
+    let mut random = lcg(1);
+    let mut circuit = Circuit::new();
+    let nodes = circuit.mesh(40, 40, &mut random);
+    circuit.source(nodes[0]);
+    circuit
+}
+
+/// Many small nets, each driven by a buffer from the far end of the one
+/// before, as in a chain of routed signals: block triangular.
+fn buffered_nets() -> Circuit {
+    let mut random = lcg(2);
+    let mut circuit = Circuit::new();
+    let mut previous: Option<usize> = None;
+    for _ in 0..40 {
+        let nodes = circuit.mesh(8, 5, &mut random);
+        match previous {
+            Some(input) => {
+                circuit.vccs(nodes[0], input, 1e-2);
+                circuit.load(nodes[0], 1e-2);
+            }
+            None => circuit.source(nodes[0]),
+        }
+        previous = nodes.last().copied();
+    }
+    circuit
+}
+
+/// A mesh with transconductances between random nodes, which break its
+/// symmetry and couple distant parts of it.
+fn coupled_mesh() -> Circuit {
+    let mut random = lcg(3);
+    let mut circuit = Circuit::new();
+    let nodes = circuit.mesh(20, 20, &mut random);
+    circuit.source(nodes[0]);
+    for _ in 0..nodes.len() {
+        let output = nodes[(random() * nodes.len() as f64) as usize % nodes.len()];
+        let input = nodes[(random() * nodes.len() as f64) as usize % nodes.len()];
+        if output != input {
+            circuit.vccs(output, input, 1e-4 * random());
+        }
+    }
+    circuit
+}
+
+fn main() {
+    let orderings = [
+        Ordering::Natural,
+        Ordering::Markowitz,
+        Ordering::Amd,
+        Ordering::Colamd,
+        Ordering::Btf,
+        Ordering::Auto,
+    ];
+    for (name, circuit) in [
+        ("grid", grid()),
+        ("buffered nets", buffered_nets()),
+        ("coupled mesh", coupled_mesh()),
+    ] {
+        let matrix = circuit.matrix();
+        let statistics = Statistics::of(&matrix);
+        println!(
+            "{}: {} unknowns, {} entries, symmetry {:.2}, {} blocks (largest {}), auto picks {:?}",
+            name,
+            statistics.size,
+            statistics.nnz,
+            statistics.symmetry,
+            statistics.blocks,
+            statistics.largest_block,
+            Ordering::choose(&statistics)
+        );
+        println!(
+            "{:>10} {:>10} {:>10} {:>12} {:>12} {:>10}",
+            "ordering", "L+U", "fill-in", "analyze", "refactor", "error"
+        );
+        let b: Vec<f64> = (0..matrix.size()).map(|i| (i % 7) as f64).collect();
+        for ordering in orderings {
+            let mut lu = Lu::new(LuOptions {
+                ordering,
+                ..LuOptions::default()
+            });
+            let start = Instant::now();
+            lu.analyze(&matrix).expect("benchmark matrix is singular");
+            let analyze = start.elapsed();
+            let start = Instant::now();
+            for _ in 0..REFACTORS {
+                lu.refactor(&matrix).unwrap();
+            }
+            let refactor = start.elapsed() / REFACTORS;
+
+            let mut x = b.clone();
+            lu.solve(&mut x);
+            let error = backward_error(&matrix, &x, &b);
+            assert!(error < 1e-6, "{:?} backward error {}", ordering, error);
+
+            let symbolic = lu.symbolic().unwrap();
+            println!(
+                "{:>10} {:>10} {:>10} {:>12} {:>12} {:>10.1e}",
+                format!("{:?}", ordering),
+                symbolic.factor_nnz(),
+                symbolic.fill_in(),
+                milliseconds(analyze),
+                milliseconds(refactor),
+                error
+            );
+        }
+        println!();
+    }
+}
+
+/// `|A x - b| / (|A| |x| + |b|)` in the infinity norm.
+fn backward_error(matrix: &SparseMatrix, x: &[f64], b: &[f64]) -> f64 {
+    let mut row_sums = vec![0.0; matrix.size()];
+    for (row, _, value) in matrix.entries() {
+        row_sums[row] += f64::abs(value);
+    }
+    let norm = |v: &[f64]| v.iter().fold(0.0, |m, x| f64::max(m, x.abs()));
+    let residual: Vec<f64> = matrix
+        .multiply(x)
+        .iter()
+        .zip(b)
+        .map(|(ax, b)| ax - b)
+        .collect();
+    norm(&residual) / (norm(&row_sums) * norm(x) + norm(b))
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1e3)
+}
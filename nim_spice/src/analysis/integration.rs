@@ -0,0 +1,404 @@
+//! Integration formulas for the charge and flux states of the transient
+//! analysis.
+//!
+//! Every formula approximates the derivative of a state at the new time
+//! point as `ag[0] q(n+1)` plus a term from the states and derivatives
+//! already accepted. With the state a linear function of a circuit
+//! quantity, `q = C v` or `φ = L i`, that is a companion model: a
+//! conductance (or resistance) `ag[0] C` and a source from the history.
+
+use std::fmt::Display;
+
+/// Highest Gear order, as in SPICE.
+pub const MAX_ORDER: usize = 6;
+
+/// Weight of the old derivative in the modified trapezoidal rule, the `xmu`
+/// of SPICE's trapezoidal formula, which leaves `1 - xmu = 0.55` on the new
+/// point. Trapezoidal proper uses one half for both.
+const MODIFIED_TRAP_XMU: f64 = 0.45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Method {
+    BackwardEuler,
+    Trapezoidal,
+    /// Trapezoidal with slightly more weight on the new point, like
+    /// LTspice's default. A stiff state that trapezoidal would leave
+    /// ringing from one step to the next decays instead, at the cost of a
+    /// small first-order error.
+    #[default]
+    ModifiedTrapezoidal,
+    /// Backward differentiation of variable order up to `maxord`.
+    Gear,
+}
+
+impl Method {
+    /// The method named by `.options method=`.
+    pub fn parse(name: &str) -> Option<Method> {
+        match name.to_ascii_lowercase().as_str() {
+            "euler" | "be" => Some(Method::BackwardEuler),
+            "trap" | "trapezoidal" => Some(Method::Trapezoidal),
+            "modtrap" => Some(Method::ModifiedTrapezoidal),
+            "gear" | "bdf" => Some(Method::Gear),
+            _ => None,
+        }
+    }
+
+    /// Highest order the method reaches.
+    pub fn max_order(self, maxord: usize) -> usize {
+        match self {
+            Method::BackwardEuler => 1,
+            Method::Trapezoidal | Method::ModifiedTrapezoidal => 2,
+            Method::Gear => maxord.clamp(1, MAX_ORDER),
+        }
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Method::BackwardEuler => "euler",
+            Method::Trapezoidal => "trap",
+            Method::ModifiedTrapezoidal => "modtrap",
+            Method::Gear => "gear",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Accepted values of one state, newest first, and its derivative at the
+/// newest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    values: Vec<f64>,
+    derivative: f64,
+}
+
+impl History {
+    /// A state that starts at `value` and is not changing, as at the
+    /// operating point.
+    pub fn new(value: f64) -> Self {
+        History {
+            values: vec![value],
+            derivative: 0.0,
+        }
+    }
+
+    /// The value `k` points back, zero being the newest.
+    pub fn value(&self, k: usize) -> f64 {
+        self.values[k.min(self.values.len() - 1)]
+    }
+
+    /// Every value kept, newest first, as the truncation error estimate
+    /// wants them.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn derivative(&self) -> f64 {
+        self.derivative
+    }
+
+    /// Record the value and derivative at a newly accepted point.
+    pub fn accept(&mut self, value: f64, derivative: f64) {
+        self.values.insert(0, value);
+        self.values.truncate(MAX_ORDER + 1);
+        self.derivative = derivative;
+    }
+}
+
+/// Linearised derivative of a state `q = value * x`: `dq/dt = geq x + ceq`.
+/// For a capacitor `geq` is a conductance and `ceq` a current; for an
+/// inductor they are a resistance and a voltage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Companion {
+    pub geq: f64,
+    pub ceq: f64,
+}
+
+/// The integration formula for the step being taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Integrator {
+    method: Method,
+    max_order: usize,
+    order: usize,
+    /// Accepted step sizes, newest first.
+    steps: Vec<f64>,
+    ag: Vec<f64>,
+}
+
+impl Integrator {
+    /// `maxord` only matters for Gear.
+    pub fn new(method: Method, maxord: usize) -> Self {
+        Integrator {
+            method,
+            max_order: method.max_order(maxord),
+            order: 1,
+            steps: Vec::new(),
+            ag: Vec::new(),
+        }
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// The highest order the accepted history allows.
+    pub fn available_order(&self) -> usize {
+        (self.steps.len() + 1).min(self.max_order)
+    }
+
+    /// Choose the order of the next step, within what the method and the
+    /// history allow.
+    pub fn set_order(&mut self, order: usize) {
+        self.order = order.clamp(1, self.available_order());
+    }
+
+    /// Accepted step sizes, newest first.
+    pub fn steps(&self) -> &[f64] {
+        &self.steps
+    }
+
+    /// Coefficients of the formula for a step of `h`, newest point first.
+    pub fn ag(&self) -> &[f64] {
+        &self.ag
+    }
+
+    /// Set up the formula for a step of `h` from the last accepted point.
+    pub fn prepare(&mut self, h: f64) {
+        self.ag = match (self.method, self.order) {
+            (Method::Trapezoidal, 2) => trapezoidal(h, 0.5),
+            (Method::ModifiedTrapezoidal, 2) => trapezoidal(h, MODIFIED_TRAP_XMU),
+            (_, order) => gear(h, &self.steps, order),
+        };
+    }
+
+    /// Record a step of `h` as accepted and, until the method's highest
+    /// order, raise the order for the next one.
+    pub fn accept(&mut self, h: f64) {
+        self.steps.insert(0, h);
+        self.steps.truncate(MAX_ORDER);
+        self.order = (self.order + 1).min(self.available_order());
+    }
+
+    /// Forget the history, as after a breakpoint where the waveforms have
+    /// a corner: the next step is first order again.
+    pub fn restart(&mut self) {
+        self.steps.clear();
+        self.order = 1;
+    }
+
+    /// Whether the formula of this step is the trapezoidal one, which
+    /// carries the derivative rather than older values.
+    fn is_trapezoidal(&self) -> bool {
+        matches!(
+            self.method,
+            Method::Trapezoidal | Method::ModifiedTrapezoidal
+        ) && self.order == 2
+    }
+
+    /// The part of the derivative that does not depend on the new value.
+    fn remainder(&self, history: &History) -> f64 {
+        if self.is_trapezoidal() {
+            -self.ag[0] * history.value(0) + self.ag[1] * history.derivative()
+        } else {
+            (1..self.ag.len())
+                .map(|k| self.ag[k] * history.value(k - 1))
+                .sum()
+        }
+    }
+
+    /// Derivative of a state at the new point where it has `value`.
+    pub fn derivative(&self, history: &History, value: f64) -> f64 {
+        self.ag[0] * value + self.remainder(history)
+    }
+
+    /// Companion model of a state `q = scale * x`, such as `q = C v`.
+    pub fn companion(&self, history: &History, scale: f64) -> Companion {
+        Companion {
+            geq: self.ag[0] * scale,
+            ceq: self.remainder(history),
+        }
+    }
+}
+
+/// The theta method `q' = (q(n+1) - q(n)) / (h (1 - xmu)) - xmu / (1 - xmu) q'(n)`,
+/// as `[1 / (h (1 - xmu)), -xmu / (1 - xmu)]`; the second coefficient
+/// multiplies the old derivative.
+fn trapezoidal(h: f64, xmu: f64) -> Vec<f64> {
+    vec![1.0 / (h * (1.0 - xmu)), -xmu / (1.0 - xmu)]
+}
+
+/// Backward differentiation of `order` on the variable steps `steps`,
+/// exact for polynomials up to that degree. With times measured from the
+/// new point in units of `h`, `s[0] = 0, s[1] = -1, s[2] = -1 - h1/h, ...`,
+/// the coefficients solve `Σ a[j] s[j]^m = [m == 1]` for `m = 0..=order`.
+fn gear(h: f64, steps: &[f64], order: usize) -> Vec<f64> {
+    let size = order + 1;
+    let mut s = vec![0.0; size];
+    let mut t = 0.0;
+    for j in 1..size {
+        t -= if j == 1 { h } else { steps[j - 2] };
+        s[j] = t / h;
+    }
+    // Rows m = 0..=order of the Vandermonde system, augmented.
+    let mut a: Vec<Vec<f64>> = (0..size)
+        .map(|m| {
+            let mut row: Vec<f64> = s.iter().map(|x| x.powi(m as i32)).collect();
+            row.push(if m == 1 { 1.0 } else { 0.0 });
+            row
+        })
+        .collect();
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|x, y| a[*x][column].abs().total_cmp(&a[*y][column].abs()))
+            .unwrap();
+        a.swap(column, pivot);
+        let (done, rest) = a.split_at_mut(column + 1);
+        let pivot_row = &done[column];
+        for row in rest {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+        }
+    }
+    let mut x = vec![0.0; size];
+    for row in (0..size).rev() {
+        let sum: f64 = (row + 1..size).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (a[row][size] - sum) / a[row][row];
+    }
+    x.into_iter().map(|a| a / h).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrate `x' = -λ x` from `x(0) = 1` with the companion model of a
+    /// unit capacitor, which makes each step `geq x + ceq = -λ x`.
+    fn decay(method: Method, maxord: usize, lambda: f64, h: f64, steps: usize) -> f64 {
+        let mut integrator = Integrator::new(method, maxord);
+        let mut history = History::new(1.0);
+        let mut x = 1.0;
+        for _ in 0..steps {
+            integrator.prepare(h);
+            let companion = integrator.companion(&history, 1.0);
+            x = -companion.ceq / (companion.geq + lambda);
+            history.accept(x, integrator.derivative(&history, x));
+            integrator.accept(h);
+        }
+        x
+    }
+
+    #[test]
+    fn test_gear_coefficients() {
+        let h = 0.5;
+        let mut integrator = Integrator::new(Method::Gear, 6);
+        for _ in 0..6 {
+            integrator.accept(h);
+        }
+        let expected: [&[f64]; 3] = [
+            &[1.0, -1.0],
+            &[1.5, -2.0, 0.5],
+            &[11.0 / 6.0, -3.0, 1.5, -1.0 / 3.0],
+        ];
+        for (order, expected) in (1..).zip(expected) {
+            integrator.set_order(order);
+            integrator.prepare(h);
+            for (a, e) in integrator.ag().iter().zip(expected) {
+                assert!((a * h - e).abs() < 1e-12, "order {}", order);
+            }
+        }
+        integrator.set_order(9);
+        assert_eq!(integrator.order(), 6);
+    }
+
+    #[test]
+    fn test_gear_variable_steps() {
+        // Order k is exact for polynomials of degree k on uneven steps.
+        let steps = [0.3, 0.1, 0.25, 0.05, 0.2, 0.15];
+        let mut integrator = Integrator::new(Method::Gear, 6);
+        let mut times = vec![0.0];
+        for h in steps {
+            integrator.accept(h);
+            times.insert(0, times[0] + h);
+        }
+        let h = 0.12;
+        let t = times[0] + h;
+        for order in 1..=6 {
+            integrator.set_order(order);
+            integrator.prepare(h);
+            let q = |t: f64| t.powi(order as i32);
+            let mut history = History::new(q(times[times.len() - 1]));
+            for &time in times.iter().rev().skip(1) {
+                history.accept(q(time), 0.0);
+            }
+            let derivative = integrator.derivative(&history, q(t));
+            let exact = order as f64 * t.powi(order as i32 - 1);
+            assert!(
+                (derivative - exact).abs() < 1e-9 * exact.abs().max(1.0),
+                "order {}",
+                order
+            );
+        }
+    }
+
+    #[test]
+    fn test_accuracy() {
+        // Error at t = 1 for h = 0.1 and h = 0.05 shows each method's order.
+        let exact = (-1.0f64).exp();
+        let error =
+            |method, maxord, n| (decay(method, maxord, 1.0, 1.0 / n as f64, n) - exact).abs();
+        let ratio = |method, maxord| error(method, maxord, 10) / error(method, maxord, 20);
+        assert!((ratio(Method::BackwardEuler, 1) - 2.0).abs() < 0.2);
+        assert!(ratio(Method::Trapezoidal, 2) > 3.5);
+        assert!(ratio(Method::Gear, 2) > 3.0);
+        assert!(error(Method::ModifiedTrapezoidal, 2, 20) < error(Method::BackwardEuler, 1, 20));
+    }
+
+    #[test]
+    fn test_ringing() {
+        // A stiff state with a step far above its time constant. After the
+        // first-order start, trapezoidal flips its sign every step and
+        // barely decays, the modified rule damps it and backward Euler does
+        // not ring at all.
+        let trap = [20, 21].map(|n| decay(Method::Trapezoidal, 2, 1e3, 0.1, n));
+        assert!(trap[0] * trap[1] < 0.0);
+        assert!(trap[1].abs() > 0.9 * trap[0].abs());
+        let modtrap = decay(Method::ModifiedTrapezoidal, 2, 1e3, 0.1, 21);
+        assert!(modtrap.abs() < 0.1 * trap[1].abs());
+        let euler = decay(Method::BackwardEuler, 1, 1e3, 0.1, 21);
+        assert!(euler > 0.0 && euler < 1e-20);
+    }
+
+    #[test]
+    fn test_inductor_companion() {
+        // A current ramping at 2 A/s through 3 H: every method gives 6 V
+        // once the ramp is in its history.
+        for method in [
+            Method::BackwardEuler,
+            Method::Trapezoidal,
+            Method::ModifiedTrapezoidal,
+            Method::Gear,
+        ] {
+            let mut integrator = Integrator::new(method, 3);
+            let inductance = 3.0;
+            let mut history = History::new(0.0);
+            let mut t = 0.0;
+            for h in [0.1, 0.2, 0.05, 0.3] {
+                t += h;
+                integrator.prepare(h);
+                let current = 2.0 * t;
+                let companion = integrator.companion(&history, inductance);
+                let voltage = companion.geq * current + companion.ceq;
+                assert!((voltage - 6.0).abs() < 1e-9, "{} at {}", method, t);
+                history.accept(inductance * current, voltage);
+                integrator.accept(h);
+            }
+        }
+    }
+}
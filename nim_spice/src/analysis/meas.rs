@@ -0,0 +1,631 @@
+//! Evaluation of `.meas` directives against simulation results.
+//!
+//! Expressions are evaluated at every point of a run and the resulting
+//! curve is treated as piece-wise linear between points, as LTspice does:
+//! values at `AT=` and crossings are interpolated, and integrals use the
+//! trapezoidal rule.
+
+use crate::analysis::results::{Dataset, ResultSet, StepPoint};
+use crate::frontend::parser::expression::{is_probe, Expr, Scope};
+use crate::frontend::parser::meas::{
+    parse_meas, AnalysisKind, Condition, Count, Edge, MeasureKind, Measurement, Point, Statistic,
+};
+use crate::frontend::parser::netlist::Netlist;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Measurement results with one row per `.step` point and one column per
+/// `.meas` directive. A failed measurement keeps the reason, e.g. a
+/// crossing that never happens.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeasurementTable {
+    pub names: Vec<String>,
+    pub rows: Vec<MeasurementRow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasurementRow {
+    pub step: StepPoint,
+    pub values: Vec<Result<f64, String>>,
+}
+
+impl MeasurementTable {
+    /// The value of a measurement in one row.
+    pub fn value(&self, row: usize, name: &str) -> Option<f64> {
+        let column = self
+            .names
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(name))?;
+        self.rows.get(row)?.values[column].clone().ok()
+    }
+}
+
+impl Display for MeasurementTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "step")?;
+        for name in &self.names {
+            write!(f, "\t{}", name)?;
+        }
+        writeln!(f)?;
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.step.values.is_empty() {
+                write!(f, "{}", i + 1)?;
+            } else {
+                write!(f, "{}", row.step)?;
+            }
+            for value in &row.values {
+                match value {
+                    Ok(value) => write!(f, "\t{}", value)?,
+                    Err(_) => write!(f, "\tFAILED")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `.meas` directives of a netlist, in order.
+pub fn measurements(netlist: &Netlist) -> Result<Vec<Measurement>, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut measurements: Vec<Measurement> = Vec::new();
+    for directive in &netlist.directives {
+        let is_meas = directive.split_whitespace().next().is_some_and(|first| {
+            first.eq_ignore_ascii_case(".meas") || first.eq_ignore_ascii_case(".measure")
+        });
+        if !is_meas {
+            continue;
+        }
+        match parse_meas(directive) {
+            Ok(meas) => {
+                if measurements
+                    .iter()
+                    .any(|m| m.name.eq_ignore_ascii_case(&meas.name))
+                {
+                    error_handler.add_error(Error::new(
+                        ErrorType::Semantic,
+                        format!("Measurement {} is defined twice", meas.name),
+                        0,
+                        0,
+                    ));
+                }
+                measurements.push(meas);
+            }
+            Err(errors) => error_handler.errors.extend(errors.errors),
+        }
+    }
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(measurements)
+    }
+}
+
+/// Evaluate the `.meas` directives of `netlist` on every run of `results`.
+/// Only measurements for the analysis that produced the results, or for
+/// no analysis in particular, become columns of the table.
+pub fn measure_all(
+    netlist: &Netlist,
+    results: &ResultSet,
+) -> Result<MeasurementTable, ErrorHandler> {
+    let measurements = measurements(netlist)?;
+    let parameters = parameters(netlist);
+    let kind = results.runs.first().map(analysis_of);
+    let measurements: Vec<Measurement> = measurements
+        .into_iter()
+        .filter(|m| m.analysis.is_none() || m.analysis == kind)
+        .collect();
+
+    let mut table = MeasurementTable {
+        names: measurements.iter().map(|m| m.name.clone()).collect(),
+        rows: Vec::new(),
+    };
+    for (step, dataset) in results.steps.iter().zip(&results.runs) {
+        table.rows.push(MeasurementRow {
+            step: step.clone(),
+            values: measure(&measurements, dataset, step, &parameters),
+        });
+    }
+    Ok(table)
+}
+
+/// Evaluate measurements in order on one run, so that `PARAM` can use the
+/// results before it.
+pub fn measure(
+    measurements: &[Measurement],
+    dataset: &Dataset,
+    step: &StepPoint,
+    parameters: &BTreeMap<String, f64>,
+) -> Vec<Result<f64, String>> {
+    let mut known: Vec<(String, f64)> = Vec::new();
+    let mut values = Vec::new();
+    for measurement in measurements {
+        let context = Context {
+            dataset,
+            step,
+            parameters,
+            known: &known,
+        };
+        let value = context.evaluate(&measurement.kind);
+        if let Ok(value) = value {
+            known.push((measurement.name.clone(), value));
+        }
+        values.push(value);
+    }
+    values
+}
+
+/// The analysis a run came from, told apart by its sweep axis.
+fn analysis_of(dataset: &Dataset) -> AnalysisKind {
+    match dataset.axis.to_ascii_lowercase().as_str() {
+        "time" => AnalysisKind::Tran,
+        "frequency" | "freq" => AnalysisKind::Ac,
+        "" => AnalysisKind::Op,
+        _ => AnalysisKind::Dc,
+    }
+}
+
+/// Numeric `.param` values; parameters defined by expressions are left out.
+fn parameters(netlist: &Netlist) -> BTreeMap<String, f64> {
+    let mut parameters = BTreeMap::new();
+    for directive in &netlist.directives {
+        let mut words = directive.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case(".param"))
+        {
+            continue;
+        }
+        let rest: Vec<&str> = words.collect();
+        let joined = rest.join(" ").replace(" =", "=").replace("= ", "=");
+        for assignment in joined.split_whitespace() {
+            if let Some((name, value)) = assignment.split_once('=') {
+                if let Some(value) = parse_number(value) {
+                    parameters.insert(name.to_ascii_lowercase(), value);
+                }
+            }
+        }
+    }
+    parameters
+}
+
+struct Context<'a> {
+    dataset: &'a Dataset,
+    step: &'a StepPoint,
+    parameters: &'a BTreeMap<String, f64>,
+    known: &'a [(String, f64)],
+}
+
+/// Names visible to a measurement expression, at one point of the run or,
+/// for `PARAM` and `AT=` values, outside of any point.
+struct PointScope<'a> {
+    context: &'a Context<'a>,
+    index: Option<usize>,
+}
+
+impl Scope for PointScope<'_> {
+    fn variable(&self, name: &str) -> Option<f64> {
+        let context = self.context;
+        if let Some(index) = self.index {
+            let vector = match name.to_ascii_lowercase().as_str() {
+                "time" | "freq" | "frequency" => context.dataset.vector(&context.dataset.axis),
+                _ => context.dataset.vector(name),
+            };
+            if let Some(value) = vector.and_then(|v| v.get(index)) {
+                return Some(*value);
+            }
+        }
+        context
+            .known
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+            .or_else(|| context.step.value(name))
+            .or_else(|| context.parameters.get(&name.to_ascii_lowercase()).copied())
+    }
+
+    fn function(&self, name: &str, args: &[Expr]) -> Option<Result<f64, String>> {
+        let lower = name.to_ascii_lowercase();
+        match lower.as_str() {
+            "mag" | "db" | "ph" | "re" | "im" => {
+                let [arg] = args else {
+                    return Some(Err(format!("{}() takes 1 argument", name)));
+                };
+                let value = match arg {
+                    Expr::Call(probe, probe_args) if is_probe(probe) => {
+                        self.probe(probe, probe_args)
+                    }
+                    _ => arg.eval(self).map(|v| (v, 0.0)),
+                };
+                Some(value.map(|(re, im)| match lower.as_str() {
+                    "mag" => re.hypot(im),
+                    "db" => 20.0 * re.hypot(im).log10(),
+                    "ph" => im.atan2(re).to_degrees(),
+                    "re" => re,
+                    _ => im,
+                }))
+            }
+            // Complex probes read as their magnitude whatever their phase,
+            // real ones as their signed value; `re()` and `im()` give the
+            // signed parts of a complex probe.
+            _ if is_probe(name) => Some(self.probe(name, args).map(|(re, im)| {
+                if self.is_complex(name, args) {
+                    re.hypot(im)
+                } else {
+                    re
+                }
+            })),
+            _ => None,
+        }
+    }
+}
+
+impl PointScope<'_> {
+    /// `V(a)`, `V(a,b)`, `I(R1)`, `Ic(Q1)` and so on at the current point,
+    /// as real and imaginary parts.
+    fn probe(&self, name: &str, args: &[Expr]) -> Result<(f64, f64), String> {
+        let Some(index) = self.index else {
+            return Err(format!(
+                "{}(...) can only be used at a point of a run",
+                name
+            ));
+        };
+        let dataset = self.context.dataset;
+        let read = |node: &Expr| -> Result<(f64, f64), String> {
+            let Expr::Variable(node) = node else {
+                return Err(format!("Invalid argument of {}()", name));
+            };
+            let vector = format!("{}({})", name, node);
+            let Some(real) = dataset.vector(&vector).and_then(|v| v.get(index)) else {
+                return Err(format!("No vector {}", vector));
+            };
+            let imaginary = dataset
+                .imaginary(&vector)
+                .and_then(|v| v.get(index))
+                .copied()
+                .unwrap_or(0.0);
+            Ok((*real, imaginary))
+        };
+        match args {
+            [node] => read(node),
+            [a, b] if name.eq_ignore_ascii_case("V") => {
+                let ((ar, ai), (br, bi)) = (read(a)?, read(b)?);
+                Ok((ar - br, ai - bi))
+            }
+            _ => Err(format!("Wrong number of arguments for {}()", name)),
+        }
+    }
+
+    /// Whether the vectors a probe reads are complex, as from `.ac`.
+    fn is_complex(&self, name: &str, args: &[Expr]) -> bool {
+        args.iter().any(|arg| match arg {
+            Expr::Variable(node) => self
+                .context
+                .dataset
+                .imaginary(&format!("{}({})", name, node))
+                .is_some(),
+            _ => false,
+        })
+    }
+}
+
+impl Context<'_> {
+    fn scope(&self, index: Option<usize>) -> PointScope<'_> {
+        PointScope {
+            context: self,
+            index,
+        }
+    }
+
+    fn axis(&self) -> &[f64] {
+        self.dataset.axis_values()
+    }
+
+    fn points(&self) -> usize {
+        self.axis().len().max(1)
+    }
+
+    /// The value of `expr` at every point of the run.
+    fn series(&self, expr: &Expr) -> Result<Vec<f64>, String> {
+        (0..self.points())
+            .map(|i| expr.eval(&self.scope(Some(i))))
+            .collect()
+    }
+
+    fn constant(&self, expr: &Expr) -> Result<f64, String> {
+        expr.eval(&self.scope(None))
+    }
+
+    fn evaluate(&self, kind: &MeasureKind) -> Result<f64, String> {
+        match kind {
+            MeasureKind::Find { expr, at: None } => {
+                if self.axis().len() > 1 {
+                    return Err("FIND needs AT or WHEN".to_string());
+                }
+                expr.eval(&self.scope(Some(0)))
+            }
+            MeasureKind::Find { expr, at: Some(at) } => {
+                let x = self.locate(at)?;
+                interpolate(self.axis(), &self.series(expr)?, x)
+            }
+            MeasureKind::Deriv { expr, at } => {
+                let x = self.locate(at)?;
+                slope(self.axis(), &self.series(expr)?, x)
+            }
+            MeasureKind::When(condition) => self.crossing(condition),
+            MeasureKind::TrigTarg { trig, targ } => Ok(self.locate(targ)? - self.locate(trig)?),
+            MeasureKind::Statistic {
+                function,
+                expr,
+                from,
+                to,
+            } => {
+                let axis = self.axis();
+                let (Some(first), Some(last)) = (axis.first(), axis.last()) else {
+                    return Err("Statistics need a swept analysis".to_string());
+                };
+                let from = from.as_ref().map_or(Ok(*first), |e| self.constant(e))?;
+                let to = to.as_ref().map_or(Ok(*last), |e| self.constant(e))?;
+                statistic(*function, axis, &self.series(expr)?, from, to)
+            }
+            MeasureKind::Param(expr) => self.constant(expr),
+        }
+    }
+
+    fn locate(&self, point: &Point) -> Result<f64, String> {
+        match point {
+            Point::At(expr) => self.constant(expr),
+            Point::When(condition) => self.crossing(condition),
+        }
+    }
+
+    /// The axis value where `lhs - rhs` changes sign on the requested edge.
+    fn crossing(&self, condition: &Condition) -> Result<f64, String> {
+        let lhs = self.series(&condition.lhs)?;
+        let rhs = self.series(&condition.rhs)?;
+        let delay = match &condition.delay {
+            Some(delay) => self.constant(delay)?,
+            None => f64::NEG_INFINITY,
+        };
+        let axis = self.axis();
+        let mut crossings = (1..axis.len()).filter_map(|i| {
+            let a = lhs[i - 1] - rhs[i - 1];
+            let b = lhs[i] - rhs[i];
+            let rising = a < 0.0 && b >= 0.0;
+            let falling = a > 0.0 && b <= 0.0;
+            let wanted = match condition.edge {
+                Edge::Rise => rising,
+                Edge::Fall => falling,
+                Edge::Cross => rising || falling,
+            };
+            let x = axis[i - 1] + (axis[i] - axis[i - 1]) * a / (a - b);
+            (wanted && x >= delay).then_some(x)
+        });
+        let found = match condition.count {
+            Count::Nth(n) => crossings.nth(n - 1),
+            Count::Last => crossings.next_back(),
+        };
+        found.ok_or_else(|| "The condition is never met".to_string())
+    }
+}
+
+/// Index of the segment `[axis[i], axis[i + 1]]` holding `x`.
+fn segment(axis: &[f64], x: f64) -> Result<usize, String> {
+    match (axis.first(), axis.last()) {
+        (Some(first), Some(last)) if x >= *first && x <= *last => {
+            let i = axis.partition_point(|a| *a <= x);
+            Ok(i.saturating_sub(1).min(axis.len().saturating_sub(2)))
+        }
+        _ => Err(format!("{} is outside the simulated range", x)),
+    }
+}
+
+fn interpolate(axis: &[f64], y: &[f64], x: f64) -> Result<f64, String> {
+    let i = segment(axis, x)?;
+    if axis.len() == 1 || axis[i + 1] == axis[i] {
+        return Ok(y[i]);
+    }
+    let t = (x - axis[i]) / (axis[i + 1] - axis[i]);
+    Ok(y[i] + (y[i + 1] - y[i]) * t)
+}
+
+fn slope(axis: &[f64], y: &[f64], x: f64) -> Result<f64, String> {
+    if axis.len() < 2 {
+        return Err("DERIV needs at least two points".to_string());
+    }
+    let i = segment(axis, x)?;
+    Ok((y[i + 1] - y[i]) / (axis[i + 1] - axis[i]))
+}
+
+fn statistic(
+    function: Statistic,
+    axis: &[f64],
+    y: &[f64],
+    from: f64,
+    to: f64,
+) -> Result<f64, String> {
+    if to < from {
+        return Err(format!("FROM={} is after TO={}", from, to));
+    }
+    // The curve clipped to [from, to], with interpolated end points.
+    let mut points = vec![(from, interpolate(axis, y, from)?)];
+    points.extend(
+        axis.iter()
+            .zip(y)
+            .filter(|(x, _)| **x > from && **x < to)
+            .map(|(x, y)| (*x, *y)),
+    );
+    points.push((to, interpolate(axis, y, to)?));
+
+    let integral = |square: bool| {
+        points
+            .windows(2)
+            .map(|w| {
+                let ((x0, a), (x1, b)) = (w[0], w[1]);
+                if square {
+                    (a * a + a * b + b * b) / 3.0 * (x1 - x0)
+                } else {
+                    (a + b) / 2.0 * (x1 - x0)
+                }
+            })
+            .sum::<f64>()
+    };
+    let values = points.iter().map(|(_, y)| *y);
+    let width = to - from;
+    Ok(match function {
+        Statistic::Integ => integral(false),
+        Statistic::Avg if width == 0.0 => points[0].1,
+        Statistic::Avg => integral(false) / width,
+        Statistic::Rms if width == 0.0 => points[0].1.abs(),
+        Statistic::Rms => (integral(true) / width).sqrt(),
+        Statistic::Min => values.fold(f64::INFINITY, f64::min),
+        Statistic::Max => values.fold(f64::NEG_INFINITY, f64::max),
+        Statistic::Pp => {
+            let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+            max - min
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::step;
+    use std::f64::consts::PI;
+
+    /// A 1 kHz sine of amplitude `a` on V(out) and a 0 to `a` ramp on
+    /// V(ramp), over 2 ms.
+    fn transient(a: f64) -> Dataset {
+        let time: Vec<f64> = (0..=2000).map(|i| i as f64 * 1e-6).collect();
+        let mut dataset = Dataset::new("time");
+        dataset.insert(
+            "V(out)",
+            time.iter()
+                .map(|t| a * (2.0 * PI * 1e3 * t).sin())
+                .collect(),
+        );
+        dataset.insert("V(ramp)", time.iter().map(|t| a * t / 2e-3).collect());
+        dataset.insert("time", time);
+        dataset
+    }
+
+    fn run(directives: &[&str], dataset: &Dataset) -> Vec<Result<f64, String>> {
+        let mut netlist = Netlist::new();
+        for directive in directives {
+            netlist.add_directive(directive.to_string());
+        }
+        let measurements = measurements(&netlist).unwrap();
+        measure(
+            &measurements,
+            dataset,
+            &StepPoint::default(),
+            &parameters(&netlist),
+        )
+    }
+
+    fn close(value: &Result<f64, String>, expected: f64) -> bool {
+        (value.as_ref().unwrap() - expected).abs() < 1e-4 * expected.abs().max(1e-3)
+    }
+
+    #[test]
+    fn test_measure() {
+        let values = run(
+            &[
+                ".meas TRAN peak MAX V(out)",
+                ".meas TRAN trough MIN V(out) FROM=0.5m TO=1m",
+                ".meas TRAN swing PP V(out)",
+                ".meas TRAN vrms RMS V(out) FROM=0 TO=1m",
+                ".meas TRAN mean AVG V(ramp)",
+                ".meas TRAN area INTEG V(ramp)",
+                ".meas TRAN v1 FIND V(ramp) AT=0.5m",
+                ".meas TRAN slope DERIV V(ramp) AT=1m",
+                ".meas TRAN half WHEN V(ramp)=V(out) RISE=1",
+                ".meas TRAN fall FIND V(ramp) WHEN V(out)=0 FALL=1",
+                ".meas TRAN last WHEN V(out)=0.5*k CROSS=LAST",
+                ".meas TRAN period TRIG V(out) VAL=0 FALL=1 TARG V(out) VAL=0 FALL=1 TD=0.6m",
+                ".meas ratio PARAM swing / peak",
+                ".meas TRAN never WHEN V(ramp)=10",
+            ],
+            &transient(2.0),
+        );
+        assert!(close(&values[0], 2.0));
+        assert!(close(&values[1], -2.0));
+        assert!(close(&values[2], 4.0));
+        assert!(close(&values[3], 2.0 / 2f64.sqrt()));
+        assert!(close(&values[4], 1.0));
+        assert!(close(&values[5], 2e-3));
+        assert!(close(&values[6], 0.5));
+        assert!(close(&values[7], 1e3));
+        assert!(close(&values[9], 0.5));
+        assert!(close(&values[11], 1e-3));
+        assert!(close(&values[12], 2.0));
+        assert!(values[13].is_err());
+        // `k` is not defined, so the measurement fails rather than panics.
+        assert!(values[10].is_err());
+        // The ramp meets the sine on its way down, after the first peak.
+        let half = *values[8].as_ref().unwrap();
+        assert!(half > 0.25e-3 && half < 0.5e-3);
+    }
+
+    #[test]
+    fn test_ac() {
+        let mut dataset = Dataset::new("frequency");
+        dataset.insert("frequency", vec![1.0, 10.0, 100.0]);
+        dataset.insert_complex("V(out)", vec![-1.0, 0.0, 0.0], vec![0.0, -1.0, -0.1]);
+        let values = run(
+            &[
+                ".meas AC gain FIND db(V(out)) AT=100",
+                ".meas AC phase FIND ph(V(out)) AT=10",
+                ".meas AC mag FIND V(out) AT=10",
+                ".meas AC inverted FIND V(out) AT=1",
+                ".meas AC real FIND re(V(out)) AT=1",
+            ],
+            &dataset,
+        );
+        assert!(close(&values[0], -20.0));
+        assert!(close(&values[1], -90.0));
+        assert!(close(&values[2], 1.0));
+        // A complex probe is its magnitude even when it happens to be real.
+        assert!(close(&values[3], 1.0));
+        assert!(close(&values[4], -1.0));
+    }
+
+    #[test]
+    fn test_measure_all() {
+        let mut netlist = Netlist::new();
+        netlist.add_directive(".param k=3".to_string());
+        netlist.add_directive(".step param a list 1 2".to_string());
+        netlist.add_directive(".meas TRAN peak MAX V(out)".to_string());
+        netlist.add_directive(".meas AC gain FIND V(out) AT=1".to_string());
+        netlist.add_directive(".meas scaled PARAM peak * k * a".to_string());
+        let results = step::run(&netlist, |netlist| {
+            let a = netlist
+                .directives
+                .iter()
+                .find_map(|d| d.strip_prefix(".param a="))
+                .and_then(parse_number)
+                .unwrap();
+            Ok(transient(a))
+        })
+        .unwrap();
+        let table = measure_all(&netlist, &results).unwrap();
+        assert_eq!(table.names, ["peak", "scaled"]);
+        assert_eq!(table.rows.len(), 2);
+        assert!((table.value(1, "peak").unwrap() - 2.0).abs() < 1e-9);
+        assert!((table.value(1, "scaled").unwrap() - 12.0).abs() < 1e-9);
+        assert_eq!(table.to_string().lines().next(), Some("step\tpeak\tscaled"));
+        assert!(table
+            .to_string()
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with("a=2\t"));
+
+        netlist.add_directive(".meas TRAN peak MIN V(out)".to_string());
+        assert!(measure_all(&netlist, &results).is_err());
+    }
+}
@@ -1 +1,9 @@
-
+pub mod integration;
+pub mod meas;
+pub mod newton;
+pub mod op;
+pub mod options;
+pub mod results;
+pub mod step;
+pub mod timestep;
+pub mod tran;
@@ -0,0 +1,474 @@
+//! Newton-Raphson iteration on the MNA equations.
+//!
+//! A [`System`] stamps the linearisation of its devices around the current
+//! solution, in companion form, so each solve gives the next solution
+//! directly. Devices limit their own controlling voltages while loading
+//! (see `devices::limiting`) and say so, which keeps the iteration going;
+//! on top of that the whole step can be damped. The iteration has
+//! converged when no device limited, every unknown moved by less than its
+//! tolerance and every device agrees. A device that cannot be evaluated at
+//! all, such as an expression dividing by zero, ends the iteration.
+
+use crate::analysis::options::Options;
+use crate::maths::sparse::{Handle, Lu, Pattern, SparseMatrix};
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+
+/// Unknowns reported when the iteration fails, worst first.
+const REPORTED_UNKNOWNS: usize = 5;
+
+/// What an MNA unknown measures, which decides its absolute tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownKind {
+    Voltage,
+    Current,
+}
+
+/// A circuit's equations, as seen by the Newton iteration.
+pub trait System {
+    /// Number of unknowns.
+    fn size(&self) -> usize;
+
+    /// Name of unknown `index` for diagnostics, such as `V(out)` or
+    /// `I(V1)`.
+    fn name(&self, index: usize) -> String;
+
+    fn kind(&self, index: usize) -> UnknownKind;
+
+    /// Ask for every matrix entry that [`System::load`] will stamp. Called
+    /// once, before the first load.
+    fn setup(&mut self, pattern: &mut Pattern);
+
+    /// Stamp the Jacobian and right-hand side linearised around `solution`
+    /// into the cleared `matrix` and `rhs`, recording in `load` the devices
+    /// that had to limit.
+    fn load(
+        &mut self,
+        solution: &[f64],
+        matrix: &mut SparseMatrix,
+        rhs: &mut [f64],
+        load: &mut Load,
+    );
+
+    /// Devices whose own convergence test fails at `solution`, as SPICE's
+    /// device `convTest`. Most devices are judged by their node voltages
+    /// alone.
+    fn unconverged(&self, _solution: &[f64], _options: &Options) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Bookkeeping of one load.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Load {
+    source_factor: f64,
+    limited: Vec<String>,
+    failure: Option<(String, String)>,
+}
+
+impl Default for Load {
+    fn default() -> Self {
+        Load {
+            source_factor: 1.0,
+            limited: Vec::new(),
+            failure: None,
+        }
+    }
+}
+
+impl Load {
+    /// Fraction of their value that independent sources should stamp,
+    /// below one while sources are being ramped up.
+    pub fn source_factor(&self) -> f64 {
+        self.source_factor
+    }
+
+    /// Note that `device` limited a controlling voltage.
+    pub fn limit(&mut self, device: &str) {
+        if !self.limited.iter().any(|d| d == device) {
+            self.limited.push(device.to_string());
+        }
+    }
+
+    pub fn limited(&self) -> &[String] {
+        &self.limited
+    }
+
+    /// Note that `device` could not be evaluated, the first such device
+    /// being the one reported.
+    pub fn fail(&mut self, device: &str, message: String) {
+        if self.failure.is_none() {
+            self.failure = Some((device.to_string(), message));
+        }
+    }
+}
+
+/// An unknown that had not settled when the iteration gave up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsettled {
+    pub name: String,
+    pub value: f64,
+    /// Change in the last iteration.
+    pub change: f64,
+    pub tolerance: f64,
+}
+
+/// Why the iteration failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NewtonError {
+    /// The Jacobian could not be factorised; `unknown` names the pivot row
+    /// that broke down, usually a floating node or a loop of voltage
+    /// sources.
+    Singular { unknown: String },
+    /// No convergence within the iteration limit.
+    NoConvergence {
+        iterations: usize,
+        /// Unknowns that still moved by more than their tolerance, worst
+        /// first.
+        unknowns: Vec<Unsettled>,
+        /// Devices that limited or failed their own test in the last
+        /// iteration.
+        devices: Vec<String>,
+    },
+    /// A device could not be evaluated at an iterate.
+    Device { device: String, message: String },
+}
+
+impl Display for NewtonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NewtonError::Singular { unknown } => write!(f, "Singular matrix at {}", unknown),
+            NewtonError::NoConvergence {
+                iterations,
+                unknowns,
+                devices,
+            } => {
+                write!(f, "No convergence after {} iterations", iterations)?;
+                for (i, unknown) in unknowns.iter().enumerate() {
+                    write!(
+                        f,
+                        "{} {} = {:e} (changed by {:e}, tolerance {:e})",
+                        if i == 0 { ";" } else { "," },
+                        unknown.name,
+                        unknown.value,
+                        unknown.change,
+                        unknown.tolerance
+                    )?;
+                }
+                if !devices.is_empty() {
+                    write!(f, "; devices: {}", devices.join(", "))?;
+                }
+                Ok(())
+            }
+            NewtonError::Device { device, message } => write!(f, "{}: {}", device, message),
+        }
+    }
+}
+
+impl From<NewtonError> for Error {
+    fn from(error: NewtonError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// The Newton iteration for one system, keeping its matrix and pivot order
+/// between solves.
+#[derive(Debug, Clone)]
+pub struct Newton {
+    pub reltol: f64,
+    pub abstol: f64,
+    pub vntol: f64,
+    /// Largest change of any node voltage in one iteration. Larger steps
+    /// are scaled down as a whole, after the devices have limited.
+    pub damping: Option<f64>,
+    /// Scale of the independent sources, see [`Load::source_factor`].
+    pub source_factor: f64,
+    /// Conductance added from every node to ground, or to its voltage in
+    /// `anchor` when there is one. Gmin stepping and pseudo-transient
+    /// continuation both solve the circuit with this shunt and then take
+    /// it away gradually.
+    pub shunt: f64,
+    pub anchor: Option<Vec<f64>>,
+    matrix: SparseMatrix,
+    diagonal: Vec<(usize, Handle)>,
+    lu: Lu,
+    rhs: Vec<f64>,
+}
+
+impl Newton {
+    pub fn new(system: &mut dyn System, options: &Options) -> Self {
+        let mut pattern = Pattern::new(system.size());
+        system.setup(&mut pattern);
+        let diagonal = (0..system.size())
+            .filter(|i| system.kind(*i) == UnknownKind::Voltage)
+            .map(|i| (i, pattern.handle(i, i)))
+            .collect();
+        Newton {
+            reltol: options.reltol,
+            abstol: options.abstol,
+            vntol: options.vntol,
+            damping: None,
+            source_factor: 1.0,
+            shunt: 0.0,
+            anchor: None,
+            matrix: pattern.matrix(),
+            diagonal,
+            lu: Lu::new(options.lu_options()),
+            rhs: vec![0.0; system.size()],
+        }
+    }
+
+    /// Iterate from the guess in `solution` until convergence or `limit`
+    /// iterations, usually `itl1` or `itl4`. Returns the number of
+    /// iterations; `solution` holds the last iterate either way.
+    pub fn solve(
+        &mut self,
+        system: &mut dyn System,
+        solution: &mut [f64],
+        limit: usize,
+        options: &Options,
+    ) -> Result<usize, NewtonError> {
+        let mut unsettled = Vec::new();
+        let mut devices = Vec::new();
+        for iteration in 1..=limit {
+            self.matrix.clear();
+            self.rhs.fill(0.0);
+            let mut load = Load {
+                source_factor: self.source_factor,
+                ..Load::default()
+            };
+            system.load(solution, &mut self.matrix, &mut self.rhs, &mut load);
+            if let Some((device, message)) = load.failure.take() {
+                return Err(NewtonError::Device { device, message });
+            }
+            if self.shunt != 0.0 {
+                for &(i, handle) in &self.diagonal {
+                    self.matrix.add(handle, self.shunt);
+                    if let Some(anchor) = &self.anchor {
+                        self.rhs[i] += self.shunt * anchor[i];
+                    }
+                }
+            }
+            if let Err(singular) = self.lu.factor(&self.matrix) {
+                return Err(NewtonError::Singular {
+                    unknown: system.name(singular.row),
+                });
+            }
+            self.lu.solve(&mut self.rhs);
+            self.damp(system, solution);
+
+            unsettled.clear();
+            for (i, (&old, &new)) in solution.iter().zip(&self.rhs).enumerate() {
+                let tolerance = self.reltol * old.abs().max(new.abs())
+                    + match system.kind(i) {
+                        UnknownKind::Voltage => self.vntol,
+                        UnknownKind::Current => self.abstol,
+                    };
+                if !new.is_finite() || (new - old).abs() > tolerance {
+                    unsettled.push(Unsettled {
+                        name: system.name(i),
+                        value: new,
+                        change: new - old,
+                        tolerance,
+                    });
+                }
+            }
+            solution.copy_from_slice(&self.rhs);
+            devices = load.limited;
+            for device in system.unconverged(solution, options) {
+                if !devices.contains(&device) {
+                    devices.push(device);
+                }
+            }
+            // A first iteration only shows where the guess led, never that
+            // it was right.
+            if iteration > 1 && unsettled.is_empty() && devices.is_empty() {
+                return Ok(iteration);
+            }
+        }
+        unsettled.sort_by(|a, b| {
+            (b.change.abs() / b.tolerance).total_cmp(&(a.change.abs() / a.tolerance))
+        });
+        unsettled.truncate(REPORTED_UNKNOWNS);
+        Err(NewtonError::NoConvergence {
+            iterations: limit,
+            unknowns: unsettled,
+            devices,
+        })
+    }
+
+    /// Scale the step from `solution` to `self.rhs` so no node voltage
+    /// moves by more than the damping limit.
+    fn damp(&mut self, system: &dyn System, solution: &[f64]) {
+        let Some(limit) = self.damping else {
+            return;
+        };
+        let largest = (0..solution.len())
+            .filter(|i| system.kind(*i) == UnknownKind::Voltage)
+            .map(|i| (self.rhs[i] - solution[i]).abs())
+            .fold(0.0, f64::max);
+        if largest > limit {
+            let factor = limit / largest;
+            for (new, old) in self.rhs.iter_mut().zip(solution) {
+                *new = old + factor * (*new - old);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::devices::limiting::{critical_voltage, pnjlim, thermal_voltage};
+
+    /// A source `V1` on node `in`, a resistor to node `a` and a diode `D1`
+    /// from `a` to ground. Unknowns are V(in), V(a) and I(V1).
+    pub(crate) struct Rectifier {
+        source: f64,
+        resistance: f64,
+        saturation: f64,
+        limiting: bool,
+        vd: f64,
+        handles: Vec<Handle>,
+    }
+
+    impl Rectifier {
+        pub(crate) fn new(source: f64, limiting: bool) -> Self {
+            Rectifier {
+                source,
+                resistance: 1e3,
+                saturation: 1e-14,
+                limiting,
+                vd: 0.0,
+                handles: Vec::new(),
+            }
+        }
+    }
+
+    impl System for Rectifier {
+        fn size(&self) -> usize {
+            3
+        }
+
+        fn name(&self, index: usize) -> String {
+            ["V(in)", "V(a)", "I(V1)"][index].to_string()
+        }
+
+        fn kind(&self, index: usize) -> UnknownKind {
+            if index == 2 {
+                UnknownKind::Current
+            } else {
+                UnknownKind::Voltage
+            }
+        }
+
+        fn setup(&mut self, pattern: &mut Pattern) {
+            self.handles = [(0, 0), (0, 1), (1, 0), (1, 1), (0, 2), (2, 0)]
+                .iter()
+                .map(|(r, c)| pattern.handle(*r, *c))
+                .collect();
+        }
+
+        fn load(
+            &mut self,
+            solution: &[f64],
+            matrix: &mut SparseMatrix,
+            rhs: &mut [f64],
+            load: &mut Load,
+        ) {
+            let g = 1.0 / self.resistance;
+            let h = &self.handles;
+            matrix.add(h[0], g);
+            matrix.add(h[1], -g);
+            matrix.add(h[2], -g);
+            matrix.add(h[3], g);
+            matrix.add(h[4], 1.0);
+            matrix.add(h[5], 1.0);
+            rhs[2] = self.source * load.source_factor();
+
+            let vt = thermal_voltage(27.0);
+            let mut vd = solution[1];
+            if self.limiting {
+                let vcrit = critical_voltage(vt, self.saturation);
+                let (limited, changed) = pnjlim(vd, self.vd, vt, vcrit);
+                if changed {
+                    load.limit("D1");
+                }
+                vd = limited;
+            }
+            self.vd = vd;
+            let id = self.saturation * ((vd / vt).exp() - 1.0);
+            let gd = self.saturation / vt * (vd / vt).exp() + 1e-12;
+            matrix.add(h[3], gd);
+            rhs[1] -= id - gd * vd;
+        }
+    }
+
+    #[test]
+    fn test_diode() {
+        let options = Options::default();
+        let mut system = Rectifier::new(5.0, true);
+        let mut newton = Newton::new(&mut system, &options);
+        let mut solution = vec![0.0; 3];
+        let iterations = newton
+            .solve(&mut system, &mut solution, options.itl1, &options)
+            .unwrap();
+        assert!(iterations > 2 && iterations < 100);
+        let vt = thermal_voltage(27.0);
+        let id = 1e-14 * ((solution[1] / vt).exp() - 1.0);
+        assert!((solution[0] - 5.0).abs() < 1e-9);
+        assert!((id - (5.0 - solution[1]) / 1e3).abs() < 1e-8);
+        assert!((solution[2] + id).abs() < 1e-8);
+
+        // Without limiting the first step overflows the exponential.
+        let mut system = Rectifier::new(5.0, false);
+        let mut newton = Newton::new(&mut system, &options);
+        let mut solution = vec![0.0; 3];
+        assert!(newton
+            .solve(&mut system, &mut solution, options.itl1, &options)
+            .is_err());
+    }
+
+    #[test]
+    fn test_damping() {
+        let options = Options::default();
+        let mut system = Rectifier::new(5.0, true);
+        let mut newton = Newton::new(&mut system, &options);
+        newton.damping = Some(0.5);
+        let mut solution = vec![0.0; 3];
+        let iterations = newton
+            .solve(&mut system, &mut solution, options.itl1, &options)
+            .unwrap();
+        // Ten half-volt steps at least to bring V(in) up to 5 V.
+        assert!(iterations >= 10);
+        assert!((solution[0] - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_no_convergence() {
+        let options = Options::default();
+        let mut system = Rectifier::new(5.0, true);
+        let mut newton = Newton::new(&mut system, &options);
+        let mut solution = vec![0.0; 3];
+        let error = newton
+            .solve(&mut system, &mut solution, 3, &options)
+            .unwrap_err();
+        let NewtonError::NoConvergence {
+            iterations,
+            unknowns,
+            devices,
+        } = &error
+        else {
+            panic!("{}", error);
+        };
+        assert_eq!(*iterations, 3);
+        // V(a) stays near the source while the junction voltage climbs in
+        // limited steps, so the current is what has not settled.
+        assert_eq!(unknowns[0].name, "I(V1)");
+        assert_eq!(devices, &["D1"]);
+        assert!(error
+            .to_string()
+            .starts_with("No convergence after 3 iterations; "));
+        assert!(error.to_string().ends_with("; devices: D1"));
+    }
+}
@@ -0,0 +1,314 @@
+//! The DC operating point, with SPICE's fallbacks for circuits on which
+//! plain Newton iteration fails.
+//!
+//! Strategies are tried in order: plain Newton, diagonal gmin stepping,
+//! source stepping and pseudo-transient continuation, each unless turned
+//! off in `.options`. Every attempt is kept, in `OperatingPoint::attempts`
+//! or `OpError::attempts`, so the caller can report which one found the
+//! solution and why the others did not.
+
+use crate::analysis::newton::{Newton, NewtonError, System};
+use crate::analysis::options::Options;
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+
+/// Shunt conductance at the first step of pseudo-transient continuation,
+/// standing for the capacitance over the first pseudo time step.
+const PSEUDO_TRANSIENT_START: f64 = 1.0;
+
+/// A pseudo time step that has to be cut back below this shunt is not
+/// getting anywhere.
+const PSEUDO_TRANSIENT_LIMIT: f64 = 1e6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Newton,
+    GminStepping,
+    SourceStepping,
+    PseudoTransient,
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Strategy::Newton => "Newton iteration",
+            Strategy::GminStepping => "gmin stepping",
+            Strategy::SourceStepping => "source stepping",
+            Strategy::PseudoTransient => "pseudo-transient continuation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One strategy as it was tried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    pub strategy: Strategy,
+    /// Newton solves made, one for plain Newton iteration.
+    pub steps: usize,
+    /// Newton iterations over all of them.
+    pub iterations: usize,
+    /// Why it failed, `None` if it found the operating point.
+    pub error: Option<NewtonError>,
+}
+
+impl Display for Attempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            None => write!(
+                f,
+                "{}: converged in {} iterations over {} steps",
+                self.strategy, self.iterations, self.steps
+            ),
+            Some(error) => write!(
+                f,
+                "{}: failed after {} iterations over {} steps: {}",
+                self.strategy, self.iterations, self.steps, error
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatingPoint {
+    pub solution: Vec<f64>,
+    /// Every strategy tried, the last one successful.
+    pub attempts: Vec<Attempt>,
+}
+
+impl OperatingPoint {
+    /// The strategy that found the solution.
+    pub fn strategy(&self) -> Strategy {
+        self.attempts.last().unwrap().strategy
+    }
+}
+
+/// Every enabled strategy failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpError {
+    pub attempts: Vec<Attempt>,
+}
+
+impl Display for OpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No DC operating point found")?;
+        if self.attempts.is_empty() {
+            write!(f, ": every strategy is turned off")?;
+        }
+        for attempt in &self.attempts {
+            write!(f, "\n  {}", attempt)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<OpError> for Error {
+    fn from(error: OpError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// Solve for the operating point from an all-zero guess, with the
+/// independent sources scaled by `newton.source_factor`.
+pub fn operating_point(
+    newton: &mut Newton,
+    system: &mut dyn System,
+    options: &Options,
+) -> Result<OperatingPoint, OpError> {
+    let strategies = [
+        (Strategy::Newton, !options.noopiter),
+        (Strategy::GminStepping, options.gminsteps > 0),
+        (Strategy::SourceStepping, options.srcsteps > 0),
+        (Strategy::PseudoTransient, options.ptransteps > 0),
+    ];
+    let source_factor = newton.source_factor;
+    let mut attempts = Vec::new();
+    for (strategy, enabled) in strategies {
+        if !enabled {
+            continue;
+        }
+        let mut solution = vec![0.0; system.size()];
+        let mut attempt = Attempt {
+            strategy,
+            steps: 0,
+            iterations: 0,
+            error: None,
+        };
+        let mut solver = Solver {
+            newton: &mut *newton,
+            system: &mut *system,
+            options,
+            attempt: &mut attempt,
+        };
+        let result = match strategy {
+            Strategy::Newton => solver.step(&mut solution),
+            Strategy::GminStepping => solver.gmin_stepping(&mut solution),
+            Strategy::SourceStepping => solver.source_stepping(&mut solution),
+            Strategy::PseudoTransient => solver.pseudo_transient(&mut solution),
+        };
+        newton.source_factor = source_factor;
+        newton.shunt = 0.0;
+        newton.anchor = None;
+        attempt.error = result.err();
+        let converged = attempt.error.is_none();
+        attempts.push(attempt);
+        if converged {
+            return Ok(OperatingPoint { solution, attempts });
+        }
+    }
+    Err(OpError { attempts })
+}
+
+/// The state a strategy works with.
+struct Solver<'a> {
+    newton: &'a mut Newton,
+    system: &'a mut dyn System,
+    options: &'a Options,
+    attempt: &'a mut Attempt,
+}
+
+impl Solver<'_> {
+    /// One Newton solve from `solution`, counted in the attempt.
+    fn step(&mut self, solution: &mut [f64]) -> Result<(), NewtonError> {
+        self.attempt.steps += 1;
+        let result = self
+            .newton
+            .solve(self.system, solution, self.options.itl1, self.options);
+        self.attempt.iterations += match &result {
+            Ok(iterations) => *iterations,
+            Err(NewtonError::NoConvergence { iterations, .. }) => *iterations,
+            Err(NewtonError::Singular { .. } | NewtonError::Device { .. }) => 0,
+        };
+        result.map(|_| ())
+    }
+
+    /// Start with a large conductance from every node to ground and take
+    /// it down a decade per step to `gmin`, then away.
+    fn gmin_stepping(&mut self, solution: &mut [f64]) -> Result<(), NewtonError> {
+        let mut shunt = self.options.gmin * 10f64.powi(self.options.gminsteps as i32);
+        for _ in 0..self.options.gminsteps {
+            self.newton.shunt = shunt;
+            self.step(solution)?;
+            shunt /= 10.0;
+        }
+        self.newton.shunt = 0.0;
+        self.step(solution)
+    }
+
+    /// Ramp every independent source up from zero in equal steps.
+    fn source_stepping(&mut self, solution: &mut [f64]) -> Result<(), NewtonError> {
+        let steps = self.options.srcsteps;
+        let full = self.newton.source_factor;
+        for k in 1..=steps {
+            self.newton.source_factor = full * k as f64 / steps as f64;
+            self.step(solution)?;
+        }
+        Ok(())
+    }
+
+    /// Backward Euler on the circuit with a capacitor from every node to
+    /// ground, so the solution settles in pseudo time. The shunt is the
+    /// capacitance over the time step: it shrinks a decade after each
+    /// converged step and grows two after a failed one, until it is below
+    /// `gmin` and a final solve without it finishes the job.
+    fn pseudo_transient(&mut self, solution: &mut [f64]) -> Result<(), NewtonError> {
+        let mut shunt = PSEUDO_TRANSIENT_START;
+        while shunt > self.options.gmin && self.attempt.steps < self.options.ptransteps {
+            let previous = solution.to_vec();
+            self.newton.shunt = shunt;
+            self.newton.anchor = Some(previous.clone());
+            match self.step(solution) {
+                Ok(()) => shunt /= 10.0,
+                Err(error) => {
+                    solution.copy_from_slice(&previous);
+                    shunt *= 100.0;
+                    if shunt > PSEUDO_TRANSIENT_LIMIT {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        self.newton.shunt = 0.0;
+        self.newton.anchor = None;
+        self.step(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::newton::tests::Rectifier;
+
+    fn solve(limiting: bool, options: &Options) -> Result<OperatingPoint, OpError> {
+        let mut system = Rectifier::new(5.0, limiting);
+        let mut newton = Newton::new(&mut system, options);
+        operating_point(&mut newton, &mut system, options)
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let options = Options::default();
+        let op = solve(true, &options).unwrap();
+        assert_eq!(op.strategy(), Strategy::Newton);
+        assert_eq!(op.attempts.len(), 1);
+
+        // Without junction limiting plain Newton iteration overflows, and
+        // gmin stepping keeps the first steps small enough.
+        let op = solve(false, &options).unwrap();
+        assert_eq!(op.attempts[0].strategy, Strategy::Newton);
+        assert!(op.attempts[0].error.is_some());
+        assert_eq!(op.strategy(), Strategy::GminStepping);
+        assert_eq!(op.attempts[1].steps, 11);
+        let reference = solve(true, &options).unwrap().solution;
+        assert!((op.solution[1] - reference[1]).abs() < 1e-5);
+
+        let options = Options {
+            gminsteps: 0,
+            ..Options::default()
+        };
+        let op = solve(false, &options).unwrap();
+        assert_eq!(op.strategy(), Strategy::SourceStepping);
+        assert!((op.solution[1] - reference[1]).abs() < 1e-5);
+
+        let options = Options {
+            gminsteps: 0,
+            srcsteps: 0,
+            ..Options::default()
+        };
+        let op = solve(false, &options).unwrap();
+        assert_eq!(op.strategy(), Strategy::PseudoTransient);
+        assert!((op.solution[1] - reference[1]).abs() < 1e-5);
+
+        let options = Options {
+            noopiter: true,
+            ..Options::default()
+        };
+        let op = solve(true, &options).unwrap();
+        assert_eq!(op.attempts.len(), 1);
+        assert_eq!(op.strategy(), Strategy::GminStepping);
+    }
+
+    #[test]
+    fn test_all_fail() {
+        let options = Options {
+            gminsteps: 0,
+            srcsteps: 0,
+            ptransteps: 0,
+            ..Options::default()
+        };
+        let error = solve(false, &options).unwrap_err();
+        assert_eq!(error.attempts.len(), 1);
+        let message = error.to_string();
+        assert!(message.starts_with("No DC operating point found\n  Newton iteration: failed after 100 iterations over 1 steps"));
+
+        let options = Options {
+            noopiter: true,
+            ..options
+        };
+        let error = solve(false, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No DC operating point found: every strategy is turned off"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Simulator settings from `.options` lines.
+//!
+//! Names and defaults follow SPICE, apart from `ptransteps`, which is
+//! particular to nim_spice. Options this simulator does not use are
+//! accepted and ignored, since netlists written for other simulators are
+//! full of them.
+
+use crate::analysis::integration::{Method, MAX_ORDER};
+use crate::frontend::parser::netlist::Netlist;
+use crate::maths::sparse::LuOptions;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Relative tolerance on every unknown.
+    pub reltol: f64,
+    /// Absolute tolerance on branch currents.
+    pub abstol: f64,
+    /// Absolute tolerance on node voltages.
+    pub vntol: f64,
+    /// Absolute tolerance on charges.
+    pub chgtol: f64,
+    /// Conductance put across every junction so it never floats.
+    pub gmin: f64,
+    /// Newton iteration limit for the DC operating point and DC sweeps.
+    pub itl1: usize,
+    /// Newton iteration limit for one transient time point.
+    pub itl4: usize,
+    /// Decades of diagonal gmin stepping for the operating point; zero
+    /// turns it off.
+    pub gminsteps: usize,
+    /// Steps of source ramping for the operating point; zero turns it off.
+    pub srcsteps: usize,
+    /// Steps of pseudo-transient continuation for the operating point;
+    /// zero turns it off. A nim_spice extension with no counterpart in
+    /// ngspice or LTspice, so other simulators ignore it.
+    pub ptransteps: usize,
+    /// Skip the plain Newton iteration and go straight to the convergence
+    /// aids.
+    pub noopiter: bool,
+    /// Integration method of the transient analysis.
+    pub method: Method,
+    /// Highest order of the Gear method.
+    pub maxord: usize,
+    /// Factor by which the local truncation error of a time step may
+    /// exceed the tolerances; larger is faster and less accurate.
+    pub trtol: f64,
+    pub pivrel: f64,
+    pub pivtol: f64,
+    /// Temperature at which model parameters are given, in °C.
+    pub tnom: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            reltol: 1e-3,
+            abstol: 1e-12,
+            vntol: 1e-6,
+            chgtol: 1e-14,
+            gmin: 1e-12,
+            itl1: 100,
+            itl4: 10,
+            gminsteps: 10,
+            srcsteps: 10,
+            ptransteps: 50,
+            noopiter: false,
+            method: Method::default(),
+            maxord: 2,
+            trtol: 7.0,
+            pivrel: 1e-3,
+            pivtol: 1e-13,
+            tnom: 27.0,
+        }
+    }
+}
+
+impl Options {
+    /// Defaults overridden by every `.options` line of the netlist, later
+    /// lines winning.
+    pub fn from_netlist(netlist: &Netlist) -> Result<Self, ErrorHandler> {
+        let mut options = Options::default();
+        let mut error_handler = ErrorHandler::new();
+        for directive in &netlist.directives {
+            let Some(rest) = strip_keyword(directive) else {
+                continue;
+            };
+            for (key, value) in pairs(rest) {
+                if let Err(message) = options.set(&key, value.as_deref()) {
+                    error_handler.add_error(Error::new(ErrorType::Semantic, message, 0, 0));
+                }
+            }
+        }
+        if error_handler.has_errors() {
+            Err(error_handler)
+        } else {
+            Ok(options)
+        }
+    }
+
+    /// Set one option by name. `value` is `None` for a bare flag.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<(), String> {
+        let key = key.to_ascii_lowercase();
+        let number = || {
+            let value = value.ok_or_else(|| format!("Option {} needs a value", key))?;
+            parse_number(value)
+                .ok_or_else(|| format!("Invalid value for option {}: {}", key, value))
+        };
+        let count = |least: f64| {
+            let value = number()?;
+            if value < least || value.fract() != 0.0 {
+                return Err(format!(
+                    "Option {} must be an integer of at least {}",
+                    key, least
+                ));
+            }
+            Ok(value as usize)
+        };
+        let flag = || match value {
+            None => Ok::<bool, String>(true),
+            Some(_) => Ok(number()? != 0.0),
+        };
+        match key.as_str() {
+            "reltol" => self.reltol = number()?,
+            "abstol" => self.abstol = number()?,
+            "vntol" => self.vntol = number()?,
+            "chgtol" => self.chgtol = number()?,
+            "gmin" => self.gmin = number()?,
+            "itl1" => self.itl1 = count(1.0)?,
+            "itl4" => self.itl4 = count(1.0)?,
+            "gminsteps" => self.gminsteps = count(0.0)?,
+            "srcsteps" => self.srcsteps = count(0.0)?,
+            "ptransteps" => self.ptransteps = count(0.0)?,
+            "noopiter" => self.noopiter = flag()?,
+            "method" => {
+                let name = value.ok_or_else(|| format!("Option {} needs a value", key))?;
+                self.method = Method::parse(name)
+                    .ok_or_else(|| format!("Unknown integration method: {}", name))?;
+            }
+            "maxord" => {
+                self.maxord = count(1.0)?;
+                if self.maxord > MAX_ORDER {
+                    return Err(format!("Option maxord must be at most {}", MAX_ORDER));
+                }
+            }
+            "trtol" => {
+                self.trtol = number()?;
+                if self.trtol <= 0.0 {
+                    return Err(format!("Option {} must be positive", key));
+                }
+            }
+            "pivrel" => self.pivrel = number()?,
+            "pivtol" => self.pivtol = number()?,
+            "tnom" => self.tnom = number()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn lu_options(&self) -> LuOptions {
+        LuOptions {
+            relative_threshold: self.pivrel,
+            absolute_threshold: self.pivtol,
+            ..LuOptions::default()
+        }
+    }
+}
+
+/// The text after `.options`, `.option` or `.opt`.
+fn strip_keyword(directive: &str) -> Option<&str> {
+    let directive = directive.trim_start();
+    let end = directive
+        .find(|c: char| c.is_whitespace())
+        .unwrap_or(directive.len());
+    let keyword = directive[..end].to_ascii_lowercase();
+    matches!(keyword.as_str(), ".options" | ".option" | ".opt").then(|| &directive[end..])
+}
+
+/// `key=value` pairs and bare flags, allowing spaces around `=`.
+fn pairs(text: &str) -> Vec<(String, Option<String>)> {
+    let spaced = text.replace('=', " = ");
+    let words: Vec<&str> = spaced.split_whitespace().collect();
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        if words.get(i + 1) == Some(&"=") {
+            pairs.push((
+                words[i].to_string(),
+                words.get(i + 2).map(|v| v.to_string()),
+            ));
+            i += 3;
+        } else {
+            pairs.push((words[i].to_string(), None));
+            i += 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_netlist() {
+        let mut netlist = Netlist::new();
+        netlist.add_directive(".options reltol=1e-4 ABSTOL = 1p".to_string());
+        netlist.add_directive(".opt itl1=500 itl4=40 plotwinsize=0 numdgt=7".to_string());
+        netlist.add_directive(".param reltol=1".to_string());
+        netlist.add_directive(
+            ".options gminsteps=0 noopiter method=gear maxord=4 trtol=1".to_string(),
+        );
+        let options = Options::from_netlist(&netlist).unwrap();
+        assert_eq!(options.reltol, 1e-4);
+        assert_eq!(options.abstol, 1e-12);
+        assert_eq!(options.itl1, 500);
+        assert_eq!(options.itl4, 40);
+        assert_eq!(options.vntol, 1e-6);
+        assert_eq!(options.gminsteps, 0);
+        assert_eq!(options.srcsteps, 10);
+        assert!(options.noopiter);
+        assert_eq!(options.method, Method::Gear);
+        assert_eq!(options.maxord, 4);
+        assert_eq!(options.trtol, 1.0);
+
+        let mut netlist = Netlist::new();
+        netlist.add_directive(".option itl1=2.5 gmin=abc method=rk4 maxord=7".to_string());
+        let errors = Options::from_netlist(&netlist).unwrap_err();
+        assert_eq!(errors.errors.len(), 4);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// The vectors written by one analysis run. Every vector has one entry per
+/// point of the sweep axis: time for `.tran`, frequency for `.ac`, the
+/// swept source for `.dc`, and a single point for `.op`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dataset {
+    /// Name of the vector holding the sweep axis, e.g. `time`.
+    pub axis: String,
+    pub vectors: BTreeMap<String, Vec<f64>>,
+    /// Imaginary parts of complex vectors, as written by `.ac`. The real
+    /// parts are in `vectors`.
+    pub imaginary: BTreeMap<String, Vec<f64>>,
+}
+
+impl Dataset {
+    pub fn new(axis: impl Into<String>) -> Self {
+        Dataset {
+            axis: axis.into(),
+            vectors: BTreeMap::new(),
+            imaginary: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, values: Vec<f64>) {
+        self.vectors.insert(name.into(), values);
+    }
+
+    pub fn insert_complex(&mut self, name: impl Into<String>, real: Vec<f64>, imaginary: Vec<f64>) {
+        let name = name.into();
+        self.imaginary.insert(name.clone(), imaginary);
+        self.vectors.insert(name, real);
+    }
+
+    /// A vector by name, ignoring case as SPICE does, so `v(out)` finds
+    /// `V(out)`. For complex vectors this is the real part.
+    pub fn vector(&self, name: &str) -> Option<&[f64]> {
+        lookup(&self.vectors, name)
+    }
+
+    /// The imaginary part of a complex vector.
+    pub fn imaginary(&self, name: &str) -> Option<&[f64]> {
+        lookup(&self.imaginary, name)
+    }
+
+    /// The sweep axis, empty for an operating point.
+    pub fn axis_values(&self) -> &[f64] {
+        self.vector(&self.axis).unwrap_or(&[])
+    }
+}
+
+fn lookup<'a>(vectors: &'a BTreeMap<String, Vec<f64>>, name: &str) -> Option<&'a [f64]> {
+    vectors
+        .get(name)
+        .or_else(|| {
+            vectors
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, values)| values)
+        })
+        .map(Vec::as_slice)
+}
+
+/// The values of the stepped quantities for one run, in `.step` order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepPoint {
+    pub values: Vec<(String, f64)>,
+}
+
+impl StepPoint {
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+impl Display for StepPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, value)) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Results of a simulation with one run per `.step` point. Without
+/// `.step` there is a single run with an empty step point.
+///
+/// Runs are stored with the first `.step` varying fastest, so with
+/// `shape == [3, 2]` the run for `(i, j)` is at `i + 3 * j`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    /// Number of values of each `.step`, in directive order.
+    pub shape: Vec<usize>,
+    pub steps: Vec<StepPoint>,
+    pub runs: Vec<Dataset>,
+}
+
+impl ResultSet {
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Index of the run at the given position of each nested `.step`.
+    pub fn index(&self, position: &[usize]) -> Option<usize> {
+        if position.len() != self.shape.len() {
+            return None;
+        }
+        let mut index = 0;
+        let mut stride = 1;
+        for (p, n) in position.iter().zip(&self.shape) {
+            if p >= n {
+                return None;
+            }
+            index += p * stride;
+            stride *= n;
+        }
+        Some(index)
+    }
+
+    pub fn run(&self, index: usize) -> Option<(&StepPoint, &Dataset)> {
+        Some((self.steps.get(index)?, self.runs.get(index)?))
+    }
+
+    /// One curve per run for a vector, as step point, axis and values,
+    /// for overlaying a family of curves.
+    pub fn family(&self, vector: &str) -> Vec<(&StepPoint, &[f64], &[f64])> {
+        self.steps
+            .iter()
+            .zip(&self.runs)
+            .filter_map(|(step, run)| Some((step, run.axis_values(), run.vector(vector)?)))
+            .collect()
+    }
+}
@@ -0,0 +1,461 @@
+//! Running a netlist once per `.step` point.
+//!
+//! Each point produces a copy of the netlist with the stepped quantities
+//! written back into it, as `.param` and `.temp` lines, element values or
+//! `.model` parameters, and without the `.step` lines themselves. The
+//! simulator then runs that copy like any other netlist.
+
+use crate::analysis::results::{Dataset, ResultSet, StepPoint};
+use crate::frontend::parser::netlist::Netlist;
+use crate::frontend::parser::step::{parse_step, Step, StepTarget, MAX_POINTS};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+
+/// LTspice allows `.step` to be nested three deep.
+pub const MAX_NESTING: usize = 3;
+
+/// The `.step` directives of a netlist, in order. Nested steps together
+/// may not run more than [`MAX_POINTS`] combinations.
+pub fn steps(netlist: &Netlist) -> Result<Vec<Step>, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut steps = Vec::new();
+    for directive in netlist.directives.iter().filter(|d| is_step(d)) {
+        match parse_step(directive) {
+            Ok(step) => steps.push(step),
+            Err(errors) => error_handler.errors.extend(errors.errors),
+        }
+    }
+    if steps.len() > MAX_NESTING {
+        error_handler.add_error(Error::new(
+            ErrorType::Semantic,
+            format!(
+                "At most {} .step directives can be nested, found {}",
+                MAX_NESTING,
+                steps.len()
+            ),
+            0,
+            0,
+        ));
+    }
+    let combinations: f64 = steps
+        .iter()
+        .map(|step| step.sweep.values().len() as f64)
+        .product();
+    if combinations > MAX_POINTS as f64 {
+        error_handler.add_error(Error::new(
+            ErrorType::Semantic,
+            format!(
+                "The nested .step directives have {} points, more than {}",
+                combinations, MAX_POINTS
+            ),
+            0,
+            0,
+        ));
+    }
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(steps)
+    }
+}
+
+/// Every combination of step values, with the first `.step` varying
+/// fastest.
+pub fn step_points(steps: &[Step]) -> Vec<StepPoint> {
+    let mut points = vec![StepPoint::default()];
+    for step in steps {
+        let values = step.sweep.values();
+        points = values
+            .iter()
+            .flat_map(|value| {
+                points.iter().map(move |point| {
+                    let mut point = point.clone();
+                    point.values.push((step.target.to_string(), *value));
+                    point
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+/// The netlist to simulate at one step point.
+pub fn apply(
+    netlist: &Netlist,
+    steps: &[Step],
+    point: &StepPoint,
+) -> Result<Netlist, ErrorHandler> {
+    let mut out = netlist.clone();
+    out.directives.retain(|d| !is_step(d));
+    for (step, (_, value)) in steps.iter().zip(&point.values) {
+        let result = match &step.target {
+            StepTarget::Param(name) => {
+                set_param(&mut out, name, *value);
+                Ok(())
+            }
+            StepTarget::Temp => {
+                out.directives.retain(|d| !directive_is(d, ".temp"));
+                out.add_directive(format!(".temp {}", value));
+                Ok(())
+            }
+            StepTarget::Component(name) => set_component(&mut out, name, *value),
+            StepTarget::Model {
+                kind,
+                model,
+                parameter,
+            } => set_model_parameter(&mut out, kind, model, parameter, *value),
+        };
+        if let Err(message) = result {
+            let mut error_handler = ErrorHandler::new();
+            error_handler.add_error(Error::new(ErrorType::Semantic, message, 0, 0));
+            return Err(error_handler);
+        }
+    }
+    Ok(out)
+}
+
+/// Run `simulate` at every step point of the netlist. Errors from a run
+/// are reported with the step point they happened at.
+pub fn run<F>(netlist: &Netlist, mut simulate: F) -> Result<ResultSet, ErrorHandler>
+where
+    F: FnMut(&Netlist) -> Result<Dataset, ErrorHandler>,
+{
+    let steps = steps(netlist)?;
+    let mut results = ResultSet {
+        shape: steps.iter().map(|s| s.sweep.values().len()).collect(),
+        ..ResultSet::default()
+    };
+    for point in step_points(&steps) {
+        let run = apply(netlist, &steps, &point).and_then(|n| simulate(&n));
+        match run {
+            Ok(dataset) => {
+                results.steps.push(point);
+                results.runs.push(dataset);
+            }
+            Err(mut errors) => {
+                if !point.values.is_empty() {
+                    for error in &mut errors.errors {
+                        error.message = format!("{} (at .step {})", error.message, point);
+                    }
+                }
+                return Err(errors);
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn is_step(directive: &str) -> bool {
+    directive_is(directive, ".step")
+}
+
+fn directive_is(directive: &str, name: &str) -> bool {
+    directive
+        .split_whitespace()
+        .next()
+        .is_some_and(|first| first.eq_ignore_ascii_case(name))
+}
+
+/// Replace any definition of `name` in the `.param` lines with `value`.
+fn set_param(netlist: &mut Netlist, name: &str, value: f64) {
+    let mut directives = Vec::new();
+    for directive in netlist.directives.drain(..) {
+        if !directive_is(&directive, ".param") {
+            directives.push(directive);
+            continue;
+        }
+        let rest = directive.trim_start()[".param".len()..].to_string();
+        let kept: Vec<String> = assignments(&rest)
+            .into_iter()
+            .filter(|(key, _)| !key.eq_ignore_ascii_case(name))
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        if !kept.is_empty() {
+            directives.push(format!(".param {}", kept.join(" ")));
+        }
+    }
+    directives.push(format!(".param {}={}", name, value));
+    netlist.directives = directives;
+}
+
+/// Set the value of an element: the number or `{expression}` that starts
+/// its value, after a leading `DC` for sources.
+fn set_component(netlist: &mut Netlist, name: &str, value: f64) -> Result<(), String> {
+    let Some(component) = netlist
+        .components
+        .iter_mut()
+        .find(|c| c.name.eq_ignore_ascii_case(name))
+    else {
+        return Err(format!("Stepped element {} is not in the netlist", name));
+    };
+    let mut words = split_groups(&component.value, false);
+    let position = match words.first() {
+        Some(first) if first.eq_ignore_ascii_case("DC") => 1,
+        _ => 0,
+    };
+    match words.get(position) {
+        Some(word) if word.starts_with('{') || parse_number(word).is_some() => {
+            words[position] = value.to_string()
+        }
+        _ => return Err(format!("Stepped element {} has no value to replace", name)),
+    }
+    component.value = words.join(" ");
+    Ok(())
+}
+
+fn set_model_parameter(
+    netlist: &mut Netlist,
+    kind: &str,
+    model: &str,
+    parameter: &str,
+    value: f64,
+) -> Result<(), String> {
+    for directive in &mut netlist.directives {
+        let mut words = directive.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case(".model"))
+            || !words.next().is_some_and(|w| w.eq_ignore_ascii_case(model))
+        {
+            continue;
+        }
+        let rest = directive.trim_start()[".model".len()..].trim_start();
+        let (name, rest) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+        let rest = rest.trim();
+        let type_end = rest
+            .find(|c: char| c == '(' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let (model_type, parameters) = rest.split_at(type_end);
+        if !model_type.eq_ignore_ascii_case(kind) {
+            return Err(format!(
+                "Model {} is of type {}, not {}",
+                model, model_type, kind
+            ));
+        }
+        let parameters = parameters.trim();
+        let parameters = parameters
+            .strip_prefix('(')
+            .and_then(|p| p.strip_suffix(')'))
+            .unwrap_or(parameters);
+
+        let mut list = assignments(parameters);
+        match list
+            .iter_mut()
+            .find(|(key, _)| key.eq_ignore_ascii_case(parameter))
+        {
+            Some(entry) => entry.1 = value.to_string(),
+            None => list.push((parameter.to_string(), value.to_string())),
+        }
+        let list: Vec<String> = list
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        *directive = format!(".model {} {}({})", name, model_type, list.join(" "));
+        return Ok(());
+    }
+    Err(format!("Stepped model {} is not in the netlist", model))
+}
+
+/// `key=value` pairs separated by whitespace or commas. Spaces around `=`
+/// are allowed and values may be `{expressions}` containing spaces.
+fn assignments(text: &str) -> Vec<(String, String)> {
+    let words = split_groups(text, true);
+
+    // Rejoin `a = 1`, `a= 1` and `a =1`.
+    let mut joined: Vec<String> = Vec::new();
+    for word in words {
+        match joined.last_mut() {
+            Some(last) if last.ends_with('=') || word.starts_with('=') => last.push_str(&word),
+            _ => joined.push(word),
+        }
+    }
+    joined
+        .into_iter()
+        .filter_map(|word| {
+            let (key, value) = word.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Split on whitespace, and on commas if `commas`, outside braces and
+/// parentheses, so that `{R * 2}` and `SIN(0 1 1k)` stay one word.
+fn split_groups(text: &str, commas: bool) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' | '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            '}' | ')' => {
+                depth -= 1;
+                current.push(c);
+            }
+            c if depth == 0 && (c.is_whitespace() || (commas && c == ',')) => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::parser::netlist::{Component, Node};
+
+    fn divider() -> Netlist {
+        let mut netlist = Netlist::new();
+        netlist.add_component(Component::new(
+            "V1",
+            vec![Node::new("in"), Node::new("0")],
+            "DC 5 AC 1",
+        ));
+        netlist.add_component(Component::new(
+            "R1",
+            vec![Node::new("in"), Node::new("out")],
+            "{R}",
+        ));
+        netlist.add_component(Component::new(
+            "Q1",
+            vec![Node::new("out"), Node::new("in"), Node::new("0")],
+            "2N2222",
+        ));
+        netlist.add_directive(".param R = 1k C=1n".to_string());
+        netlist.add_directive(".model 2N2222 NPN(IS=1e-14 VAF=100)".to_string());
+        netlist.add_directive(".op".to_string());
+        netlist
+    }
+
+    #[test]
+    fn test_step_points() {
+        let mut netlist = divider();
+        netlist.add_directive(".step param R list 1k 2k 3k".to_string());
+        netlist.add_directive(".step V1 1 2 1".to_string());
+        let steps = steps(&netlist).unwrap();
+        let points = step_points(&steps);
+        assert_eq!(points.len(), 6);
+        assert_eq!(points[1].to_string(), "R=2000 V1=1");
+        assert_eq!(points[3].value("v1"), Some(2.0));
+
+        let applied = apply(&netlist, &steps, &points[4]).unwrap();
+        assert_eq!(applied.component("V1").unwrap().value, "DC 2 AC 1");
+        assert_eq!(applied.component("R1").unwrap().value, "{R}");
+        assert_eq!(
+            applied.directives,
+            [
+                ".param C=1n",
+                ".model 2N2222 NPN(IS=1e-14 VAF=100)",
+                ".op",
+                ".param R=2000"
+            ]
+        );
+
+        netlist.add_directive(".step temp 0 50 25".to_string());
+        netlist.add_directive(".step NPN 2N2222(VAF) 50 100 50".to_string());
+        let errors = super::steps(&netlist).err().unwrap();
+        assert!(errors.errors[0].message.contains("At most 3"));
+
+        // Each sweep is within the cap on its own, but not their product.
+        let mut netlist = divider();
+        netlist.add_directive(".step param R 1 100 1".to_string());
+        netlist.add_directive(".step param C 1 100 1".to_string());
+        netlist.add_directive(".step temp 1 100 1".to_string());
+        let errors = super::steps(&netlist).err().unwrap();
+        assert_eq!(
+            errors.errors[0].message,
+            "The nested .step directives have 1000000 points, more than 100000"
+        );
+    }
+
+    #[test]
+    fn test_component_step() {
+        let mut netlist = divider();
+        netlist.components[1].value = "{R * 2} tc1=1m".to_string();
+        netlist.add_directive(".step R1 list 5k".to_string());
+        let steps = steps(&netlist).unwrap();
+        let applied = apply(&netlist, &steps, &step_points(&steps)[0]).unwrap();
+        assert_eq!(applied.component("R1").unwrap().value, "5000 tc1=1m");
+
+        let mut netlist = divider();
+        netlist.components[0].value = "SIN(0 1 1k)".to_string();
+        netlist.add_directive(".step V1 list 1".to_string());
+        let steps = super::steps(&netlist).unwrap();
+        let errors = apply(&netlist, &steps, &step_points(&steps)[0])
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors.errors[0].message,
+            "Stepped element V1 has no value to replace"
+        );
+    }
+
+    #[test]
+    fn test_model_step() {
+        let mut netlist = divider();
+        netlist.add_directive(".step NPN 2N2222(VAF) 50 100 50".to_string());
+        netlist.add_directive(".step NPN 2N2222(BF) list 200".to_string());
+        let steps = steps(&netlist).unwrap();
+        let point = &step_points(&steps)[1];
+        let applied = apply(&netlist, &steps, point).unwrap();
+        assert_eq!(
+            applied.directives[1],
+            ".model 2N2222 NPN(IS=1e-14 VAF=100 BF=200)"
+        );
+
+        let mut netlist = divider();
+        netlist.add_directive(".step PNP 2N2222(VAF) 50 100 50".to_string());
+        let steps = super::steps(&netlist).unwrap();
+        assert!(apply(&netlist, &steps, &step_points(&steps)[0]).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut netlist = divider();
+        netlist.add_directive(".step param R 1k 3k 1k".to_string());
+        netlist.add_directive(".step temp list 25 85".to_string());
+        let results = run(&netlist, |netlist| {
+            let r = netlist
+                .directives
+                .iter()
+                .find_map(|d| d.strip_prefix(".param R="))
+                .and_then(parse_number)
+                .unwrap();
+            let mut dataset = Dataset::new("time");
+            dataset.insert("time", vec![0.0, 1.0]);
+            dataset.insert("I(R1)", vec![5.0 / r, 5.0 / r]);
+            Ok(dataset)
+        })
+        .unwrap();
+        assert_eq!(results.shape, [3, 2]);
+        assert_eq!(results.len(), 6);
+        let index = results.index(&[1, 1]).unwrap();
+        assert_eq!(index, 4);
+        let (point, dataset) = results.run(index).unwrap();
+        assert_eq!(point.to_string(), "R=2000 temp=85");
+        assert_eq!(dataset.vector("i(r1)"), Some(&[2.5e-3, 2.5e-3][..]));
+        assert_eq!(results.family("I(R1)").len(), 6);
+        assert_eq!(results.index(&[3, 0]), None);
+
+        let errors = run(&netlist, |_| {
+            let mut errors = ErrorHandler::new();
+            errors.add_error(Error::new(ErrorType::Runtime, "singular".to_string(), 0, 0));
+            Err(errors)
+        })
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors.errors[0].message,
+            "singular (at .step R=1000 temp=25)"
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Time step control of the transient analysis.
+//!
+//! After each converged step the local truncation error of every quantity
+//! is estimated from the difference between the corrector, the value the
+//! integration formula gave, and the predictor, the polynomial through the
+//! accepted points extrapolated to the new time. The step is kept if the
+//! error is within `trtol` times the tolerances and the next one is sized
+//! so that it would be; otherwise it is retried shorter. Steps land exactly
+//! on the corners of the source waveforms, where the integration restarts
+//! at first order.
+
+use crate::analysis::integration::{Integrator, Method, MAX_ORDER};
+use crate::analysis::options::Options;
+use crate::frontend::parser::tran::Tran;
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+
+/// Time over which `startup` ramps the independent sources up, as in
+/// LTspice.
+pub const STARTUP_TIME: f64 = 20e-6;
+
+/// A step is rejected when its truncation error allows less than this
+/// fraction of it.
+const REJECT_RATIO: f64 = 0.9;
+
+/// Most a step may grow over the one before.
+const MAX_GROWTH: f64 = 2.0;
+
+/// A step on which Newton iteration fails is retried this many times
+/// shorter.
+const FAILURE_CUT: f64 = 8.0;
+
+/// Smallest step, relative to the largest.
+const MIN_STEP_RATIO: f64 = 1e-11;
+
+/// Times closer than this, relative to the largest step, are the same
+/// breakpoint.
+const BREAKPOINT_RATIO: f64 = 5e-5;
+
+/// Error constants of backward differentiation by order: the local error
+/// is the constant times `h^(k+1)` times the `(k+1)`th derivative.
+const GEAR_ERROR: [f64; MAX_ORDER] = [
+    1.0 / 2.0,
+    2.0 / 9.0,
+    3.0 / 22.0,
+    12.0 / 125.0,
+    10.0 / 137.0,
+    20.0 / 343.0,
+];
+
+/// Error constant of the trapezoidal rule.
+const TRAPEZOIDAL_ERROR: f64 = 1.0 / 12.0;
+
+/// What a quantity measures, which sets its absolute tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Voltage,
+    Current,
+    Charge,
+}
+
+/// The step had to be cut below the smallest step allowed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestepError {
+    pub time: f64,
+    pub step: f64,
+}
+
+impl Display for TimestepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Time step too small at t = {:e} s: {:e} s",
+            self.time, self.step
+        )
+    }
+}
+
+impl From<TimestepError> for Error {
+    fn from(error: TimestepError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// The time and step size of a transient analysis as it goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timestep {
+    stop: f64,
+    max_step: f64,
+    min_step: f64,
+    resolution: f64,
+    reltol: f64,
+    abstol: f64,
+    vntol: f64,
+    chgtol: f64,
+    trtol: f64,
+    startup: bool,
+    uic: bool,
+    /// Times ahead to land on, in order, the stop time last.
+    breakpoints: Vec<f64>,
+    time: f64,
+    /// Step to try next.
+    step: f64,
+}
+
+impl Timestep {
+    /// Without a largest step in `.tran`, steps are kept below the print
+    /// step and a fiftieth of the simulated time, as in SPICE.
+    pub fn new(tran: &Tran, options: &Options) -> Self {
+        let span = tran.stop - tran.start;
+        let print_step = if tran.print_step > 0.0 {
+            tran.print_step
+        } else {
+            span / 50.0
+        };
+        let max_step = tran.max_step.unwrap_or(print_step.min(span / 50.0));
+        let mut timestep = Timestep {
+            stop: tran.stop,
+            max_step,
+            min_step: MIN_STEP_RATIO * max_step,
+            resolution: BREAKPOINT_RATIO * max_step,
+            reltol: options.reltol,
+            abstol: options.abstol,
+            vntol: options.vntol,
+            chgtol: options.chgtol,
+            trtol: options.trtol,
+            startup: tran.startup,
+            uic: tran.uic,
+            breakpoints: vec![tran.stop],
+            time: 0.0,
+            step: (tran.stop / 100.0).min(print_step).min(max_step) / 10.0,
+        };
+        if tran.startup {
+            timestep.add_breakpoint(STARTUP_TIME);
+        }
+        timestep
+    }
+
+    /// With `uic` the simulation starts from the initial conditions
+    /// instead of the operating point.
+    pub fn needs_operating_point(&self) -> bool {
+        !self.uic
+    }
+
+    /// Time of the last accepted point.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn max_step(&self) -> f64 {
+        self.max_step
+    }
+
+    pub fn finished(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Make sure a time point falls on `time`, such as a corner of a
+    /// source waveform. Times already passed or beyond the stop time are
+    /// ignored.
+    pub fn add_breakpoint(&mut self, time: f64) {
+        if time <= self.time + self.resolution || time >= self.stop - self.resolution {
+            return;
+        }
+        let index = self.breakpoints.partition_point(|b| *b < time);
+        let near = |i: usize| {
+            self.breakpoints
+                .get(i)
+                .is_some_and(|b| (b - time).abs() <= self.resolution)
+        };
+        if !near(index) && (index == 0 || !near(index - 1)) {
+            self.breakpoints.insert(index, time);
+        }
+    }
+
+    pub fn add_breakpoints(&mut self, times: impl IntoIterator<Item = f64>) {
+        for time in times {
+            self.add_breakpoint(time);
+        }
+    }
+
+    /// Scale of the independent sources at `time`. With `startup` they are
+    /// off at the operating point and ramp up linearly over
+    /// [`STARTUP_TIME`].
+    pub fn source_factor(&self, time: f64) -> f64 {
+        if self.startup {
+            (time / STARTUP_TIME).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Size of the next step to try: the step the error allows, at most
+    /// the largest step, landing on the next breakpoint rather than
+    /// passing it or leaving a sliver before it. A breakpoint barely
+    /// further than the resolution is still approached in halves, so that
+    /// a rejected step is always retried shorter.
+    pub fn next(&self) -> f64 {
+        let step = self.step.min(self.max_step);
+        let Some(breakpoint) = self.breakpoints.first() else {
+            return step;
+        };
+        let gap = breakpoint - self.time;
+        if step >= gap - self.resolution.min(0.1 * gap) {
+            gap
+        } else if 2.0 * step > gap {
+            gap / 2.0
+        } else {
+            step
+        }
+    }
+
+    /// Largest step the local truncation error of one quantity allows,
+    /// judged on a converged step of `h` to `value`. `past` holds the
+    /// accepted values of the quantity, newest first, at the integrator's
+    /// steps. Infinite while there is too little history to tell, right
+    /// after the start or a breakpoint.
+    pub fn allowed_step(
+        &self,
+        integrator: &Integrator,
+        h: f64,
+        quantity: Quantity,
+        past: &[f64],
+        value: f64,
+    ) -> f64 {
+        let order = integrator.order();
+        let steps = integrator.steps();
+        if past.len() <= order || steps.len() < order {
+            return f64::INFINITY;
+        }
+        let predicted = predict(&past[..=order], steps, h);
+        // Both errors are proportional to the (k+1)th derivative: the
+        // predictor's with the distances to the points it goes through
+        // over (k+1)!, the corrector's with the error constant.
+        let mut spread = h;
+        let mut distance = h;
+        for (j, step) in steps[..order].iter().enumerate() {
+            distance += step;
+            spread *= distance / (j + 2) as f64;
+        }
+        let corrector = error_constant(integrator.method(), order) * h.powi(order as i32 + 1);
+        let error = corrector * (value - predicted).abs() / (spread + corrector);
+        let tolerance = self.reltol * value.abs().max(predicted.abs())
+            + match quantity {
+                Quantity::Voltage => self.vntol,
+                Quantity::Current => self.abstol,
+                Quantity::Charge => self.chgtol,
+            };
+        if error == 0.0 {
+            return f64::INFINITY;
+        }
+        h * (self.trtol * tolerance / error).powf(1.0 / (order + 1) as f64)
+    }
+
+    /// Finish a converged step of `h` whose truncation error allows at
+    /// most `allowed`, the smallest [`Self::allowed_step`] over every
+    /// quantity. Returns whether the step is kept; a step that is not is
+    /// retried with the shorter step [`Self::next`] gives. A kept step
+    /// moves the time on and is recorded in `integrator`, which restarts
+    /// on breakpoints.
+    pub fn complete(
+        &mut self,
+        h: f64,
+        allowed: f64,
+        integrator: &mut Integrator,
+    ) -> Result<bool, TimestepError> {
+        if allowed < REJECT_RATIO * h {
+            self.step = allowed;
+            self.check()?;
+            return Ok(false);
+        }
+        self.time += h;
+        integrator.accept(h);
+        self.step = allowed.min(MAX_GROWTH * h);
+        if self
+            .breakpoints
+            .first()
+            .is_some_and(|b| (b - self.time).abs() <= self.resolution)
+        {
+            self.time = self.breakpoints.remove(0);
+            integrator.restart();
+            // The waveforms bend here, so start again with a short step.
+            let gap = self.breakpoints.first().map_or(h, |b| b - self.time);
+            self.step = self.step.min(0.1 * h.min(gap));
+        }
+        Ok(true)
+    }
+
+    /// Newton iteration did not converge on a step of `h`: retry it
+    /// shorter, at first order.
+    pub fn failed(&mut self, h: f64, integrator: &mut Integrator) -> Result<(), TimestepError> {
+        self.step = h / FAILURE_CUT;
+        integrator.set_order(1);
+        self.check()
+    }
+
+    fn check(&self) -> Result<(), TimestepError> {
+        if self.step < self.min_step {
+            return Err(TimestepError {
+                time: self.time,
+                step: self.step,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Value `h` after the newest point of the polynomial through `past`,
+/// newest first, spaced by `steps`.
+fn predict(past: &[f64], steps: &[f64], h: f64) -> f64 {
+    let mut times = vec![0.0; past.len()];
+    for j in 1..past.len() {
+        times[j] = times[j - 1] - steps[j - 1];
+    }
+    past.iter()
+        .zip(&times)
+        .enumerate()
+        .map(|(j, (value, tj))| {
+            let weight: f64 = times
+                .iter()
+                .enumerate()
+                .filter(|(m, _)| *m != j)
+                .map(|(_, tm)| (h - tm) / (tj - tm))
+                .product();
+            value * weight
+        })
+        .sum()
+}
+
+fn error_constant(method: Method, order: usize) -> f64 {
+    match (method, order) {
+        (Method::Trapezoidal | Method::ModifiedTrapezoidal, 2) => TRAPEZOIDAL_ERROR,
+        (_, order) => GEAR_ERROR[order - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::History;
+    use crate::devices::tests::{circuit, transient};
+    use crate::frontend::parser::source::Waveform;
+    use crate::frontend::parser::tran::parse_tran;
+
+    const TAU: f64 = 1e-3;
+
+    fn pulse() -> Waveform {
+        Waveform::Pulse {
+            v1: 0.0,
+            v2: 1.0,
+            delay: 1e-3,
+            rise: 1e-6,
+            fall: 1e-6,
+            width: 2e-3,
+            period: 0.0,
+            cycles: None,
+        }
+    }
+
+    /// Exact response of `τ v' = u - v` from `v(0) = 0`: on each linear
+    /// piece `u = u0 + s t` it is `u - sτ + (v0 - u0 + sτ) e^(-t/τ)`.
+    fn exact(source: &Waveform, time: f64) -> f64 {
+        let mut corners = vec![0.0];
+        corners.extend(source.breakpoints(0.0, time));
+        corners.push(time);
+        let mut v = 0.0;
+        for piece in corners.windows(2) {
+            let (t0, t1) = (piece[0], piece[1]);
+            if t1 <= t0 {
+                continue;
+            }
+            let u0 = source.value(t0);
+            let slope = (source.value(t1) - u0) / (t1 - t0);
+            let decay = (-(t1 - t0) / TAU).exp();
+            v = source.value(t1) - slope * TAU + (v - u0 + slope * TAU) * decay;
+        }
+        v
+    }
+
+    /// Simulate the RC low-pass with a unit capacitor and the pulse as
+    /// input, returning the accepted points and the rejected step count.
+    fn simulate(tran: &str, options: &Options) -> (Vec<(f64, f64)>, usize) {
+        let tran = parse_tran(tran).unwrap();
+        let source = pulse();
+        let mut timestep = Timestep::new(&tran, options);
+        timestep.add_breakpoints(source.breakpoints(0.0, tran.stop));
+        let mut integrator = Integrator::new(options.method, options.maxord);
+        let mut history = History::new(0.0);
+        let mut past = vec![0.0];
+        let mut points = vec![(0.0, 0.0)];
+        let mut rejected = 0;
+        while !timestep.finished() {
+            let h = timestep.next();
+            integrator.prepare(h);
+            let t = timestep.time() + h;
+            let u = timestep.source_factor(t) * source.value(t);
+            let companion = integrator.companion(&history, 1.0);
+            let v = (u / TAU - companion.ceq) / (companion.geq + 1.0 / TAU);
+            let allowed = timestep.allowed_step(&integrator, h, Quantity::Voltage, &past, v);
+            let derivative = integrator.derivative(&history, v);
+            if timestep.complete(h, allowed, &mut integrator).unwrap() {
+                history.accept(v, derivative);
+                past.insert(0, v);
+                past.truncate(MAX_ORDER + 1);
+                points.push((timestep.time(), v));
+            } else {
+                rejected += 1;
+            }
+        }
+        (points, rejected)
+    }
+
+    fn worst_error(points: &[(f64, f64)]) -> f64 {
+        let source = pulse();
+        points
+            .iter()
+            .map(|(t, v)| (v - exact(&source, *t)).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_predict() {
+        // A quadratic through three unevenly spaced points.
+        let f = |t: f64| 1.0 + 2.0 * t - 3.0 * t * t;
+        let past = [f(0.0), f(-0.5), f(-1.25)];
+        assert!((predict(&past, &[0.5, 0.75], 0.3) - f(0.3)).abs() < 1e-12);
+        assert_eq!(predict(&[2.0], &[], 1.0), 2.0);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let options = Options::default();
+        let (points, _) = simulate(".tran 5m", &options);
+        let times: Vec<f64> = points.iter().map(|p| p.0).collect();
+        for corner in pulse().breakpoints(0.0, 5e-3) {
+            assert!(times.contains(&corner), "no point at {}", corner);
+        }
+        assert_eq!(*times.last().unwrap(), 5e-3);
+        // Steps grow between the edges up to the default largest step,
+        // a fiftieth of the simulated time.
+        let steps: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).collect();
+        let largest = steps.iter().cloned().fold(0.0, f64::max);
+        assert!(largest <= 1e-4 * (1.0 + 1e-9));
+        assert!(largest > 0.5e-4);
+        assert!(steps.len() < 400, "{} steps", steps.len());
+        assert!(worst_error(&points) < 1e-2, "{}", worst_error(&points));
+
+        // Tmax from .tran.
+        let (points, _) = simulate(".tran 0 5m 0 20u", &options);
+        assert!(points
+            .windows(2)
+            .all(|w| w[1].0 - w[0].0 <= 20e-6 * (1.0 + 1e-9)));
+    }
+
+    #[test]
+    fn test_tolerances() {
+        // Modified trapezoidal adds a first-order error the estimate does
+        // not see, so compare on the trapezoidal rule proper.
+        let loose = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let tight = Options {
+            reltol: 1e-5,
+            trtol: 1.0,
+            ..loose.clone()
+        };
+        let (coarse, _) = simulate(".tran 0 5m 0 1m", &loose);
+        let (fine, _) = simulate(".tran 0 5m 0 1m", &tight);
+        assert!(fine.len() > 2 * coarse.len());
+        assert!(worst_error(&fine) < worst_error(&coarse) / 10.0);
+
+        // Backward Euler's first-order error adds up over the steps.
+        for (method, bound) in [(Method::BackwardEuler, 5e-2), (Method::Gear, 1e-2)] {
+            let options = Options {
+                method,
+                maxord: 4,
+                ..Options::default()
+            };
+            let (points, _) = simulate(".tran 0 5m 0 1m", &options);
+            assert!(worst_error(&points) < bound, "{}", method);
+        }
+    }
+
+    #[test]
+    fn test_rejection_and_failure() {
+        let tran = parse_tran(".tran 1m").unwrap();
+        let options = Options::default();
+        let mut timestep = Timestep::new(&tran, &options);
+        let mut integrator = Integrator::new(Method::Trapezoidal, 2);
+        let h = timestep.next();
+        assert!((h - 1e-6).abs() < 1e-18);
+        // An error allowing half the step rejects it.
+        assert!(!timestep.complete(h, h / 2.0, &mut integrator).unwrap());
+        assert_eq!(timestep.time(), 0.0);
+        assert_eq!(timestep.next(), h / 2.0);
+        // A kept step grows the next at most twofold.
+        assert!(timestep.complete(h / 2.0, 1.0, &mut integrator).unwrap());
+        assert!((timestep.next() - h).abs() < 1e-18);
+        assert_eq!(integrator.order(), 2);
+
+        timestep.failed(h, &mut integrator).unwrap();
+        assert_eq!(integrator.order(), 1);
+        assert_eq!(timestep.next(), h / 8.0);
+        let mut step = h;
+        let error = loop {
+            match timestep.failed(step, &mut integrator) {
+                Ok(()) => step = timestep.next(),
+                Err(error) => break error,
+            }
+        };
+        assert!(error.step < 1e-11 * timestep.max_step());
+        assert!(error
+            .to_string()
+            .starts_with("Time step too small at t = 5"));
+        assert!((error.time - 5e-7).abs() < 1e-18);
+
+        // A step rejected just before a breakpoint barely past the
+        // resolution is retried shorter rather than landing on it again.
+        let mut timestep = Timestep::new(&tran, &options);
+        timestep.add_breakpoint(1.5e-9);
+        let h = timestep.next();
+        assert_eq!(h, 1.5e-9);
+        assert!(!timestep.complete(h, 0.8 * h, &mut integrator).unwrap());
+        assert!(timestep.next() < 0.8 * h);
+    }
+
+    #[test]
+    fn test_startup_and_uic() {
+        let options = Options::default();
+        let timestep = Timestep::new(&parse_tran(".tran 1m startup").unwrap(), &options);
+        assert!(timestep.needs_operating_point());
+        assert_eq!(timestep.source_factor(0.0), 0.0);
+        assert_eq!(timestep.source_factor(10e-6), 0.5);
+        assert_eq!(timestep.source_factor(1e-3), 1.0);
+        assert_eq!(timestep.breakpoints, [STARTUP_TIME, 1e-3]);
+
+        let timestep = Timestep::new(&parse_tran(".tran 1m uic").unwrap(), &options);
+        assert!(!timestep.needs_operating_point());
+        assert_eq!(timestep.source_factor(0.0), 1.0);
+    }
+
+    #[test]
+    fn test_startup_transient() {
+        // The source starts from zero in the operating point and follows
+        // the ramp until STARTUP_TIME.
+        let options = Options::default();
+        let mut divider = circuit(&["V1 in 0 5", "R1 in out 1k", "R2 out 0 1k"]);
+        let out = divider.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut divider, ".tran 100u startup", &options);
+        assert_eq!(points[0].1[out], 0.0);
+        assert!(points.iter().any(|(t, _)| *t == STARTUP_TIME));
+        for (t, solution) in &points {
+            let expected = 2.5 * (t / STARTUP_TIME).min(1.0);
+            assert!(
+                (solution[out] - expected).abs() < 1e-9,
+                "{} {}",
+                t,
+                solution[out]
+            );
+        }
+    }
+}
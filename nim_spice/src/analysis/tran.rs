@@ -0,0 +1,157 @@
+//! The transient analysis.
+//!
+//! It starts from the operating point, or from the initial conditions with
+//! `uic`, and steps to the stop time as [`Timestep`] sizes the steps. After
+//! every accepted point the devices are asked again for their breakpoints
+//! within the next step, as a switch only knows its next crossing of the
+//! threshold once its control has moved towards it, so that steps land on
+//! those too. Asking for only that window keeps the cost of each step
+//! independent of the number of waveform corners.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::newton::{Newton, NewtonError, System};
+use crate::analysis::op::{operating_point, OpError};
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Timestep, TimestepError};
+use crate::devices::generic::Circuit;
+use crate::frontend::parser::tran::Tran;
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+
+/// Why the transient analysis stopped before the stop time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranError {
+    OperatingPoint(OpError),
+    Timestep(TimestepError),
+    /// A device could not be evaluated, which a shorter step does not
+    /// cure.
+    Device(NewtonError),
+}
+
+impl Display for TranError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranError::OperatingPoint(error) => write!(f, "{}", error),
+            TranError::Timestep(error) => write!(f, "{}", error),
+            TranError::Device(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<OpError> for TranError {
+    fn from(error: OpError) -> Self {
+        TranError::OperatingPoint(error)
+    }
+}
+
+impl From<TimestepError> for TranError {
+    fn from(error: TimestepError) -> Self {
+        TranError::Timestep(error)
+    }
+}
+
+impl From<TranError> for Error {
+    fn from(error: TranError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// The accepted points of `circuit` over `tran`, the first at time zero.
+pub fn transient(
+    circuit: &mut Circuit,
+    tran: &Tran,
+    options: &Options,
+) -> Result<Vec<(f64, Vec<f64>)>, TranError> {
+    let mut newton = Newton::new(circuit, options);
+    let mut timestep = Timestep::new(tran, options);
+    timestep.add_breakpoints(circuit.breakpoints(0.0, horizon(&timestep)));
+    let mut integrator = Integrator::new(options.method, options.maxord);
+    circuit.set_initial();
+    newton.source_factor = timestep.source_factor(0.0);
+    let mut solution = if timestep.needs_operating_point() {
+        operating_point(&mut newton, circuit, options)?.solution
+    } else {
+        circuit.initial_conditions();
+        vec![0.0; circuit.size()]
+    };
+    circuit.accept(&solution, 0.0);
+    let mut points = vec![(0.0, solution.clone())];
+    while !timestep.finished() {
+        let h = timestep.next();
+        integrator.prepare(h);
+        let t = timestep.time() + h;
+        circuit.set_transient(t, &integrator);
+        newton.source_factor = timestep.source_factor(t);
+        let mut next = solution.clone();
+        match newton.solve(circuit, &mut next, options.itl4, options) {
+            Ok(_) => {}
+            Err(error @ NewtonError::Device { .. }) => return Err(TranError::Device(error)),
+            Err(_) => {
+                timestep.failed(h, &mut integrator)?;
+                continue;
+            }
+        }
+        let allowed = circuit.truncation(&timestep, &integrator, h);
+        if timestep.complete(h, allowed, &mut integrator)? {
+            solution = next;
+            circuit.accept(&solution, t);
+            timestep.add_breakpoints(circuit.breakpoints(t, horizon(&timestep)));
+            points.push((t, solution.clone()));
+        }
+    }
+    Ok(points)
+}
+
+/// End of the window the devices are asked for breakpoints in after a
+/// point: the step about to be tried, which a breakpoint in it can only
+/// shorten and a rejection only cuts back. Corners beyond are asked for
+/// as they come into view.
+fn horizon(timestep: &Timestep) -> f64 {
+    timestep.time() + timestep.next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::tests::circuit;
+    use crate::frontend::parser::tran::parse_tran;
+
+    #[test]
+    fn test_errors() {
+        // Two sources fighting over a node have no operating point to start
+        // from.
+        let options = Options::default();
+        let lines = ["V1 a 0 1", "V2 a 0 2"];
+        let tran = parse_tran(".tran 1m").unwrap();
+        let error = transient(&mut circuit(&lines), &tran, &options).unwrap_err();
+        assert!(matches!(error, TranError::OperatingPoint(_)));
+        assert!(error.to_string().starts_with("No DC operating point found"));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        // Steps land on the corners of a waveform and on the stop time.
+        let options = Options::default();
+        let lines = ["V1 a 0 PULSE(0 1 0.3m 1u 1u 1m)", "R1 a 0 1k"];
+        let tran = parse_tran(".tran 1m uic").unwrap();
+        let points = transient(&mut circuit(&lines), &tran, &options).unwrap();
+        assert!(points.iter().any(|(t, _)| (t - 0.3e-3).abs() < 1e-12));
+        assert_eq!(points.last().unwrap().0, 1e-3);
+
+        // The corners of a pulse train are asked for a step at a time, and
+        // still every one of them is landed on.
+        let lines = ["V1 a 0 PULSE(0 1 0 1u 1u 3u 10u)", "R1 a 0 1k"];
+        let tran = parse_tran(".tran 100u").unwrap();
+        let points = transient(&mut circuit(&lines), &tran, &options).unwrap();
+        for cycle in 0..10 {
+            for corner in [1e-6, 4e-6, 5e-6] {
+                let time = cycle as f64 * 10e-6 + corner;
+                assert!(
+                    points.iter().any(|(t, _)| (t - time).abs() < 1e-12),
+                    "{}",
+                    time
+                );
+            }
+        }
+    }
+}
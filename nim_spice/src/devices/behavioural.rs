@@ -0,0 +1,1026 @@
+//! Behavioural devices, whose value is an expression: the `B` source with
+//! `V=` or `I=`, `E` and `G` sources with `VALUE=`, and LTspice's resistor
+//! with `R=`, capacitor with `Q=` and inductor with `Flux=`.
+//!
+//! Expressions read node voltages `V(a)` and `V(a,b)`, the currents
+//! `I(Vname)` of elements with a branch current, `time` and `temp`, and in
+//! a capacitor's charge or an inductor's flux `x`, the voltage across the
+//! one or the current through the other. The last three devices are
+//! sources in disguise: a resistor's current is `V(n+,n-)/R`, a capacitor's
+//! `ddt(Q)` and an inductor's voltage `ddt(Flux)`.
+//!
+//! The expression runs on dual numbers seeded with the unknowns it reads,
+//! a few at a time, which gives the Jacobian exactly, `table()` included
+//! through the slope of the segment its argument falls on. `ddt`, `idt` and
+//! `delay` keep their argument's history across time steps. Each is an
+//! input of its own, and notes the derivatives of its argument, so that
+//! the chain through it is closed afterwards with the operator's own
+//! derivative: the integration formula's for `ddt`, the trapezoidal rule's
+//! for `idt` and the interpolation weight for `delay`, or in the AC
+//! analysis `jω`, `1/jω` and `e^(-jωτ)`. A `Q=` capacitor thereby has its
+//! capacitance at every frequency, not only in the transient analysis.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::generic::{add, current, entry, Device, LoadContext, State, Unknown, Unknowns};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::expression::{is_probe, BinaryOp, Expr, Scope};
+use crate::frontend::parser::netlist::{Component, Node};
+use crate::maths::complex::Complex;
+use crate::maths::dual::Dual;
+use crate::maths::sparse::{Handle, Pattern, Scalar, SparseMatrix};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+/// How many inputs one evaluation of an expression is seeded with.
+const WIDTH: usize = 8;
+
+type Number = Dual<WIDTH>;
+
+/// Relative and absolute change of slope, per second, that makes a corner
+/// of a delayed argument.
+const CORNER: [f64; 2] = [0.5, 1.0];
+
+/// The text of `key=` when it starts the line after the two nodes, up to
+/// the end of the line, as in `B1 out 0 V=2*V(in)`.
+pub fn expression<'a>(line: &'a ElementLine, key: &str) -> Option<&'a str> {
+    let text = line.after(2);
+    let head = text.get(..key.len())?;
+    if !head.eq_ignore_ascii_case(key) {
+        return None;
+    }
+    let rest = text[key.len()..].trim_start().strip_prefix('=')?;
+    Some(rest.trim())
+}
+
+/// Whether `key=` holds an expression rather than a number, as in a
+/// resistor's `R=V(a)*1k` against `R=1k tc1=1m`.
+pub fn is_behavioural(line: &ElementLine, key: &str) -> bool {
+    expression(line, key)
+        .is_some_and(|text| Expr::parse(text).is_ok_and(|e| !matches!(e, Expr::Number(_))))
+}
+
+/// What an operator does to its argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// `ddt(x)`.
+    Derivative,
+    /// `idt(x [, ic])`, starting from `ic`.
+    Integral(f64),
+    /// `delay(x, td)`.
+    Delay(f64),
+}
+
+/// An occurrence of `ddt`, `idt` or `delay` in an expression and what it
+/// remembers.
+#[derive(Debug, Clone, PartialEq)]
+struct Operator {
+    kind: Kind,
+    /// The argument of a `ddt`.
+    state: State,
+    /// The argument and the result at the last load, and the derivative of
+    /// the one with the other.
+    argument: f64,
+    output: f64,
+    slope: f64,
+    /// Accepted times, arguments and results, the oldest first.
+    accepted: Vec<(f64, f64, f64)>,
+    /// Times at which corners of a delayed argument come out.
+    corners: Vec<f64>,
+}
+
+impl Operator {
+    fn new(kind: Kind) -> Self {
+        Operator {
+            kind,
+            state: State::default(),
+            argument: 0.0,
+            output: 0.0,
+            slope: 0.0,
+            accepted: Vec::new(),
+            corners: Vec::new(),
+        }
+    }
+
+    /// The result for `argument` at `time`. Outside the transient analysis
+    /// a derivative is zero, an integral its initial value and a delay
+    /// passes its argument through.
+    fn apply(&mut self, argument: f64, time: f64, integrator: Option<&Integrator>) -> f64 {
+        self.argument = argument;
+        let last = self
+            .accepted
+            .last()
+            .copied()
+            .filter(|_| integrator.is_some());
+        (self.output, self.slope) = match (self.kind, integrator, last) {
+            (Kind::Derivative, Some(integrator), _) => {
+                self.state.integrate(integrator, argument, 1.0, argument);
+                (self.state.derivative(), integrator.ag()[0])
+            }
+            (Kind::Derivative, _, _) => {
+                self.state.reset(argument);
+                (0.0, 0.0)
+            }
+            (Kind::Integral(_), _, Some((t0, x0, y0))) => {
+                let half = (time - t0) / 2.0;
+                (y0 + half * (x0 + argument), half)
+            }
+            (Kind::Integral(initial), _, None) => (initial, 0.0),
+            (Kind::Delay(delay), _, Some((t0, x0, _))) if time - delay >= t0 => {
+                // Between the last accepted point and this one.
+                let weight = (time - delay - t0) / (time - t0);
+                (x0 + (argument - x0) * weight, weight)
+            }
+            (Kind::Delay(delay), _, Some(_)) => {
+                let target = time - delay;
+                let points = &self.accepted;
+                let index = points.partition_point(|p| p.0 <= target);
+                let value = if index == 0 {
+                    points[0].1
+                } else {
+                    let ((t0, x0, _), (t1, x1, _)) = (points[index - 1], points[index]);
+                    x0 + (x1 - x0) * (target - t0) / (t1 - t0)
+                };
+                (value, 0.0)
+            }
+            (Kind::Delay(_), _, None) => (argument, 1.0),
+        };
+        self.output
+    }
+
+    /// The derivative of the result with the argument at angular frequency
+    /// `omega`.
+    fn transfer(&self, omega: f64) -> Complex {
+        match self.kind {
+            Kind::Derivative => Complex::new(0.0, omega),
+            Kind::Integral(_) if omega == 0.0 => Complex::from(0.0),
+            Kind::Integral(_) => Complex::new(0.0, -1.0 / omega),
+            Kind::Delay(delay) => Complex::from_polar(1.0, -omega * delay),
+        }
+    }
+
+    fn accept(&mut self, time: f64) {
+        if self.accepted.last().is_some_and(|p| p.0 >= time) {
+            // The operating point again, at the start of a transient.
+            self.accepted.clear();
+            self.corners.clear();
+        }
+        self.state.accept();
+        self.accepted.push((time, self.argument, self.output));
+        self.corners.retain(|t| *t > time);
+        let Kind::Delay(delay) = self.kind else {
+            self.accepted.drain(..self.accepted.len() - 1);
+            return;
+        };
+        let slope = |a: &(f64, f64, f64), b: &(f64, f64, f64)| (b.1 - a.1) / (b.0 - a.0);
+        if let [.., a, b, c] = self.accepted.as_slice() {
+            let (earlier, later) = (slope(a, b), slope(b, c));
+            if (later - earlier).abs() > CORNER[0] * earlier.abs().max(later.abs()) + CORNER[1] {
+                self.corners.push(b.0 + delay);
+            }
+        }
+        let start = self.accepted.partition_point(|p| p.0 <= time - delay);
+        let keep = start
+            .saturating_sub(1)
+            .min(self.accepted.len().saturating_sub(3));
+        self.accepted.drain(..keep);
+    }
+}
+
+/// Something an expression reads, by name.
+#[derive(Debug, Clone, PartialEq)]
+enum Probe {
+    Voltage(String),
+    Current(String),
+}
+
+impl Probe {
+    fn key(&self) -> String {
+        match self {
+            Probe::Voltage(node) => format!("v({})", node.to_ascii_lowercase()),
+            Probe::Current(element) => format!("i({})", element.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// What `x` stands for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    /// The voltage across the device.
+    Across,
+    /// The current through it.
+    Through,
+}
+
+/// The derivatives at the last load of the value, and of each operator's
+/// argument, with each unknown read and then each operator's result.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Linearisation {
+    gradient: Vec<f64>,
+    rows: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Behavioural {
+    name: String,
+    nodes: [Node; 2],
+    /// Whether the value is a voltage across the output, with a branch
+    /// current of its own, rather than a current through it.
+    voltage: bool,
+    expression: Expr,
+    x: Option<Binding>,
+    probes: Vec<Probe>,
+    operators: Vec<Operator>,
+    temperature: f64,
+    unknowns: [Unknown; 2],
+    branch: Unknown,
+    /// The unknown of each probe, by its key.
+    sensed: HashMap<String, Unknown>,
+    /// The distinct unknowns the expression reads, its first inputs.
+    inputs: Vec<usize>,
+    /// `(n+, br)`, `(n-, br)`, `(br, n+)` and `(br, n-)` of a voltage.
+    entries: [Option<Handle>; 4],
+    /// The output's entries in the column of each input, `(br, k)` for a
+    /// voltage and `(n+, k)` and `(n-, k)` for a current.
+    input_entries: Vec<[Option<Handle>; 2]>,
+    linearisation: Linearisation,
+}
+
+impl Behavioural {
+    /// `Bname n+ n- V=expression` or `I=expression`, the current flowing
+    /// from `n+` through the source to `n-`.
+    pub fn source(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        if expression(line, "v").is_some() {
+            Behavioural::new(line, components, "v", true, None, |e, _| e)
+        } else if expression(line, "i").is_some() {
+            Behavioural::new(line, components, "i", false, None, |e, _| e)
+        } else {
+            Err("Neither V= nor I= given".to_string())
+        }
+    }
+
+    /// `Ename n+ n- VALUE={expression}` and the same for `G`.
+    pub fn value(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        let voltage = line.name.to_ascii_uppercase().starts_with('E');
+        Behavioural::new(line, components, "value", voltage, None, |e, _| e)
+    }
+
+    /// `Rname n+ n- R=expression`.
+    pub fn resistor(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        Behavioural::new(line, components, "r", false, None, |e, [p, n]| {
+            let across = Expr::Call(
+                "V".to_string(),
+                vec![
+                    Expr::Variable(p.name.clone()),
+                    Expr::Variable(n.name.clone()),
+                ],
+            );
+            Expr::Binary(BinaryOp::Divide, Box::new(across), Box::new(e))
+        })
+    }
+
+    /// `Cname n+ n- Q=expression` of the voltage across it, `x`.
+    pub fn capacitor(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        Behavioural::new(
+            line,
+            components,
+            "q",
+            false,
+            Some(Binding::Across),
+            |e, _| Expr::Call("ddt".to_string(), vec![e]),
+        )
+    }
+
+    /// `Lname n+ n- Flux=expression` of the current through it, `x`.
+    pub fn inductor(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        Behavioural::new(
+            line,
+            components,
+            "flux",
+            true,
+            Some(Binding::Through),
+            |e, _| Expr::Call("ddt".to_string(), vec![e]),
+        )
+    }
+
+    /// The device of the expression after `key=`, made into the value by
+    /// `wrap`, given the nodes.
+    fn new(
+        line: &ElementLine,
+        components: &[Component],
+        key: &str,
+        voltage: bool,
+        x: Option<Binding>,
+        wrap: impl FnOnce(Expr, &[Node; 2]) -> Expr,
+    ) -> Result<Self, String> {
+        let nodes = line.nodes()?;
+        let text =
+            expression(line, key).ok_or_else(|| format!("No {}= given", key.to_uppercase()))?;
+        let parsed = Expr::parse(text).map_err(|e| e.errors[0].message.clone())?;
+        let expression = wrap(parsed, &nodes);
+        let mut probes = Vec::new();
+        let mut operators = Vec::new();
+        collect(&expression, &mut probes, &mut operators)?;
+        expression.eval(&Check { x: x.is_some() })?;
+        for probe in &probes {
+            if let Probe::Current(source) = probe {
+                if !components
+                    .iter()
+                    .any(|c| c.name.eq_ignore_ascii_case(source) && has_branch(c))
+                {
+                    return Err(format!("No voltage source {}", source));
+                }
+            }
+        }
+        Ok(Behavioural {
+            name: line.name.clone(),
+            nodes,
+            voltage,
+            expression,
+            x,
+            probes,
+            operators,
+            temperature: 0.0,
+            unknowns: [None; 2],
+            branch: None,
+            sensed: HashMap::new(),
+            inputs: Vec::new(),
+            entries: [None; 4],
+            input_entries: Vec::new(),
+            linearisation: Linearisation::default(),
+        })
+    }
+
+    /// The value at `solution` and its derivatives with each input, the
+    /// operators' results followed through their arguments, or why the
+    /// expression could not be evaluated.
+    fn linearise(
+        &mut self,
+        solution: &[f64],
+        time: f64,
+        integrator: Option<&Integrator>,
+    ) -> Result<(f64, Vec<f64>), String> {
+        let size = self.inputs.len() + self.operators.len();
+        let count = self.operators.len();
+        let mut gradient = vec![0.0; size];
+        let mut rows = vec![vec![0.0; size]; count];
+        let x = self.x.map(|binding| match binding {
+            Binding::Across => self.unknowns,
+            Binding::Through => [self.branch, None],
+        });
+        let mut offset = 0;
+        let value = loop {
+            let evaluation = Evaluation {
+                solution,
+                time,
+                temperature: self.temperature,
+                integrator,
+                sensed: &self.sensed,
+                inputs: &self.inputs,
+                x,
+                offset,
+                operators: RefCell::new(&mut self.operators),
+                arguments: RefCell::new(vec![Dual::constant(0.0); count]),
+                next: Cell::new(0),
+            };
+            let result = self.expression.eval(&evaluation)?;
+            let arguments = evaluation.arguments.into_inner();
+            for slot in 0..WIDTH.min(size.saturating_sub(offset)) {
+                gradient[offset + slot] = result.derivative(slot);
+                for (row, argument) in rows.iter_mut().zip(&arguments) {
+                    row[offset + slot] = argument.derivative(slot);
+                }
+            }
+            offset += WIDTH;
+            if offset >= size {
+                break result.value;
+            }
+        };
+        let transfer: Vec<f64> = self.operators.iter().map(|o| o.slope).collect();
+        let jacobian = chain(&gradient, &rows, &transfer, self.inputs.len());
+        self.linearisation = Linearisation { gradient, rows };
+        Ok((value, jacobian))
+    }
+
+    fn stamp<T: Scalar>(&self, matrix: &mut SparseMatrix<T>, jacobian: &[T], one: T) {
+        if self.voltage {
+            add(matrix, self.entries[0], one);
+            add(matrix, self.entries[1], -one);
+            add(matrix, self.entries[2], one);
+            add(matrix, self.entries[3], -one);
+            for (handles, g) in self.input_entries.iter().zip(jacobian) {
+                add(matrix, handles[0], -*g);
+            }
+        } else {
+            for (handles, g) in self.input_entries.iter().zip(jacobian) {
+                add(matrix, handles[0], *g);
+                add(matrix, handles[1], -*g);
+            }
+        }
+    }
+}
+
+/// The derivatives with the inputs, `F + E (I - T B)⁻¹ T A`, where `F` and
+/// `E` are those of the value with the unknowns and with the operators'
+/// results, `A` and `B` those of the operators' arguments, and `T` the
+/// derivatives of the results with the arguments.
+fn chain<T: Scalar + From<f64>>(
+    gradient: &[f64],
+    rows: &[Vec<f64>],
+    transfer: &[T],
+    inputs: usize,
+) -> Vec<T> {
+    let count = transfer.len();
+    // (I - T B)ᵀ z = E, for the row z = E (I - T B)⁻¹.
+    let mut system: Vec<Vec<T>> = (0..count)
+        .map(|l| {
+            (0..count)
+                .map(|j| {
+                    let identity = T::from(if l == j { 1.0 } else { 0.0 });
+                    identity - transfer[j] * T::from(rows[j][inputs + l])
+                })
+                .collect()
+        })
+        .collect();
+    let mut z: Vec<T> = gradient[inputs..].iter().map(|g| T::from(*g)).collect();
+    solve(&mut system, &mut z);
+    (0..inputs)
+        .map(|k| {
+            let mut total = T::from(gradient[k]);
+            for j in 0..count {
+                total += z[j] * transfer[j] * T::from(rows[j][k]);
+            }
+            total
+        })
+        .collect()
+}
+
+/// Solve a small dense system in place by Gaussian elimination with
+/// partial pivoting, leaving the solution in `rhs`.
+fn solve<T: Scalar>(matrix: &mut [Vec<T>], rhs: &mut [T]) {
+    let size = rhs.len();
+    for column in 0..size {
+        let pivot = (column..size)
+            .max_by(|a, b| {
+                matrix[*a][column]
+                    .magnitude()
+                    .total_cmp(&matrix[*b][column].magnitude())
+            })
+            .unwrap_or(column);
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        for row in column + 1..size {
+            let factor = matrix[row][column] / matrix[column][column];
+            let (upper, lower) = matrix.split_at_mut(row);
+            for (entry, above) in lower[0][column..].iter_mut().zip(&upper[column][column..]) {
+                *entry -= factor * *above;
+            }
+            let term = factor * rhs[column];
+            rhs[row] -= term;
+        }
+    }
+    for row in (0..size).rev() {
+        let mut value = rhs[row];
+        for k in row + 1..size {
+            value -= matrix[row][k] * rhs[k];
+        }
+        rhs[row] = value / matrix[row][row];
+    }
+}
+
+/// Whether `component` has a branch current an expression may read.
+fn has_branch(component: &Component) -> bool {
+    let line = ElementLine::new(component);
+    match component.prefix() {
+        'V' | 'E' | 'H' | 'L' => true,
+        'B' => expression(&line, "v").is_some(),
+        _ => false,
+    }
+}
+
+/// Gather the probes and the operators of `expr` in the order evaluation
+/// meets them, checking their arguments.
+fn collect(
+    expr: &Expr,
+    probes: &mut Vec<Probe>,
+    operators: &mut Vec<Operator>,
+) -> Result<(), String> {
+    match expr {
+        Expr::Number(_) | Expr::Variable(_) => Ok(()),
+        Expr::Unary(_, operand) => collect(operand, probes, operators),
+        Expr::Binary(_, lhs, rhs) => {
+            collect(lhs, probes, operators)?;
+            collect(rhs, probes, operators)
+        }
+        Expr::Call(name, args) => {
+            let names: Vec<&str> = args
+                .iter()
+                .map(|arg| match arg {
+                    Expr::Variable(name) => name.as_str(),
+                    _ => "",
+                })
+                .collect();
+            let kind = match name.to_ascii_lowercase().as_str() {
+                "v" if (1..=2).contains(&args.len()) && !names.contains(&"") => {
+                    probes.extend(names.iter().map(|n| Probe::Voltage(n.to_string())));
+                    return Ok(());
+                }
+                "i" if args.len() == 1 && !names[0].is_empty() => {
+                    probes.push(Probe::Current(names[0].to_string()));
+                    return Ok(());
+                }
+                _ if is_probe(name) => return Err(format!("Unsupported probe {}()", name)),
+                "ddt" if args.len() == 1 => Kind::Derivative,
+                "idt" | "sdt" if matches!(args.len(), 1 | 2) => {
+                    Kind::Integral(args.get(1).map(constant).transpose()?.unwrap_or(0.0))
+                }
+                "delay" if args.len() == 2 => {
+                    let delay = constant(&args[1])?;
+                    if delay.is_nan() || delay <= 0.0 {
+                        return Err("The delay must be positive".to_string());
+                    }
+                    Kind::Delay(delay)
+                }
+                "ddt" | "idt" | "sdt" | "delay" => {
+                    return Err(format!("Wrong number of arguments to {}()", name))
+                }
+                _ => {
+                    for arg in args {
+                        collect(arg, probes, operators)?;
+                    }
+                    return Ok(());
+                }
+            };
+            operators.push(Operator::new(kind));
+            collect(&args[0], probes, operators)
+        }
+    }
+}
+
+/// The value of an expression that reads nothing, such as a delay.
+fn constant(expr: &Expr) -> Result<f64, String> {
+    struct Constants;
+    impl Scope for Constants {
+        fn variable(&self, _name: &str) -> Option<f64> {
+            None
+        }
+    }
+    expr.eval(&Constants)
+        .map_err(|message| format!("Not a constant: {}", message))
+}
+
+/// A dry run with everything zero, which finds unknown names and functions
+/// and wrong numbers of arguments while the line is parsed.
+struct Check {
+    x: bool,
+}
+
+impl Scope for Check {
+    fn variable(&self, name: &str) -> Option<f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "time" | "temp" => Some(0.0),
+            "x" if self.x => Some(0.0),
+            _ => None,
+        }
+    }
+
+    fn function(&self, name: &str, args: &[Expr]) -> Option<Result<f64, String>> {
+        match name.to_ascii_lowercase().as_str() {
+            "v" | "i" => Some(Ok(0.0)),
+            "ddt" | "idt" | "sdt" | "delay" => Some(args[0].eval(self)),
+            _ => None,
+        }
+    }
+}
+
+/// One evaluation of an expression, seeding the inputs from `offset` on.
+struct Evaluation<'a> {
+    solution: &'a [f64],
+    time: f64,
+    temperature: f64,
+    integrator: Option<&'a Integrator>,
+    sensed: &'a HashMap<String, Unknown>,
+    inputs: &'a [usize],
+    /// `x` as the difference of two unknowns.
+    x: Option<[Unknown; 2]>,
+    offset: usize,
+    operators: RefCell<&'a mut Vec<Operator>>,
+    /// Each operator's argument, as met.
+    arguments: RefCell<Vec<Number>>,
+    /// The next operator to be met.
+    next: Cell<usize>,
+}
+
+impl Evaluation<'_> {
+    /// Input `index` at `value`, a variable if it is among those seeded.
+    fn seed(&self, value: f64, index: usize) -> Number {
+        match index.checked_sub(self.offset) {
+            Some(slot) if slot < WIDTH => Dual::variable(value, slot),
+            _ => Dual::constant(value),
+        }
+    }
+
+    fn unknown(&self, unknown: Unknown) -> Number {
+        let Some(unknown) = unknown else {
+            return Dual::constant(0.0);
+        };
+        let index = self.inputs.iter().position(|i| *i == unknown);
+        self.seed(self.solution[unknown], index.unwrap_or(usize::MAX))
+    }
+
+    fn probe(&self, probe: Probe) -> Number {
+        self.unknown(self.sensed.get(&probe.key()).copied().flatten())
+    }
+
+    fn operator(&self, args: &[Expr]) -> Result<Number, String> {
+        let index = self.next.get();
+        self.next.set(index + 1);
+        let argument = args[0].eval(self)?;
+        let output =
+            self.operators.borrow_mut()[index].apply(argument.value, self.time, self.integrator);
+        self.arguments.borrow_mut()[index] = argument;
+        Ok(self.seed(output, self.inputs.len() + index))
+    }
+}
+
+impl Scope<Number> for Evaluation<'_> {
+    fn variable(&self, name: &str) -> Option<Number> {
+        match name.to_ascii_lowercase().as_str() {
+            "time" => Some(Dual::constant(self.time)),
+            "temp" => Some(Dual::constant(self.temperature)),
+            "x" => self.x.map(|[p, n]| self.unknown(p) - self.unknown(n)),
+            _ => None,
+        }
+    }
+
+    fn function(&self, name: &str, args: &[Expr]) -> Option<Result<Number, String>> {
+        let name_of = |arg: &Expr| match arg {
+            Expr::Variable(name) => name.clone(),
+            _ => String::new(),
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "v" => {
+                let voltages = args.iter().map(|a| self.probe(Probe::Voltage(name_of(a))));
+                voltages.reduce(|a, b| a - b).map(Ok)
+            }
+            "i" => Some(Ok(self.probe(Probe::Current(name_of(&args[0]))))),
+            "ddt" | "idt" | "sdt" | "delay" => Some(self.operator(args)),
+            _ => None,
+        }
+    }
+}
+
+impl Device for Behavioural {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        if self.voltage {
+            self.branch = Some(unknowns.branch(&self.name));
+        }
+        let mut read = Vec::new();
+        for probe in &self.probes {
+            let unknown = match probe {
+                Probe::Voltage(node) => unknowns.node(&Node::new(node.as_str())),
+                Probe::Current(element) => Some(unknowns.branch(element)),
+            };
+            self.sensed.insert(probe.key(), unknown);
+            read.push(unknown);
+        }
+        match self.x {
+            Some(Binding::Across) => read.extend(self.unknowns),
+            Some(Binding::Through) => read.push(self.branch),
+            None => {}
+        }
+        for unknown in read.into_iter().flatten() {
+            if !self.inputs.contains(&unknown) {
+                self.inputs.push(unknown);
+            }
+        }
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p, n] = self.unknowns;
+        let br = self.branch;
+        if self.voltage {
+            self.entries = [
+                entry(pattern, p, br),
+                entry(pattern, n, br),
+                entry(pattern, br, p),
+                entry(pattern, br, n),
+            ];
+        }
+        self.input_entries = self
+            .inputs
+            .iter()
+            .map(|k| {
+                if self.voltage {
+                    [entry(pattern, br, Some(*k)), None]
+                } else {
+                    [entry(pattern, p, Some(*k)), entry(pattern, n, Some(*k))]
+                }
+            })
+            .collect();
+    }
+
+    fn temperature(&mut self, temperature: f64, _nominal: f64) {
+        self.temperature = temperature;
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let (value, jacobian) =
+            match self.linearise(context.solution, context.time, context.integrator) {
+                Ok(linearisation) => linearisation,
+                Err(message) => {
+                    context.fail(&self.name, message);
+                    return;
+                }
+            };
+        let linear: f64 = self
+            .inputs
+            .iter()
+            .zip(&jacobian)
+            .map(|(k, g)| g * context.solution[*k])
+            .sum();
+        self.stamp(matrix, &jacobian, 1.0);
+        match self.branch {
+            Some(br) if self.voltage => rhs[br] += value - linear,
+            _ => current(rhs, self.unknowns[0], self.unknowns[1], value - linear),
+        }
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let transfer: Vec<Complex> = self.operators.iter().map(|o| o.transfer(omega)).collect();
+        let Linearisation { gradient, rows } = &self.linearisation;
+        if gradient.len() != self.inputs.len() + self.operators.len() {
+            // Never loaded.
+            return;
+        }
+        let jacobian = chain(gradient, rows, &transfer, self.inputs.len());
+        self.stamp(matrix, &jacobian, Complex::from(1.0));
+    }
+
+    fn accept(&mut self, _solution: &[f64], time: f64) {
+        for operator in &mut self.operators {
+            operator.accept(time);
+        }
+    }
+
+    /// The truncation error of every `ddt`'s argument, and no step longer
+    /// than a `delay`.
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.operators
+            .iter()
+            .map(|operator| match operator.kind {
+                Kind::Derivative => {
+                    operator
+                        .state
+                        .allowed_step(timestep, integrator, h, Quantity::Charge)
+                }
+                Kind::Delay(delay) => delay,
+                Kind::Integral(_) => f64::INFINITY,
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        self.operators
+            .iter()
+            .flat_map(|operator| operator.corners.iter().copied())
+            .filter(|t| *t > from && *t <= to)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::newton::{Newton, System};
+    use crate::analysis::options::Options;
+    use crate::devices::generic::Circuit;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+
+    fn parse(text: &str) -> Result<Behavioural, String> {
+        let (name, rest) = text.split_once(' ').unwrap();
+        let line = ElementLine::new(&Component::new(name, Vec::new(), rest));
+        let components = [Component::new("V1", Vec::new(), "a 0 1")];
+        match name.chars().next() {
+            Some('B') => Behavioural::source(&line, &components),
+            Some('R') => Behavioural::resistor(&line, &components),
+            Some('C') => Behavioural::capacitor(&line, &components),
+            _ => Behavioural::value(&line, &components),
+        }
+    }
+
+    #[test]
+    fn test_sources() {
+        let (circuit, solution) = operating_point(&[
+            "V1 a 0 3",
+            "B1 b 0 V=2*V(a)**2 + 1",
+            "B2 0 c I=V(a, b)*1m",
+            "R1 c 0 1k",
+            "E1 d 0 VALUE={I(V1)*1k + V(b)}",
+            "R2 d 0 1k",
+            "G1 0 e value = {I(E1)}",
+            "R3 e 0 1",
+            "B3 f 0 V=I(B1) + time + temp",
+        ]);
+        let v = |node: &str| solution[circuit.unknowns().find(node).unwrap()];
+        assert!((v("V(b)") - 19.0).abs() < 1e-12);
+        assert!((v("V(c)") + 16.0).abs() < 1e-12);
+        assert!((v("V(d)") - 19.0).abs() < 1e-12);
+        assert!((v("V(e)") + 19e-3).abs() < 1e-12);
+        assert!((v("V(f)") - 27.0).abs() < 1e-12);
+
+        // A transfer curve from a table, solved through its slope.
+        let (circuit, solution) = operating_point(&[
+            "V1 in 0 0.25",
+            "B1 a 0 V=table(V(in), 1, 1, 0, 0, 0.5, 2)",
+            "I1 0 b 1m",
+            "B2 b 0 I=table(V(b), 0, 0, 1, 2m)",
+        ]);
+        let v = |node: &str| solution[circuit.unknowns().find(node).unwrap()];
+        assert!((v("V(a)") - 1.0).abs() < 1e-12);
+        assert!((v("V(b)") - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_nonlinear() {
+        // A junction written as a B source, solved by Newton iteration.
+        let (circuit, solution) =
+            operating_point(&["I1 0 d 1m", "B1 d 0 I=1e-14*(exp(V(d)/0.025) - 1)"]);
+        let vd = solution[circuit.unknowns().find("V(d)").unwrap()];
+        assert!((vd - 0.025 * (1e-3f64 / 1e-14 + 1.0).ln()).abs() < 1e-9);
+
+        // R = 1k (1 + V(a)) from 2 V through 1k settles at √2.
+        let (circuit, solution) =
+            operating_point(&["V1 in 0 2", "R0 in a 1k", "R1 a 0 R=1k*(1 + V(a))"]);
+        let va = solution[circuit.unknowns().find("V(a)").unwrap()];
+        assert!((va - 2f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_many_inputs() {
+        // More inputs than one evaluation seeds.
+        let mut lines: Vec<String> = (1..=10).map(|k| format!("V{} n{} 0 {}", k, k, k)).collect();
+        let sum = (1..=10)
+            .map(|k| format!("{}*V(n{})**2", k, k))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        lines.push(format!("B1 out 0 V={}", sum));
+        lines.push("B2 0 o2 I=V(out)*1m".to_string());
+        lines.push("R1 o2 0 1k".to_string());
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (circuit, solution) = operating_point(&lines);
+        let expected: f64 = (1..=10).map(|k| (k * k * k) as f64).sum();
+        let out = solution[circuit.unknowns().find("V(o2)").unwrap()];
+        assert!((out - expected).abs() < 1e-9);
+        let source = circuit.device("B1").unwrap();
+        assert_eq!(source.name(), "B1");
+    }
+
+    #[test]
+    fn test_charge_and_flux() {
+        // A charge 1u*x is a 1 uF capacitor: an RC of 1 ms.
+        let mut rc = circuit(&[
+            "V1 in 0 PULSE(0 1 0 1n 1n 1 2)",
+            "R1 in a 1k",
+            "C1 a 0 Q=1u*x",
+        ]);
+        let options = Options::default();
+        let points = transient(&mut rc, ".tran 10u 2m", &options);
+        let a = rc.unknowns().find("V(a)").unwrap();
+        for (t, solution) in points.iter().filter(|(t, _)| *t > 1e-5) {
+            let expected = 1.0 - (-t / 1e-3).exp();
+            assert!(
+                (solution[a] - expected).abs() < 2e-3,
+                "{} {}",
+                t,
+                solution[a]
+            );
+        }
+        let omega = 1e3;
+        let mut rc = circuit(&["V1 in 0 0 AC 1", "R1 in a 1k", "C1 a 0 Q=1u*x + 1n*x**2"]);
+        let response = ac(&mut rc, omega);
+        let expected = Complex::from(1.0) / Complex::new(1.0, omega * 1e-3);
+        let a = rc.unknowns().find("V(a)").unwrap();
+        assert!((response[a] - expected).norm() < 1e-9, "{}", response[a]);
+
+        // A flux 1m*tanh(x) is 1 mH at zero current: an L/R of 1 us.
+        let mut lr = circuit(&["V1 in 0 0 AC 1", "R1 in a 1k", "L1 a 0 Flux=1m*tanh(x)"]);
+        let response = ac(&mut lr, 1e6);
+        let jwl = Complex::new(0.0, 1e3);
+        let expected = jwl / (jwl + Complex::from(1e3));
+        let a = lr.unknowns().find("V(a)").unwrap();
+        assert!((response[a] - expected).norm() < 1e-9);
+        assert!(lr.unknowns().find("I(L1)").is_some());
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut ramp = circuit(&[
+            "V1 a 0 PWL(0 0 1m 1 2m 1)",
+            "B1 d 0 V=ddt(V(a))",
+            "B2 i 0 V=idt(V(a), 0.5)",
+            "B3 l 0 V=delay(V(a), 0.3m)",
+            "B4 n 0 V=idt(ddt(V(a)))",
+        ]);
+        let options = Options::default();
+        let points = transient(&mut ramp, ".tran 10u 2m", &options);
+        let find = |name: &str| ramp.unknowns().find(name).unwrap();
+        let (d, i, l, n) = (find("V(d)"), find("V(i)"), find("V(l)"), find("V(n)"));
+        for (t, solution) in &points {
+            let a = (t / 1e-3).min(1.0);
+            let integral = if *t <= 1e-3 {
+                0.5 + 500.0 * t * t
+            } else {
+                0.5 + 0.5e-3 + (t - 1e-3)
+            };
+            let delayed = ((t - 0.3e-3) / 1e-3).clamp(0.0, 1.0);
+            assert!(
+                (solution[i] - integral).abs() < 1e-6,
+                "{} {}",
+                t,
+                solution[i]
+            );
+            assert!(
+                (solution[l] - delayed).abs() < 1e-9,
+                "{} {}",
+                t,
+                solution[l]
+            );
+            // The step in ddt at each corner costs half a step of trapezoid.
+            assert!((solution[n] - a).abs() < 1e-3, "{} {}", t, solution[n]);
+            if *t > 1e-5 && (t - 1e-3).abs() > 3e-5 {
+                let slope = if *t < 1e-3 { 1e3 } else { 0.0 };
+                assert!((solution[d] - slope).abs() < 1e-3, "{} {}", t, solution[d]);
+            }
+        }
+        // The corner of the ramp comes out of the delay as a breakpoint.
+        assert!(points.iter().any(|(t, _)| (t - 1.3e-3).abs() < 1e-12));
+
+        // In AC they are jω, 1/jω and a phase.
+        let omega = 2e3;
+        let mut filter = circuit(&[
+            "V1 a 0 0 AC 1",
+            "B1 d 0 V=ddt(V(a))",
+            "B2 i 0 V=idt(V(a))",
+            "B3 l 0 V=delay(V(a), 0.3m)",
+            "G1 0 g value={ddt(2*V(a) + V(g))}",
+            "R1 g 0 1k",
+        ]);
+        let response = ac(&mut filter, omega);
+        let find = |name: &str| filter.unknowns().find(name).unwrap();
+        let jw = Complex::new(0.0, omega);
+        assert!((response[find("V(d)")] - jw).norm() < 1e-9);
+        assert!((response[find("V(i)")] - Complex::from(1.0) / jw).norm() < 1e-12);
+        let phase = Complex::from_polar(1.0, -omega * 0.3e-3);
+        assert!((response[find("V(l)")] - phase).norm() < 1e-12);
+        // g = 1k jω (2 + g), a loop through the operator.
+        let gain = jw.scale(2e3) / (Complex::from(1.0) - jw.scale(1e3));
+        assert!((response[find("V(g)")] - gain).norm() < 1e-9 * gain.norm());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("B1 a 0 V=2*V(a)").is_ok());
+        assert_eq!(parse("B1 a 0 2").unwrap_err(), "Neither V= nor I= given");
+        assert_eq!(
+            parse("B1 a 0 V=foo(1)").unwrap_err(),
+            "Unknown function: foo"
+        );
+        assert_eq!(parse("B1 a 0 V=x").unwrap_err(), "Unknown name: x");
+        assert!(parse("C1 a 0 Q=1u*x").is_ok());
+        assert_eq!(parse("B1 a 0 V=I(R1)").unwrap_err(), "No voltage source R1");
+        assert_eq!(
+            parse("B1 a 0 V=Ic(Q1)").unwrap_err(),
+            "Unsupported probe Ic()"
+        );
+        assert_eq!(
+            parse("B1 a 0 V=delay(V(a), time)").unwrap_err(),
+            "Not a constant: Unknown name: time"
+        );
+        assert_eq!(
+            parse("B1 a 0 V=delay(V(a), -1)").unwrap_err(),
+            "The delay must be positive"
+        );
+        assert!(parse("E1 a 0 VALUE={V(a)+}").is_err());
+        assert!(parse("R1 a 0 R=1k*(1+V(a))").is_ok());
+
+        // An expression that fails as it is evaluated ends the iteration
+        // with its message.
+        let mut source = parse("B1 a 0 V=1").unwrap();
+        source.expression = Expr::Call("sqrt".to_string(), Vec::new());
+        let options = Options::default();
+        let mut circuit = Circuit::new(vec![Box::new(source)], &options);
+        let mut newton = Newton::new(&mut circuit, &options);
+        circuit.set_dc();
+        let mut solution = vec![0.0; circuit.size()];
+        let error = newton
+            .solve(&mut circuit, &mut solution, options.itl1, &options)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "B1: sqrt() takes 1 argument, found 0");
+    }
+}
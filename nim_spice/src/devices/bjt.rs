@@ -0,0 +1,1168 @@
+//! The Gummel-Poon bipolar junction transistor.
+//!
+//! The transport current `IS (exp(vbe/NF vt) - exp(vbc/NR vt))` is divided
+//! by the normalised base charge `qb`, which carries the Early effect of
+//! `VAF` and `VAR` and the high injection knees `IKF` and `IKR`. Leakage
+//! through `ISE` and `ISC` adds to the base current, and the base
+//! resistance falls from `RB` towards `RBM` as the base current crowds
+//! towards the emitter, with `IRB` the current halfway. Junction
+//! capacitances `CJE`, `CJC` (split by `XCJC` across `RB`) and the
+//! substrate's `CJS` are joined by the transit time charges of `TF`, with
+//! its bias dependence `XTF`, `VTF` and `ITF`, and `TR`; `PTF` delays the
+//! transport current by a second order excess phase. Kull's epitaxial
+//! collector of `RCO`, `GAMMA`, `VO` and `QCO` models quasi-saturation.
+//! Temperature follows ngspice's `bjttemp.c`, and the load `bjtload.c`.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::diode::{depletion, junction_temperature};
+use crate::devices::generic::{
+    current, flicker_noise, shot_noise, thermal_noise, voltage, Conductance, Device, LoadContext,
+    NoiseSource, State, Unknown, Unknowns, BOLTZMANN, CHARGE, KELVIN,
+};
+use crate::devices::limiting::{critical_voltage, pnjlim};
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// Parameters of an `NPN` or `PNP` model. Voltages and currents given as
+/// zero, such as `VAF` and `IKF`, are infinite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BjtModel {
+    /// 1 for NPN, -1 for PNP.
+    pub polarity: f64,
+    pub is: f64,
+    pub bf: f64,
+    pub nf: f64,
+    pub vaf: f64,
+    pub ikf: f64,
+    pub ise: f64,
+    pub ne: f64,
+    pub br: f64,
+    pub nr: f64,
+    pub var: f64,
+    pub ikr: f64,
+    pub isc: f64,
+    pub nc: f64,
+    pub rb: f64,
+    pub irb: f64,
+    /// Base resistance at high current, `RB` unless given.
+    pub rbm: f64,
+    pub re: f64,
+    pub rc: f64,
+    pub cje: f64,
+    pub vje: f64,
+    pub mje: f64,
+    pub tf: f64,
+    pub xtf: f64,
+    pub vtf: f64,
+    pub itf: f64,
+    /// Excess phase at `1/(2π TF)`, in degrees.
+    pub ptf: f64,
+    pub cjc: f64,
+    pub vjc: f64,
+    pub mjc: f64,
+    /// Fraction of `CJC` on the inner side of `RB`.
+    pub xcjc: f64,
+    pub tr: f64,
+    pub cjs: f64,
+    pub vjs: f64,
+    pub mjs: f64,
+    pub xtb: f64,
+    pub eg: f64,
+    pub xti: f64,
+    pub kf: f64,
+    pub af: f64,
+    pub fc: f64,
+    /// Resistance of the epitaxial collector, none when zero.
+    pub rco: f64,
+    pub vo: f64,
+    pub gamma: f64,
+    pub qco: f64,
+    pub tre1: f64,
+    pub tre2: f64,
+    pub trb1: f64,
+    pub trb2: f64,
+    pub trm1: f64,
+    pub trm2: f64,
+    pub trc1: f64,
+    pub trc2: f64,
+    /// Temperature the parameters were measured at in °C, `.options tnom`
+    /// unless given.
+    pub tnom: Option<f64>,
+}
+
+impl Default for BjtModel {
+    fn default() -> Self {
+        BjtModel {
+            polarity: 1.0,
+            is: 1e-16,
+            bf: 100.0,
+            nf: 1.0,
+            vaf: 0.0,
+            ikf: 0.0,
+            ise: 0.0,
+            ne: 1.5,
+            br: 1.0,
+            nr: 1.0,
+            var: 0.0,
+            ikr: 0.0,
+            isc: 0.0,
+            nc: 2.0,
+            rb: 0.0,
+            irb: 0.0,
+            rbm: 0.0,
+            re: 0.0,
+            rc: 0.0,
+            cje: 0.0,
+            vje: 0.75,
+            mje: 0.33,
+            tf: 0.0,
+            xtf: 0.0,
+            vtf: 0.0,
+            itf: 0.0,
+            ptf: 0.0,
+            cjc: 0.0,
+            vjc: 0.75,
+            mjc: 0.33,
+            xcjc: 1.0,
+            tr: 0.0,
+            cjs: 0.0,
+            vjs: 0.75,
+            mjs: 0.0,
+            xtb: 0.0,
+            eg: 1.11,
+            xti: 3.0,
+            kf: 0.0,
+            af: 1.0,
+            fc: 0.5,
+            rco: 0.0,
+            vo: 10.0,
+            gamma: 1e-11,
+            qco: 0.0,
+            tre1: 0.0,
+            tre2: 0.0,
+            trb1: 0.0,
+            trb2: 0.0,
+            trm1: 0.0,
+            trm2: 0.0,
+            trc1: 0.0,
+            trc2: 0.0,
+            tnom: None,
+        }
+    }
+}
+
+impl BjtModel {
+    /// The parameters of an `NPN` or `PNP` model card, with ngspice's and
+    /// LTspice's alternative names.
+    pub fn new(model: &Model) -> Result<Self, String> {
+        model.unknown_parameters(&[
+            "is", "bf", "nf", "vaf", "va", "ikf", "ik", "ise", "ne", "br", "nr", "var", "vb",
+            "ikr", "isc", "nc", "rb", "irb", "rbm", "re", "rc", "cje", "vje", "pe", "mje", "me",
+            "tf", "xtf", "vtf", "itf", "ptf", "cjc", "vjc", "pc", "mjc", "mc", "xcjc", "tr", "cjs",
+            "ccs", "vjs", "ps", "mjs", "ms", "xtb", "eg", "xti", "kf", "af", "fc", "rco", "vo",
+            "gamma", "qco", "tre1", "tre2", "trb1", "trb2", "trm1", "trm2", "trc1", "trc2", "tnom",
+            "tref", "level",
+        ])?;
+        let d = BjtModel::default();
+        let value = |names: &[&str], default: f64| -> Result<f64, String> {
+            Ok(model.alias(names)?.unwrap_or(default))
+        };
+        let rb = value(&["rb"], d.rb)?;
+        let bjt = BjtModel {
+            polarity: if model.kind == "PNP" { -1.0 } else { 1.0 },
+            is: value(&["is"], d.is)?,
+            bf: value(&["bf"], d.bf)?,
+            nf: value(&["nf"], d.nf)?,
+            vaf: value(&["vaf", "va"], d.vaf)?,
+            ikf: value(&["ikf", "ik"], d.ikf)?,
+            ise: value(&["ise"], d.ise)?,
+            ne: value(&["ne"], d.ne)?,
+            br: value(&["br"], d.br)?,
+            nr: value(&["nr"], d.nr)?,
+            var: value(&["var", "vb"], d.var)?,
+            ikr: value(&["ikr"], d.ikr)?,
+            isc: value(&["isc"], d.isc)?,
+            nc: value(&["nc"], d.nc)?,
+            rb,
+            irb: value(&["irb"], d.irb)?,
+            rbm: value(&["rbm"], rb)?,
+            re: value(&["re"], d.re)?,
+            rc: value(&["rc"], d.rc)?,
+            cje: value(&["cje"], d.cje)?,
+            vje: value(&["vje", "pe"], d.vje)?,
+            mje: value(&["mje", "me"], d.mje)?,
+            tf: value(&["tf"], d.tf)?,
+            xtf: value(&["xtf"], d.xtf)?,
+            vtf: value(&["vtf"], d.vtf)?,
+            itf: value(&["itf"], d.itf)?,
+            ptf: value(&["ptf"], d.ptf)?,
+            cjc: value(&["cjc"], d.cjc)?,
+            vjc: value(&["vjc", "pc"], d.vjc)?,
+            mjc: value(&["mjc", "mc"], d.mjc)?,
+            xcjc: value(&["xcjc"], d.xcjc)?,
+            tr: value(&["tr"], d.tr)?,
+            cjs: value(&["cjs", "ccs"], d.cjs)?,
+            vjs: value(&["vjs", "ps"], d.vjs)?,
+            mjs: value(&["mjs", "ms"], d.mjs)?,
+            xtb: value(&["xtb"], d.xtb)?,
+            eg: value(&["eg"], d.eg)?,
+            xti: value(&["xti"], d.xti)?,
+            kf: value(&["kf"], d.kf)?,
+            af: value(&["af"], d.af)?,
+            fc: value(&["fc"], d.fc)?,
+            rco: value(&["rco"], d.rco)?,
+            vo: value(&["vo"], d.vo)?,
+            gamma: value(&["gamma"], d.gamma)?,
+            qco: value(&["qco"], d.qco)?,
+            tre1: value(&["tre1"], d.tre1)?,
+            tre2: value(&["tre2"], d.tre2)?,
+            trb1: value(&["trb1"], d.trb1)?,
+            trb2: value(&["trb2"], d.trb2)?,
+            trm1: value(&["trm1"], d.trm1)?,
+            trm2: value(&["trm2"], d.trm2)?,
+            trc1: value(&["trc1"], d.trc1)?,
+            trc2: value(&["trc2"], d.trc2)?,
+            tnom: model.alias(&["tnom", "tref"])?,
+        };
+        if bjt.is <= 0.0 || bjt.nf <= 0.0 || bjt.nr <= 0.0 || bjt.ne <= 0.0 || bjt.nc <= 0.0 {
+            return Err(format!(
+                "Model {}: IS, NF, NR, NE and NC must be positive",
+                model.name
+            ));
+        }
+        if bjt.bf <= 0.0 || bjt.br <= 0.0 {
+            return Err(format!("Model {}: BF and BR must be positive", model.name));
+        }
+        if bjt.vje <= 0.0 || bjt.vjc <= 0.0 || bjt.vjs <= 0.0 {
+            return Err(format!(
+                "Model {}: VJE, VJC and VJS must be positive",
+                model.name
+            ));
+        }
+        if bjt.mje >= 1.0 || bjt.mjc >= 1.0 || bjt.mjs >= 1.0 || bjt.fc >= 1.0 {
+            return Err(format!(
+                "Model {}: MJE, MJC, MJS and FC must be below 1",
+                model.name
+            ));
+        }
+        if bjt.rbm > rb {
+            return Err(format!("Model {}: RBM must not exceed RB", model.name));
+        }
+        Ok(bjt)
+    }
+}
+
+/// `1/x`, zero for an infinite parameter given as zero.
+fn inverse(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        1.0 / x
+    }
+}
+
+/// The model's parameters at the instance temperature, scaled by its area
+/// and multiplier.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scaled {
+    temperature: f64,
+    vt: f64,
+    is: f64,
+    bf: f64,
+    br: f64,
+    ise: f64,
+    isc: f64,
+    /// Inverses of `IKF` and `IKR`.
+    oik: f64,
+    oikr: f64,
+    itf: f64,
+    irb: f64,
+    vcrit: f64,
+    /// `RBM`, and the part `RB - RBM` the base current modulates.
+    rbpr: f64,
+    rbpi: f64,
+    /// Conductances of `RC` and `RE`, zero without them.
+    gc: f64,
+    ge: f64,
+    /// Junction potentials and zero bias capacitances of the emitter,
+    /// collector and substrate junctions.
+    be: (f64, f64),
+    bc: (f64, f64),
+    cs: (f64, f64),
+    rco: f64,
+    qco: f64,
+}
+
+/// The currents, conductances and charges at a bias point, as for an NPN,
+/// and the quantities kept for the convergence test, AC and noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bias {
+    vbe: f64,
+    vbc: f64,
+    vbx: f64,
+    vcs: f64,
+    /// Base to the collector outside the epitaxial region.
+    vbcx: f64,
+    /// Collector and base currents, gmin included.
+    cc: f64,
+    cb: f64,
+    gpi: f64,
+    gmu: f64,
+    gm: f64,
+    go: f64,
+    /// Conductance of the base resistance.
+    gx: f64,
+    /// `cbe/qb`, where the excess phase history starts.
+    transport: f64,
+    /// The delayed transport current to keep for the next step.
+    phase: f64,
+    qbe: f64,
+    qbc: f64,
+    qbx: f64,
+    qcs: f64,
+    qbcx: f64,
+    capbe: f64,
+    capbc: f64,
+    capbx: f64,
+    capcs: f64,
+    capbcx: f64,
+    /// Change of the emitter charge with `vbc`, through `qb` and `VTF`.
+    geqcb: f64,
+    /// Current through the epitaxial collector and its derivatives with
+    /// `vbc` and `vbcx`.
+    iepi: f64,
+    gepi: f64,
+    gepx: f64,
+}
+
+/// The step and previous step of a transient, with the accepted history of
+/// the delayed transport current, `None` on the first step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Excess {
+    delta: f64,
+    previous: f64,
+    history: Option<[f64; 2]>,
+}
+
+/// The unknowns of the terminals and of the inner nodes, the same as the
+/// terminals without the resistances.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Terminals {
+    collector: Unknown,
+    base: Unknown,
+    emitter: Unknown,
+    substrate: Unknown,
+    /// Inside `RC`, `RB` and `RE`.
+    collector_prime: Unknown,
+    base_prime: Unknown,
+    emitter_prime: Unknown,
+    /// Inside the epitaxial collector.
+    collector_inner: Unknown,
+}
+
+/// The matrix entries of each branch of the equivalent circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Stamps {
+    collector: Conductance,
+    base: Conductance,
+    emitter: Conductance,
+    gpi: Conductance,
+    gmu: Conductance,
+    gm: Conductance,
+    go: Conductance,
+    geqcb: Conductance,
+    bx: Conductance,
+    cs: Conductance,
+    epi: Conductance,
+    epx: Conductance,
+    bcx: Conductance,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bjt {
+    name: String,
+    /// Collector, base, emitter and substrate.
+    nodes: [Node; 4],
+    model: BjtModel,
+    area: f64,
+    multiplier: f64,
+    /// `vbe` and `vce` to start from with `uic`.
+    initial: Option<(f64, f64)>,
+    temp: Option<f64>,
+    dtemp: f64,
+    scaled: Scaled,
+    terminals: Terminals,
+    stamps: Stamps,
+    /// Charges of the emitter, inner and outer collector, substrate and
+    /// epitaxial junctions.
+    charges: [State; 5],
+    bias: Bias,
+    /// The delayed transport current at the point being solved and the two
+    /// accepted before, once a transient has started.
+    excess: [f64; 3],
+    excess_started: bool,
+    /// The last accepted time and the step that reached it.
+    accepted: f64,
+    last_step: Option<f64>,
+}
+
+impl Bjt {
+    /// `Qname c b e [s] model [area] [off] [area=] [m=] [ic=vbe,vce]
+    /// [temp=] [dtemp=]`, the substrate grounded unless given.
+    pub fn parse(line: &ElementLine, models: &[Model]) -> Result<Self, String> {
+        line.unknown_parameters(&["area", "m", "ic", "temp", "dtemp"])?;
+        let [collector, base, emitter] = line.nodes()?;
+        let is_model = |word: &String| models.iter().any(|m| m.name.eq_ignore_ascii_case(word));
+        let (substrate, first) = match line.words.get(3) {
+            Some(word) if !is_model(word) && line.words.len() > 4 => (Node::new(word.as_str()), 4),
+            _ => (Node::new("0"), 3),
+        };
+        let model = line.words.get(first).ok_or("No model given")?;
+        let model = BjtModel::new(find_model(models, model, &["NPN", "PNP"])?)?;
+        let words: Vec<&String> = line.words[first + 1..]
+            .iter()
+            .filter(|w| !w.eq_ignore_ascii_case("off"))
+            .collect();
+        let area = match (line.number("area")?, words.first()) {
+            (Some(area), _) => area,
+            (None, None) => 1.0,
+            (None, Some(_)) => line.value(first + 1, "area")?,
+        };
+        let multiplier = line.number("m")?.unwrap_or(1.0);
+        if area <= 0.0 || multiplier <= 0.0 {
+            return Err("The area and multiplier must be positive".to_string());
+        }
+        let initial = match line.numbers("ic")?.as_slice() {
+            [] => None,
+            [vbe] => Some((*vbe, 0.0)),
+            [vbe, vce] => Some((*vbe, *vce)),
+            _ => return Err("Expected ic=vbe,vce".to_string()),
+        };
+        Ok(Bjt {
+            name: line.name.clone(),
+            nodes: [collector, base, emitter, substrate],
+            model,
+            area,
+            multiplier,
+            initial,
+            temp: line.number("temp")?,
+            dtemp: line.number("dtemp")?.unwrap_or(0.0),
+            scaled: Scaled::default(),
+            terminals: Terminals::default(),
+            stamps: Stamps::default(),
+            charges: Default::default(),
+            bias: Bias::default(),
+            excess: [0.0; 3],
+            excess_started: false,
+            accepted: 0.0,
+            last_step: None,
+        })
+    }
+
+    /// Collector and base currents into the device at the last load.
+    pub fn currents(&self) -> (f64, f64) {
+        let p = self.model.polarity;
+        (p * self.bias.cc, p * self.bias.cb)
+    }
+
+    /// The junction voltages `vbe`, `vbc`, `vbx`, `vcs` and `vbcx` of
+    /// `solution`, as for an NPN.
+    fn voltages(&self, solution: &[f64]) -> [f64; 5] {
+        let t = &self.terminals;
+        let v = |node: Unknown| voltage(solution, node);
+        let p = self.model.polarity;
+        [
+            p * (v(t.base_prime) - v(t.emitter_prime)),
+            p * (v(t.base_prime) - v(t.collector_inner)),
+            p * (v(t.base) - v(t.collector_prime)),
+            p * (v(t.substrate) - v(t.collector_prime)),
+            p * (v(t.base_prime) - v(t.collector_prime)),
+        ]
+    }
+
+    /// Evaluate the equations at the junction voltages `[vbe, vbc, vbx,
+    /// vcs, vbcx]`, the transport current delayed by `excess` in a
+    /// transient.
+    fn evaluate(&self, voltages: [f64; 5], gmin: f64, excess: Option<Excess>) -> Bias {
+        let m = &self.model;
+        let s = &self.scaled;
+        let [vbe, vbc, vbx, vcs, vbcx] = voltages;
+        let vt = s.vt;
+
+        // The junctions and their leakage.
+        let junction = |v: f64, n: f64, is: f64, leak: f64, nl: f64| {
+            let vtn = n * vt;
+            if v > -5.0 * vtn {
+                let ev = (v / vtn).exp();
+                let (cl, gl) = if leak == 0.0 {
+                    (0.0, 0.0)
+                } else {
+                    let evl = (v / (nl * vt)).exp();
+                    (leak * (evl - 1.0), leak * evl / (nl * vt))
+                };
+                (is * (ev - 1.0) + gmin * v, is * ev / vtn + gmin, cl, gl)
+            } else {
+                let g = -is / v + gmin;
+                (g * v, g, -leak, -leak / v)
+            }
+        };
+        let (cbe, gbe, cben, gben) = junction(vbe, m.nf, s.is, s.ise, m.ne);
+        let (cbc, gbc, cbcn, gbcn) = junction(vbc, m.nr, s.is, s.isc, m.nc);
+
+        // The normalised base charge.
+        let (ovaf, ovar) = (inverse(m.vaf), inverse(m.var));
+        let q1 = 1.0 / (1.0 - ovaf * vbc - ovar * vbe);
+        let (qb, dqbdve, dqbdvc) = if s.oik == 0.0 && s.oikr == 0.0 {
+            (q1, q1 * q1 * ovar, q1 * q1 * ovaf)
+        } else {
+            let q2 = s.oik * cbe + s.oikr * cbc;
+            let arg = (1.0 + 4.0 * q2).max(0.0);
+            let sqarg = if arg == 0.0 { 1.0 } else { arg.sqrt() };
+            let qb = q1 * (1.0 + sqarg) / 2.0;
+            (
+                qb,
+                q1 * (qb * ovar + s.oik * gbe / sqarg),
+                q1 * (qb * ovaf + s.oikr * gbc / sqarg),
+            )
+        };
+
+        // The transport current, delayed by the excess phase.
+        let td = m.ptf.to_radians() * m.tf;
+        let (mut cc, mut cex, mut gex, mut phase) = (0.0, cbe, gbe, 0.0);
+        if let Some(excess) = excess.filter(|_| td != 0.0) {
+            let arg1 = excess.delta / td;
+            let arg2 = 3.0 * arg1;
+            let arg1 = arg2 * arg1;
+            let denom = 1.0 + arg1 + arg2;
+            let arg3 = arg1 / denom;
+            let [h1, h2] = excess.history.unwrap_or([cbe / qb; 2]);
+            let ratio = excess.delta / excess.previous;
+            cc = (h1 * (1.0 + ratio + arg2) - h2 * ratio) / denom;
+            cex = cbe * arg3;
+            gex = gbe * arg3;
+            phase = cc + cex / qb;
+        }
+        cc += (cex - cbc) / qb - cbc / s.br - cbcn;
+        let cb = cbe / s.bf + cben + cbc / s.br + cbcn;
+
+        // The base resistance, crowded by the base current.
+        let mut rx = s.rbpr + s.rbpi / qb;
+        if s.irb != 0.0 {
+            let arg1 = (cb / s.irb).max(1e-9);
+            let arg2 = (-1.0 + (1.0 + 14.59025 * arg1).sqrt()) / 2.4317 / arg1.sqrt();
+            let tan = arg2.tan();
+            rx = s.rbpr + 3.0 * s.rbpi * (tan - arg2) / arg2 / tan / tan;
+        }
+        let gx = inverse(rx);
+        let gpi = gbe / s.bf + gben;
+        let gmu = gbc / s.br + gbcn;
+        let go = (gbc + (cex - cbc) * dqbdvc / qb) / qb;
+        let gm = (gex - (cex - cbc) * dqbdve / qb) / qb - go;
+
+        // The emitter charge, its transit time modulated by the bias.
+        let (mut cbe_tf, mut gbe_tf, mut geqcb) = (cbe, gbe, 0.0);
+        if m.tf != 0.0 && vbe > 0.0 {
+            let (mut argtf, mut arg2, mut arg3) = (0.0, 0.0, 0.0);
+            if m.xtf != 0.0 {
+                let ovtf = inverse(1.44 * m.vtf);
+                argtf = m.xtf * (vbc * ovtf).exp();
+                arg2 = argtf;
+                if s.itf != 0.0 {
+                    let temp = cbe / (cbe + s.itf);
+                    argtf *= temp * temp;
+                    arg2 = argtf * (3.0 - temp - temp);
+                }
+                arg3 = cbe * argtf * ovtf;
+            }
+            cbe_tf = cbe * (1.0 + argtf) / qb;
+            gbe_tf = (gbe * (1.0 + arg2) - cbe_tf * dqbdve) / qb;
+            geqcb = m.tf * (arg3 - cbe_tf * dqbdvc) / qb;
+        }
+        let (qbe, capbe) = depletion(vbe, s.be.1, s.be.0, m.mje, m.fc);
+        let (qbc, capbc) = depletion(vbc, s.bc.1 * m.xcjc, s.bc.0, m.mjc, m.fc);
+        let (qbx, capbx) = depletion(vbx, s.bc.1 * (1.0 - m.xcjc), s.bc.0, m.mjc, m.fc);
+        let (qcs, capcs) = substrate(vcs, s.cs.1, s.cs.0, m.mjs);
+
+        // The epitaxial collector, and the charge stored in it.
+        let (mut iepi, mut gepi, mut gepx, mut qbci, mut capbci, mut qbcx, mut capbcx) =
+            (0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        if s.rco > 0.0 {
+            let k = |v: f64| {
+                let k = (1.0 + m.gamma * (v / vt).exp()).sqrt();
+                (k, (k * k - 1.0) / (2.0 * vt * k))
+            };
+            let (kbci, dkbci) = k(vbc);
+            let (kbcx, dkbcx) = k(vbcx);
+            let vrci = vbc - vbcx;
+            let iohm = (vrci + vt * (kbci - kbcx - ((kbci + 1.0) / (kbcx + 1.0)).ln())) / s.rco;
+            let (gohm_ci, gohm_cx) = ((1.0 + kbci) / (2.0 * s.rco), -(1.0 + kbcx) / (2.0 * s.rco));
+            let ovo = inverse(m.vo);
+            let denom = 1.0 + vrci.abs() * ovo;
+            iepi = iohm / denom;
+            let dvelocity = iepi * vrci.signum() * ovo;
+            gepi = (gohm_ci - dvelocity) / denom;
+            gepx = (gohm_cx + dvelocity) / denom;
+            qbci = s.qco * kbci;
+            capbci = s.qco * dkbci;
+            qbcx = s.qco * kbcx;
+            capbcx = s.qco * dkbcx;
+        }
+
+        Bias {
+            vbe,
+            vbc,
+            vbx,
+            vcs,
+            vbcx,
+            cc,
+            cb,
+            gpi,
+            gmu,
+            gm,
+            go,
+            gx,
+            transport: cbe / qb,
+            phase,
+            qbe: qbe + m.tf * cbe_tf,
+            qbc: qbc + m.tr * cbc + qbci,
+            qbx,
+            qcs,
+            qbcx,
+            capbe: capbe + m.tf * gbe_tf,
+            capbc: capbc + m.tr * gbc + capbci,
+            capbx,
+            capcs,
+            capbcx,
+            geqcb,
+            iepi,
+            gepi,
+            gepx,
+        }
+    }
+}
+
+/// Charge and capacitance of the substrate junction at `v`, linear in
+/// capacitance when forward biased.
+fn substrate(v: f64, cj: f64, vj: f64, mj: f64) -> (f64, f64) {
+    if v < 0.0 {
+        let arg = 1.0 - v / vj;
+        let sarg = (-mj * arg.ln()).exp();
+        (vj * cj * (1.0 - arg * sarg) / (1.0 - mj), cj * sarg)
+    } else {
+        (
+            v * cj * (1.0 + mj * v / (2.0 * vj)),
+            cj * (1.0 + mj * v / vj),
+        )
+    }
+}
+
+impl Device for Bjt {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let m = &self.model;
+        let collector = unknowns.node(&self.nodes[0]);
+        let base = unknowns.node(&self.nodes[1]);
+        let emitter = unknowns.node(&self.nodes[2]);
+        let substrate = unknowns.node(&self.nodes[3]);
+        let mut inner = |resistance: f64, outer: Unknown, name: &str| {
+            if resistance > 0.0 {
+                Some(unknowns.internal(&self.name, name))
+            } else {
+                outer
+            }
+        };
+        let collector_prime = inner(m.rc, collector, "c");
+        let base_prime = inner(m.rb, base, "b");
+        let emitter_prime = inner(m.re, emitter, "e");
+        let collector_inner = inner(m.rco, collector_prime, "ci");
+        self.terminals = Terminals {
+            collector,
+            base,
+            emitter,
+            substrate,
+            collector_prime,
+            base_prime,
+            emitter_prime,
+            collector_inner,
+        };
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let t = self.terminals;
+        let (c, b, e) = (t.collector_prime, t.base_prime, t.emitter_prime);
+        let ci = t.collector_inner;
+        let mut stamps = Stamps {
+            gpi: Conductance::new(pattern, b, e),
+            gmu: Conductance::new(pattern, b, ci),
+            gm: Conductance::controlled(pattern, ci, e, b, e),
+            go: Conductance::new(pattern, ci, e),
+            geqcb: Conductance::controlled(pattern, b, e, b, ci),
+            bx: Conductance::new(pattern, t.base, c),
+            cs: Conductance::new(pattern, t.substrate, c),
+            ..Stamps::default()
+        };
+        if c != t.collector {
+            stamps.collector = Conductance::new(pattern, t.collector, c);
+        }
+        if b != t.base {
+            stamps.base = Conductance::new(pattern, t.base, b);
+        }
+        if e != t.emitter {
+            stamps.emitter = Conductance::new(pattern, t.emitter, e);
+        }
+        if ci != c {
+            stamps.epi = Conductance::controlled(pattern, c, ci, b, ci);
+            stamps.epx = Conductance::controlled(pattern, c, ci, b, c);
+            stamps.bcx = Conductance::new(pattern, b, c);
+        }
+        self.stamps = stamps;
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let m = &self.model;
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let t = celsius + KELVIN;
+        let tnom = m.tnom.unwrap_or(nominal) + KELVIN;
+        let vt = BOLTZMANN * t / CHARGE;
+        let ratio = t / tnom;
+        let factlog = (ratio - 1.0) * m.eg / vt + m.xti * ratio.ln();
+        let bfactor = ratio.powf(m.xtb);
+        let scale = self.area * self.multiplier;
+        let is = m.is * factlog.exp() * scale;
+        let dt = t - tnom;
+        let resistance = |r: f64, tc1: f64, tc2: f64| r * (1.0 + tc1 * dt + tc2 * dt * dt) / scale;
+        let rb = resistance(m.rb, m.trb1, m.trb2);
+        let rbm = resistance(m.rbm, m.trm1, m.trm2);
+        let junction =
+            |vj: f64, cj: f64, mj: f64| junction_temperature(vj, cj * scale, mj, t, tnom);
+        self.scaled = Scaled {
+            temperature: celsius,
+            vt,
+            is,
+            bf: m.bf * bfactor,
+            br: m.br * bfactor,
+            ise: m.ise * (factlog / m.ne).exp() / bfactor * scale,
+            isc: m.isc * (factlog / m.nc).exp() / bfactor * scale,
+            oik: inverse(m.ikf * scale),
+            oikr: inverse(m.ikr * scale),
+            itf: m.itf * scale,
+            irb: m.irb * scale,
+            vcrit: critical_voltage(vt, is),
+            rbpr: rbm,
+            rbpi: rb - rbm,
+            gc: inverse(resistance(m.rc, m.trc1, m.trc2)),
+            ge: inverse(resistance(m.re, m.tre1, m.tre2)),
+            be: junction(m.vje, m.cje, m.mje),
+            bc: junction(m.vjc, m.cjc, m.mjc),
+            cs: junction(m.vjs, m.cjs, m.mjs),
+            rco: m.rco / scale,
+            qco: m.qco * scale,
+        };
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let [vbe, vbc, vbx, vcs, vbcx] = self.voltages(context.solution);
+        let (vt, vcrit) = (self.scaled.vt, self.scaled.vcrit);
+        let (vbe, limited_be) = pnjlim(vbe, self.bias.vbe, vt * self.model.nf, vcrit);
+        let (vbc, limited_bc) = pnjlim(vbc, self.bias.vbc, vt * self.model.nr, vcrit);
+        let (vbcx, limited_bcx) = if self.scaled.rco > 0.0 {
+            pnjlim(vbcx, self.bias.vbcx, vt, vcrit)
+        } else {
+            (vbc, false)
+        };
+        if limited_be || limited_bc || limited_bcx {
+            context.limit(&self.name);
+        }
+
+        let excess = context.integrator.map(|_| {
+            let delta = context.time - self.accepted;
+            Excess {
+                delta,
+                previous: self.last_step.unwrap_or(delta),
+                history: self
+                    .excess_started
+                    .then_some([self.excess[1], self.excess[2]]),
+            }
+        });
+        let bias = self.evaluate([vbe, vbc, vbx, vcs, vbcx], context.gmin, excess);
+        self.bias = bias;
+
+        let (mut cc, mut cb) = (bias.cc, bias.cb);
+        let (mut gpi, mut gmu, mut geqcb) = (bias.gpi, bias.gmu, 0.0);
+        let (mut geqbx, mut gccs, mut gbcx) = (0.0, 0.0, 0.0);
+        let (mut cqbx, mut cqcs, mut cqbcx) = (0.0, 0.0, 0.0);
+        let [qbe, qbc, qbx, qcs, qbcx] = &mut self.charges;
+        match context.integrator {
+            Some(integrator) => {
+                if !self.excess_started {
+                    self.excess = [bias.phase, bias.transport, bias.transport];
+                    self.excess_started = true;
+                }
+                self.excess[0] = bias.phase;
+                let companion = qbe.integrate(integrator, bias.qbe, bias.capbe, vbe);
+                gpi += companion.geq;
+                cb += qbe.derivative();
+                geqcb = integrator.ag()[0] * bias.geqcb;
+                let companion = qbc.integrate(integrator, bias.qbc, bias.capbc, vbc);
+                gmu += companion.geq;
+                cb += qbc.derivative();
+                cc -= qbc.derivative();
+                geqbx = qbx.integrate(integrator, bias.qbx, bias.capbx, vbx).geq;
+                cqbx = qbx.derivative();
+                gccs = qcs.integrate(integrator, bias.qcs, bias.capcs, vcs).geq;
+                cqcs = qcs.derivative();
+                gbcx = qbcx.integrate(integrator, bias.qbcx, bias.capbcx, vbcx).geq;
+                cqbcx = qbcx.derivative();
+            }
+            None => {
+                self.excess_started = false;
+                qbe.reset(bias.qbe);
+                qbc.reset(bias.qbc);
+                qbx.reset(bias.qbx);
+                qcs.reset(bias.qcs);
+                qbcx.reset(bias.qbcx);
+            }
+        }
+
+        let (gm, go) = (bias.gm, bias.go);
+        let st = &self.stamps;
+        st.collector.stamp(matrix, self.scaled.gc);
+        st.base.stamp(matrix, bias.gx);
+        st.emitter.stamp(matrix, self.scaled.ge);
+        st.gpi.stamp(matrix, gpi);
+        st.gmu.stamp(matrix, gmu);
+        st.gm.stamp(matrix, gm);
+        st.go.stamp(matrix, go);
+        st.geqcb.stamp(matrix, geqcb);
+        st.bx.stamp(matrix, geqbx);
+        st.cs.stamp(matrix, gccs);
+        st.epi.stamp(matrix, bias.gepi);
+        st.epx.stamp(matrix, bias.gepx);
+        st.bcx.stamp(matrix, gbcx);
+
+        let p = self.model.polarity;
+        let t = self.terminals;
+        let ceqbe = p * (cc + cb - vbe * (gm + go + gpi) + vbc * (go - geqcb));
+        let ceqbc = p * (-cc + vbe * (gm + go) - vbc * (gmu + go));
+        current(rhs, t.base_prime, t.emitter_prime, ceqbe);
+        current(rhs, t.base_prime, t.collector_inner, ceqbc);
+        current(rhs, t.base, t.collector_prime, p * (cqbx - geqbx * vbx));
+        current(rhs, t.substrate, t.collector_prime, p * (cqcs - gccs * vcs));
+        current(
+            rhs,
+            t.base_prime,
+            t.collector_prime,
+            p * (cqbcx - gbcx * vbcx),
+        );
+        let iepi = bias.iepi - bias.gepi * vbc - bias.gepx * vbcx;
+        current(rhs, t.collector_prime, t.collector_inner, p * iepi);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let b = &self.bias;
+        let st = &self.stamps;
+        let td = self.model.ptf.to_radians() * self.model.tf;
+        // The delay turns the transconductance by ω td.
+        let gm = Complex::from_polar(b.gm + b.go, -omega * td) - Complex::from(b.go);
+        st.collector.stamp(matrix, Complex::from(self.scaled.gc));
+        st.base.stamp(matrix, Complex::from(b.gx));
+        st.emitter.stamp(matrix, Complex::from(self.scaled.ge));
+        st.gpi.stamp(matrix, Complex::new(b.gpi, omega * b.capbe));
+        st.gmu.stamp(matrix, Complex::new(b.gmu, omega * b.capbc));
+        st.gm.stamp(matrix, gm);
+        st.go.stamp(matrix, Complex::from(b.go));
+        st.geqcb.stamp(matrix, Complex::new(0.0, omega * b.geqcb));
+        st.bx.stamp(matrix, Complex::new(0.0, omega * b.capbx));
+        st.cs.stamp(matrix, Complex::new(0.0, omega * b.capcs));
+        st.epi.stamp(matrix, Complex::from(b.gepi));
+        st.epx.stamp(matrix, Complex::from(b.gepx));
+        st.bcx.stamp(matrix, Complex::new(0.0, omega * b.capbcx));
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let t = self.terminals;
+        let s = &self.scaled;
+        let m = &self.model;
+        let mut sources = Vec::new();
+        let resistances = [
+            ("rc", t.collector, t.collector_prime, s.gc),
+            ("rb", t.base, t.base_prime, self.bias.gx),
+            ("re", t.emitter, t.emitter_prime, s.ge),
+        ];
+        for (name, positive, negative, g) in resistances {
+            if positive != negative && g > 0.0 {
+                sources.push(NoiseSource {
+                    name: format!("{} {}", self.name, name),
+                    positive,
+                    negative,
+                    density: thermal_noise(g, s.temperature),
+                });
+            }
+        }
+        sources.push(NoiseSource {
+            name: format!("{} ic", self.name),
+            positive: t.collector_inner,
+            negative: t.emitter_prime,
+            density: shot_noise(self.bias.cc),
+        });
+        sources.push(NoiseSource {
+            name: format!("{} ib", self.name),
+            positive: t.base_prime,
+            negative: t.emitter_prime,
+            density: shot_noise(self.bias.cb),
+        });
+        if m.kf > 0.0 {
+            sources.push(NoiseSource {
+                name: format!("{} flicker", self.name),
+                positive: t.base_prime,
+                negative: t.emitter_prime,
+                density: flicker_noise(m.kf, m.af, 1.0, self.bias.cb, frequency),
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some((vbe, vce)) = self.initial {
+            let p = self.model.polarity;
+            let (vbe, vce) = (p * vbe, p * vce);
+            let vbc = vbe - vce;
+            self.bias = self.evaluate([vbe, vbc, vbc, -vce, vbc], 0.0, None);
+            let b = self.bias;
+            for (charge, value) in self
+                .charges
+                .iter_mut()
+                .zip([b.qbe, b.qbc, b.qbx, b.qcs, b.qbcx])
+            {
+                charge.reset(value);
+            }
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], time: f64) {
+        for charge in &mut self.charges {
+            charge.accept();
+        }
+        if self.excess_started {
+            self.excess = [self.excess[0], self.excess[0], self.excess[1]];
+        }
+        self.last_step = (time > self.accepted).then_some(time - self.accepted);
+        self.accepted = time;
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charges
+            .iter()
+            .map(|charge| charge.allowed_step(timestep, integrator, h, Quantity::Charge))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The collector and base currents predicted by the linearisation must
+    /// agree with those at the new junction voltages, as in ngspice's
+    /// `BJTconvTest`.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let b = &self.bias;
+        let [vbe, vbc, ..] = self.voltages(solution);
+        let (delvbe, delvbc) = (vbe - b.vbe, vbc - b.vbc);
+        let cchat = b.cc + (b.gm + b.go) * delvbe - (b.go + b.gmu) * delvbc;
+        let cbhat = b.cb + b.gpi * delvbe + b.gmu * delvbc;
+        let far = |hat: f64, value: f64| {
+            (hat - value).abs() > options.reltol * hat.abs().max(value.abs()) + options.abstol
+        };
+        far(cchat, b.cc) || far(cbhat, b.cb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::tests::{ac, circuit, operating_point, solve_dc, transient};
+
+    const GMIN: f64 = 1e-12;
+
+    /// Collector and base currents of the Gummel-Poon equations at `vbe`
+    /// and `vbc` without leakage, with `is`, `bf`, `vaf` and `ikf`.
+    fn gummel_poon(vbe: f64, vbc: f64, is: f64, bf: f64, vaf: f64, ikf: f64) -> (f64, f64) {
+        let vt = BOLTZMANN * (27.0 + KELVIN) / CHARGE;
+        let cbe = is * ((vbe / vt).exp() - 1.0) + GMIN * vbe;
+        let cbc = is * ((vbc / vt).exp() - 1.0) + GMIN * vbc;
+        let q1 = 1.0 / (1.0 - vbc / vaf);
+        let qb = q1 * (1.0 + (1.0 + 4.0 * cbe / ikf).sqrt()) / 2.0;
+        ((cbe - cbc) / qb - cbc, cbe / bf + cbc)
+    }
+
+    #[test]
+    fn test_forward_active() {
+        let (circuit, solution) = operating_point(&[
+            "V1 b 0 0.7",
+            "V2 c 0 5",
+            "Q1 c b 0 QN",
+            "V3 pb 0 -0.7",
+            "V4 pc 0 -5",
+            "Q2 pc pb 0 QP",
+            ".model QN NPN(IS=1e-16 BF=200 VAF=50 IKF=10m)",
+            ".model QP PNP(IS=1e-16 BF=200 VAF=50 IKF=10m)",
+        ]);
+        let find = |name: &str| solution[circuit.unknowns().find(name).unwrap()];
+        let (ic, ib) = gummel_poon(0.7, -4.3, 1e-16, 200.0, 50.0, 10e-3);
+        // The sources carry the currents out of the terminals.
+        assert!(
+            (find("I(V2)") / -ic - 1.0).abs() < 1e-9,
+            "{}",
+            find("I(V2)")
+        );
+        assert!((find("I(V1)") / -ib - 1.0).abs() < 1e-9);
+        assert!((find("I(V4)") / ic - 1.0).abs() < 1e-9);
+        assert!((find("I(V3)") / ib - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_resistances() {
+        let (circuit, solution) = operating_point(&[
+            "I1 0 b 10u",
+            "V2 c 0 5",
+            "Q1 c b e QN",
+            "R1 e 0 100",
+            ".model QN NPN(IS=1e-15 BF=100 RB=100 RE=1 RC=10)",
+        ]);
+        let find = |name: &str| solution[circuit.unknowns().find(name).unwrap()];
+        assert!(circuit.unknowns().find("V(Q1#ci)").is_none());
+        // 10 µA through RB, and beta times more through RC.
+        assert!((find("V(b)") - find("V(Q1#b)") - 1e-3).abs() < 1e-9);
+        let ic = -find("I(V2)");
+        assert!((find("V(c)") - find("V(Q1#c)") - 10.0 * ic).abs() < 1e-9);
+        assert!((ic / 1e-3 - 1.0).abs() < 1e-3, "{}", ic);
+        let ie = find("V(e)") / 100.0;
+        assert!((find("V(e)") - find("V(Q1#e)") + ie).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_temperature() {
+        let options = Options::default();
+        let lines = [
+            "I1 0 c 1m",
+            "Q1 c c 0 QN",
+            ".model QN NPN(IS=1e-16 XTB=1.5)",
+        ];
+        let mut hot = circuit(&lines);
+        hot.set_temperature(127.0, &options);
+        let c = hot.unknowns().find("V(c)").unwrap();
+        let hot = solve_dc(&mut hot);
+        // IS grows as (T/Tnom)^XTI exp((T/Tnom - 1) EG/vt), BF as
+        // (T/Tnom)^XTB; the diode connected transistor carries IS (1 + 1/BF)
+        // of the junction's current.
+        let (t, tnom) = (127.0 + KELVIN, 27.0 + KELVIN);
+        let vt = BOLTZMANN * t / CHARGE;
+        let is = 1e-16 * ((t / tnom - 1.0) * 1.11 / vt).exp() * (t / tnom).powi(3);
+        let bf = 100.0 * (t / tnom).powf(1.5);
+        let mut vbe = 0.5;
+        for _ in 0..20 {
+            let cbe = 1e-3 / (1.0 + 1.0 / bf) - 2.0 * GMIN * vbe;
+            vbe = vt * (cbe / is + 1.0).ln();
+        }
+        assert!((hot[c] - vbe).abs() < 1e-9, "{} {}", hot[c], vbe);
+    }
+
+    #[test]
+    fn test_small_signal() {
+        let lines = |vin: f64| {
+            [
+                format!("V1 in 0 {} AC 1", vin),
+                "R1 in b 1k".to_string(),
+                "Q1 c b e QN".to_string(),
+                "R2 e 0 10".to_string(),
+                "R3 vcc c 2k".to_string(),
+                "V2 vcc 0 10".to_string(),
+                ".model QN NPN(IS=1e-15 BF=150 VAF=80 IKF=50m ISE=1e-14 RB=50 RE=0.5 RC=2 CJE=2p CJC=1p TF=0.3n XTF=2 VTF=3 ITF=10m CJS=1p)".to_string(),
+            ]
+        };
+        let out = |vin: f64| {
+            let lines = lines(vin);
+            let (circuit, solution) =
+                operating_point(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+            solution[circuit.unknowns().find("V(c)").unwrap()]
+        };
+        let delta = 1e-5;
+        let gain = (out(0.8 + delta) - out(0.8 - delta)) / (2.0 * delta);
+        let lines = lines(0.8);
+        let mut amplifier = circuit(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+        let c = amplifier.unknowns().find("V(c)").unwrap();
+        // The small signal gain at low frequency is the slope of the DC
+        // transfer, and the capacitances take it away at high frequency.
+        let response = ac(&mut amplifier, 1.0);
+        assert!(gain < -10.0, "{}", gain);
+        assert!(
+            (response[c].re / gain - 1.0).abs() < 1e-5,
+            "{:?} {}",
+            response[c],
+            gain
+        );
+        let response = ac(&mut amplifier, 1e11);
+        assert!(response[c].norm() < 0.5 * gain.abs());
+
+        let noise = amplifier.noise(1e3);
+        let names: Vec<&str> = noise
+            .iter()
+            .map(|n| n.name.as_str())
+            .filter(|name| name.starts_with("Q1"))
+            .collect();
+        assert_eq!(names, ["Q1 rc", "Q1 rb", "Q1 re", "Q1 ic", "Q1 ib"]);
+    }
+
+    #[test]
+    fn test_quasi_saturation() {
+        let collector = |model: &str, vce: f64| {
+            let lines = [
+                "I1 0 b 10u".to_string(),
+                format!("V2 c 0 {}", vce),
+                "Q1 c b 0 QN".to_string(),
+                model.to_string(),
+            ];
+            let (circuit, solution) =
+                operating_point(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+            -solution[circuit.unknowns().find("I(V2)").unwrap()]
+        };
+        let plain = ".model QN NPN(IS=1e-15 BF=100)";
+        let epi = ".model QN NPN(IS=1e-15 BF=100 RCO=500 GAMMA=1e-10 VO=5 QCO=1p)";
+        // Far from saturation the epitaxial layer barely matters, but it
+        // holds the current back as the collector voltage falls.
+        assert!((collector(epi, 10.0) / collector(plain, 10.0) - 1.0).abs() < 0.05);
+        assert!(collector(epi, 0.5) < 0.9 * collector(plain, 0.5));
+    }
+
+    #[test]
+    fn test_switching() {
+        let options = Options::default();
+        let mut inverter = circuit(&[
+            "V1 in 0 PULSE(0 5 10n 1n 1n 40n 1u)",
+            "R1 in b 1k",
+            "Q1 c b 0 Q2N3904",
+            "R2 vcc c 1k",
+            "V2 vcc 0 5",
+            ".model Q2N3904 NPN(IS=6.734f XTI=3 EG=1.11 VAF=74.03 BF=416.4 NE=1.259 ISE=6.734f IKF=66.78m XTB=1.5 BR=.7371 NC=2 ISC=0 IKR=0 RC=1 CJC=3.638p MJC=.3085 VJC=.75 FC=.5 CJE=4.493p MJE=.2593 VJE=.75 TR=239.5n TF=301.2p ITF=.4 VTF=4 XTF=2 PTF=30 RB=10 Vceo=40 Icrating=200m mfg=NXP)",
+        ]);
+        let c = inverter.unknowns().find("V(c)").unwrap();
+        let points = transient(&mut inverter, ".tran 400n", &options);
+        let at = |time: f64| {
+            points
+                .iter()
+                .min_by(|a, b| (a.0 - time).abs().total_cmp(&(b.0 - time).abs()))
+                .unwrap()
+                .1[c]
+        };
+        // Off at first, saturated while driven, and stored charge holding
+        // it on for a while after the drive falls.
+        assert!(at(0.0) > 4.99);
+        assert!(at(45e-9) < 0.2, "{}", at(45e-9));
+        assert!(at(55e-9) < 0.5, "{}", at(55e-9));
+        assert!(at(400e-9) > 4.9, "{}", at(400e-9));
+    }
+}
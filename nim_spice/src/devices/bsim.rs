@@ -0,0 +1,1669 @@
+//! What the BSIM3 and BSIM4 MOSFETs share.
+//!
+//! A card's binnable parameters take their `L`, `W` and `P` terms at the
+//! instance's effective length and width, and [`select`] picks among
+//! cards named `name.1`, `name.2` ... by `LMIN`, `LMAX`, `WMIN` and
+//! `WMAX` as foundry kits bin them. The cores in [`crate::devices::bsim3`]
+//! and [`crate::devices::bsim4`] give the channel current, the drain to
+//! bulk current and the intrinsic terminal charges at a bias, written
+//! against [`Real`] so that every conductance and the nine
+//! transcapacitances come out of one [`Dual`] evaluation. The instance
+//! here adds the overlap charges, the source and drain junctions with
+//! `IJTH`'s linear continuation, the sheet resistances of `RSH` and the
+//! channel's thermal and flicker noise.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::bsim3::Bsim3;
+use crate::devices::bsim4::Bsim4;
+use crate::devices::generic::{
+    current, voltage, Conductance, Device, LoadContext, NoiseSource, State, Unknown, Unknowns,
+    BOLTZMANN, CHARGE, KELVIN,
+};
+use crate::devices::limiting::{critical_voltage, fetlim, limvds, pnjlim};
+use crate::devices::model::Model;
+use crate::devices::mosfet::{energy_gap, DEFAULT_SIZE, EPSILON_SI};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::dual::{Dual, Real};
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// Arguments of `exp` beyond which the cores switch to asymptotes, as
+/// BSIM's `EXP_THRESHOLD`.
+pub(crate) const EXP_THRESHOLD: f64 = 34.0;
+/// BSIM's stand-in for an infinite Early voltage.
+pub(crate) const MAX_EXP: f64 = 5.834617425e14;
+/// Smoothing of the overlap and charge model's limits.
+const DELTA_1: f64 = 0.02;
+const DELTA_3: f64 = 0.02;
+const DELTA_4: f64 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Bsim3,
+    Bsim4,
+}
+
+impl Version {
+    /// The BSIM of a MOS model `LEVEL`: 8 or 49 for BSIM3, 14 or 54 for
+    /// BSIM4.
+    pub fn of(level: f64) -> Option<Version> {
+        match level as i64 {
+            _ if level.fract() != 0.0 => None,
+            8 | 49 => Some(Version::Bsim3),
+            14 | 54 => Some(Version::Bsim4),
+            _ => None,
+        }
+    }
+}
+
+/// The card among `name.1`, `name.2` ... whose `LMIN` to `LMAX` and
+/// `WMIN` to `WMAX` hold a drawn length `l` and width `w`.
+pub fn select<'a>(models: &'a [Model], name: &str, l: f64, w: f64) -> Option<&'a Model> {
+    let prefix = format!("{}.", name.to_ascii_lowercase());
+    models.iter().rev().find(|model| {
+        let within = |min: &str, max: &str, x: f64| {
+            let min = model.number(min).ok().flatten().unwrap_or(0.0);
+            let max = model.number(max).ok().flatten().unwrap_or(1.0);
+            // A kit's ranges meet at their ends; allow for rounding.
+            let slack = 1e-6 * x;
+            min - slack <= x && x < max + slack
+        };
+        model.name.to_ascii_lowercase().starts_with(&prefix)
+            && (model.kind == "NMOS" || model.kind == "PMOS")
+            && within("lmin", "lmax", l)
+            && within("wmin", "wmax", w)
+    })
+}
+
+/// Parameters every BSIM card may have besides its version's own.
+const SHARED: [&str; 10] = [
+    "level", "version", "binunit", "paramchk", "lmin", "lmax", "wmin", "wmax", "tnom", "tref",
+];
+
+/// That a card names only the parameters `binnable`, with their `L`, `W`
+/// and `P` terms, and `others`, and that their values are numbers.
+pub(crate) fn check_card(model: &Model, binnable: &[&str], others: &[&str]) -> Result<(), String> {
+    let sized: Vec<String> = binnable
+        .iter()
+        .flat_map(|name| ["l", "w", "p"].map(|prefix| format!("{}{}", prefix, name)))
+        .collect();
+    let mut known: Vec<&str> = sized.iter().map(String::as_str).collect();
+    known.extend(binnable);
+    known.extend(others);
+    known.extend(SHARED);
+    model.unknown_parameters(&known)?;
+    for (name, _) in &model.parameters {
+        // Kits write versions such as 4.5.0.
+        if name != "version" {
+            model.number(name)?;
+        }
+    }
+    Ok(())
+}
+
+/// A card's parameters at one geometry: a binnable `P` takes
+/// `P + LP/Leff + WP/Weff + PP/(Leff Weff)`, in µm with `BINUNIT=1`.
+pub(crate) struct Binning<'a> {
+    model: &'a Model,
+    inv_l: f64,
+    inv_w: f64,
+}
+
+impl<'a> Binning<'a> {
+    /// The card before `Leff` and `Weff` are known, for the parameters
+    /// giving them.
+    pub(crate) fn unbinned(model: &'a Model) -> Self {
+        Binning {
+            model,
+            inv_l: 0.0,
+            inv_w: 0.0,
+        }
+    }
+
+    pub(crate) fn at(model: &'a Model, leff: f64, weff: f64) -> Self {
+        let binunit = model.number("binunit").ok().flatten().unwrap_or(1.0);
+        let unit = if binunit == 1.0 { 1e-6 } else { 1.0 };
+        Binning {
+            model,
+            inv_l: unit / leff,
+            inv_w: unit / weff,
+        }
+    }
+
+    /// The value given, its form checked when the card was parsed.
+    pub(crate) fn get(&self, name: &str) -> Option<f64> {
+        self.model.number(name).ok().flatten()
+    }
+
+    pub(crate) fn value(&self, name: &str, default: f64) -> f64 {
+        self.get(name).unwrap_or(default)
+    }
+
+    pub(crate) fn given(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// A binnable parameter, `None` where neither it nor its size terms
+    /// are given.
+    pub(crate) fn binned_given(&self, name: &str) -> Option<f64> {
+        let terms = ["l", "w", "p"].map(|prefix| self.get(&format!("{}{}", prefix, name)));
+        if self.get(name).is_none() && terms.iter().all(Option::is_none) {
+            return None;
+        }
+        let [l, w, p] = terms.map(|term| term.unwrap_or(0.0));
+        Some(
+            self.get(name).unwrap_or(0.0)
+                + l * self.inv_l
+                + w * self.inv_w
+                + p * self.inv_l * self.inv_w,
+        )
+    }
+
+    pub(crate) fn binned(&self, name: &str, default: f64) -> f64 {
+        match self.binned_given(name) {
+            Some(value) if self.given(name) => value,
+            Some(terms) => default + terms,
+            None => default,
+        }
+    }
+}
+
+/// The `M` line's drawn geometry.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Geometry {
+    pub l: f64,
+    pub w: f64,
+    pub ad: f64,
+    pub r#as: f64,
+    pub pd: f64,
+    pub ps: f64,
+    pub nrd: f64,
+    pub nrs: f64,
+}
+
+/// The overlap capacitances of a core's geometry.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Overlap {
+    /// Constant overlaps per width to source and drain, with the fringe.
+    pub cgso: f64,
+    pub cgdo: f64,
+    /// Constant overlap per length to the bulk.
+    pub cgbo: f64,
+    /// Bias dependent overlaps of the lightly doped extensions.
+    pub cgsl: f64,
+    pub cgdl: f64,
+    pub ckappas: f64,
+    pub ckappad: f64,
+    pub weff: f64,
+    pub leff: f64,
+}
+
+impl Overlap {
+    /// The gate's overlap charges with the source, drain and bulk.
+    fn charges<T: Real>(&self, vgs: T, vgd: T, vgb: T) -> [T; 3] {
+        let lightly_doped = |v: T, c: f64, kappa: f64| {
+            if c == 0.0 {
+                return T::constant(0.0);
+            }
+            let t0 = v + DELTA_1;
+            let overlap = (t0 - (t0 * t0 + 4.0 * DELTA_1).sqrt()) * 0.5;
+            let root = (T::constant(1.0) - overlap * (4.0 / kappa)).sqrt();
+            (v - overlap - (root - 1.0) * (kappa / 2.0)) * c
+        };
+        [
+            (vgs * self.cgso + lightly_doped(vgs, self.cgsl, self.ckappas)) * self.weff,
+            (vgd * self.cgdo + lightly_doped(vgd, self.cgdl, self.ckappad)) * self.weff,
+            vgb * (self.cgbo * self.leff),
+        ]
+    }
+}
+
+/// The charge model a card's `CAPMOD` selects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ChargeModel {
+    /// BSIM3's and BSIM4's `CAPMOD=0`: piecewise between accumulation,
+    /// depletion and the linear and saturated channel, with a threshold of
+    /// its own from the flat band voltage `VFBCV`.
+    Piecewise { vfbcv: f64 },
+    /// BSIM3's `CAPMOD=1`: the depletion charge smooth in the gate drive,
+    /// the channel's piecewise at `VdsatCV`.
+    Simple,
+    /// BSIM3's `CAPMOD=2`, also BSIM4's `CAPMOD=1`: smooth through every
+    /// region.
+    Smooth,
+    /// BSIM3's `CAPMOD=3` and BSIM4's `CAPMOD=2`: the smooth model with the
+    /// charge centroid's distance below the oxide and the gate drive's
+    /// surface potential shift.
+    Thickness(Thickness),
+}
+
+/// The charge thickness model's parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Thickness {
+    /// Electrical oxide capacitance, which `cox_wl` is of, and the physical
+    /// oxide's thickness and capacitance.
+    pub cox: f64,
+    pub toxp: f64,
+    pub coxp: f64,
+    /// The Debye length in the channel, `ACDE` and `MOIN`.
+    pub ldeb: f64,
+    pub acde: f64,
+    pub moin: f64,
+    /// BSIM4's `ADOS` and `BDOS`, both one for BSIM3.
+    pub ados: f64,
+    pub bdos: f64,
+    /// BSIM4's `4 (VTH0 - VFB - PHI)`, where BSIM3 takes the threshold at
+    /// the bias instead.
+    pub vtfbphi2: Option<f64>,
+}
+
+impl Thickness {
+    /// The physical oxide's capacitance per area in series with a centroid
+    /// `tcen` below it.
+    fn series<T: Real>(&self, tcen: T) -> T {
+        let ccen = T::constant(EPSILON_SI) / tcen;
+        ccen * self.coxp / (ccen + self.coxp)
+    }
+
+    /// The capacitance per area to the inversion charge at `vgsteff` past
+    /// the threshold, its centroid nearer the oxide at a higher field.
+    pub(crate) fn inversion<T: Real>(&self, vgsteff: T, offset: T) -> T {
+        let field = (vgsteff + offset) / (2e8 * self.toxp);
+        self.series(
+            T::constant(self.ados * 1.9e-9) / ((field.ln() * (0.7 * self.bdos)).exp() + 1.0),
+        )
+    }
+}
+
+impl ChargeModel {
+    /// The model of `capmod`, or `None` where the version has no such
+    /// model.
+    pub(crate) fn of(
+        version: Version,
+        capmod: f64,
+        vfbcv: f64,
+        thickness: Thickness,
+    ) -> Option<Self> {
+        Some(match (version, capmod as i64) {
+            (_, 0) => ChargeModel::Piecewise { vfbcv },
+            (Version::Bsim3, 1) => ChargeModel::Simple,
+            (Version::Bsim3, 2) | (Version::Bsim4, 1) => ChargeModel::Smooth,
+            (Version::Bsim3, 3) | (Version::Bsim4, 2) => ChargeModel::Thickness(thickness),
+            _ => return None,
+        })
+    }
+}
+
+/// What a core gives the charge model of its `CAPMOD`, as for an n
+/// channel with `vds ≥ 0`.
+pub(crate) struct ChargeBias<T> {
+    /// Gate voltage past poly depletion, threshold and effective bulk bias,
+    /// and drain bias.
+    pub vgs: T,
+    pub vth: T,
+    pub vbs: T,
+    pub vds: T,
+    /// The square root of the surface potential at this bulk bias.
+    pub sqrt_phis: T,
+    /// Surface potential and flat band voltage at zero bias.
+    pub phi: f64,
+    pub vfb: f64,
+    pub k1ox: f64,
+    /// `Abulk0` and the subthreshold swing at this bias.
+    pub abulk0: T,
+    pub n: T,
+    pub vtm: f64,
+    /// Oxide capacitance over the whole gate.
+    pub cox_wl: f64,
+    /// `1 + (CLC/Leff)^CLE`.
+    pub abulk_factor: f64,
+    pub noff: f64,
+    pub voffcv: f64,
+    pub xpart: f64,
+}
+
+/// The gate, drain and bulk charges of `model` at a bias, the source's
+/// balancing them, split between source and drain 40/60, 50/50 or 0/100
+/// by `XPART`.
+pub(crate) fn charges<T: Real>(model: &ChargeModel, c: ChargeBias<T>) -> [T; 3] {
+    // Forward bulk bias counts by the surface potential it takes off.
+    let vbs = if c.vbs.value() < 0.0 {
+        c.vbs
+    } else {
+        c.vbs * c.phi / (c.vbs + c.phi)
+    };
+    let c = ChargeBias { vbs, ..c };
+    let [qgate, qbulk, qsrc] = match model {
+        ChargeModel::Piecewise { vfbcv } => return piecewise(&c, *vfbcv),
+        ChargeModel::Simple => simple(&c),
+        ChargeModel::Smooth => smooth(&c),
+        ChargeModel::Thickness(thickness) => charge_thickness(&c, thickness),
+    };
+    [qgate, -(qgate + qbulk + qsrc), qbulk]
+}
+
+/// The gate drive of the charges, with its own `NOFF` and `VOFFCV`.
+fn gate_drive<T: Real>(c: &ChargeBias<T>) -> T {
+    let noff = c.n * (c.noff * c.vtm);
+    let arg = (c.vgs - c.vth - c.voffcv) / noff;
+    if arg.value() > EXP_THRESHOLD {
+        c.vgs - c.vth - c.voffcv
+    } else if arg.value() < -EXP_THRESHOLD {
+        noff * (-EXP_THRESHOLD).exp()
+    } else {
+        noff * (arg.exp() + 1.0).ln()
+    }
+}
+
+/// The gate voltage past the flat band, smoothly no lower than the bulk.
+fn flat_band<T: Real>(c: &ChargeBias<T>) -> T {
+    let v3 = c.vbs - c.vgs + (c.vfb - DELTA_3);
+    let t0 = if c.vfb <= 0.0 {
+        (v3 * v3 - 4.0 * DELTA_3 * c.vfb).sqrt()
+    } else {
+        (v3 * v3 + 4.0 * DELTA_3 * c.vfb).sqrt()
+    };
+    T::constant(c.vfb) - (v3 + t0) * 0.5
+}
+
+/// The depletion charge under `vdep` across the depletion layer, for a
+/// gate of capacitance `cox_wl`.
+fn depletion<T: Real>(vdep: T, k1ox: f64, cox_wl: f64) -> T {
+    let half = 0.5 * k1ox;
+    if k1ox == 0.0 {
+        T::constant(0.0)
+    } else if vdep.value() < 0.0 {
+        vdep * cox_wl
+    } else {
+        ((vdep + half * half).sqrt() - half) * (cox_wl * k1ox)
+    }
+}
+
+/// `VdseffCV`, the drain bias smoothly no higher than `vdsat`.
+fn drain_drive<T: Real>(vdsat: T, vds: T) -> T {
+    let v4 = vdsat - vds - DELTA_4;
+    vdsat - (v4 + (v4 * v4 + vdsat * (4.0 * DELTA_4)).sqrt()) * 0.5
+}
+
+/// The channel's charges at a gate drive `vgst` and drain bias `vds` below
+/// saturation: the inversion charge as the gate sees it, the bulk's share
+/// of it and the source's share by `xpart`.
+fn channel<T: Real>(vgst: T, vds: T, abulk: T, cox_wl: f64, xpart: f64) -> [T; 3] {
+    let t0 = abulk * vds;
+    let t1 = (vgst - t0 * 0.5 + 1e-20) * 12.0;
+    let qinv = (vgst - t0 * 0.5 + t0 * t0 / t1) * cox_wl;
+    let qbulk = (T::constant(1.0) - abulk) * (vds * 0.5 - t0 * vds / t1) * cox_wl;
+    let qsrc = if xpart > 0.5 {
+        -(vgst * 0.5 + t0 * 0.25 - t0 * t0 / (t1 * 2.0)) * cox_wl
+    } else if xpart < 0.5 {
+        let t1 = t1 / 12.0;
+        let t2 = T::constant(0.5 * cox_wl) / (t1 * t1);
+        let t3 = vgst * (t0 * t0 * (2.0 / 3.0) + vgst * (vgst - t0 * (4.0 / 3.0)))
+            - t0 * t0 * t0 * (2.0 / 15.0);
+        -t2 * t3
+    } else {
+        -qinv * 0.5
+    };
+    [qinv, qbulk, qsrc]
+}
+
+/// `CAPMOD=0`, which gives the drain's charge itself.
+fn piecewise<T: Real>(c: &ChargeBias<T>, vfbcv: f64) -> [T; 3] {
+    let zero = T::constant(0.0);
+    let vgst = c.vgs - c.sqrt_phis * c.k1ox - (vfbcv + c.phi);
+    let arg = c.vgs - c.vbs - vfbcv;
+    if arg.value() <= 0.0 {
+        let qgate = arg * c.cox_wl;
+        return [qgate, zero, -qgate];
+    }
+    if vgst.value() <= 0.0 {
+        let qgate = depletion(arg, c.k1ox, c.cox_wl);
+        return [qgate, zero, -qgate];
+    }
+
+    let abulk = c.abulk0 * c.abulk_factor;
+    let vdsat = vgst / abulk;
+    let above = c.vgs - (vfbcv + c.phi);
+    if c.vds.value() >= vdsat.value() {
+        let qgate = (above - vdsat / 3.0) * c.cox_wl;
+        let qinv = vgst * (-2.0 / 3.0 * c.cox_wl);
+        let share = if c.xpart > 0.5 {
+            0.0
+        } else if c.xpart < 0.5 {
+            0.4
+        } else {
+            0.5
+        };
+        return [qgate, qinv * share, -(qgate + qinv)];
+    }
+    let t1 = vdsat * 2.0 - c.vds;
+    let t2 = c.vds / (t1 * 3.0);
+    let t3 = t2 * c.vds;
+    let t4 = vgst / vdsat * (0.25 * c.cox_wl);
+    let qgate = (above - (c.vds - t3) * 0.5) * c.cox_wl;
+    if c.xpart > 0.5 {
+        let qdrn = t4 * (c.vds * 2.0 - t1 - t3 * 3.0);
+        let qsrc = t4 * (t3 - t1 - c.vds * 2.0);
+        [qgate, qdrn, -(qgate + qdrn + qsrc)]
+    } else if c.xpart < 0.5 {
+        let t6 = vdsat * vdsat * 8.0 - vdsat * c.vds * 6.0 + c.vds * c.vds * 1.2;
+        let qdrn = t4 * (c.vds - t1 - t2 / t1 * t6);
+        [qgate, qdrn, -(qgate - t4 * (t1 + t3) * 2.0)]
+    } else {
+        let qdrn = -t4 * (t1 + t3);
+        [qgate, qdrn, -(qgate + qdrn * 2.0)]
+    }
+}
+
+/// `CAPMOD=1` as gate, bulk and source charges.
+fn simple<T: Real>(c: &ChargeBias<T>) -> [T; 3] {
+    let vgsteff = gate_drive(c);
+    let arg = c.vgs - c.vbs - c.vfb - vgsteff;
+    let qdep = if arg.value() <= 0.0 {
+        arg * c.cox_wl
+    } else {
+        depletion(arg, c.k1ox, c.cox_wl)
+    };
+
+    let abulk = c.abulk0 * c.abulk_factor;
+    let vdsat = vgsteff / abulk;
+    if vdsat.value() < c.vds.value() {
+        let qgate = qdep + (vgsteff - vdsat / 3.0) * c.cox_wl;
+        let qbulk = -qdep + (vdsat - vgsteff) * (c.cox_wl / 3.0);
+        let share = if c.xpart > 0.5 {
+            2.0 / 3.0
+        } else if c.xpart < 0.5 {
+            0.4
+        } else {
+            1.0 / 3.0
+        };
+        return [qgate, qbulk, vgsteff * (-share * c.cox_wl)];
+    }
+    let [qinv, qbulk, qsrc] = channel(vgsteff, c.vds, abulk, c.cox_wl, c.xpart);
+    [qdep + qinv - qbulk, qbulk - qdep, qsrc]
+}
+
+/// `CAPMOD=2` as gate, bulk and source charges.
+fn smooth<T: Real>(c: &ChargeBias<T>) -> [T; 3] {
+    let vgsteff = gate_drive(c);
+
+    // Accumulation below the flat band, depletion above it.
+    let vfbeff = flat_band(c);
+    let qac0 = (vfbeff - c.vfb) * c.cox_wl;
+    let qsub0 = depletion(c.vgs - vfbeff - c.vbs - vgsteff, c.k1ox, c.cox_wl);
+
+    // Inversion charge along a channel pinching off at VdsatCV.
+    let abulk = c.abulk0 * c.abulk_factor;
+    let vdseff = drain_drive(vgsteff / abulk, c.vds);
+    let [qinv, qbulk, qsrc] = channel(vgsteff, vdseff, abulk, c.cox_wl, c.xpart);
+    [qinv - qbulk + qac0 + qsub0, qbulk - qac0 - qsub0, qsrc]
+}
+
+/// `CAPMOD=3` as gate, bulk and source charges.
+fn charge_thickness<T: Real>(c: &ChargeBias<T>, t: &Thickness) -> [T; 3] {
+    let vgsteff = gate_drive(c);
+    // The oxide capacitance in series with the centroid's, over the gate.
+    let centroid = |tcen: T| t.series(tcen) * (c.cox_wl / t.cox);
+
+    // The accumulation and depletion centroid falls from the Debye length
+    // as the gate goes past the flat band.
+    let vfbeff = flat_band(c);
+    let tox = 1e8 * t.toxp;
+    let arg = (c.vgs - c.vbs - c.vfb) * (t.acde / tox);
+    let tcen = if arg.value() <= -EXP_THRESHOLD {
+        T::constant(t.ldeb * (-EXP_THRESHOLD).exp())
+    } else if arg.value() >= EXP_THRESHOLD {
+        T::constant(t.ldeb * MAX_EXP)
+    } else {
+        arg.exp() * t.ldeb
+    };
+    let link = 1e-3 * t.toxp;
+    let v3 = -tcen + (t.ldeb - link);
+    let v4 = (v3 * v3 + 4.0 * link * t.ldeb).sqrt();
+    let cox_wl_cen = centroid(-(v3 + v4) * 0.5 + t.ldeb);
+    let qac0 = (vfbeff - c.vfb) * cox_wl_cen;
+    let qsub0 = depletion(c.vgs - vfbeff - c.vbs - vgsteff, c.k1ox, 1.0) * cox_wl_cen;
+
+    // The surface potential rises past 2φ with the inversion charge.
+    let (denominator, t0) = if c.k1ox <= 0.0 {
+        (0.25 * t.moin * c.vtm, 0.5 * c.phi.sqrt())
+    } else {
+        (t.moin * c.vtm * c.k1ox * c.k1ox, c.k1ox * c.phi.sqrt())
+    };
+    let delta_phi = ((vgsteff + 2.0 * t0) * vgsteff / denominator + 1.0).ln() * c.vtm;
+    let vgdp = if t.vtfbphi2.is_some() {
+        let t0 = vgsteff - delta_phi - 0.001;
+        (t0 + (t0 * t0 + vgsteff * 0.004).sqrt()) * 0.5
+    } else {
+        vgsteff - delta_phi
+    };
+
+    // The inversion centroid.
+    let offset = match t.vtfbphi2 {
+        Some(vtfbphi2) => T::constant(vtfbphi2),
+        None => {
+            let t3 = (c.vth - c.vfb - c.phi) * 4.0;
+            if t3.value() >= 0.0 {
+                t3
+            } else {
+                T::constant(1e-20)
+            }
+        }
+    };
+    let cox_wl_cen = t.inversion(vgsteff, offset) * (c.cox_wl / t.cox);
+
+    let abulk = c.abulk0 * c.abulk_factor;
+    let vdseff = drain_drive(vgdp / abulk, c.vds);
+    let [qinv, qbulk, qsrc] = channel(vgdp, vdseff, abulk, 1.0, c.xpart);
+    let [qinv, qbulk, qsrc] = [qinv * cox_wl_cen, qbulk * cox_wl_cen, qsrc * cox_wl_cen];
+    [qinv - qbulk + qac0 + qsub0, qbulk - qac0 - qsub0, qsrc]
+}
+
+/// The intrinsic transistor at a bias, as for an n channel with
+/// `vds ≥ 0`.
+pub(crate) struct Intrinsic<T> {
+    /// Channel current from drain to source.
+    pub ids: T,
+    /// Current from drain to bulk, by impact ionisation and gate induced
+    /// drain leakage.
+    pub idb: T,
+    /// Gate, drain and bulk charges; the source's balances them.
+    pub charges: [T; 3],
+    pub von: f64,
+    pub vdsat: f64,
+    pub noise: NoiseBias,
+}
+
+/// The bias quantities of the channel's noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct NoiseBias {
+    pub vgsteff: f64,
+    pub vdseff: f64,
+    pub vds: f64,
+    pub abulk: f64,
+    pub vgst2vtm: f64,
+    pub ueff: f64,
+    /// Saturation field `2 VSAT / ueff`.
+    pub esat: f64,
+    /// Inversion charge of the whole channel.
+    pub qinv: f64,
+    pub rds: f64,
+    /// Density the trapping saturates at, per unit area.
+    pub nstar: f64,
+}
+
+/// Noise parameters and the geometry they scale with.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct NoiseModel {
+    /// The unified flicker noise of `NOIA`, `NOIB` and `NOIC`, else
+    /// SPICE2's `KF` and `AF`.
+    pub unified_flicker: bool,
+    /// Thermal noise from the inversion charge, else SPICE2's
+    /// `8kT/3 (gm + gds + gmbs)`.
+    pub charge_thermal: bool,
+    pub kf: f64,
+    pub af: f64,
+    pub ef: f64,
+    pub noia: f64,
+    pub noib: f64,
+    pub noic: f64,
+    pub em: f64,
+    /// BSIM3 counts the traps per cm and eV, BSIM4 per m and J: the
+    /// thermal energy per kelvin and the scale of the strong and the weak
+    /// inversion densities in those units.
+    pub energy: f64,
+    pub strong_scale: f64,
+    pub weak_scale: f64,
+    pub cox: f64,
+    pub leff: f64,
+    pub weff: f64,
+    pub litl: f64,
+}
+
+impl NoiseModel {
+    /// Thermal and flicker densities of the channel at `bias`, the
+    /// conductances summing to `g`, at `temperature` in K and `frequency`.
+    fn densities(
+        &self,
+        bias: &NoiseBias,
+        ids: f64,
+        g: f64,
+        temperature: f64,
+        frequency: f64,
+    ) -> (f64, f64) {
+        let kt = BOLTZMANN * temperature;
+        let thermal = if self.charge_thermal {
+            let t0 = bias.ueff * bias.qinv.abs();
+            4.0 * kt * t0 / (t0 * bias.rds + self.leff * self.leff)
+        } else {
+            8.0 * kt * g.abs() / 3.0
+        };
+        if frequency <= 0.0 {
+            return (thermal, 0.0);
+        }
+        let ids = ids.abs();
+        let effective = frequency.powf(self.ef);
+        let flicker = if !self.unified_flicker {
+            if self.kf == 0.0 {
+                0.0
+            } else {
+                self.kf * ids.max(1e-38).powf(self.af)
+                    / (effective * self.leff * self.leff * self.cox)
+            }
+        } else if bias.vgsteff <= 0.0 || bias.esat <= 0.0 {
+            0.0
+        } else {
+            // Strong inversion in parallel with the weak, as b3noi.c and
+            // b4noi.c.
+            let vt = self.energy * temperature;
+            let t0 = ((bias.vds - bias.vdseff) / self.litl + self.em) / bias.esat;
+            let clm = self.litl * t0.max(1e-38).ln();
+            let n0 = self.cox * bias.vgsteff / CHARGE;
+            let nl =
+                self.cox * bias.vgsteff * (1.0 - bias.abulk * bias.vdseff / bias.vgst2vtm).max(0.0)
+                    / CHARGE;
+            let nstar = bias.nstar;
+            let t1 = CHARGE * CHARGE * vt * ids * bias.ueff;
+            let t2 = self.strong_scale * effective * bias.abulk * self.cox * self.leff * self.leff;
+            let t3 = self.noia * ((n0 + nstar) / (nl + nstar)).max(1e-38).ln();
+            let t4 = self.noib * (n0 - nl);
+            let t5 = self.noic * 0.5 * (n0 * n0 - nl * nl);
+            let t6 = vt * ids * ids;
+            let t7 = self.strong_scale * effective * self.leff * self.leff * self.weff;
+            let t8 = self.noia + self.noib * nl + self.noic * nl * nl;
+            let t9 = (nl + nstar) * (nl + nstar);
+            let strong = t1 / t2 * (t3 + t4 + t5) + t6 / t7 * clm * t8 / t9;
+            let weak =
+                self.noia * vt * ids * ids / (self.leff * self.weff * effective * self.weak_scale);
+            if strong + weak > 0.0 {
+                strong * weak / (strong + weak)
+            } else {
+                0.0
+            }
+        };
+        (thermal, flicker)
+    }
+}
+
+/// The version's core at one geometry and temperature.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Core {
+    Bsim3(Box<Bsim3>),
+    Bsim4(Box<Bsim4>),
+}
+
+impl Core {
+    fn new(
+        version: Version,
+        model: &Model,
+        geometry: &Geometry,
+        t: f64,
+        tnom: f64,
+    ) -> Result<Self, String> {
+        Ok(match version {
+            Version::Bsim3 => Core::Bsim3(Box::new(Bsim3::new(model, geometry, t, tnom)?)),
+            Version::Bsim4 => Core::Bsim4(Box::new(Bsim4::new(model, geometry, t, tnom)?)),
+        })
+    }
+
+    fn intrinsic<T: Real>(&self, vgs: T, vds: T, vbs: T) -> Intrinsic<T> {
+        match self {
+            Core::Bsim3(core) => core.intrinsic(vgs, vds, vbs),
+            Core::Bsim4(core) => core.intrinsic(vgs, vds, vbs),
+        }
+    }
+
+    fn overlap(&self) -> &Overlap {
+        match self {
+            Core::Bsim3(core) => &core.overlap,
+            Core::Bsim4(core) => &core.overlap,
+        }
+    }
+
+    fn noise(&self) -> &NoiseModel {
+        match self {
+            Core::Bsim3(core) => &core.noise,
+            Core::Bsim4(core) => &core.noise,
+        }
+    }
+
+    /// Effective width of the junctions' gate edge.
+    fn weff(&self) -> f64 {
+        match self {
+            Core::Bsim3(core) => core.weff,
+            Core::Bsim4(core) => core.weff,
+        }
+    }
+}
+
+/// A source or drain junction at temperature, for the instance's size.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Junction {
+    is: f64,
+    /// Emission coefficient times the thermal voltage.
+    nvt: f64,
+    /// Forward voltage past which the current continues linearly, none
+    /// without `IJTH`.
+    vjsm: Option<f64>,
+    vcrit: f64,
+    /// Zero bias capacitance, built-in potential and grading of the
+    /// bottom, the sidewall along the field oxide and that along the gate.
+    parts: [(f64, f64, f64); 3],
+}
+
+impl Junction {
+    /// The junction of `side`, `s` or `d`, whose BSIM4 parameters name it
+    /// while BSIM3's are the same for both.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        card: &Binning,
+        version: Version,
+        side: char,
+        area: f64,
+        perimeter: f64,
+        weff: f64,
+        t: f64,
+        tnom: f64,
+    ) -> Self {
+        let value = |name: &str, default: f64| match version {
+            Version::Bsim3 => card.value(name, default),
+            Version::Bsim4 => {
+                let named = |side: char| match name {
+                    "ijth" => format!("ijth{}fwd", side),
+                    "js" => format!("js{}", side),
+                    _ => format!("{}{}", name, side),
+                };
+                card.get(&named(side))
+                    .or_else(|| card.get(&named('s')))
+                    .unwrap_or(default)
+            }
+        };
+        let dt = t - tnom;
+        let vt = BOLTZMANN * t / CHARGE;
+        let vtnom = BOLTZMANN * tnom / CHARGE;
+        let nj = value("nj", 1.0);
+        let xti = value("xti", 3.0);
+        let exponent = energy_gap(tnom) / vtnom - energy_gap(t) / vt + xti * (t / tnom).ln();
+        let factor = (exponent / nj).exp();
+        let js = value("js", 1e-4) * factor;
+        let jsw = value("jsw", 0.0) * factor;
+        let is = js * area + jsw * perimeter;
+        let nvt = nj * vt;
+        let ijth = value("ijth", 0.1);
+        let vjsm = (ijth > 0.0 && is > 0.0).then(|| nvt * (ijth / is + 1.0).ln());
+
+        let pb = value("pb", 1.0);
+        let pbsw = value("pbsw", pb);
+        let pbswg = value("pbswg", pbsw);
+        let cj = value("cj", 5e-4);
+        let cjsw = value("cjsw", 5e-10);
+        let cjswg = value("cjswg", cjsw);
+        let mj = value("mj", 0.5);
+        let mjsw = value("mjsw", 0.33);
+        let mjswg = value("mjswg", mjsw);
+        let potential = |pb: f64, tpb: &str| (pb - card.value(tpb, 0.0) * dt).max(0.01);
+        let capacitance = |c: f64, tc: &str| (c * (1.0 + card.value(tc, 0.0) * dt)).max(0.0);
+        // The sidewall along the gate is the gate's width, if the
+        // perimeter is that long.
+        let (side_length, gate_length) = if perimeter < weff {
+            (0.0, perimeter)
+        } else {
+            (perimeter - weff, weff)
+        };
+        Junction {
+            is,
+            nvt,
+            vjsm,
+            vcrit: critical_voltage(nvt, is.max(1e-300)),
+            parts: [
+                (capacitance(cj, "tcj") * area, potential(pb, "tpb"), mj),
+                (
+                    capacitance(cjsw, "tcjsw") * side_length,
+                    potential(pbsw, "tpbsw"),
+                    mjsw,
+                ),
+                (
+                    capacitance(cjswg, "tcjswg") * gate_length,
+                    potential(pbswg, "tpbswg"),
+                    mjswg,
+                ),
+            ],
+        }
+    }
+
+    /// Current with gmin, and conductance.
+    fn current(&self, v: f64, gmin: f64) -> (f64, f64) {
+        if self.is <= 0.0 {
+            return (gmin * v, gmin);
+        }
+        match self.vjsm {
+            Some(vjsm) if v >= vjsm => {
+                let isevjsm = self.is * (vjsm / self.nvt).exp();
+                let g = isevjsm / self.nvt;
+                (isevjsm - self.is + g * (v - vjsm) + gmin * v, g + gmin)
+            }
+            _ => {
+                let ev = (v / self.nvt).exp();
+                (
+                    self.is * (ev - 1.0) + gmin * v,
+                    self.is * ev / self.nvt + gmin,
+                )
+            }
+        }
+    }
+
+    /// Depletion charge and capacitance, linear in the capacitance when
+    /// forward biased.
+    fn charge(&self, v: f64) -> (f64, f64) {
+        self.parts
+            .iter()
+            .filter(|(c, _, _)| *c > 0.0)
+            .map(|&(c, pb, m)| {
+                if v < 0.0 {
+                    let arg = 1.0 - v / pb;
+                    let sarg = arg.powf(-m);
+                    (pb * c * (1.0 - arg * sarg) / (1.0 - m), c * sarg)
+                } else {
+                    (c * v * (1.0 + m * v / (2.0 * pb)), c * (1.0 + m * v / pb))
+                }
+            })
+            .fold((0.0, 0.0), |(q, c), (dq, dc)| (q + dq, c + dc))
+    }
+}
+
+/// The linearisation at a bias point, as for an n channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bias {
+    vgs: f64,
+    vds: f64,
+    vbs: f64,
+    von: f64,
+    vdsat: f64,
+    /// Channel current and its derivatives with `vgs`, `vds` and `vbs`.
+    ids: f64,
+    gm: f64,
+    gds: f64,
+    gmbs: f64,
+    /// Current from the drain, or the source when reversed, to the bulk,
+    /// and its derivatives.
+    idb: f64,
+    gdb: [f64; 3],
+    cbd: f64,
+    gbd: f64,
+    cbs: f64,
+    gbs: f64,
+    qbd: f64,
+    capbd: f64,
+    qbs: f64,
+    capbs: f64,
+    /// Gate, drain and bulk charges, intrinsic and overlap, and their
+    /// derivatives with `vgs`, `vds` and `vbs`.
+    charges: [f64; 3],
+    capacitances: [[f64; 3]; 3],
+    noise: NoiseBias,
+}
+
+impl Bias {
+    fn vbd(&self) -> f64 {
+        self.vbs - self.vds
+    }
+}
+
+/// Conductances the device stamps through.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Stamps {
+    /// Drain and source sheet resistances.
+    series: [Conductance; 2],
+    /// `gds`, `gm` and `gmbs`.
+    channel: [Conductance; 3],
+    /// Bulk to drain and bulk to source.
+    junctions: [Conductance; 2],
+    /// The drain to bulk current, from the drain and from the source when
+    /// reversed, controlled by `vgs`, `vds` and `vbs`.
+    substrate: [[Conductance; 3]; 2],
+    /// The gate, drain and bulk charges by `vgs`, `vds` and `vbs`.
+    charges: [[Conductance; 3]; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bsim {
+    name: String,
+    /// Drain, gate, source and bulk.
+    nodes: [Node; 4],
+    model: Model,
+    version: Version,
+    /// 1 for NMOS, -1 for PMOS.
+    polarity: f64,
+    geometry: Geometry,
+    multiplier: f64,
+    /// `vds`, `vgs` and `vbs` to start from with `uic`.
+    initial: Option<[f64; 3]>,
+    temp: Option<f64>,
+    dtemp: f64,
+    temperature: f64,
+    core: Core,
+    drain: Junction,
+    source: Junction,
+    /// Conductances of the drain and source sheet resistances.
+    series: [f64; 2],
+    /// Drain, gate, source, bulk, and the drain and source inside the
+    /// sheet resistances.
+    unknowns: [Unknown; 6],
+    stamps: Stamps,
+    /// Gate, drain and bulk charges, bulk to drain and bulk to source.
+    charges: [State; 5],
+    bias: Bias,
+}
+
+impl Bsim {
+    /// `Mname d g s b model [L=] [W=] [AD=] [AS=] [PD=] [PS=] [NRD=] [NRS=]
+    /// [off] [m=] [ic=vds,vgs,vbs] [temp=] [dtemp=]` of a BSIM `model`.
+    pub fn parse(line: &ElementLine, model: &Model, version: Version) -> Result<Self, String> {
+        line.unknown_parameters(&[
+            "l", "w", "ad", "as", "pd", "ps", "nrd", "nrs", "m", "ic", "temp", "dtemp",
+        ])?;
+        let [drain, gate, source, bulk] = line.nodes()?;
+        match version {
+            Version::Bsim3 => crate::devices::bsim3::check(model)?,
+            Version::Bsim4 => crate::devices::bsim4::check(model)?,
+        }
+        let number = |name: &str, default: f64| -> Result<f64, String> {
+            Ok(line.number(name)?.unwrap_or(default))
+        };
+        let geometry = Geometry {
+            l: number("l", DEFAULT_SIZE)?,
+            w: number("w", DEFAULT_SIZE)?,
+            ad: number("ad", 0.0)?,
+            r#as: number("as", 0.0)?,
+            pd: number("pd", 0.0)?,
+            ps: number("ps", 0.0)?,
+            nrd: number("nrd", 1.0)?,
+            nrs: number("nrs", 1.0)?,
+        };
+        let multiplier = number("m", 1.0)?;
+        if multiplier <= 0.0 {
+            return Err("The multiplier must be positive".to_string());
+        }
+        let initial = match line.numbers("ic")?.as_slice() {
+            [] => None,
+            [vds] => Some([*vds, 0.0, 0.0]),
+            [vds, vgs] => Some([*vds, *vgs, 0.0]),
+            [vds, vgs, vbs] => Some([*vds, *vgs, *vbs]),
+            _ => return Err("Expected ic=vds,vgs,vbs".to_string()),
+        };
+        let tnom = model.alias(&["tnom", "tref"])?.unwrap_or(27.0) + KELVIN;
+        let core = Core::new(version, model, &geometry, tnom, tnom)?;
+        Ok(Bsim {
+            name: line.name.clone(),
+            nodes: [drain, gate, source, bulk],
+            model: model.clone(),
+            version,
+            polarity: if model.kind == "PMOS" { -1.0 } else { 1.0 },
+            geometry,
+            multiplier,
+            initial,
+            temp: line.number("temp")?,
+            dtemp: number("dtemp", 0.0)?,
+            temperature: tnom,
+            core,
+            drain: Junction::default(),
+            source: Junction::default(),
+            series: [0.0; 2],
+            unknowns: [None; 6],
+            stamps: Stamps::default(),
+            charges: Default::default(),
+            bias: Bias::default(),
+        })
+    }
+
+    /// Drain current, threshold and saturation voltage at the last load.
+    pub fn operating_point(&self) -> (f64, f64, f64) {
+        let p = self.polarity;
+        (p * self.bias.ids, p * self.bias.von, p * self.bias.vdsat)
+    }
+
+    /// `vgs`, `vds` and `vbs` of `solution`, as for an n channel.
+    fn voltages(&self, solution: &[f64]) -> [f64; 3] {
+        let [_, gate, _, bulk, drain, source] = self.unknowns;
+        let v = |node: Unknown| voltage(solution, node);
+        let p = self.polarity;
+        [
+            p * (v(gate) - v(source)),
+            p * (v(drain) - v(source)),
+            p * (v(bulk) - v(source)),
+        ]
+    }
+
+    fn evaluate(&self, voltages: [f64; 3], gmin: f64) -> Bias {
+        let [vgs, vds, vbs] = voltages;
+        let x: [Dual<3>; 3] = std::array::from_fn(|i| Dual::variable(voltages[i], i));
+        let intrinsic = if vds >= 0.0 {
+            self.core.intrinsic(x[0], x[1], x[2])
+        } else {
+            // Drain and source swap, the source's charge balancing the
+            // others becoming the drain's.
+            let mut reversed = self.core.intrinsic(x[0] - x[1], -x[1], x[2] - x[1]);
+            let [qg, qd, qb] = reversed.charges;
+            reversed.charges = [qg, -(qg + qd + qb), qb];
+            reversed.ids = -reversed.ids;
+            reversed
+        };
+        let [qgs, qgd, qgb] = self.core.overlap().charges(x[0], x[0] - x[1], x[0] - x[2]);
+        let [qg, qd, qb] = intrinsic.charges;
+        let m = self.multiplier;
+        let charges = [(qg + qgs + qgd + qgb) * m, (qd - qgd) * m, (qb - qgb) * m];
+
+        let vbd = vbs - vds;
+        let (cbd, gbd) = self.drain.current(vbd, gmin);
+        let (cbs, gbs) = self.source.current(vbs, gmin);
+        let (qbd, capbd) = self.drain.charge(vbd);
+        let (qbs, capbs) = self.source.charge(vbs);
+        let ids = intrinsic.ids * m;
+        let idb = intrinsic.idb * m;
+        Bias {
+            vgs,
+            vds,
+            vbs,
+            von: intrinsic.von,
+            vdsat: intrinsic.vdsat,
+            ids: ids.value,
+            gm: ids.derivative(0),
+            gds: ids.derivative(1),
+            gmbs: ids.derivative(2),
+            idb: idb.value,
+            gdb: idb.gradient,
+            cbd,
+            gbd,
+            cbs,
+            gbs,
+            qbd,
+            capbd,
+            qbs,
+            capbs,
+            charges: charges.map(|q| q.value),
+            capacitances: charges.map(|q| q.gradient),
+            noise: intrinsic.noise,
+        }
+    }
+}
+
+impl Device for Bsim {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let [drain, gate, source, bulk] = self.nodes.each_ref().map(|n| unknowns.node(n));
+        let rsh = self.model.number("rsh").ok().flatten().unwrap_or(0.0);
+        let inner = if rsh * self.geometry.nrd > 0.0 {
+            Some(unknowns.internal(&self.name, "d"))
+        } else {
+            drain
+        };
+        let source_inner = if rsh * self.geometry.nrs > 0.0 {
+            Some(unknowns.internal(&self.name, "s"))
+        } else {
+            source
+        };
+        self.unknowns = [drain, gate, source, bulk, inner, source_inner];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [drain, gate, source, bulk, d, s] = self.unknowns;
+        let controls = [gate, d, bulk];
+        let controlled = |pattern: &mut Pattern, p: Unknown, n: Unknown| {
+            controls.map(|c| Conductance::controlled(pattern, p, n, c, s))
+        };
+        self.stamps = Stamps {
+            series: [
+                Conductance::new(pattern, drain, d),
+                Conductance::new(pattern, source, s),
+            ],
+            channel: [
+                Conductance::new(pattern, d, s),
+                Conductance::controlled(pattern, d, s, gate, s),
+                Conductance::controlled(pattern, d, s, bulk, s),
+            ],
+            junctions: [
+                Conductance::new(pattern, bulk, d),
+                Conductance::new(pattern, bulk, s),
+            ],
+            substrate: [controlled(pattern, d, bulk), controlled(pattern, s, bulk)],
+            charges: [
+                controlled(pattern, gate, s),
+                controlled(pattern, d, s),
+                controlled(pattern, bulk, s),
+            ],
+        };
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let t = celsius + KELVIN;
+        let tnom = self
+            .model
+            .alias(&["tnom", "tref"])
+            .ok()
+            .flatten()
+            .unwrap_or(nominal)
+            + KELVIN;
+        self.temperature = t;
+        self.core = Core::new(self.version, &self.model, &self.geometry, t, tnom)
+            .expect("the geometry was checked when parsed");
+        let g = &self.geometry;
+        let m = self.multiplier;
+        let card = Binning::unbinned(&self.model);
+        let weff = self.core.weff();
+        let junction = |side, area, perimeter| {
+            Junction::new(
+                &card,
+                self.version,
+                side,
+                area * m,
+                perimeter * m,
+                weff * m,
+                t,
+                tnom,
+            )
+        };
+        self.drain = junction('d', g.ad, g.pd);
+        self.source = junction('s', g.r#as, g.ps);
+        let rsh = card.value("rsh", 0.0);
+        let conductance = |squares: f64| {
+            if rsh * squares > 0.0 {
+                m / (rsh * squares)
+            } else {
+                0.0
+            }
+        };
+        self.series = [conductance(g.nrd), conductance(g.nrs)];
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let [new_vgs, new_vds, new_vbs] = self.voltages(context.solution);
+        let old = self.bias;
+
+        // Limit the new voltages as ngspice's b3ld.c does, noting a change
+        // in the voltages limited rather than in their sums.
+        let (vgs, vds, changed) = if old.vds >= 0.0 {
+            let vgs = fetlim(new_vgs, old.vgs, old.von);
+            let vds = limvds(new_vds, old.vds);
+            (vgs, vds, vgs != new_vgs || vds != new_vds)
+        } else {
+            let new_vgd = new_vgs - new_vds;
+            let vgd = fetlim(new_vgd, old.vgs - old.vds, old.von);
+            let vds = -limvds(-new_vds, -old.vds);
+            let changed = vgd != new_vgd || vds != new_vds;
+            (if changed { vgd + vds } else { new_vgs }, vds, changed)
+        };
+        let (vbs, limited) = if vds >= 0.0 {
+            pnjlim(new_vbs, old.vbs, self.source.nvt, self.source.vcrit)
+        } else {
+            let (vbd, limited) = pnjlim(
+                new_vbs - new_vds,
+                old.vbd(),
+                self.drain.nvt,
+                self.drain.vcrit,
+            );
+            (
+                if limited || changed {
+                    vbd + vds
+                } else {
+                    new_vbs
+                },
+                limited,
+            )
+        };
+        if limited || changed {
+            context.limit(&self.name);
+        }
+
+        let bias = self.evaluate([vgs, vds, vbs], context.gmin);
+        self.bias = bias;
+        let voltages = [vgs, vds, vbs];
+        let (mut gbd, mut gbs) = (bias.gbd, bias.gbs);
+        let (mut cbd, mut cbs) = (bias.cbd, bias.cbs);
+        let mut scale = 0.0;
+        let mut currents = [0.0; 3];
+        let [qg, qd, qb, qbd, qbs] = &mut self.charges;
+        match context.integrator {
+            Some(integrator) => {
+                gbd += qbd
+                    .integrate(integrator, bias.qbd, bias.capbd, bias.vbd())
+                    .geq;
+                cbd += qbd.derivative();
+                gbs += qbs.integrate(integrator, bias.qbs, bias.capbs, vbs).geq;
+                cbs += qbs.derivative();
+                // Each terminal charge depends on three voltages: stamp
+                // the integration coefficient times each capacitance.
+                scale = integrator.ag()[0];
+                for (i, charge) in [qg, qd, qb].into_iter().enumerate() {
+                    charge.integrate(integrator, bias.charges[i], 0.0, 0.0);
+                    let linear: f64 = (0..3)
+                        .map(|j| scale * bias.capacitances[i][j] * voltages[j])
+                        .sum();
+                    currents[i] = charge.derivative() - linear;
+                }
+            }
+            None => {
+                qbd.reset(bias.qbd);
+                qbs.reset(bias.qbs);
+                for (i, charge) in [qg, qd, qb].into_iter().enumerate() {
+                    charge.reset(bias.charges[i]);
+                }
+            }
+        }
+
+        let st = &self.stamps;
+        st.series[0].stamp(matrix, self.series[0]);
+        st.series[1].stamp(matrix, self.series[1]);
+        st.channel[0].stamp(matrix, bias.gds);
+        st.channel[1].stamp(matrix, bias.gm);
+        st.channel[2].stamp(matrix, bias.gmbs);
+        st.junctions[0].stamp(matrix, gbd);
+        st.junctions[1].stamp(matrix, gbs);
+        let end = if vds >= 0.0 { 0 } else { 1 };
+        for j in 0..3 {
+            st.substrate[end][j].stamp(matrix, bias.gdb[j]);
+            for i in 0..3 {
+                st.charges[i][j].stamp(matrix, scale * bias.capacitances[i][j]);
+            }
+        }
+
+        let p = self.polarity;
+        let [_, g, _, b, d, s] = self.unknowns;
+        let ieq = bias.ids - bias.gm * vgs - bias.gds * vds - bias.gmbs * vbs;
+        current(rhs, d, s, p * ieq);
+        let idb: f64 = bias.idb - (0..3).map(|j| bias.gdb[j] * voltages[j]).sum::<f64>();
+        current(rhs, if end == 0 { d } else { s }, b, p * idb);
+        current(rhs, b, d, p * (cbd - gbd * bias.vbd()));
+        current(rhs, b, s, p * (cbs - gbs * vbs));
+        for (terminal, value) in [g, d, b].into_iter().zip(currents) {
+            current(rhs, terminal, s, p * value);
+        }
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let b = &self.bias;
+        let st = &self.stamps;
+        st.series[0].stamp(matrix, Complex::from(self.series[0]));
+        st.series[1].stamp(matrix, Complex::from(self.series[1]));
+        st.channel[0].stamp(matrix, Complex::from(b.gds));
+        st.channel[1].stamp(matrix, Complex::from(b.gm));
+        st.channel[2].stamp(matrix, Complex::from(b.gmbs));
+        st.junctions[0].stamp(matrix, Complex::new(b.gbd, omega * b.capbd));
+        st.junctions[1].stamp(matrix, Complex::new(b.gbs, omega * b.capbs));
+        let end = if b.vds >= 0.0 { 0 } else { 1 };
+        for j in 0..3 {
+            st.substrate[end][j].stamp(matrix, Complex::from(b.gdb[j]));
+            for i in 0..3 {
+                st.charges[i][j].stamp(matrix, Complex::new(0.0, omega * b.capacitances[i][j]));
+            }
+        }
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let [drain, _, source, _, d, s] = self.unknowns;
+        let celsius = self.temperature - KELVIN;
+        let mut sources = Vec::new();
+        for (name, outer, inner, g) in [
+            ("rd", drain, d, self.series[0]),
+            ("rs", source, s, self.series[1]),
+        ] {
+            if outer != inner {
+                sources.push(NoiseSource {
+                    name: format!("{} {}", self.name, name),
+                    positive: outer,
+                    negative: inner,
+                    density: crate::devices::generic::thermal_noise(g, celsius),
+                });
+            }
+        }
+        let b = &self.bias;
+        let m = self.multiplier;
+        let (thermal, flicker) = self.core.noise().densities(
+            &b.noise,
+            b.ids / m,
+            (b.gm + b.gds + b.gmbs) / m,
+            self.temperature,
+            frequency,
+        );
+        for (name, density) in [("channel", thermal), ("flicker", flicker)] {
+            sources.push(NoiseSource {
+                name: format!("{} {}", self.name, name),
+                positive: d,
+                negative: s,
+                density: density * m,
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some([vds, vgs, vbs]) = self.initial {
+            let p = self.polarity;
+            self.bias = self.evaluate([p * vgs, p * vds, p * vbs], 0.0);
+            for i in 0..3 {
+                self.charges[i].reset(self.bias.charges[i]);
+            }
+            self.charges[3].reset(self.bias.qbd);
+            self.charges[4].reset(self.bias.qbs);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        for charge in &mut self.charges {
+            charge.accept();
+        }
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charges
+            .iter()
+            .map(|charge| charge.allowed_step(timestep, integrator, h, Quantity::Charge))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The drain and bulk currents predicted by the linearisation must
+    /// agree with those at the new voltages, as in ngspice's
+    /// `BSIM3convTest`.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let b = &self.bias;
+        let [vgs, vds, vbs] = self.voltages(solution);
+        let (delvgs, delvds, delvbs) = (vgs - b.vgs, vds - b.vds, vbs - b.vbs);
+        let delvbd = delvbs - delvds;
+        let cd = b.ids - b.cbd;
+        let cdhat = cd + b.gm * delvgs + b.gds * delvds + b.gmbs * delvbs - b.gbd * delvbd;
+        let cbhat = b.cbs + b.cbd + b.gbd * delvbd + b.gbs * delvbs;
+        let far = |hat: f64, value: f64| {
+            (hat - value).abs() > options.reltol * hat.abs().max(value.abs()) + options.abstol
+        };
+        far(cdhat, cd) || far(cbhat, b.cbs + b.cbd)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::devices::mosfet::EPSILON_OX;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+    use crate::frontend::parser::netlist::Component;
+
+    const BSIM3: &str = ".model MOD NMOS(LEVEL=49 VERSION=3.3 TOX=4n NCH=4e17 VTH0=0.45 U0=350 VSAT=1e5 RDSW=150 PCLM=1.2 CGSO=2e-10 CGDO=2e-10 CJ=1e-3 ALPHA0=1e-6 BETA0=20)";
+    const BSIM4: &str = ".model MOD NMOS(LEVEL=54 VERSION=4.5 TOXE=2n NDEP=5e17 VTH0=0.4 U0=300 MOBMOD=0 DVTP0=1e-7 DVTP1=0.5 FPROUT=1 PDITS=0.1 AGIDL=1e-9 CGSO=2e-10 CGDO=2e-10 CJS=1e-3 PDITSD=0.5)";
+
+    /// Compare a core against a fixture of reference curves: a `.model`
+    /// card and the `l` and `w` it was evaluated at in the header, then a
+    /// row of `temp,capmod,xpart,vgs,vds,vbs,ids,idb,qg,qd,qb` per bias,
+    /// in Celsius and SI units. `intrinsic` evaluates the card at a
+    /// geometry and temperature for `[vgs, vds, vbs]`.
+    ///
+    /// Currents must agree within 1% and charges within 1% or 0.1% of the
+    /// oxide's charge at 1 V, the difference BSIM's own physical constants
+    /// against CODATA's make.
+    pub(crate) fn reference(
+        fixture: &str,
+        intrinsic: impl Fn(&Model, &Geometry, f64, [f64; 3]) -> Intrinsic<f64>,
+    ) {
+        let mut card = "";
+        let mut geometry = Geometry::default();
+        let mut rows = 0;
+        for line in fixture.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                if comment.starts_with(".model") {
+                    card = comment;
+                }
+                for word in comment.split_whitespace() {
+                    match word.split_once('=') {
+                        Some(("l", l)) => geometry.l = l.parse().unwrap(),
+                        Some(("w", w)) => geometry.w = w.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            let Ok(row) = line
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<f64>, _>>()
+            else {
+                continue;
+            };
+            let [temp, capmod, xpart, vgs, vds, vbs, ids, idb, qg, qd, qb] = row[..] else {
+                panic!("{}", line);
+            };
+            let model = format!(
+                "{} CAPMOD={} XPART={})",
+                card.strip_suffix(')').unwrap(),
+                capmod,
+                xpart
+            );
+            let model = Model::parse(&model).unwrap();
+            let binning = Binning::unbinned(&model);
+            let tox = binning.get("toxe").or(binning.get("tox")).unwrap();
+            let oxide = EPSILON_OX / tox * geometry.l * geometry.w;
+            let result = intrinsic(&model, &geometry, temp, [vgs, vds, vbs]);
+            let close = |value: f64, expected: f64, floor: f64| {
+                (value - expected).abs() <= 0.01 * expected.abs() + floor
+            };
+            let [g, d, b] = result.charges;
+            assert!(
+                close(result.ids, ids, 1e-12)
+                    && close(result.idb, idb, 1e-15)
+                    && close(g, qg, 1e-3 * oxide)
+                    && close(d, qd, 1e-3 * oxide)
+                    && close(b, qb, 1e-3 * oxide),
+                "{}: {:?} {:?} against {:?}",
+                line,
+                [result.ids, result.idb],
+                result.charges,
+                [ids, idb, qg, qd, qb]
+            );
+            rows += 1;
+        }
+        assert!(rows > 0 && geometry.l > 0.0 && geometry.w > 0.0);
+    }
+
+    /// The drain current into `V2` of a transistor biased by `V1` at the
+    /// gate and `V2` at the drain over a bulk at -0.5 V.
+    fn drain_current(model: &str, vgs: f64, vds: f64) -> f64 {
+        let lines = [
+            format!("V1 g 0 {}", vgs),
+            format!("V2 d 0 {}", vds),
+            "V3 b 0 -0.5".to_string(),
+            "M1 d g 0 b MOD L=0.5u W=5u AD=1p AS=1p PD=6u PS=6u".to_string(),
+            model.to_string(),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (circuit, solution) = operating_point(&lines);
+        -solution[circuit.unknowns().find("I(V2)").unwrap()]
+    }
+
+    #[test]
+    fn test_conductances() {
+        // The small signal drain current at low frequency against a finite
+        // difference of the operating point, forward and reversed.
+        for model in [BSIM3, BSIM4] {
+            for (vgs, vds) in [(1.2, 0.05), (1.2, 1.5), (0.3, 1.0), (1.0, -0.4)] {
+                let delta = 1e-6;
+                let gm = (drain_current(model, vgs + delta, vds)
+                    - drain_current(model, vgs - delta, vds))
+                    / (2.0 * delta);
+                let gds = (drain_current(model, vgs, vds + delta)
+                    - drain_current(model, vgs, vds - delta))
+                    / (2.0 * delta);
+                for (gate, expected) in [(true, gm), (false, gds)] {
+                    let lines = [
+                        format!("V1 g 0 {} AC {}", vgs, if gate { 1 } else { 0 }),
+                        format!("V2 d 0 {} AC {}", vds, if gate { 0 } else { 1 }),
+                        "V3 b 0 -0.5".to_string(),
+                        "M1 d g 0 b MOD L=0.5u W=5u AD=1p AS=1p PD=6u PS=6u".to_string(),
+                        model.to_string(),
+                    ];
+                    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+                    let mut mos = circuit(&lines);
+                    let branch = mos.unknowns().find("I(V2)").unwrap();
+                    let response = -ac(&mut mos, 1.0)[branch].re;
+                    assert!(
+                        (response - expected).abs() < 1e-4 * expected.abs() + 1e-10,
+                        "{} at vgs {} vds {}: {} {}",
+                        model,
+                        vgs,
+                        vds,
+                        response,
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    /// The capacitance the gate shows at `vgs` with drain, source and
+    /// bulk grounded.
+    fn gate_capacitance(model: &str, vgs: f64) -> f64 {
+        let lines = [
+            "I1 0 g 0 AC 1".to_string(),
+            "R1 g x 1".to_string(),
+            format!("V1 x 0 {}", vgs),
+            "M1 0 g 0 0 MOD L=10u W=10u".to_string(),
+            model.to_string(),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut mos = circuit(&lines);
+        let g = mos.unknowns().find("V(g)").unwrap();
+        let omega = 1e6;
+        let admittance = Complex::from(1.0) / ac(&mut mos, omega)[g] - Complex::from(1.0);
+        admittance.im / omega
+    }
+
+    #[test]
+    fn test_gate_capacitance() {
+        // The whole oxide in strong inversion and in accumulation, far
+        // less in depletion; the overlaps here are zero.
+        let model = ".model MOD NMOS(LEVEL=8 TOX=10n VTH0=0.6 CGSO=0 CGDO=0 CGBO=0 CF=0)";
+        let oxide = EPSILON_OX / 10e-9 * 100e-12;
+        for vgs in [3.0, -3.0] {
+            let c = gate_capacitance(model, vgs);
+            assert!((c / oxide - 1.0).abs() < 0.05, "{} {}", vgs, c / oxide);
+        }
+        assert!(gate_capacitance(model, 0.3) < 0.7 * oxide);
+    }
+
+    #[test]
+    fn test_charge_models() {
+        // Every CAPMOD of either version has the oxide in accumulation and
+        // strong inversion and less in depletion; the charge thickness
+        // model's centroid below the oxide takes some off in inversion.
+        let oxide = EPSILON_OX / 10e-9 * 100e-12;
+        let model = |level: u8, capmod: u8| {
+            let tox = if level == 8 { "TOX" } else { "TOXE" };
+            format!(
+                ".model MOD NMOS(LEVEL={} {}=10n VTH0=0.6 CAPMOD={} CGSO=0 CGDO=0 CGBO=0 CF=0)",
+                level, tox, capmod
+            )
+        };
+        for (level, capmods, thickness) in [(8, 0..=3, 3), (14, 0..=2, 2)] {
+            for capmod in capmods {
+                let model = model(level, capmod);
+                for vgs in [3.0, -3.0] {
+                    let c = gate_capacitance(&model, vgs) / oxide;
+                    assert!(c > 0.9 && c < 1.02, "{} at {}: {}", model, vgs, c);
+                }
+                let c = gate_capacitance(&model, 0.0) / oxide;
+                assert!(c < 0.7, "{}: {}", model, c);
+            }
+            let smooth = gate_capacitance(&model(level, thickness - 1), 3.0);
+            assert!(gate_capacitance(&model(level, thickness), 3.0) < 0.99 * smooth);
+        }
+
+        let geometry = Geometry {
+            l: 1e-6,
+            w: 1e-6,
+            ..Geometry::default()
+        };
+        let model = Model::parse(".model MOD NMOS(LEVEL=49 CAPMOD=4)").unwrap();
+        let error = Bsim3::new(&model, &geometry, 300.0, 300.0).unwrap_err();
+        assert_eq!(error, "Model MOD: CAPMOD=4 is not one of 0, 1, 2 and 3");
+        let model = Model::parse(".model MOD NMOS(LEVEL=54 CAPMOD=3)").unwrap();
+        let error = Bsim4::new(&model, &geometry, 300.0, 300.0).unwrap_err();
+        assert_eq!(error, "Model MOD: CAPMOD=3 is not one of 0, 1 and 2");
+    }
+
+    #[test]
+    fn test_binning() {
+        let models = [
+            ".model MOD.1 NMOS(LEVEL=49 LMIN=0.1u LMAX=1u WMIN=1u WMAX=100u VTH0=0.5 LVTH0=0.02)",
+            ".model MOD.2 NMOS(LEVEL=49 LMIN=1u LMAX=100u WMIN=1u WMAX=100u VTH0=0.9)",
+        ];
+        let current = |l: &str| {
+            let lines = [
+                "V1 g 0 0.8",
+                "V2 d 0 0.05",
+                &format!("M1 d g 0 0 MOD L={} W=10u", l),
+                models[0],
+                models[1],
+            ];
+            let (mos, solution) = operating_point(&lines);
+            -solution[mos.unknowns().find("I(V2)").unwrap()]
+        };
+        // Either side of LMAX: the short card's threshold, with its length
+        // term, is far the lower.
+        assert!(current("0.99u") > 10.0 * current("1.01u"));
+        let model = Model::parse(models[0]).unwrap();
+        let at = Binning::at(&model, 0.4e-6, 10e-6);
+        assert!((at.binned("vth0", 0.7) - (0.5 + 0.02 / 0.4)).abs() < 1e-12);
+        assert_eq!(at.binned("k1", 0.53), 0.53);
+        let component = Component::new("M1", Vec::new(), "d g 0 0 MOD L=1u W=10u");
+        let error = crate::devices::mosfet::build(&ElementLine::new(&component), &[]).unwrap_err();
+        assert_eq!(error, "No model MOD for L=0.000001 W=0.00001");
+    }
+
+    #[test]
+    fn test_inverter() {
+        let options = Options::default();
+        let mut inverter = circuit(&[
+            "V1 in 0 PULSE(0 1.2 1n 50p 50p 2n 5n)",
+            "V2 vdd 0 1.2",
+            "M1 out in 0 0 N L=0.1u W=1u AD=0.1p AS=0.1p PD=1.2u PS=1.2u",
+            "M2 out in vdd vdd P L=0.1u W=2u AD=0.2p AS=0.2p PD=2.2u PS=2.2u",
+            "C1 out 0 5f",
+            ".model N NMOS(LEVEL=54 TOXE=2n VTH0=0.35 U0=300 CGSO=2e-10 CGDO=2e-10)",
+            ".model P PMOS(LEVEL=54 TOXE=2n VTH0=-0.35 U0=100 CGSO=2e-10 CGDO=2e-10)",
+        ]);
+        let out = inverter.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut inverter, ".tran 5n", &options);
+        let at = |time: f64| {
+            points
+                .iter()
+                .min_by(|a, b| (a.0 - time).abs().total_cmp(&(b.0 - time).abs()))
+                .unwrap()
+                .1[out]
+        };
+        assert!(at(0.5e-9) > 1.19, "{}", at(0.5e-9));
+        assert!(at(2.5e-9) < 0.01, "{}", at(2.5e-9));
+        assert!(at(4.5e-9) > 1.19, "{}", at(4.5e-9));
+    }
+
+    #[test]
+    fn test_noise() {
+        let (circuit, _) = operating_point(&[
+            "V1 g 0 1",
+            "V2 d 0 1",
+            "M1 d g 0 0 MOD L=1u W=10u",
+            ".model MOD NMOS(LEVEL=49 NOIMOD=2)",
+        ]);
+        let device = circuit.device("M1").unwrap();
+        let at = |f: f64| {
+            let sources = device.noise(f);
+            [sources[0].density, sources[1].density]
+        };
+        // White thermal noise, flicker falling as 1/f.
+        let [thermal, low] = at(10.0);
+        let [_, high] = at(1000.0);
+        assert!(thermal > 0.0 && thermal < 1e-20);
+        assert!((low / high / 100.0 - 1.0).abs() < 1e-9, "{} {}", low, high);
+    }
+}
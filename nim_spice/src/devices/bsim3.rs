@@ -0,0 +1,822 @@
+//! The BSIM3v3.3 core, `LEVEL=8` or `49`, after the Berkeley manual and
+//! the equations of its reference `b3temp.c` and `b3ld.c`.
+//!
+//! [`Bsim3::new`] works out the parameters of one geometry and
+//! temperature from a card: the effective length and width with `LINT`,
+//! `WINT` and their size terms, the binned parameters at them, `K1` and
+//! `K2` from the doping where they are not given, and the temperature
+//! scaling of the mobility, saturation velocity, series resistance and
+//! threshold. [`Bsim3::intrinsic`] then gives the channel current with
+//! short channel, narrow width, DIBL, velocity saturation and the output
+//! resistance of channel length modulation, DIBL and substrate current
+//! induced body effect, the substrate current and the charges of
+//! `CAPMOD` 0 to 3, the charge thickness model of `CAPMOD=3` by default.
+
+use crate::devices::bsim::{
+    charges, check_card, Binning, ChargeBias, ChargeModel, Geometry, Intrinsic, NoiseBias,
+    NoiseModel, Overlap, Thickness, Version, EXP_THRESHOLD, MAX_EXP,
+};
+use crate::devices::generic::{BOLTZMANN, CHARGE};
+use crate::devices::model::Model;
+use crate::devices::mosfet::{energy_gap, EPSILON_OX, EPSILON_SI};
+use crate::maths::dual::Real;
+
+/// Binnable parameters, each with `L`, `W` and `P` terms.
+const BINNABLE: &[&str] = &[
+    "cdsc", "cdscb", "cdscd", "cit", "nfactor", "xj", "vsat", "at", "a0", "ags", "a1", "a2",
+    "keta", "nsub", "nch", "npeak", "ngate", "gamma1", "gamma2", "vbx", "vbm", "xt", "k1", "kt1",
+    "kt1l", "kt2", "k2", "k3", "k3b", "w0", "nlx", "dvt0", "dvt1", "dvt2", "dvt0w", "dvt1w",
+    "dvt2w", "drout", "dsub", "vth0", "vtho", "ua", "ua1", "ub", "ub1", "uc", "uc1", "u0", "ute",
+    "voff", "delta", "rdsw", "prwg", "prwb", "prt", "eta0", "etab", "pclm", "pdiblc1", "pdiblc2",
+    "pdiblcb", "pscbe1", "pscbe2", "pvag", "wr", "dwg", "dwb", "b0", "b1", "alpha0", "alpha1",
+    "beta0", "elm", "cgsl", "cgdl", "ckappa", "cf", "clc", "cle", "vfbcv", "noff", "voffcv",
+    "acde", "moin", "vfb",
+];
+
+/// Parameters of the whole card.
+const PARAMETERS: &[&str] = &[
+    "mobmod", "capmod", "noimod", "nqsmod", "acnqsmod", "tox", "toxm", "xpart", "lint", "ll",
+    "lln", "lw", "lwn", "lwl", "llc", "lwc", "lwlc", "wint", "wl", "wln", "ww", "wwn", "wwl",
+    "wlc", "wwc", "wwlc", "dlc", "dwc", "xl", "xw", "cgso", "cgdo", "cgbo", "rsh", "js", "jsw",
+    "pb", "mj", "pbsw", "mjsw", "pbswg", "mjswg", "cj", "cjsw", "cjswg", "nj", "xti", "ijth",
+    "tpb", "tpbsw", "tpbswg", "tcj", "tcjsw", "tcjswg", "kf", "af", "ef", "noia", "noib", "noic",
+    "em",
+];
+
+/// The parameters of a BSIM3 card, for its unknown names and values that
+/// are not numbers.
+pub(crate) fn check(model: &Model) -> Result<(), String> {
+    check_card(model, BINNABLE, PARAMETERS)?;
+    for name in ["mobmod", "capmod", "noimod"] {
+        if let Some(value) = model.number(name)? {
+            if value.fract() != 0.0 {
+                return Err(format!("Model {}: {} must be an integer", model.name, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A BSIM3 transistor of one geometry at one temperature, as an n channel.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Bsim3 {
+    pub(crate) overlap: Overlap,
+    pub(crate) noise: NoiseModel,
+    pub(crate) weff: f64,
+    leff: f64,
+    tox: f64,
+    cox: f64,
+    cox_wl: f64,
+    mobmod: u8,
+    xpart: f64,
+    vtm: f64,
+    // Threshold.
+    phi: f64,
+    sqrt_phi: f64,
+    xdep0: f64,
+    vbi: f64,
+    vbsc: f64,
+    cdep0: f64,
+    litl: f64,
+    factor1: f64,
+    vth0: f64,
+    k1: f64,
+    k1ox: f64,
+    k2ox: f64,
+    k3: f64,
+    k3b: f64,
+    w0: f64,
+    nlx: f64,
+    dvt0: f64,
+    dvt1: f64,
+    dvt2: f64,
+    dvt0w: f64,
+    dvt1w: f64,
+    dvt2w: f64,
+    kt1: f64,
+    kt1l: f64,
+    kt2: f64,
+    /// `T/TNOM - 1`.
+    temperature_ratio: f64,
+    eta0: f64,
+    etab: f64,
+    theta0vb0: f64,
+    nfactor: f64,
+    cdsc: f64,
+    cdscb: f64,
+    cdscd: f64,
+    cit: f64,
+    ngate: f64,
+    vfb: f64,
+    voff: f64,
+    // Current.
+    ua: f64,
+    ub: f64,
+    uc: f64,
+    u0: f64,
+    vsat: f64,
+    rds0: f64,
+    prwg: f64,
+    prwb: f64,
+    dwg: f64,
+    dwb: f64,
+    a0: f64,
+    ags: f64,
+    b0: f64,
+    b1: f64,
+    keta: f64,
+    xj: f64,
+    a1: f64,
+    a2: f64,
+    delta: f64,
+    pclm: f64,
+    theta_rout: f64,
+    pdiblb: f64,
+    pvag: f64,
+    pscbe1: f64,
+    pscbe2: f64,
+    alpha0: f64,
+    alpha1: f64,
+    beta0: f64,
+    // Charge.
+    charge_model: ChargeModel,
+    vfbzb: f64,
+    abulk_factor: f64,
+    noff: f64,
+    voffcv: f64,
+}
+
+/// The effective length and width of `geometry`, for the current and for
+/// the charges, with the size terms of `LINT` and `WINT`.
+pub(crate) fn effective_size(card: &Binning, geometry: &Geometry) -> [f64; 4] {
+    let l = geometry.l + card.value("xl", 0.0);
+    let w = geometry.w + card.value("xw", 0.0);
+    let lengths = |n: &str, m: &str| (l.powf(card.value(n, 1.0)), w.powf(card.value(m, 1.0)));
+    let size = |(t0, t1): (f64, f64), [a, b, c]: [&str; 3]| {
+        card.value(a, 0.0) / t0 + card.value(b, 0.0) / t1 + card.value(c, 0.0) / (t0 * t1)
+    };
+    let (t0, t1) = lengths("lln", "lwn");
+    let lint = card.value("lint", 0.0);
+    let dl = lint + size((t0, t1), ["ll", "lw", "lwl"]);
+    let dlc = card.value("dlc", lint) + size((t0, t1), ["llc", "lwc", "lwlc"]);
+    let (t2, t3) = lengths("wln", "wwn");
+    let wint = card.value("wint", 0.0);
+    let dw = wint + size((t2, t3), ["wl", "ww", "wwl"]);
+    let dwc = card.value("dwc", wint) + size((t2, t3), ["wlc", "wwc", "wwlc"]);
+    [l - 2.0 * dl, w - 2.0 * dw, l - 2.0 * dlc, w - 2.0 * dwc]
+}
+
+impl Bsim3 {
+    /// The transistor of `geometry` at `t` of a card measured at `tnom`,
+    /// both in K.
+    pub(crate) fn new(
+        model: &Model,
+        geometry: &Geometry,
+        t: f64,
+        tnom: f64,
+    ) -> Result<Self, String> {
+        let card = Binning::unbinned(model);
+        let [leff, weff, leff_cv, weff_cv] = effective_size(&card, geometry);
+        if leff <= 0.0 || weff <= 0.0 || leff_cv <= 0.0 || weff_cv <= 0.0 {
+            return Err(format!(
+                "Model {}: the effective length and width must be positive",
+                model.name
+            ));
+        }
+        let tox = card.value("tox", 1.5e-8);
+        if tox <= 0.0 {
+            return Err(format!("Model {}: TOX must be positive", model.name));
+        }
+        let toxm = card.value("toxm", tox);
+        let cox = EPSILON_OX / tox;
+        let polarity = if model.kind == "PMOS" { -1.0 } else { 1.0 };
+        let mobmod = card.value("mobmod", 1.0) as u8;
+        let p = Binning::at(model, leff, weff);
+
+        let vtm0 = BOLTZMANN * tnom / CHARGE;
+        let vtm = BOLTZMANN * t / CHARGE;
+        let ratio = t / tnom;
+        let ni = 1.45e10
+            * (tnom / 300.15)
+            * (tnom / 300.15).sqrt()
+            * (21.5565981 - energy_gap(tnom) / (2.0 * vtm0)).exp();
+
+        // Doping in cm⁻³, unless clearly given in m⁻³.
+        let per_cm3 = |n: f64, limit: f64| if n > limit { n * 1e-6 } else { n };
+        let gamma1 = p.binned_given("gamma1");
+        let npeak = match p.binned_given("nch").or(p.binned_given("npeak")) {
+            Some(npeak) => per_cm3(npeak, 1e20),
+            None => match gamma1 {
+                Some(gamma1) => 3.021e22 * (gamma1 * cox).powi(2),
+                None => 1.7e17,
+            },
+        };
+        let nsub = p.binned("nsub", 6e16);
+        let ngate = per_cm3(p.binned("ngate", 0.0), 1e23);
+
+        let phi = 2.0 * vtm0 * (npeak / ni).ln();
+        let sqrt_phi = phi.sqrt();
+        let xdep0 = (2.0 * EPSILON_SI / (CHARGE * npeak * 1e6)).sqrt() * sqrt_phi;
+        let xj = p.binned("xj", 1.5e-7);
+        let litl = (3.0 * xj * tox).sqrt();
+        let vbi = vtm0 * (1e20 * npeak / (ni * ni)).ln();
+        let cdep0 = (CHARGE * EPSILON_SI * npeak * 1e6 / 2.0 / phi).sqrt();
+
+        let vbm = -p.binned("vbm", -3.0).abs();
+        let (k1, k2) = match (p.binned_given("k1"), p.binned_given("k2")) {
+            (None, None) => {
+                let xt = p.binned("xt", 1.55e-7);
+                let vbx = -p
+                    .binned_given("vbx")
+                    .unwrap_or(phi - 7.7348e-4 * npeak * xt * xt)
+                    .abs();
+                let gamma1 = gamma1.unwrap_or(5.753e-12 * npeak.sqrt() / cox);
+                let gamma2 = p.binned("gamma2", 5.753e-12 * nsub.sqrt() / cox);
+                let t1 = (phi - vbx).sqrt() - sqrt_phi;
+                let t2 = (phi * (phi - vbm)).sqrt() - phi;
+                let k2 = (gamma1 - gamma2) * t1 / (2.0 * t2 + vbm);
+                (gamma2 - 2.0 * k2 * (phi - vbm).sqrt(), k2)
+            }
+            (k1, k2) => (k1.unwrap_or(0.53), k2.unwrap_or(-0.0186)),
+        };
+        let vbsc = if k2 < 0.0 {
+            let t0 = 0.5 * k1 / k2;
+            (0.9 * (phi - t0 * t0)).clamp(-30.0, -3.0)
+        } else {
+            -30.0
+        }
+        .min(vbm);
+
+        let vth0 = p
+            .binned_given("vth0")
+            .or(p.binned_given("vtho"))
+            .map(|vth0| polarity * vth0);
+        let vfb = p
+            .binned_given("vfb")
+            .unwrap_or(vth0.map_or(-1.0, |vth0| vth0 - phi - k1 * sqrt_phi));
+        let vth0 = vth0.unwrap_or(vfb + phi + k1 * sqrt_phi);
+        let k1ox = k1 * tox / toxm;
+        let k2ox = k2 * tox / toxm;
+
+        // Short channel and DIBL at zero bias, as b3temp.c.
+        let factor1 = (EPSILON_SI / EPSILON_OX * tox).sqrt();
+        let lt0 = (EPSILON_SI / EPSILON_OX * tox * xdep0).sqrt();
+        let theta = |x: f64| {
+            let t0 = (-0.5 * x).max(-EXP_THRESHOLD).exp();
+            t0 + 2.0 * t0 * t0
+        };
+        let drout = p.binned("drout", 0.56);
+        let theta0vb0 = theta(p.binned("dsub", drout) * leff / lt0);
+        let pdiblc1 = p.binned("pdiblc1", 0.39);
+        let pdiblc2 = p.binned("pdiblc2", 0.0086);
+        let theta_rout = pdiblc1 * theta(drout * leff / lt0) + pdiblc2;
+
+        let dvt0 = p.binned("dvt0", 2.2);
+        let dvt1 = p.binned("dvt1", 0.53);
+        let dvt0w = p.binned("dvt0w", 0.0);
+        let dvt1w = p.binned("dvt1w", 5.3e6);
+        let k3 = p.binned("k3", 80.0);
+        let w0 = p.binned("w0", 2.5e-6);
+        let nlx = p.binned("nlx", 1.74e-7);
+        let kt1 = p.binned("kt1", -0.11);
+        let kt1l = p.binned("kt1l", 0.0);
+        let zero_bias = factor1 * xdep0.sqrt();
+        let narrow = dvt0w * theta(dvt1w * weff * leff / zero_bias) * (vbi - phi);
+        let short = dvt0 * theta(dvt1 * leff / zero_bias) * (vbi - phi);
+        let t4 = tox * phi / (weff + w0);
+        let t5 = k1ox * ((1.0 + nlx / leff).sqrt() - 1.0) * sqrt_phi
+            + (kt1 + kt1l / leff) * (ratio - 1.0);
+        let vfbzb = vth0 - narrow - short + k3 * t4 + t5 - phi - k1 * sqrt_phi;
+
+        // Temperature.
+        let dt = ratio - 1.0;
+        let u0 = p.binned("u0", if polarity > 0.0 { 0.067 } else { 0.025 });
+        let u0 = if u0 > 1.0 { u0 * 1e-4 } else { u0 };
+        let (uc, uc1) = if mobmod == 3 {
+            (-0.0465, -0.056)
+        } else {
+            (-4.65e-11, -5.6e-11)
+        };
+        let rdsw = p.binned("rdsw", 0.0) + p.binned("prt", 0.0) * dt;
+        let wr = p.binned("wr", 1.0);
+
+        // Overlaps, the fringe included.
+        let cgsl = p.binned("cgsl", 0.0);
+        let cgdl = p.binned("cgdl", 0.0);
+        let cf = p.binned(
+            "cf",
+            2.0 * EPSILON_OX / std::f64::consts::PI * (1.0 + 0.4e-6 / tox).ln(),
+        );
+        let dlc = card.get("dlc");
+        let dwc = card.value("dwc", card.value("wint", 0.0));
+        let side = |given: &str, lightly_doped: f64| {
+            card.get(given).unwrap_or(match dlc {
+                Some(dlc) if dlc > 0.0 => (dlc * cox - lightly_doped).max(0.0),
+                _ => 0.6 * xj * cox,
+            })
+        };
+        let ckappa = p.binned("ckappa", 0.6);
+        let overlap = Overlap {
+            cgso: side("cgso", cgsl) + cf,
+            cgdo: side("cgdo", cgdl) + cf,
+            cgbo: card.value("cgbo", 2.0 * dwc * cox),
+            cgsl,
+            cgdl,
+            ckappas: ckappa,
+            ckappad: ckappa,
+            weff: weff_cv,
+            leff: leff_cv,
+        };
+
+        let thickness = Thickness {
+            cox,
+            toxp: tox,
+            coxp: cox,
+            ldeb: (EPSILON_SI * vtm0 / (CHARGE * npeak * 1e6)).sqrt() / 3.0,
+            acde: p.binned("acde", 1.0),
+            moin: p.binned("moin", 15.0),
+            ados: 1.0,
+            bdos: 1.0,
+            vtfbphi2: None,
+        };
+        let capmod = card.value("capmod", 3.0);
+        let Some(charge_model) =
+            ChargeModel::of(Version::Bsim3, capmod, p.binned("vfbcv", -1.0), thickness)
+        else {
+            return Err(format!(
+                "Model {}: CAPMOD={} is not one of 0, 1, 2 and 3",
+                model.name, capmod
+            ));
+        };
+
+        let noimod = card.value("noimod", 1.0) as u8;
+        let noise = NoiseModel {
+            unified_flicker: noimod == 2 || noimod == 3,
+            charge_thermal: noimod == 2 || noimod == 4,
+            kf: card.value("kf", 0.0),
+            af: card.value("af", 1.0),
+            ef: card.value("ef", 1.0),
+            noia: card.value("noia", if polarity > 0.0 { 1e20 } else { 9.9e18 }),
+            noib: card.value("noib", if polarity > 0.0 { 5e4 } else { 2.4e3 }),
+            noic: card.value("noic", if polarity > 0.0 { -1.4e-12 } else { 1.4e-12 }),
+            em: card.value("em", 4.1e7),
+            energy: BOLTZMANN / CHARGE,
+            strong_scale: 1e8,
+            weak_scale: 4e36,
+            cox,
+            leff,
+            weff,
+            litl,
+        };
+
+        Ok(Bsim3 {
+            overlap,
+            noise,
+            weff,
+            leff,
+            tox,
+            cox,
+            cox_wl: cox * weff_cv * leff_cv,
+            mobmod,
+            xpart: card.value("xpart", 0.0),
+            vtm,
+            phi,
+            sqrt_phi,
+            xdep0,
+            vbi,
+            vbsc,
+            cdep0,
+            litl,
+            factor1,
+            vth0,
+            k1,
+            k1ox,
+            k2ox,
+            k3,
+            k3b: p.binned("k3b", 0.0),
+            w0,
+            nlx,
+            dvt0,
+            dvt1,
+            dvt2: p.binned("dvt2", -0.032),
+            dvt0w,
+            dvt1w,
+            dvt2w: p.binned("dvt2w", -0.032),
+            kt1,
+            kt1l,
+            kt2: p.binned("kt2", 0.022),
+            temperature_ratio: dt,
+            eta0: p.binned("eta0", 0.08),
+            etab: p.binned("etab", -0.07),
+            theta0vb0,
+            nfactor: p.binned("nfactor", 1.0),
+            cdsc: p.binned("cdsc", 2.4e-4),
+            cdscb: p.binned("cdscb", 0.0),
+            cdscd: p.binned("cdscd", 0.0),
+            cit: p.binned("cit", 0.0),
+            ngate,
+            vfb,
+            voff: p.binned("voff", -0.08),
+            ua: p.binned("ua", 2.25e-9) + p.binned("ua1", 4.31e-9) * dt,
+            ub: p.binned("ub", 5.87e-19) + p.binned("ub1", -7.61e-18) * dt,
+            uc: p.binned("uc", uc) + p.binned("uc1", uc1) * dt,
+            u0: u0 * ratio.powf(p.binned("ute", -1.5)),
+            vsat: p.binned("vsat", 8e4) - p.binned("at", 3.3e4) * dt,
+            rds0: rdsw / (weff * 1e6).powf(wr),
+            prwg: p.binned("prwg", 0.0),
+            prwb: p.binned("prwb", 0.0),
+            dwg: p.binned("dwg", 0.0),
+            dwb: p.binned("dwb", 0.0),
+            a0: p.binned("a0", 1.0),
+            ags: p.binned("ags", 0.0),
+            b0: p.binned("b0", 0.0),
+            b1: p.binned("b1", 0.0),
+            keta: p.binned("keta", -0.047),
+            xj,
+            a1: p.binned("a1", 0.0),
+            a2: p.binned("a2", 1.0),
+            delta: p.binned("delta", 0.01),
+            pclm: p.binned("pclm", 1.3),
+            theta_rout,
+            pdiblb: p.binned("pdiblcb", 0.0),
+            pvag: p.binned("pvag", 0.0),
+            pscbe1: p.binned("pscbe1", 4.24e8),
+            pscbe2: p.binned("pscbe2", 1e-5),
+            alpha0: p.binned("alpha0", 0.0),
+            alpha1: p.binned("alpha1", 0.0),
+            beta0: p.binned("beta0", 30.0),
+            charge_model,
+            vfbzb,
+            abulk_factor: 1.0 + (p.binned("clc", 0.1e-6) / leff_cv).powf(p.binned("cle", 0.6)),
+            noff: p.binned("noff", 1.0),
+            voffcv: p.binned("voffcv", 0.0),
+        })
+    }
+
+    /// The transistor at `vgs`, `vds ≥ 0` and `vbs`.
+    pub(crate) fn intrinsic<T: Real>(&self, vgs: T, vds: T, vbs: T) -> Intrinsic<T> {
+        let c = T::constant;
+        let one = c(1.0);
+
+        // The bulk bias, kept above VBSC, and the surface potential.
+        let t0 = vbs - self.vbsc - 0.001;
+        let t1 = (t0 * t0 - 0.004 * self.vbsc).sqrt();
+        let vbseff = ((t0 + t1) * 0.5 + self.vbsc).max(vbs);
+        let sqrt_phis = if vbseff.value() > 0.0 {
+            c(self.phi * self.sqrt_phi) / (vbseff * 0.5 + self.phi)
+        } else {
+            (-vbseff + self.phi).sqrt()
+        };
+        let xdep = sqrt_phis * (self.xdep0 / self.sqrt_phi);
+
+        // Threshold.
+        let t3 = xdep.sqrt();
+        let v0 = self.vbi - self.phi;
+        let soft = |t0: T| {
+            if t0.value() >= -0.5 {
+                t0 + 1.0
+            } else {
+                (t0 * 3.0 + 1.0) / (t0 * 8.0 + 3.0)
+            }
+        };
+        let theta = |t0: T| {
+            if t0.value() > -EXP_THRESHOLD {
+                let t1 = t0.exp();
+                t1 * (t1 * 2.0 + 1.0)
+            } else {
+                let min = (-EXP_THRESHOLD).exp();
+                c(min * (1.0 + 2.0 * min))
+            }
+        };
+        let lt1 = t3 * soft(vbseff * self.dvt2) * self.factor1;
+        let ltw = t3 * soft(vbseff * self.dvt2w) * self.factor1;
+        let theta0 = theta(c(-0.5 * self.dvt1 * self.leff) / lt1);
+        let delt_vth = theta0 * (self.dvt0 * v0);
+        let narrow = theta(c(-0.5 * self.dvt1w * self.weff * self.leff) / ltw) * (self.dvt0w * v0);
+        let temperature = (vbseff * self.kt2 + self.kt1 + self.kt1l / self.leff)
+            * self.temperature_ratio
+            + self.k1ox * ((1.0 + self.nlx / self.leff).sqrt() - 1.0) * self.sqrt_phi;
+        let t3 = vbseff * self.etab + self.eta0;
+        let t3 = if t3.value() < 1e-4 {
+            (-t3 + 2e-4) / (t3 * -2e4 + 3.0)
+        } else {
+            t3
+        };
+        let dibl = t3 * self.theta0vb0 * vds;
+        let tmp2 = self.tox * self.phi / (self.weff + self.w0);
+        let vth = sqrt_phis * self.k1ox - vbseff * self.k2ox - delt_vth - narrow
+            + (vbseff * self.k3b + self.k3) * tmp2
+            + temperature
+            - dibl
+            + (self.vth0 - self.k1 * self.sqrt_phi);
+
+        // Subthreshold swing.
+        let tmp2 = c(self.nfactor * EPSILON_SI) / xdep;
+        let tmp3 = vbseff * self.cdscb + vds * self.cdscd + self.cdsc;
+        let tmp4 = (tmp2 + tmp3 * theta0 + self.cit) / self.cox;
+        let n = if tmp4.value() >= -0.5 {
+            tmp4 + 1.0
+        } else {
+            (tmp4 * 3.0 + 1.0) / (tmp4 * 8.0 + 3.0)
+        };
+
+        // Poly gate depletion.
+        let vgs_eff = if self.ngate > 1e18 && self.ngate < 1e25 && vgs.value() > self.vfb + self.phi
+        {
+            let t1 = 1e6 * CHARGE * EPSILON_SI * self.ngate / (self.cox * self.cox);
+            let t4 = ((vgs - self.vfb - self.phi) * (2.0 / t1) + 1.0).sqrt();
+            let t2 = (t4 - 1.0) * t1;
+            let t7 = -(t2 * t2 * (0.5 / t1)) + (1.12 - 0.05);
+            let t6 = (t7 * t7 + 0.224).sqrt();
+            vgs - (-(t7 + t6) * 0.5 + 1.12)
+        } else {
+            vgs
+        };
+
+        // The effective gate drive, smooth through the threshold.
+        let vtm = self.vtm;
+        let vgst = vgs_eff - vth;
+        let t10 = n * (2.0 * vtm);
+        let vgst_nvt = vgst / t10;
+        let exp_arg = (-vgst + 2.0 * self.voff) / t10;
+        let vgsteff = if vgst_nvt.value() > EXP_THRESHOLD {
+            vgst
+        } else if exp_arg.value() > EXP_THRESHOLD {
+            ((vgst - self.voff) / (n * vtm)).exp() * (vtm * self.cdep0 / self.cox)
+        } else {
+            let t1 = t10 * (vgst_nvt.exp() + 1.0).ln();
+            let t2 = one + t10 * exp_arg.exp() * (self.cox / (vtm * self.cdep0));
+            t1 / t2
+        };
+
+        // Bias dependent width and series resistance.
+        let t9 = sqrt_phis - self.sqrt_phi;
+        let weff = -(vgsteff * self.dwg + t9 * self.dwb) * 2.0 + self.weff;
+        let weff = if weff.value() < 2e-8 {
+            (-weff + 4e-8) * 2e-8 / (-weff * 2.0 + 6e-8)
+        } else {
+            weff
+        };
+        let t0 = vgsteff * self.prwg + t9 * self.prwb;
+        let rds = if t0.value() >= -0.9 {
+            (t0 + 1.0) * self.rds0
+        } else {
+            (t0 + 0.8) * self.rds0 / (t0 * 20.0 + 17.0)
+        };
+
+        // Bulk charge factor.
+        let t1 = c(0.5 * self.k1ox) / sqrt_phis;
+        let t9 = (xdep * self.xj).sqrt();
+        let t5 = c(self.leff) / (t9 * 2.0 + self.leff);
+        let t2 = t5 * self.a0 + self.b0 / (self.weff + self.b1);
+        let abulk0 = t1 * t2 + 1.0;
+        let abulk = abulk0 - t1 * t5 * t5 * t5 * (self.ags * self.a0) * vgsteff;
+        let floor = |a: T| {
+            if a.value() < 0.1 {
+                (-a + 0.2) / (a * -20.0 + 3.0)
+            } else {
+                a
+            }
+        };
+        let t2 = vbseff * self.keta;
+        let keta = if t2.value() >= -0.9 {
+            one / (t2 + 1.0)
+        } else {
+            (t2 * 20.0 + 17.0) / (t2 + 0.8)
+        };
+        let abulk0 = floor(abulk0) * keta;
+        let abulk = floor(abulk) * keta;
+
+        // Mobility.
+        let t5 = match self.mobmod {
+            2 => {
+                vgsteff / self.tox * ((vbseff * self.uc + self.ua) + vgsteff * (self.ub / self.tox))
+            }
+            3 => {
+                let t3 = (vgsteff + vth * 2.0) / self.tox;
+                t3 * (t3 * self.ub + self.ua) * (vbseff * self.uc + 1.0)
+            }
+            _ => {
+                let t3 = (vgsteff + vth * 2.0) / self.tox;
+                t3 * ((vbseff * self.uc + self.ua) + t3 * self.ub)
+            }
+        };
+        let denominator = if t5.value() >= -0.8 {
+            t5 + 1.0
+        } else {
+            (t5 + 0.6) / (t5 * 10.0 + 7.0)
+        };
+        let ueff = c(self.u0) / denominator;
+
+        // Saturation.
+        let wv_cox = weff * (self.vsat * self.cox);
+        let wv_cox_rds = wv_cox * rds;
+        let esat = c(2.0 * self.vsat) / ueff;
+        let esat_l = esat * self.leff;
+        let lambda = if self.a1 == 0.0 {
+            c(self.a2)
+        } else if self.a1 > 0.0 {
+            let t0 = 1.0 - self.a2;
+            let t1 = -(vgsteff * self.a1) + (t0 - 0.0001);
+            let t2 = (t1 * t1 + 0.0004 * t0).sqrt();
+            -(t1 + t2) * 0.5 + (self.a2 + t0)
+        } else {
+            let t1 = vgsteff * self.a1 + (self.a2 - 0.0001);
+            (t1 + (t1 * t1 + 0.0004 * self.a2).sqrt()) * 0.5
+        };
+        let vgst2vtm = vgsteff + 2.0 * vtm;
+        let vdsat = if self.rds0 == 0.0 && lambda.value() == 1.0 {
+            esat_l * vgst2vtm / (abulk * esat_l + vgst2vtm)
+        } else {
+            let t9 = abulk * wv_cox_rds;
+            let t7 = vgst2vtm * t9;
+            let t6 = vgst2vtm * wv_cox_rds;
+            let t0 = abulk * (t9 - 1.0 + one / lambda) * 2.0;
+            let t1 = vgst2vtm * (c(2.0) / lambda - 1.0) + abulk * esat_l + t7 * 3.0;
+            let t2 = vgst2vtm * (esat_l + t6 * 2.0);
+            let t3 = (t1 * t1 - t0 * t2 * 2.0).sqrt();
+            (t1 - t3) / t0
+        };
+        let t1 = vdsat - vds - self.delta;
+        let t2 = (t1 * t1 + vdsat * (4.0 * self.delta)).sqrt();
+        let vdseff = (vdsat - (t1 + t2) * 0.5).min(vds);
+        let diff_vds = vds - vdseff;
+
+        // Output resistance.
+        let tmp4 = one - abulk * vdsat * 0.5 / vgst2vtm;
+        let t0 = esat_l + vdsat + wv_cox_rds * vgsteff * tmp4 * 2.0;
+        let t1 = c(2.0) / lambda - 1.0 + wv_cox_rds * abulk;
+        let vasat = t0 / t1;
+        let vaclm = if self.pclm > 0.0 && diff_vds.value() > 1e-10 {
+            (abulk + vgsteff / esat_l) * self.leff / (abulk * (self.pclm * self.litl)) * diff_vds
+        } else {
+            c(MAX_EXP)
+        };
+        let vadibl = if self.theta_rout > 0.0 {
+            let t8 = abulk * vdsat;
+            let vadibl = (vgst2vtm - vgst2vtm * t8 / (vgst2vtm + t8)) / self.theta_rout;
+            let t7 = vbseff * self.pdiblb;
+            if t7.value() >= -0.9 {
+                vadibl / (t7 + 1.0)
+            } else {
+                vadibl * (t7 * 20.0 + 17.0) / (t7 + 0.8)
+            }
+        } else {
+            c(MAX_EXP)
+        };
+        let t9 = vgsteff * c(self.pvag) / esat_l;
+        let t0 = if t9.value() > -0.9 {
+            t9 + 1.0
+        } else {
+            (t9 + 0.8) / (t9 * 20.0 + 17.0)
+        };
+        let va = vasat + t0 * vaclm * vadibl / (vaclm + vadibl);
+        let vascbe = if self.pscbe2 > 0.0 {
+            if diff_vds.value() > self.pscbe1 * self.litl / EXP_THRESHOLD {
+                (c(self.pscbe1 * self.litl) / diff_vds).exp() * (self.leff / self.pscbe2)
+            } else {
+                c(MAX_EXP * self.leff / self.pscbe2)
+            }
+        } else {
+            c(MAX_EXP)
+        };
+
+        // Current.
+        let beta = ueff * weff * (self.cox / self.leff);
+        let fgche1 = vgsteff * (one - vdseff * abulk * 0.5 / vgst2vtm);
+        let fgche2 = vdseff / esat_l + 1.0;
+        let gche = beta * fgche1 / fgche2;
+        let idl = gche * vdseff / (gche * rds + 1.0);
+        let idsa = idl * (diff_vds / va + 1.0);
+        let ids = idsa * (diff_vds / vascbe + 1.0);
+
+        // Substrate current by impact ionisation.
+        let alpha = self.alpha0 + self.alpha1 * self.leff;
+        let isub = if alpha <= 0.0 || self.beta0 <= 0.0 {
+            c(0.0)
+        } else if diff_vds.value() > self.beta0 / EXP_THRESHOLD {
+            diff_vds * (alpha / self.leff) * (c(-self.beta0) / diff_vds).exp() * idsa
+        } else {
+            diff_vds * (alpha / self.leff * (-EXP_THRESHOLD).exp()) * idsa
+        };
+
+        let charges = charges(
+            &self.charge_model,
+            ChargeBias {
+                vgs: vgs_eff,
+                vth,
+                vbs: vbseff,
+                vds,
+                sqrt_phis,
+                phi: self.phi,
+                vfb: self.vfbzb,
+                k1ox: self.k1ox,
+                abulk0,
+                n,
+                vtm,
+                cox_wl: self.cox_wl,
+                abulk_factor: self.abulk_factor,
+                noff: self.noff,
+                voffcv: self.voffcv,
+                xpart: self.xpart,
+            },
+        );
+
+        Intrinsic {
+            ids,
+            idb: isub,
+            charges,
+            von: vth.value(),
+            vdsat: vdsat.value(),
+            noise: NoiseBias {
+                vgsteff: vgsteff.value(),
+                vdseff: vdseff.value(),
+                vds: vds.value(),
+                abulk: abulk.value(),
+                vgst2vtm: vgst2vtm.value(),
+                ueff: ueff.value(),
+                esat: esat.value(),
+                qinv: self.cox_wl * fgche1.value(),
+                rds: rds.value(),
+                nstar: 2e14,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::bsim::tests::reference;
+    use crate::devices::generic::KELVIN;
+
+    fn core(card: &str, l: f64, w: f64, celsius: f64) -> Bsim3 {
+        let model = Model::parse(card).unwrap();
+        check(&model).unwrap();
+        let geometry = Geometry {
+            l,
+            w,
+            ..Geometry::default()
+        };
+        Bsim3::new(&model, &geometry, celsius + KELVIN, 27.0 + KELVIN).unwrap()
+    }
+
+    #[test]
+    fn test_square_law() {
+        // Without body effect, short channel effects, mobility degradation
+        // or velocity saturation a long channel follows the square law.
+        let card = ".model MOD NMOS(LEVEL=49 TOX=10n VTH0=0.5 K1=0 K2=0 K3=0 NLX=0 DVT0=0 DVT0W=0 ETA0=0 A0=0 KETA=0 UA=0 UB=0 UC=0 U0=500 VSAT=1e10 RDSW=0 PCLM=0 PDIBLC1=0 PDIBLC2=0 PSCBE2=0)";
+        let bsim = core(card, 100e-6, 100e-6, 27.0);
+        let beta = 500e-4 * EPSILON_OX / 10e-9;
+        let vt = BOLTZMANN * (27.0 + KELVIN) / CHARGE;
+        let ids = |vgs: f64, vds: f64| bsim.intrinsic(vgs, vds, 0.0).ids;
+        let saturated = beta / 2.0 * 2.0 * (2.0 + 2.0 * vt);
+        assert!(
+            (ids(2.5, 3.0) / saturated - 1.0).abs() < 0.01,
+            "{}",
+            ids(2.5, 3.0)
+        );
+        let linear = beta * 2.0 * 0.05 * (1.0 - 0.05 / (2.0 * (2.0 + 2.0 * vt)));
+        assert!(
+            (ids(2.5, 0.05) / linear - 1.0).abs() < 0.01,
+            "{}",
+            ids(2.5, 0.05)
+        );
+        assert_eq!(bsim.intrinsic(2.5, 3.0, 0.0).von, 0.5);
+    }
+
+    #[test]
+    fn test_subthreshold() {
+        // An exponential below threshold with a swing of n kT/q per e.
+        let bsim = core(
+            ".model MOD NMOS(LEVEL=49 TOX=5n VTH0=0.5)",
+            1e-6,
+            10e-6,
+            27.0,
+        );
+        let ids = |vgs: f64| bsim.intrinsic(vgs, 1.0, 0.0).ids;
+        let swing = 0.1 / (ids(0.2) / ids(0.1)).log10();
+        assert!(swing > 0.06 && swing < 0.1, "{}", swing);
+        assert!(ids(1.5) > 1e4 * ids(0.2));
+    }
+
+    #[test]
+    fn test_temperature() {
+        // Mobility falls with temperature faster than the threshold.
+        let card = ".model MOD NMOS(LEVEL=49 TOX=5n VTH0=0.5)";
+        let cold = core(card, 1e-6, 10e-6, 27.0).intrinsic(2.0, 2.0, 0.0);
+        let hot = core(card, 1e-6, 10e-6, 127.0).intrinsic(2.0, 2.0, 0.0);
+        assert!(hot.ids < 0.9 * cold.ids && hot.von < cold.von);
+    }
+
+    #[test]
+    fn test_reference() {
+        reference(
+            include_str!("../../tests/fixtures/bsim3.csv"),
+            |model, geometry, celsius, [vgs, vds, vbs]| {
+                let bsim = Bsim3::new(model, geometry, celsius + KELVIN, 27.0 + KELVIN).unwrap();
+                bsim.intrinsic(vgs, vds, vbs)
+            },
+        );
+    }
+}
@@ -0,0 +1,1024 @@
+//! The BSIM4 core, `LEVEL=14` or `54`, after the Berkeley BSIM4 manual.
+//!
+//! It keeps BSIM3's structure and adds what BSIM4 changed of the DC
+//! model: the threshold's short channel terms in their `cosh` form, the
+//! lateral doping of `LPE0` and `LPEB` and the pocket implant's DITS of
+//! `DVTP0` and `DVTP1`, the subthreshold swing's `MINV`, the mobility
+//! models of `MOBMOD` 0 to 2, the series resistance's `RDSWMIN`, the
+//! output conductance's `FPROUT` and `PDITS` terms and gate induced drain
+//! leakage. The charges are those of `CAPMOD` 0 to 2, shared with BSIM3,
+//! the charge thickness model of `CAPMOD=2` by default with the physical
+//! oxide thickness `TOXP` and its `ADOS` and `BDOS`. Gate tunnelling, layout stress, the
+//! well proximity effect and the series resistance as nodes of
+//! `RDSMOD=1` are accepted on the card and not modelled.
+
+use crate::devices::bsim::{
+    charges, check_card, Binning, ChargeBias, ChargeModel, Geometry, Intrinsic, NoiseBias,
+    NoiseModel, Overlap, Thickness, Version, EXP_THRESHOLD, MAX_EXP,
+};
+use crate::devices::bsim3::effective_size;
+use crate::devices::generic::{BOLTZMANN, CHARGE};
+use crate::devices::model::Model;
+use crate::devices::mosfet::{energy_gap, EPSILON_0, EPSILON_SI};
+use crate::maths::dual::Real;
+
+/// Binnable parameters, each with `L`, `W` and `P` terms.
+const BINNABLE: &[&str] = &[
+    "cdsc",
+    "cdscb",
+    "cdscd",
+    "cit",
+    "nfactor",
+    "xj",
+    "vsat",
+    "at",
+    "a0",
+    "ags",
+    "a1",
+    "a2",
+    "keta",
+    "nsub",
+    "ndep",
+    "nsd",
+    "phin",
+    "ngate",
+    "gamma1",
+    "gamma2",
+    "vbx",
+    "vbm",
+    "xt",
+    "k1",
+    "kt1",
+    "kt1l",
+    "kt2",
+    "k2",
+    "k3",
+    "k3b",
+    "w0",
+    "dvtp0",
+    "dvtp1",
+    "lpe0",
+    "lpeb",
+    "dvt0",
+    "dvt1",
+    "dvt2",
+    "dvt0w",
+    "dvt1w",
+    "dvt2w",
+    "drout",
+    "dsub",
+    "vth0",
+    "vtho",
+    "ua",
+    "ua1",
+    "ub",
+    "ub1",
+    "uc",
+    "uc1",
+    "ud",
+    "ud1",
+    "up",
+    "lp",
+    "u0",
+    "eu",
+    "ute",
+    "voff",
+    "minv",
+    "voffl",
+    "delta",
+    "rdsw",
+    "rdswmin",
+    "rdw",
+    "rdwmin",
+    "rsw",
+    "rswmin",
+    "prwg",
+    "prwb",
+    "prt",
+    "eta0",
+    "etab",
+    "pclm",
+    "pdiblc1",
+    "pdiblc2",
+    "pdiblcb",
+    "fprout",
+    "pdits",
+    "pditsd",
+    "pscbe1",
+    "pscbe2",
+    "pvag",
+    "wr",
+    "dwg",
+    "dwb",
+    "b0",
+    "b1",
+    "alpha0",
+    "alpha1",
+    "beta0",
+    "agidl",
+    "bgidl",
+    "cgidl",
+    "egidl",
+    "agisl",
+    "bgisl",
+    "cgisl",
+    "egisl",
+    "aigc",
+    "bigc",
+    "cigc",
+    "aigsd",
+    "bigsd",
+    "cigsd",
+    "aigbacc",
+    "bigbacc",
+    "cigbacc",
+    "aigbinv",
+    "bigbinv",
+    "cigbinv",
+    "nigc",
+    "nigbinv",
+    "nigbacc",
+    "ntox",
+    "eigbinv",
+    "pigcd",
+    "poxedge",
+    "xrcrg1",
+    "xrcrg2",
+    "lambda",
+    "vtl",
+    "xn",
+    "lc",
+    "elm",
+    "cgsl",
+    "cgdl",
+    "ckappas",
+    "ckappad",
+    "cf",
+    "clc",
+    "cle",
+    "vfbcv",
+    "noff",
+    "voffcv",
+    "acde",
+    "moin",
+    "vfb",
+    "tvoff",
+    "tvfbsdoff",
+    "kvth0we",
+    "k2we",
+    "ku0we",
+];
+
+/// Parameters of the whole card.
+const PARAMETERS: &[&str] = &[
+    "mobmod", "capmod", "rdsmod", "igcmod", "igbmod", "rbodymod", "rgatemod", "trnqsmod",
+    "acnqsmod", "fnoimod", "tnoimod", "diomod", "tempmod", "permod", "geomod", "rgeomod",
+    "gidlmod", "mtrlmod", "toxe", "toxp", "toxm", "dtox", "ados", "bdos", "epsrox", "toxref",
+    "xpart", "lint", "ll", "lln", "lw", "lwn", "lwl", "llc", "lwc", "lwlc", "wint", "wl", "wln",
+    "ww", "wwn", "wwl", "wlc", "wwc", "wwlc", "dlc", "dwc", "dlcig", "dlcigd", "dwj", "xl", "xw",
+    "lintnoi", "cgso", "cgdo", "cgbo", "rsh", "rshg", "xgw", "xgl", "ngcon", "dmcg", "dmci",
+    "dmdg", "dmcgt", "pditsl", "xjbvs", "xjbvd", "bvs", "bvd", "jss", "jsd", "jsws", "jswd",
+    "jswgs", "jswgd", "njs", "njd", "xtis", "xtid", "ijthsfwd", "ijthdfwd", "ijthsrev", "ijthdrev",
+    "pbs", "pbd", "mjs", "mjd", "pbsws", "pbswd", "mjsws", "mjswd", "pbswgs", "pbswgd", "mjswgs",
+    "mjswgd", "cjs", "cjd", "cjsws", "cjswd", "cjswgs", "cjswgd", "tpb", "tpbsw", "tpbswg", "tcj",
+    "tcjsw", "tcjswg", "kf", "af", "ef", "noia", "noib", "noic", "em", "ntnoi", "tnoia", "tnoib",
+    "rnoia", "rnoib", "gbmin", "rbpb", "rbpd", "rbps", "rbdb", "rbsb", "saref", "sbref", "wlod",
+    "ku0", "kvsat", "kvth0", "tku0", "llodku0", "wlodku0", "llodvth", "wlodvth", "lku0", "wku0",
+    "pku0", "lkvth0", "wkvth0", "pkvth0", "stk2", "lodk2", "steta0", "lodeta0", "web", "wec",
+    "scref", "wpemod",
+];
+
+/// The parameters of a BSIM4 card, for its unknown names and values that
+/// are not numbers.
+pub(crate) fn check(model: &Model) -> Result<(), String> {
+    check_card(model, BINNABLE, PARAMETERS)?;
+    for name in ["mobmod", "capmod", "fnoimod", "tnoimod"] {
+        if let Some(value) = model.number(name)? {
+            if value.fract() != 0.0 {
+                return Err(format!("Model {}: {} must be an integer", model.name, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `1 / (2 (cosh x - 1))`, BSIM4's short channel factor, kept finite at
+/// short lengths.
+fn short_channel<T: Real>(x: T) -> T {
+    if x.value() < EXP_THRESHOLD {
+        let t1 = x.exp();
+        let t2 = t1 - 1.0;
+        t1 / (t2 * t2 + t1 * (2.0 * (-EXP_THRESHOLD).exp()))
+    } else {
+        T::constant(1.0 / (MAX_EXP - 2.0))
+    }
+}
+
+/// A BSIM4 transistor of one geometry at one temperature, as an n channel.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Bsim4 {
+    pub(crate) overlap: Overlap,
+    pub(crate) noise: NoiseModel,
+    pub(crate) weff: f64,
+    leff: f64,
+    toxe: f64,
+    coxe: f64,
+    cox_wl: f64,
+    mobmod: u8,
+    xpart: f64,
+    vtm: f64,
+    // Threshold.
+    phi: f64,
+    sqrt_phi: f64,
+    xdep0: f64,
+    vbi: f64,
+    vbsc: f64,
+    cdep0: f64,
+    litl: f64,
+    factor1: f64,
+    vth0: f64,
+    k1: f64,
+    k1ox: f64,
+    k2ox: f64,
+    k3: f64,
+    k3b: f64,
+    w0: f64,
+    lpe0: f64,
+    lpeb: f64,
+    dvtp0: f64,
+    dvtp1: f64,
+    dvt0: f64,
+    dvt1: f64,
+    dvt2: f64,
+    dvt0w: f64,
+    dvt1w: f64,
+    dvt2w: f64,
+    kt1: f64,
+    kt1l: f64,
+    kt2: f64,
+    temperature_ratio: f64,
+    eta0: f64,
+    etab: f64,
+    theta0vb0: f64,
+    nfactor: f64,
+    cdsc: f64,
+    cdscb: f64,
+    cdscd: f64,
+    cit: f64,
+    ngate: f64,
+    vfb: f64,
+    voff: f64,
+    mstar: f64,
+    // Current.
+    ua: f64,
+    ub: f64,
+    uc: f64,
+    eu: f64,
+    u0: f64,
+    vtfbphi1: f64,
+    vtfbphi2: f64,
+    thickness: Thickness,
+    vsat: f64,
+    rds0: f64,
+    rdswmin: f64,
+    prwg: f64,
+    prwb: f64,
+    dwg: f64,
+    dwb: f64,
+    a0: f64,
+    ags: f64,
+    b0: f64,
+    b1: f64,
+    keta: f64,
+    xj: f64,
+    a1: f64,
+    a2: f64,
+    delta: f64,
+    pclm: f64,
+    theta_rout: f64,
+    pdiblb: f64,
+    fprout: f64,
+    pdits: f64,
+    pditsd: f64,
+    pditsl: f64,
+    pvag: f64,
+    pscbe1: f64,
+    pscbe2: f64,
+    alpha0: f64,
+    alpha1: f64,
+    beta0: f64,
+    agidl: f64,
+    bgidl: f64,
+    cgidl: f64,
+    egidl: f64,
+    // Charge.
+    charge_model: ChargeModel,
+    vfbzb: f64,
+    abulk_factor: f64,
+    noff: f64,
+    voffcv: f64,
+}
+
+impl Bsim4 {
+    /// The transistor of `geometry` at `t` of a card measured at `tnom`,
+    /// both in K.
+    pub(crate) fn new(
+        model: &Model,
+        geometry: &Geometry,
+        t: f64,
+        tnom: f64,
+    ) -> Result<Self, String> {
+        let card = Binning::unbinned(model);
+        let [leff, weff, leff_cv, weff_cv] = effective_size(&card, geometry);
+        if leff <= 0.0 || weff <= 0.0 || leff_cv <= 0.0 || weff_cv <= 0.0 {
+            return Err(format!(
+                "Model {}: the effective length and width must be positive",
+                model.name
+            ));
+        }
+        let toxe = card.value("toxe", 3e-9);
+        if toxe <= 0.0 {
+            return Err(format!("Model {}: TOXE must be positive", model.name));
+        }
+        let toxm = card.value("toxm", toxe);
+        let epsrox = card.value("epsrox", 3.9);
+        let coxe = epsrox * EPSILON_0 / toxe;
+        let polarity = if model.kind == "PMOS" { -1.0 } else { 1.0 };
+        let mobmod = card.value("mobmod", 0.0) as u8;
+        let p = Binning::at(model, leff, weff);
+
+        let vtm0 = BOLTZMANN * tnom / CHARGE;
+        let vtm = BOLTZMANN * t / CHARGE;
+        let ratio = t / tnom;
+        let ni = 1.45e10
+            * (tnom / 300.15)
+            * (tnom / 300.15).sqrt()
+            * (21.5565981 - energy_gap(tnom) / (2.0 * vtm0)).exp();
+
+        // Doping in cm⁻³, unless clearly given in m⁻³.
+        let per_cm3 = |n: f64, limit: f64| if n > limit { n * 1e-6 } else { n };
+        let gamma1 = p.binned_given("gamma1");
+        let ndep = match p.binned_given("ndep") {
+            Some(ndep) => per_cm3(ndep, 1e20),
+            None => match gamma1 {
+                Some(gamma1) => 3.01248e22 * (gamma1 * coxe).powi(2),
+                None => 1.7e17,
+            },
+        };
+        let nsub = p.binned("nsub", 6e16);
+        let nsd = per_cm3(p.binned("nsd", 1e20), 1e23);
+        let ngate = per_cm3(p.binned("ngate", 0.0), 1e23);
+
+        let phi = vtm0 * (ndep / ni).ln() + p.binned("phin", 0.0) + 0.4;
+        let sqrt_phi = phi.sqrt();
+        let xdep0 = (2.0 * EPSILON_SI / (CHARGE * ndep * 1e6)).sqrt() * sqrt_phi;
+        let xj = p.binned("xj", 1.5e-7);
+        let litl = (3.0 * 3.9 / epsrox * xj * toxe).sqrt();
+        let vbi = vtm0 * (nsd * ndep / (ni * ni)).ln();
+        let cdep0 = (CHARGE * EPSILON_SI * ndep * 1e6 / 2.0 / phi).sqrt();
+
+        let vbm = -p.binned("vbm", -3.0).abs();
+        let (k1, k2) = match (p.binned_given("k1"), p.binned_given("k2")) {
+            (None, None) => {
+                let xt = p.binned("xt", 1.55e-7);
+                let vbx = -p
+                    .binned_given("vbx")
+                    .unwrap_or(phi - 7.7348e-4 * ndep * xt * xt)
+                    .abs();
+                let gamma1 = gamma1.unwrap_or(5.753e-12 * ndep.sqrt() / coxe);
+                let gamma2 = p.binned("gamma2", 5.753e-12 * nsub.sqrt() / coxe);
+                let t1 = (phi - vbx).sqrt() - sqrt_phi;
+                let t2 = (phi * (phi - vbm)).sqrt() - phi;
+                let k2 = (gamma1 - gamma2) * t1 / (2.0 * t2 + vbm);
+                (gamma2 - 2.0 * k2 * (phi - vbm).sqrt(), k2)
+            }
+            (k1, k2) => (k1.unwrap_or(0.53), k2.unwrap_or(-0.0186)),
+        };
+        let vbsc = if k2 < 0.0 {
+            let t0 = 0.5 * k1 / k2;
+            (0.9 * (phi - t0 * t0)).clamp(-30.0, -3.0)
+        } else {
+            -30.0
+        }
+        .min(vbm);
+
+        let vth0 = p
+            .binned_given("vth0")
+            .or(p.binned_given("vtho"))
+            .map(|vth0| polarity * vth0);
+        let vfb = p
+            .binned_given("vfb")
+            .unwrap_or(vth0.map_or(-1.0, |vth0| vth0 - phi - k1 * sqrt_phi));
+        let vth0 = vth0.unwrap_or(vfb + phi + k1 * sqrt_phi);
+        let k1ox = k1 * toxe / toxm;
+        let k2ox = k2 * toxe / toxm;
+
+        // Short channel and DIBL at zero bias.
+        let factor1 = (EPSILON_SI / (epsrox * EPSILON_0) * toxe).sqrt();
+        let lt0 = factor1 * xdep0.sqrt();
+        let drout = p.binned("drout", 0.56);
+        let theta0vb0 = short_channel(p.binned("dsub", drout) * leff / lt0);
+        let theta_rout = p.binned("pdiblc1", 0.39) * short_channel(drout * leff / lt0)
+            + p.binned("pdiblc2", 0.0086);
+        let dvt0 = p.binned("dvt0", 2.2);
+        let dvt1 = p.binned("dvt1", 0.53);
+        let dvt0w = p.binned("dvt0w", 0.0);
+        let dvt1w = p.binned("dvt1w", 5.3e6);
+        let k3 = p.binned("k3", 80.0);
+        let w0 = p.binned("w0", 2.5e-6);
+        let lpe0 = p.binned("lpe0", 1.74e-7);
+        let kt1 = p.binned("kt1", -0.11);
+        let kt1l = p.binned("kt1l", 0.0);
+        let narrow = dvt0w * short_channel(dvt1w * weff * leff / lt0) * (vbi - phi);
+        let short = dvt0 * short_channel(dvt1 * leff / lt0) * (vbi - phi);
+        let t4 = toxe * phi / (weff + w0);
+        let t5 = k1ox * ((1.0 + lpe0 / leff).sqrt() - 1.0) * sqrt_phi
+            + (kt1 + kt1l / leff) * (ratio - 1.0);
+        let vfbzb = vth0 - narrow - short + k3 * t4 + t5 - phi - k1 * sqrt_phi;
+
+        // Temperature.
+        let dt = ratio - 1.0;
+        let u0 = p.binned("u0", if polarity > 0.0 { 0.067 } else { 0.025 });
+        let u0 = if u0 > 1.0 { u0 * 1e-4 } else { u0 };
+        let ua = if mobmod == 2 { 1e-15 } else { 1e-9 };
+        let uc = if mobmod == 1 { -0.0465 } else { -0.0465e-9 };
+        let uc1 = if mobmod == 1 { -0.056 } else { -0.056e-9 };
+        let prt = p.binned("prt", 0.0) * dt;
+        let width = (weff * 1e6).powf(p.binned("wr", 1.0));
+
+        // Overlaps, the fringe included.
+        let cgsl = p.binned("cgsl", 0.0);
+        let cgdl = p.binned("cgdl", 0.0);
+        let cf = p.binned(
+            "cf",
+            2.0 * epsrox * EPSILON_0 / std::f64::consts::PI * (1.0 + 0.4e-6 / toxe).ln(),
+        );
+        let dlc = card.get("dlc");
+        let side = |given: &str, lightly_doped: f64| {
+            card.get(given).unwrap_or(match dlc {
+                Some(dlc) if dlc > 0.0 => (dlc * coxe - lightly_doped).max(0.0),
+                _ => 0.6 * xj * coxe,
+            })
+        };
+        let overlap = Overlap {
+            cgso: side("cgso", cgsl) + cf,
+            cgdo: side("cgdo", cgdl) + cf,
+            cgbo: card.value("cgbo", 0.0),
+            cgsl,
+            cgdl,
+            ckappas: p.binned("ckappas", 0.6),
+            ckappad: p.binned("ckappad", p.binned("ckappas", 0.6)),
+            weff: weff_cv,
+            leff: leff_cv,
+        };
+
+        // The gate drive's offsets to the vertical field, of mobility and
+        // of the inversion charge's centroid.
+        let vtfbphi = vth0 - vfb - phi;
+        let vtfbphi1 = (vtfbphi * if polarity > 0.0 { 2.0 } else { 2.5 }).max(0.0);
+        let vtfbphi2 = (4.0 * vtfbphi).max(0.0);
+        let toxp = card.value("toxp", toxe - card.value("dtox", 0.0));
+        if toxp <= 0.0 {
+            return Err(format!("Model {}: TOXP must be positive", model.name));
+        }
+        let thickness = Thickness {
+            cox: coxe,
+            toxp,
+            coxp: epsrox * EPSILON_0 / toxp,
+            ldeb: (EPSILON_SI * vtm0 / (CHARGE * ndep * 1e6)).sqrt() / 3.0,
+            acde: p.binned("acde", 1.0),
+            moin: p.binned("moin", 15.0),
+            ados: card.value("ados", 1.0),
+            bdos: card.value("bdos", 1.0),
+            vtfbphi2: Some(vtfbphi2),
+        };
+        let capmod = card.value("capmod", 2.0);
+        let Some(charge_model) =
+            ChargeModel::of(Version::Bsim4, capmod, p.binned("vfbcv", -1.0), thickness)
+        else {
+            return Err(format!(
+                "Model {}: CAPMOD={} is not one of 0, 1 and 2",
+                model.name, capmod
+            ));
+        };
+
+        let noise = NoiseModel {
+            unified_flicker: card.value("fnoimod", 1.0) == 1.0,
+            charge_thermal: card.value("tnoimod", 0.0) == 0.0,
+            kf: card.value("kf", 0.0),
+            af: card.value("af", 1.0),
+            ef: card.value("ef", 1.0),
+            noia: card.value("noia", if polarity > 0.0 { 6.25e41 } else { 6.188e40 }),
+            noib: card.value("noib", if polarity > 0.0 { 3.125e26 } else { 1.5e25 }),
+            noic: card.value("noic", 8.75e9),
+            em: card.value("em", 4.1e7),
+            energy: BOLTZMANN,
+            strong_scale: 1e10,
+            weak_scale: 1e10,
+            cox: coxe,
+            leff,
+            weff,
+            litl,
+        };
+
+        Ok(Bsim4 {
+            overlap,
+            noise,
+            weff,
+            leff,
+            toxe,
+            coxe,
+            cox_wl: coxe * weff_cv * leff_cv,
+            mobmod,
+            xpart: card.value("xpart", 0.0),
+            vtm,
+            phi,
+            sqrt_phi,
+            xdep0,
+            vbi,
+            vbsc,
+            cdep0,
+            litl,
+            factor1,
+            vth0,
+            k1,
+            k1ox,
+            k2ox,
+            k3,
+            k3b: p.binned("k3b", 0.0),
+            w0,
+            lpe0,
+            lpeb: p.binned("lpeb", 0.0),
+            dvtp0: p.binned("dvtp0", 0.0),
+            dvtp1: p.binned("dvtp1", 0.0),
+            dvt0,
+            dvt1,
+            dvt2: p.binned("dvt2", -0.032),
+            dvt0w,
+            dvt1w,
+            dvt2w: p.binned("dvt2w", -0.032),
+            kt1,
+            kt1l,
+            kt2: p.binned("kt2", 0.022),
+            temperature_ratio: dt,
+            eta0: p.binned("eta0", 0.08),
+            etab: p.binned("etab", -0.07),
+            theta0vb0,
+            nfactor: p.binned("nfactor", 1.0),
+            cdsc: p.binned("cdsc", 2.4e-4),
+            cdscb: p.binned("cdscb", 0.0),
+            cdscd: p.binned("cdscd", 0.0),
+            cit: p.binned("cit", 0.0),
+            ngate,
+            vfb,
+            voff: p.binned("voff", -0.08) + p.binned("voffl", 0.0) / leff,
+            mstar: 0.5 + p.binned("minv", 0.0).atan() / std::f64::consts::PI,
+            ua: p.binned("ua", ua) + p.binned("ua1", 1e-9) * dt,
+            ub: p.binned("ub", 1e-19) + p.binned("ub1", -1e-18) * dt,
+            uc: p.binned("uc", uc) + p.binned("uc1", uc1) * dt,
+            eu: p.binned("eu", if polarity > 0.0 { 1.67 } else { 1.0 }),
+            u0: u0 * ratio.powf(p.binned("ute", -1.5)),
+            vtfbphi1,
+            vtfbphi2,
+            thickness,
+            vsat: p.binned("vsat", 8e4) - p.binned("at", 3.3e4) * dt,
+            rds0: (p.binned("rdsw", 200.0) + prt).max(0.0) / width,
+            rdswmin: (p.binned("rdswmin", 0.0) + prt).max(0.0) / width,
+            prwg: p.binned("prwg", 1.0),
+            prwb: p.binned("prwb", 0.0),
+            dwg: p.binned("dwg", 0.0),
+            dwb: p.binned("dwb", 0.0),
+            a0: p.binned("a0", 1.0),
+            ags: p.binned("ags", 0.0),
+            b0: p.binned("b0", 0.0),
+            b1: p.binned("b1", 0.0),
+            keta: p.binned("keta", -0.047),
+            xj,
+            a1: p.binned("a1", 0.0),
+            a2: p.binned("a2", 1.0),
+            delta: p.binned("delta", 0.01),
+            pclm: p.binned("pclm", 1.3),
+            theta_rout,
+            pdiblb: p.binned("pdiblcb", 0.0),
+            fprout: p.binned("fprout", 0.0),
+            pdits: p.binned("pdits", 0.0),
+            pditsd: p.binned("pditsd", 0.0),
+            pditsl: card.value("pditsl", 0.0),
+            pvag: p.binned("pvag", 0.0),
+            pscbe1: p.binned("pscbe1", 4.24e8),
+            pscbe2: p.binned("pscbe2", 1e-5),
+            alpha0: p.binned("alpha0", 0.0),
+            alpha1: p.binned("alpha1", 0.0),
+            beta0: p.binned("beta0", 30.0),
+            agidl: p.binned("agidl", 0.0),
+            bgidl: p.binned("bgidl", 2.3e9),
+            cgidl: p.binned("cgidl", 0.5),
+            egidl: p.binned("egidl", 0.8),
+            charge_model,
+            vfbzb,
+            abulk_factor: 1.0 + (p.binned("clc", 0.1e-6) / leff_cv).powf(p.binned("cle", 0.6)),
+            noff: p.binned("noff", 1.0),
+            voffcv: p.binned("voffcv", 0.0),
+        })
+    }
+
+    /// The transistor at `vgs`, `vds ≥ 0` and `vbs`.
+    pub(crate) fn intrinsic<T: Real>(&self, vgs: T, vds: T, vbs: T) -> Intrinsic<T> {
+        let c = T::constant;
+        let one = c(1.0);
+
+        // The bulk bias, kept above VBSC, and the surface potential.
+        let t0 = vbs - self.vbsc - 0.001;
+        let t1 = (t0 * t0 - 0.004 * self.vbsc).sqrt();
+        let vbseff = ((t0 + t1) * 0.5 + self.vbsc).max(vbs);
+        let sqrt_phis = if vbseff.value() > 0.0 {
+            c(self.phi * self.sqrt_phi) / (vbseff * 0.5 + self.phi)
+        } else {
+            (-vbseff + self.phi).sqrt()
+        };
+        let xdep = sqrt_phis * (self.xdep0 / self.sqrt_phi);
+
+        // Threshold.
+        let t3 = xdep.sqrt();
+        let v0 = self.vbi - self.phi;
+        let soft = |t0: T| {
+            if t0.value() >= -0.5 {
+                t0 + 1.0
+            } else {
+                (t0 * 3.0 + 1.0) / (t0 * 8.0 + 3.0)
+            }
+        };
+        let lt1 = t3 * soft(vbseff * self.dvt2) * self.factor1;
+        let ltw = t3 * soft(vbseff * self.dvt2w) * self.factor1;
+        let theta0 = short_channel(c(self.dvt1 * self.leff) / lt1);
+        let delt_vth = theta0 * (self.dvt0 * v0);
+        let narrow = short_channel(c(self.dvt1w * self.weff * self.leff) / ltw) * (self.dvt0w * v0);
+        let temperature = (vbseff * self.kt2 + self.kt1 + self.kt1l / self.leff)
+            * self.temperature_ratio
+            + self.k1ox * ((1.0 + self.lpe0 / self.leff).sqrt() - 1.0) * self.sqrt_phi;
+        let t3 = vbseff * self.etab + self.eta0;
+        let t3 = if t3.value() < 1e-4 {
+            (-t3 + 2e-4) / (t3 * -2e4 + 3.0)
+        } else {
+            t3
+        };
+        let dibl = t3 * self.theta0vb0 * vds;
+        let lpe_vb = (1.0 + self.lpeb / self.leff).sqrt();
+        let tmp2 = self.toxe * self.phi / (self.weff + self.w0);
+        let vth = (sqrt_phis * self.k1ox - self.k1 * self.sqrt_phi) * lpe_vb
+            - vbseff * self.k2ox
+            - delt_vth
+            - narrow
+            + (vbseff * self.k3b + self.k3) * tmp2
+            + temperature
+            - dibl
+            + self.vth0;
+
+        // Subthreshold swing, then the pocket implant's drain induced
+        // threshold shift.
+        let tmp1 = c(EPSILON_SI) / xdep;
+        let tmp3 = vbseff * self.cdscb + vds * self.cdscd + self.cdsc;
+        let tmp4 = (tmp1 * self.nfactor + tmp3 * theta0 + self.cit) / self.coxe;
+        let n = soft(tmp4);
+        let vtm = self.vtm;
+        let vth = if self.dvtp0 > 0.0 {
+            let t0 = vds * -self.dvtp1;
+            let t2 = if t0.value() < -EXP_THRESHOLD {
+                c((-EXP_THRESHOLD).exp())
+            } else {
+                t0.exp()
+            };
+            let t3 = (t2 + 1.0) * self.dvtp0 + self.leff;
+            vth - n * vtm * (c(self.leff) / t3).ln()
+        } else {
+            vth
+        };
+        let nstar = (tmp1 + self.coxe + self.cit) * (vtm / CHARGE);
+
+        // Poly gate depletion.
+        let vgs_eff = if self.ngate > 1e18 && self.ngate < 1e25 && vgs.value() > self.vfb + self.phi
+        {
+            let t1 = 1e6 * CHARGE * EPSILON_SI * self.ngate / (self.coxe * self.coxe);
+            let t8 = vgs - self.vfb - self.phi;
+            let t4 = (t8 * (2.0 / t1) + 1.0).sqrt();
+            let t2 = t8 * 2.0 / (t4 + 1.0);
+            let t7 = -(t2 * t2 * (0.5 / t1)) + (1.12 - 0.05);
+            let t6 = (t7 * t7 + 0.224).sqrt();
+            vgs - (-(t7 + t6) * 0.5 + 1.12)
+        } else {
+            vgs
+        };
+
+        // The effective gate drive, smooth through the threshold.
+        let vgst = vgs_eff - vth;
+        let nvt = n * vtm;
+        let vgst_nvt = vgst * self.mstar / nvt;
+        let exp_arg = (-vgst * (1.0 - self.mstar) + self.voff) / nvt;
+        let vgsteff = if vgst_nvt.value() > EXP_THRESHOLD {
+            vgst
+        } else if exp_arg.value() > EXP_THRESHOLD {
+            ((vgst - self.voff) / nvt).exp() * (vtm * self.cdep0 / self.coxe)
+        } else {
+            nvt * (vgst_nvt.exp() + 1.0).ln()
+                / (n * exp_arg.exp() * (self.coxe / self.cdep0) + self.mstar)
+        };
+
+        // Bias dependent width and series resistance.
+        let t9 = sqrt_phis - self.sqrt_phi;
+        let weff = -(vgsteff * self.dwg + t9 * self.dwb) * 2.0 + self.weff;
+        let weff = if weff.value() < 2e-8 {
+            (-weff + 4e-8) * 2e-8 / (-weff * 2.0 + 6e-8)
+        } else {
+            weff
+        };
+        let t2 = one / (vgsteff * self.prwg + 1.0) + t9 * self.prwb;
+        let rds = (t2 + (t2 * t2 + 0.01).sqrt()) * (0.5 * self.rds0) + self.rdswmin;
+
+        // Bulk charge factor.
+        let t1 = c(0.5 * self.k1ox) * lpe_vb / sqrt_phis;
+        let t9 = (xdep * self.xj).sqrt();
+        let t5 = c(self.leff) / (t9 * 2.0 + self.leff);
+        let t2 = t5 * self.a0 + self.b0 / (self.weff + self.b1);
+        let abulk0 = t1 * t2 + 1.0;
+        let abulk = abulk0 - t1 * t5 * t5 * t5 * (self.ags * self.a0) * vgsteff;
+        let floor = |a: T| {
+            if a.value() < 0.1 {
+                (-a + 0.2) / (a * -20.0 + 3.0)
+            } else {
+                a
+            }
+        };
+        let t2 = vbseff * self.keta;
+        let keta = if t2.value() >= -0.9 {
+            one / (t2 + 1.0)
+        } else {
+            (t2 * 20.0 + 17.0) / (t2 + 0.8)
+        };
+        let abulk0 = floor(abulk0) * keta;
+        let abulk = floor(abulk) * keta;
+
+        // Mobility.
+        let t5 = match self.mobmod {
+            1 => {
+                let t3 = (vgsteff + vth * 2.0) / self.toxe;
+                t3 * (t3 * self.ub + self.ua) * (vbseff * self.uc + 1.0)
+            }
+            2 => {
+                let t0 = (vgsteff + self.vtfbphi1) / self.toxe;
+                t0.powf(c(self.eu)) * (vbseff * self.uc + self.ua)
+            }
+            _ => {
+                let t3 = (vgsteff + vth * 2.0) / self.toxe;
+                t3 * ((vbseff * self.uc + self.ua) + t3 * self.ub)
+            }
+        };
+        let denominator = if t5.value() >= -0.8 {
+            t5 + 1.0
+        } else {
+            (t5 + 0.6) / (t5 * 10.0 + 7.0)
+        };
+        let ueff = c(self.u0) / denominator;
+
+        // Saturation.
+        let wv_cox_rds = weff * (self.vsat * self.coxe) * rds;
+        let esat = c(2.0 * self.vsat) / ueff;
+        let esat_l = esat * self.leff;
+        let lambda = if self.a1 == 0.0 {
+            c(self.a2)
+        } else if self.a1 > 0.0 {
+            let t0 = 1.0 - self.a2;
+            let t1 = -(vgsteff * self.a1) + (t0 - 0.0001);
+            let t2 = (t1 * t1 + 0.0004 * t0).sqrt();
+            -(t1 + t2) * 0.5 + (self.a2 + t0)
+        } else {
+            let t1 = vgsteff * self.a1 + (self.a2 - 0.0001);
+            (t1 + (t1 * t1 + 0.0004 * self.a2).sqrt()) * 0.5
+        };
+        let vgst2vtm = vgsteff + 2.0 * vtm;
+        let vdsat = if rds.value() == 0.0 && lambda.value() == 1.0 {
+            esat_l * vgst2vtm / (abulk * esat_l + vgst2vtm)
+        } else {
+            let t9 = abulk * wv_cox_rds;
+            let t7 = vgst2vtm * t9;
+            let t6 = vgst2vtm * wv_cox_rds;
+            let t0 = abulk * (t9 - 1.0 + one / lambda) * 2.0;
+            let t1 = vgst2vtm * (c(2.0) / lambda - 1.0) + abulk * esat_l + t7 * 3.0;
+            let t2 = vgst2vtm * (esat_l + t6 * 2.0);
+            let t3 = (t1 * t1 - t0 * t2 * 2.0).sqrt();
+            (t1 - t3) / t0
+        };
+        let t1 = vdsat - vds - self.delta;
+        let t2 = (t1 * t1 + vdsat * (4.0 * self.delta)).sqrt();
+        let vdseff = (vdsat - (t1 + t2) * 0.5).min(vds);
+        let diff_vds = vds - vdseff;
+
+        // Linear region current, through the oxide in series with the
+        // inversion charge's centroid.
+        let coxeff = self.thickness.inversion(vgsteff, c(self.vtfbphi2));
+        let beta = ueff * weff * coxeff / self.leff;
+        let fgche1 = vgsteff * (one - vdseff * abulk * 0.5 / vgst2vtm);
+        let fgche2 = vdseff / esat_l + 1.0;
+        let gche = beta * fgche1 / fgche2;
+        let idl = gche * vdseff / (gche * rds + 1.0);
+
+        // Output resistance.
+        let tmp4 = one - abulk * vdsat * 0.5 / vgst2vtm;
+        let t0 = esat_l + vdsat + wv_cox_rds * vgsteff * tmp4 * 2.0;
+        let t1 = c(2.0) / lambda - 1.0 + wv_cox_rds * abulk;
+        let vasat = t0 / t1;
+        let fp = if self.fprout > 0.0 {
+            one / (c(self.fprout * self.leff.sqrt()) / vgst2vtm + 1.0)
+        } else {
+            one
+        };
+        let t9 = vgsteff * c(self.pvag) / esat_l;
+        let pvag = if t9.value() > -0.9 {
+            t9 + 1.0
+        } else {
+            (t9 + 0.8) / (t9 * 20.0 + 17.0)
+        };
+        let clm = if self.pclm > 0.0 && diff_vds.value() > 1e-10 {
+            Some(
+                fp * pvag * (idl * rds + 1.0) * (vdsat / esat + self.leff)
+                    / (self.pclm * self.litl),
+            )
+        } else {
+            None
+        };
+        let clm_factor = match clm {
+            Some(cclm) => {
+                let va = vasat + cclm * diff_vds;
+                (va / vasat).ln() / cclm + 1.0
+            }
+            None => one,
+        };
+        let vadibl = if self.theta_rout > 0.0 {
+            let t8 = abulk * vdsat;
+            let vadibl = (vgst2vtm - vgst2vtm * t8 / (vgst2vtm + t8)) / self.theta_rout;
+            let t7 = vbseff * self.pdiblb;
+            let vadibl = if t7.value() >= -0.9 {
+                vadibl / (t7 + 1.0)
+            } else {
+                vadibl * (t7 * 20.0 + 17.0) / (t7 + 0.8)
+            };
+            vadibl * pvag
+        } else {
+            c(MAX_EXP)
+        };
+        let vadits = if self.pdits > 0.0 {
+            let t1 = vds * self.pditsd;
+            let t2 = if t1.value() > EXP_THRESHOLD {
+                c(MAX_EXP)
+            } else {
+                t1.exp()
+            };
+            (t2 * (1.0 + self.pditsl * self.leff) + 1.0) * fp / self.pdits
+        } else {
+            c(MAX_EXP)
+        };
+        let vascbe = if self.pscbe2 > 0.0 {
+            if diff_vds.value() > self.pscbe1 * self.litl / EXP_THRESHOLD {
+                (c(self.pscbe1 * self.litl) / diff_vds).exp() * (self.leff / self.pscbe2)
+            } else {
+                c(MAX_EXP * self.leff / self.pscbe2)
+            }
+        } else {
+            c(MAX_EXP)
+        };
+        let idsa = idl * clm_factor * (diff_vds / vadibl + 1.0) * (diff_vds / vadits + 1.0);
+        let ids = idsa * (diff_vds / vascbe + 1.0);
+
+        // Substrate current by impact ionisation.
+        let alpha = self.alpha0 + self.alpha1 * self.leff;
+        let isub = if alpha <= 0.0 || self.beta0 <= 0.0 {
+            c(0.0)
+        } else if diff_vds.value() > self.beta0 / EXP_THRESHOLD {
+            diff_vds * (alpha / self.leff) * (c(-self.beta0) / diff_vds).exp() * idsa
+        } else {
+            diff_vds * (alpha / self.leff * (-EXP_THRESHOLD).exp()) * idsa
+        };
+
+        // Gate induced drain leakage, falling off as the drain junction
+        // is forward biased.
+        let t1 = (vds - vgs_eff - self.egidl) / (3.0 * self.toxe);
+        let vbd = vbs - vds;
+        let igidl = if self.agidl <= 0.0
+            || self.bgidl <= 0.0
+            || self.cgidl <= 0.0
+            || t1.value() <= 0.0
+            || vbd.value() > 0.0
+        {
+            c(0.0)
+        } else {
+            let t2 = c(self.bgidl) / t1;
+            let igidl = if t2.value() < 100.0 {
+                t1 * (self.agidl * self.weff) * (-t2).exp()
+            } else {
+                t1 * (self.agidl * self.weff * 3.720075976e-44)
+            };
+            let t5 = -(vbd * vbd * vbd);
+            igidl * t5 / (t5 + self.cgidl)
+        };
+
+        let charges = charges(
+            &self.charge_model,
+            ChargeBias {
+                vgs: vgs_eff,
+                vth,
+                vbs: vbseff,
+                vds,
+                sqrt_phis,
+                phi: self.phi,
+                vfb: self.vfbzb,
+                k1ox: self.k1ox,
+                abulk0,
+                n,
+                vtm,
+                cox_wl: self.cox_wl,
+                abulk_factor: self.abulk_factor,
+                noff: self.noff,
+                voffcv: self.voffcv,
+                xpart: self.xpart,
+            },
+        );
+
+        Intrinsic {
+            ids,
+            idb: isub + igidl,
+            charges,
+            von: vth.value(),
+            vdsat: vdsat.value(),
+            noise: NoiseBias {
+                vgsteff: vgsteff.value(),
+                vdseff: vdseff.value(),
+                vds: vds.value(),
+                abulk: abulk.value(),
+                vgst2vtm: vgst2vtm.value(),
+                ueff: ueff.value(),
+                esat: esat.value(),
+                qinv: self.cox_wl * fgche1.value(),
+                rds: rds.value(),
+                nstar: nstar.value(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::bsim::tests::reference;
+    use crate::devices::generic::KELVIN;
+
+    fn core(card: &str, l: f64) -> Bsim4 {
+        let model = Model::parse(card).unwrap();
+        check(&model).unwrap();
+        let geometry = Geometry {
+            l,
+            w: 1e-6,
+            ..Geometry::default()
+        };
+        Bsim4::new(&model, &geometry, 27.0 + KELVIN, 27.0 + KELVIN).unwrap()
+    }
+
+    #[test]
+    fn test_short_channel() {
+        // The threshold rolls off at short lengths and falls with the
+        // drain voltage there.
+        let card = ".model MOD NMOS(LEVEL=54 TOXE=2n VTH0=0.4 LPE0=0)";
+        let von = |l: f64, vds: f64| core(card, l).intrinsic(0.0, vds, 0.0).von;
+        assert!(von(0.05e-6, 0.05) < von(1e-6, 0.05) - 0.01);
+        assert!(von(0.05e-6, 1.0) < von(0.05e-6, 0.05) - 0.01);
+        assert!((von(10e-6, 1.0) - von(10e-6, 0.05)).abs() < 1e-3);
+        // The pocket implant's DITS raises it at low drain voltages.
+        let pocket = ".model MOD NMOS(LEVEL=54 TOXE=2n VTH0=0.4 LPE0=0 DVTP0=1e-7 DVTP1=1)";
+        assert!(core(pocket, 0.1e-6).intrinsic(0.0, 0.05, 0.0).von > von(0.1e-6, 0.05));
+    }
+
+    #[test]
+    fn test_gidl() {
+        // Drain leakage with the gate off, growing with the drain voltage.
+        let card = ".model MOD NMOS(LEVEL=54 TOXE=2n VTH0=0.4 AGIDL=1e-9 BGIDL=1e9 CGIDL=0.1)";
+        let bsim = core(card, 0.1e-6);
+        let idb = |vds: f64| bsim.intrinsic(-0.5, vds, 0.0).idb;
+        assert!(
+            idb(1.0) > 0.0 && idb(1.5) > 10.0 * idb(1.0),
+            "{} {}",
+            idb(1.0),
+            idb(1.5)
+        );
+        let card = ".model MOD NMOS(LEVEL=54 TOXE=2n VTH0=0.4)";
+        assert_eq!(core(card, 0.1e-6).intrinsic(-0.5, 1.5, 0.0).idb, 0.0);
+    }
+
+    #[test]
+    fn test_reference() {
+        reference(
+            include_str!("../../tests/fixtures/bsim4.csv"),
+            |model, geometry, celsius, [vgs, vds, vbs]| {
+                let bsim = Bsim4::new(model, geometry, celsius + KELVIN, 27.0 + KELVIN).unwrap();
+                bsim.intrinsic(vgs, vds, vbs)
+            },
+        );
+    }
+}
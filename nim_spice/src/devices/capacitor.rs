@@ -0,0 +1,205 @@
+//! Capacitors, with an initial voltage and the series and parallel
+//! parasitics of real parts.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::generic::{
+    current, Conductance, Device, LoadContext, State, Unknown, Unknowns,
+};
+use crate::devices::inductor::Inductor;
+use crate::devices::parameters::ElementLine;
+use crate::devices::resistor::Resistor;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capacitor {
+    name: String,
+    nodes: [Node; 2],
+    capacitance: f64,
+    /// Voltage to start from with `uic`.
+    initial: Option<f64>,
+    unknowns: [Unknown; 2],
+    stamp: Conductance,
+    charge: State,
+}
+
+impl Capacitor {
+    pub fn new(name: impl Into<String>, positive: Node, negative: Node, capacitance: f64) -> Self {
+        Capacitor {
+            name: name.into(),
+            nodes: [positive, negative],
+            capacitance,
+            initial: None,
+            unknowns: [None; 2],
+            stamp: Conductance::default(),
+            charge: State::default(),
+        }
+    }
+
+    pub fn capacitance(&self) -> f64 {
+        self.capacitance
+    }
+
+    /// Current into the positive node, at the point last loaded.
+    pub fn current(&self) -> f64 {
+        self.charge.derivative()
+    }
+}
+
+/// `Cname n+ n- value [ic=] [m=] [Rser=] [Lser=] [Rpar=] [Cpar=]`, the
+/// value also as `c=`. The parasitics become devices of their own: `Rser`
+/// and `Lser` in series through internal nodes `Cname#1` and `Cname#2`,
+/// `Rpar` and `Cpar` across the terminals. Part data from a schematic
+/// library (`V`, `Irms`, `mfg`, `pn`, `type`) is ignored.
+pub fn build(line: &ElementLine) -> Result<Vec<Box<dyn Device>>, String> {
+    line.unknown_parameters(&[
+        "c", "ic", "m", "rser", "lser", "rpar", "cpar", "v", "irms", "mfg", "pn", "type",
+    ])?;
+    let [positive, negative] = line.nodes()?;
+    let multiplier = line.number("m")?.unwrap_or(1.0);
+    if multiplier <= 0.0 {
+        return Err("The multiplier must be positive".to_string());
+    }
+    let name = &line.name;
+    let mut devices: Vec<Box<dyn Device>> = Vec::new();
+    let mut terminal = positive.clone();
+    if let Some(rser) = line.number("rser")?.filter(|r| *r != 0.0) {
+        let node = Node::new(format!("{}#1", name));
+        devices.push(Box::new(Resistor::new(
+            format!("{}.Rser", name),
+            terminal,
+            node.clone(),
+            rser,
+        )));
+        terminal = node;
+    }
+    if let Some(lser) = line.number("lser")?.filter(|l| *l != 0.0) {
+        let node = Node::new(format!("{}#2", name));
+        devices.push(Box::new(Inductor::new(
+            format!("{}.Lser", name),
+            terminal,
+            node.clone(),
+            lser,
+        )));
+        terminal = node;
+    }
+    let mut capacitor = Capacitor::new(
+        name.clone(),
+        terminal,
+        negative.clone(),
+        line.value(2, "c")? * multiplier,
+    );
+    capacitor.initial = line.number("ic")?;
+    devices.push(Box::new(capacitor));
+    if let Some(rpar) = line.number("rpar")?.filter(|r| *r != 0.0) {
+        devices.push(Box::new(Resistor::new(
+            format!("{}.Rpar", name),
+            positive.clone(),
+            negative.clone(),
+            rpar,
+        )));
+    }
+    if let Some(cpar) = line.number("cpar")?.filter(|c| *c != 0.0) {
+        devices.push(Box::new(Capacitor::new(
+            format!("{}.Cpar", name),
+            positive,
+            negative,
+            cpar,
+        )));
+    }
+    Ok(devices)
+}
+
+impl Device for Capacitor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        self.stamp = Conductance::new(pattern, self.unknowns[0], self.unknowns[1]);
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let v = context.voltage(self.unknowns[0]) - context.voltage(self.unknowns[1]);
+        let q = self.capacitance * v;
+        let Some(integrator) = context.integrator else {
+            self.charge.reset(q);
+            return;
+        };
+        let companion = self.charge.integrate(integrator, q, self.capacitance, v);
+        self.stamp.stamp(matrix, companion.geq);
+        current(rhs, self.unknowns[0], self.unknowns[1], companion.ceq);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        self.stamp
+            .stamp(matrix, Complex::new(0.0, omega * self.capacitance));
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some(v) = self.initial {
+            self.charge.reset(self.capacitance * v);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        self.charge.accept();
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charge
+            .allowed_step(timestep, integrator, h, Quantity::Charge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::integration::Method;
+    use crate::analysis::options::Options;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+
+    #[test]
+    fn test_capacitor() {
+        // Rpar and the 1 kΩ divide at DC; Rser sees no current.
+        let (divider, solution) =
+            operating_point(&["V1 in 0 2", "R1 in out 1k", "C1 out 0 1u Rser=10 Rpar=1k"]);
+        assert_eq!(divider.unknowns().len(), 4);
+        let out = divider.unknowns().find("V(out)").unwrap();
+        assert!((solution[out] - 1.0).abs() < 1e-12);
+
+        // Two 0.5 µF in parallel and Cpar make 2 µF, so with 1 kΩ the output
+        // is 3 dB down at ω = 500.
+        let mut rc = circuit(&[
+            "V1 in 0 0 AC 1",
+            "R1 in out 1k",
+            "C1 out 0 0.5u m=2 Cpar=1u",
+        ]);
+        let out = rc.unknowns().find("V(out)").unwrap();
+        let response = ac(&mut rc, 500.0);
+        assert!((response[out].norm() - 0.5f64.sqrt()).abs() < 1e-9);
+
+        // Discharging from 1 V with uic, trapezoidal to within 0.1 %.
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut rc = circuit(&["R1 out 0 1k", "C1 out 0 1u ic=1"]);
+        let out = rc.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut rc, ".tran 3m uic", &options);
+        for (t, solution) in points.iter().skip(1) {
+            let exact = (-t / 1e-3).exp();
+            assert!(
+                (solution[out] - exact).abs() < 1e-3,
+                "{} {}",
+                t,
+                solution[out]
+            );
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! The controlled sources: `E` and `G` controlled by voltages, `F` and `H`
+//! by the currents through voltage sources, each either linear or a SPICE
+//! `POLY` of several controls.
+
+use crate::devices::generic::{
+    add, current, entry, voltage, Conductance, Device, LoadContext, Unknown, Unknowns,
+};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::{Component, Node};
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Handle, Pattern, SparseMatrix};
+use crate::utils::number::parse_number;
+
+/// A SPICE `POLY(n)` of `n` controls: the constant, the linear terms, then
+/// the products of each higher degree with their indices non-decreasing,
+/// in lexicographic order. For two controls that is `p0 + p1 x1 + p2 x2 +
+/// p3 x1² + p4 x1 x2 + p5 x2² + p6 x1³ + ...`, as far as there are
+/// coefficients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poly {
+    dimensions: usize,
+    coefficients: Vec<f64>,
+    /// The controls multiplied in each term.
+    terms: Vec<Vec<usize>>,
+}
+
+impl Poly {
+    /// A one-dimensional polynomial with a single coefficient is, as in
+    /// SPICE, the linear gain rather than a constant.
+    pub fn new(dimensions: usize, coefficients: Vec<f64>) -> Self {
+        let coefficients = if dimensions == 1 && coefficients.len() == 1 {
+            vec![0.0, coefficients[0]]
+        } else {
+            coefficients
+        };
+        Poly {
+            dimensions,
+            terms: terms(dimensions, coefficients.len()),
+            coefficients,
+        }
+    }
+
+    /// `gain x`.
+    pub fn linear(gain: f64) -> Self {
+        Poly::new(1, vec![0.0, gain])
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// The value at `x` and its partial derivatives.
+    pub fn eval(&self, x: &[f64]) -> (f64, Vec<f64>) {
+        let mut value = 0.0;
+        let mut derivatives = vec![0.0; self.dimensions];
+        for (coefficient, term) in self.coefficients.iter().zip(&self.terms) {
+            value += coefficient * term.iter().map(|&i| x[i]).product::<f64>();
+            for (k, &i) in term.iter().enumerate() {
+                let others: f64 = term
+                    .iter()
+                    .enumerate()
+                    .filter(|(l, _)| *l != k)
+                    .map(|(_, &j)| x[j])
+                    .product();
+                derivatives[i] += coefficient * others;
+            }
+        }
+        (value, derivatives)
+    }
+}
+
+/// The first `count` terms of a polynomial in `dimensions` variables, each
+/// degree built from the one below.
+fn terms(dimensions: usize, count: usize) -> Vec<Vec<usize>> {
+    let mut terms = vec![Vec::new()];
+    let mut last: Vec<Vec<usize>> = vec![Vec::new()];
+    while terms.len() < count && dimensions > 0 {
+        last = last
+            .iter()
+            .flat_map(|term| {
+                let start = term.last().copied().unwrap_or(0);
+                (start..dimensions).map(move |i| {
+                    let mut next = term.clone();
+                    next.push(i);
+                    next
+                })
+            })
+            .collect();
+        terms.extend(last.iter().cloned());
+    }
+    terms.truncate(count);
+    terms
+}
+
+/// What a controlled source senses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control {
+    /// The voltage between two nodes.
+    Voltage(Node, Node),
+    /// The current through a voltage source or inductor, by name.
+    Current(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Controlled {
+    name: String,
+    nodes: [Node; 2],
+    /// An `E` or `H`, with a branch current, rather than a `G` or `F`.
+    voltage: bool,
+    controls: Vec<Control>,
+    poly: Poly,
+    unknowns: [Unknown; 2],
+    branch: Unknown,
+    /// The controls as differences of two unknowns, a current being the
+    /// difference between its branch and ground.
+    sensed: Vec<[Unknown; 2]>,
+    /// `(n+, br)`, `(n-, br)`, `(br, n+)` and `(br, n-)`.
+    entries: [Option<Handle>; 4],
+    /// `(br, c+)` and `(br, c-)` of each control, of an `E` or `H`.
+    control_entries: Vec<[Option<Handle>; 2]>,
+    /// The transconductance of each control, of a `G` or `F`.
+    transconductances: Vec<Conductance>,
+    /// Partial derivatives at the last load, for the AC analysis.
+    gains: Vec<f64>,
+}
+
+impl Controlled {
+    /// `Ename n+ n- nc+ nc- gain`, `Fname n+ n- vname gain` and the same
+    /// for `G` and `H`, or with `POLY(n)`, the `n` controls and then the
+    /// coefficients after the output nodes. `components` holds the sources
+    /// whose currents `F` and `H` sense.
+    pub fn parse(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        for key in ["table", "laplace"] {
+            if line.parameter(key).is_some() {
+                return Err(format!("{}= is not supported", key));
+            }
+        }
+        line.unknown_parameters(&[])?;
+        let [positive, negative] = line.nodes()?;
+        let letter = line.name.to_ascii_uppercase().chars().next();
+        let by_current = matches!(letter, Some('F' | 'H'));
+        let words = &line.words[2..];
+        let (dimensions, words) = match words.first().map(|w| w.to_ascii_uppercase()) {
+            Some(w) if w.starts_with("POLY(") && w.ends_with(')') => {
+                let dimensions = w[5..w.len() - 1]
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("Invalid {}", words[0]))?;
+                (Some(dimensions), &words[1..])
+            }
+            _ => (None, words),
+        };
+        let width = if by_current { 1 } else { 2 };
+        let count = dimensions.unwrap_or(1);
+        if words.len() < count * width {
+            return Err(format!("Expected {} controls", count));
+        }
+        let (names, values) = words.split_at(count * width);
+        let mut controls = Vec::new();
+        for control in names.chunks(width) {
+            controls.push(match control {
+                [source] => {
+                    if !components.iter().any(|c| {
+                        c.name.eq_ignore_ascii_case(source)
+                            && matches!(c.prefix(), 'V' | 'E' | 'H' | 'L')
+                    }) {
+                        return Err(format!("No voltage source {}", source));
+                    }
+                    Control::Current(source.clone())
+                }
+                [p, n] => Control::Voltage(Node::new(p.as_str()), Node::new(n.as_str())),
+                _ => unreachable!(),
+            });
+        }
+        let coefficients = values
+            .iter()
+            .map(|v| parse_number(v).ok_or_else(|| format!("Invalid coefficient: {}", v)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        let poly = match (dimensions, &coefficients[..]) {
+            (None, [gain]) => Poly::linear(*gain),
+            (None, _) => return Err("Expected a single gain".to_string()),
+            (Some(_), []) => return Err("No coefficients given".to_string()),
+            (Some(n), _) => Poly::new(n, coefficients),
+        };
+        Ok(Controlled {
+            name: line.name.clone(),
+            nodes: [positive, negative],
+            voltage: matches!(letter, Some('E' | 'H')),
+            controls,
+            poly,
+            unknowns: [None; 2],
+            branch: None,
+            sensed: Vec::new(),
+            entries: [None; 4],
+            control_entries: Vec::new(),
+            transconductances: Vec::new(),
+            gains: Vec::new(),
+        })
+    }
+
+    pub fn poly(&self) -> &Poly {
+        &self.poly
+    }
+}
+
+impl Device for Controlled {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        if self.voltage {
+            self.branch = Some(unknowns.branch(&self.name));
+        }
+        self.sensed = self
+            .controls
+            .iter()
+            .map(|control| match control {
+                Control::Voltage(p, n) => [unknowns.node(p), unknowns.node(n)],
+                Control::Current(source) => [Some(unknowns.branch(source)), None],
+            })
+            .collect();
+        self.gains = vec![0.0; self.controls.len()];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p, n] = self.unknowns;
+        let br = self.branch;
+        if self.voltage {
+            self.entries = [
+                entry(pattern, p, br),
+                entry(pattern, n, br),
+                entry(pattern, br, p),
+                entry(pattern, br, n),
+            ];
+            self.control_entries = self
+                .sensed
+                .iter()
+                .map(|[cp, cn]| [entry(pattern, br, *cp), entry(pattern, br, *cn)])
+                .collect();
+        } else {
+            self.transconductances = self
+                .sensed
+                .iter()
+                .map(|[cp, cn]| Conductance::controlled(pattern, p, n, *cp, *cn))
+                .collect();
+        }
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let x: Vec<f64> = self
+            .sensed
+            .iter()
+            .map(|[p, n]| voltage(context.solution, *p) - voltage(context.solution, *n))
+            .collect();
+        let (value, gains) = self.poly.eval(&x);
+        // What is left of the output once the linear terms are stamped.
+        let offset = value - gains.iter().zip(&x).map(|(g, x)| g * x).sum::<f64>();
+        match self.branch {
+            Some(br) => {
+                add(matrix, self.entries[0], 1.0);
+                add(matrix, self.entries[1], -1.0);
+                add(matrix, self.entries[2], 1.0);
+                add(matrix, self.entries[3], -1.0);
+                for (entries, g) in self.control_entries.iter().zip(&gains) {
+                    add(matrix, entries[0], -g);
+                    add(matrix, entries[1], *g);
+                }
+                rhs[br] += offset;
+            }
+            None => {
+                for (stamp, g) in self.transconductances.iter().zip(&gains) {
+                    stamp.stamp(matrix, *g);
+                }
+                current(rhs, self.unknowns[0], self.unknowns[1], offset);
+            }
+        }
+        self.gains = gains;
+    }
+
+    fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        if self.branch.is_some() {
+            add(matrix, self.entries[0], Complex::from(1.0));
+            add(matrix, self.entries[1], Complex::from(-1.0));
+            add(matrix, self.entries[2], Complex::from(1.0));
+            add(matrix, self.entries[3], Complex::from(-1.0));
+            for (entries, g) in self.control_entries.iter().zip(&self.gains) {
+                add(matrix, entries[0], Complex::from(-g));
+                add(matrix, entries[1], Complex::from(*g));
+            }
+        } else {
+            for (stamp, g) in self.transconductances.iter().zip(&self.gains) {
+                stamp.stamp(matrix, Complex::from(*g));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::tests::{ac, circuit, operating_point};
+
+    #[test]
+    fn test_poly() {
+        assert_eq!(
+            terms(2, 10),
+            [
+                vec![],
+                vec![0],
+                vec![1],
+                vec![0, 0],
+                vec![0, 1],
+                vec![1, 1],
+                vec![0, 0, 0],
+                vec![0, 0, 1],
+                vec![0, 1, 1],
+                vec![1, 1, 1],
+            ]
+        );
+        // 1 + 2a + 3b + 4a² + 5ab at a = 2, b = 3.
+        let poly = Poly::new(2, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let (value, gains) = poly.eval(&[2.0, 3.0]);
+        assert_eq!(value, 1.0 + 4.0 + 9.0 + 16.0 + 30.0);
+        assert_eq!(gains, [2.0 + 16.0 + 15.0, 3.0 + 10.0]);
+        assert_eq!(Poly::new(1, vec![5.0]), Poly::linear(5.0));
+        let (value, gains) = Poly::new(1, vec![0.0, 0.0, 0.0, 2.0]).eval(&[3.0]);
+        assert_eq!((value, gains[0]), (54.0, 54.0));
+    }
+
+    #[test]
+    fn test_controlled() {
+        let (gains, solution) = operating_point(&[
+            "V1 in 0 2",
+            "R1 in 0 1k",
+            "E1 e 0 in 0 3",
+            "G1 0 g in 0 1m",
+            "RG g 0 1k",
+            "F1 0 f V1 2",
+            "RF f 0 1k",
+            "H1 h 0 V1 -1k",
+        ]);
+        let find = |name: &str| solution[gains.unknowns().find(name).unwrap()];
+        // 2 mA leave V1's positive terminal, so its branch current is -2 mA.
+        assert!((find("I(V1)") + 2e-3).abs() < 1e-15);
+        assert!((find("V(e)") - 6.0).abs() < 1e-12);
+        assert!((find("V(g)") - 2.0).abs() < 1e-12);
+        assert!((find("V(f)") + 4.0).abs() < 1e-12);
+        assert!((find("V(h)") - 2.0).abs() < 1e-12);
+
+        // A multiplier, solved by Newton, and its small-signal gain V(b)
+        // with respect to V(a).
+        let (multiplier, solution) = operating_point(&[
+            "V1 a 0 2 AC 1",
+            "V2 b 0 3",
+            "E1 out 0 POLY(2) a 0 b 0 0 0 0 0 1",
+            "R1 out 0 1k",
+        ]);
+        let out = multiplier.unknowns().find("V(out)").unwrap();
+        assert!((solution[out] - 6.0).abs() < 1e-12);
+        let mut multiplier = circuit(&[
+            "V1 a 0 2 AC 1",
+            "V2 b 0 3",
+            "G1 0 out POLY(2) a 0 b 0 0 0 0 0 1m",
+            "R1 out 0 1k",
+        ]);
+        let out = multiplier.unknowns().find("V(out)").unwrap();
+        let response = ac(&mut multiplier, 1.0);
+        assert!((response[out] - Complex::from(3.0)).norm() < 1e-12);
+
+        let line = ElementLine::new(&Component::new("F1", vec![], "a 0 V9 2"));
+        assert!(Controlled::parse(&line, &[]).is_err());
+        let line = ElementLine::new(&Component::new("E1", vec![], "a 0 value={V(b)}"));
+        assert!(Controlled::parse(&line, &[]).is_err());
+    }
+}
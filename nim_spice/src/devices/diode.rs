@@ -0,0 +1,745 @@
+//! The SPICE junction diode.
+//!
+//! Shockley's equation with emission coefficient `N`, a recombination
+//! current `ISR`, high injection above `IKF` and an exponential reverse
+//! breakdown at `BV` with LTspice's low-level knee `IBVL`, in series with
+//! `RS` through an internal node. The
+//! junction charge is the depletion charge of `CJO`, `VJ` and `M`, carried
+//! on linearly above `FC VJ`, plus the diffusion charge `TT I`. Saturation
+//! currents, junction potential and capacitance follow the temperature as
+//! in ngspice's `diotemp.c`, and the load follows `dioload.c`.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::generic::{
+    current, flicker_noise, shot_noise, thermal_noise, voltage, Conductance, Device, LoadContext,
+    NoiseSource, State, Unknown, Unknowns, BOLTZMANN, CHARGE, KELVIN,
+};
+use crate::devices::limiting::{critical_voltage, pnjlim};
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// Temperature at which the energy gap formula is referred, in K.
+pub(crate) const REFERENCE_TEMPERATURE: f64 = 300.15;
+
+/// Relative tolerance of the breakdown current matched at `BV`, SPICE's
+/// default `reltol`.
+const BREAKDOWN_TOLERANCE: f64 = 1e-3;
+
+/// LTspice's sidewall, soft recovery and piece-wise linear parameters,
+/// which the junction model here has no place for. Cards with them load,
+/// and the model lists the ones it ignored.
+const IGNORED: [&str; 14] = [
+    "isw",
+    "ns",
+    "vp",
+    "ron",
+    "roff",
+    "vfwd",
+    "vrev",
+    "rrev",
+    "ilimit",
+    "revilimit",
+    "epsilon",
+    "revepsilon",
+    "rsw",
+    "cjsw",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiodeModel {
+    pub is: f64,
+    pub n: f64,
+    pub rs: f64,
+    pub tt: f64,
+    pub cjo: f64,
+    pub vj: f64,
+    pub m: f64,
+    pub fc: f64,
+    pub eg: f64,
+    pub xti: f64,
+    pub kf: f64,
+    pub af: f64,
+    pub bv: Option<f64>,
+    pub ibv: f64,
+    /// Emission coefficient of the breakdown, `N` unless given.
+    pub nbv: f64,
+    /// Current of LTspice's low-level breakdown, which softens the knee
+    /// of a zener below `BV`; none when zero.
+    pub ibvl: f64,
+    /// Emission coefficient of the low-level breakdown.
+    pub nbvl: f64,
+    pub isr: f64,
+    pub nr: f64,
+    /// Knee current of high injection, none when zero.
+    pub ikf: f64,
+    pub trs1: f64,
+    pub trs2: f64,
+    pub tbv1: f64,
+    pub tbv2: f64,
+    /// Temperature the parameters were measured at in °C, `.options tnom`
+    /// unless given.
+    pub tnom: Option<f64>,
+    /// Parameters of the card in `IGNORED`, for the caller to warn about.
+    pub ignored: Vec<String>,
+}
+
+impl Default for DiodeModel {
+    fn default() -> Self {
+        DiodeModel {
+            is: 1e-14,
+            n: 1.0,
+            rs: 0.0,
+            tt: 0.0,
+            cjo: 0.0,
+            vj: 1.0,
+            m: 0.5,
+            fc: 0.5,
+            eg: 1.11,
+            xti: 3.0,
+            kf: 0.0,
+            af: 1.0,
+            bv: None,
+            ibv: 1e-3,
+            nbv: 1.0,
+            ibvl: 0.0,
+            nbvl: 1.0,
+            isr: 0.0,
+            nr: 2.0,
+            ikf: 0.0,
+            trs1: 0.0,
+            trs2: 0.0,
+            tbv1: 0.0,
+            tbv2: 0.0,
+            tnom: None,
+            ignored: Vec::new(),
+        }
+    }
+}
+
+impl DiodeModel {
+    /// The parameters of a `D` model card, with ngspice's and LTspice's
+    /// alternative names. LTspice's parameters in `IGNORED` are accepted
+    /// and listed in `ignored`.
+    pub fn new(model: &Model) -> Result<Self, String> {
+        let known = [
+            "is", "n", "rs", "tt", "cjo", "cj0", "cj", "vj", "pb", "m", "mj", "fc", "eg", "xti",
+            "kf", "af", "bv", "vb", "vrb", "ibv", "ib", "nbv", "ibvl", "nbvl", "isr", "nr", "ikf",
+            "ik", "trs1", "trs", "trs2", "tbv1", "tbv", "tbv2", "tnom", "tref", "level",
+        ];
+        model.unknown_parameters(&[&known[..], &IGNORED[..]].concat())?;
+        let d = DiodeModel::default();
+        let n = model.number("n")?.unwrap_or(d.n);
+        let value = |names: &[&str], default: f64| -> Result<f64, String> {
+            Ok(model.alias(names)?.unwrap_or(default))
+        };
+        let diode = DiodeModel {
+            is: value(&["is"], d.is)?,
+            n,
+            rs: value(&["rs"], d.rs)?,
+            tt: value(&["tt"], d.tt)?,
+            cjo: value(&["cjo", "cj0", "cj"], d.cjo)?,
+            vj: value(&["vj", "pb"], d.vj)?,
+            m: value(&["m", "mj"], d.m)?,
+            fc: value(&["fc"], d.fc)?,
+            eg: value(&["eg"], d.eg)?,
+            xti: value(&["xti"], d.xti)?,
+            kf: value(&["kf"], d.kf)?,
+            af: value(&["af"], d.af)?,
+            bv: model.alias(&["bv", "vb", "vrb"])?.filter(|bv| *bv != 0.0),
+            ibv: value(&["ibv", "ib"], d.ibv)?,
+            nbv: value(&["nbv"], n)?,
+            ibvl: value(&["ibvl"], d.ibvl)?,
+            nbvl: value(&["nbvl"], d.nbvl)?,
+            isr: value(&["isr"], d.isr)?,
+            nr: value(&["nr"], d.nr)?,
+            ikf: value(&["ikf", "ik"], d.ikf)?,
+            trs1: value(&["trs1", "trs"], d.trs1)?,
+            trs2: value(&["trs2"], d.trs2)?,
+            tbv1: value(&["tbv1", "tbv"], d.tbv1)?,
+            tbv2: value(&["tbv2"], d.tbv2)?,
+            tnom: model.alias(&["tnom", "tref"])?,
+            ignored: model
+                .parameters
+                .iter()
+                .map(|(key, _)| key.clone())
+                .filter(|key| IGNORED.contains(&key.as_str()))
+                .collect(),
+        };
+        if diode.is <= 0.0 || diode.n <= 0.0 || diode.vj <= 0.0 {
+            return Err(format!(
+                "Model {}: IS, N and VJ must be positive",
+                model.name
+            ));
+        }
+        if diode.nbvl <= 0.0 {
+            return Err(format!("Model {}: NBVL must be positive", model.name));
+        }
+        if diode.m >= 1.0 || diode.fc >= 1.0 {
+            return Err(format!("Model {}: M and FC must be below 1", model.name));
+        }
+        Ok(diode)
+    }
+}
+
+/// Junction potential and zero-bias capacitance scaled from `tnom` to `t`,
+/// both in K, through the temperature dependence of silicon's energy gap.
+pub(crate) fn junction_temperature(vj: f64, cj: f64, m: f64, t: f64, tnom: f64) -> (f64, f64) {
+    let factor = |t: f64| {
+        let vt = BOLTZMANN * t / CHARGE;
+        let egfet = 1.16 - (7.02e-4 * t * t) / (t + 1108.0);
+        let arg = -egfet / (2.0 * BOLTZMANN * t)
+            + 1.1150877 / (BOLTZMANN * (2.0 * REFERENCE_TEMPERATURE));
+        -2.0 * vt * (1.5 * (t / REFERENCE_TEMPERATURE).ln() + CHARGE * arg)
+    };
+    let fact1 = tnom / REFERENCE_TEMPERATURE;
+    let fact2 = t / REFERENCE_TEMPERATURE;
+    let pbo = (vj - factor(tnom)) / fact1;
+    let gmaold = (vj - pbo) / pbo;
+    let cj = cj / (1.0 + m * (4e-4 * (tnom - REFERENCE_TEMPERATURE) - gmaold));
+    let potential = factor(t) + fact2 * pbo;
+    let gmanew = (potential - pbo) / pbo;
+    (
+        potential,
+        cj * (1.0 + m * (4e-4 * (t - REFERENCE_TEMPERATURE) - gmanew)),
+    )
+}
+
+/// Depletion charge and capacitance of a junction at `v`, carried on
+/// linearly in capacitance above `fc vj` where the formula would diverge.
+pub(crate) fn depletion(v: f64, cj: f64, vj: f64, m: f64, fc: f64) -> (f64, f64) {
+    if cj == 0.0 {
+        return (0.0, 0.0);
+    }
+    let corner = fc * vj;
+    if v < corner {
+        let arg = 1.0 - v / vj;
+        let sarg = (-m * arg.ln()).exp();
+        (vj * cj * (1.0 - arg * sarg) / (1.0 - m), cj * sarg)
+    } else {
+        let xfc = (1.0 - fc).ln();
+        let f1 = vj * (1.0 - ((1.0 - m) * xfc).exp()) / (1.0 - m);
+        let f2 = ((1.0 + m) * xfc).exp();
+        let f3 = 1.0 - fc * (1.0 + m);
+        let czof2 = cj / f2;
+        (
+            cj * f1 + czof2 * (f3 * (v - corner) + m / (2.0 * vj) * (v * v - corner * corner)),
+            czof2 * (f3 + m * v / vj),
+        )
+    }
+}
+
+/// The model's parameters at the instance temperature, scaled by its area
+/// and multiplier.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scaled {
+    temperature: f64,
+    vt: f64,
+    is: f64,
+    isr: f64,
+    vcrit: f64,
+    /// The voltage where the breakdown current is `IBV`.
+    breakdown: Option<f64>,
+    /// `BV` at the temperature and `IBVL` scaled, for the low-level
+    /// breakdown.
+    bv: f64,
+    ibvl: f64,
+    vj: f64,
+    cj: f64,
+    ikf: f64,
+    /// Conductance of `RS`, zero without one.
+    gs: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diode {
+    name: String,
+    nodes: [Node; 2],
+    model: DiodeModel,
+    area: f64,
+    multiplier: f64,
+    /// Junction voltage to start from with `uic`.
+    initial: Option<f64>,
+    temp: Option<f64>,
+    dtemp: f64,
+    scaled: Scaled,
+    /// Anode, cathode and the junction's anode inside `RS`.
+    unknowns: [Unknown; 3],
+    series: Conductance,
+    junction: Conductance,
+    charge: State,
+    /// The linearisation of the last load: junction voltage, current
+    /// including gmin, conductance and capacitance.
+    vd: f64,
+    cd: f64,
+    gd: f64,
+    capacitance: f64,
+}
+
+impl Diode {
+    /// `Dname anode cathode model [area] [off] [area=] [m=] [ic=] [temp=]
+    /// [dtemp=]`.
+    pub fn parse(line: &ElementLine, models: &[Model]) -> Result<Self, String> {
+        line.unknown_parameters(&["area", "m", "ic", "temp", "dtemp"])?;
+        let [anode, cathode] = line.nodes()?;
+        let model = line.words.get(2).ok_or("No model given")?;
+        let model = DiodeModel::new(find_model(models, model, &["D"])?)?;
+        let words: Vec<&String> = line.words[3..]
+            .iter()
+            .filter(|w| !w.eq_ignore_ascii_case("off"))
+            .collect();
+        let area = match (line.number("area")?, words.first()) {
+            (Some(area), _) => area,
+            (None, None) => 1.0,
+            (None, Some(_)) => line.value(3, "area")?,
+        };
+        let multiplier = line.number("m")?.unwrap_or(1.0);
+        if area <= 0.0 || multiplier <= 0.0 {
+            return Err("The area and multiplier must be positive".to_string());
+        }
+        Ok(Diode {
+            area,
+            multiplier,
+            initial: line.number("ic")?,
+            temp: line.number("temp")?,
+            dtemp: line.number("dtemp")?.unwrap_or(0.0),
+            ..Diode::new(&line.name, anode, cathode, model)
+        })
+    }
+
+    /// A diode of unit area, such as the body diode of a power MOSFET.
+    pub fn new(name: &str, anode: Node, cathode: Node, model: DiodeModel) -> Self {
+        Diode {
+            name: name.to_string(),
+            nodes: [anode, cathode],
+            model,
+            area: 1.0,
+            multiplier: 1.0,
+            initial: None,
+            temp: None,
+            dtemp: 0.0,
+            scaled: Scaled::default(),
+            unknowns: [None; 3],
+            series: Conductance::default(),
+            junction: Conductance::default(),
+            charge: State::default(),
+            vd: 0.0,
+            cd: 0.0,
+            gd: 0.0,
+            capacitance: 0.0,
+        }
+    }
+
+    /// Take the multiplier and temperature of the instance the diode is
+    /// part of.
+    pub(crate) fn set_instance(&mut self, multiplier: f64, temp: Option<f64>, dtemp: f64) {
+        self.multiplier = multiplier;
+        self.temp = temp;
+        self.dtemp = dtemp;
+    }
+
+    /// Junction voltage, current and conductance at the last load.
+    pub fn operating_point(&self) -> (f64, f64, f64) {
+        (self.vd, self.cd, self.gd)
+    }
+
+    /// Current and conductance of the junction at `vd`, without gmin.
+    fn junction_current(&self, vd: f64) -> (f64, f64) {
+        let s = &self.scaled;
+        let m = &self.model;
+        let (mut cd, mut gd) = self.shockley(vd);
+        // The low-level breakdown, taken as zero at zero bias.
+        if s.ibvl > 0.0 && s.breakdown.is_some() {
+            let vtl = m.nbvl * s.vt;
+            let e = (-(s.bv + vd) / vtl).exp();
+            cd -= s.ibvl * (e - (-s.bv / vtl).exp());
+            gd += s.ibvl * e / vtl;
+        }
+        (cd, gd)
+    }
+
+    /// The junction of SPICE: forward, reverse and breakdown.
+    fn shockley(&self, vd: f64) -> (f64, f64) {
+        let s = &self.scaled;
+        let m = &self.model;
+        let vte = m.n * s.vt;
+        if vd >= -3.0 * vte {
+            let evd = (vd / vte).exp();
+            let mut cd = s.is * (evd - 1.0);
+            let mut gd = s.is * evd / vte;
+            if s.isr > 0.0 {
+                let vtr = m.nr * s.vt;
+                let evr = (vd / vtr).exp();
+                let arg = 1.0 - vd / m.vj;
+                let base = arg * arg + 0.005;
+                let generation = base.powf(m.m / 2.0);
+                let slope = -m.m * arg / m.vj * base.powf(m.m / 2.0 - 1.0);
+                let recombination = s.isr * (evr - 1.0);
+                cd += recombination * generation;
+                gd += s.isr * evr / vtr * generation + recombination * slope;
+            }
+            if s.ikf > 0.0 && cd > 1e-18 {
+                let factor = (s.ikf / (s.ikf + cd)).sqrt();
+                gd *= factor * (1.0 - 0.5 * cd / (s.ikf + cd));
+                cd *= factor;
+            }
+            (cd, gd)
+        } else if s.breakdown.is_none_or(|bv| vd >= -bv) {
+            let arg = 3.0 * vte / (vd * std::f64::consts::E);
+            let arg = arg * arg * arg;
+            (-s.is * (1.0 + arg), s.is * 3.0 * arg / vd)
+        } else {
+            let vtebrk = m.nbv * s.vt;
+            let evrev = (-(s.breakdown.unwrap() + vd) / vtebrk).exp();
+            (-s.is * evrev, s.is * evrev / vtebrk)
+        }
+    }
+
+    /// Junction charge and capacitance at `vd`, where the junction carries
+    /// `cd` with conductance `gd`.
+    fn junction_charge(&self, vd: f64, cd: f64, gd: f64) -> (f64, f64) {
+        let (q, c) = depletion(
+            vd,
+            self.scaled.cj,
+            self.scaled.vj,
+            self.model.m,
+            self.model.fc,
+        );
+        (q + self.model.tt * cd, c + self.model.tt * gd)
+    }
+
+    fn junction_voltage(&self, solution: &[f64]) -> f64 {
+        voltage(solution, self.unknowns[2]) - voltage(solution, self.unknowns[1])
+    }
+}
+
+impl Device for Diode {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let anode = unknowns.node(&self.nodes[0]);
+        let cathode = unknowns.node(&self.nodes[1]);
+        let inner = if self.model.rs > 0.0 {
+            Some(unknowns.internal(&self.name, "a"))
+        } else {
+            anode
+        };
+        self.unknowns = [anode, cathode, inner];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [anode, cathode, inner] = self.unknowns;
+        if inner != anode {
+            self.series = Conductance::new(pattern, anode, inner);
+        }
+        self.junction = Conductance::new(pattern, inner, cathode);
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let m = &self.model;
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let t = celsius + KELVIN;
+        let tnom = m.tnom.unwrap_or(nominal) + KELVIN;
+        let vt = BOLTZMANN * t / CHARGE;
+        let ratio = t / tnom;
+        let scale = self.area * self.multiplier;
+        let saturation =
+            |is: f64, n: f64| is * ((ratio - 1.0) * m.eg / (n * vt) + m.xti / n * ratio.ln()).exp();
+        let is = saturation(m.is, m.n) * scale;
+        let (vj, cj) = junction_temperature(m.vj, m.cjo * scale, m.m, t, tnom);
+        let dt = t - tnom;
+        let rs = m.rs * (1.0 + m.trs1 * dt + m.trs2 * dt * dt);
+
+        // Move the knee of the breakdown so that the current there is IBV.
+        let bv = m.bv.map(|bv| bv * (1.0 + m.tbv1 * dt + m.tbv2 * dt * dt));
+        let breakdown = bv.map(|bv| {
+            let vtebrk = m.nbv * vt;
+            let cbv = m.ibv * scale;
+            if cbv < is * bv / vt {
+                return bv;
+            }
+            let mut xbv = bv - vtebrk * (1.0 + cbv / is).ln();
+            for _ in 0..25 {
+                xbv = bv - vtebrk * (cbv / is + 1.0 - xbv / vt).ln();
+                let xcbv = is * (((bv - xbv) / vtebrk).exp() - 1.0 + xbv / vt);
+                if (xcbv - cbv).abs() <= BREAKDOWN_TOLERANCE * cbv {
+                    break;
+                }
+            }
+            xbv
+        });
+        self.scaled = Scaled {
+            temperature: celsius,
+            vt,
+            is,
+            isr: saturation(m.isr, m.nr) * scale,
+            vcrit: critical_voltage(m.n * vt, is),
+            breakdown,
+            bv: bv.unwrap_or(0.0),
+            ibvl: m.ibvl * scale,
+            vj,
+            cj,
+            ikf: m.ikf * scale,
+            gs: if rs > 0.0 { scale / rs } else { 0.0 },
+        };
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let s = &self.scaled;
+        let vte = self.model.n * s.vt;
+        let vtebrk = self.model.nbv * s.vt;
+        let new = self.junction_voltage(context.solution);
+        let (vd, limited) = match s.breakdown {
+            Some(bv) if new < (-bv + 10.0 * vtebrk).min(0.0) => {
+                let (v, limited) = pnjlim(-(new + bv), -(self.vd + bv), vtebrk, s.vcrit);
+                (-(v + bv), limited)
+            }
+            _ => pnjlim(new, self.vd, vte, s.vcrit),
+        };
+        if limited {
+            context.limit(&self.name);
+        }
+        let (cd, gd) = self.junction_current(vd);
+        let (q, capacitance) = self.junction_charge(vd, cd, gd);
+        let mut g = gd + context.gmin;
+        let mut i = cd + context.gmin * vd;
+        self.vd = vd;
+        self.cd = i;
+        self.gd = g;
+        self.capacitance = capacitance;
+        match context.integrator {
+            Some(integrator) => {
+                let companion = self.charge.integrate(integrator, q, capacitance, vd);
+                g += companion.geq;
+                i += companion.geq * vd + companion.ceq;
+            }
+            None => self.charge.reset(q),
+        }
+        self.series.stamp(matrix, self.scaled.gs);
+        self.junction.stamp(matrix, g);
+        current(rhs, self.unknowns[2], self.unknowns[1], i - g * vd);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        self.series.stamp(matrix, Complex::from(self.scaled.gs));
+        self.junction
+            .stamp(matrix, Complex::new(self.gd, omega * self.capacitance));
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let [anode, cathode, inner] = self.unknowns;
+        let mut sources = Vec::new();
+        if self.scaled.gs > 0.0 {
+            sources.push(NoiseSource {
+                name: format!("{} rs", self.name),
+                positive: anode,
+                negative: inner,
+                density: thermal_noise(self.scaled.gs, self.scaled.temperature),
+            });
+        }
+        sources.push(NoiseSource {
+            name: format!("{} shot", self.name),
+            positive: inner,
+            negative: cathode,
+            density: shot_noise(self.cd),
+        });
+        if self.model.kf > 0.0 {
+            sources.push(NoiseSource {
+                name: format!("{} flicker", self.name),
+                positive: inner,
+                negative: cathode,
+                density: flicker_noise(self.model.kf, self.model.af, 1.0, self.cd, frequency),
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some(vd) = self.initial {
+            let (cd, gd) = self.junction_current(vd);
+            self.charge.reset(self.junction_charge(vd, cd, gd).0);
+            self.vd = vd;
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        self.charge.accept();
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charge
+            .allowed_step(timestep, integrator, h, Quantity::Charge)
+    }
+
+    /// The current predicted by the linearisation must agree with the one
+    /// at the new junction voltage, as in ngspice's `DIOconvTest`.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let delta = self.gd * (self.junction_voltage(solution) - self.vd);
+        let predicted = self.cd + delta;
+        let tolerance = options.reltol * predicted.abs().max(self.cd.abs()) + options.abstol;
+        delta.abs() > tolerance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::Method;
+    use crate::devices::model::parse_models;
+    use crate::devices::tests::{ac, circuit, operating_point, solve_dc, transient};
+
+    /// The forward voltage of a junction carrying `i`, with gmin across it.
+    fn forward(i: f64, is: f64, n: f64, temperature: f64) -> f64 {
+        let vt = BOLTZMANN * (temperature + KELVIN) / CHARGE;
+        let mut v = 0.6;
+        for _ in 0..20 {
+            v = n * vt * ((i - 1e-12 * v) / is + 1.0).ln();
+        }
+        v
+    }
+
+    #[test]
+    fn test_forward() {
+        let (circuit, solution) = operating_point(&[
+            "I1 0 a 1m",
+            "D1 a 0 DMOD",
+            "I2 0 b 1m",
+            "D2 b 0 DMOD 2",
+            ".model DMOD D(IS=1e-14 N=1.5 RS=10)",
+        ]);
+        let find = |name: &str| solution[circuit.unknowns().find(name).unwrap()];
+        let vd = forward(1e-3, 1e-14, 1.5, 27.0);
+        assert!((find("V(a)") - vd - 10.0 * 1e-3).abs() < 1e-9);
+        // Twice the area halves RS and doubles IS.
+        let vd = forward(1e-3, 2e-14, 1.5, 27.0);
+        assert!((find("V(b)") - vd - 5.0 * 1e-3).abs() < 1e-9);
+        assert!(circuit.unknowns().find("V(D1#a)").is_some());
+
+        let noise = circuit.noise(10.0);
+        let shot = noise.iter().find(|n| n.name == "D1 shot").unwrap();
+        assert!((shot.density / (2.0 * CHARGE * 1e-3) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_temperature() {
+        let options = Options::default();
+        let mut hot = circuit(&["I1 0 a 1m", "D1 a 0 DMOD", ".model DMOD D(IS=1e-14)"]);
+        hot.set_temperature(127.0, &options);
+        let a = hot.unknowns().find("V(a)").unwrap();
+        let solution = solve_dc(&mut hot);
+        // IS grows as (T/Tnom)^XTI exp((T/Tnom - 1) EG/vt).
+        let (t, tnom) = (127.0 + KELVIN, 27.0 + KELVIN);
+        let vt = BOLTZMANN * t / CHARGE;
+        let is = 1e-14 * ((t / tnom - 1.0) * 1.11 / vt).exp() * (t / tnom).powi(3);
+        assert!((solution[a] - forward(1e-3, is, 1.0, 127.0)).abs() < 1e-9);
+
+        // Unchanged at the nominal temperature, and the built-in
+        // potential falls as the temperature rises.
+        let (vj, cj) = junction_temperature(0.7, 1e-12, 0.5, tnom, tnom);
+        assert!((vj - 0.7).abs() < 1e-12 && (cj - 1e-12).abs() < 1e-24);
+        let (vj, cj) = junction_temperature(0.7, 1e-12, 0.5, t, tnom);
+        assert!(vj < 0.6 && cj > 1e-12, "{} {}", vj, cj);
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let (circuit, solution) = operating_point(&[
+            "I1 a 0 1m",
+            "D1 a 0 Z5V1",
+            "I2 b 0 10m",
+            "D2 b 0 Z5V1",
+            ".model Z5V1 D(BV=5.1 IBV=1m NBV=2)",
+        ]);
+        let find = |name: &str| solution[circuit.unknowns().find(name).unwrap()];
+        // IBV flows at BV, and ten times as much NBV vt ln 10 further on.
+        assert!((find("V(a)") + 5.1).abs() < 1e-4, "{}", find("V(a)"));
+        let vt = BOLTZMANN * (27.0 + KELVIN) / CHARGE;
+        assert!((find("V(b)") + 5.1 + 2.0 * vt * 10f64.ln()).abs() < 1e-4);
+
+        // The low-level breakdown of an LTspice zener card adds its own
+        // exponential below the knee; the LTspice-only extras load.
+        let reverse = |card: &str| {
+            let (circuit, solution) = operating_point(&["V1 0 a 4", "D1 a 0 DZ", card]);
+            solution[circuit.unknowns().find("I(V1)").unwrap()]
+        };
+        let plain = reverse(".model DZ D(BV=5.1 IBV=1m)");
+        let soft = reverse(".model DZ D(BV=5.1 IBV=1m IBVL=10u NBVL=20 Vp=1 Epsilon=0.1)");
+        let vtl = 20.0 * vt;
+        let expected = 10e-6 * ((-1.1 / vtl).exp() - (-5.1 / vtl).exp());
+        // I(V1) runs from ground into the anode, against the diode current.
+        assert!(
+            (plain - soft - expected).abs() < 1e-3 * expected,
+            "{}",
+            plain - soft
+        );
+        let (circuit, solution) = operating_point(&[
+            "I1 0 a 1m",
+            "D1 a 0 DZ",
+            ".model DZ D(BV=5.1 IBV=1m IBVL=10u NBVL=20)",
+        ]);
+        let a = solution[circuit.unknowns().find("V(a)").unwrap()];
+        assert!(a > 0.0 && a < 1.0);
+
+        let models = parse_models(&[".model DZ D(BV=5.1 Vp=1 Epsilon=0.1)".to_string()]).unwrap();
+        assert_eq!(
+            DiodeModel::new(&models[0]).unwrap().ignored,
+            ["vp", "epsilon"]
+        );
+        let models = parse_models(&[".model DZ D(BV=5.1)".to_string()]).unwrap();
+        assert!(DiodeModel::new(&models[0]).unwrap().ignored.is_empty());
+    }
+
+    #[test]
+    fn test_capacitance() {
+        let (q, c) = depletion(-3.0, 1e-12, 1.0, 0.5, 0.5);
+        assert!((c - 0.5e-12).abs() < 1e-24);
+        assert!((q + 2e-12).abs() < 1e-24);
+        // Continuous at the corner, in charge and capacitance.
+        let below = depletion(0.5 - 1e-9, 1e-12, 1.0, 0.5, 0.5);
+        let above = depletion(0.5, 1e-12, 1.0, 0.5, 0.5);
+        assert!((below.0 - above.0).abs() < 1e-20 && (below.1 - above.1).abs() < 1e-19);
+
+        // Reverse biased at 3 V through an inductor, a short at DC; the AC
+        // current into the diode is jωC.
+        let mut biased = circuit(&[
+            "V1 in 0 -3",
+            "L1 in a 1",
+            "I1 0 a 0 AC 1",
+            "D1 a 0 DMOD",
+            ".model DMOD D(CJO=1p VJ=1 M=0.5 TT=1n)",
+        ]);
+        let a = biased.unknowns().find("V(a)").unwrap();
+        let omega = 1e9;
+        let response = ac(&mut biased, omega);
+        // 1 A into the diode in parallel with the inductor's jωL.
+        let admittance = Complex::from(1.0) / response[a] - Complex::new(0.0, -1.0 / omega);
+        assert!((admittance.im / (omega * 0.5e-12) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rectifier() {
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut rectifier = circuit(&[
+            "V1 in 0 SINE(0 5 1k)",
+            "D1 in out DMOD",
+            "R1 out 0 1k",
+            ".model DMOD D(IS=1e-14 CJO=10p TT=10n)",
+        ]);
+        let out = rectifier.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut rectifier, ".tran 2m", &options);
+        let peak = points.iter().map(|(_, s)| s[out]).fold(f64::MIN, f64::max);
+        let trough = points.iter().map(|(_, s)| s[out]).fold(f64::MAX, f64::min);
+        // One forward drop below the peak, and blocking in between.
+        assert!(peak > 4.2 && peak < 4.5, "{}", peak);
+        assert!(trough > -1e-3, "{}", trough);
+    }
+}
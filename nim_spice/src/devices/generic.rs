@@ -1 +1,824 @@
+//! What every circuit element implements, and the circuit that drives them.
+//!
+//! A [`Device`] claims its unknowns, asks for the matrix entries it will
+//! stamp, and then stamps its linearisation whenever an analysis asks: at
+//! every Newton iteration of the DC and transient analyses, at every
+//! frequency of the AC analysis. Charges and fluxes go through a [`State`],
+//! which integrates them and estimates their truncation error. A
+//! [`Circuit`] holds the devices and is the [`System`] the analyses solve,
+//! so a new model is one more `Device` and nothing else.
 
+use crate::analysis::integration::{Companion, History, Integrator};
+use crate::analysis::newton::{Load, System, UnknownKind};
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Handle, Pattern, Scalar, SparseMatrix};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// Boltzmann's constant in J/K.
+pub const BOLTZMANN: f64 = 1.380649e-23;
+
+/// Elementary charge in C.
+pub const CHARGE: f64 = 1.602176634e-19;
+
+/// Offset of the Celsius scale from absolute zero.
+pub const KELVIN: f64 = 273.15;
+
+/// Circuit temperature unless `.temp` or `.step temp` says otherwise, in °C.
+pub const DEFAULT_TEMPERATURE: f64 = 27.0;
+
+/// An MNA unknown, `None` for ground, which has none.
+pub type Unknown = Option<usize>;
+
+/// The unknowns of a circuit: node voltages, the internal nodes of
+/// devices and the branch currents of sources and inductors, in the order
+/// devices claim them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Unknowns {
+    names: Vec<String>,
+    kinds: Vec<UnknownKind>,
+    lookup: HashMap<String, usize>,
+}
+
+impl Unknowns {
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The voltage of `node`, numbered the first time it is asked for.
+    pub fn node(&mut self, node: &Node) -> Unknown {
+        if node.is_ground() {
+            return None;
+        }
+        Some(self.claim(format!("V({})", node.name), UnknownKind::Voltage))
+    }
+
+    /// A node inside `device` that no other device sees, such as the node
+    /// between a diode's junction and its series resistance.
+    pub fn internal(&mut self, device: &str, name: &str) -> usize {
+        self.claim(format!("V({}#{})", device, name), UnknownKind::Voltage)
+    }
+
+    /// The current through `device`, for elements whose current cannot be
+    /// written in terms of node voltages.
+    pub fn branch(&mut self, device: &str) -> usize {
+        self.claim(format!("I({})", device), UnknownKind::Current)
+    }
+
+    /// The unknown by its name, `V(out)` or `I(V1)`, ignoring case.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.lookup.get(&name.to_ascii_lowercase()).copied()
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        &self.names[index]
+    }
+
+    pub fn kind(&self, index: usize) -> UnknownKind {
+        self.kinds[index]
+    }
+
+    fn claim(&mut self, name: String, kind: UnknownKind) -> usize {
+        let key = name.to_ascii_lowercase();
+        if let Some(index) = self.lookup.get(&key) {
+            return *index;
+        }
+        self.names.push(name);
+        self.kinds.push(kind);
+        self.lookup.insert(key, self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
+
+/// The handle of entry `(row, column)`, `None` if either is ground.
+pub fn entry(pattern: &mut Pattern, row: Unknown, column: Unknown) -> Option<Handle> {
+    Some(pattern.handle(row?, column?))
+}
+
+/// Add `value` to an entry that may be in the ground row or column.
+pub fn add<T: Scalar>(matrix: &mut SparseMatrix<T>, handle: Option<Handle>, value: T) {
+    if let Some(handle) = handle {
+        matrix.add(handle, value);
+    }
+}
+
+/// Stamp a current `value` flowing through a device from `positive` to
+/// `negative`, that is out of `positive` and into `negative`.
+pub fn current<T: Scalar>(rhs: &mut [T], positive: Unknown, negative: Unknown, value: T) {
+    if let Some(p) = positive {
+        rhs[p] -= value;
+    }
+    if let Some(n) = negative {
+        rhs[n] += value;
+    }
+}
+
+/// Voltage of an unknown, zero for ground.
+pub fn voltage(solution: &[f64], node: Unknown) -> f64 {
+    node.map_or(0.0, |n| solution[n])
+}
+
+/// The four entries of a conductance, or of a transconductance whose
+/// current from `positive` to `negative` is controlled by the voltage
+/// between two other nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Conductance {
+    entries: [Option<Handle>; 4],
+}
+
+impl Conductance {
+    /// A conductance between two nodes.
+    pub fn new(pattern: &mut Pattern, positive: Unknown, negative: Unknown) -> Self {
+        Conductance::controlled(pattern, positive, negative, positive, negative)
+    }
+
+    /// A current from `positive` to `negative` proportional to the voltage
+    /// from `control_positive` to `control_negative`.
+    pub fn controlled(
+        pattern: &mut Pattern,
+        positive: Unknown,
+        negative: Unknown,
+        control_positive: Unknown,
+        control_negative: Unknown,
+    ) -> Self {
+        Conductance {
+            entries: [
+                entry(pattern, positive, control_positive),
+                entry(pattern, positive, control_negative),
+                entry(pattern, negative, control_positive),
+                entry(pattern, negative, control_negative),
+            ],
+        }
+    }
+
+    pub fn stamp<T: Scalar>(&self, matrix: &mut SparseMatrix<T>, g: T) {
+        add(matrix, self.entries[0], g);
+        add(matrix, self.entries[1], -g);
+        add(matrix, self.entries[2], -g);
+        add(matrix, self.entries[3], g);
+    }
+}
+
+/// A charge or flux of a device: its value at the point being solved and
+/// the values accepted before.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    history: History,
+    value: f64,
+    derivative: f64,
+}
+
+impl State {
+    /// Settle at `value`, not changing, as at the operating point.
+    pub fn reset(&mut self, value: f64) {
+        self.history = History::new(value);
+        self.value = value;
+        self.derivative = 0.0;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Time derivative at the point being solved: a current for a charge,
+    /// a voltage for a flux.
+    pub fn derivative(&self) -> f64 {
+        self.derivative
+    }
+
+    /// Take `value` at the new time point, where it changes by
+    /// `capacitance` per unit of the controlling quantity, now at
+    /// `control`. Returns the companion `dq/dt = geq x + ceq` linearised
+    /// around `control`.
+    pub fn integrate(
+        &mut self,
+        integrator: &Integrator,
+        value: f64,
+        capacitance: f64,
+        control: f64,
+    ) -> Companion {
+        self.value = value;
+        self.derivative = integrator.derivative(&self.history, value);
+        let geq = integrator.ag()[0] * capacitance;
+        Companion {
+            geq,
+            ceq: self.derivative - geq * control,
+        }
+    }
+
+    /// Keep the current value as an accepted point.
+    pub fn accept(&mut self) {
+        self.history.accept(self.value, self.derivative);
+    }
+
+    /// Largest step the truncation error of this state allows after a step
+    /// of `h`.
+    pub fn allowed_step(
+        &self,
+        timestep: &Timestep,
+        integrator: &Integrator,
+        h: f64,
+        quantity: Quantity,
+    ) -> f64 {
+        timestep.allowed_step(integrator, h, quantity, self.history.values(), self.value)
+    }
+}
+
+/// A noise current between two nodes, white over the band it is asked
+/// for at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseSource {
+    /// The device and the mechanism, such as `R1` or `D1 shot`.
+    pub name: String,
+    pub positive: Unknown,
+    pub negative: Unknown,
+    /// Power spectral density in A²/Hz.
+    pub density: f64,
+}
+
+/// Thermal noise density `4kTG` of a conductance at `temperature` in °C.
+pub fn thermal_noise(conductance: f64, temperature: f64) -> f64 {
+    4.0 * BOLTZMANN * (temperature + KELVIN) * conductance.abs()
+}
+
+/// Shot noise density `2qI` of a current crossing a junction.
+pub fn shot_noise(current: f64) -> f64 {
+    2.0 * CHARGE * current.abs()
+}
+
+/// Flicker noise density `KF I^AF / f^EF`.
+pub fn flicker_noise(kf: f64, af: f64, ef: f64, current: f64, frequency: f64) -> f64 {
+    if kf == 0.0 || frequency <= 0.0 {
+        return 0.0;
+    }
+    kf * current.abs().powf(af) / frequency.powf(ef)
+}
+
+/// Everything a device sees while it stamps its linearisation.
+pub struct LoadContext<'a> {
+    /// The iterate to linearise around.
+    pub solution: &'a [f64],
+    pub time: f64,
+    /// The formula for charges and fluxes, `None` outside the transient
+    /// analysis, where they are not changing.
+    pub integrator: Option<&'a Integrator>,
+    /// Solving the operating point a transient analysis starts from, where
+    /// sources take their waveform's value at time zero even when a DC
+    /// value is given.
+    pub initial: bool,
+    /// Conductance put across every junction.
+    pub gmin: f64,
+    load: &'a mut Load,
+}
+
+impl LoadContext<'_> {
+    pub fn voltage(&self, node: Unknown) -> f64 {
+        voltage(self.solution, node)
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.integrator.is_some()
+    }
+
+    /// See [`Load::source_factor`].
+    pub fn source_factor(&self) -> f64 {
+        self.load.source_factor()
+    }
+
+    /// Note that `device` limited a controlling voltage.
+    pub fn limit(&mut self, device: &str) {
+        self.load.limit(device);
+    }
+
+    /// Note that `device` could not be evaluated, which ends the iteration
+    /// with `message`.
+    pub fn fail(&mut self, device: &str, message: String) {
+        self.load.fail(device, message);
+    }
+}
+
+/// A circuit element.
+pub trait Device: Debug {
+    /// The instance name, such as `R1`.
+    fn name(&self) -> &str;
+
+    /// Claim the unknowns: the nodes on the element line, internal nodes
+    /// and branch currents. Called once, before [`Device::setup`].
+    fn allocate(&mut self, unknowns: &mut Unknowns);
+
+    /// Ask for every matrix entry the loads will stamp and keep the
+    /// handles. Called once, when the unknowns are all known.
+    fn setup(&mut self, pattern: &mut Pattern);
+
+    /// Work out the parameters that depend on the circuit temperature, both
+    /// in °C. Called before the first load and whenever the temperature
+    /// changes.
+    fn temperature(&mut self, _temperature: f64, _nominal: f64) {}
+
+    /// Stamp the Jacobian and right-hand side linearised around
+    /// `context.solution`, in the DC or transient analysis. Keep what the
+    /// AC and noise analyses will need of the operating point.
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]);
+
+    /// Stamp the small-signal admittances at angular frequency `omega`,
+    /// linearised at the last DC load, and any AC excitation.
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, rhs: &mut [Complex]);
+
+    /// Noise currents at `frequency` around the operating point.
+    fn noise(&self, _frequency: f64) -> Vec<NoiseSource> {
+        Vec::new()
+    }
+
+    /// Start the states from the `ic=` values on the element line instead
+    /// of an operating point, for a transient analysis with `uic`.
+    fn initial_conditions(&mut self) {}
+
+    /// Record the point just solved at `time` as accepted.
+    fn accept(&mut self, _solution: &[f64], _time: f64) {}
+
+    /// Largest next step the truncation error of the states allows after
+    /// an accepted step of `h`.
+    fn truncation(&self, _timestep: &Timestep, _integrator: &Integrator, _h: f64) -> f64 {
+        f64::INFINITY
+    }
+
+    /// Times after `from`, up to `to`, where the device's waveforms have
+    /// corners. Asked again after every accepted point for the span of the
+    /// next step, so that corners depending on the solution, such as a
+    /// switch's, can be given as they come into view.
+    fn breakpoints(&self, _from: f64, _to: f64) -> Vec<f64> {
+        Vec::new()
+    }
+
+    /// Whether the device's own test fails at `solution`, such as a diode
+    /// whose current is not yet consistent with its voltage.
+    fn unconverged(&self, _solution: &[f64], _options: &Options) -> bool {
+        false
+    }
+}
+
+/// Devices and their unknowns, as the analyses solve them.
+#[derive(Debug)]
+pub struct Circuit {
+    devices: Vec<Box<dyn Device>>,
+    unknowns: Unknowns,
+    gmin: f64,
+    time: f64,
+    integrator: Option<Integrator>,
+    initial: bool,
+}
+
+impl Circuit {
+    /// Allocate the unknowns of `devices` and set them to the default
+    /// temperature.
+    pub fn new(mut devices: Vec<Box<dyn Device>>, options: &Options) -> Self {
+        let mut unknowns = Unknowns::default();
+        for device in devices.iter_mut() {
+            device.allocate(&mut unknowns);
+        }
+        let mut circuit = Circuit {
+            devices,
+            unknowns,
+            gmin: options.gmin,
+            time: 0.0,
+            integrator: None,
+            initial: false,
+        };
+        circuit.set_temperature(DEFAULT_TEMPERATURE, options);
+        circuit
+    }
+
+    pub fn unknowns(&self) -> &Unknowns {
+        &self.unknowns
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &dyn Device> {
+        self.devices.iter().map(|d| d.as_ref())
+    }
+
+    pub fn device(&self, name: &str) -> Option<&dyn Device> {
+        self.devices().find(|d| d.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64, options: &Options) {
+        for device in self.devices.iter_mut() {
+            device.temperature(temperature, options.tnom);
+        }
+    }
+
+    /// Load for the DC analyses from now on.
+    pub fn set_dc(&mut self) {
+        self.integrator = None;
+        self.time = 0.0;
+        self.initial = false;
+    }
+
+    /// Load for the operating point a transient analysis starts from: DC,
+    /// with the sources at their waveforms' values at time zero.
+    pub fn set_initial(&mut self) {
+        self.set_dc();
+        self.initial = true;
+    }
+
+    /// Load for the transient step to `time`, with the formula already
+    /// prepared for it.
+    pub fn set_transient(&mut self, time: f64, integrator: &Integrator) {
+        self.time = time;
+        self.initial = false;
+        self.integrator = Some(integrator.clone());
+    }
+
+    /// Start the states from the element lines' `ic=` values.
+    pub fn initial_conditions(&mut self) {
+        for device in self.devices.iter_mut() {
+            device.initial_conditions();
+        }
+    }
+
+    /// Record `solution` at `time` as an accepted point.
+    pub fn accept(&mut self, solution: &[f64], time: f64) {
+        for device in self.devices.iter_mut() {
+            device.accept(solution, time);
+        }
+    }
+
+    /// The smallest step any device allows next.
+    pub fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.devices
+            .iter()
+            .map(|d| d.truncation(timestep, integrator, h))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Every device's breakpoints after `from`, up to `to`, in order. The
+    /// transient analysis asks after every accepted point, see
+    /// [`crate::analysis::tran::transient`].
+    pub fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        let mut times: Vec<f64> = self
+            .devices
+            .iter()
+            .flat_map(|d| d.breakpoints(from, to))
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        times
+    }
+
+    /// Stamp the whole circuit at angular frequency `omega` into the
+    /// cleared `matrix` and `rhs`.
+    pub fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, rhs: &mut [Complex]) {
+        for device in &self.devices {
+            device.ac_load(omega, matrix, rhs);
+        }
+    }
+
+    pub fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        self.devices
+            .iter()
+            .flat_map(|d| d.noise(frequency))
+            .collect()
+    }
+}
+
+impl System for Circuit {
+    fn size(&self) -> usize {
+        self.unknowns.len()
+    }
+
+    fn name(&self, index: usize) -> String {
+        self.unknowns.name(index).to_string()
+    }
+
+    fn kind(&self, index: usize) -> UnknownKind {
+        self.unknowns.kind(index)
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        for device in self.devices.iter_mut() {
+            device.setup(pattern);
+        }
+    }
+
+    fn load(
+        &mut self,
+        solution: &[f64],
+        matrix: &mut SparseMatrix,
+        rhs: &mut [f64],
+        load: &mut Load,
+    ) {
+        let mut context = LoadContext {
+            solution,
+            time: self.time,
+            integrator: self.integrator.as_ref(),
+            initial: self.initial,
+            gmin: self.gmin,
+            load,
+        };
+        for device in self.devices.iter_mut() {
+            device.load(&mut context, matrix, rhs);
+        }
+    }
+
+    fn unconverged(&self, solution: &[f64], options: &Options) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|d| d.unconverged(solution, options))
+            .map(|d| d.name().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::Method;
+    use crate::analysis::newton::Newton;
+    use crate::frontend::parser::tran::parse_tran;
+    use crate::maths::sparse::Lu;
+
+    #[derive(Debug)]
+    struct Resistor {
+        name: String,
+        nodes: [Node; 2],
+        conductance: f64,
+        temperature: f64,
+        unknowns: [Unknown; 2],
+        stamp: Conductance,
+    }
+
+    impl Resistor {
+        fn boxed(name: &str, a: &str, b: &str, resistance: f64) -> Box<dyn Device> {
+            Box::new(Resistor {
+                name: name.to_string(),
+                nodes: [Node::new(a), Node::new(b)],
+                conductance: 1.0 / resistance,
+                temperature: 0.0,
+                unknowns: [None; 2],
+                stamp: Conductance::default(),
+            })
+        }
+    }
+
+    impl Device for Resistor {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn allocate(&mut self, unknowns: &mut Unknowns) {
+            self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        }
+
+        fn setup(&mut self, pattern: &mut Pattern) {
+            self.stamp = Conductance::new(pattern, self.unknowns[0], self.unknowns[1]);
+        }
+
+        fn temperature(&mut self, temperature: f64, _nominal: f64) {
+            self.temperature = temperature;
+        }
+
+        fn load(
+            &mut self,
+            _context: &mut LoadContext,
+            matrix: &mut SparseMatrix,
+            _rhs: &mut [f64],
+        ) {
+            self.stamp.stamp(matrix, self.conductance);
+        }
+
+        fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+            self.stamp.stamp(matrix, Complex::from(self.conductance));
+        }
+
+        fn noise(&self, _frequency: f64) -> Vec<NoiseSource> {
+            vec![NoiseSource {
+                name: self.name.clone(),
+                positive: self.unknowns[0],
+                negative: self.unknowns[1],
+                density: thermal_noise(self.conductance, self.temperature),
+            }]
+        }
+    }
+
+    /// A voltage source with a branch current, stepping from zero to its
+    /// value just after `delay`, with an AC magnitude of one.
+    #[derive(Debug)]
+    struct Source {
+        value: f64,
+        delay: f64,
+        node: Node,
+        unknowns: [Unknown; 2],
+        entries: [Option<Handle>; 2],
+    }
+
+    impl Device for Source {
+        fn name(&self) -> &str {
+            "V1"
+        }
+
+        fn allocate(&mut self, unknowns: &mut Unknowns) {
+            self.unknowns = [unknowns.node(&self.node), Some(unknowns.branch("V1"))];
+        }
+
+        fn setup(&mut self, pattern: &mut Pattern) {
+            let [node, branch] = self.unknowns;
+            self.entries = [entry(pattern, node, branch), entry(pattern, branch, node)];
+        }
+
+        fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+            add(matrix, self.entries[0], 1.0);
+            add(matrix, self.entries[1], 1.0);
+            let value = if context.time > self.delay {
+                self.value
+            } else {
+                0.0
+            };
+            rhs[self.unknowns[1].unwrap()] = value * context.source_factor();
+        }
+
+        fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, rhs: &mut [Complex]) {
+            add(matrix, self.entries[0], Complex::from(1.0));
+            add(matrix, self.entries[1], Complex::from(1.0));
+            rhs[self.unknowns[1].unwrap()] = Complex::from(1.0);
+        }
+
+        fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+            if (from..to).contains(&self.delay) {
+                vec![self.delay]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Capacitor {
+        capacitance: f64,
+        node: Node,
+        unknown: Unknown,
+        stamp: Conductance,
+        charge: State,
+    }
+
+    impl Device for Capacitor {
+        fn name(&self) -> &str {
+            "C1"
+        }
+
+        fn allocate(&mut self, unknowns: &mut Unknowns) {
+            self.unknown = unknowns.node(&self.node);
+        }
+
+        fn setup(&mut self, pattern: &mut Pattern) {
+            self.stamp = Conductance::new(pattern, self.unknown, None);
+        }
+
+        fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+            let v = context.voltage(self.unknown);
+            let q = self.capacitance * v;
+            let Some(integrator) = context.integrator else {
+                self.charge.reset(q);
+                return;
+            };
+            let companion = self.charge.integrate(integrator, q, self.capacitance, v);
+            self.stamp.stamp(matrix, companion.geq);
+            current(rhs, self.unknown, None, companion.ceq);
+        }
+
+        fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+            self.stamp
+                .stamp(matrix, Complex::new(0.0, omega * self.capacitance));
+        }
+
+        fn accept(&mut self, _solution: &[f64], _time: f64) {
+            self.charge.accept();
+        }
+
+        fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+            self.charge
+                .allowed_step(timestep, integrator, h, Quantity::Charge)
+        }
+    }
+
+    /// `V1` on `in`, 1 kΩ to `out` and 1 µF from `out` to ground.
+    fn rc(delay: f64, options: &Options) -> Circuit {
+        let devices: Vec<Box<dyn Device>> = vec![
+            Box::new(Source {
+                value: 2.0,
+                delay,
+                node: Node::new("in"),
+                unknowns: [None; 2],
+                entries: [None; 2],
+            }),
+            Resistor::boxed("R1", "in", "out", 1e3),
+            Box::new(Capacitor {
+                capacitance: 1e-6,
+                node: Node::new("out"),
+                unknown: None,
+                stamp: Conductance::default(),
+                charge: State::default(),
+            }),
+        ];
+        Circuit::new(devices, options)
+    }
+
+    #[test]
+    fn test_unknowns() {
+        let options = Options::default();
+        let mut circuit = rc(-1.0, &options);
+        let unknowns = circuit.unknowns();
+        assert_eq!(unknowns.len(), 3);
+        assert_eq!(unknowns.find("v(OUT)"), Some(2));
+        assert_eq!(unknowns.find("I(v1)"), Some(1));
+        assert_eq!(unknowns.kind(1), UnknownKind::Current);
+        assert!(circuit.device("r1").is_some());
+
+        let mut newton = Newton::new(&mut circuit, &options);
+        let mut solution = vec![0.0; 3];
+        newton
+            .solve(&mut circuit, &mut solution, options.itl1, &options)
+            .unwrap();
+        // No current through the capacitor at DC.
+        assert!((solution[0] - 2.0).abs() < 1e-12);
+        assert!((solution[2] - 2.0).abs() < 1e-12);
+        assert!(solution[1].abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_transient() {
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut circuit = rc(1e-3, &options);
+        let mut newton = Newton::new(&mut circuit, &options);
+        let mut solution = vec![0.0; 3];
+        let tran = parse_tran(".tran 5m").unwrap();
+        let mut timestep = Timestep::new(&tran, &options);
+        timestep.add_breakpoints(circuit.breakpoints(0.0, tran.stop));
+        let mut integrator = Integrator::new(options.method, options.maxord);
+
+        // The source is off at the operating point.
+        circuit.set_dc();
+        newton
+            .solve(&mut circuit, &mut solution, options.itl1, &options)
+            .unwrap();
+        circuit.accept(&solution, 0.0);
+        let mut worst: f64 = 0.0;
+        while !timestep.finished() {
+            let h = timestep.next();
+            integrator.prepare(h);
+            let t = timestep.time() + h;
+            circuit.set_transient(t, &integrator);
+            let mut next = solution.clone();
+            newton
+                .solve(&mut circuit, &mut next, options.itl4, &options)
+                .unwrap();
+            let allowed = circuit.truncation(&timestep, &integrator, h);
+            if timestep.complete(h, allowed, &mut integrator).unwrap() {
+                solution = next;
+                circuit.accept(&solution, t);
+                let exact = if t <= 1e-3 {
+                    0.0
+                } else {
+                    2.0 * (1.0 - (-(t - 1e-3) / 1e-3).exp())
+                };
+                worst = worst.max((solution[2] - exact).abs());
+            }
+        }
+        assert!(worst < 1e-2, "{}", worst);
+        assert!((solution[2] - 2.0 * (1.0 - (-4.0f64).exp())).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_ac_and_noise() {
+        let options = Options::default();
+        let mut circuit = rc(0.0, &options);
+        let mut pattern = Pattern::new(circuit.size());
+        circuit.setup(&mut pattern);
+        let mut matrix: SparseMatrix<Complex> = pattern.matrix();
+        let mut lu = Lu::new(options.lu_options());
+        // At the corner frequency the output is 3 dB down and 45° behind.
+        let omega = 1.0 / 1e-3;
+        let mut rhs = vec![Complex::default(); 3];
+        circuit.ac_load(omega, &mut matrix, &mut rhs);
+        lu.factor(&matrix).unwrap();
+        lu.solve(&mut rhs);
+        assert!((rhs[2].norm() - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!((rhs[2].arg().to_degrees() + 45.0).abs() < 1e-9);
+
+        let noise = circuit.noise(1e3);
+        assert_eq!(noise.len(), 1);
+        // √(4kTR) of 1 kΩ at 27 °C is about 4.07 nV/√Hz.
+        let density = (noise[0].density * 1e6).sqrt();
+        assert!((density - 4.07e-9).abs() < 1e-11);
+        assert_eq!(shot_noise(-1e-3), 2.0 * CHARGE * 1e-3);
+        assert_eq!(flicker_noise(1e-16, 1.0, 1.0, 1e-3, 10.0), 1e-20);
+    }
+}
@@ -0,0 +1,410 @@
+//! Inductors, with an initial current and parasitics, and the `K` element
+//! that couples them.
+//!
+//! An inductor's current is an unknown of its own, `I(Lname)`, with the
+//! branch equation `V(n+) - V(n-) = dφ/dt`. A coupling adds the mutual
+//! fluxes `M i` of the other inductors to that equation, each integrated
+//! like the inductor's own.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::capacitor::Capacitor;
+use crate::devices::generic::{add, entry, Device, LoadContext, State, Unknown, Unknowns};
+use crate::devices::parameters::ElementLine;
+use crate::devices::resistor::Resistor;
+use crate::frontend::parser::netlist::{Component, Node};
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Handle, Pattern, SparseMatrix};
+use crate::utils::number::parse_number;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inductor {
+    name: String,
+    nodes: [Node; 2],
+    inductance: f64,
+    /// Current to start from with `uic`.
+    initial: Option<f64>,
+    unknowns: [Unknown; 2],
+    branch: usize,
+    /// `(n+, br)`, `(n-, br)`, `(br, n+)`, `(br, n-)` and `(br, br)`.
+    entries: [Option<Handle>; 5],
+    flux: State,
+}
+
+impl Inductor {
+    pub fn new(name: impl Into<String>, positive: Node, negative: Node, inductance: f64) -> Self {
+        Inductor {
+            name: name.into(),
+            nodes: [positive, negative],
+            inductance,
+            initial: None,
+            unknowns: [None; 2],
+            branch: 0,
+            entries: [None; 5],
+            flux: State::default(),
+        }
+    }
+
+    /// The inductor itself of `Lname n+ n- value [ic=] [m=] ...`, without
+    /// its parasitics.
+    pub fn parse(line: &ElementLine) -> Result<Self, String> {
+        line.unknown_parameters(&[
+            "l", "ic", "m", "rser", "rpar", "cpar", "ipk", "mfg", "pn", "type",
+        ])?;
+        let [positive, negative] = line.nodes()?;
+        let multiplier = line.number("m")?.unwrap_or(1.0);
+        if multiplier <= 0.0 {
+            return Err("The multiplier must be positive".to_string());
+        }
+        let inductance = line.value(2, "l")? / multiplier;
+        if inductance == 0.0 {
+            return Err("Inductance is zero".to_string());
+        }
+        let mut inductor = Inductor::new(line.name.clone(), positive, negative, inductance);
+        inductor.initial = line.number("ic")?;
+        Ok(inductor)
+    }
+
+    pub fn inductance(&self) -> f64 {
+        self.inductance
+    }
+
+    /// Current from the positive to the negative node.
+    pub fn current(&self, solution: &[f64]) -> f64 {
+        solution[self.branch]
+    }
+}
+
+/// `Lname n+ n- value [ic=] [m=] [Rser=] [Rpar=] [Cpar=]`, the value also
+/// as `l=`. `Rser` goes in series through an internal node `Lname#1`,
+/// `Rpar` and `Cpar` across the terminals. Part data from a schematic
+/// library (`Ipk`, `mfg`, `pn`, `type`) is ignored.
+pub fn build(line: &ElementLine) -> Result<Vec<Box<dyn Device>>, String> {
+    let mut inductor = Inductor::parse(line)?;
+    let [positive, negative] = inductor.nodes.clone();
+    let name = &line.name;
+    let mut devices: Vec<Box<dyn Device>> = Vec::new();
+    if let Some(rser) = line.number("rser")?.filter(|r| *r != 0.0) {
+        let node = Node::new(format!("{}#1", name));
+        devices.push(Box::new(Resistor::new(
+            format!("{}.Rser", name),
+            positive.clone(),
+            node.clone(),
+            rser,
+        )));
+        inductor.nodes[0] = node;
+    }
+    devices.push(Box::new(inductor));
+    if let Some(rpar) = line.number("rpar")?.filter(|r| *r != 0.0) {
+        devices.push(Box::new(Resistor::new(
+            format!("{}.Rpar", name),
+            positive.clone(),
+            negative.clone(),
+            rpar,
+        )));
+    }
+    if let Some(cpar) = line.number("cpar")?.filter(|c| *c != 0.0) {
+        devices.push(Box::new(Capacitor::new(
+            format!("{}.Cpar", name),
+            positive,
+            negative,
+            cpar,
+        )));
+    }
+    Ok(devices)
+}
+
+impl Device for Inductor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        self.branch = unknowns.branch(&self.name);
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p, n] = self.unknowns;
+        let br = Some(self.branch);
+        self.entries = [
+            entry(pattern, p, br),
+            entry(pattern, n, br),
+            entry(pattern, br, p),
+            entry(pattern, br, n),
+            entry(pattern, br, br),
+        ];
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        add(matrix, self.entries[0], 1.0);
+        add(matrix, self.entries[1], -1.0);
+        add(matrix, self.entries[2], 1.0);
+        add(matrix, self.entries[3], -1.0);
+        let i = context.solution[self.branch];
+        let flux = self.inductance * i;
+        let Some(integrator) = context.integrator else {
+            // A short at DC.
+            self.flux.reset(flux);
+            return;
+        };
+        let companion = self.flux.integrate(integrator, flux, self.inductance, i);
+        add(matrix, self.entries[4], -companion.geq);
+        rhs[self.branch] += companion.ceq;
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        add(matrix, self.entries[0], Complex::from(1.0));
+        add(matrix, self.entries[1], Complex::from(-1.0));
+        add(matrix, self.entries[2], Complex::from(1.0));
+        add(matrix, self.entries[3], Complex::from(-1.0));
+        add(
+            matrix,
+            self.entries[4],
+            Complex::new(0.0, -omega * self.inductance),
+        );
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some(i) = self.initial {
+            self.flux.reset(self.inductance * i);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        self.flux.accept();
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.flux
+            .allowed_step(timestep, integrator, h, Quantity::Charge)
+    }
+}
+
+/// The mutual inductance between two of the coupled inductors.
+#[derive(Debug, Clone, PartialEq)]
+struct Mutual {
+    /// Indices of the two inductors in the coupling.
+    inductors: [usize; 2],
+    inductance: f64,
+    /// `(br1, br2)` and `(br2, br1)`.
+    entries: [Option<Handle>; 2],
+    /// The flux `M i2` linking the first inductor and `M i1` the second.
+    fluxes: [State; 2],
+}
+
+/// `Kname L1 L2 [L3 ...] k`: every pair of the inductors coupled with
+/// `M = k √(L1 L2)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coupling {
+    name: String,
+    inductors: Vec<String>,
+    coefficient: f64,
+    /// The inductors' initial currents.
+    initial: Vec<Option<f64>>,
+    branches: Vec<usize>,
+    mutuals: Vec<Mutual>,
+}
+
+impl Coupling {
+    /// The inductors are looked up among `components`, for their values.
+    pub fn parse(line: &ElementLine, components: &[Component]) -> Result<Self, String> {
+        line.unknown_parameters(&[])?;
+        let Some((coefficient, names)) = line.words.split_last() else {
+            return Err("No coupling coefficient given".to_string());
+        };
+        let coefficient = parse_number(coefficient)
+            .ok_or_else(|| format!("Invalid coupling coefficient: {}", coefficient))?;
+        if coefficient.abs() > 1.0 {
+            return Err("The coupling coefficient must be between -1 and 1".to_string());
+        }
+        if names.len() < 2 {
+            return Err("At least two inductors must be coupled".to_string());
+        }
+        let mut inductors = Vec::new();
+        for name in names {
+            let component = components
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(name) && c.prefix() == 'L')
+                .ok_or_else(|| format!("No inductor {}", name))?;
+            inductors.push(Inductor::parse(&ElementLine::new(component))?);
+        }
+        let mut mutuals = Vec::new();
+        for first in 0..inductors.len() {
+            for second in first + 1..inductors.len() {
+                let product = inductors[first].inductance * inductors[second].inductance;
+                mutuals.push(Mutual {
+                    inductors: [first, second],
+                    inductance: coefficient * product.abs().sqrt(),
+                    entries: [None; 2],
+                    fluxes: [State::default(), State::default()],
+                });
+            }
+        }
+        Ok(Coupling {
+            name: line.name.clone(),
+            inductors: names.to_vec(),
+            coefficient,
+            initial: inductors.iter().map(|l| l.initial).collect(),
+            branches: Vec::new(),
+            mutuals,
+        })
+    }
+
+    pub fn coefficient(&self) -> f64 {
+        self.coefficient
+    }
+}
+
+impl Device for Coupling {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.branches = self.inductors.iter().map(|l| unknowns.branch(l)).collect();
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        for mutual in self.mutuals.iter_mut() {
+            let [first, second] = mutual.inductors.map(|i| Some(self.branches[i]));
+            mutual.entries = [entry(pattern, first, second), entry(pattern, second, first)];
+        }
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        for mutual in self.mutuals.iter_mut() {
+            let m = mutual.inductance;
+            let branches = mutual.inductors.map(|i| self.branches[i]);
+            let currents = branches.map(|b| context.solution[b]);
+            for k in 0..2 {
+                // The flux linking one inductor is M times the other's current.
+                let other = currents[1 - k];
+                let Some(integrator) = context.integrator else {
+                    mutual.fluxes[k].reset(m * other);
+                    continue;
+                };
+                let companion = mutual.fluxes[k].integrate(integrator, m * other, m, other);
+                add(matrix, mutual.entries[k], -companion.geq);
+                rhs[branches[k]] += companion.ceq;
+            }
+        }
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        for mutual in &self.mutuals {
+            let admittance = Complex::new(0.0, -omega * mutual.inductance);
+            add(matrix, mutual.entries[0], admittance);
+            add(matrix, mutual.entries[1], admittance);
+        }
+    }
+
+    fn initial_conditions(&mut self) {
+        for mutual in self.mutuals.iter_mut() {
+            let [first, second] = mutual.inductors.map(|i| self.initial[i]);
+            if let Some(i) = second {
+                mutual.fluxes[0].reset(mutual.inductance * i);
+            }
+            if let Some(i) = first {
+                mutual.fluxes[1].reset(mutual.inductance * i);
+            }
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        for mutual in self.mutuals.iter_mut() {
+            mutual.fluxes.iter_mut().for_each(State::accept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::Method;
+    use crate::analysis::options::Options;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+
+    #[test]
+    fn test_inductor() {
+        // A short at DC, through its series resistance.
+        let (divider, solution) =
+            operating_point(&["V1 in 0 2", "R1 in out 1k", "L1 out 0 1m Rser=1k Rpar=1meg"]);
+        let out = divider.unknowns().find("V(out)").unwrap();
+        let current = divider.unknowns().find("I(L1)").unwrap();
+        assert!((solution[out] - 0.999_500_249_875_062_5).abs() < 1e-12);
+        assert!((solution[current] - 1.0e-3 * 0.999_500_249_875_062_5).abs() < 1e-12);
+
+        // 1 kΩ and 2 mH, two 4 mH in parallel, corner at ω = 500 k.
+        let mut rl = circuit(&["V1 in 0 0 AC 1", "R1 in out 1k", "L1 out 0 4m m=2"]);
+        let out = rl.unknowns().find("V(out)").unwrap();
+        let response = ac(&mut rl, 5e5);
+        assert!((response[out].norm() - 0.5f64.sqrt()).abs() < 1e-9);
+        assert!((response[out].arg().to_degrees() - 45.0).abs() < 1e-7);
+
+        // 1 A decaying through 1 Ω with τ = 1 ms, pulling the node negative.
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut rl = circuit(&["R1 a 0 1", "L1 a 0 1m ic=1"]);
+        let a = rl.unknowns().find("V(a)").unwrap();
+        let points = transient(&mut rl, ".tran 3m uic", &options);
+        for (t, solution) in points.iter().skip(1) {
+            let exact = -(-t / 1e-3).exp();
+            assert!((solution[a] - exact).abs() < 1e-3, "{} {}", t, solution[a]);
+        }
+    }
+
+    #[test]
+    fn test_coupling() {
+        // Driven by a current, a short at DC, and unloaded, the secondary
+        // sees the turns ratio √(L2/L1) = 2.
+        let mut transformer =
+            circuit(&["I1 0 in 0 AC 1", "L1 in 0 1m", "L2 out 0 4m", "K1 L1 L2 1"]);
+        let find = |name: &str| transformer.unknowns().find(name).unwrap();
+        let (input, out) = (find("V(in)"), find("V(out)"));
+        let response = ac(&mut transformer, 1e3);
+        assert!((response[out] / response[input] - Complex::from(2.0)).norm() < 1e-12);
+
+        // With k = 0.5 and a 1 Ω load on a secondary wound from ground to
+        // the output, 1 A in the primary gives -jωM R / (R + jωL2).
+        let mut loaded = circuit(&[
+            "I1 0 in 0 AC 1",
+            "L1 in 0 1m",
+            "L2 0 out 4m",
+            "R1 out 0 1",
+            "K1 L1 L2 0.5",
+        ]);
+        let out = loaded.unknowns().find("V(out)").unwrap();
+        let omega = 1e3;
+        let response = ac(&mut loaded, omega);
+        let m = 0.5 * (1e-3f64 * 4e-3).sqrt();
+        let exact = -(Complex::new(0.0, omega * m) / Complex::new(1.0, omega * 4e-3));
+        assert!((response[out] - exact).norm() < 1e-12, "{}", response[out]);
+
+        // Two 1 mH with k = 0.5 and 1 Ω each, the first starting at 1 A:
+        // the sum of the currents decays through L + M and the difference
+        // through L - M.
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut coupled = circuit(&[
+            "L1 a 0 1m ic=1",
+            "R1 a 0 1",
+            "L2 b 0 1m",
+            "R2 b 0 1",
+            "K1 L1 L2 0.5",
+        ]);
+        let a = coupled.unknowns().find("V(a)").unwrap();
+        let points = transient(&mut coupled, ".tran 3m uic", &options);
+        for (t, solution) in points.iter().skip(1) {
+            let exact = -0.5 * ((-t / 1.5e-3).exp() + (-t / 0.5e-3).exp());
+            assert!((solution[a] - exact).abs() < 2e-3, "{} {}", t, solution[a]);
+        }
+
+        let line = ElementLine::new(&Component::new("K1", vec![], "L1 L3 0.5"));
+        assert!(Coupling::parse(&line, &[Component::new("L1", vec![], "a 0 1m")]).is_err());
+    }
+}
@@ -0,0 +1,820 @@
+//! Junction field effect transistors, and the MESFETs that share their
+//! structure.
+//!
+//! A JFET of an `NJF` or `PJF` card follows ngspice's `jfetload.c`:
+//! Shichman and Hodges' square law `BETA (vgs - VTO)² (1 + LAMBDA vds)`
+//! with Sydney Sadler's doping tail `B`, which makes the current cubic near
+//! pinch off and leaves it unchanged at `B = 1`. The gate is a pair of
+//! junction diodes of saturation current `IS`, emission coefficient `N`
+//! and recombination current `ISR`, to the source and to the drain inside
+//! `RS` and `RD`, each with the depletion charge of `CGS` or `CGD` at
+//! built-in potential `PB`. `VTOTC` moves the threshold and `BETATCE` and
+//! `BEX` scale `BETA` with temperature; `IS` scales as a diode's and the
+//! junction capacitances as in ngspice's `jfettemp.c`. The channel and
+//! gate noise is that of `jfetnoi.c`, thermal noise `8kT gm/3` and
+//! flicker noise `KF id^AF/f` in the channel.
+//!
+//! MESFETs of `NMF` and `PMF` cards, parsed in
+//! [`crate::devices::mesfet`], swap the channel for Statz's or Curtice's
+//! and, for Statz's, the depletion charges for his gate charge.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::diode::{depletion, junction_temperature};
+use crate::devices::generic::{
+    current, flicker_noise, thermal_noise, voltage, Conductance, Device, LoadContext, NoiseSource,
+    State, Unknown, Unknowns, BOLTZMANN, CHARGE, KELVIN,
+};
+use crate::devices::limiting::{critical_voltage, fetlim, pnjlim};
+use crate::devices::mesfet::{self, curtice, statz, statz_charge, StatzCharge};
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::dual::{Dual, Real};
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// The drain current law of a channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    /// A JFET's square law with doping tail `b`.
+    ShichmanHodges { b: f64 },
+    /// Statz's MESFET, saturating at `vds = 3/alpha`.
+    Statz { alpha: f64, b: f64 },
+    /// Curtice's quadratic MESFET.
+    Curtice { alpha: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JfetModel {
+    /// 1 for an n channel, -1 for a p channel.
+    pub polarity: f64,
+    pub channel: Channel,
+    pub vto: f64,
+    pub beta: f64,
+    pub lambda: f64,
+    pub rd: f64,
+    pub rs: f64,
+    pub cgs: f64,
+    pub cgd: f64,
+    pub pb: f64,
+    /// Grading coefficient of the gate junctions.
+    pub m: f64,
+    pub fc: f64,
+    pub is: f64,
+    pub n: f64,
+    pub isr: f64,
+    pub nr: f64,
+    pub kf: f64,
+    pub af: f64,
+    /// Threshold change per K.
+    pub vtotc: f64,
+    /// `BETA` change in % per K, and its exponent of `T/TNOM`.
+    pub betatce: f64,
+    pub bex: f64,
+    pub xti: f64,
+    pub eg: f64,
+    /// Temperature the parameters were measured at in °C, `.options tnom`
+    /// unless given.
+    pub tnom: Option<f64>,
+}
+
+impl JfetModel {
+    /// The parameters of an `NJF` or `PJF` card, with ngspice's and
+    /// LTspice's names. LTspice's impact ionisation `ALPHA` and `VK` are
+    /// accepted and ignored.
+    pub fn new(model: &Model) -> Result<Self, String> {
+        model.unknown_parameters(&[
+            "level", "vto", "vt0", "beta", "lambda", "b", "rd", "rs", "cgs", "cgd", "pb", "m",
+            "fc", "is", "n", "isr", "nr", "kf", "af", "vtotc", "tcv", "betatce", "bex", "xti",
+            "eg", "tnom", "tref", "alpha", "vk",
+        ])?;
+        if model.number("level")?.is_some_and(|level| level != 1.0) {
+            return Err(format!(
+                "Model {}: only JFET level 1 is supported",
+                model.name
+            ));
+        }
+        let value = |names: &[&str], default: f64| -> Result<f64, String> {
+            Ok(model.alias(names)?.unwrap_or(default))
+        };
+        JfetModel {
+            polarity: if model.kind == "PJF" { -1.0 } else { 1.0 },
+            channel: Channel::ShichmanHodges {
+                b: value(&["b"], 1.0)?,
+            },
+            vto: value(&["vto", "vt0"], -2.0)?,
+            beta: value(&["beta"], 1e-4)?,
+            lambda: value(&["lambda"], 0.0)?,
+            rd: value(&["rd"], 0.0)?,
+            rs: value(&["rs"], 0.0)?,
+            cgs: value(&["cgs"], 0.0)?,
+            cgd: value(&["cgd"], 0.0)?,
+            pb: value(&["pb"], 1.0)?,
+            m: value(&["m"], 0.5)?,
+            fc: value(&["fc"], 0.5)?,
+            is: value(&["is"], 1e-14)?,
+            n: value(&["n"], 1.0)?,
+            isr: value(&["isr"], 0.0)?,
+            nr: value(&["nr"], 2.0)?,
+            kf: value(&["kf"], 0.0)?,
+            af: value(&["af"], 1.0)?,
+            vtotc: value(&["vtotc"], 0.0)? - value(&["tcv"], 0.0)?,
+            betatce: value(&["betatce"], 0.0)?,
+            bex: value(&["bex"], 0.0)?,
+            xti: value(&["xti"], 3.0)?,
+            eg: value(&["eg"], 1.11)?,
+            tnom: model.alias(&["tnom", "tref"])?,
+        }
+        .checked(&model.name)
+    }
+
+    pub(crate) fn checked(self, name: &str) -> Result<Self, String> {
+        if self.is <= 0.0 || self.n <= 0.0 || self.pb <= 0.0 || self.beta < 0.0 {
+            return Err(format!(
+                "Model {}: IS, N and PB must be positive and BETA not negative",
+                name
+            ));
+        }
+        if self.m >= 1.0 || self.fc >= 1.0 {
+            return Err(format!("Model {}: M and FC must be below 1", name));
+        }
+        if matches!(self.channel, Channel::ShichmanHodges { b } if b != 1.0 && self.pb == self.vto)
+        {
+            return Err(format!("Model {}: PB must differ from VTO with B", name));
+        }
+        Ok(self)
+    }
+}
+
+/// The model's parameters at the instance temperature, scaled by its area
+/// and multiplier.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scaled {
+    temperature: f64,
+    vt: f64,
+    vto: f64,
+    beta: f64,
+    is: f64,
+    isr: f64,
+    vcrit: f64,
+    pb: f64,
+    cgs: f64,
+    cgd: f64,
+    /// Conductances of `RD` and `RS`, zero without them.
+    gd: f64,
+    gs: f64,
+    /// The doping tail's cubic coefficient, `(1 - B)/(PB - VTO)` at
+    /// `TNOM` as ngspice has it.
+    bfac: f64,
+    statz: StatzCharge,
+}
+
+/// The linearisation at a bias point, as for an n channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bias {
+    vgs: f64,
+    vds: f64,
+    /// Channel current from drain to source and its derivatives with
+    /// `vgs` and `vds`.
+    ids: f64,
+    gm: f64,
+    gds: f64,
+    /// Gate to source and gate to drain junctions, gmin included.
+    igs: f64,
+    ggs: f64,
+    igd: f64,
+    ggd: f64,
+    /// Gate charges on the source and drain sides and their capacitances.
+    charges: [f64; 2],
+    capacitances: [f64; 2],
+}
+
+impl Bias {
+    fn vgd(&self) -> f64 {
+        self.vgs - self.vds
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jfet {
+    name: String,
+    /// Drain, gate and source.
+    nodes: [Node; 3],
+    model: JfetModel,
+    area: f64,
+    multiplier: f64,
+    /// `vds` and `vgs` to start from with `uic`.
+    initial: Option<[f64; 2]>,
+    temp: Option<f64>,
+    dtemp: f64,
+    scaled: Scaled,
+    /// Drain, gate, source, and the drain and source inside `RD` and `RS`.
+    unknowns: [Unknown; 5],
+    /// Drain and source resistances, the channel's conductance and
+    /// transconductance, and the gate to source and drain junctions.
+    stamps: [Conductance; 6],
+    /// Gate to source and gate to drain.
+    charges: [State; 2],
+    bias: Bias,
+    /// `vgs`, `vgd` and the gate charges at the last accepted point, where
+    /// Statz's charge is split between the source and drain from.
+    accepted: ([f64; 2], [f64; 2]),
+}
+
+impl Jfet {
+    /// `Jname d g s model [area] [off] [area=] [m=] [ic=vds,vgs] [temp=]
+    /// [dtemp=]`, or a MESFET's `Zname` with the same syntax.
+    pub fn parse(line: &ElementLine, models: &[Model]) -> Result<Self, String> {
+        line.unknown_parameters(&["area", "m", "ic", "temp", "dtemp"])?;
+        let [drain, gate, source] = line.nodes()?;
+        let name = line.words.get(3).ok_or("No model given")?;
+        let model = if line.name.to_ascii_uppercase().starts_with('Z') {
+            mesfet::model(find_model(models, name, &["NMF", "PMF"])?)?
+        } else {
+            JfetModel::new(find_model(models, name, &["NJF", "PJF"])?)?
+        };
+        let words: Vec<&String> = line.words[4..]
+            .iter()
+            .filter(|w| !w.eq_ignore_ascii_case("off"))
+            .collect();
+        let area = match (line.number("area")?, words.first()) {
+            (Some(area), _) => area,
+            (None, None) => 1.0,
+            (None, Some(_)) => line.value(4, "area")?,
+        };
+        let multiplier = line.number("m")?.unwrap_or(1.0);
+        if area <= 0.0 || multiplier <= 0.0 {
+            return Err("The area and multiplier must be positive".to_string());
+        }
+        let initial = match line.numbers("ic")?.as_slice() {
+            [] => None,
+            [vds] => Some([*vds, 0.0]),
+            [vds, vgs] => Some([*vds, *vgs]),
+            _ => return Err("Expected ic=vds,vgs".to_string()),
+        };
+        Ok(Jfet {
+            name: line.name.clone(),
+            nodes: [drain, gate, source],
+            model,
+            area,
+            multiplier,
+            initial,
+            temp: line.number("temp")?,
+            dtemp: line.number("dtemp")?.unwrap_or(0.0),
+            scaled: Scaled::default(),
+            unknowns: [None; 5],
+            stamps: [Conductance::default(); 6],
+            charges: Default::default(),
+            bias: Bias::default(),
+            accepted: ([0.0; 2], [0.0; 2]),
+        })
+    }
+
+    /// Drain current, transconductance and output conductance at the last
+    /// load.
+    pub fn operating_point(&self) -> (f64, f64, f64) {
+        let b = &self.bias;
+        (self.model.polarity * b.ids, b.gm, b.gds)
+    }
+
+    /// `vgs` and `vds` of `solution`, as for an n channel.
+    fn voltages(&self, solution: &[f64]) -> [f64; 2] {
+        let [_, gate, _, drain, source] = self.unknowns;
+        let v = |node: Unknown| voltage(solution, node);
+        let p = self.model.polarity;
+        [p * (v(gate) - v(source)), p * (v(drain) - v(source))]
+    }
+
+    /// The channel current from drain to source for `vds ≥ 0`.
+    fn channel<T: Real>(&self, vgs: T, vds: T) -> T {
+        let s = &self.scaled;
+        let vgst = vgs - s.vto;
+        if vgst.value() <= 0.0 {
+            return T::constant(0.0);
+        }
+        let betap = (vds * self.model.lambda + 1.0) * s.beta;
+        match self.model.channel {
+            Channel::ShichmanHodges { b } => {
+                let cpart = if vgst.value() >= vds.value() {
+                    let apart = (vgst - vds) * (3.0 * s.bfac) + 2.0 * b;
+                    vds * (vds * (vds * s.bfac - b) + vgst * apart)
+                } else {
+                    vgst * vgst * (vgst * s.bfac + b)
+                };
+                betap * cpart
+            }
+            Channel::Statz { alpha, b } => betap * statz(vgst, vds, alpha, b),
+            Channel::Curtice { alpha } => betap * curtice(vgst, vds, alpha),
+        }
+    }
+
+    /// Current and conductance of a gate junction at `v`, gmin included.
+    fn junction(&self, v: f64, gmin: f64) -> (f64, f64) {
+        let s = &self.scaled;
+        let m = &self.model;
+        let x = Dual::<1>::variable(v, 0);
+        let vte = m.n * s.vt;
+        let mut i = if v > -3.0 * vte {
+            ((x / vte).exp() - 1.0) * s.is
+        } else {
+            Dual::constant(-s.is)
+        };
+        if s.isr > 0.0 {
+            let arg = -(x / s.pb) + 1.0;
+            let generation = (arg * arg + 0.005).powf(Dual::constant(m.m / 2.0));
+            i = i + ((x / (m.nr * s.vt)).exp() - 1.0) * generation * s.isr;
+        }
+        (i.value + gmin * v, i.derivative(0) + gmin)
+    }
+
+    /// The gate charges on the source and drain sides and their
+    /// capacitances. Statz's single charge is split as ngspice's
+    /// `mesload.c` does, by averaging its changes with `vgs` and with `vgd`
+    /// since the last accepted point.
+    fn gate_charges(&self, vgs: f64, vgd: f64) -> ([f64; 2], [f64; 2]) {
+        let s = &self.scaled;
+        let m = &self.model;
+        if let Channel::Statz { .. } = m.channel {
+            let a = statz_charge(
+                Dual::<2>::variable(vgs, 0),
+                Dual::variable(vgd, 1),
+                &s.statz,
+            );
+            let ([old_vgs, old_vgd], [qgs, qgd]) = self.accepted;
+            let b = statz_charge(old_vgs, vgd, &s.statz);
+            let c = statz_charge(vgs, old_vgd, &s.statz);
+            let d = statz_charge(old_vgs, old_vgd, &s.statz);
+            return (
+                [
+                    qgs + 0.5 * (a.value - b + c - d),
+                    qgd + 0.5 * (a.value - c + b - d),
+                ],
+                [a.derivative(0), a.derivative(1)],
+            );
+        }
+        let (qgs, capgs) = depletion(vgs, s.cgs, s.pb, m.m, m.fc);
+        let (qgd, capgd) = depletion(vgd, s.cgd, s.pb, m.m, m.fc);
+        ([qgs, qgd], [capgs, capgd])
+    }
+
+    fn evaluate(&self, voltages: [f64; 2], gmin: f64) -> Bias {
+        let [vgs, vds] = voltages;
+        let x: [Dual<2>; 2] = std::array::from_fn(|i| Dual::variable(voltages[i], i));
+        let ids = if vds >= 0.0 {
+            self.channel(x[0], x[1])
+        } else {
+            -self.channel(x[0] - x[1], -x[1])
+        };
+        let vgd = vgs - vds;
+        let (igs, ggs) = self.junction(vgs, gmin);
+        let (igd, ggd) = self.junction(vgd, gmin);
+        let (charges, capacitances) = self.gate_charges(vgs, vgd);
+        Bias {
+            vgs,
+            vds,
+            ids: ids.value,
+            gm: ids.derivative(0),
+            gds: ids.derivative(1),
+            igs,
+            ggs,
+            igd,
+            ggd,
+            charges,
+            capacitances,
+        }
+    }
+}
+
+impl Device for Jfet {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let [drain, gate, source] = self.nodes.each_ref().map(|n| unknowns.node(n));
+        let inner = |unknowns: &mut Unknowns, r: f64, outer: Unknown, name: &str| {
+            if r > 0.0 {
+                Some(unknowns.internal(&self.name, name))
+            } else {
+                outer
+            }
+        };
+        let d = inner(unknowns, self.model.rd, drain, "d");
+        let s = inner(unknowns, self.model.rs, source, "s");
+        self.unknowns = [drain, gate, source, d, s];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [drain, gate, source, d, s] = self.unknowns;
+        self.stamps = [
+            Conductance::new(pattern, drain, d),
+            Conductance::new(pattern, source, s),
+            Conductance::new(pattern, d, s),
+            Conductance::controlled(pattern, d, s, gate, s),
+            Conductance::new(pattern, gate, s),
+            Conductance::new(pattern, gate, d),
+        ];
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let m = &self.model;
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let t = celsius + KELVIN;
+        let tnom = m.tnom.unwrap_or(nominal) + KELVIN;
+        let vt = BOLTZMANN * t / CHARGE;
+        let ratio = t / tnom;
+        let dt = t - tnom;
+        let scale = self.area * self.multiplier;
+        let saturation = |is: f64, n: f64| {
+            is * ((ratio - 1.0) * m.eg / (n * vt) + m.xti / n * ratio.ln()).exp() * scale
+        };
+        let is = saturation(m.is, m.n);
+        let (pb, cgs) = junction_temperature(m.pb, m.cgs * scale, m.m, t, tnom);
+        let (_, cgd) = junction_temperature(m.pb, m.cgd * scale, m.m, t, tnom);
+        let vto = m.vto + m.vtotc * dt;
+        let conductance = |r: f64| if r > 0.0 { scale / r } else { 0.0 };
+        let bfac = match m.channel {
+            Channel::ShichmanHodges { b } if b != 1.0 => (1.0 - b) / (m.pb - m.vto),
+            _ => 0.0,
+        };
+        let statz = match m.channel {
+            Channel::Statz { alpha, .. } => StatzCharge {
+                pb,
+                vcap: 1.0 / alpha,
+                vto,
+                vmax: m.fc * pb,
+                cgs,
+                cgd,
+            },
+            _ => StatzCharge::default(),
+        };
+        self.scaled = Scaled {
+            temperature: celsius,
+            vt,
+            vto,
+            beta: m.beta * ratio.powf(m.bex) * 1.01f64.powf(m.betatce * dt) * scale,
+            is,
+            isr: saturation(m.isr, m.nr),
+            vcrit: critical_voltage(m.n * vt, is),
+            pb,
+            cgs,
+            cgd,
+            gd: conductance(m.rd),
+            gs: conductance(m.rs),
+            bfac,
+            statz,
+        };
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let [new_vgs, new_vds] = self.voltages(context.solution);
+        let new_vgd = new_vgs - new_vds;
+        let old = self.bias;
+        let s = &self.scaled;
+
+        // Limit both junctions, then the channel, as jfetload.c does.
+        let vte = self.model.n * s.vt;
+        let (vgs, vgs_limited) = pnjlim(new_vgs, old.vgs, vte, s.vcrit);
+        let (vgd, vgd_limited) = pnjlim(new_vgd, old.vgd(), vte, s.vcrit);
+        let vgs = fetlim(vgs, old.vgs, s.vto);
+        let vgd = fetlim(vgd, old.vgd(), s.vto);
+        let (vgs, vds) = if vgs_limited || vgd_limited || vgs != new_vgs || vgd != new_vgd {
+            context.limit(&self.name);
+            (vgs, vgs - vgd)
+        } else {
+            (new_vgs, new_vds)
+        };
+
+        let bias = self.evaluate([vgs, vds], context.gmin);
+        self.bias = bias;
+        let vgd = bias.vgd();
+        let (mut ggs, mut ggd) = (bias.ggs, bias.ggd);
+        let (mut igs, mut igd) = (bias.igs, bias.igd);
+        let [qgs, qgd] = &mut self.charges;
+        match context.integrator {
+            Some(integrator) => {
+                let companion =
+                    qgs.integrate(integrator, bias.charges[0], bias.capacitances[0], vgs);
+                ggs += companion.geq;
+                igs += qgs.derivative();
+                let companion =
+                    qgd.integrate(integrator, bias.charges[1], bias.capacitances[1], vgd);
+                ggd += companion.geq;
+                igd += qgd.derivative();
+            }
+            None => {
+                qgs.reset(bias.charges[0]);
+                qgd.reset(bias.charges[1]);
+            }
+        }
+
+        let st = &self.stamps;
+        st[0].stamp(matrix, self.scaled.gd);
+        st[1].stamp(matrix, self.scaled.gs);
+        st[2].stamp(matrix, bias.gds);
+        st[3].stamp(matrix, bias.gm);
+        st[4].stamp(matrix, ggs);
+        st[5].stamp(matrix, ggd);
+
+        let p = self.model.polarity;
+        let [_, g, _, d, s] = self.unknowns;
+        current(rhs, d, s, p * (bias.ids - bias.gm * vgs - bias.gds * vds));
+        current(rhs, g, s, p * (igs - ggs * vgs));
+        current(rhs, g, d, p * (igd - ggd * vgd));
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let b = &self.bias;
+        let st = &self.stamps;
+        st[0].stamp(matrix, Complex::from(self.scaled.gd));
+        st[1].stamp(matrix, Complex::from(self.scaled.gs));
+        st[2].stamp(matrix, Complex::from(b.gds));
+        st[3].stamp(matrix, Complex::from(b.gm));
+        st[4].stamp(matrix, Complex::new(b.ggs, omega * b.capacitances[0]));
+        st[5].stamp(matrix, Complex::new(b.ggd, omega * b.capacitances[1]));
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let [drain, _, source, d, s] = self.unknowns;
+        let sc = &self.scaled;
+        let mut sources = Vec::new();
+        for (name, outer, inner, g) in [("rd", drain, d, sc.gd), ("rs", source, s, sc.gs)] {
+            if outer != inner {
+                sources.push(NoiseSource {
+                    name: format!("{} {}", self.name, name),
+                    positive: outer,
+                    negative: inner,
+                    density: thermal_noise(g, sc.temperature),
+                });
+            }
+        }
+        sources.push(NoiseSource {
+            name: format!("{} channel", self.name),
+            positive: d,
+            negative: s,
+            density: thermal_noise(2.0 / 3.0 * self.bias.gm, sc.temperature),
+        });
+        if self.model.kf > 0.0 {
+            sources.push(NoiseSource {
+                name: format!("{} flicker", self.name),
+                positive: d,
+                negative: s,
+                density: flicker_noise(self.model.kf, self.model.af, 1.0, self.bias.ids, frequency),
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some([vds, vgs]) = self.initial {
+            let p = self.model.polarity;
+            self.bias = self.evaluate([p * vgs, p * vds], 0.0);
+            self.charges[0].reset(self.bias.charges[0]);
+            self.charges[1].reset(self.bias.charges[1]);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        for charge in &mut self.charges {
+            charge.accept();
+        }
+        self.accepted = (
+            [self.bias.vgs, self.bias.vgd()],
+            [self.charges[0].value(), self.charges[1].value()],
+        );
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charges
+            .iter()
+            .map(|charge| charge.allowed_step(timestep, integrator, h, Quantity::Charge))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The gate and drain currents predicted by the linearisation must
+    /// agree with those at the new voltages, as in ngspice's
+    /// `JFETconvTest`.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let b = &self.bias;
+        let [vgs, vds] = self.voltages(solution);
+        let (delvgs, delvds) = (vgs - b.vgs, vds - b.vds);
+        let delvgd = delvgs - delvds;
+        let cg = b.igs + b.igd;
+        let cd = b.ids - b.igd;
+        let cghat = cg + b.ggs * delvgs + b.ggd * delvgd;
+        let cdhat = cd + b.gm * delvgs + b.gds * delvds - b.ggd * delvgd;
+        let far = |hat: f64, value: f64| {
+            (hat - value).abs() > options.reltol * hat.abs().max(value.abs()) + options.abstol
+        };
+        far(cghat, cg) || far(cdhat, cd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::Method;
+    use crate::devices::tests::{ac, circuit, operating_point, solve_dc, transient};
+
+    /// The current into `V2` of a transistor biased by `V1` at the gate and
+    /// `V2` at the drain.
+    fn drain_current(element: &str, model: &str, vgs: f64, vds: f64) -> f64 {
+        let lines = [
+            format!("V1 g 0 {}", vgs),
+            format!("V2 d 0 {}", vds),
+            format!("{} d g 0 MOD", element),
+            model.to_string(),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (circuit, solution) = operating_point(&lines);
+        -solution[circuit.unknowns().find("I(V2)").unwrap()]
+    }
+
+    /// The small signal drain current at low frequency against a finite
+    /// difference of the operating point, for `vgs` and for `vds`.
+    fn check_conductances(element: &str, model: &str, vgs: f64, vds: f64) {
+        let delta = 1e-6;
+        let id = |vgs: f64, vds: f64| drain_current(element, model, vgs, vds);
+        let gm = (id(vgs + delta, vds) - id(vgs - delta, vds)) / (2.0 * delta);
+        let gds = (id(vgs, vds + delta) - id(vgs, vds - delta)) / (2.0 * delta);
+        for (ac_gate, expected) in [(true, gm), (false, gds)] {
+            let lines = [
+                format!("V1 g 0 {} AC {}", vgs, if ac_gate { 1 } else { 0 }),
+                format!("V2 d 0 {} AC {}", vds, if ac_gate { 0 } else { 1 }),
+                format!("{} d g 0 MOD", element),
+                model.to_string(),
+            ];
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let mut fet = circuit(&lines);
+            let branch = fet.unknowns().find("I(V2)").unwrap();
+            let response = -ac(&mut fet, 1.0)[branch].re;
+            assert!(
+                (response - expected).abs() < 1e-4 * expected.abs() + 1e-9,
+                "{} at vgs {} vds {}: {} {}",
+                model,
+                vgs,
+                vds,
+                response,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_square_law() {
+        let model = ".model MOD NJF(VTO=-2 BETA=1m LAMBDA=0.01)";
+        // Saturated, then linear.
+        let expected = 1e-3 * 1.0 * (1.0 + 0.01 * 5.0);
+        assert!((drain_current("J1", model, -1.0, 5.0) / expected - 1.0).abs() < 1e-7);
+        let expected = 1e-3 * 0.5 * (2.0 - 0.5) * (1.0 + 0.01 * 0.5);
+        assert!((drain_current("J1", model, -1.0, 0.5) / expected - 1.0).abs() < 1e-7);
+        // Pinched off, but for the gate leakage.
+        assert!(drain_current("J1", model, -2.5, 5.0).abs() < 1e-10);
+
+        // A p channel mirrors it, with the same negative VTO.
+        let model = ".model MOD PJF(VTO=-2 BETA=1m LAMBDA=0.01)";
+        let expected = 1e-3 * (1.0 + 0.01 * 5.0);
+        assert!((drain_current("J1", model, 1.0, -5.0) / -expected - 1.0).abs() < 1e-7);
+
+        // The doping tail: B (vgs - VTO)² plus a cubic that restores the
+        // full current at vgs = PB.
+        let model = ".model MOD NJF(VTO=-2 BETA=1m B=0.5 PB=1)";
+        let bfac = 0.5 / 3.0;
+        let expected = 1e-3 * (0.5 + bfac * 1.0);
+        assert!((drain_current("J1", model, -1.0, 5.0) / expected - 1.0).abs() < 1e-7);
+        for (vgs, vds) in [(-1.0, 5.0), (-1.0, 0.3), (-0.5, -0.4), (-1.5, -3.0)] {
+            check_conductances("J1", model, vgs, vds);
+        }
+    }
+
+    #[test]
+    fn test_gate() {
+        // Forward biased, the gate is a diode of IS to the source and
+        // another to the drain.
+        let (forward, solution) = operating_point(&[
+            "I1 0 g 1m",
+            "J1 0 g 0 MOD",
+            ".model MOD NJF(VTO=-2 IS=1e-14 N=1.2 RS=10 RD=10)",
+        ]);
+        let vt = BOLTZMANN * (27.0 + KELVIN) / CHARGE;
+        let vgs = 1.2 * vt * (0.5e-3 / 1e-14f64 + 1.0).ln();
+        let g = solution[forward.unknowns().find("V(g)").unwrap()];
+        assert!((g - vgs - 10.0 * 0.5e-3).abs() < 1e-5, "{} {}", g, vgs);
+
+        // Reverse biased, the gate carries the depletion capacitances of
+        // both junctions.
+        let mut reverse = circuit(&[
+            "V1 g 0 -3 AC 1",
+            "J1 0 g 0 MOD",
+            ".model MOD NJF(VTO=-2 CGS=2p CGD=1p PB=1)",
+        ]);
+        let branch = reverse.unknowns().find("I(V1)").unwrap();
+        let omega = 1e6;
+        let response = ac(&mut reverse, omega)[branch];
+        let expected = omega * 3e-12 / 2.0;
+        assert!((response.im.abs() / expected - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_temperature() {
+        let model = ".model MOD NJF(VTO=-2 BETA=1m VTOTC=-2.5m BETATCE=-0.5)";
+        let options = Options::default();
+        let mut hot = circuit(&["V1 g 0 -1", "V2 d 0 5", "J1 d g 0 MOD", model]);
+        hot.set_temperature(127.0, &options);
+        let branch = hot.unknowns().find("I(V2)").unwrap();
+        let id = -solve_dc(&mut hot)[branch];
+        // VTO falls by 0.25 V and BETA by 1.01^-50.
+        let expected = 1e-3 * 1.01f64.powf(-50.0) * 1.25f64.powi(2);
+        assert!((id / expected - 1.0).abs() < 1e-5, "{} {}", id, expected);
+    }
+
+    #[test]
+    fn test_noise() {
+        let (circuit, _) = operating_point(&[
+            "V1 g 0 -1",
+            "V2 d 0 5",
+            "J1 d g 0 MOD",
+            ".model MOD NJF(VTO=-2 BETA=1m RD=100 KF=1e-15)",
+        ]);
+        let noise = circuit.noise(100.0);
+        let find = |name: &str| noise.iter().find(|n| n.name == name).unwrap().density;
+        let gm = 2e-3;
+        let thermal = 8.0 * BOLTZMANN * (27.0 + KELVIN) / 3.0 * gm;
+        assert!((find("J1 channel") / thermal - 1.0).abs() < 1e-3);
+        assert!((find("J1 rd") / thermal_noise(0.01, 27.0) - 1.0).abs() < 1e-12);
+        assert!((find("J1 flicker") / (1e-15 * 1e-3 / 100.0) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_mesfet() {
+        // Statz saturates at 3/ALPHA, softened by B.
+        let statz = ".model MOD NMF(VTO=-2 BETA=10m ALPHA=3 B=0.5 LAMBDA=0.05)";
+        let expected = 10e-3 / 1.5 * (1.0 + 0.05 * 3.0);
+        assert!((drain_current("Z1", statz, -1.0, 3.0) / expected - 1.0).abs() < 1e-7);
+        let curtice = ".model MOD NMF(LEVEL=2 VTO=-2 BETA=10m ALPHA=3)";
+        let expected = 10e-3 * 0.5f64.tanh();
+        assert!((drain_current("Z1", curtice, -1.0, 0.5 / 3.0) / expected - 1.0).abs() < 1e-6);
+        for model in [statz, curtice] {
+            for (vgs, vds) in [(-1.0, 3.0), (-0.5, 0.2), (-1.0, -0.3)] {
+                check_conductances("Z1", model, vgs, vds);
+            }
+        }
+    }
+
+    #[test]
+    fn test_amplifier() {
+        // A common source stage, self biased through RS and bypassed: the
+        // gain at mid band is gm RD, through the gate charges when the
+        // transients settle.
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        for (element, model) in [
+            ("J1", ".model MOD NJF(VTO=-2 BETA=1m CGS=5p CGD=2p)"),
+            (
+                "Z1",
+                ".model MOD NMF(VTO=-2 BETA=1m ALPHA=3 B=0 CGS=5p CGD=2p)",
+            ),
+        ] {
+            let line = format!("{} d g s MOD", element);
+            let mut stage = circuit(&[
+                "V1 vdd 0 15",
+                "V2 in 0 SINE(0 10m 10k)",
+                "C1 in g 1u",
+                "R1 g 0 1meg",
+                "R2 vdd d 10k",
+                "R3 s 0 1k",
+                "C2 s 0 100u",
+                &line,
+                model,
+            ]);
+            let (d, s) = (
+                stage.unknowns().find("V(d)").unwrap(),
+                stage.unknowns().find("V(s)").unwrap(),
+            );
+            let points = transient(&mut stage, ".tran 0.2m", &options);
+            let (_, first) = &points[0];
+            let late: Vec<f64> = points
+                .iter()
+                .filter(|(t, _)| *t > 0.1e-3)
+                .map(|(_, x)| x[d])
+                .collect();
+            let swing = late.iter().fold(f64::MIN, |a, &b| a.max(b))
+                - late.iter().fold(f64::MAX, |a, &b| a.min(b));
+            // id = BETA (vgs - VTO)² with vgs = -1k id, and gm = 2 BETA
+            // (vgs - VTO).
+            let vs = first[s];
+            let gm = 2.0 * 1e-3 * (2.0 - vs);
+            let gain = swing / 20e-3;
+            assert!(
+                (gain / (gm * 10e3) - 1.0).abs() < 0.05,
+                "{} {}",
+                element,
+                gain
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Limiting of controlling voltages between Newton iterations.
+//!
+//! An exponential junction or a MOS channel linearised far from its last
+//! operating point predicts wild steps. Devices pass each new controlling
+//! voltage through these functions before evaluating themselves, as in
+//! SPICE's `DEVpnjlim`, `DEVfetlim` and `DEVlimvds`.
+
+use crate::devices::generic::{BOLTZMANN, CHARGE, KELVIN};
+
+/// Thermal voltage `kT/q` at `temperature` in °C.
+pub fn thermal_voltage(temperature: f64) -> f64 {
+    BOLTZMANN * (temperature + KELVIN) / CHARGE
+}
+
+/// Junction voltage at which the diode current curve bends most sharply,
+/// above which steps are limited.
+pub fn critical_voltage(vt: f64, saturation_current: f64) -> f64 {
+    vt * (vt / (std::f64::consts::SQRT_2 * saturation_current)).ln()
+}
+
+/// Limit the new voltage across a pn junction, returning it and whether it
+/// was changed. Above `vcrit`, steps of more than `2 vt` are replaced by
+/// the step that would change the current by the same factor.
+pub fn pnjlim(vnew: f64, vold: f64, vt: f64, vcrit: f64) -> (f64, bool) {
+    if vnew <= vcrit || (vnew - vold).abs() <= 2.0 * vt {
+        return (vnew, false);
+    }
+    let limited = if vold > 0.0 {
+        let arg = 1.0 + (vnew - vold) / vt;
+        if arg > 0.0 {
+            vold + vt * arg.ln()
+        } else {
+            vcrit
+        }
+    } else {
+        vt * (vnew / vt).ln()
+    };
+    (limited, true)
+}
+
+/// Limit the new gate-source voltage of a FET with threshold `vto`, keeping
+/// steps small near threshold and letting the channel turn on or off in
+/// stages.
+pub fn fetlim(vnew: f64, vold: f64, vto: f64) -> f64 {
+    let vtsthi = (2.0 * (vold - vto)).abs() + 2.0;
+    let vtstlo = (vold - vto).abs() + 1.0;
+    let vtox = vto + 3.5;
+    let delta = vnew - vold;
+    if vold >= vto {
+        if vold >= vtox {
+            if delta <= 0.0 {
+                // Going off.
+                if vnew >= vtox {
+                    if -delta > vtstlo {
+                        return vold - vtstlo;
+                    }
+                    vnew
+                } else {
+                    vnew.max(vto + 2.0)
+                }
+            } else if delta >= vtsthi {
+                // Staying on.
+                vold + vtsthi
+            } else {
+                vnew
+            }
+        } else if delta <= 0.0 {
+            // Middle region, decreasing.
+            vnew.max(vto - 0.5)
+        } else {
+            vnew.min(vto + 4.0)
+        }
+    } else if delta <= 0.0 {
+        // Off and going further off.
+        if -delta > vtsthi {
+            vold - vtsthi
+        } else {
+            vnew
+        }
+    } else {
+        let vtemp = vto + 0.5;
+        if vnew <= vtemp {
+            if delta > vtstlo {
+                vold + vtstlo
+            } else {
+                vnew
+            }
+        } else {
+            vtemp
+        }
+    }
+}
+
+/// Limit the new drain-source voltage of a FET.
+pub fn limvds(vnew: f64, vold: f64) -> f64 {
+    if vold >= 3.5 {
+        if vnew > vold {
+            vnew.min(3.0 * vold + 2.0)
+        } else if vnew < 3.5 {
+            vnew.max(2.0)
+        } else {
+            vnew
+        }
+    } else if vnew > vold {
+        vnew.min(4.0)
+    } else {
+        vnew.max(-0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pnjlim() {
+        let vt = thermal_voltage(27.0);
+        assert!((vt - 0.025865).abs() < 1e-5);
+        let vcrit = critical_voltage(vt, 1e-14);
+        assert!(vcrit > 0.6 && vcrit < 0.8);
+
+        // Small steps and steps below vcrit pass through.
+        assert_eq!(pnjlim(0.65, 0.64, vt, vcrit), (0.65, false));
+        assert_eq!(pnjlim(0.3, -5.0, vt, vcrit), (0.3, false));
+        // A jump from 0.6 V to 5 V becomes a step of a few vt.
+        let (v, limited) = pnjlim(5.0, 0.6, vt, vcrit);
+        assert!(limited);
+        assert!(v > 0.6 && v < 0.6 + 10.0 * vt);
+        // From reverse bias the new voltage is compressed logarithmically.
+        let (v, limited) = pnjlim(5.0, -1.0, vt, vcrit);
+        assert!(limited);
+        assert!((v - vt * (5.0 / vt).ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fetlim() {
+        // Off, turning on: clamped just above threshold.
+        assert_eq!(fetlim(5.0, 0.0, 1.0), 1.5);
+        // Middle region, increasing: clamped at vto + 4.
+        assert_eq!(fetlim(10.0, 2.0, 1.0), 5.0);
+        // Well on, big increase: limited to vtsthi.
+        assert_eq!(fetlim(20.0, 5.0, 1.0), 15.0);
+        // Small steps pass.
+        assert_eq!(fetlim(5.1, 5.0, 1.0), 5.1);
+        assert_eq!(limvds(10.0, 1.0), 4.0);
+        assert_eq!(limvds(-3.0, 1.0), -0.5);
+        assert_eq!(limvds(30.0, 5.0), 17.0);
+    }
+}
@@ -0,0 +1,162 @@
+//! GaAs MESFETs of `NMF` and `PMF` model cards.
+//!
+//! Level 1 is Statz's model as in ngspice's `mesload.c`: a square law
+//! softened by `B` at large gate drive, saturating through a cubic in
+//! `ALPHA vds` that reaches the saturated current at `vds = 3/ALPHA`, with
+//! Statz's gate charge, which moves from the source side to the drain side
+//! as the channel reverses and saturates at the junction's built-in
+//! potential. Level 2 is Curtice's quadratic model, `BETA (vgs - VTO)²
+//! tanh(ALPHA vds)`, with depletion capacitances `CGS` and `CGD` as in a
+//! JFET. Both are run by [`crate::devices::jfet::Jfet`], which adds the
+//! gate junctions, series resistances, temperature and noise.
+
+use crate::devices::jfet::{Channel, JfetModel};
+use crate::devices::model::Model;
+use crate::maths::dual::Real;
+
+/// The parameters of an `NMF` or `PMF` card, with PSpice's GaAsFET
+/// defaults.
+pub fn model(model: &Model) -> Result<JfetModel, String> {
+    model.unknown_parameters(&[
+        "level", "vto", "beta", "alpha", "b", "lambda", "rd", "rs", "cgs", "cgd", "pb", "vbi", "m",
+        "fc", "is", "n", "kf", "af", "vtotc", "tcv", "betatce", "bex", "xti", "eg", "tnom", "tref",
+    ])?;
+    let value = |names: &[&str], default: f64| -> Result<f64, String> {
+        Ok(model.alias(names)?.unwrap_or(default))
+    };
+    let alpha = value(&["alpha"], 2.0)?;
+    let channel = match model.number("level")?.unwrap_or(1.0) as i32 {
+        1 => Channel::Statz {
+            alpha,
+            b: value(&["b"], 0.3)?,
+        },
+        2 => Channel::Curtice { alpha },
+        level => return Err(format!("Model {}: no MESFET level {}", model.name, level)),
+    };
+    if alpha <= 0.0 {
+        return Err(format!("Model {}: ALPHA must be positive", model.name));
+    }
+    JfetModel {
+        polarity: if model.kind == "PMF" { -1.0 } else { 1.0 },
+        channel,
+        vto: value(&["vto"], -2.5)?,
+        beta: value(&["beta"], 0.1)?,
+        lambda: value(&["lambda"], 0.0)?,
+        rd: value(&["rd"], 0.0)?,
+        rs: value(&["rs"], 0.0)?,
+        cgs: value(&["cgs"], 0.0)?,
+        cgd: value(&["cgd"], 0.0)?,
+        pb: value(&["pb", "vbi"], 1.0)?,
+        m: value(&["m"], 0.5)?,
+        fc: value(&["fc"], 0.5)?,
+        is: value(&["is"], 1e-14)?,
+        n: value(&["n"], 1.0)?,
+        isr: 0.0,
+        nr: 2.0,
+        kf: value(&["kf"], 0.0)?,
+        af: value(&["af"], 1.0)?,
+        vtotc: value(&["vtotc"], 0.0)? - value(&["tcv"], 0.0)?,
+        betatce: value(&["betatce"], 0.0)?,
+        bex: value(&["bex"], 0.0)?,
+        xti: value(&["xti"], 0.0)?,
+        eg: value(&["eg"], 1.11)?,
+        tnom: model.alias(&["tnom", "tref"])?,
+    }
+    .checked(&model.name)
+}
+
+/// Statz's drain current over `BETA (1 + LAMBDA vds)`, at a positive gate
+/// drive `vgst` and `vds ≥ 0`.
+pub(crate) fn statz<T: Real>(vgst: T, vds: T, alpha: f64, b: f64) -> T {
+    let square = vgst * vgst / (vgst * b + 1.0);
+    if vds.value() >= 3.0 / alpha {
+        return square;
+    }
+    let factor = -(vds * (alpha / 3.0)) + 1.0;
+    square * (-(factor * factor * factor) + 1.0)
+}
+
+/// Curtice's drain current over `BETA (1 + LAMBDA vds)`.
+pub(crate) fn curtice<T: Real>(vgst: T, vds: T, alpha: f64) -> T {
+    vgst * vgst * (vds * alpha).tanh()
+}
+
+/// Statz's gate charge at `vgs` and `vgd`, for a channel with threshold
+/// `vto` pinching off over `vcap = 1/ALPHA`. Zero-bias capacitances `cgs`
+/// and `cgd` face the side at the higher voltage and the other one; the
+/// depletion charge is carried on linearly above `vmax`, ngspice's fixed
+/// 0.5 V being `FC PB` with the default `FC`.
+pub(crate) fn statz_charge<T: Real>(vgs: T, vgd: T, s: &StatzCharge) -> T {
+    let c = T::constant;
+    let root = ((vgs - vgd) * (vgs - vgd) + s.vcap * s.vcap).sqrt();
+    let veff1 = (vgs + vgd + root) * 0.5;
+    let veff2 = veff1 - root;
+    let delta = 0.2;
+    let vnroot = ((veff1 - s.vto) * (veff1 - s.vto) + delta * delta).sqrt();
+    let vnew = (veff1 + s.vto + vnroot) * 0.5;
+    let (vnew, extension) = if vnew.value() < s.vmax {
+        (vnew, c(0.0))
+    } else {
+        (c(s.vmax), (vnew - s.vmax) / (1.0 - s.vmax / s.pb).sqrt())
+    };
+    let qroot = (-(vnew / s.pb) + 1.0).sqrt();
+    ((-qroot + 1.0) * (2.0 * s.pb) + extension) * s.cgs + veff2 * s.cgd
+}
+
+/// The parameters of Statz's gate charge at the instance temperature.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct StatzCharge {
+    pub pb: f64,
+    pub vcap: f64,
+    pub vto: f64,
+    pub vmax: f64,
+    pub cgs: f64,
+    pub cgd: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::dual::Dual;
+
+    #[test]
+    fn test_statz() {
+        // Saturated past 3/ALPHA, and continuous with zero slope there.
+        let vgst = 1.0;
+        let saturated = statz(vgst, 2.0, 2.0, 0.3);
+        assert!((saturated - 1.0 / 1.3).abs() < 1e-15);
+        let x = Dual::<1>::variable(1.5 - 1e-9, 0);
+        let below = statz(Dual::constant(vgst), x, 2.0, 0.3);
+        assert!((below.value - 1.0 / 1.3).abs() < 1e-12);
+        assert!(below.derivative(0).abs() < 1e-12);
+        // Linear at small vds: 1 - (1 - a vds/3)³ ≈ a vds.
+        assert!((statz(vgst, 1e-6, 2.0, 0.3) / (2e-6 / 1.3) - 1.0).abs() < 1e-5);
+        assert!((curtice(1.0, 10.0, 2.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_statz_charge() {
+        let s = StatzCharge {
+            pb: 0.8,
+            vcap: 0.5,
+            vto: -2.0,
+            vmax: 0.4,
+            cgs: 1e-12,
+            cgd: 0.1e-12,
+        };
+        let capacitances = |vgs: f64, vgd: f64| {
+            let q = statz_charge(Dual::<2>::variable(vgs, 0), Dual::variable(vgd, 1), &s);
+            (q.derivative(0), q.derivative(1))
+        };
+        // Saturated, the source side takes CGS over the depletion root and
+        // the drain side CGD; reversed, they swap.
+        let (cgs, cgd) = capacitances(0.0, -5.0);
+        assert!((cgs / 1e-12 - 1.0).abs() < 0.02, "{}", cgs);
+        assert!((cgd / 0.1e-12 - 1.0).abs() < 0.03, "{}", cgd);
+        let (cgs_reverse, cgd_reverse) = capacitances(-5.0, 0.0);
+        assert!((cgs_reverse - cgd).abs() < 1e-18 && (cgd_reverse - cgs).abs() < 1e-18);
+        // Pinched off, little remains but CGD on both sides.
+        let (cgs, _) = capacitances(-6.0, -8.0);
+        assert!(cgs < 0.15e-12, "{}", cgs);
+    }
+}
@@ -1 +1,181 @@
-mod generic;
+use crate::devices::behavioural::{expression, is_behavioural, Behavioural};
+use crate::devices::bjt::Bjt;
+use crate::devices::controlled::Controlled;
+use crate::devices::diode::Diode;
+use crate::devices::generic::Device;
+use crate::devices::inductor::Coupling;
+use crate::devices::jfet::Jfet;
+use crate::devices::model::{parse_models, Model};
+use crate::devices::parameters::ElementLine;
+use crate::devices::resistor::Resistor;
+use crate::devices::source::Source;
+use crate::devices::transmission::TransmissionLine;
+use crate::frontend::parser::netlist::{Component, Netlist};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::path::Path;
+
+pub mod behavioural;
+pub mod bjt;
+pub mod bsim;
+pub mod bsim3;
+pub mod bsim4;
+pub mod capacitor;
+pub mod controlled;
+pub mod diode;
+pub mod generic;
+pub mod inductor;
+pub mod jfet;
+pub mod limiting;
+pub mod mesfet;
+pub mod model;
+pub mod mosfet;
+pub mod parameters;
+pub mod resistor;
+pub mod source;
+pub mod switch;
+pub mod transmission;
+pub mod vdmos;
+
+/// The devices of the top level of `netlist`, a part's parasitics among
+/// them, with the parameters of its `.model` cards. Relative `file=` paths
+/// are found in `directory`, normally the netlist's folder.
+pub fn build(netlist: &Netlist, directory: &Path) -> Result<Vec<Box<dyn Device>>, ErrorHandler> {
+    let models = parse_models(&netlist.directives)?;
+    let mut error_handler = ErrorHandler::new();
+    let mut devices = Vec::new();
+    for component in &netlist.components {
+        match build_component(component, &netlist.components, &models, directory) {
+            Ok(built) => devices.extend(built),
+            Err(message) => error_handler.add_error(Error::new(
+                ErrorType::Syntax,
+                format!("{}: {}", component.name, message),
+                0,
+                0,
+            )),
+        }
+    }
+    if error_handler.has_errors() {
+        return Err(error_handler);
+    }
+    Ok(devices)
+}
+
+fn build_component(
+    component: &Component,
+    components: &[Component],
+    models: &[Model],
+    directory: &Path,
+) -> Result<Vec<Box<dyn Device>>, String> {
+    let line = ElementLine::new(component);
+    let device: Box<dyn Device> = match component.prefix() {
+        'R' if is_behavioural(&line, "r") => Box::new(Behavioural::resistor(&line, components)?),
+        'R' => Box::new(Resistor::parse(&line)?),
+        'C' if expression(&line, "q").is_some() => {
+            Box::new(Behavioural::capacitor(&line, components)?)
+        }
+        'C' => return capacitor::build(&line),
+        'L' if expression(&line, "flux").is_some() => {
+            Box::new(Behavioural::inductor(&line, components)?)
+        }
+        'L' => return inductor::build(&line),
+        'K' => Box::new(Coupling::parse(&line, components)?),
+        'E' | 'G' if expression(&line, "value").is_some() => {
+            Box::new(Behavioural::value(&line, components)?)
+        }
+        'E' | 'F' | 'G' | 'H' => Box::new(Controlled::parse(&line, components)?),
+        'B' => Box::new(Behavioural::source(&line, components)?),
+        'D' => Box::new(Diode::parse(&line, models)?),
+        'Q' => Box::new(Bjt::parse(&line, models)?),
+        'M' => return mosfet::build(&line, models),
+        'J' | 'Z' => Box::new(Jfet::parse(&line, models)?),
+        'S' | 'W' => return switch::build(&line, components, models),
+        'T' => Box::new(TransmissionLine::lossless(&line)?),
+        'O' => Box::new(TransmissionLine::lossy(&line, models)?),
+        'V' | 'I' => Box::new(Source::parse(&line, directory)?),
+        prefix => return Err(format!("Unsupported element type {}", prefix)),
+    };
+    Ok(vec![device])
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::analysis::newton::{Newton, System};
+    use crate::analysis::op::operating_point as solve;
+    use crate::analysis::options::Options;
+    use crate::analysis::tran::transient as run;
+    use crate::devices::generic::Circuit;
+    use crate::frontend::parser::tran::parse_tran;
+    use crate::maths::complex::Complex;
+    use crate::maths::sparse::{Lu, Pattern, SparseMatrix};
+
+    /// The circuit of element lines such as `R1 a b 1k` and `.model`
+    /// cards.
+    pub(crate) fn circuit(lines: &[&str]) -> Circuit {
+        let mut netlist = Netlist::new();
+        for line in lines {
+            if line.starts_with('.') {
+                netlist.add_directive(line.to_string());
+                continue;
+            }
+            let (name, rest) = line.split_once(' ').unwrap();
+            netlist.add_component(Component::new(name, Vec::new(), rest));
+        }
+        let devices = build(&netlist, Path::new(".")).unwrap();
+        Circuit::new(devices, &Options::default())
+    }
+
+    pub(crate) fn operating_point(lines: &[&str]) -> (Circuit, Vec<f64>) {
+        let mut circuit = circuit(lines);
+        let solution = solve_dc(&mut circuit);
+        (circuit, solution)
+    }
+
+    pub(crate) fn solve_dc(circuit: &mut Circuit) -> Vec<f64> {
+        let options = Options::default();
+        let mut newton = Newton::new(circuit, &options);
+        circuit.set_dc();
+        solve(&mut newton, circuit, &options).unwrap().solution
+    }
+
+    /// The response at angular frequency `omega` around the operating point.
+    pub(crate) fn ac(circuit: &mut Circuit, omega: f64) -> Vec<Complex> {
+        solve_dc(circuit);
+        let mut pattern = Pattern::new(circuit.size());
+        circuit.setup(&mut pattern);
+        let mut matrix: SparseMatrix<Complex> = pattern.matrix();
+        let mut rhs = vec![Complex::default(); circuit.size()];
+        circuit.ac_load(omega, &mut matrix, &mut rhs);
+        let mut lu = Lu::new(Options::default().lu_options());
+        lu.factor(&matrix).unwrap();
+        lu.solve(&mut rhs);
+        rhs
+    }
+
+    /// The accepted points of a transient analysis, the first at time zero.
+    pub(crate) fn transient(
+        circuit: &mut Circuit,
+        tran: &str,
+        options: &Options,
+    ) -> Vec<(f64, Vec<f64>)> {
+        run(circuit, &parse_tran(tran).unwrap(), options).unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        let mut netlist = Netlist::new();
+        netlist.add_component(Component::new("R1", Vec::new(), "a 0 1k"));
+        netlist.add_component(Component::new("C1", Vec::new(), "a 0 1u Rser=1 Lser=1n"));
+        netlist.add_component(Component::new("U1", Vec::new(), "a 0"));
+        netlist.add_component(Component::new("R2", Vec::new(), "a 0 1k tc3=1"));
+        let errors = build(&netlist, Path::new(".")).unwrap_err().errors;
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "U1: Unsupported element type U");
+        assert_eq!(errors[1].message, "R2: Unknown parameter tc3");
+
+        netlist.components.truncate(2);
+        let devices = build(&netlist, Path::new(".")).unwrap();
+        let names: Vec<&str> = devices.iter().map(|d| d.name()).collect();
+        assert_eq!(names, ["R1", "C1.Rser", "C1.Lser", "C1"]);
+    }
+}
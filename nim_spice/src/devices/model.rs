@@ -0,0 +1,194 @@
+//! `.model` cards, the parameters an element line names by model.
+
+use crate::devices::parameters::split;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+
+/// `.model name type(parameter=value ...)`, the parentheses optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    pub name: String,
+    /// The type in upper case, such as `D` or `NPN`.
+    pub kind: String,
+    /// Parameter names in lower case, in the order given.
+    pub parameters: Vec<(String, String)>,
+    /// Bare words such as VDMOS's `pchan`, in lower case.
+    pub flags: Vec<String>,
+}
+
+impl Model {
+    pub fn parse(directive: &str) -> Result<Self, String> {
+        let mut rest = directive.trim();
+        let mut word = || {
+            let end = rest
+                .find(|c: char| c == '(' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let (word, tail) = rest.split_at(end);
+            rest = tail.trim_start();
+            word
+        };
+        if !word().eq_ignore_ascii_case(".model") {
+            return Err("Not a .model card".to_string());
+        }
+        let name = word().to_string();
+        let kind = word().to_ascii_uppercase();
+        if name.is_empty() || kind.is_empty() {
+            return Err("A .model needs a name and a type".to_string());
+        }
+        let text = rest
+            .strip_prefix('(')
+            .map_or(rest, |r| r.trim_end().strip_suffix(')').unwrap_or(r));
+        let (words, parameters) = split(text);
+        if let Some(word) = words.iter().find(|w| parse_number(w).is_some()) {
+            return Err(format!(
+                "Model {}: expected parameter=value, found {}",
+                name, word
+            ));
+        }
+        Ok(Model {
+            name,
+            kind,
+            parameters,
+            flags: words.iter().map(|w| w.to_ascii_lowercase()).collect(),
+        })
+    }
+
+    /// The value of numeric parameter `name`, the last one winning.
+    pub fn number(&self, name: &str) -> Result<Option<f64>, String> {
+        match self.parameters.iter().rev().find(|(key, _)| key == name) {
+            None => Ok(None),
+            Some((_, text)) => parse_number(text).map(Some).ok_or_else(|| {
+                format!("Model {}: invalid value for {}: {}", self.name, name, text)
+            }),
+        }
+    }
+
+    /// Whether the bare word `name` is among the parameters.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// The first of `names`, a parameter and its aliases, given.
+    pub fn alias(&self, names: &[&str]) -> Result<Option<f64>, String> {
+        for name in names {
+            if let Some(value) = self.number(name)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every parameter or flag not in `known`, to report. Part data a
+    /// schematic library adds to its models, such as `mfg=` and `Vpk=`, is
+    /// never reported.
+    pub fn unknown_parameters(&self, known: &[&str]) -> Result<(), String> {
+        const PART_DATA: [&str; 13] = [
+            "mfg", "type", "vpk", "ipk", "iave", "irms", "diss", "icrating", "vceo", "icrave",
+            "vds", "ron", "qg",
+        ];
+        let mut keys = self
+            .parameters
+            .iter()
+            .map(|(key, _)| key)
+            .chain(&self.flags);
+        match keys.find(|key| !known.contains(&key.as_str()) && !PART_DATA.contains(&key.as_str()))
+        {
+            Some(key) => Err(format!(
+                "Model {}: unsupported {} parameter {}",
+                self.name, self.kind, key
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Every `.model` card among `directives`.
+pub fn parse_models(directives: &[String]) -> Result<Vec<Model>, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut models = Vec::new();
+    for directive in directives {
+        let is_model = directive
+            .split_whitespace()
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case(".model"));
+        if !is_model {
+            continue;
+        }
+        match Model::parse(directive) {
+            Ok(model) => models.push(model),
+            Err(message) => {
+                error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+            }
+        }
+    }
+    if error_handler.has_errors() {
+        return Err(error_handler);
+    }
+    Ok(models)
+}
+
+/// The model called `name` of one of the `kinds`, later cards winning.
+pub fn find_model<'a>(
+    models: &'a [Model],
+    name: &str,
+    kinds: &[&str],
+) -> Result<&'a Model, String> {
+    let model = models
+        .iter()
+        .rev()
+        .find(|m| m.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No model {}", name))?;
+    if !kinds.contains(&model.kind.as_str()) {
+        return Err(format!(
+            "Model {} is of type {}, not {}",
+            name,
+            model.kind,
+            kinds.join(" or ")
+        ));
+    }
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model() {
+        let model = Model::parse(
+            ".model 1N4148 D(Is=2.52n Rs=.568 N=1.752 Cjo=4p M=.4 tt=20n Iave=200m mfg=OnSemi)",
+        )
+        .unwrap();
+        assert_eq!(model.name, "1N4148");
+        assert_eq!(model.kind, "D");
+        assert_eq!(model.number("is"), Ok(Some(2.52e-9)));
+        assert_eq!(model.alias(&["cj0", "cjo"]), Ok(Some(4e-12)));
+        assert_eq!(model.number("bv"), Ok(None));
+        assert!(model
+            .unknown_parameters(&["is", "rs", "n", "cjo", "m", "tt"])
+            .is_ok());
+        assert!(model.unknown_parameters(&["is"]).is_err());
+        assert!(model.number("mfg").is_err());
+
+        let bare = Model::parse(".MODEL DMOD d").unwrap();
+        assert_eq!((bare.kind.as_str(), bare.parameters.len()), ("D", 0));
+        let spaced = Model::parse(".model Q1 NPN (BF = 200 VAF=100)").unwrap();
+        assert_eq!(spaced.number("bf"), Ok(Some(200.0)));
+        let flagged = Model::parse(".model M1 VDMOS(pchan Vto=-2)").unwrap();
+        assert!(flagged.flag("pchan") && !flagged.flag("nchan"));
+        assert!(flagged.unknown_parameters(&["vto"]).is_err());
+        assert!(flagged.unknown_parameters(&["vto", "pchan"]).is_ok());
+        assert!(Model::parse(".model D1 D(1n)").is_err());
+
+        let models = parse_models(&[
+            ".tran 1m".to_string(),
+            ".model 1N4148 D(Is=1n)".to_string(),
+            ".model Q1 NPN".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(models.len(), 2);
+        assert!(find_model(&models, "1n4148", &["D"]).is_ok());
+        assert!(find_model(&models, "Q1", &["D"]).is_err());
+        assert!(find_model(&models, "Q2", &["NPN"]).is_err());
+    }
+}
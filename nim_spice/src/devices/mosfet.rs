@@ -0,0 +1,1295 @@
+//! MOSFET levels 1, 2 and 3.
+//!
+//! Level 1 is Shichman and Hodges' square law with body effect and channel
+//! length modulation `LAMBDA`. Level 2 is Grove and Frohman's bulk charge
+//! model, with SPICE2's short and narrow channel threshold corrections,
+//! mobility degradation by `UCRIT`, `UEXP` and `UTRA`, velocity saturation
+//! at `VMAX` and weak inversion above `NFS`. Level 3 is SPICE2's semi
+//! empirical short channel model, with drain induced barrier lowering
+//! `ETA`, mobility modulation `THETA` and saturation field `KAPPA`. All
+//! three share the bulk junction diodes, Meyer's gate capacitances, the
+//! overlap capacitances and the series resistances of ngspice's
+//! `mos1load.c`. The drain current is written once against [`Real`] and
+//! differentiated exactly with [`Dual`] numbers.
+//!
+//! Power MOSFETs of a `VDMOS` model are in [`crate::devices::vdmos`].
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::bsim::{self, Bsim, Version};
+use crate::devices::diode::{depletion, junction_temperature, REFERENCE_TEMPERATURE};
+use crate::devices::generic::{
+    current, flicker_noise, thermal_noise, voltage, Conductance, Device, LoadContext, NoiseSource,
+    State, Unknown, Unknowns, BOLTZMANN, CHARGE, KELVIN,
+};
+use crate::devices::limiting::{critical_voltage, fetlim, limvds, pnjlim};
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::devices::vdmos;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::dual::{Dual, Real};
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// Permittivity of free space in F/m.
+pub(crate) const EPSILON_0: f64 = 8.854214871e-12;
+/// Permittivity of silicon dioxide in F/m.
+pub(crate) const EPSILON_OX: f64 = 3.9 * EPSILON_0;
+/// Permittivity of silicon in F/m.
+pub(crate) const EPSILON_SI: f64 = 11.7 * EPSILON_0;
+/// Intrinsic carrier density of silicon in cm⁻³.
+const INTRINSIC_DENSITY: f64 = 1.45e10;
+/// Channel length and width of an instance that gives none, in m.
+pub(crate) const DEFAULT_SIZE: f64 = 100e-6;
+
+/// The energy gap of silicon at `t` in K.
+pub(crate) fn energy_gap(t: f64) -> f64 {
+    1.16 - (7.02e-4 * t * t) / (t + 1108.0)
+}
+
+/// A MOSFET of an `NMOS` or `PMOS` model, a BSIM one for levels 8 and 49
+/// or 14 and 54, or a power MOSFET of a `VDMOS` one. The model is the
+/// first word after the nodes that names one.
+pub fn build(line: &ElementLine, models: &[Model]) -> Result<Vec<Box<dyn Device>>, String> {
+    let is_model = |word: &String| models.iter().any(|m| m.name.eq_ignore_ascii_case(word));
+    let index = match line.words.get(3) {
+        Some(word) if is_model(word) => 3,
+        _ => 4,
+    };
+    let name = line.words.get(index).ok_or("No model given")?;
+    let model = match find_model(models, name, &["NMOS", "PMOS", "VDMOS"]) {
+        Err(_) if index == 4 && !is_model(name) => {
+            // A binned kit names its cards `name.1`, `name.2` ... by size.
+            let l = line.number("l")?.unwrap_or(DEFAULT_SIZE);
+            let w = line.number("w")?.unwrap_or(DEFAULT_SIZE);
+            bsim::select(models, name, l, w)
+                .ok_or_else(|| format!("No model {} for L={} W={}", name, l, w))?
+        }
+        found => found?,
+    };
+    if model.kind == "VDMOS" {
+        return vdmos::build(line, model, index);
+    }
+    if index != 4 {
+        return Err("Expected drain, gate, source and bulk nodes".to_string());
+    }
+    if let Some(version) = Version::of(model.number("level")?.unwrap_or(1.0)) {
+        return Ok(vec![Box::new(Bsim::parse(line, model, version)?)]);
+    }
+    Ok(vec![Box::new(Mosfet::parse(line, model)?)])
+}
+
+/// Parameters of an `NMOS` or `PMOS` model of level 1, 2 or 3. Process
+/// parameters such as `NSUB` and `TOX` give the electrical ones not given
+/// themselves, and SPICE's centimetre units are kept on the card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MosModel {
+    pub level: u8,
+    /// 1 for NMOS, -1 for PMOS.
+    pub polarity: f64,
+    pub vto: Option<f64>,
+    pub kp: Option<f64>,
+    pub gamma: Option<f64>,
+    pub phi: Option<f64>,
+    pub lambda: Option<f64>,
+    pub rd: f64,
+    pub rs: f64,
+    pub rsh: f64,
+    pub cbd: Option<f64>,
+    pub cbs: Option<f64>,
+    pub is: f64,
+    pub js: f64,
+    pub pb: f64,
+    pub cj: f64,
+    pub mj: f64,
+    pub cjsw: f64,
+    pub mjsw: f64,
+    pub fc: f64,
+    pub cgso: f64,
+    pub cgdo: f64,
+    pub cgbo: f64,
+    pub tox: Option<f64>,
+    /// Substrate doping in cm⁻³.
+    pub nsub: Option<f64>,
+    /// Surface state density in cm⁻².
+    pub nss: f64,
+    /// Fast surface state density in cm⁻², giving weak inversion.
+    pub nfs: f64,
+    /// Gate material: 1 opposite to the substrate, -1 the same, 0 aluminium.
+    pub tpg: f64,
+    pub xj: f64,
+    pub ld: f64,
+    /// Surface mobility in cm²/Vs.
+    pub uo: f64,
+    /// Critical field of mobility degradation in V/cm.
+    pub ucrit: f64,
+    pub uexp: f64,
+    pub utra: f64,
+    /// Maximum carrier drift velocity in m/s, none when zero.
+    pub vmax: f64,
+    pub neff: f64,
+    pub delta: f64,
+    pub theta: f64,
+    pub eta: f64,
+    pub kappa: f64,
+    pub kf: f64,
+    pub af: f64,
+    /// Temperature the parameters were measured at in °C, `.options tnom`
+    /// unless given.
+    pub tnom: Option<f64>,
+}
+
+impl MosModel {
+    /// The parameters of an `NMOS` or `PMOS` model card.
+    pub fn new(model: &Model) -> Result<Self, String> {
+        let level = model.number("level")?.unwrap_or(1.0);
+        if ![1.0, 2.0, 3.0].contains(&level) {
+            return Err(format!(
+                "Model {}: MOS level {} is not supported",
+                model.name, level
+            ));
+        }
+        let level = level as u8;
+        let mut known = vec![
+            "level", "vto", "vt0", "kp", "gamma", "phi", "lambda", "rd", "rs", "rsh", "cbd", "cbs",
+            "is", "js", "pb", "cj", "mj", "cjsw", "mjsw", "fc", "cgso", "cgdo", "cgbo", "tox",
+            "nsub", "nss", "tpg", "ld", "uo", "u0", "kf", "af", "tnom", "tref",
+        ];
+        match level {
+            2 => known.extend([
+                "nfs", "xj", "ucrit", "uexp", "utra", "vmax", "neff", "delta",
+            ]),
+            3 => known.extend(["nfs", "xj", "vmax", "delta", "theta", "eta", "kappa"]),
+            _ => {}
+        }
+        model.unknown_parameters(&known)?;
+        let value = |names: &[&str], default: f64| -> Result<f64, String> {
+            Ok(model.alias(names)?.unwrap_or(default))
+        };
+        let tox = model.number("tox")?.filter(|tox| *tox > 0.0);
+        let mos = MosModel {
+            level,
+            polarity: if model.kind == "PMOS" { -1.0 } else { 1.0 },
+            vto: model.alias(&["vto", "vt0"])?,
+            kp: model.number("kp")?,
+            gamma: model.number("gamma")?,
+            phi: model.number("phi")?,
+            lambda: model.number("lambda")?,
+            rd: value(&["rd"], 0.0)?,
+            rs: value(&["rs"], 0.0)?,
+            rsh: value(&["rsh"], 0.0)?,
+            cbd: model.number("cbd")?,
+            cbs: model.number("cbs")?,
+            is: value(&["is"], 1e-14)?,
+            js: value(&["js"], 0.0)?,
+            pb: value(&["pb"], 0.8)?,
+            cj: value(&["cj"], 0.0)?,
+            mj: value(&["mj"], 0.5)?,
+            cjsw: value(&["cjsw"], 0.0)?,
+            mjsw: value(&["mjsw"], if level == 1 { 0.5 } else { 0.33 })?,
+            fc: value(&["fc"], 0.5)?,
+            cgso: value(&["cgso"], 0.0)?,
+            cgdo: value(&["cgdo"], 0.0)?,
+            cgbo: value(&["cgbo"], 0.0)?,
+            tox: if level == 1 { tox } else { tox.or(Some(1e-7)) },
+            nsub: model.number("nsub")?.filter(|nsub| *nsub > 0.0),
+            nss: value(&["nss"], 0.0)?,
+            nfs: value(&["nfs"], 0.0)?,
+            tpg: value(&["tpg"], 1.0)?,
+            xj: value(&["xj"], 0.0)?,
+            ld: value(&["ld"], 0.0)?,
+            uo: value(&["uo", "u0"], 600.0)?,
+            ucrit: value(&["ucrit"], 1e4)?,
+            uexp: value(&["uexp"], 0.0)?,
+            utra: value(&["utra"], 0.0)?,
+            vmax: value(&["vmax"], 0.0)?,
+            neff: value(&["neff"], 1.0)?,
+            delta: value(&["delta"], 0.0)?,
+            theta: value(&["theta"], 0.0)?,
+            eta: value(&["eta"], 0.0)?,
+            kappa: value(&["kappa"], 0.2)?,
+            kf: value(&["kf"], 0.0)?,
+            af: value(&["af"], 1.0)?,
+            tnom: model.alias(&["tnom", "tref"])?,
+        };
+        if mos.pb <= 0.0 || mos.mj >= 1.0 || mos.mjsw >= 1.0 || mos.fc >= 1.0 {
+            return Err(format!(
+                "Model {}: PB must be positive and MJ, MJSW and FC below 1",
+                model.name
+            ));
+        }
+        if mos.phi.is_some_and(|phi| phi <= 0.0) {
+            return Err(format!("Model {}: PHI must be positive", model.name));
+        }
+        Ok(mos)
+    }
+}
+
+/// The model's parameters at the instance temperature, for its size and
+/// multiplier. Threshold voltages are as for an n channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scaled {
+    temperature: f64,
+    vt: f64,
+    /// The threshold without the body effect, `VTO - GAMMA √PHI`.
+    vbi: f64,
+    phi: f64,
+    gamma: f64,
+    /// Oxide capacitance per area, zero for a level 1 model without `TOX`.
+    cox: f64,
+    /// `KP W / Leff`.
+    beta: f64,
+    /// Surface mobility in m²/Vs.
+    uo: f64,
+    /// Depletion width per √V, zero without `NSUB`.
+    xd: f64,
+    width: f64,
+    length: f64,
+    /// Conductances of the drain and source resistances, zero without.
+    gd: f64,
+    gs: f64,
+    drain_is: f64,
+    source_is: f64,
+    drain_vcrit: f64,
+    source_vcrit: f64,
+    pb: f64,
+    /// Zero bias bottom and sidewall capacitances of the drain and source
+    /// junctions.
+    cbd: (f64, f64),
+    cbs: (f64, f64),
+    /// Overlap capacitances from the gate to source, drain and bulk.
+    overlap: [f64; 3],
+}
+
+/// The linearisation at a bias point, as for an n channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bias {
+    vgs: f64,
+    vds: f64,
+    vbs: f64,
+    von: f64,
+    vdsat: f64,
+    /// Channel current from drain to source and its derivatives with
+    /// `vgs`, `vds` and `vbs`.
+    ids: f64,
+    gm: f64,
+    gds: f64,
+    gmbs: f64,
+    /// Bulk to drain and bulk to source junctions, gmin included.
+    cbd: f64,
+    gbd: f64,
+    cbs: f64,
+    gbs: f64,
+    qbd: f64,
+    capbd: f64,
+    qbs: f64,
+    capbs: f64,
+    /// Half of Meyer's gate to source, drain and bulk capacitances.
+    meyer: [f64; 3],
+}
+
+impl Bias {
+    fn vgd(&self) -> f64 {
+        self.vgs - self.vds
+    }
+
+    fn vbd(&self) -> f64 {
+        self.vbs - self.vds
+    }
+
+    /// Gate to source, drain and bulk voltages.
+    fn gate(&self) -> [f64; 3] {
+        [self.vgs, self.vgd(), self.vgs - self.vbs]
+    }
+}
+
+/// Meyer's gate capacitances, halved as ngspice's `DEVqmeyer` keeps them,
+/// from the gate to the source, drain and bulk of a channel with
+/// threshold `von` saturating at `vdsat`. `cox` is the whole gate's.
+pub(crate) fn meyer(vgs: f64, vgd: f64, von: f64, vdsat: f64, phi: f64, cox: f64) -> [f64; 3] {
+    let vgst = vgs - von;
+    if vgst <= -phi {
+        [0.0, 0.0, cox / 2.0]
+    } else if vgst <= -phi / 2.0 {
+        [0.0, 0.0, -vgst * cox / (2.0 * phi)]
+    } else if vgst <= 0.0 {
+        [
+            vgst * cox / (1.5 * phi) + cox / 3.0,
+            0.0,
+            -vgst * cox / (2.0 * phi),
+        ]
+    } else {
+        let vds = vgs - vgd;
+        if vdsat <= vds {
+            [cox / 3.0, 0.0, 0.0]
+        } else {
+            let vddif = 2.0 * vdsat - vds;
+            let vddif1 = vdsat - vds;
+            let vddif2 = vddif * vddif;
+            [
+                cox * (1.0 - vddif1 * vddif1 / vddif2) / 3.0,
+                cox * (1.0 - vdsat * vdsat / vddif2) / 3.0,
+                0.0,
+            ]
+        }
+    }
+}
+
+/// `√(PHI - vbs)`, carried on as SPICE does where the bulk junction is
+/// forward biased.
+fn depletion_root<T: Real>(phi: f64, vbs: T) -> T {
+    if vbs.value() <= 0.0 {
+        (-vbs + phi).sqrt()
+    } else {
+        let root = phi.sqrt();
+        T::constant(root) / (vbs / (2.0 * phi) + 1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mosfet {
+    name: String,
+    /// Drain, gate, source and bulk.
+    nodes: [Node; 4],
+    model: MosModel,
+    length: f64,
+    width: f64,
+    /// Drain and source areas and perimeters.
+    ad: f64,
+    r#as: f64,
+    pd: f64,
+    ps: f64,
+    /// Squares of `RSH` in the drain and source.
+    nrd: f64,
+    nrs: f64,
+    multiplier: f64,
+    /// `vds`, `vgs` and `vbs` to start from with `uic`.
+    initial: Option<[f64; 3]>,
+    temp: Option<f64>,
+    dtemp: f64,
+    scaled: Scaled,
+    /// Drain, gate, source, bulk, and the drain and source inside `RD`
+    /// and `RS`.
+    unknowns: [Unknown; 6],
+    /// Drain and source resistances, the channel's conductance and
+    /// transconductances, the bulk junctions and the gate capacitances.
+    stamps: [Conductance; 10],
+    /// Gate to source, drain and bulk, bulk to drain and source.
+    charges: [State; 5],
+    bias: Bias,
+    /// The bias and gate charges at the last accepted point, where Meyer's
+    /// capacitances are integrated from.
+    accepted: (Bias, [f64; 3]),
+}
+
+impl Mosfet {
+    /// `Mname d g s b model [L=] [W=] [AD=] [AS=] [PD=] [PS=] [NRD=] [NRS=]
+    /// [off] [m=] [ic=vds,vgs,vbs] [temp=] [dtemp=]`.
+    pub fn parse(line: &ElementLine, model: &Model) -> Result<Self, String> {
+        line.unknown_parameters(&[
+            "l", "w", "ad", "as", "pd", "ps", "nrd", "nrs", "m", "ic", "temp", "dtemp",
+        ])?;
+        let [drain, gate, source, bulk] = line.nodes()?;
+        let model = MosModel::new(model)?;
+        let number = |name: &str, default: f64| -> Result<f64, String> {
+            Ok(line.number(name)?.unwrap_or(default))
+        };
+        let length = number("l", DEFAULT_SIZE)?;
+        let width = number("w", DEFAULT_SIZE)?;
+        if width <= 0.0 || length - 2.0 * model.ld <= 0.0 {
+            return Err("The width and effective length must be positive".to_string());
+        }
+        let multiplier = number("m", 1.0)?;
+        if multiplier <= 0.0 {
+            return Err("The multiplier must be positive".to_string());
+        }
+        let initial = match line.numbers("ic")?.as_slice() {
+            [] => None,
+            [vds] => Some([*vds, 0.0, 0.0]),
+            [vds, vgs] => Some([*vds, *vgs, 0.0]),
+            [vds, vgs, vbs] => Some([*vds, *vgs, *vbs]),
+            _ => return Err("Expected ic=vds,vgs,vbs".to_string()),
+        };
+        Ok(Mosfet {
+            name: line.name.clone(),
+            nodes: [drain, gate, source, bulk],
+            model,
+            length,
+            width,
+            ad: number("ad", 0.0)?,
+            r#as: number("as", 0.0)?,
+            pd: number("pd", 0.0)?,
+            ps: number("ps", 0.0)?,
+            nrd: number("nrd", 1.0)?,
+            nrs: number("nrs", 1.0)?,
+            multiplier,
+            initial,
+            temp: line.number("temp")?,
+            dtemp: number("dtemp", 0.0)?,
+            scaled: Scaled::default(),
+            unknowns: [None; 6],
+            stamps: [Conductance::default(); 10],
+            charges: Default::default(),
+            bias: Bias::default(),
+            accepted: (Bias::default(), [0.0; 3]),
+        })
+    }
+
+    /// Drain current, threshold and saturation voltage at the last load.
+    pub fn operating_point(&self) -> (f64, f64, f64) {
+        let p = self.model.polarity;
+        (p * self.bias.ids, p * self.bias.von, p * self.bias.vdsat)
+    }
+
+    /// `vgs`, `vds` and `vbs` of `solution`, as for an n channel.
+    fn voltages(&self, solution: &[f64]) -> [f64; 3] {
+        let [_, gate, _, bulk, drain, source] = self.unknowns;
+        let v = |node: Unknown| voltage(solution, node);
+        let p = self.model.polarity;
+        [
+            p * (v(gate) - v(source)),
+            p * (v(drain) - v(source)),
+            p * (v(bulk) - v(source)),
+        ]
+    }
+
+    /// The channel current from drain to source for `vds ≥ 0`, with the
+    /// threshold and saturation voltages.
+    fn channel<T: Real>(&self, vgs: T, vds: T, vbs: T) -> (T, f64, f64) {
+        match self.model.level {
+            1 => self.level1(vgs, vds, vbs),
+            2 => self.level2(vgs, vds, vbs),
+            _ => self.level3(vgs, vds, vbs),
+        }
+    }
+
+    fn level1<T: Real>(&self, vgs: T, vds: T, vbs: T) -> (T, f64, f64) {
+        let s = &self.scaled;
+        let zero = T::constant(0.0);
+        let sarg = if vbs.value() <= 0.0 {
+            (-vbs + s.phi).sqrt()
+        } else {
+            let root = s.phi.sqrt();
+            (T::constant(root) - vbs / (2.0 * root)).max(zero)
+        };
+        let von = sarg * s.gamma + s.vbi;
+        let vgst = vgs - von;
+        let vdsat = vgst.value().max(0.0);
+        if vgst.value() <= 0.0 {
+            return (zero, von.value(), vdsat);
+        }
+        let betap = (vds * self.model.lambda.unwrap_or(0.0) + 1.0) * s.beta;
+        let ids = if vgst.value() <= vds.value() {
+            betap * vgst * vgst * 0.5
+        } else {
+            betap * vds * (vgst - vds * 0.5)
+        };
+        (ids, von.value(), vdsat)
+    }
+
+    fn level2<T: Real>(&self, vgs: T, vds: T, vbs: T) -> (T, f64, f64) {
+        let m = &self.model;
+        let s = &self.scaled;
+        let phi = s.phi;
+        let sarg = depletion_root(phi, vbs);
+        let barg = depletion_root(phi, vbs - vds);
+
+        // The threshold, raised by a narrow channel and lowered where the
+        // source and drain depletion regions share the bulk charge.
+        let factor = 0.25 * m.delta * std::f64::consts::PI * EPSILON_SI / (s.cox * s.width);
+        let eta = 1.0 + factor;
+        let vbin = (-vbs + phi) * factor + s.vbi;
+        let mut gammad = T::constant(s.gamma);
+        if s.xd > 0.0 && m.xj > 0.0 {
+            let share = |root: T| {
+                let arg = (root * (2.0 * s.xd / m.xj) + 1.0).sqrt();
+                (arg - 1.0) * (0.5 * m.xj / s.length)
+            };
+            gammad = gammad * (T::constant(1.0) - share(sarg) - share(barg));
+        }
+        let vth = vbin + gammad * sarg;
+        let xn = gammad / (sarg * 2.0) + (1.0 + CHARGE * m.nfs * 1e4 / s.cox + factor);
+        let von = if m.nfs > 0.0 { vth + xn * s.vt } else { vth };
+        if m.nfs == 0.0 && vgs.value() <= von.value() {
+            return (T::constant(0.0), von.value(), 0.0);
+        }
+        let vgsx = vgs.max(von);
+
+        // Mobility, degraded by the transverse field.
+        let mut ueff = T::constant(s.uo);
+        let vgst = vgsx - vth - vds * m.utra;
+        if m.uexp != 0.0 && m.ucrit > 0.0 && vgst.value() > 0.0 {
+            let ufact =
+                (T::constant(m.ucrit * 1e2 * EPSILON_SI / s.cox) / vgst).powf(T::constant(m.uexp));
+            if ufact.value() < 1.0 {
+                ueff = ufact * s.uo;
+            }
+        }
+        let beta = ueff * (s.cox * s.width / s.length);
+
+        // The bulk charge current, up to where the channel pinches off.
+        let linear = |v: T| {
+            let bulk = depletion_root(phi, vbs - v);
+            let charge = (bulk * bulk * bulk - sarg * sarg * sarg) * gammad * (2.0 / 3.0);
+            beta * ((vgsx - vbin - v * (eta / 2.0)) * v - charge)
+        };
+        let mut vdsat = (vgsx - vbin) / eta;
+        if gammad.value() > 0.0 {
+            let g2 = gammad * gammad / (2.0 * eta * eta);
+            let arg = ((vdsat - vbs + phi) * (2.0 * eta) / g2 / (eta * eta) + 1.0).sqrt();
+            vdsat = vdsat + g2 * (T::constant(1.0) - arg);
+        }
+        vdsat = vdsat.max(T::constant(0.0));
+        if m.vmax > 0.0 {
+            // Where the carriers at the drain end reach VMAX.
+            let excess = |v: T| {
+                let bulk = depletion_root(phi, vbs - v);
+                let charge = vgsx - vbin - v * eta - gammad * bulk;
+                linear(v) - charge * (s.width * m.neff * s.cox * m.vmax)
+            };
+            vdsat = solve(excess, T::constant(0.0), vdsat);
+        }
+
+        // Beyond pinch-off the depletion region at the drain shortens the
+        // channel.
+        let ids = if vds.value() <= vdsat.value() {
+            linear(vds)
+        } else {
+            let saturated = linear(vdsat);
+            match m.lambda {
+                Some(_) => saturated,
+                None if s.xd > 0.0 => {
+                    let excess = vds - vdsat;
+                    let delta = if m.vmax > 0.0 {
+                        let arg = ueff.powi(-1) * (s.xd * s.xd * m.vmax / 2.0);
+                        (arg * arg + excess * (s.xd * s.xd)).sqrt() - arg
+                    } else {
+                        let quarter = excess / 4.0;
+                        ((quarter * quarter + 1.0).sqrt() + quarter).sqrt() * s.xd - s.xd
+                    };
+                    saturated / (T::constant(1.0) - shortening(delta, s.length) / s.length)
+                }
+                None => saturated,
+            }
+        };
+        let ids = match m.lambda {
+            Some(lambda) => ids / (T::constant(1.0) - vds * lambda),
+            None => ids,
+        };
+        let ids = weak_inversion(ids, vgs, von, xn, s.vt, m.nfs);
+        (ids, von.value(), vdsat.value())
+    }
+
+    fn level3<T: Real>(&self, vgs: T, vds: T, vbs: T) -> (T, f64, f64) {
+        let m = &self.model;
+        let s = &self.scaled;
+        let one = T::constant(1.0);
+        let sqphbs = depletion_root(s.phi, vbs);
+
+        // Short channel, narrow channel and barrier lowering corrections
+        // to the threshold.
+        let mut fshort = one;
+        if s.xd > 0.0 && m.xj > 0.0 {
+            let wp = sqphbs * s.xd;
+            let wponxj = wp / m.xj;
+            let wc = (wponxj * 0.8013292 - wponxj * wponxj * 0.01110777 + 0.0631353) * m.xj;
+            let ratio = wp / (wp + m.xj);
+            let argb = (one - ratio * ratio).sqrt();
+            fshort = one - ((wc + m.ld) * argb - m.ld) / s.length;
+        }
+        let fnarrow = m.delta * std::f64::consts::PI * EPSILON_SI / (2.0 * s.cox * s.width);
+        let sigma = m.eta * 8.15e-22 / (s.cox * s.length.powi(3));
+        let gammas = fshort * s.gamma;
+        let vth = gammas * sqphbs + sqphbs * sqphbs * fnarrow - vds * sigma + s.vbi;
+        let fbody = gammas / (sqphbs * 4.0) + fnarrow;
+        let onfg = fbody + 1.0;
+        let xn = gammas / (sqphbs * 2.0) + (1.0 + CHARGE * m.nfs * 1e4 / s.cox + fnarrow / 2.0);
+        let von = if m.nfs > 0.0 { vth + xn * s.vt } else { vth };
+        if m.nfs == 0.0 && vgs.value() <= von.value() {
+            return (T::constant(0.0), von.value(), 0.0);
+        }
+        let vgsx = vgs.max(von);
+
+        // Mobility modulated by the gate field, and velocity saturation.
+        let us = (one + (vgsx - vth) * m.theta).powi(-1) * s.uo;
+        let beta = us * (s.cox * s.width / s.length);
+        let vsat = (vgsx - vth) / onfg;
+        let (vdsat, vc) = if m.vmax > 0.0 {
+            let vc = us.powi(-1) * (m.vmax * s.length);
+            (vsat + vc - (vsat * vsat + vc * vc).sqrt(), Some(vc))
+        } else {
+            (vsat, None)
+        };
+        let linear = |v: T| {
+            let current = beta * (vgsx - vth - onfg * v * 0.5) * v;
+            match vc {
+                Some(vc) => current / (v / vc + 1.0),
+                None => current,
+            }
+        };
+        let ids = if vds.value() <= vdsat.value() {
+            linear(vds)
+        } else {
+            let saturated = linear(vdsat);
+            let excess = vds - vdsat;
+            let delta = match vc {
+                None if s.xd > 0.0 => (excess * (m.kappa * s.xd * s.xd)).sqrt(),
+                None => T::constant(0.0),
+                Some(vc) => {
+                    // The lateral field at pinch-off, from the slope of
+                    // the linear current there.
+                    let a = vgsx - vth;
+                    let denom = vdsat / vc + 1.0;
+                    let slope = ((a - onfg * vdsat) * denom
+                        - (a - onfg * vdsat * 0.5) * vdsat / vc)
+                        / (denom * denom);
+                    let field = (a - onfg * vdsat * 0.5) * vdsat / (slope * s.length);
+                    let arg = field * (s.xd * s.xd / 2.0);
+                    (arg * arg + excess * (m.kappa * s.xd * s.xd)).sqrt() - arg
+                }
+            };
+            saturated / (one - shortening(delta, s.length) / s.length)
+        };
+        let ids = weak_inversion(ids, vgs, von, xn, s.vt, m.nfs);
+        (ids, von.value(), vdsat.value())
+    }
+
+    /// Evaluate the device at `[vgs, vds, vbs]` with `gmin` across the
+    /// junctions, swapping drain and source where `vds` is negative.
+    fn evaluate(&self, voltages: [f64; 3], gmin: f64) -> Bias {
+        let s = &self.scaled;
+        let m = &self.model;
+        let [vgs, vds, vbs] = voltages;
+        let x: [Dual<3>; 3] = std::array::from_fn(|i| Dual::variable(voltages[i], i));
+        let (ids, von, vdsat) = if vds >= 0.0 {
+            self.channel(x[0], x[1], x[2])
+        } else {
+            let (ids, von, vdsat) = self.channel(x[0] - x[1], -x[1], x[2] - x[1]);
+            (-ids, von, vdsat)
+        };
+
+        let junction = |v: f64, is: f64| {
+            if v <= -3.0 * s.vt {
+                (gmin * v - is, gmin)
+            } else {
+                let ev = (v / s.vt).exp();
+                (is * (ev - 1.0) + gmin * v, is * ev / s.vt + gmin)
+            }
+        };
+        let vbd = vbs - vds;
+        let (cbd, gbd) = junction(vbd, s.drain_is);
+        let (cbs, gbs) = junction(vbs, s.source_is);
+        let bulk = |v: f64, (bottom, side): (f64, f64)| {
+            let (q1, c1) = depletion(v, bottom, s.pb, m.mj, m.fc);
+            let (q2, c2) = depletion(v, side, s.pb, m.mjsw, m.fc);
+            (q1 + q2, c1 + c2)
+        };
+        let (qbd, capbd) = bulk(vbd, s.cbd);
+        let (qbs, capbs) = bulk(vbs, s.cbs);
+
+        let cox = s.cox * s.width * s.length;
+        let meyer = if vds >= 0.0 {
+            meyer(vgs, vgs - vds, von, vdsat, s.phi, cox)
+        } else {
+            let [gd, gs, gb] = meyer(vgs - vds, vgs, von, vdsat, s.phi, cox);
+            [gs, gd, gb]
+        };
+        Bias {
+            vgs,
+            vds,
+            vbs,
+            von,
+            vdsat,
+            ids: ids.value,
+            gm: ids.derivative(0),
+            gds: ids.derivative(1),
+            gmbs: ids.derivative(2),
+            cbd,
+            gbd,
+            cbs,
+            gbs,
+            qbd,
+            capbd,
+            qbs,
+            capbs,
+            meyer,
+        }
+    }
+
+    /// Gate capacitances, Meyer's averaged over the step and the overlaps,
+    /// and the gate charges they give at `bias`.
+    fn gate_charges(&self, bias: &Bias) -> ([f64; 3], [f64; 3]) {
+        let (old, charges) = &self.accepted;
+        let gate = bias.gate();
+        let old_gate = old.gate();
+        let overlap = self.scaled.overlap;
+        let capacitances: [f64; 3] =
+            std::array::from_fn(|i| bias.meyer[i] + old.meyer[i] + overlap[i]);
+        let values =
+            std::array::from_fn(|i| charges[i] + capacitances[i] * (gate[i] - old_gate[i]));
+        (capacitances, values)
+    }
+}
+
+/// The channel shortening `delta`, kept below the whole length as SPICE
+/// does to stop the current diverging.
+fn shortening<T: Real>(delta: T, length: f64) -> T {
+    if delta.value() > 0.5 * length {
+        T::constant(length) - T::constant(length * length / 4.0) / delta
+    } else {
+        delta
+    }
+}
+
+/// The current below the threshold `von`, falling exponentially from its
+/// value there with slope `xn vt`, where the model has fast surface states.
+fn weak_inversion<T: Real>(ids: T, vgs: T, von: T, xn: T, vt: f64, nfs: f64) -> T {
+    if nfs > 0.0 && vgs.value() < von.value() {
+        ids * ((vgs - von) / (xn * vt)).exp()
+    } else {
+        ids
+    }
+}
+
+/// The root of `f`, increasing from below zero at `low` to above at
+/// `high`: bisection to close in, then Newton steps that carry the
+/// derivatives of a [`Dual`] root.
+fn solve<T: Real>(f: impl Fn(T) -> T, low: T, high: T) -> T {
+    let (mut a, mut b) = (low.value(), high.value());
+    let value = |v: f64| f(T::constant(v)).value();
+    if value(b) <= 0.0 || value(a) >= 0.0 {
+        return high;
+    }
+    for _ in 0..60 {
+        let middle = 0.5 * (a + b);
+        if value(middle) > 0.0 {
+            b = middle;
+        } else {
+            a = middle;
+        }
+        if b - a < 1e-12 * b.abs().max(1.0) {
+            break;
+        }
+    }
+    let mut x = T::constant(0.5 * (a + b));
+    for _ in 0..2 {
+        let h = 1e-7 * x.value().abs().max(1e-3);
+        let slope = (value(x.value() + h) - value(x.value() - h)) / (2.0 * h);
+        x = x - f(x) / slope;
+    }
+    x
+}
+
+impl Device for Mosfet {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let [drain, gate, source, bulk] = self.nodes.each_ref().map(|n| unknowns.node(n));
+        let m = &self.model;
+        let inner = if m.rd > 0.0 || m.rsh * self.nrd > 0.0 {
+            Some(unknowns.internal(&self.name, "d"))
+        } else {
+            drain
+        };
+        let source_inner = if m.rs > 0.0 || m.rsh * self.nrs > 0.0 {
+            Some(unknowns.internal(&self.name, "s"))
+        } else {
+            source
+        };
+        self.unknowns = [drain, gate, source, bulk, inner, source_inner];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [drain, gate, source, bulk, d, s] = self.unknowns;
+        self.stamps = [
+            Conductance::new(pattern, drain, d),
+            Conductance::new(pattern, source, s),
+            Conductance::new(pattern, d, s),
+            Conductance::controlled(pattern, d, s, gate, s),
+            Conductance::controlled(pattern, d, s, bulk, s),
+            Conductance::new(pattern, bulk, d),
+            Conductance::new(pattern, bulk, s),
+            Conductance::new(pattern, gate, s),
+            Conductance::new(pattern, gate, d),
+            Conductance::new(pattern, gate, bulk),
+        ];
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let m = &self.model;
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let t = celsius + KELVIN;
+        let tnom = m.tnom.unwrap_or(nominal) + KELVIN;
+        let vt = BOLTZMANN * t / CHARGE;
+        let vtnom = BOLTZMANN * tnom / CHARGE;
+        let scale = self.multiplier;
+
+        // Electrical parameters from the process ones, at tnom.
+        let egfet1 = energy_gap(tnom);
+        let cox = m.tox.map_or(0.0, |tox| EPSILON_OX / tox);
+        let kp =
+            m.kp.unwrap_or(if cox > 0.0 { m.uo * 1e-4 * cox } else { 2e-5 });
+        let phi = m.phi.unwrap_or(match m.nsub {
+            Some(nsub) if nsub > INTRINSIC_DENSITY => 2.0 * vtnom * (nsub / INTRINSIC_DENSITY).ln(),
+            Some(_) => 0.1,
+            None => 0.6,
+        });
+        let gamma = m.gamma.unwrap_or(match m.nsub {
+            Some(nsub) if cox > 0.0 => (2.0 * EPSILON_SI * CHARGE * nsub * 1e6).sqrt() / cox,
+            _ => 0.0,
+        });
+        let vto = m.vto.unwrap_or(match m.nsub {
+            Some(_) if cox > 0.0 => {
+                let fermis = m.polarity * 0.5 * phi;
+                let wkfng = if m.tpg != 0.0 {
+                    3.25 + 0.5 * egfet1 - m.polarity * m.tpg * 0.5 * egfet1
+                } else {
+                    3.2
+                };
+                let vfb = wkfng - (3.25 + 0.5 * egfet1 + fermis) - m.nss * 1e4 * CHARGE / cox;
+                vfb + m.polarity * (gamma * phi.sqrt() + phi)
+            }
+            _ => 0.0,
+        });
+        let xd = m.nsub.map_or(0.0, |nsub| {
+            (2.0 * EPSILON_SI / (CHARGE * nsub * 1e6)).sqrt()
+        });
+
+        // Their values at t, as in ngspice's mos1temp.c.
+        let ratio = t / tnom;
+        let ratio4 = ratio * ratio.sqrt();
+        let egfet = energy_gap(t);
+        let pbfact = |t: f64, vt: f64| {
+            let arg = -energy_gap(t) / (2.0 * BOLTZMANN * t)
+                + 1.1150877 / (BOLTZMANN * 2.0 * REFERENCE_TEMPERATURE);
+            -2.0 * vt * (1.5 * (t / REFERENCE_TEMPERATURE).ln() + CHARGE * arg)
+        };
+        let phio = (phi - pbfact(tnom, vtnom)) / (tnom / REFERENCE_TEMPERATURE);
+        let tphi = t / REFERENCE_TEMPERATURE * phio + pbfact(t, vt);
+        let tvbi = vto - m.polarity * gamma * phi.sqrt()
+            + 0.5 * (egfet1 - egfet)
+            + m.polarity * 0.5 * (tphi - phi);
+        let saturation = (-egfet / vt + egfet1 / vtnom).exp();
+        let length = self.length - 2.0 * m.ld;
+        let width = self.width;
+        let (pb, _) = junction_temperature(m.pb, 0.0, m.mj, t, tnom);
+        let capacitance = |cj: f64, mj: f64| junction_temperature(m.pb, cj * scale, mj, t, tnom).1;
+        let junction = |given: Option<f64>, area: f64, perimeter: f64| match given {
+            Some(c) => (
+                capacitance(c, m.mj),
+                capacitance(m.cjsw, m.mjsw) * perimeter,
+            ),
+            None => (
+                capacitance(m.cj, m.mj) * area,
+                capacitance(m.cjsw, m.mjsw) * perimeter,
+            ),
+        };
+        let saturation_current = |area: f64| {
+            let is = if m.js > 0.0 && area > 0.0 {
+                m.js * area
+            } else {
+                m.is
+            };
+            is * saturation * scale
+        };
+        let resistance = |r: f64, squares: f64| {
+            let r = if r > 0.0 { r } else { m.rsh * squares };
+            if r > 0.0 {
+                scale / r
+            } else {
+                0.0
+            }
+        };
+        let drain_is = saturation_current(self.ad);
+        let source_is = saturation_current(self.r#as);
+        self.scaled = Scaled {
+            temperature: celsius,
+            vt,
+            vbi: m.polarity * tvbi,
+            phi: tphi,
+            gamma,
+            cox,
+            beta: kp / ratio4 * width / length * scale,
+            uo: m.uo * 1e-4 / ratio4,
+            xd,
+            width: width * scale,
+            length,
+            gd: resistance(m.rd, self.nrd),
+            gs: resistance(m.rs, self.nrs),
+            drain_is,
+            source_is,
+            drain_vcrit: critical_voltage(vt, drain_is),
+            source_vcrit: critical_voltage(vt, source_is),
+            pb,
+            cbd: junction(m.cbd, self.ad, self.pd),
+            cbs: junction(m.cbs, self.r#as, self.ps),
+            overlap: [
+                m.cgso * width * scale,
+                m.cgdo * width * scale,
+                m.cgbo * length * scale,
+            ],
+        };
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let [vgs, vds, vbs] = self.voltages(context.solution);
+        let old = self.bias;
+        let s = &self.scaled;
+
+        // Limit the new voltages as ngspice's mos1load.c does.
+        let (vgs, vds) = if old.vds >= 0.0 {
+            let vgs = fetlim(vgs, old.vgs, old.von);
+            (vgs, limvds(vds, old.vds))
+        } else {
+            let vgd = fetlim(vgs - vds, old.vgd(), old.von);
+            let vds = -limvds(-vds, -old.vds);
+            (vgd + vds, vds)
+        };
+        let (vbs, limited) = if vds >= 0.0 {
+            pnjlim(vbs, old.vbs, s.vt, s.source_vcrit)
+        } else {
+            let (vbd, limited) = pnjlim(vbs - vds, old.vbd(), s.vt, s.drain_vcrit);
+            (vbd + vds, limited)
+        };
+        let [new_vgs, new_vds, _] = self.voltages(context.solution);
+        if limited || vgs != new_vgs || vds != new_vds {
+            context.limit(&self.name);
+        }
+
+        let bias = self.evaluate([vgs, vds, vbs], context.gmin);
+        self.bias = bias;
+        let (mut gbd, mut gbs) = (bias.gbd, bias.gbs);
+        let (mut cbd, mut cbs) = (bias.cbd, bias.cbs);
+        let mut gate = [0.0; 3];
+        let mut gate_currents = [0.0; 3];
+        let (capacitances, charges) = self.gate_charges(&bias);
+        let vgate = bias.gate();
+        let [qgs, qgd, qgb, qbd, qbs] = &mut self.charges;
+        match context.integrator {
+            Some(integrator) => {
+                let companion = qbd.integrate(integrator, bias.qbd, bias.capbd, bias.vbd());
+                gbd += companion.geq;
+                cbd += qbd.derivative();
+                let companion = qbs.integrate(integrator, bias.qbs, bias.capbs, vbs);
+                gbs += companion.geq;
+                cbs += qbs.derivative();
+                for (i, charge) in [qgs, qgd, qgb].into_iter().enumerate() {
+                    let companion =
+                        charge.integrate(integrator, charges[i], capacitances[i], vgate[i]);
+                    gate[i] = companion.geq;
+                    gate_currents[i] = companion.ceq;
+                }
+            }
+            None => {
+                qbd.reset(bias.qbd);
+                qbs.reset(bias.qbs);
+                let overlap = self.scaled.overlap;
+                for (i, charge) in [qgs, qgd, qgb].into_iter().enumerate() {
+                    charge.reset((2.0 * bias.meyer[i] + overlap[i]) * vgate[i]);
+                }
+            }
+        }
+
+        let st = &self.stamps;
+        st[0].stamp(matrix, self.scaled.gd);
+        st[1].stamp(matrix, self.scaled.gs);
+        st[2].stamp(matrix, bias.gds);
+        st[3].stamp(matrix, bias.gm);
+        st[4].stamp(matrix, bias.gmbs);
+        st[5].stamp(matrix, gbd);
+        st[6].stamp(matrix, gbs);
+        for (i, g) in gate.iter().enumerate() {
+            st[7 + i].stamp(matrix, *g);
+        }
+
+        let p = self.model.polarity;
+        let [_, g, _, b, d, s] = self.unknowns;
+        let ieq = bias.ids - bias.gm * vgs - bias.gds * vds - bias.gmbs * vbs;
+        current(rhs, d, s, p * ieq);
+        current(rhs, b, d, p * (cbd - gbd * bias.vbd()));
+        current(rhs, b, s, p * (cbs - gbs * vbs));
+        current(rhs, g, s, p * gate_currents[0]);
+        current(rhs, g, d, p * gate_currents[1]);
+        current(rhs, g, b, p * gate_currents[2]);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let b = &self.bias;
+        let st = &self.stamps;
+        let overlap = self.scaled.overlap;
+        st[0].stamp(matrix, Complex::from(self.scaled.gd));
+        st[1].stamp(matrix, Complex::from(self.scaled.gs));
+        st[2].stamp(matrix, Complex::from(b.gds));
+        st[3].stamp(matrix, Complex::from(b.gm));
+        st[4].stamp(matrix, Complex::from(b.gmbs));
+        st[5].stamp(matrix, Complex::new(b.gbd, omega * b.capbd));
+        st[6].stamp(matrix, Complex::new(b.gbs, omega * b.capbs));
+        for i in 0..3 {
+            let capacitance = 2.0 * b.meyer[i] + overlap[i];
+            st[7 + i].stamp(matrix, Complex::new(0.0, omega * capacitance));
+        }
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let [drain, _, source, _, d, s] = self.unknowns;
+        let sc = &self.scaled;
+        let m = &self.model;
+        let mut sources = Vec::new();
+        for (name, outer, inner, g) in [("rd", drain, d, sc.gd), ("rs", source, s, sc.gs)] {
+            if outer != inner {
+                sources.push(NoiseSource {
+                    name: format!("{} {}", self.name, name),
+                    positive: outer,
+                    negative: inner,
+                    density: thermal_noise(g, sc.temperature),
+                });
+            }
+        }
+        sources.push(NoiseSource {
+            name: format!("{} channel", self.name),
+            positive: d,
+            negative: s,
+            density: thermal_noise(2.0 / 3.0 * self.bias.gm, sc.temperature),
+        });
+        if m.kf > 0.0 && sc.cox > 0.0 {
+            let kf = m.kf / (sc.cox * sc.length * sc.length);
+            sources.push(NoiseSource {
+                name: format!("{} flicker", self.name),
+                positive: d,
+                negative: s,
+                density: flicker_noise(kf, m.af, 1.0, self.bias.ids, frequency),
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some([vds, vgs, vbs]) = self.initial {
+            let p = self.model.polarity;
+            self.bias = self.evaluate([p * vgs, p * vds, p * vbs], 0.0);
+            let bias = self.bias;
+            let overlap = self.scaled.overlap;
+            let gate = bias.gate();
+            for i in 0..3 {
+                self.charges[i].reset((2.0 * bias.meyer[i] + overlap[i]) * gate[i]);
+            }
+            self.charges[3].reset(bias.qbd);
+            self.charges[4].reset(bias.qbs);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        for charge in &mut self.charges {
+            charge.accept();
+        }
+        let charges = [0, 1, 2].map(|i| self.charges[i].value());
+        self.accepted = (self.bias, charges);
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charges
+            .iter()
+            .map(|charge| charge.allowed_step(timestep, integrator, h, Quantity::Charge))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The drain and bulk currents predicted by the linearisation must
+    /// agree with those at the new voltages, as in ngspice's
+    /// `MOS1convTest`.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let b = &self.bias;
+        let [vgs, vds, vbs] = self.voltages(solution);
+        let (delvgs, delvds, delvbs) = (vgs - b.vgs, vds - b.vds, vbs - b.vbs);
+        let delvbd = delvbs - delvds;
+        let cd = b.ids - b.cbd;
+        let cdhat = cd + b.gm * delvgs + b.gds * delvds + b.gmbs * delvbs - b.gbd * delvbd;
+        let cbhat = b.cbs + b.cbd + b.gbd * delvbd + b.gbs * delvbs;
+        let far = |hat: f64, value: f64| {
+            (hat - value).abs() > options.reltol * hat.abs().max(value.abs()) + options.abstol
+        };
+        far(cdhat, cd) || far(cbhat, b.cbs + b.cbd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+    use crate::frontend::parser::netlist::Component;
+
+    /// The drain current into `V2` of a transistor biased by `V1` at the
+    /// gate and `V2` at the drain, and the same through a finite
+    /// difference of the solution against AC's small signal.
+    fn drain_current(model: &str, vgs: f64, vds: f64) -> f64 {
+        let lines = [
+            format!("V1 g 0 {}", vgs),
+            format!("V2 d 0 {}", vds),
+            "V3 b 0 -1".to_string(),
+            "M1 d g 0 b MOD L=2u W=10u".to_string(),
+            model.to_string(),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (circuit, solution) = operating_point(&lines);
+        -solution[circuit.unknowns().find("I(V2)").unwrap()]
+    }
+
+    #[test]
+    fn test_level1() {
+        let model = ".model MOD NMOS(VTO=0.7 KP=100u GAMMA=0.5 PHI=0.6 LAMBDA=0.02)";
+        let beta = 100e-6 * 10.0 / 2.0;
+        let vth = 0.7 + 0.5 * ((0.6f64 + 1.0).sqrt() - 0.6f64.sqrt());
+        // Saturated, then in the triode region, but for the drain
+        // junction's leakage.
+        let expected = beta / 2.0 * (2.0 - vth).powi(2) * (1.0 + 0.02 * 3.0);
+        assert!((drain_current(model, 2.0, 3.0) / expected - 1.0).abs() < 1e-7);
+        let expected = beta * 0.1 * (2.0 - vth - 0.05) * (1.0 + 0.02 * 0.1);
+        let id = drain_current(model, 2.0, 0.1);
+        assert!((id / expected - 1.0).abs() < 1e-6, "{} {}", id, expected);
+        // Off below threshold, but for the junction's leakage and gmin.
+        assert!(drain_current(model, 0.5, 3.0).abs() < 1e-10);
+
+        // A p channel mirrors it.
+        let (circuit, solution) = operating_point(&[
+            "V1 g 0 -2",
+            "V2 d 0 -3",
+            "M1 d g 0 0 MOD L=2u W=10u",
+            ".model MOD PMOS(VTO=-0.7 KP=100u LAMBDA=0.02)",
+        ]);
+        let expected = beta / 2.0 * 1.3f64.powi(2) * (1.0 + 0.02 * 3.0);
+        let id = solution[circuit.unknowns().find("I(V2)").unwrap()];
+        assert!((id / expected - 1.0).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_process_parameters() {
+        // VTO, KP, GAMMA and PHI from TOX, NSUB and UO.
+        let component = Component::new("M1", Vec::new(), "d g 0 0 MOD");
+        let model = Model::parse(".model MOD NMOS(TOX=20n NSUB=1e16 UO=500)").unwrap();
+        let mut mosfet = Mosfet::parse(&ElementLine::new(&component), &model).unwrap();
+        mosfet.temperature(27.0, 27.0);
+        let cox = EPSILON_OX / 20e-9;
+        assert!((mosfet.scaled.cox / cox - 1.0).abs() < 1e-12);
+        assert!((mosfet.scaled.beta / (500e-4 * cox) - 1.0).abs() < 1e-9);
+        let phi = 2.0 * BOLTZMANN * (27.0 + KELVIN) / CHARGE * (1e16 / INTRINSIC_DENSITY).ln();
+        assert!((mosfet.scaled.phi - phi).abs() < 1e-6);
+        let gamma = (2.0 * EPSILON_SI * CHARGE * 1e22).sqrt() / cox;
+        assert!((mosfet.scaled.gamma - gamma).abs() < 1e-9);
+        // An n+ polysilicon gate over p substrate: a threshold below 1 V.
+        let vth = mosfet.scaled.vbi + gamma * phi.sqrt();
+        assert!(vth > 0.0 && vth < 1.0, "{}", vth);
+    }
+
+    /// The small signal drain current into `V2` at low frequency against a
+    /// finite difference of the operating point, for `vgs` or `vds`.
+    fn check_conductances(model: &str, vgs: f64, vds: f64) {
+        let delta = 1e-6;
+        let gm = (drain_current(model, vgs + delta, vds) - drain_current(model, vgs - delta, vds))
+            / (2.0 * delta);
+        let gds = (drain_current(model, vgs, vds + delta) - drain_current(model, vgs, vds - delta))
+            / (2.0 * delta);
+        for (ac_gate, expected) in [(true, gm), (false, gds)] {
+            let lines = [
+                format!("V1 g 0 {} AC {}", vgs, if ac_gate { 1 } else { 0 }),
+                format!("V2 d 0 {} AC {}", vds, if ac_gate { 0 } else { 1 }),
+                "V3 b 0 -1".to_string(),
+                "M1 d g 0 b MOD L=2u W=10u".to_string(),
+                model.to_string(),
+            ];
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let mut mos = circuit(&lines);
+            let branch = mos.unknowns().find("I(V2)").unwrap();
+            let response = -ac(&mut mos, 1.0)[branch].re;
+            assert!(
+                (response - expected).abs() < 1e-4 * expected.abs() + 1e-9,
+                "{} at vgs {} vds {}: {} {}",
+                model,
+                vgs,
+                vds,
+                response,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_levels_2_and_3() {
+        let models = [
+            ".model MOD NMOS(LEVEL=2 VTO=0.8 TOX=25n NSUB=5e15 UO=600 XJ=0.3u LD=0.2u DELTA=1 UCRIT=1e4 UEXP=0.2 NFS=1e11)",
+            ".model MOD NMOS(LEVEL=2 VTO=0.8 TOX=25n NSUB=5e15 UO=600 VMAX=5e4 NEFF=2 XJ=0.3u)",
+            ".model MOD NMOS(LEVEL=3 VTO=0.8 TOX=25n NSUB=5e15 UO=600 XJ=0.3u LD=0.2u THETA=0.1 ETA=0.2 KAPPA=0.5 VMAX=1e5 DELTA=1 NFS=1e11)",
+            ".model MOD NMOS(LEVEL=3 VTO=0.8 TOX=25n NSUB=5e15 UO=600)",
+        ];
+        for model in models {
+            // Increasing with the gate voltage and, past saturation, only
+            // slowly with the drain voltage.
+            let low = drain_current(model, 1.5, 3.0);
+            let high = drain_current(model, 3.0, 3.0);
+            assert!(low > 0.0 && high > 2.0 * low, "{} {} {}", model, low, high);
+            let saturated = drain_current(model, 2.0, 5.0) / drain_current(model, 2.0, 4.0);
+            assert!(
+                saturated > 1.0 && saturated < 1.2,
+                "{} {}",
+                model,
+                saturated
+            );
+            for (vgs, vds) in [(2.0, 0.2), (2.0, 4.0), (3.0, -0.5)] {
+                check_conductances(model, vgs, vds);
+            }
+        }
+        // Weak inversion conducts below threshold.
+        let weak = drain_current(models[0], 0.7, 3.0);
+        assert!(weak > 1e-10 && drain_current(models[0], 0.5, 3.0) < weak / 100.0);
+    }
+
+    #[test]
+    fn test_meyer() {
+        // Saturated, the gate sees two thirds of the oxide to the source.
+        let mut mos = circuit(&[
+            "V2 d 0 5",
+            "I1 0 g 0 AC 1",
+            "R1 g x 1",
+            "V3 x 0 2",
+            "M1 d g 0 0 MOD L=10u W=10u",
+            ".model MOD NMOS(VTO=1 KP=50u TOX=50n CGSO=1n CGDO=1n)",
+        ]);
+        let g = mos.unknowns().find("V(g)").unwrap();
+        let omega = 1e6;
+        let response = ac(&mut mos, omega);
+        let admittance = Complex::from(1.0) / response[g] - Complex::from(1.0);
+        let cox = EPSILON_OX / 50e-9 * 100e-12;
+        let expected = 2.0 / 3.0 * cox + 2e-9 * 10e-6;
+        assert!((admittance.im / (omega * expected) - 1.0).abs() < 1e-6);
+
+        let half = meyer(0.5, 0.5, 1.0, 0.0, 0.6, 3.0);
+        assert_eq!(half, [0.0, 0.0, 0.5 * 3.0 / 1.2]);
+    }
+
+    #[test]
+    fn test_inverter() {
+        let options = Options::default();
+        let mut inverter = circuit(&[
+            "V1 in 0 PULSE(0 5 10n 1n 1n 20n 50n)",
+            "V2 vdd 0 5",
+            "M1 out in 0 0 N L=1u W=10u",
+            "M2 out in vdd vdd P L=1u W=20u",
+            "C1 out 0 50f",
+            ".model N NMOS(LEVEL=3 VTO=0.7 TOX=20n NSUB=1e16 UO=500 CGSO=0.3n CGDO=0.3n CJ=0.3m)",
+            ".model P PMOS(LEVEL=3 VTO=-0.7 TOX=20n NSUB=1e16 UO=200 CGSO=0.3n CGDO=0.3n CJ=0.3m)",
+        ]);
+        let out = inverter.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut inverter, ".tran 50n", &options);
+        let at = |time: f64| {
+            points
+                .iter()
+                .min_by(|a, b| (a.0 - time).abs().total_cmp(&(b.0 - time).abs()))
+                .unwrap()
+                .1[out]
+        };
+        assert!(at(5e-9) > 4.99);
+        assert!(at(25e-9) < 0.01, "{}", at(25e-9));
+        assert!(at(45e-9) > 4.99, "{}", at(45e-9));
+    }
+}
@@ -0,0 +1,206 @@
+//! Element lines taken apart into positional words and `name=value`
+//! parameters.
+
+use crate::frontend::parser::netlist::{Component, Node};
+use crate::utils::number::parse_number;
+
+/// The words of an element line after the instance name: its nodes, then
+/// values, model names and flags, with the `name=value` parameters kept
+/// apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementLine {
+    pub name: String,
+    pub words: Vec<String>,
+    /// Parameter names in lower case, in the order given.
+    pub parameters: Vec<(String, String)>,
+    text: String,
+}
+
+impl ElementLine {
+    /// Split `component`. Where its nodes end and its value starts does
+    /// not matter, since schematic and netlist readers draw that line
+    /// differently for sources with controlling nodes.
+    pub fn new(component: &Component) -> Self {
+        let mut text: String = component
+            .nodes
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        text.push(' ');
+        text.push_str(&component.value);
+        let (words, parameters) = split(&text);
+        ElementLine {
+            name: component.name.clone(),
+            words,
+            parameters,
+            text,
+        }
+    }
+
+    /// The text after the first `count` words, for values with a grammar of
+    /// their own such as an independent source's `SINE(0 1 1k)`.
+    pub fn after(&self, count: usize) -> &str {
+        let mut rest = self.text.trim_start();
+        for _ in 0..count {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        }
+        rest
+    }
+
+    /// The first `N` words, as nodes.
+    pub fn nodes<const N: usize>(&self) -> Result<[Node; N], String> {
+        if self.words.len() < N {
+            return Err(format!("Expected {} nodes", N));
+        }
+        Ok(std::array::from_fn(|i| Node::new(self.words[i].as_str())))
+    }
+
+    /// The element's value: parameter `key` if given, else the word at
+    /// `index`, as in `C1 a b 1u` or `C1 a b c=1u`.
+    pub fn value(&self, index: usize, key: &str) -> Result<f64, String> {
+        if let Some(value) = self.number(key)? {
+            return Ok(value);
+        }
+        let word = self.words.get(index).ok_or("No value given")?;
+        parse_number(word).ok_or_else(|| format!("Invalid value: {}", word))
+    }
+
+    /// The text of parameter `name`, the last one winning.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .rev()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of numeric parameter `name`, `None` if it is not given.
+    pub fn number(&self, name: &str) -> Result<Option<f64>, String> {
+        match self.parameter(name) {
+            None => Ok(None),
+            Some(text) => parse_number(text)
+                .map(Some)
+                .ok_or_else(|| format!("Invalid value for {}: {}", name, text)),
+        }
+    }
+
+    /// The comma separated values of parameter `name`, as in `tc=1m,2u`.
+    pub fn numbers(&self, name: &str) -> Result<Vec<f64>, String> {
+        let Some(text) = self.parameter(name) else {
+            return Ok(Vec::new());
+        };
+        text.split(',')
+            .map(|v| parse_number(v).ok_or_else(|| format!("Invalid value for {}: {}", name, text)))
+            .collect()
+    }
+
+    /// Whether a bare word such as `noiseless` or `off` is on the line.
+    pub fn flag(&self, word: &str) -> bool {
+        self.words.iter().any(|w| w.eq_ignore_ascii_case(word))
+    }
+
+    /// Every parameter not in `known`, to report.
+    pub fn unknown_parameters(&self, known: &[&str]) -> Result<(), String> {
+        match self
+            .parameters
+            .iter()
+            .find(|(key, _)| !known.contains(&key.as_str()))
+        {
+            Some((key, _)) => Err(format!("Unknown parameter {}", key)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Words and `name=value` pairs of `text`. Spaces around `=` are allowed,
+/// and a parenthesised group such as `POLY(2)` or `tc=(1m, 2u)` stays one
+/// word, its parentheses dropped from parameter values.
+pub(crate) fn split(text: &str) -> (Vec<String>, Vec<(String, String)>) {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                current.push(c);
+            }
+            '=' if depth == 0 => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push("=".to_string());
+            }
+            c if depth == 0 && c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c if depth > 0 && c.is_whitespace() => {
+                if !current.ends_with(',') && !current.ends_with('(') {
+                    current.push(',');
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    let mut words = Vec::new();
+    let mut parameters = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens.get(i + 1).is_some_and(|t| t == "=") && i + 2 < tokens.len() {
+            let value = tokens[i + 2].trim_start_matches('(').trim_end_matches(')');
+            parameters.push((tokens[i].to_ascii_lowercase(), value.replace(",,", ",")));
+            i += 3;
+        } else {
+            if tokens[i] != "=" {
+                words.push(tokens[i].clone());
+            }
+            i += 1;
+        }
+    }
+    (words, parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let component = Component::new(
+            "R1",
+            vec![Node::new("in"), Node::new("out")],
+            "1k TC1 = 1m tc=(2m 3u) noiseless m=2",
+        );
+        let line = ElementLine::new(&component);
+        assert_eq!(line.words, ["in", "out", "1k", "noiseless"]);
+        assert_eq!(line.number("tc1"), Ok(Some(1e-3)));
+        assert_eq!(line.numbers("tc"), Ok(vec![2e-3, 3e-6]));
+        assert_eq!(line.number("m"), Ok(Some(2.0)));
+        assert_eq!(line.number("tc2"), Ok(None));
+        assert!(line.flag("NOISELESS"));
+        assert_eq!(line.value(2, "r"), Ok(1e3));
+        assert_eq!(line.value(2, "m"), Ok(2.0));
+        assert!(line.unknown_parameters(&["tc1", "tc", "m"]).is_ok());
+        assert!(line.unknown_parameters(&["tc1"]).is_err());
+
+        let component = Component::new("E1", vec![], "out 0 POLY(2) a 0 b 0 1 2 3");
+        let line = ElementLine::new(&component);
+        assert_eq!(line.words[2], "POLY(2)");
+        assert_eq!(line.words.len(), 10);
+        assert_eq!(line.after(3), "a 0 b 0 1 2 3");
+        let bad = Component::new("R2", vec![], "a b 1k tc1=x");
+        assert!(ElementLine::new(&bad).number("tc1").is_err());
+    }
+}
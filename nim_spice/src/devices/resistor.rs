@@ -0,0 +1,151 @@
+//! Resistors, with quadratic temperature coefficients and thermal noise.
+
+use crate::devices::generic::{
+    thermal_noise, voltage, Conductance, Device, LoadContext, NoiseSource, Unknown, Unknowns,
+};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resistor {
+    name: String,
+    nodes: [Node; 2],
+    /// Resistance at the nominal temperature.
+    resistance: f64,
+    tc1: f64,
+    tc2: f64,
+    /// Number of resistors in parallel.
+    multiplier: f64,
+    noiseless: bool,
+    /// Temperature of this instance in °C, instead of the circuit's.
+    temp: Option<f64>,
+    /// Offset of this instance from the circuit temperature.
+    dtemp: f64,
+    temperature: f64,
+    conductance: f64,
+    unknowns: [Unknown; 2],
+    stamp: Conductance,
+}
+
+impl Resistor {
+    pub fn new(name: impl Into<String>, positive: Node, negative: Node, resistance: f64) -> Self {
+        Resistor {
+            name: name.into(),
+            nodes: [positive, negative],
+            resistance,
+            tc1: 0.0,
+            tc2: 0.0,
+            multiplier: 1.0,
+            noiseless: false,
+            temp: None,
+            dtemp: 0.0,
+            temperature: 0.0,
+            conductance: 1.0 / resistance,
+            unknowns: [None; 2],
+            stamp: Conductance::default(),
+        }
+    }
+
+    /// `Rname n+ n- value [tc1=] [tc2=] [tc=tc1,tc2] [m=] [temp=] [dtemp=]
+    /// [noiseless]`, the value also as `r=`.
+    pub fn parse(line: &ElementLine) -> Result<Self, String> {
+        line.unknown_parameters(&["r", "tc1", "tc2", "tc", "m", "temp", "dtemp"])?;
+        let [positive, negative] = line.nodes()?;
+        let value = line.value(2, "r")?;
+        if value == 0.0 {
+            return Err("Resistance is zero".to_string());
+        }
+        let mut resistor = Resistor::new(line.name.clone(), positive, negative, value);
+        let tc = line.numbers("tc")?;
+        resistor.tc1 = line.number("tc1")?.or(tc.first().copied()).unwrap_or(0.0);
+        resistor.tc2 = line.number("tc2")?.or(tc.get(1).copied()).unwrap_or(0.0);
+        resistor.multiplier = line.number("m")?.unwrap_or(1.0);
+        if resistor.multiplier <= 0.0 {
+            return Err("The multiplier must be positive".to_string());
+        }
+        resistor.temp = line.number("temp")?;
+        resistor.dtemp = line.number("dtemp")?.unwrap_or(0.0);
+        resistor.noiseless = line.flag("noiseless");
+        Ok(resistor)
+    }
+
+    /// Resistance at the present temperature, of all parallel instances.
+    pub fn resistance(&self) -> f64 {
+        1.0 / self.conductance
+    }
+
+    /// Current from the positive to the negative node.
+    pub fn current(&self, solution: &[f64]) -> f64 {
+        (voltage(solution, self.unknowns[0]) - voltage(solution, self.unknowns[1]))
+            * self.conductance
+    }
+}
+
+impl Device for Resistor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        self.stamp = Conductance::new(pattern, self.unknowns[0], self.unknowns[1]);
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        self.temperature = self.temp.unwrap_or(temperature + self.dtemp);
+        let difference = self.temperature - nominal;
+        let factor = 1.0 + self.tc1 * difference + self.tc2 * difference * difference;
+        self.conductance = self.multiplier / (self.resistance * factor);
+    }
+
+    fn load(&mut self, _context: &mut LoadContext, matrix: &mut SparseMatrix, _rhs: &mut [f64]) {
+        self.stamp.stamp(matrix, self.conductance);
+    }
+
+    fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        self.stamp.stamp(matrix, Complex::from(self.conductance));
+    }
+
+    fn noise(&self, _frequency: f64) -> Vec<NoiseSource> {
+        if self.noiseless {
+            return Vec::new();
+        }
+        vec![NoiseSource {
+            name: self.name.clone(),
+            positive: self.unknowns[0],
+            negative: self.unknowns[1],
+            density: thermal_noise(self.conductance, self.temperature),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::devices::tests::operating_point;
+
+    #[test]
+    fn test_resistor() {
+        let (circuit, solution) =
+            operating_point(&["V1 in 0 10", "R1 in out 1k", "R2 out 0 r=3k m=3"]);
+        let out = circuit.unknowns().find("V(out)").unwrap();
+        assert!((solution[out] - 5.0).abs() < 1e-12);
+
+        // At 127 °C, 100 degrees above nominal, tc1 = 1m and tc2 = 10u
+        // raise the resistance by 20 %.
+        let (circuit, solution) = operating_point(&[
+            "I1 0 a 1m",
+            "R1 a 0 1k tc=1m,10u temp=127",
+            "R2 a b 1k tc1=1m dtemp=100",
+        ]);
+        let a = circuit.unknowns().find("V(a)").unwrap();
+        assert!((solution[a] - 1.2).abs() < 1e-12);
+        let noise = circuit.noise(1.0);
+        assert_eq!(noise.len(), 2);
+        assert!(noise[0].density > 0.0);
+    }
+}
@@ -0,0 +1,151 @@
+//! Independent voltage and current sources.
+
+use crate::devices::generic::{add, current, entry, Device, LoadContext, Unknown, Unknowns};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::frontend::parser::source::{parse_source, SourceSpec};
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Handle, Pattern, SparseMatrix};
+use std::path::Path;
+
+/// `Vname n+ n- spec` or `Iname n+ n- spec`, with the DC value, AC
+/// excitation and waveform of [`parse_source`]. A current source drives
+/// its current from `n+` through itself to `n-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    name: String,
+    nodes: [Node; 2],
+    /// A voltage source, with a branch current, rather than a current
+    /// source.
+    voltage: bool,
+    spec: SourceSpec,
+    unknowns: [Unknown; 2],
+    branch: Unknown,
+    /// `(n+, br)`, `(n-, br)`, `(br, n+)` and `(br, n-)`.
+    entries: [Option<Handle>; 4],
+}
+
+impl Source {
+    /// A relative `file=` of a PWL is found in `directory`.
+    pub fn parse(line: &ElementLine, directory: &Path) -> Result<Self, String> {
+        let [positive, negative] = line.nodes()?;
+        let spec = parse_source(line.after(2), directory).map_err(|errors| {
+            errors
+                .errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })?;
+        Ok(Source {
+            name: line.name.clone(),
+            nodes: [positive, negative],
+            voltage: line.name.to_ascii_uppercase().starts_with('V'),
+            spec,
+            unknowns: [None; 2],
+            branch: None,
+            entries: [None; 4],
+        })
+    }
+
+    /// Value for the analysis of `context`. The operating point and DC
+    /// sweeps take the DC value when the line gives one, as SPICE does,
+    /// and otherwise the waveform's value at time zero; the transient
+    /// analysis, its own operating point included, follows the waveform.
+    pub fn value(&self, context: &LoadContext) -> f64 {
+        match &self.spec.waveform {
+            Some(waveform) if context.is_transient() || context.initial || !self.spec.dc_given => {
+                waveform.value(context.time)
+            }
+            _ => self.spec.dc,
+        }
+    }
+}
+
+impl Device for Source {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        if self.voltage {
+            self.branch = Some(unknowns.branch(&self.name));
+        }
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p, n] = self.unknowns;
+        let br = self.branch;
+        self.entries = [
+            entry(pattern, p, br),
+            entry(pattern, n, br),
+            entry(pattern, br, p),
+            entry(pattern, br, n),
+        ];
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let value = self.value(context) * context.source_factor();
+        match self.branch {
+            Some(br) => {
+                add(matrix, self.entries[0], 1.0);
+                add(matrix, self.entries[1], -1.0);
+                add(matrix, self.entries[2], 1.0);
+                add(matrix, self.entries[3], -1.0);
+                rhs[br] += value;
+            }
+            None => current(rhs, self.unknowns[0], self.unknowns[1], value),
+        }
+    }
+
+    fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, rhs: &mut [Complex]) {
+        let value = self
+            .spec
+            .ac
+            .map_or(Complex::default(), |(magnitude, phase)| {
+                Complex::from_polar(magnitude, phase.to_radians())
+            });
+        match self.branch {
+            Some(br) => {
+                add(matrix, self.entries[0], Complex::from(1.0));
+                add(matrix, self.entries[1], Complex::from(-1.0));
+                add(matrix, self.entries[2], Complex::from(1.0));
+                add(matrix, self.entries[3], Complex::from(-1.0));
+                rhs[br] += value;
+            }
+            None => current(rhs, self.unknowns[0], self.unknowns[1], value),
+        }
+    }
+
+    fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        self.spec
+            .waveform
+            .as_ref()
+            .map_or(Vec::new(), |w| w.breakpoints(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::options::Options;
+    use crate::devices::tests::{circuit, operating_point, transient};
+
+    #[test]
+    fn test_dc_value() {
+        // The DC value sets the operating point, the waveform the transient.
+        let lines = ["V1 a 0 DC 5 SIN(0 1 1k)", "R1 a 0 1k"];
+        let (sine, solution) = operating_point(&lines);
+        let a = sine.unknowns().find("V(a)").unwrap();
+        assert_eq!(solution[a], 5.0);
+        let (_, solution) = operating_point(&["V1 a 0 SIN(2 1 1k)", "R1 a 0 1k"]);
+        assert_eq!(solution[a], 2.0);
+
+        let points = transient(&mut circuit(&lines), ".tran 10u 0.5m", &Options::default());
+        assert_eq!(points[0].1[a], 0.0);
+        for (t, solution) in &points {
+            let expected = (2.0 * std::f64::consts::PI * 1e3 * t).sin();
+            assert!((solution[a] - expected).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Voltage and current controlled switches, `S` and `W`.
+//!
+//! A switch of an `SW` or `CSW` model conducts through `RON` when on and
+//! `ROFF` when off. With a positive hysteresis `VH` (`IH`) it turns on as
+//! the control rises above `VT + VH` and off as it falls below `VT - VH`,
+//! keeping its state in between; with none it turns at `VT`. A negative
+//! `VH` makes the change smooth instead, as in LTspice: the log of the
+//! conductance follows a cubic from off at `VT - |VH|` to on at
+//! `VT + |VH|`, with a continuous slope at both ends. LTspice's `VSER`
+//! puts a voltage in series while on, `ILIMIT` bends the current over into
+//! a limit with a `tanh`, and `LSER` becomes an inductor of its own.
+//!
+//! An abrupt switch does not let a transient step pass over its switching
+//! instant. Its last two accepted points predict when the control will
+//! cross the threshold, which becomes a breakpoint, and a step that
+//! crossed it anyway is rejected and retried so that it ends just past the
+//! crossing, found by interpolating the control.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::Timestep;
+use crate::devices::controlled::Control;
+use crate::devices::generic::{
+    current, thermal_noise, voltage, Conductance, Device, LoadContext, NoiseSource, Unknown,
+    Unknowns,
+};
+use crate::devices::inductor::Inductor;
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::{Component, Node};
+use crate::maths::complex::Complex;
+use crate::maths::dual::{Dual, Real};
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// Fraction of a step that a switching instant is overshot by, so that the
+/// step retried up to it, or the breakpoint predicted for it, lands just
+/// past the threshold.
+const OVERSHOOT: f64 = 1e-3;
+
+/// How many of the last accepted steps ahead a predicted switching instant
+/// may lie to become a breakpoint.
+const HORIZON: f64 = 2.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchModel {
+    pub ron: f64,
+    pub roff: f64,
+    /// Threshold and hysteresis of the control, in V for an `SW` model and
+    /// A for a `CSW` one.
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub lser: f64,
+    pub vser: f64,
+    pub ilimit: Option<f64>,
+}
+
+impl SwitchModel {
+    /// The parameters of an `SW` card, or with `IT` and `IH` of a `CSW`
+    /// card.
+    pub fn new(model: &Model) -> Result<Self, String> {
+        let by_current = model.kind == "CSW";
+        let (threshold, hysteresis) = if by_current {
+            ("it", "ih")
+        } else {
+            ("vt", "vh")
+        };
+        model.unknown_parameters(&[
+            "ron", "roff", threshold, hysteresis, "lser", "vser", "ilimit",
+        ])?;
+        let value = |name: &str, default: f64| -> Result<f64, String> {
+            Ok(model.number(name)?.unwrap_or(default))
+        };
+        let switch = SwitchModel {
+            ron: value("ron", 1.0)?,
+            roff: value("roff", 1e12)?,
+            threshold: value(threshold, 0.0)?,
+            hysteresis: value(hysteresis, 0.0)?,
+            lser: value("lser", 0.0)?,
+            vser: value("vser", 0.0)?,
+            ilimit: model.number("ilimit")?.filter(|i| *i != 0.0),
+        };
+        if switch.ron <= 0.0 || switch.roff <= 0.0 {
+            return Err(format!(
+                "Model {}: RON and ROFF must be positive",
+                model.name
+            ));
+        }
+        if switch.ilimit.is_some_and(|i| i < 0.0) {
+            return Err(format!("Model {}: ILIMIT must be positive", model.name));
+        }
+        Ok(switch)
+    }
+
+    /// Whether the change between off and on is smooth rather than abrupt.
+    pub fn smooth(&self) -> bool {
+        self.hysteresis < 0.0
+    }
+
+    /// The state at `control` of an abrupt switch that was `on`.
+    fn state(&self, on: bool, control: f64) -> bool {
+        if control > self.threshold + self.hysteresis {
+            true
+        } else if control < self.threshold - self.hysteresis {
+            false
+        } else {
+            on
+        }
+    }
+
+    /// The threshold an abrupt switch that is `on` changes state at.
+    fn edge(&self, on: bool) -> f64 {
+        if on {
+            self.threshold - self.hysteresis
+        } else {
+            self.threshold + self.hysteresis
+        }
+    }
+
+    /// How far on the switch is at `control`, from 0 to 1.
+    fn fraction<T: Real>(&self, control: T, on: bool) -> T {
+        if !self.smooth() {
+            return T::constant(if on { 1.0 } else { 0.0 });
+        }
+        let width = -self.hysteresis;
+        let x = (control - (self.threshold - width)) / (2.0 * width);
+        if x.value() <= 0.0 {
+            T::constant(0.0)
+        } else if x.value() >= 1.0 {
+            T::constant(1.0)
+        } else {
+            x * x * (-(x * 2.0) + 3.0)
+        }
+    }
+
+    /// The current through the switch at voltage `v` and `control`.
+    fn current<T: Real>(&self, v: T, control: T, on: bool) -> T {
+        let s = self.fraction(control, on);
+        let (log_on, log_off) = ((1.0 / self.ron).ln(), (1.0 / self.roff).ln());
+        let g = (s * (log_on - log_off) + log_off).exp();
+        let drive = (v - s * self.vser) * g;
+        match self.ilimit {
+            Some(limit) => (drive / limit).tanh() * limit,
+            None => drive,
+        }
+    }
+}
+
+/// `Sname n+ n- nc+ nc- model [on|off]` or `Wname n+ n- vname model
+/// [on|off]`, the current through voltage source `vname` controlling a
+/// `W`, which must be among `components`. `on` or `off` is the state to
+/// start the operating point from. `LSER` of the model becomes
+/// `Sname.Lser` from `n+` to the internal node `Sname#1`.
+pub fn build(
+    line: &ElementLine,
+    components: &[Component],
+    models: &[Model],
+) -> Result<Vec<Box<dyn Device>>, String> {
+    line.unknown_parameters(&[])?;
+    let by_current = line.name.to_ascii_uppercase().starts_with('W');
+    let [positive, negative] = line.nodes()?;
+    let (control, index) = if by_current {
+        let source = line.words.get(2).ok_or("No controlling source given")?;
+        if !components.iter().any(|c| {
+            c.name.eq_ignore_ascii_case(source) && matches!(c.prefix(), 'V' | 'E' | 'H' | 'L')
+        }) {
+            return Err(format!("No voltage source {}", source));
+        }
+        (Control::Current(source.clone()), 3)
+    } else {
+        let [_, _, p, n] = line.nodes()?;
+        (Control::Voltage(p, n), 4)
+    };
+    let name = line.words.get(index).ok_or("No model given")?;
+    let kind = if by_current { "CSW" } else { "SW" };
+    let model = SwitchModel::new(find_model(models, name, &[kind])?)?;
+    let mut devices: Vec<Box<dyn Device>> = Vec::new();
+    let mut terminal = positive;
+    if model.lser != 0.0 {
+        let node = Node::new(format!("{}#1", line.name));
+        devices.push(Box::new(Inductor::new(
+            format!("{}.Lser", line.name),
+            terminal,
+            node.clone(),
+            model.lser,
+        )));
+        terminal = node;
+    }
+    let on = line.flag("on");
+    devices.push(Box::new(Switch {
+        name: line.name.clone(),
+        nodes: [terminal, negative],
+        control,
+        model,
+        temperature: 0.0,
+        unknowns: [None; 2],
+        sensed: [None; 2],
+        stamps: [Conductance::default(); 2],
+        on,
+        accepted_on: on,
+        control_value: 0.0,
+        accepted: [None; 2],
+        conductances: [0.0; 2],
+    }));
+    Ok(devices)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Switch {
+    name: String,
+    nodes: [Node; 2],
+    control: Control,
+    model: SwitchModel,
+    temperature: f64,
+    unknowns: [Unknown; 2],
+    /// The control as the difference of two unknowns, a current being the
+    /// difference between its branch and ground.
+    sensed: [Unknown; 2],
+    /// The switch's conductance and its transconductance from the control.
+    stamps: [Conductance; 2],
+    /// The state at the last load and at the last accepted point.
+    on: bool,
+    accepted_on: bool,
+    /// The control at the last load.
+    control_value: f64,
+    /// Time and control of the two last accepted points, the newest
+    /// first.
+    accepted: [Option<(f64, f64)>; 2],
+    /// Derivatives of the current with the voltage across the switch and
+    /// with the control, at the last load.
+    conductances: [f64; 2],
+}
+
+impl Switch {
+    /// Whether the switch was on at the last load.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    fn sense(&self, solution: &[f64]) -> f64 {
+        voltage(solution, self.sensed[0]) - voltage(solution, self.sensed[1])
+    }
+}
+
+impl Device for Switch {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = [unknowns.node(&self.nodes[0]), unknowns.node(&self.nodes[1])];
+        self.sensed = match &self.control {
+            Control::Voltage(p, n) => [unknowns.node(p), unknowns.node(n)],
+            Control::Current(source) => [Some(unknowns.branch(source)), None],
+        };
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p, n] = self.unknowns;
+        let [cp, cn] = self.sensed;
+        self.stamps = [
+            Conductance::new(pattern, p, n),
+            Conductance::controlled(pattern, p, n, cp, cn),
+        ];
+    }
+
+    fn temperature(&mut self, temperature: f64, _nominal: f64) {
+        self.temperature = temperature;
+    }
+
+    /// The state follows the control from the last accepted point in a
+    /// transient analysis, and from the last iteration at the operating
+    /// point, which starts from `on` or `off`. A change of state is a
+    /// limited step, so that Newton iteration goes on.
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let control = self.sense(context.solution);
+        let [p, n] = self.unknowns;
+        let v = context.voltage(p) - context.voltage(n);
+        let previous = if context.is_transient() {
+            self.accepted_on
+        } else {
+            self.on
+        };
+        let on = self.model.state(previous, control);
+        if on != self.on && !self.model.smooth() {
+            context.limit(&self.name);
+        }
+        self.on = on;
+        self.control_value = control;
+        let i = self
+            .model
+            .current(Dual::<2>::variable(v, 0), Dual::variable(control, 1), on);
+        let (g, gc) = (i.derivative(0), i.derivative(1));
+        self.conductances = [g, gc];
+        self.stamps[0].stamp(matrix, g);
+        self.stamps[1].stamp(matrix, gc);
+        current(rhs, p, n, i.value - g * v - gc * control);
+    }
+
+    fn ac_load(&self, _omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        self.stamps[0].stamp(matrix, Complex::from(self.conductances[0]));
+        self.stamps[1].stamp(matrix, Complex::from(self.conductances[1]));
+    }
+
+    fn noise(&self, _frequency: f64) -> Vec<NoiseSource> {
+        vec![NoiseSource {
+            name: self.name.clone(),
+            positive: self.unknowns[0],
+            negative: self.unknowns[1],
+            density: thermal_noise(self.conductances[0], self.temperature),
+        }]
+    }
+
+    fn accept(&mut self, solution: &[f64], time: f64) {
+        let control = self.sense(solution);
+        self.on = self.model.state(self.on, control);
+        self.accepted_on = self.on;
+        if self.accepted[0].is_some_and(|(t, _)| t >= time) {
+            // The operating point again, at the start of a transient.
+            self.accepted = [None; 2];
+        }
+        self.accepted = [Some((time, control)), self.accepted[0]];
+    }
+
+    /// A step over which an abrupt switch changed state may only reach
+    /// just past the crossing of its threshold.
+    fn truncation(&self, _timestep: &Timestep, _integrator: &Integrator, h: f64) -> f64 {
+        let Some((_, before)) = self.accepted[0] else {
+            return f64::INFINITY;
+        };
+        if self.model.smooth() || self.on == self.accepted_on || self.control_value == before {
+            return f64::INFINITY;
+        }
+        let edge = self.model.edge(self.accepted_on);
+        let fraction = ((edge - before) / (self.control_value - before)).clamp(0.0, 1.0);
+        if fraction <= OVERSHOOT {
+            // The last point sat on the threshold.
+            return f64::INFINITY;
+        }
+        h * (fraction + OVERSHOOT)
+    }
+
+    /// The instant the control will cross the threshold, if the trend of
+    /// the last two accepted points reaches it soon.
+    fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        let [Some((t1, c1)), Some((t0, c0))] = self.accepted else {
+            return Vec::new();
+        };
+        if self.model.smooth() || c1 == c0 {
+            return Vec::new();
+        }
+        let step = t1 - t0;
+        let slope = (c1 - c0) / step;
+        let crossing = t1 + (self.model.edge(self.accepted_on) - c1) / slope + OVERSHOOT * step;
+        if crossing > t1 && crossing <= t1 + HORIZON * step && crossing > from && crossing <= to {
+            vec![crossing]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn unconverged(&self, solution: &[f64], _options: &Options) -> bool {
+        !self.model.smooth() && self.model.state(self.on, self.sense(solution)) != self.on
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::integration::Method;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+
+    /// The voltage across `R1` from `V1` through the switch, with the
+    /// control at `vc`.
+    fn divider(model: &str, vc: f64) -> f64 {
+        let control = format!("V2 c 0 {}", vc);
+        let (circuit, solution) = operating_point(&[
+            "V1 in 0 1",
+            "S1 in out c 0 SMOD",
+            "R1 out 0 1k",
+            &control,
+            model,
+        ]);
+        assert_eq!(circuit.device("S1.Lser").is_some(), model.contains("LSER"));
+        solution[circuit.unknowns().find("V(out)").unwrap()]
+    }
+
+    #[test]
+    fn test_abrupt() {
+        let model = ".model SMOD SW(RON=1 ROFF=1meg VT=1 LSER=10n)";
+        assert!((divider(model, 2.0) - 1e3 / 1001.0).abs() < 1e-12);
+        assert!((divider(model, 0.5) - 1e3 / 1001e3).abs() < 1e-12);
+
+        // VSER is a drop while on, and ILIMIT caps the current.
+        let model = ".model SMOD SW(RON=1 VT=1 VSER=0.2)";
+        assert!((divider(model, 2.0) - 0.8e3 / 1001.0).abs() < 1e-12);
+        let model = ".model SMOD SW(RON=1 VT=1 ILIMIT=0.1m)";
+        assert!((divider(model, 2.0) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_current_controlled() {
+        let (circuit, solution) = operating_point(&[
+            "I1 0 c 2m",
+            "V2 c 0 0",
+            "V1 in 0 1",
+            "W1 in out V2 WMOD",
+            "R1 out 0 1k",
+            ".model WMOD CSW(IT=1m RON=1 ROFF=1meg)",
+        ]);
+        let out = solution[circuit.unknowns().find("V(out)").unwrap()];
+        assert!((out - 1e3 / 1001.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_smooth() {
+        // Halfway through the transition the resistance is the geometric
+        // mean of RON and ROFF.
+        let model = ".model SMOD SW(RON=10 ROFF=1meg VT=1 VH=-0.5)";
+        let expected = 1e3 / (1e3 + (10.0f64 * 1e6).sqrt());
+        assert!((divider(model, 1.0) - expected).abs() < 1e-12);
+        assert!((divider(model, 1.5) - 1e3 / 1010.0).abs() < 1e-12);
+
+        // The transconductance from the control, in AC, matches the
+        // operating points either side.
+        let vc = 1.2;
+        let delta = 1e-6;
+        let slope = (divider(model, vc + delta) - divider(model, vc - delta)) / (2.0 * delta);
+        let mut smooth = circuit(&[
+            "V1 in 0 1",
+            "S1 in out c 0 SMOD",
+            "R1 out 0 1k",
+            "V2 c 0 1.2 AC 1",
+            model,
+        ]);
+        let out = smooth.unknowns().find("V(out)").unwrap();
+        let response = ac(&mut smooth, 1.0)[out].re;
+        assert!(
+            (response / slope - 1.0).abs() < 1e-5,
+            "{} {}",
+            response,
+            slope
+        );
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        // A triangle up to 2 V and back: on past 1.5 V at 0.75 ms, off
+        // below 0.5 V at 1.75 ms. Steps land just past both instants.
+        let options = Options::default();
+        let mut switch = circuit(&[
+            "V1 in 0 1",
+            "S1 in out c 0 SMOD",
+            "R1 out 0 1k",
+            "V2 c 0 PWL(0 0 1m 2 2m 0)",
+            ".model SMOD SW(RON=1 ROFF=1meg VT=1 VH=0.5)",
+        ]);
+        let out = switch.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut switch, ".tran 2m", &options);
+        let on: Vec<f64> = points
+            .iter()
+            .filter(|(_, x)| x[out] > 0.5)
+            .map(|(t, _)| *t)
+            .collect();
+        let (first, last) = (on[0], on[on.len() - 1]);
+        assert!((0.75e-3..0.75e-3 + 1e-7).contains(&first), "{}", first);
+        assert!(last < 1.75e-3, "{}", last);
+        let after = points.iter().find(|(t, _)| *t > last).unwrap().0;
+        assert!(after > 1.75e-3 && after < 1.75e-3 + 1e-7, "{}", after);
+    }
+
+    #[test]
+    fn test_boost_converter() {
+        // A boost converter switched at 100 kHz with 50 % duty doubles its
+        // input, less a diode drop, once settled after 200 cycles.
+        let options = Options {
+            method: Method::Trapezoidal,
+            ..Options::default()
+        };
+        let mut boost = circuit(&[
+            "V1 in 0 5",
+            "L1 in sw 100u",
+            "S1 sw 0 g 0 SMOD",
+            "V2 g 0 PULSE(0 5 0 10n 10n 4.99u 10u)",
+            "D1 sw out DMOD",
+            "C1 out 0 10u",
+            "R1 out 0 20",
+            ".model SMOD SW(RON=10m ROFF=1meg VT=2.5)",
+            ".model DMOD D(IS=1e-14 RS=10m)",
+        ]);
+        let out = boost.unknowns().find("V(out)").unwrap();
+        let points = transient(&mut boost, ".tran 2m", &options);
+        let settled: Vec<f64> = points
+            .iter()
+            .filter(|(t, _)| *t > 1.9e-3)
+            .map(|(_, x)| x[out])
+            .collect();
+        let mean = settled.iter().sum::<f64>() / settled.len() as f64;
+        assert!(mean > 8.8 && mean < 9.6, "{}", mean);
+    }
+}
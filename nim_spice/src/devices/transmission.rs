@@ -0,0 +1,1042 @@
+//! Transmission lines: the lossless `T` element and the lossy `O` element
+//! of an `LTRA` model.
+//!
+//! A line is a two-port with a current of its own at each port,
+//! `I(Tname#1)` and `I(Tname#2)`, flowing into the port's positive
+//! terminal. In the time domain the first port's current is
+//!
+//! `i1 = y0 ⊛ v1 - h ⊛ (y0 ⊛ v2 + i2)`
+//!
+//! and the second's likewise, `y0` being the characteristic admittance and
+//! `h` the propagation of a wave from one end to the other. For a lossless
+//! line `y0` is `1/Z0` and `h` a delay of `TD`, so only the other port's
+//! history a delay ago enters. For an RLCG line both have tails, written
+//! with modified Bessel functions as in Roychowdhury and Pederson's
+//! convolution model, which are convolved with the whole history of the
+//! port voltages, taken to be linear between accepted points and at their
+//! operating point values before time zero. An RC line, without
+//! inductance, has no delay: `y0` is `√(C/R)` times the half derivative and
+//! `h` spreads a change out from the moment it is made, both integrated
+//! exactly over the linear pieces, the last of which reaches the other
+//! port's unknowns. At DC the line is the two-port of its series resistance
+//! and shunt conductance, and in the AC analysis the exact solution of the
+//! telegrapher's equations.
+//!
+//! No step may be longer than the delay, so that `h` only ever needs
+//! accepted points. Where a port's voltage turns a corner, a breakpoint is
+//! set a delay later, when the corner reaches the other end.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::timestep::Timestep;
+use crate::devices::generic::{add, entry, voltage, Device, LoadContext, Unknown, Unknowns};
+use crate::devices::model::{find_model, Model};
+use crate::devices::parameters::ElementLine;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::sparse::{Handle, Pattern, Scalar, SparseMatrix};
+use std::f64::consts::PI;
+
+/// Nodes and weights of four-point Gauss-Legendre quadrature on `[-1, 1]`.
+const GAUSS: [(f64, f64); 4] = [
+    (-0.861_136_311_594_052_6, 0.347_854_845_137_453_9),
+    (-0.339_981_043_584_856_3, 0.652_145_154_862_546_1),
+    (0.339_981_043_584_856_3, 0.652_145_154_862_546_1),
+    (0.861_136_311_594_052_6, 0.347_854_845_137_453_9),
+];
+
+/// A line's resistance, inductance, conductance and capacitance, each
+/// over its whole length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub resistance: f64,
+    pub inductance: f64,
+    pub conductance: f64,
+    pub capacitance: f64,
+}
+
+impl Line {
+    /// A lossless line of impedance `z0` and delay `delay`.
+    pub fn lossless(z0: f64, delay: f64) -> Self {
+        Line {
+            resistance: 0.0,
+            inductance: z0 * delay,
+            conductance: 0.0,
+            capacitance: delay / z0,
+        }
+    }
+
+    /// The characteristic admittance at high frequency, `√(C/L)`.
+    pub fn admittance(&self) -> f64 {
+        (self.capacitance / self.inductance).sqrt()
+    }
+
+    /// Whether the line has no inductance, so that a change diffuses along
+    /// it rather than travelling.
+    pub fn is_rc(&self) -> bool {
+        self.inductance == 0.0
+    }
+
+    /// `Y0(0)`, `√(G/R)`, or `√(C/L)` without loss.
+    fn dc_admittance(&self) -> f64 {
+        if self.resistance == 0.0 {
+            self.admittance()
+        } else {
+            (self.conductance / self.resistance).sqrt()
+        }
+    }
+
+    pub fn delay(&self) -> f64 {
+        (self.inductance * self.capacitance).sqrt()
+    }
+
+    /// `μ` and `ν`, the mean and half the difference of `R/L` and `G/C`,
+    /// the rates at which a wave decays and spreads.
+    fn rates(&self) -> (f64, f64) {
+        let a = self.resistance / self.inductance;
+        let b = self.conductance / self.capacitance;
+        ((a + b) / 2.0, (a - b) / 2.0)
+    }
+
+    /// Whether a wave changes shape on the way, unlike on a lossless or
+    /// distortionless line.
+    pub fn is_dispersive(&self) -> bool {
+        self.rates().1 != 0.0
+    }
+
+    /// The tail of `y0` after its impulse, over `√(C/L)`:
+    /// `ν e^(-μτ) (I1(ντ) - I0(ντ))`.
+    fn admittance_kernel(&self, tau: f64) -> f64 {
+        let (mu, nu) = self.rates();
+        let x = nu.abs() * tau;
+        nu * (-(mu - nu.abs()) * tau).exp() * (nu.signum() * i1e(x) - i0e(x))
+    }
+
+    /// The tail of `h` after its delayed impulse, for `τ > T`:
+    /// `ν T e^(-μτ) I1(ν√(τ² - T²)) / √(τ² - T²)`.
+    fn propagation_kernel(&self, tau: f64) -> f64 {
+        let (mu, nu) = self.rates();
+        let delay = self.delay();
+        let x = nu.abs() * (tau * tau - delay * delay).max(0.0).sqrt();
+        let ratio = if x < 1e-8 { 0.5 } else { i1e(x) / x };
+        nu * nu * delay * (x - mu * tau).exp() * ratio
+    }
+
+    /// The integral of [`Line::admittance_kernel`], `Y0(0) √(L/C) - 1`.
+    fn admittance_total(&self) -> f64 {
+        if self.resistance == 0.0 {
+            return 0.0;
+        }
+        (self.conductance * self.inductance / (self.resistance * self.capacitance)).sqrt() - 1.0
+    }
+
+    /// The integral of [`Line::propagation_kernel`], `H(0) - e^(-μT)`.
+    fn propagation_total(&self) -> f64 {
+        let (mu, _) = self.rates();
+        (-(self.resistance * self.conductance).sqrt()).exp() - (-mu * self.delay()).exp()
+    }
+
+    /// The integrals of an RC line's `h(τ) = a e^(-a²/τ) / (√π τ^(3/2))`,
+    /// `a = √(RC)/2`, and of `τ h(τ)` from zero to `tau`.
+    fn diffusion(&self, tau: f64) -> (f64, f64) {
+        let a = (self.resistance * self.capacitance).sqrt() / 2.0;
+        let tail = erfc(a / tau.sqrt());
+        let moment = 2.0 * a * (tau / PI).sqrt() * (-a * a / tau).exp() - 2.0 * a * a * tail;
+        (tail, moment)
+    }
+
+    /// The chain parameters `A = D`, `B` and `C` at DC, for
+    /// `v1 = A v2 - B i2` and `i1 = C v2 - D i2`.
+    fn dc(&self) -> [f64; 3] {
+        let theta = (self.resistance * self.conductance).sqrt();
+        let sinhc = if theta == 0.0 {
+            1.0
+        } else {
+            theta.sinh() / theta
+        };
+        [
+            theta.cosh(),
+            self.resistance * sinhc,
+            self.conductance * sinhc,
+        ]
+    }
+
+    /// `Y0` and `H` at angular frequency `omega`.
+    fn ac(&self, omega: f64) -> (Complex, Complex) {
+        let series = Complex::new(self.resistance, omega * self.inductance);
+        let shunt = Complex::new(self.conductance, omega * self.capacitance);
+        ((shunt / series).sqrt(), (-(series * shunt).sqrt()).exp())
+    }
+}
+
+/// `I0(x) e^-x` for `x ≥ 0`, after Abramowitz and Stegun 9.8.1 and 9.8.2.
+fn i0e(x: f64) -> f64 {
+    if x < 3.75 {
+        let y = (x / 3.75).powi(2);
+        let i0 = 1.0
+            + y * (3.515_622_9
+                + y * (3.089_942_4
+                    + y * (1.206_749_2 + y * (0.265_973_2 + y * (0.036_076_8 + y * 0.004_581_3)))));
+        return i0 * (-x).exp();
+    }
+    let y = 3.75 / x;
+    (0.398_942_28
+        + y * (0.013_285_92
+            + y * (0.002_253_19
+                + y * (-0.001_575_65
+                    + y * (0.009_162_81
+                        + y * (-0.020_577_06
+                            + y * (0.026_355_37 + y * (-0.016_476_33 + y * 0.003_923_77))))))))
+        / x.sqrt()
+}
+
+/// `I1(x) e^-x` for `x ≥ 0`, after Abramowitz and Stegun 9.8.3 and 9.8.4.
+fn i1e(x: f64) -> f64 {
+    if x < 3.75 {
+        let y = (x / 3.75).powi(2);
+        let i1 = x
+            * (0.5
+                + y * (0.878_905_94
+                    + y * (0.514_988_69
+                        + y * (0.150_849_34
+                            + y * (0.026_587_33 + y * (0.003_015_32 + y * 0.000_324_11))))));
+        return i1 * (-x).exp();
+    }
+    let y = 3.75 / x;
+    (0.398_942_28
+        + y * (-0.039_880_24
+            + y * (-0.003_620_18
+                + y * (0.001_638_01
+                    + y * (-0.010_315_55
+                        + y * (0.022_829_67
+                            + y * (-0.028_953_12 + y * (0.017_876_54 - y * 0.004_200_59))))))))
+        / x.sqrt()
+}
+
+/// `erfc(x)` for `x ≥ 0` to a relative 1.2e-7, after Numerical Recipes'
+/// Chebyshev fit.
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x);
+    t * (-x * x - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77)))))))))
+        .exp()
+}
+
+/// An accepted point of both ports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    time: f64,
+    voltages: [f64; 2],
+    /// `y0 ⊛ v + i` of each port, the wave it sends to the other end.
+    waves: [f64; 2],
+}
+
+/// What a transient load found of `y0 ⊛ v` at its time, `conductance v +
+/// history`, for the point accepted there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    time: f64,
+    conductance: f64,
+    history: [f64; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransmissionLine {
+    name: String,
+    /// The positive and negative terminals of the first port, then of the
+    /// second.
+    nodes: [Node; 4],
+    line: Line,
+    /// Relative and absolute change of a port's slope, in V/s, that makes
+    /// a corner.
+    corner: [f64; 2],
+    unknowns: [Unknown; 4],
+    branches: [usize; 2],
+    /// `(n+, br)` and `(n-, br)` of each port.
+    terminals: [[Option<Handle>; 2]; 2],
+    /// Each port's branch row in the columns `1+`, `1-`, `2+`, `2-`, `br1`
+    /// and `br2`.
+    rows: [[Option<Handle>; 6]; 2],
+    /// The accepted points a load may still need, the oldest first.
+    history: Vec<Point>,
+    step: Option<Step>,
+    /// Times at which corners seen at one end arrive at the other.
+    corners: Vec<f64>,
+}
+
+impl TransmissionLine {
+    pub fn new(name: impl Into<String>, nodes: [Node; 4], line: Line) -> Self {
+        TransmissionLine {
+            name: name.into(),
+            nodes,
+            line,
+            corner: [0.5, 1.0],
+            unknowns: [None; 4],
+            branches: [0; 2],
+            terminals: [[None; 2]; 2],
+            rows: [[None; 6]; 2],
+            history: Vec::new(),
+            step: None,
+            corners: Vec::new(),
+        }
+    }
+
+    /// `Tname n1+ n1- n2+ n2- Z0= TD=`, the delay also as a frequency
+    /// `F=` at which the line is `NL` wavelengths long, a quarter unless
+    /// given. `REL` and `ABS` set what makes a corner.
+    pub fn lossless(line: &ElementLine) -> Result<Self, String> {
+        line.unknown_parameters(&["z0", "zo", "td", "f", "nl", "rel", "abs"])?;
+        let nodes = line.nodes()?;
+        let z0 = match line.number("z0")? {
+            Some(z0) => z0,
+            None => line.number("zo")?.ok_or("No Z0 given")?,
+        };
+        let delay = match (line.number("td")?, line.number("f")?) {
+            (Some(delay), _) => delay,
+            (None, Some(frequency)) => line.number("nl")?.unwrap_or(0.25) / frequency,
+            (None, None) => return Err("Neither TD nor F given".to_string()),
+        };
+        if z0 <= 0.0 || !(delay > 0.0 && delay.is_finite()) {
+            return Err("Z0 and the delay must be positive".to_string());
+        }
+        let mut tline = TransmissionLine::new(line.name.clone(), nodes, Line::lossless(z0, delay));
+        tline.corner = [
+            line.number("rel")?.unwrap_or(tline.corner[0]),
+            line.number("abs")?.unwrap_or(tline.corner[1]),
+        ];
+        Ok(tline)
+    }
+
+    /// `Oname n1+ n1- n2+ n2- model` of an `LTRA` card with `R`, `L`, `G`
+    /// and `C` per unit length, `LEN` and the corner tolerances `REL` and
+    /// `ABS`. Without `L` the line is an RC line and takes no `G`. ngspice's choices of interpolation, step limiting and history
+    /// compaction are accepted and ignored: the history is convolved in
+    /// full, linearly interpolated, and steps never exceed the delay.
+    pub fn lossy(line: &ElementLine, models: &[Model]) -> Result<Self, String> {
+        line.unknown_parameters(&[])?;
+        let nodes = line.nodes()?;
+        let model = line.words.get(4).ok_or("No model given")?;
+        let model = find_model(models, model, &["LTRA"])?;
+        model.unknown_parameters(&[
+            "r",
+            "l",
+            "g",
+            "c",
+            "len",
+            "rel",
+            "abs",
+            "compactrel",
+            "compactabs",
+            "nocontrol",
+            "steplimit",
+            "nosteplimit",
+            "lininterp",
+            "quadinterp",
+            "mixedinterp",
+            "truncnr",
+            "truncdontcut",
+        ])?;
+        let value = |name: &str| -> Result<f64, String> { Ok(model.number(name)?.unwrap_or(0.0)) };
+        let length = model.number("len")?.unwrap_or(1.0);
+        let tline = Line {
+            resistance: value("r")? * length,
+            inductance: value("l")? * length,
+            conductance: value("g")? * length,
+            capacitance: value("c")? * length,
+        };
+        if tline.capacitance <= 0.0 || length <= 0.0 {
+            return Err(format!("Model {}: C and LEN must be positive", model.name));
+        }
+        if tline.resistance < 0.0 || tline.inductance < 0.0 || tline.conductance < 0.0 {
+            return Err(format!(
+                "Model {}: R, L and G cannot be negative",
+                model.name
+            ));
+        }
+        if tline.resistance == 0.0 && tline.conductance > 0.0 {
+            return Err(format!("Model {}: a line with G needs R", model.name));
+        }
+        if tline.is_rc() && (tline.resistance == 0.0 || tline.conductance > 0.0) {
+            return Err(format!(
+                "Model {}: a line without L needs R and no G",
+                model.name
+            ));
+        }
+        let mut lossy = TransmissionLine::new(line.name.clone(), nodes, tline);
+        lossy.corner = [
+            model.number("rel")?.unwrap_or(lossy.corner[0]),
+            model.number("abs")?.unwrap_or(lossy.corner[1]),
+        ];
+        Ok(lossy)
+    }
+
+    pub fn line(&self) -> &Line {
+        &self.line
+    }
+
+    /// The voltage across each port.
+    fn voltages(&self, solution: &[f64]) -> [f64; 2] {
+        let v = |node: usize| voltage(solution, self.unknowns[node]);
+        [v(0) - v(1), v(2) - v(3)]
+    }
+
+    fn stamp<T: Scalar>(&self, matrix: &mut SparseMatrix<T>, port: usize, values: [T; 6]) {
+        for (handle, value) in self.rows[port].iter().zip(values) {
+            add(matrix, *handle, value);
+        }
+    }
+
+    /// `signal` at `time`, linear between accepted points and held beyond
+    /// them.
+    fn interpolate(&self, time: f64, signal: impl Fn(&Point) -> f64) -> f64 {
+        let points = &self.history;
+        let index = points.partition_point(|p| p.time <= time);
+        if index == 0 {
+            return signal(&points[0]);
+        }
+        if index == points.len() {
+            return signal(&points[index - 1]);
+        }
+        let (a, b) = (&points[index - 1], &points[index]);
+        signal(a) + (signal(b) - signal(a)) * (time - a.time) / (b.time - a.time)
+    }
+
+    /// `∫ kernel(τ) x(time - τ) dτ` over `τ ≥ lower`, `x` being `signal`
+    /// linear between accepted points, held after the last and at the
+    /// first before it. `total` is the integral of the kernel over the
+    /// same range.
+    fn convolve(
+        &self,
+        kernel: impl Fn(f64) -> f64,
+        signal: impl Fn(&Point) -> f64,
+        time: f64,
+        lower: f64,
+        total: f64,
+    ) -> f64 {
+        let end = time - lower;
+        let (mut sum, mut covered) = (0.0, 0.0);
+        let mut segment = |from: f64, to: f64, x0: f64, x1: f64| {
+            let half = (to - from) / 2.0;
+            for (node, weight) in GAUSS {
+                let s = (node + 1.0) / 2.0;
+                let k = kernel(time - from - (to - from) * s) * weight * half;
+                sum += k * (x0 + (x1 - x0) * s);
+                covered += k;
+            }
+        };
+        let points = &self.history;
+        let last = &points[points.len() - 1];
+        if end > last.time {
+            segment(last.time, end, signal(last), signal(last));
+        }
+        for pair in points.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if a.time >= end {
+                break;
+            }
+            let to = b.time.min(end);
+            let x = signal(a) + (signal(b) - signal(a)) * (to - a.time) / (b.time - a.time);
+            segment(a.time, to, signal(a), x);
+        }
+        sum + signal(&points[0]) * (total - covered)
+    }
+
+    /// Stamp the branch rows of a transient step to `time`, from the
+    /// accepted history.
+    fn load_transient(&mut self, time: f64, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        if self.line.is_rc() {
+            self.load_rc(time, matrix, rhs);
+            return;
+        }
+        let line = self.line;
+        let (mu, _) = line.rates();
+        let (y, delay) = (line.admittance(), line.delay());
+        let last = self.history[self.history.len() - 1];
+        let h = time - last.time;
+        // The part of y0 ⊛ v over the step itself, split between the new
+        // voltage and the last accepted one.
+        let (mut weight, mut previous) = (0.0, 0.0);
+        if line.is_dispersive() {
+            for (node, w) in GAUSS {
+                let s = (node + 1.0) / 2.0;
+                let k = line.admittance_kernel(h * s) * w * h / 2.0;
+                weight += k * (1.0 - s);
+                previous += k * s;
+            }
+        }
+        let mut history = [0.0; 2];
+        for port in 0..2 {
+            let other = 1 - port;
+            let mut incident =
+                (-mu * delay).exp() * self.interpolate(time - delay, |p| p.waves[other]);
+            if line.is_dispersive() {
+                history[port] = previous * last.voltages[port]
+                    + self.convolve(
+                        |tau| line.admittance_kernel(tau),
+                        |p| p.voltages[port],
+                        time,
+                        h,
+                        line.admittance_total() - weight - previous,
+                    );
+                incident += self.convolve(
+                    |tau| line.propagation_kernel(tau),
+                    |p| p.waves[other],
+                    time,
+                    delay,
+                    line.propagation_total(),
+                );
+            }
+            let g = y * (1.0 + weight);
+            let mut values = [0.0; 6];
+            values[2 * port] = -g;
+            values[2 * port + 1] = g;
+            values[4 + port] = 1.0;
+            self.stamp(matrix, port, values);
+            rhs[self.branches[port]] += y * history[port] - incident;
+        }
+        self.step = Some(Step {
+            time,
+            conductance: y * (1.0 + weight),
+            history: history.map(|h| y * h),
+        });
+    }
+
+    /// Stamp the branch rows of a transient step of an RC line to `time`.
+    /// `y0 ⊛ v` sums the half derivatives of the pieces of `v`, and `h`
+    /// weighs the other port's wave by its integrals over each piece, the
+    /// last piece ending at that port's unknowns.
+    fn load_rc(&mut self, time: f64, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let line = self.line;
+        let scale = 2.0 * (line.capacitance / (PI * line.resistance)).sqrt();
+        let last = self.history[self.history.len() - 1];
+        let h = time - last.time;
+        let g = scale / h.sqrt();
+        let history = [0, 1].map(|port| {
+            let pieces: f64 = self
+                .history
+                .windows(2)
+                .map(|pair| {
+                    let (a, b) = (&pair[0], &pair[1]);
+                    let slope = (b.voltages[port] - a.voltages[port]) / (b.time - a.time);
+                    slope * ((time - a.time).sqrt() - (time - b.time).sqrt())
+                })
+                .sum();
+            scale * pieces - g * last.voltages[port]
+        });
+        let (tail, moment) = line.diffusion(h);
+        let coupling = tail - moment / h;
+        for port in 0..2 {
+            let other = 1 - port;
+            let incident = coupling * history[other]
+                + moment / h * last.waves[other]
+                + self.diffuse(|p| p.waves[other], time);
+            let mut values = [0.0; 6];
+            values[2 * port] = -g;
+            values[2 * port + 1] = g;
+            values[2 * other] = coupling * g;
+            values[2 * other + 1] = -coupling * g;
+            values[4 + port] = 1.0;
+            values[4 + other] = coupling;
+            self.stamp(matrix, port, values);
+            rhs[self.branches[port]] += history[port] - incident;
+        }
+        self.step = Some(Step {
+            time,
+            conductance: g,
+            history,
+        });
+    }
+
+    /// `∫ h(τ) x(time - τ) dτ` of an RC line over the accepted points, `x`
+    /// being `signal` linear between them and at the first before it.
+    fn diffuse(&self, signal: impl Fn(&Point) -> f64, time: f64) -> f64 {
+        let first = &self.history[0];
+        let mut sum = signal(first) * (1.0 - self.line.diffusion(time - first.time).0);
+        for pair in self.history.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            // x is α + βτ over τ from time - b.time to time - a.time.
+            let beta = (signal(a) - signal(b)) / (b.time - a.time);
+            let alpha = signal(a) - beta * (time - a.time);
+            let (near, far) = (
+                self.line.diffusion(time - b.time),
+                self.line.diffusion(time - a.time),
+            );
+            sum += alpha * (far.0 - near.0) + beta * (far.1 - near.1);
+        }
+        sum
+    }
+
+    /// Set a breakpoint a delay after the second newest point if either
+    /// port's voltage turned a corner there. Before the first point the
+    /// voltages were settled.
+    fn find_corners(&mut self) {
+        if self.line.is_rc() {
+            // Corners diffuse along an RC line rather than arrive.
+            return;
+        }
+        let (before, corner, after) = match self.history.as_slice() {
+            [.., a, b, c] => (Some(*a), *b, *c),
+            [b, c] => (None, *b, *c),
+            _ => return,
+        };
+        let [rel, abs] = self.corner;
+        for port in 0..2 {
+            let slope =
+                |a: &Point, b: &Point| (b.voltages[port] - a.voltages[port]) / (b.time - a.time);
+            let earlier = before.map_or(0.0, |a| slope(&a, &corner));
+            let later = slope(&corner, &after);
+            if (later - earlier).abs() > rel * earlier.abs().max(later.abs()) + abs {
+                let arrival = corner.time + self.line.delay();
+                if !self.corners.contains(&arrival) {
+                    self.corners.push(arrival);
+                }
+            }
+        }
+    }
+}
+
+impl Device for TransmissionLine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        self.unknowns = std::array::from_fn(|i| unknowns.node(&self.nodes[i]));
+        self.branches = [1, 2].map(|port| unknowns.branch(&format!("{}#{}", self.name, port)));
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [p1, n1, p2, n2] = self.unknowns;
+        let [b1, b2] = self.branches.map(Some);
+        self.terminals = [
+            [entry(pattern, p1, b1), entry(pattern, n1, b1)],
+            [entry(pattern, p2, b2), entry(pattern, n2, b2)],
+        ];
+        self.rows = [b1, b2].map(|row| [p1, n1, p2, n2, b1, b2].map(|c| entry(pattern, row, c)));
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        for [positive, negative] in self.terminals {
+            add(matrix, positive, 1.0);
+            add(matrix, negative, -1.0);
+        }
+        if context.is_transient() && !self.history.is_empty() {
+            self.load_transient(context.time, matrix, rhs);
+            return;
+        }
+        let [a, b, c] = self.line.dc();
+        self.stamp(matrix, 0, [1.0, -1.0, -a, a, 0.0, b]);
+        self.stamp(matrix, 1, [0.0, 0.0, -c, c, 1.0, a]);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        for [positive, negative] in self.terminals {
+            add(matrix, positive, Complex::from(1.0));
+            add(matrix, negative, Complex::from(-1.0));
+        }
+        if omega == 0.0 {
+            let [a, b, c] = self.line.dc().map(Complex::from);
+            let (zero, one) = (Complex::from(0.0), Complex::from(1.0));
+            self.stamp(matrix, 0, [one, -one, -a, a, zero, b]);
+            self.stamp(matrix, 1, [zero, zero, -c, c, one, a]);
+            return;
+        }
+        let (y0, h) = self.line.ac(omega);
+        for port in 0..2 {
+            let other = 1 - port;
+            let mut values = [Complex::from(0.0); 6];
+            values[2 * port] = -y0;
+            values[2 * port + 1] = y0;
+            values[2 * other] = h * y0;
+            values[2 * other + 1] = -(h * y0);
+            values[4 + port] = Complex::from(1.0);
+            values[4 + other] = h;
+            self.stamp(matrix, port, values);
+        }
+    }
+
+    fn accept(&mut self, solution: &[f64], time: f64) {
+        if self.history.last().is_some_and(|p| p.time >= time) {
+            // The operating point again, at the start of a transient.
+            self.history.clear();
+            self.corners.clear();
+        }
+        let voltages = self.voltages(solution);
+        let currents = self.branches.map(|b| solution[b]);
+        let waves = match self
+            .step
+            .filter(|s| s.time == time && !self.history.is_empty())
+        {
+            Some(step) => std::array::from_fn(|port| {
+                step.conductance * voltages[port] + step.history[port] + currents[port]
+            }),
+            None => {
+                let settled = self.line.dc_admittance();
+                std::array::from_fn(|port| settled * voltages[port] + currents[port])
+            }
+        };
+        self.history.push(Point {
+            time,
+            voltages,
+            waves,
+        });
+        self.find_corners();
+        self.corners.retain(|t| *t > time);
+        if !self.line.is_dispersive() {
+            // A delay ago is as far back as a lossless line looks.
+            let needed = time - self.line.delay();
+            let start = self.history.partition_point(|p| p.time <= needed);
+            self.history.drain(..start.saturating_sub(1));
+        }
+    }
+
+    /// No step may reach further than a delay past the last accepted
+    /// point, which an RC line does not have.
+    fn truncation(&self, _timestep: &Timestep, _integrator: &Integrator, _h: f64) -> f64 {
+        if self.line.is_rc() {
+            f64::INFINITY
+        } else {
+            self.line.delay()
+        }
+    }
+
+    fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        self.corners
+            .iter()
+            .copied()
+            .filter(|t| *t > from && *t <= to)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::options::Options;
+    use crate::devices::tests::{ac, circuit, operating_point, transient};
+
+    /// `V(node)` at `time`, interpolated between accepted points.
+    fn at(points: &[(f64, Vec<f64>)], index: usize, time: f64) -> f64 {
+        let after = points.partition_point(|(t, _)| *t < time).max(1);
+        let ((t0, a), (t1, b)) = (&points[after - 1], &points[after]);
+        a[index] + (b[index] - a[index]) * (time - t0) / (t1 - t0)
+    }
+
+    /// An RLCG line from `a` to `b` of `sections` lumped sections, each
+    /// series resistance and inductance followed by shunt capacitance and
+    /// conductance.
+    fn ladder(sections: usize, line: &Line) -> Vec<String> {
+        let n = sections as f64;
+        let node = |k: usize| match k {
+            0 => "a".to_string(),
+            k if k == sections => "b".to_string(),
+            k => format!("x{}", k),
+        };
+        let mut lines = Vec::new();
+        for k in 0..sections {
+            let (mut middle, to) = (node(k), node(k + 1));
+            if line.resistance > 0.0 {
+                let end = if line.is_rc() {
+                    to.clone()
+                } else {
+                    format!("y{}", k)
+                };
+                lines.push(format!("R{} {} {} {}", k, middle, end, line.resistance / n));
+                middle = end;
+            }
+            if !line.is_rc() {
+                lines.push(format!("L{} {} {} {}", k, middle, to, line.inductance / n));
+            }
+            lines.push(format!("C{} {} 0 {}", k, to, line.capacitance / n));
+            if line.conductance > 0.0 {
+                lines.push(format!("RG{} {} 0 {}", k, to, n / line.conductance));
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_kernels() {
+        // With G = 0 the admittance tail integrates to -1, since Y0(0) = 0,
+        // and the propagation tail to 1 - e^(-μT).
+        let line = Line {
+            resistance: 20.0,
+            inductance: 250e-9,
+            conductance: 0.0,
+            capacitance: 100e-12,
+        };
+        let (mu, _) = line.rates();
+        let delay = line.delay();
+        let (mut admittance, mut propagation) = (0.0, 0.0);
+        let step = 1e-10;
+        for k in 0..2_000_000 {
+            let tau = (k as f64 + 0.5) * step;
+            admittance += line.admittance_kernel(tau) * step;
+            propagation += line.propagation_kernel(delay + tau) * step;
+        }
+        assert!((line.admittance_total() + 1.0).abs() < 1e-15);
+        assert!((admittance + 1.0).abs() < 0.01, "{}", admittance);
+        let expected = 1.0 - (-mu * delay).exp();
+        assert!((line.propagation_total() - expected).abs() < 1e-15);
+        assert!((propagation - expected).abs() < 5e-3, "{}", propagation);
+        assert!((i0e(1.0) - 1.266_065_878 / 1f64.exp()).abs() < 1e-7);
+        assert!((i1e(5.0) - 24.335_642_14 / 5f64.exp()).abs() < 1e-7);
+        assert!((erfc(0.5) / 0.479_500_122_2 - 1.0).abs() < 2e-7);
+        assert!((erfc(2.0) / 0.004_677_734_981 - 1.0).abs() < 2e-7);
+    }
+
+    #[test]
+    fn test_dc() {
+        // A lossless line is a wire, and an RLCG one a distributed divider.
+        let (circuit, solution) = operating_point(&[
+            "V1 in 0 1",
+            "R1 in a 50",
+            "T1 a 0 b 0 Z0=50 TD=1n",
+            "R2 b 0 50",
+        ]);
+        let find = |name: &str| circuit.unknowns().find(name).unwrap();
+        assert!((solution[find("V(b)")] - 0.5).abs() < 1e-12);
+        assert!((solution[find("I(T1#1)")] - 0.01).abs() < 1e-12);
+        assert!((solution[find("I(T1#2)")] + 0.01).abs() < 1e-12);
+
+        let line = Line {
+            resistance: 10.0,
+            inductance: 1e-6,
+            conductance: 0.02,
+            capacitance: 1e-10,
+        };
+        let terminated = ["V1 in 0 1", "R1 in a 50", "R2 b 0 100"];
+        let (circuit, solution) = operating_point(
+            &[
+                &terminated[..],
+                &[
+                    "O1 a 0 b 0 LOSSY",
+                    ".model LOSSY LTRA(R=1 L=0.1u G=2m C=10p LEN=10)",
+                ],
+            ]
+            .concat(),
+        );
+        let distributed = solution[circuit.unknowns().find("V(b)").unwrap()];
+        let mut lumped = terminated.map(String::from).to_vec();
+        lumped.extend(ladder(200, &line));
+        let lumped: Vec<&str> = lumped.iter().map(String::as_str).collect();
+        let (circuit, solution) = operating_point(&lumped);
+        let expected = solution[circuit.unknowns().find("V(b)").unwrap()];
+        assert!(
+            (distributed / expected - 1.0).abs() < 1e-4,
+            "{} {}",
+            distributed,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_lossless_transient() {
+        let mut line = circuit(&[
+            "V1 in 0 PULSE(0 1 0 0.1n 0.1n 20n 50n)",
+            "R1 in a 50",
+            "T1 a 0 b 0 Z0=50 TD=2n",
+            "R2 b 0 1meg",
+        ]);
+        let points = transient(&mut line, ".tran 0.05n 7n", &Options::default());
+        let a = line.unknowns().find("V(a)").unwrap();
+        let b = line.unknowns().find("V(b)").unwrap();
+        // Half the step enters the line, doubles at the open end two
+        // nanoseconds later and is back at the start after four.
+        assert!(at(&points, b, 1.9e-9).abs() < 1e-6);
+        assert!((at(&points, a, 1.0e-9) - 0.5).abs() < 1e-6);
+        assert!((at(&points, b, 3.0e-9) - 1.0).abs() < 1e-4);
+        assert!((at(&points, a, 3.0e-9) - 0.5).abs() < 1e-4);
+        assert!((at(&points, a, 5.0e-9) - 1.0).abs() < 1e-4);
+        // The end of the rise, a corner, arrives as a breakpoint.
+        for arrival in [2.1e-9, 4.1e-9] {
+            assert!(points.iter().any(|(t, _)| (t - arrival).abs() < 1e-15));
+        }
+    }
+
+    #[test]
+    fn test_lossy_transient() {
+        let line = Line {
+            resistance: 20.0,
+            inductance: 250e-9,
+            conductance: 0.0,
+            capacitance: 100e-12,
+        };
+        let terminated = [
+            "V1 in 0 PULSE(0 1 0 0.2n 0.2n 20n 50n)",
+            "R1 in a 50",
+            "R2 b 0 50",
+        ];
+        let mut distributed = circuit(
+            &[
+                &terminated[..],
+                &["O1 a 0 b 0 LOSSY", ".model LOSSY LTRA(R=20 L=250n C=100p)"],
+            ]
+            .concat(),
+        );
+        let mut lumped = terminated.map(String::from).to_vec();
+        lumped.extend(ladder(200, &line));
+        let lumped: Vec<&str> = lumped.iter().map(String::as_str).collect();
+        let mut lumped = circuit(&lumped);
+        let tran = ".tran 0.05n 14n";
+        let options = Options::default();
+        let points = transient(&mut distributed, tran, &options);
+        let expected = transient(&mut lumped, tran, &options);
+        let b = distributed.unknowns().find("V(b)").unwrap();
+        let lumped_b = lumped.unknowns().find("V(b)").unwrap();
+        assert!(at(&points, b, 4.5e-9).abs() < 1e-6);
+        for time in [6e-9, 8e-9, 12e-9, 14e-9] {
+            let (v, reference) = (at(&points, b, time), at(&expected, lumped_b, time));
+            assert!((v - reference).abs() < 5e-3, "{} {} {}", time, v, reference);
+        }
+        // The edge arrives attenuated by e^(-RT/2L) at most.
+        let attenuation = (-line.rates().0 * line.delay()).exp();
+        assert!(at(&points, b, 5.5e-9) > 0.5 * attenuation * 0.95);
+    }
+
+    #[test]
+    fn test_rc_transient() {
+        // An RC line against a fine ladder, at its delay-free start too.
+        let line = Line {
+            resistance: 1e3,
+            inductance: 0.0,
+            conductance: 0.0,
+            capacitance: 1e-9,
+        };
+        let terminated = [
+            "V1 in 0 PULSE(0 1 0.5u 10n 10n 3u 10u)",
+            "R1 in a 100",
+            "R2 b 0 10k",
+        ];
+        let mut distributed = circuit(
+            &[
+                &terminated[..],
+                &["O1 a 0 b 0 RC", ".model RC LTRA(R=100 C=0.1n LEN=10)"],
+            ]
+            .concat(),
+        );
+        let mut lumped = terminated.map(String::from).to_vec();
+        lumped.extend(ladder(200, &line));
+        let lumped: Vec<&str> = lumped.iter().map(String::as_str).collect();
+        let mut lumped = circuit(&lumped);
+        let tran = ".tran 10n 6u";
+        let options = Options::default();
+        let points = transient(&mut distributed, tran, &options);
+        let expected = transient(&mut lumped, tran, &options);
+        for node in ["V(a)", "V(b)"] {
+            let index = distributed.unknowns().find(node).unwrap();
+            let lumped_index = lumped.unknowns().find(node).unwrap();
+            for time in [0.6e-6, 0.8e-6, 1.5e-6, 3e-6, 4e-6, 6e-6] {
+                let (v, reference) = (at(&points, index, time), at(&expected, lumped_index, time));
+                assert!(
+                    (v - reference).abs() < 5e-3,
+                    "{} at {}: {} {}",
+                    node,
+                    time,
+                    v,
+                    reference
+                );
+            }
+        }
+        // Settled through R and the termination.
+        let b = distributed.unknowns().find("V(b)").unwrap();
+        assert!((at(&points, b, 3.4e-6) - 10.0 / 11.1).abs() < 5e-3);
+    }
+
+    #[test]
+    fn test_ac() {
+        // A mismatched lossless line against its input impedance.
+        let omega = 2.0 * std::f64::consts::PI * 60e6;
+        let mut tline = circuit(&[
+            "V1 in 0 0 AC 1",
+            "R1 in a 50",
+            "T1 a 0 b 0 Z0=75 TD=1n",
+            "R2 b 0 100",
+        ]);
+        let response = ac(&mut tline, omega);
+        let tan = Complex::new(0.0, (omega * 1e-9).tan());
+        let (z0, load) = (Complex::from(75.0), Complex::from(100.0));
+        let zin = z0 * (load + z0 * tan) / (z0 + load * tan);
+        let expected = zin / (zin + Complex::from(50.0));
+        let a = tline.unknowns().find("V(a)").unwrap();
+        assert!((response[a] - expected).norm() < 1e-12);
+
+        // A lossy one against a fine ladder, which is only as close as its
+        // sections are short.
+        let line = Line {
+            resistance: 20.0,
+            inductance: 250e-9,
+            conductance: 1e-3,
+            capacitance: 100e-12,
+        };
+        let terminated = ["V1 in 0 0 AC 1", "R1 in a 50", "R2 b 0 100"];
+        let mut distributed = circuit(
+            &[
+                &terminated[..],
+                &[
+                    "O1 a 0 b 0 LOSSY",
+                    ".model LOSSY LTRA(R=2 L=25n G=0.1m C=10p LEN=10)",
+                ],
+            ]
+            .concat(),
+        );
+        let mut lumped = terminated.map(String::from).to_vec();
+        lumped.extend(ladder(200, &line));
+        let lumped: Vec<&str> = lumped.iter().map(String::as_str).collect();
+        let mut lumped = circuit(&lumped);
+        let omega = 2.0 * std::f64::consts::PI * 50e6;
+        let b = distributed.unknowns().find("V(b)").unwrap();
+        let lumped_b = lumped.unknowns().find("V(b)").unwrap();
+        let (v, reference) = (
+            ac(&mut distributed, omega)[b],
+            ac(&mut lumped, omega)[lumped_b],
+        );
+        assert!(
+            (v - reference).norm() < 2e-3 * reference.norm(),
+            "{} {}",
+            v,
+            reference
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |text: &str| {
+            let (name, rest) = text.split_once(' ').unwrap();
+            let component =
+                crate::frontend::parser::netlist::Component::new(name, Vec::new(), rest);
+            TransmissionLine::lossless(&ElementLine::new(&component))
+        };
+        let quarter = parse("T1 a 0 b 0 Z0=50 F=250Meg").unwrap();
+        assert!((quarter.line().delay() - 1e-9).abs() < 1e-21);
+        let half = parse("T1 a 0 b 0 ZO=50 F=250Meg NL=0.5").unwrap();
+        assert!((half.line().admittance() - 0.02).abs() < 1e-15);
+        assert!((half.line().delay() - 2e-9).abs() < 1e-21);
+        assert_eq!(
+            parse("T1 a 0 b 0 Z0=50").unwrap_err(),
+            "Neither TD nor F given"
+        );
+        assert_eq!(parse("T1 a 0 b 0 TD=1n").unwrap_err(), "No Z0 given");
+
+        let lossy = |card: &str| {
+            let component =
+                crate::frontend::parser::netlist::Component::new("O1", Vec::new(), "a 0 b 0 LINE");
+            let model = Model::parse(card).unwrap();
+            TransmissionLine::lossy(&ElementLine::new(&component), &[model])
+        };
+        assert!(lossy(".model LINE LTRA(R=1 C=1p)").unwrap().line().is_rc());
+        assert_eq!(
+            lossy(".model LINE LTRA(R=1 G=1m C=1p)").unwrap_err(),
+            "Model LINE: a line without L needs R and no G"
+        );
+        assert_eq!(
+            lossy(".model LINE LTRA(L=1n)").unwrap_err(),
+            "Model LINE: C and LEN must be positive"
+        );
+    }
+}
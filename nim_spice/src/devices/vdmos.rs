@@ -0,0 +1,673 @@
+//! LTspice's vertical double diffused power MOSFET, `VDMOS`.
+//!
+//! The channel is a square law in `VTO` and `KP` without body effect,
+//! with `LAMBDA`, mobility degradation `THETA` and `MTRIODE` scaling the
+//! triode region's conductance, and an optional exponential subthreshold
+//! tail of slope `KSUBTHRES`. The gate to drain capacitance rises from
+//! `CGDMIN` with the drain well above the gate to `CGDMAX` with the gate
+//! above the drain, along LTspice's `tanh` and `atan` curves of steepness
+//! `A`, and its charge is their integral. `CGS` is linear. The body diode
+//! from source to drain is an ordinary [`Diode`] of the card's `IS`, `N`,
+//! `RB`, `CJO`, `TT`, `BV` and the rest, and `RDS` a [`Resistor`] across
+//! it. The 4th node of a subcircuit's `M` line, where vendors put the
+//! case or a thermal node, is accepted and left unconnected.
+
+use crate::analysis::integration::Integrator;
+use crate::analysis::options::Options;
+use crate::analysis::timestep::{Quantity, Timestep};
+use crate::devices::diode::{Diode, DiodeModel};
+use crate::devices::generic::{
+    current, flicker_noise, thermal_noise, voltage, Conductance, Device, LoadContext, NoiseSource,
+    State, Unknown, Unknowns, KELVIN,
+};
+use crate::devices::limiting::{fetlim, limvds};
+use crate::devices::model::Model;
+use crate::devices::parameters::ElementLine;
+use crate::devices::resistor::Resistor;
+use crate::frontend::parser::netlist::Node;
+use crate::maths::complex::Complex;
+use crate::maths::dual::{Dual, Real};
+use crate::maths::sparse::{Pattern, SparseMatrix};
+
+/// The power MOSFET of `line`, whose model `model` is its `index`th word,
+/// with its body diode and `RDS`.
+pub fn build(
+    line: &ElementLine,
+    model: &Model,
+    index: usize,
+) -> Result<Vec<Box<dyn Device>>, String> {
+    line.unknown_parameters(&["m", "ic", "temp", "dtemp"])?;
+    let [drain, gate, source] = line.nodes()?;
+    let vdmos = VdmosModel::new(model)?;
+    let multiplier = line.number("m")?.unwrap_or(1.0);
+    if multiplier <= 0.0 {
+        return Err("The multiplier must be positive".to_string());
+    }
+    if line.words.len() > index + 1 && !line.flag("off") {
+        return Err(format!("Unexpected {}", line.words[index + 1]));
+    }
+    let initial = match line.numbers("ic")?.as_slice() {
+        [] => None,
+        [vds] => Some([*vds, 0.0]),
+        [vds, vgs] => Some([*vds, *vgs]),
+        _ => return Err("Expected ic=vds,vgs".to_string()),
+    };
+    let temp = line.number("temp")?;
+    let dtemp = line.number("dtemp")?.unwrap_or(0.0);
+
+    let mut devices: Vec<Box<dyn Device>> = Vec::new();
+    let (anode, cathode) = if vdmos.polarity > 0.0 {
+        (source.clone(), drain.clone())
+    } else {
+        (drain.clone(), source.clone())
+    };
+    let mut diode = Diode::new(
+        &format!("{}.D", line.name),
+        anode,
+        cathode,
+        body_diode(model)?,
+    );
+    diode.set_instance(multiplier, temp, dtemp);
+    devices.push(Box::new(diode));
+    if let Some(rds) = model.number("rds")?.filter(|rds| *rds > 0.0) {
+        let name = format!("{}.Rds", line.name);
+        devices.push(Box::new(Resistor::new(
+            name,
+            drain.clone(),
+            source.clone(),
+            rds / multiplier,
+        )));
+    }
+    devices.insert(
+        0,
+        Box::new(Vdmos {
+            name: line.name.clone(),
+            nodes: [drain, gate, source],
+            model: vdmos,
+            multiplier,
+            initial,
+            temp,
+            dtemp,
+            scaled: Scaled::default(),
+            unknowns: [None; 6],
+            stamps: [Conductance::default(); 7],
+            charges: Default::default(),
+            bias: Bias::default(),
+        }),
+    );
+    Ok(devices)
+}
+
+/// The body diode's parameters from those of a `VDMOS` card.
+fn body_diode(model: &Model) -> Result<DiodeModel, String> {
+    let d = DiodeModel::default();
+    let value = |name: &str, default: f64| -> Result<f64, String> {
+        Ok(model.number(name)?.unwrap_or(default))
+    };
+    let n = value("n", d.n)?;
+    Ok(DiodeModel {
+        is: value("is", d.is)?,
+        n,
+        rs: value("rb", 0.0)?,
+        tt: value("tt", d.tt)?,
+        cjo: model.alias(&["cjo", "cj0"])?.unwrap_or(d.cjo),
+        vj: value("vj", d.vj)?,
+        m: value("m", d.m)?,
+        fc: value("fc", d.fc)?,
+        eg: value("eg", d.eg)?,
+        xti: value("xti", d.xti)?,
+        bv: model.number("bv")?.filter(|bv| *bv != 0.0),
+        ibv: value("ibv", d.ibv)?,
+        nbv: value("nbv", n)?,
+        tnom: model.alias(&["tnom", "tref"])?,
+        ..d
+    })
+}
+
+/// Parameters of a `VDMOS` model, an n channel unless flagged `pchan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VdmosModel {
+    /// 1 for an n channel, -1 for a p channel.
+    pub polarity: f64,
+    pub vto: f64,
+    pub kp: f64,
+    pub lambda: f64,
+    pub theta: f64,
+    pub mtriode: f64,
+    /// Slope of the subthreshold current, a square law cut off at `VTO`
+    /// without.
+    pub ksubthres: Option<f64>,
+    pub rd: f64,
+    pub rs: f64,
+    pub rg: f64,
+    pub cgdmax: f64,
+    pub cgdmin: f64,
+    pub a: f64,
+    pub cgs: f64,
+    pub kf: f64,
+    pub af: f64,
+    /// Threshold fall per K.
+    pub tcvth: f64,
+    /// Exponent of `KP`'s temperature dependence.
+    pub mu: f64,
+    pub trd1: f64,
+    pub trd2: f64,
+    pub trs1: f64,
+    pub trs2: f64,
+    pub trg1: f64,
+    pub trg2: f64,
+    /// Temperature the parameters were measured at in °C, `.options tnom`
+    /// unless given.
+    pub tnom: Option<f64>,
+}
+
+impl VdmosModel {
+    pub fn new(model: &Model) -> Result<Self, String> {
+        model.unknown_parameters(&[
+            "vto",
+            "vt0",
+            "kp",
+            "lambda",
+            "theta",
+            "mtriode",
+            "ksubthres",
+            "subthres",
+            "rd",
+            "rs",
+            "rg",
+            "rds",
+            "cgdmax",
+            "cgdmin",
+            "a",
+            "cgs",
+            "is",
+            "n",
+            "rb",
+            "tt",
+            "cjo",
+            "cj0",
+            "vj",
+            "m",
+            "fc",
+            "eg",
+            "xti",
+            "bv",
+            "ibv",
+            "nbv",
+            "kf",
+            "af",
+            "tcvth",
+            "mu",
+            "trd1",
+            "trd2",
+            "trs1",
+            "trs2",
+            "trg1",
+            "trg2",
+            "tnom",
+            "tref",
+            "nchan",
+            "pchan",
+        ])?;
+        let value = |names: &[&str], default: f64| -> Result<f64, String> {
+            Ok(model.alias(names)?.unwrap_or(default))
+        };
+        let vdmos = VdmosModel {
+            polarity: if model.flag("pchan") { -1.0 } else { 1.0 },
+            vto: value(&["vto", "vt0"], 0.0)?,
+            kp: value(&["kp"], 1.0)?,
+            lambda: value(&["lambda"], 0.0)?,
+            theta: value(&["theta"], 0.0)?,
+            mtriode: value(&["mtriode"], 1.0)?,
+            ksubthres: model.alias(&["ksubthres", "subthres"])?,
+            rd: value(&["rd"], 0.0)?,
+            rs: value(&["rs"], 0.0)?,
+            rg: value(&["rg"], 0.0)?,
+            cgdmax: value(&["cgdmax"], 0.0)?,
+            cgdmin: value(&["cgdmin"], 0.0)?,
+            a: value(&["a"], 1.0)?,
+            cgs: value(&["cgs"], 0.0)?,
+            kf: value(&["kf"], 0.0)?,
+            af: value(&["af"], 1.0)?,
+            tcvth: value(&["tcvth"], 0.0)?,
+            mu: value(&["mu"], -1.5)?,
+            trd1: value(&["trd1"], 0.0)?,
+            trd2: value(&["trd2"], 0.0)?,
+            trs1: value(&["trs1"], 0.0)?,
+            trs2: value(&["trs2"], 0.0)?,
+            trg1: value(&["trg1"], 0.0)?,
+            trg2: value(&["trg2"], 0.0)?,
+            tnom: model.alias(&["tnom", "tref"])?,
+        };
+        if vdmos.kp <= 0.0 || vdmos.mtriode <= 0.0 || vdmos.a <= 0.0 {
+            return Err(format!(
+                "Model {}: KP, MTRIODE and A must be positive",
+                model.name
+            ));
+        }
+        if vdmos.ksubthres.is_some_and(|k| k <= 0.0) {
+            return Err(format!("Model {}: KSUBTHRES must be positive", model.name));
+        }
+        if vdmos.cgdmin > vdmos.cgdmax {
+            return Err(format!("Model {}: CGDMIN exceeds CGDMAX", model.name));
+        }
+        Ok(vdmos)
+    }
+
+    /// The gate to drain charge and capacitance at `vgd`, for an n
+    /// channel.
+    pub fn gate_drain(&self, vgd: f64) -> (f64, f64) {
+        let s = (self.cgdmax - self.cgdmin) / (1.0 + std::f64::consts::FRAC_PI_2);
+        let y = self.cgdmax - s;
+        let x = self.a * vgd;
+        if vgd > 0.0 {
+            // ln cosh x, kept finite for large x.
+            let ln_cosh = x - std::f64::consts::LN_2 + (-2.0 * x).exp().ln_1p();
+            (s * ln_cosh / self.a + y * vgd, s * x.tanh() + y)
+        } else {
+            let integral = vgd * x.atan() - (x * x).ln_1p() / (2.0 * self.a);
+            (s * integral + y * vgd, s * x.atan() + y)
+        }
+    }
+}
+
+/// The model's parameters at the instance temperature and multiplier.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scaled {
+    temperature: f64,
+    /// Threshold as for an n channel.
+    vto: f64,
+    kp: f64,
+    /// Conductances of `RD`, `RS` and `RG`, zero without.
+    gd: f64,
+    gs: f64,
+    gg: f64,
+}
+
+/// The linearisation at a bias point, as for an n channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bias {
+    vgs: f64,
+    vds: f64,
+    ids: f64,
+    gm: f64,
+    gds: f64,
+    qgd: f64,
+    cgd: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vdmos {
+    name: String,
+    /// Drain, gate and source.
+    nodes: [Node; 3],
+    model: VdmosModel,
+    multiplier: f64,
+    /// `vds` and `vgs` to start from with `uic`.
+    initial: Option<[f64; 2]>,
+    temp: Option<f64>,
+    dtemp: f64,
+    scaled: Scaled,
+    /// Drain, gate and source, and the same inside `RD`, `RG` and `RS`.
+    unknowns: [Unknown; 6],
+    /// Drain, gate and source resistances, the channel's conductance and
+    /// transconductance, and the gate capacitances to source and drain.
+    stamps: [Conductance; 7],
+    /// Gate to source and gate to drain.
+    charges: [State; 2],
+    bias: Bias,
+}
+
+impl Vdmos {
+    /// Drain current at the last load.
+    pub fn drain_current(&self) -> f64 {
+        self.model.polarity * self.bias.ids
+    }
+
+    /// `vgs` and `vds` of `solution`, as for an n channel.
+    fn voltages(&self, solution: &[f64]) -> [f64; 2] {
+        let [_, _, _, d, g, s] = self.unknowns;
+        let v = |node: Unknown| voltage(solution, node);
+        let p = self.model.polarity;
+        [p * (v(g) - v(s)), p * (v(d) - v(s))]
+    }
+
+    /// The channel current from drain to source for `vds ≥ 0`.
+    fn channel<T: Real>(&self, vgs: T, vds: T) -> T {
+        let m = &self.model;
+        let mut vgst = vgs - self.scaled.vto;
+        match m.ksubthres {
+            Some(k) if vgst.value() < 40.0 * k => vgst = ((vgst / k).exp() + 1.0).ln() * k,
+            Some(_) => {}
+            None if vgst.value() <= 0.0 => return T::constant(0.0),
+            None => {}
+        }
+        let beta = (vds * m.lambda + 1.0) / (vgst * m.theta + 1.0) * self.scaled.kp;
+        if vds.value() * m.mtriode >= vgst.value() {
+            beta * vgst * vgst * 0.5
+        } else {
+            beta * vds * m.mtriode * (vgst - vds * (0.5 * m.mtriode))
+        }
+    }
+
+    fn evaluate(&self, vgs: f64, vds: f64) -> Bias {
+        let x = [Dual::<2>::variable(vgs, 0), Dual::variable(vds, 1)];
+        let ids = if vds >= 0.0 {
+            self.channel(x[0], x[1])
+        } else {
+            -self.channel(x[0] - x[1], -x[1])
+        };
+        let (qgd, cgd) = self.model.gate_drain(vgs - vds);
+        let scale = self.multiplier;
+        Bias {
+            vgs,
+            vds,
+            ids: ids.value,
+            gm: ids.derivative(0),
+            gds: ids.derivative(1),
+            qgd: qgd * scale,
+            cgd: cgd * scale,
+        }
+    }
+}
+
+impl Device for Vdmos {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn allocate(&mut self, unknowns: &mut Unknowns) {
+        let [drain, gate, source] = self.nodes.each_ref().map(|n| unknowns.node(n));
+        let m = &self.model;
+        let mut inner = |outer: Unknown, r: f64, name: &str| {
+            if r > 0.0 {
+                Some(unknowns.internal(&self.name, name))
+            } else {
+                outer
+            }
+        };
+        let d = inner(drain, m.rd, "d");
+        let g = inner(gate, m.rg, "g");
+        let s = inner(source, m.rs, "s");
+        self.unknowns = [drain, gate, source, d, g, s];
+    }
+
+    fn setup(&mut self, pattern: &mut Pattern) {
+        let [drain, gate, source, d, g, s] = self.unknowns;
+        self.stamps = [
+            Conductance::new(pattern, drain, d),
+            Conductance::new(pattern, gate, g),
+            Conductance::new(pattern, source, s),
+            Conductance::new(pattern, d, s),
+            Conductance::controlled(pattern, d, s, g, s),
+            Conductance::new(pattern, g, s),
+            Conductance::new(pattern, g, d),
+        ];
+    }
+
+    fn temperature(&mut self, temperature: f64, nominal: f64) {
+        let m = &self.model;
+        let celsius = self.temp.unwrap_or(temperature + self.dtemp);
+        let tnom = m.tnom.unwrap_or(nominal);
+        let dt = celsius - tnom;
+        let ratio = (celsius + KELVIN) / (tnom + KELVIN);
+        let conductance = |r: f64, tc1: f64, tc2: f64| {
+            let r = r * (1.0 + tc1 * dt + tc2 * dt * dt);
+            if r > 0.0 {
+                self.multiplier / r
+            } else {
+                0.0
+            }
+        };
+        self.scaled = Scaled {
+            temperature: celsius,
+            vto: m.polarity * m.vto - m.tcvth * dt,
+            kp: m.kp * ratio.powf(m.mu) * self.multiplier,
+            gd: conductance(m.rd, m.trd1, m.trd2),
+            gs: conductance(m.rs, m.trs1, m.trs2),
+            gg: conductance(m.rg, m.trg1, m.trg2),
+        };
+    }
+
+    fn load(&mut self, context: &mut LoadContext, matrix: &mut SparseMatrix, rhs: &mut [f64]) {
+        let [new_vgs, new_vds] = self.voltages(context.solution);
+        let old = self.bias;
+        let vto = self.scaled.vto;
+        let (vgs, vds) = if old.vds >= 0.0 {
+            (fetlim(new_vgs, old.vgs, vto), limvds(new_vds, old.vds))
+        } else {
+            let vgd = fetlim(new_vgs - new_vds, old.vgs - old.vds, vto);
+            let vds = -limvds(-new_vds, -old.vds);
+            (vgd + vds, vds)
+        };
+        if vgs != new_vgs || vds != new_vds {
+            context.limit(&self.name);
+        }
+        let bias = self.evaluate(vgs, vds);
+        self.bias = bias;
+
+        let cgs = self.model.cgs * self.multiplier;
+        let vgd = vgs - vds;
+        let mut gate = [0.0; 2];
+        let mut gate_currents = [0.0; 2];
+        let [qgs, qgd] = &mut self.charges;
+        match context.integrator {
+            Some(integrator) => {
+                for (i, (charge, q, c, v)) in
+                    [(qgs, cgs * vgs, cgs, vgs), (qgd, bias.qgd, bias.cgd, vgd)]
+                        .into_iter()
+                        .enumerate()
+                {
+                    let companion = charge.integrate(integrator, q, c, v);
+                    gate[i] = companion.geq;
+                    gate_currents[i] = companion.ceq;
+                }
+            }
+            None => {
+                qgs.reset(cgs * vgs);
+                qgd.reset(bias.qgd);
+            }
+        }
+
+        let st = &self.stamps;
+        let sc = &self.scaled;
+        st[0].stamp(matrix, sc.gd);
+        st[1].stamp(matrix, sc.gg);
+        st[2].stamp(matrix, sc.gs);
+        st[3].stamp(matrix, bias.gds);
+        st[4].stamp(matrix, bias.gm);
+        st[5].stamp(matrix, gate[0]);
+        st[6].stamp(matrix, gate[1]);
+
+        let p = self.model.polarity;
+        let [_, _, _, d, g, s] = self.unknowns;
+        current(rhs, d, s, p * (bias.ids - bias.gm * vgs - bias.gds * vds));
+        current(rhs, g, s, p * gate_currents[0]);
+        current(rhs, g, d, p * gate_currents[1]);
+    }
+
+    fn ac_load(&self, omega: f64, matrix: &mut SparseMatrix<Complex>, _rhs: &mut [Complex]) {
+        let st = &self.stamps;
+        let sc = &self.scaled;
+        let b = &self.bias;
+        st[0].stamp(matrix, Complex::from(sc.gd));
+        st[1].stamp(matrix, Complex::from(sc.gg));
+        st[2].stamp(matrix, Complex::from(sc.gs));
+        st[3].stamp(matrix, Complex::from(b.gds));
+        st[4].stamp(matrix, Complex::from(b.gm));
+        let cgs = self.model.cgs * self.multiplier;
+        st[5].stamp(matrix, Complex::new(0.0, omega * cgs));
+        st[6].stamp(matrix, Complex::new(0.0, omega * b.cgd));
+    }
+
+    fn noise(&self, frequency: f64) -> Vec<NoiseSource> {
+        let [drain, gate, source, d, g, s] = self.unknowns;
+        let sc = &self.scaled;
+        let m = &self.model;
+        let mut sources = Vec::new();
+        for (name, outer, inner, conductance) in [
+            ("rd", drain, d, sc.gd),
+            ("rg", gate, g, sc.gg),
+            ("rs", source, s, sc.gs),
+        ] {
+            if outer != inner {
+                sources.push(NoiseSource {
+                    name: format!("{} {}", self.name, name),
+                    positive: outer,
+                    negative: inner,
+                    density: thermal_noise(conductance, sc.temperature),
+                });
+            }
+        }
+        sources.push(NoiseSource {
+            name: format!("{} channel", self.name),
+            positive: d,
+            negative: s,
+            density: thermal_noise(2.0 / 3.0 * self.bias.gm, sc.temperature),
+        });
+        if m.kf > 0.0 {
+            sources.push(NoiseSource {
+                name: format!("{} flicker", self.name),
+                positive: d,
+                negative: s,
+                density: flicker_noise(m.kf, m.af, 1.0, self.bias.ids, frequency),
+            });
+        }
+        sources
+    }
+
+    fn initial_conditions(&mut self) {
+        if let Some([vds, vgs]) = self.initial {
+            let p = self.model.polarity;
+            self.bias = self.evaluate(p * vgs, p * vds);
+            self.charges[0].reset(self.model.cgs * self.multiplier * p * vgs);
+            self.charges[1].reset(self.bias.qgd);
+        }
+    }
+
+    fn accept(&mut self, _solution: &[f64], _time: f64) {
+        for charge in &mut self.charges {
+            charge.accept();
+        }
+    }
+
+    fn truncation(&self, timestep: &Timestep, integrator: &Integrator, h: f64) -> f64 {
+        self.charges
+            .iter()
+            .map(|charge| charge.allowed_step(timestep, integrator, h, Quantity::Charge))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The drain current predicted by the linearisation must agree with
+    /// that at the new voltages.
+    fn unconverged(&self, solution: &[f64], options: &Options) -> bool {
+        let b = &self.bias;
+        let [vgs, vds] = self.voltages(solution);
+        let predicted = b.ids + b.gm * (vgs - b.vgs) + b.gds * (vds - b.vds);
+        let actual = self.evaluate(vgs, vds).ids;
+        (predicted - actual).abs()
+            > options.reltol * predicted.abs().max(actual.abs()) + options.abstol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::generic::{BOLTZMANN, CHARGE};
+    use crate::devices::tests::{ac, circuit, operating_point};
+
+    const CARD: &str = ".model M1 VDMOS(Rd=10m Rs=5m Rg=2 Vto=4 Kp=10 Lambda=0.01 \
+        Cgdmax=2n Cgdmin=100p A=0.5 Cgs=1.5n Cjo=1n Is=10p Rb=10m mfg=Acme Vds=100 Ron=25m Qg=30n)";
+
+    #[test]
+    fn test_channel() {
+        let (circuit, solution) = operating_point(&["V1 g 0 6", "V2 d 0 10", "M1 d g 0 M1", CARD]);
+        let id = -solution[circuit.unknowns().find("I(V2)").unwrap()];
+        // RS takes its drop off the gate drive, RD and RS theirs off vds.
+        let expected = 5.0 * (2.0 - id * 0.005).powi(2) * (1.0 + 0.01 * (10.0 - id * 0.015));
+        assert!((id / expected - 1.0).abs() < 1e-6, "{} {}", id, expected);
+        assert!(circuit.device("M1.D").is_some());
+
+        // The triode region through a load, a p channel mirroring it.
+        for (card, sign) in [
+            (CARD.to_string(), 1.0),
+            (CARD.replace("Vto=4", "pchan Vto=-4"), -1.0),
+        ] {
+            let lines = [
+                format!("V1 g 0 {}", 10.0 * sign),
+                format!("V2 x 0 {}", 10.0 * sign),
+                "R1 x d 10".to_string(),
+                "M1 d g 0 0 M1".to_string(),
+                card,
+            ];
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let (circuit, solution) = operating_point(&lines);
+            let vd = sign * solution[circuit.unknowns().find("V(d)").unwrap()];
+            let id = (10.0 - vd) / 10.0;
+            // 6 V over threshold less the drop across RS, the drain nearly
+            // shorted.
+            let vds = vd - id * 0.015;
+            let vgst = 6.0 - id * 0.005;
+            let expected = 10.0 * vds * (vgst - vds / 2.0) * (1.0 + 0.01 * vds);
+            assert!((id / expected - 1.0).abs() < 1e-3, "{} {}", id, expected);
+        }
+    }
+
+    #[test]
+    fn test_body_diode() {
+        // The source above the drain conducts through the body diode alone.
+        let (circuit, solution) = operating_point(&["V1 g 0 0", "I1 d 0 1", "M1 d g 0 M1", CARD]);
+        let vd = solution[circuit.unknowns().find("V(d)").unwrap()];
+        let vt = BOLTZMANN * (27.0 + KELVIN) / CHARGE;
+        let expected = -(vt * (1.0 / 10e-12f64).ln() + 10e-3);
+        assert!((vd - expected).abs() < 1e-3, "{} {}", vd, expected);
+    }
+
+    #[test]
+    fn test_gate_charge() {
+        let model = VdmosModel::new(&Model::parse(CARD).unwrap()).unwrap();
+        // The capacitance is the charge's slope, and runs from CGDMIN to
+        // CGDMAX.
+        for vgd in [-200.0, -5.0, -0.1, 0.0, 0.1, 5.0, 2000.0] {
+            let h = 1e-4;
+            let slope = (model.gate_drain(vgd + h).0 - model.gate_drain(vgd - h).0) / (2.0 * h);
+            let (_, c) = model.gate_drain(vgd);
+            assert!((slope / c - 1.0).abs() < 1e-6, "{} {} {}", vgd, slope, c);
+            assert!((100e-12..=2e-9).contains(&c));
+        }
+        assert!((model.gate_drain(-1e6).1 / 100e-12 - 1.0).abs() < 1e-3);
+        assert!((model.gate_drain(1e6).1 / 2e-9 - 1.0).abs() < 1e-9);
+
+        // Off with the drain high, the gate sees CGS and little of CGD.
+        let mut mos = circuit(&[
+            "V1 g 0 0 AC 1",
+            "V2 d 0 50",
+            "M1 d g 0 M1",
+            &CARD.replace("Rg=2 ", ""),
+        ]);
+        let omega = 1e3;
+        let branch = mos.unknowns().find("I(V1)").unwrap();
+        let admittance = -ac(&mut mos, omega)[branch];
+        let expected = 1.5e-9 + model.gate_drain(-50.0).1;
+        assert!((admittance.im / (omega * expected) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_subthreshold() {
+        let card = CARD.replace("Vto=4", "Vto=4 ksubthres=0.1");
+        let current = |vgs: f64| {
+            let lines = [
+                format!("V1 g 0 {}", vgs),
+                "V2 d 0 10".to_string(),
+                "M1 d g 0 M1".to_string(),
+                card.clone(),
+            ];
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let (circuit, solution) = operating_point(&lines);
+            -solution[circuit.unknowns().find("I(V2)").unwrap()]
+        };
+        // Squared, the exponential tail gives a decade per 0.05 ln 10 V.
+        let ratio = current(3.5) / current(3.5 - 0.05 * 10f64.ln());
+        assert!((ratio / 10.0 - 1.0).abs() < 1e-2, "{}", ratio);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::frontend::kicad::sch::pin_number_order;
+use crate::frontend::kicad::sexpr::SExpr;
+use crate::frontend::schematic::{Schematic, SymbolKind};
+use std::collections::BTreeMap;
+
+/// `(reference, pin number, pin name)` of each pin on a net.
+type NetNodes = Vec<(String, String, String)>;
+
+/// Write the connectivity of a schematic as a KiCad netlist (`.net`,
+/// format version E), which Pcbnew reads through "Update PCB from netlist".
+///
+/// Pins are identified by their number, or by their netlist order for
+/// symbols that have none (LTspice symbols). The ground net is exported as
+/// `GND`, and nets without a label take KiCad's `Net-(<ref>-Pad<pin>)` or
+/// `unconnected-(<ref>-Pad<pin>)` form.
+pub(crate) fn export(sheet: &Schematic, source: &str) -> String {
+    let connectivity = sheet.connectivity();
+
+    struct Part<'a> {
+        value: &'a str,
+        footprint: &'a str,
+        library: &'a str,
+    }
+    let mut parts: BTreeMap<&str, Part> = BTreeMap::new();
+    let mut nets: BTreeMap<usize, NetNodes> = BTreeMap::new();
+
+    for (i, instance) in sheet.symbols.iter().enumerate() {
+        let Some(symbol) = sheet.symbol_of(instance) else {
+            continue;
+        };
+        let reference = instance.name();
+        if symbol.kind == SymbolKind::Graphic || reference.is_empty() || reference.starts_with('#')
+        {
+            continue;
+        }
+        let attribute = |key: &str| {
+            instance
+                .attributes
+                .get(key)
+                .or_else(|| symbol.attributes.get(key))
+                .map(String::as_str)
+                .unwrap_or_default()
+        };
+        parts.insert(
+            reference,
+            Part {
+                value: attribute("Value"),
+                footprint: attribute("Footprint"),
+                library: instance.symbol.split('#').next().unwrap_or_default(),
+            },
+        );
+        for (j, pin) in symbol.pins.iter().enumerate() {
+            let Some(net) = connectivity.net_index(i, j) else {
+                continue;
+            };
+            let number = if pin.number.is_empty() {
+                pin.order.to_string()
+            } else {
+                pin.number.clone()
+            };
+            nets.entry(net)
+                .or_default()
+                .push((reference.to_string(), number, pin.name.clone()));
+        }
+    }
+
+    let components = parts
+        .iter()
+        .map(|(reference, part)| {
+            let (lib, name) = part.library.split_once(':').unwrap_or(("", part.library));
+            SExpr::list(
+                "comp",
+                vec![
+                    SExpr::pair("ref", *reference),
+                    SExpr::pair("value", part.value),
+                    SExpr::pair("footprint", part.footprint),
+                    SExpr::list(
+                        "libsource",
+                        vec![SExpr::pair("lib", lib), SExpr::pair("part", name)],
+                    ),
+                    SExpr::list(
+                        "sheetpath",
+                        vec![SExpr::pair("names", "/"), SExpr::pair("tstamps", "/")],
+                    ),
+                ],
+            )
+        })
+        .collect();
+
+    let mut named: Vec<(String, NetNodes)> = nets
+        .into_iter()
+        .map(|(index, mut nodes)| {
+            nodes.sort_by(|a, b| a.0.cmp(&b.0).then(pin_number_order(&a.1, &b.1)));
+            let net = &connectivity.nets[index];
+            let name = if net.name == "0" {
+                "GND".to_string()
+            } else if net.labelled {
+                net.name.clone()
+            } else if net.name.starts_with("NC_") {
+                format!("unconnected-({}-Pad{})", nodes[0].0, nodes[0].1)
+            } else {
+                format!("Net-({}-Pad{})", nodes[0].0, nodes[0].1)
+            };
+            (name, nodes)
+        })
+        .collect();
+    named.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let nets = named
+        .into_iter()
+        .enumerate()
+        .map(|(code, (name, nodes))| {
+            let mut items = vec![
+                SExpr::pair("code", (code + 1).to_string()),
+                SExpr::pair("name", name),
+            ];
+            for (reference, pin, pin_name) in nodes {
+                let mut node = vec![SExpr::pair("ref", reference), SExpr::pair("pin", pin)];
+                if !pin_name.is_empty() && pin_name != "~" {
+                    node.push(SExpr::pair("pinfunction", pin_name));
+                }
+                items.push(SExpr::list("node", node));
+            }
+            SExpr::list("net", items)
+        })
+        .collect();
+
+    SExpr::list(
+        "export",
+        vec![
+            SExpr::pair("version", "E"),
+            SExpr::list(
+                "design",
+                vec![SExpr::pair("source", source), SExpr::pair("tool", "Nimbus")],
+            ),
+            SExpr::list("components", components),
+            SExpr::list("nets", nets),
+        ],
+    )
+    .pretty()
+}
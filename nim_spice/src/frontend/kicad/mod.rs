@@ -0,0 +1,203 @@
+//! Interchange with KiCad: import of `.kicad_sch` schematics (KiCad 6 and
+//! later) and export of schematic connectivity as a KiCad `.net` netlist.
+//!
+//! Symbols are mapped to simulation elements through their `Sim.Device`,
+//! `Sim.Type`, `Sim.Pins`, `Sim.Params`, `Sim.Name` and `Sim.Library`
+//! fields, the same fields KiCad's own simulator uses.
+
+mod export;
+mod sch;
+mod sexpr;
+mod sim;
+
+use crate::frontend::schematic::Schematic;
+use crate::frontend::Import;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::path::Path;
+
+/// Read a `.kicad_sch` file.
+pub fn import(path: &Path) -> Result<Import, ErrorHandler> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.add_error(Error::new(
+            ErrorType::IOError,
+            format!("{}: {}", path.display(), e),
+            0,
+            0,
+        ));
+        error_handler
+    })?;
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    parse(&name, &content)
+}
+
+/// Parse the contents of a `.kicad_sch` file.
+pub fn parse(name: &str, content: &str) -> Result<Import, ErrorHandler> {
+    let root = sexpr::parse(content)?;
+    let mut schematic = sch::read_schematic(&root)?;
+    schematic.name = name.to_string();
+    let netlist = sim::netlist(&schematic)?;
+    Ok(Import { schematic, netlist })
+}
+
+/// The KiCad netlist of a schematic, ready to be written to a `.net` file.
+/// `source` is recorded as the originating schematic file.
+pub fn export_netlist(schematic: &Schematic, source: &str) -> String {
+    export::export(schematic, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIVIDER: &str = r##"(kicad_sch (version 20230121) (generator eeschema)
+  (lib_symbols
+    (symbol "Device:R" (pin_numbers hide) (in_bom yes) (on_board yes)
+      (property "Reference" "R" (at 2.032 0 90))
+      (property "Value" "R" (at 0 0 90))
+      (symbol "R_0_1"
+        (rectangle (start -1.016 -2.54) (end 1.016 2.54) (stroke (width 0.254)) (fill (type none))))
+      (symbol "R_1_1"
+        (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+        (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2"))))
+    (symbol "Simulation_SPICE:VDC" (in_bom yes) (on_board yes)
+      (property "Reference" "V" (at 2.54 2.54 0))
+      (property "Value" "1" (at 2.54 0 0))
+      (property "Sim.Device" "V" (at 0 0 0))
+      (property "Sim.Type" "DC" (at 0 0 0))
+      (property "Sim.Pins" "1=+ 2=-" (at 0 0 0))
+      (symbol "VDC_0_1"
+        (circle (center 0 0) (radius 2.54) (stroke (width 0.254)) (fill (type none)))
+        (arc (start -1.27 0) (mid 0 1.27) (end 1.27 0)))
+      (symbol "VDC_1_1"
+        (pin passive line (at 0 5.08 270) (length 2.54) (name "~") (number "1"))
+        (pin passive line (at 0 -5.08 90) (length 2.54) (name "~") (number "2"))))
+    (symbol "power:GND" (power) (pin_names (offset 0)) (in_bom yes) (on_board yes)
+      (property "Reference" "#PWR" (at 0 -6.35 0))
+      (property "Value" "GND" (at 0 -3.81 0))
+      (symbol "GND_0_1"
+        (polyline (pts (xy 0 0) (xy 0 -1.27) (xy 1.27 -1.27) (xy 0 -2.54) (xy -1.27 -1.27) (xy 0 -1.27))))
+      (symbol "GND_1_1"
+        (pin power_in line (at 0 0 270) (length 0) hide (name "GND") (number "1")))))
+  (wire (pts (xy 80 44.92) (xy 80 40)))
+  (wire (pts (xy 80 40) (xy 100 40)))
+  (wire (pts (xy 100 40) (xy 100 46.19)))
+  (label "out" (at 90 40 0))
+  (text ".op" (at 60 70 0))
+  (symbol (lib_id "Device:R") (at 100 50 0) (unit 1)
+    (property "Reference" "R1" (at 0 0 0))
+    (property "Value" "10k" (at 0 0 0))
+    (property "Footprint" "Resistor_SMD:R_0603_1608Metric" (at 0 0 0))
+    (pin "1") (pin "2"))
+  (symbol (lib_id "Simulation_SPICE:VDC") (at 80 50 0) (unit 1)
+    (property "Reference" "V1" (at 0 0 0))
+    (property "Value" "5" (at 0 0 0))
+    (property "Sim.Params" "dc=5" (at 0 0 0))
+    (pin "1") (pin "2"))
+  (symbol (lib_id "power:GND") (at 80 55.08 0) (unit 1)
+    (property "Reference" "#PWR01" (at 0 0 0))
+    (property "Value" "GND" (at 0 0 0)))
+  (symbol (lib_id "power:GND") (at 100 53.81 0) (unit 1)
+    (property "Reference" "#PWR02" (at 0 0 0))
+    (property "Value" "GND" (at 0 0 0)))
+)
+"##;
+
+    #[test]
+    fn test_import() {
+        let import = parse("divider", DIVIDER).unwrap();
+        assert_eq!(import.schematic.symbols.len(), 4);
+        let netlist = import.netlist;
+        assert_eq!(netlist.components.len(), 2);
+        assert_eq!(netlist.component("R1").unwrap().to_string(), "R1 out 0 10k");
+        assert_eq!(netlist.component("V1").unwrap().to_string(), "V1 out 0 5");
+        assert_eq!(netlist.directives, [".op"]);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let errors = parse(
+            "bad",
+            "(kicad_sch (symbol (lib_id \"Device:R\") (at 1 2 0)))",
+        )
+        .err()
+        .unwrap();
+        assert!(errors.errors[0].message.contains("lib_symbols"));
+        assert_eq!(errors.errors[0].line, 1);
+    }
+
+    #[test]
+    fn test_export_netlist() {
+        let import = parse("divider", DIVIDER).unwrap();
+        let text = export_netlist(&import.schematic, "divider.kicad_sch");
+        let root = sexpr::parse(&text).unwrap();
+        let components: Vec<&str> = root
+            .child("components")
+            .unwrap()
+            .children("comp")
+            .filter_map(|c| c.child("ref").and_then(|r| r.arg(0)))
+            .collect();
+        assert_eq!(components, ["R1", "V1"]);
+        let r1 = root.child("components").unwrap().child("comp").unwrap();
+        assert_eq!(
+            r1.child("footprint").unwrap().arg(0),
+            Some("Resistor_SMD:R_0603_1608Metric")
+        );
+
+        let nets: Vec<(&str, Vec<(&str, &str)>)> = root
+            .child("nets")
+            .unwrap()
+            .children("net")
+            .map(|net| {
+                let nodes = net
+                    .children("node")
+                    .map(|n| {
+                        (
+                            n.child("ref").unwrap().arg(0).unwrap(),
+                            n.child("pin").unwrap().arg(0).unwrap(),
+                        )
+                    })
+                    .collect();
+                (net.child("name").unwrap().arg(0).unwrap(), nodes)
+            })
+            .collect();
+        assert_eq!(
+            nets,
+            [
+                ("GND", vec![("R1", "2"), ("V1", "2")]),
+                ("out", vec![("R1", "1"), ("V1", "1")]),
+            ]
+        );
+
+        // A label that looks like a numbered net keeps its name, and only
+        // an unlabelled net is named after its first pad.
+        let net_names = |sheet: &str| {
+            let import = parse("divider", sheet).unwrap();
+            let text = export_netlist(&import.schematic, "divider.kicad_sch");
+            let root = sexpr::parse(&text).unwrap();
+            root.child("nets")
+                .unwrap()
+                .children("net")
+                .map(|net| net.child("name").unwrap().arg(0).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let relabelled = DIVIDER.replace("(label \"out\"", "(label \"N1\"");
+        assert_eq!(net_names(&relabelled), ["GND", "N1"]);
+        let unlabelled = DIVIDER.replace("(label \"out\" (at 90 40 0))", "");
+        assert_eq!(net_names(&unlabelled), ["GND", "Net-(R1-Pad1)"]);
+
+        // A label named like the first numbered net does not pull an
+        // unlabelled net into it: with R1 lifted off ground onto a stub
+        // wire, its two pads stay on separate nets.
+        let colliding = DIVIDER
+            .replace("(label \"out\"", "(label \"N001\"")
+            .replace(
+                "(symbol (lib_id \"power:GND\") (at 100 53.81 0)",
+                "(wire (pts (xy 100 53.81) (xy 110 53.81)))\n  (symbol (lib_id \"power:GND\") (at 200 53.81 0)",
+            );
+        assert_eq!(net_names(&colliding), ["GND", "N001", "Net-(R1-Pad2)"]);
+    }
+}
@@ -0,0 +1,492 @@
+use crate::frontend::kicad::sexpr::SExpr;
+use crate::frontend::schematic::{
+    Flag, FlagKind, Orientation, Pin, Point, PortDirection, Schematic, Shape, Symbol,
+    SymbolInstance, SymbolKind, Text, TextKind, Wire,
+};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::collections::BTreeMap;
+
+/// Schematic coordinates are stored in hundredths of a millimetre, so the
+/// 1.27 mm KiCad grid stays integral.
+const SCALE: f64 = 100.0;
+
+/// Net names KiCad power symbols use for the global ground.
+const GROUND_NETS: [&str; 2] = ["GND", "0"];
+
+/// Convert a parsed `kicad_sch` tree into a schematic document.
+///
+/// Library symbols are taken from the embedded `lib_symbols` cache, so the
+/// KiCad libraries do not need to be installed. Power symbols become net
+/// labels and are drawn, but not netlisted.
+pub(crate) fn read_schematic(root: &SExpr) -> Result<Schematic, ErrorHandler> {
+    let mut reader = SheetReader {
+        sheet: Schematic::new(),
+        error_handler: ErrorHandler::new(),
+    };
+    if root.name() != Some("kicad_sch") {
+        reader.error(root, "Not a KiCad schematic".to_string());
+        return Err(reader.error_handler);
+    }
+
+    if let Some(symbols) = root.child("lib_symbols") {
+        let definitions: Vec<&SExpr> = symbols.children("symbol").collect();
+        for definition in &definitions {
+            for symbol in reader.library_symbol(definition, &definitions) {
+                reader.sheet.library.insert(symbol.name.clone(), symbol);
+            }
+        }
+    }
+
+    for item in root.args() {
+        match item.name() {
+            Some("wire") => reader.wire(item),
+            Some("label") => reader.label(item, FlagKind::Label),
+            Some("global_label") | Some("hierarchical_label") => {
+                let direction = match item.child("shape").and_then(|s| s.arg(0)) {
+                    Some("input") => PortDirection::Input,
+                    Some("output") => PortDirection::Output,
+                    _ => PortDirection::Bidirectional,
+                };
+                reader.label(item, FlagKind::Port(direction))
+            }
+            Some("text") => reader.text(item),
+            Some("symbol") => reader.instance(item),
+            Some("sheet") => reader.error(
+                item,
+                "Hierarchical sheets are not supported yet".to_string(),
+            ),
+            _ => {}
+        }
+    }
+
+    if reader.error_handler.has_errors() {
+        Err(reader.error_handler)
+    } else {
+        Ok(reader.sheet)
+    }
+}
+
+struct SheetReader {
+    sheet: Schematic,
+    error_handler: ErrorHandler,
+}
+
+impl SheetReader {
+    fn error(&mut self, at: &SExpr, message: String) {
+        self.error_handler.add_error(Error::new(
+            ErrorType::Syntax,
+            message,
+            at.line(),
+            at.column(),
+        ));
+    }
+
+    /// Read `(name x y ...)` as a point, flipping y when reading library
+    /// coordinates, which point up.
+    fn point(&mut self, expr: Option<&SExpr>, flip: bool, context: &SExpr) -> Option<Point> {
+        let p = expr.and_then(|e| Some((e.arg_f64(0)?, e.arg_f64(1)?)));
+        match p {
+            Some((x, y)) => {
+                let y = if flip { -y } else { y };
+                Some(Point::new(
+                    (x * SCALE).round() as i32,
+                    (y * SCALE).round() as i32,
+                ))
+            }
+            None => {
+                self.error(context, "Missing or invalid coordinates".to_string());
+                None
+            }
+        }
+    }
+
+    fn wire(&mut self, item: &SExpr) {
+        let points: Vec<&SExpr> = item
+            .child("pts")
+            .map(|pts| pts.children("xy").collect())
+            .unwrap_or_default();
+        if points.len() < 2 {
+            self.error(item, "Wire needs two points".to_string());
+            return;
+        }
+        for pair in points.windows(2) {
+            if let (Some(start), Some(end)) = (
+                self.point(Some(pair[0]), false, item),
+                self.point(Some(pair[1]), false, item),
+            ) {
+                self.sheet.wires.push(Wire { start, end });
+            }
+        }
+    }
+
+    fn label(&mut self, item: &SExpr, kind: FlagKind) {
+        let Some(name) = item.arg(0) else {
+            self.error(item, "Label without a name".to_string());
+            return;
+        };
+        if let Some(position) = self.point(item.child("at"), false, item) {
+            self.sheet.flags.push(Flag {
+                position,
+                name: name.to_string(),
+                kind,
+            });
+        }
+    }
+
+    /// Text starting with `.` is a simulation directive, as in KiCad's
+    /// simulator.
+    fn text(&mut self, item: &SExpr) {
+        let content = item.arg(0).unwrap_or_default().to_string();
+        if let Some(position) = self.point(item.child("at"), false, item) {
+            let kind = if content.trim_start().starts_with('.') {
+                TextKind::Directive
+            } else {
+                TextKind::Comment
+            };
+            self.sheet.texts.push(Text {
+                position,
+                content,
+                kind,
+            });
+        }
+    }
+
+    /// Read a `lib_symbols` entry. A symbol with several units is split into
+    /// one definition per unit, named `<lib_id>#<unit>`.
+    fn library_symbol(&mut self, definition: &SExpr, all: &[&SExpr]) -> Vec<Symbol> {
+        let Some(name) = definition.arg(0) else {
+            self.error(definition, "Library symbol without a name".to_string());
+            return Vec::new();
+        };
+        let mut base = Symbol {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        // Derived symbols only override properties of their parent.
+        let body = match definition.child("extends").and_then(|e| e.arg(0)) {
+            Some(parent) => {
+                let full = match name.split_once(':') {
+                    Some((library, _)) => format!("{}:{}", library, parent),
+                    None => parent.to_string(),
+                };
+                match all.iter().find(|d| d.arg(0) == Some(full.as_str())) {
+                    Some(parent) => *parent,
+                    None => {
+                        self.error(definition, format!("Unknown parent symbol: {}", parent));
+                        return Vec::new();
+                    }
+                }
+            }
+            None => definition,
+        };
+
+        for source in [body, definition] {
+            for property in source.children("property") {
+                if let (Some(key), Some(value)) = (property.arg(0), property.arg(1)) {
+                    base.attributes.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+        if body.has_flag("power") {
+            base.kind = SymbolKind::Graphic;
+        }
+
+        // Units are nested as `NAME_<unit>_<style>`; unit 0 is shared by all
+        // units and only the first body style is used.
+        let mut units: BTreeMap<u32, Vec<&SExpr>> = BTreeMap::new();
+        for unit in body.children("symbol") {
+            let suffix: Vec<u32> = unit
+                .arg(0)
+                .unwrap_or_default()
+                .rsplitn(3, '_')
+                .take(2)
+                .filter_map(|s| s.parse().ok())
+                .collect();
+            let (number, style) = match suffix[..] {
+                [style, unit] => (unit, style),
+                _ => (0, 1),
+            };
+            if style <= 1 {
+                units.entry(number).or_default().extend(unit.args());
+            }
+        }
+        let shared = units.remove(&0).unwrap_or_default();
+
+        let mut symbols = Vec::new();
+        if units.len() <= 1 {
+            let mut symbol = base;
+            let items = shared.iter().chain(units.values().flatten());
+            for item in items {
+                self.library_item(item, &mut symbol);
+            }
+            symbols.push(symbol);
+        } else {
+            for (number, items) in &units {
+                let mut symbol = base.clone();
+                symbol.name = format!("{}#{}", name, number);
+                for item in shared.iter().chain(items) {
+                    self.library_item(item, &mut symbol);
+                }
+                symbols.push(symbol);
+            }
+        }
+
+        // Netlist order follows the pin numbers.
+        for symbol in symbols.iter_mut() {
+            let mut numbers: Vec<String> = symbol.pins.iter().map(|p| p.number.clone()).collect();
+            numbers.sort_by(|a, b| pin_number_order(a, b));
+            for pin in symbol.pins.iter_mut() {
+                pin.order = numbers.iter().position(|n| *n == pin.number).unwrap() + 1;
+            }
+        }
+        symbols
+    }
+
+    fn library_item(&mut self, item: &SExpr, symbol: &mut Symbol) {
+        match item.name() {
+            Some("rectangle") => {
+                if let (Some(a), Some(b)) = (
+                    self.point(item.child("start"), true, item),
+                    self.point(item.child("end"), true, item),
+                ) {
+                    symbol.shapes.push(Shape::Rectangle(a, b));
+                }
+            }
+            Some("polyline") => {
+                let points: Vec<Point> = item
+                    .child("pts")
+                    .map(|pts| pts.children("xy").collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|xy| self.point(Some(xy), true, item))
+                    .collect();
+                for pair in points.windows(2) {
+                    symbol.shapes.push(Shape::Line(pair[0], pair[1]));
+                }
+            }
+            Some("circle") => {
+                let centre = self.point(item.child("center"), true, item);
+                let radius = item.child("radius").and_then(|r| r.arg_f64(0));
+                if let (Some(c), Some(r)) = (centre, radius) {
+                    let r = (r * SCALE).round() as i32;
+                    symbol.shapes.push(Shape::Circle(
+                        Point::new(c.x - r, c.y - r),
+                        Point::new(c.x + r, c.y + r),
+                    ));
+                }
+            }
+            Some("arc") => {
+                let start = self.point(item.child("start"), true, item);
+                let mid = self.point(item.child("mid"), true, item);
+                let end = self.point(item.child("end"), true, item);
+                if let (Some(start), Some(mid), Some(end)) = (start, mid, end) {
+                    let (a, b) = arc_bounds(start, mid, end);
+                    symbol.shapes.push(Shape::Arc(a, b, start, end));
+                }
+            }
+            Some("text") => {
+                if let Some(position) = self.point(item.child("at"), true, item) {
+                    let text = item.arg(0).unwrap_or_default().to_string();
+                    symbol.shapes.push(Shape::Text(position, text));
+                }
+            }
+            Some("pin") => {
+                let Some(at) = item.child("at") else {
+                    self.error(item, "Pin without a position".to_string());
+                    return;
+                };
+                let Some(position) = self.point(Some(at), true, item) else {
+                    return;
+                };
+                // The pin is drawn from its connection point towards the body.
+                let length = item
+                    .child("length")
+                    .and_then(|l| l.arg_f64(0))
+                    .unwrap_or(0.0);
+                let angle = at.arg_f64(2).unwrap_or(0.0).to_radians();
+                let tip = Point::new(
+                    position.x + (length * angle.cos() * SCALE).round() as i32,
+                    position.y - (length * angle.sin() * SCALE).round() as i32,
+                );
+                symbol.shapes.push(Shape::Line(position, tip));
+
+                let number = item
+                    .child("number")
+                    .and_then(|n| n.arg(0))
+                    .unwrap_or_default();
+                let name = item
+                    .child("name")
+                    .and_then(|n| n.arg(0))
+                    .unwrap_or_default();
+                symbol.pins.push(Pin {
+                    position,
+                    name: name.to_string(),
+                    number: number.to_string(),
+                    order: 0,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn instance(&mut self, item: &SExpr) {
+        let Some(lib_id) = item.child("lib_id").and_then(|l| l.arg(0)) else {
+            self.error(item, "Symbol without a lib_id".to_string());
+            return;
+        };
+        let Some(at) = item.child("at") else {
+            self.error(item, "Symbol without a position".to_string());
+            return;
+        };
+        let Some(position) = self.point(Some(at), false, item) else {
+            return;
+        };
+        let orientation = orientation(
+            at.arg_f64(2).unwrap_or(0.0),
+            item.child("mirror").and_then(|m| m.arg(0)),
+        );
+        let unit = item
+            .child("unit")
+            .and_then(|u| u.arg(0))
+            .unwrap_or("1")
+            .to_string();
+
+        let mut attributes = BTreeMap::new();
+        for property in item.children("property") {
+            if let (Some(key), Some(value)) = (property.arg(0), property.arg(1)) {
+                attributes.insert(key.to_string(), value.to_string());
+            }
+        }
+        let reference = attributes.get("Reference").cloned().unwrap_or_default();
+        attributes.insert("InstName".to_string(), reference);
+        attributes.insert("Unit".to_string(), unit.clone());
+
+        let split = format!("{}#{}", lib_id, unit);
+        let symbol = if self.sheet.library.contains_key(&split) {
+            split
+        } else {
+            lib_id.to_string()
+        };
+        let instance = SymbolInstance {
+            symbol,
+            position,
+            orientation,
+            attributes,
+        };
+
+        match self.sheet.library.get(&instance.symbol) {
+            Some(symbol) => {
+                if symbol.kind == SymbolKind::Graphic {
+                    let name = instance
+                        .attributes
+                        .get("Value")
+                        .cloned()
+                        .unwrap_or_default();
+                    let kind = if GROUND_NETS.contains(&name.as_str()) {
+                        FlagKind::Ground
+                    } else {
+                        FlagKind::Label
+                    };
+                    for pin in &symbol.pins {
+                        self.sheet.flags.push(Flag {
+                            position: instance.pin_position(pin),
+                            name: name.clone(),
+                            kind,
+                        });
+                    }
+                }
+            }
+            None => {
+                self.error(item, format!("Symbol {} missing from lib_symbols", lib_id));
+                return;
+            }
+        }
+        self.sheet.symbols.push(instance);
+    }
+}
+
+/// Pin numbers compare numerically where they are numbers, so `10` follows
+/// `9`.
+pub(crate) fn pin_number_order(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        _ => a.cmp(b),
+    }
+}
+
+/// KiCad rotates counter-clockwise on screen and then mirrors; find the
+/// placement that maps the axes the same way.
+fn orientation(angle: f64, mirror: Option<&str>) -> Orientation {
+    let kicad = |p: Point| {
+        let mut q = p;
+        for _ in 0..((angle.rem_euclid(360.0) / 90.0).round() as i32 % 4) {
+            q = Point::new(q.y, -q.x);
+        }
+        match mirror {
+            Some("x") => Point::new(q.x, -q.y),
+            Some("y") => Point::new(-q.x, q.y),
+            _ => q,
+        }
+    };
+    let all = [
+        Orientation::R0,
+        Orientation::R90,
+        Orientation::R180,
+        Orientation::R270,
+        Orientation::M0,
+        Orientation::M90,
+        Orientation::M180,
+        Orientation::M270,
+    ];
+    let (x, y) = (Point::new(1, 0), Point::new(0, 1));
+    all.into_iter()
+        .find(|o| o.transform(x) == kicad(x) && o.transform(y) == kicad(y))
+        .unwrap_or_default()
+}
+
+/// Bounding box of the circle through the three points of an arc.
+fn arc_bounds(a: Point, b: Point, c: Point) -> (Point, Point) {
+    let (ax, ay) = (a.x as f64, a.y as f64);
+    let (bx, by) = (b.x as f64, b.y as f64);
+    let (cx, cy) = (c.x as f64, c.y as f64);
+    let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+    if d.abs() < f64::EPSILON {
+        return (a, c);
+    }
+    let ux = ((ax * ax + ay * ay) * (by - cy)
+        + (bx * bx + by * by) * (cy - ay)
+        + (cx * cx + cy * cy) * (ay - by))
+        / d;
+    let uy = ((ax * ax + ay * ay) * (cx - bx)
+        + (bx * bx + by * by) * (ax - cx)
+        + (cx * cx + cy * cy) * (bx - ax))
+        / d;
+    let r = ((ax - ux).powi(2) + (ay - uy).powi(2)).sqrt();
+    (
+        Point::new((ux - r).round() as i32, (uy - r).round() as i32),
+        Point::new((ux + r).round() as i32, (uy + r).round() as i32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientation() {
+        assert_eq!(orientation(0.0, None), Orientation::R0);
+        assert_eq!(orientation(90.0, None), Orientation::R270);
+        assert_eq!(orientation(180.0, None), Orientation::R180);
+        assert_eq!(orientation(0.0, Some("y")), Orientation::M0);
+        assert_eq!(orientation(0.0, Some("x")), Orientation::M180);
+    }
+
+    #[test]
+    fn test_arc_bounds() {
+        let (a, b) = arc_bounds(Point::new(-10, 0), Point::new(0, -10), Point::new(10, 0));
+        assert_eq!((a, b), (Point::new(-10, -10), Point::new(10, 10)));
+    }
+}
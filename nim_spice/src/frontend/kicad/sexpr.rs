@@ -0,0 +1,340 @@
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+
+/// A node of a KiCad S-expression file. Quoted strings and bare symbols are
+/// both kept as atoms; only the writer cares about the difference.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SExpr {
+    Atom(String, usize, usize),
+    List(Vec<SExpr>, usize, usize),
+}
+
+impl SExpr {
+    pub(crate) fn list(name: &str, items: Vec<SExpr>) -> SExpr {
+        let mut all = vec![SExpr::atom(name)];
+        all.extend(items);
+        SExpr::List(all, 0, 0)
+    }
+
+    pub(crate) fn atom(value: impl Into<String>) -> SExpr {
+        SExpr::Atom(value.into(), 0, 0)
+    }
+
+    /// `(name "value")`
+    pub(crate) fn pair(name: &str, value: impl Into<String>) -> SExpr {
+        SExpr::list(name, vec![SExpr::atom(value)])
+    }
+
+    pub(crate) fn line(&self) -> usize {
+        match self {
+            SExpr::Atom(_, line, _) | SExpr::List(_, line, _) => *line,
+        }
+    }
+
+    pub(crate) fn column(&self) -> usize {
+        match self {
+            SExpr::Atom(_, _, column) | SExpr::List(_, _, column) => *column,
+        }
+    }
+
+    pub(crate) fn as_atom(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(value, ..) => Some(value),
+            SExpr::List(..) => None,
+        }
+    }
+
+    fn items(&self) -> &[SExpr] {
+        match self {
+            SExpr::List(items, ..) => items,
+            SExpr::Atom(..) => &[],
+        }
+    }
+
+    /// The leading symbol of a list, e.g. `wire` for `(wire (pts ...))`.
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.items().first().and_then(SExpr::as_atom)
+    }
+
+    /// Everything after the leading symbol.
+    pub(crate) fn args(&self) -> &[SExpr] {
+        self.items().get(1..).unwrap_or(&[])
+    }
+
+    pub(crate) fn arg(&self, index: usize) -> Option<&str> {
+        self.args().get(index).and_then(SExpr::as_atom)
+    }
+
+    pub(crate) fn arg_f64(&self, index: usize) -> Option<f64> {
+        self.arg(index).and_then(|s| s.parse().ok())
+    }
+
+    /// Child lists whose leading symbol is `name`.
+    pub(crate) fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SExpr> {
+        self.args()
+            .iter()
+            .filter(move |child| child.name() == Some(name))
+    }
+
+    pub(crate) fn child(&self, name: &str) -> Option<&SExpr> {
+        self.args().iter().find(|child| child.name() == Some(name))
+    }
+
+    /// Whether a bare flag such as `(power)` or `hide` is present.
+    pub(crate) fn has_flag(&self, flag: &str) -> bool {
+        self.args()
+            .iter()
+            .any(|child| child.as_atom() == Some(flag) || child.name() == Some(flag))
+    }
+
+    /// Render the way KiCad writes its files: a list is broken over several
+    /// lines only if it holds lists that themselves hold lists.
+    pub(crate) fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            SExpr::Atom(value, ..) => write_atom(out, value),
+            SExpr::List(items, ..) => {
+                out.push('(');
+                let nested = items.iter().any(|item| {
+                    item.items()
+                        .iter()
+                        .any(|inner| matches!(inner, SExpr::List(..)))
+                });
+                for (i, item) in items.iter().enumerate() {
+                    if nested && i > 0 && matches!(item, SExpr::List(..)) {
+                        out.push('\n');
+                        out.push_str(&"  ".repeat(indent + 1));
+                    } else if i > 0 {
+                        out.push(' ');
+                    }
+                    item.write(out, indent + 1);
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn write_atom(out: &mut String, value: &str) {
+    let bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.-+:/".contains(c));
+    if bare
+        && value
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic())
+    {
+        out.push_str(value);
+    } else {
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+/// Parse a single top level S-expression.
+pub(crate) fn parse(content: &str) -> Result<SExpr, ErrorHandler> {
+    let mut reader = Reader {
+        chars: content.chars().collect(),
+        current: 0,
+        line: 1,
+        column: 1,
+        error_handler: ErrorHandler::new(),
+    };
+    reader.skip_whitespace();
+    let expr = reader.expr();
+    reader.skip_whitespace();
+    if expr.is_some() && reader.current < reader.chars.len() {
+        reader.error("Unexpected content after the closing parenthesis".to_string());
+    }
+    match expr {
+        Some(expr) if !reader.error_handler.has_errors() => Ok(expr),
+        _ => Err(reader.error_handler),
+    }
+}
+
+struct Reader {
+    chars: Vec<char>,
+    current: usize,
+    line: usize,
+    column: usize,
+    error_handler: ErrorHandler,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.current).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.current += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&mut self, message: String) {
+        self.error_handler.add_error(Error::new(
+            ErrorType::Syntax,
+            message,
+            self.line,
+            self.column,
+        ));
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.advance();
+        }
+    }
+
+    fn expr(&mut self) -> Option<SExpr> {
+        let (line, column) = (self.line, self.column);
+        match self.peek() {
+            Some('(') => {
+                self.advance();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(')') => {
+                            self.advance();
+                            return Some(SExpr::List(items, line, column));
+                        }
+                        Some(_) => items.push(self.expr()?),
+                        None => {
+                            self.error_handler.add_error(Error::new(
+                                ErrorType::Syntax,
+                                "Unclosed parenthesis".to_string(),
+                                line,
+                                column,
+                            ));
+                            return None;
+                        }
+                    }
+                }
+            }
+            Some('"') => {
+                self.advance();
+                let mut value = String::new();
+                loop {
+                    match self.advance() {
+                        Some('"') => return Some(SExpr::Atom(value, line, column)),
+                        Some('\\') => match self.advance() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => break,
+                        },
+                        Some(c) => value.push(c),
+                        None => break,
+                    }
+                }
+                self.error_handler.add_error(Error::new(
+                    ErrorType::Syntax,
+                    "Unterminated string".to_string(),
+                    line,
+                    column,
+                ));
+                None
+            }
+            Some(')') => {
+                self.error("Unexpected ')'".to_string());
+                None
+            }
+            Some(_) => {
+                let mut value = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    value.push(c);
+                    self.advance();
+                }
+                Some(SExpr::Atom(value, line, column))
+            }
+            None => {
+                self.error("Unexpected end of file".to_string());
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        f.write_str(&out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let source = "(kicad_sch (version 20230121)\n  (wire (pts (xy 1.27 2.54) (xy 3.81 2.54)))\n  (label \"out \\\"x\\\"\" (at 1 2 0)))";
+        let expr = parse(source).unwrap();
+        assert_eq!(expr.name(), Some("kicad_sch"));
+        assert_eq!(expr.child("version").unwrap().arg(0), Some("20230121"));
+        let wire = expr.child("wire").unwrap();
+        assert_eq!(wire.line(), 2);
+        let xy: Vec<f64> = wire
+            .child("pts")
+            .unwrap()
+            .children("xy")
+            .filter_map(|p| p.arg_f64(0))
+            .collect();
+        assert_eq!(xy, [1.27, 3.81]);
+        assert_eq!(expr.child("label").unwrap().arg(0), Some("out \"x\""));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = parse("(a (b c)\n").unwrap_err();
+        assert_eq!(errors.errors[0].line, 1);
+        assert!(parse("(a \"b)").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let expr = SExpr::list(
+            "net",
+            vec![
+                SExpr::pair("code", "1"),
+                SExpr::pair("name", "/out"),
+                SExpr::list("node", vec![SExpr::pair("ref", "R1")]),
+            ],
+        );
+        let text = expr.pretty();
+        assert_eq!(
+            text,
+            "(net\n  (code \"1\")\n  (name \"/out\")\n  (node (ref R1)))\n"
+        );
+        assert_eq!(
+            parse(&text).unwrap().child("name").unwrap().arg(0),
+            Some("/out")
+        );
+    }
+}
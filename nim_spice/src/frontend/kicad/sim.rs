@@ -0,0 +1,383 @@
+use crate::frontend::kicad::sch::pin_number_order;
+use crate::frontend::parser::netlist::{Component, Netlist, Node};
+use crate::frontend::schematic::{Schematic, SymbolKind, TextKind};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::collections::BTreeMap;
+
+/// Model pin names in netlist order for each `Sim.Device`. `Sim.Pins`
+/// entries naming these are sorted accordingly; anything else keeps the
+/// order it was written in.
+const PIN_ORDERS: [(&str, &[&str]); 16] = [
+    ("R", &["+", "-"]),
+    ("C", &["+", "-"]),
+    ("L", &["+", "-"]),
+    ("V", &["+", "-"]),
+    ("I", &["+", "-"]),
+    ("D", &["A", "K"]),
+    ("NPN", &["C", "B", "E", "S"]),
+    ("PNP", &["C", "B", "E", "S"]),
+    ("NJFET", &["D", "G", "S"]),
+    ("PJFET", &["D", "G", "S"]),
+    ("NMES", &["D", "G", "S"]),
+    ("PMES", &["D", "G", "S"]),
+    ("NMOS", &["D", "G", "S", "B"]),
+    ("PMOS", &["D", "G", "S", "B"]),
+    ("E", &["+", "-", "C+", "C-"]),
+    ("G", &["+", "-", "C+", "C-"]),
+];
+
+/// The fields of a part and the net on each of its pin numbers.
+type Part = (BTreeMap<String, String>, BTreeMap<String, String>);
+
+/// Parameter names of the KiCad source waveforms, in SPICE argument order.
+const WAVEFORMS: [(&str, &str, &[&str]); 5] = [
+    ("SIN", "SINE", &["dc", "ampl", "f", "td", "theta", "phase"]),
+    (
+        "PULSE",
+        "PULSE",
+        &["y1", "y2", "td", "tr", "tf", "tw", "per", "np"],
+    ),
+    ("EXP", "EXP", &["y1", "y2", "td1", "tau1", "td2", "tau2"]),
+    ("AM", "AM", &["vo", "va", "mf", "fc", "td"]),
+    ("SFFM", "SFFM", &["vo", "va", "fc", "mdi", "fs"]),
+];
+
+/// Build a simulation netlist from a KiCad schematic using the `Sim.*`
+/// symbol fields.
+///
+/// Symbols without `Sim.Device` fall back to KiCad's inference from the
+/// reference designator for R, C, L, V, I and D; other parts without a
+/// simulation model (connectors, mounting holes, ...) are left out. Units of
+/// a multi-unit part are merged into one element.
+pub(crate) fn netlist(sheet: &Schematic) -> Result<Netlist, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut netlist = Netlist::new();
+    netlist.title = sheet.name.clone();
+    let connectivity = sheet.connectivity();
+
+    let mut parts: BTreeMap<String, Part> = BTreeMap::new();
+    let mut order: Vec<String> = Vec::new();
+    for (i, instance) in sheet.symbols.iter().enumerate() {
+        let Some(symbol) = sheet.symbol_of(instance) else {
+            continue;
+        };
+        let reference = instance.name().to_string();
+        if symbol.kind == SymbolKind::Graphic || reference.starts_with('#') {
+            continue;
+        }
+        if !order.contains(&reference) {
+            order.push(reference.clone());
+        }
+        let (fields, pins) = parts.entry(reference).or_default();
+        for (key, value) in symbol.attributes.iter().chain(&instance.attributes) {
+            fields.insert(key.clone(), value.clone());
+        }
+        for (j, pin) in symbol.pins.iter().enumerate() {
+            if let Some(net) = connectivity.net_of(i, j) {
+                pins.insert(pin.number.clone(), net.name.clone());
+            }
+        }
+    }
+
+    let mut libraries: Vec<String> = Vec::new();
+    for reference in order {
+        let (fields, pins) = &parts[&reference];
+        match component(&reference, fields, pins) {
+            Ok(Some(component)) => {
+                if let Some(library) = fields.get("Sim.Library").filter(|l| !l.is_empty()) {
+                    if !libraries.contains(library) {
+                        libraries.push(library.clone());
+                    }
+                }
+                netlist.add_component(component);
+            }
+            Ok(None) => {}
+            Err(message) => error_handler.add_error(Error::new(
+                ErrorType::Semantic,
+                format!("{}: {}", reference, message),
+                0,
+                0,
+            )),
+        }
+    }
+
+    for library in libraries {
+        netlist.add_directive(format!(".include \"{}\"", library));
+    }
+    for text in &sheet.texts {
+        if text.kind == TextKind::Directive {
+            for line in text.content.lines().filter(|l| !l.trim().is_empty()) {
+                netlist.add_directive(line.trim().to_string());
+            }
+        }
+    }
+
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(netlist)
+    }
+}
+
+/// The element line for one part, or `None` if it is not simulated.
+fn component(
+    reference: &str,
+    fields: &BTreeMap<String, String>,
+    pins: &BTreeMap<String, String>,
+) -> Result<Option<Component>, String> {
+    let field = |key: &str| fields.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+    if matches!(field("Sim.Enable"), Some("0") | Some("false")) {
+        return Ok(None);
+    }
+    let value = field("Value").unwrap_or_default();
+    let device = match field("Sim.Device") {
+        Some(device) => device.to_ascii_uppercase(),
+        None => match reference.chars().next().map(|c| c.to_ascii_uppercase()) {
+            Some(c @ ('R' | 'C' | 'L' | 'V' | 'I' | 'D')) => c.to_string(),
+            _ => return Ok(None),
+        },
+    };
+    let sim_type = field("Sim.Type").map(str::to_ascii_uppercase);
+    let params = parse_params(field("Sim.Params").unwrap_or_default());
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    };
+    let model = field("Sim.Name").unwrap_or(value);
+
+    let (prefix, text) = match device.as_str() {
+        "R" | "C" | "L" => {
+            let key = device.to_ascii_lowercase();
+            let v = param(&key).or_else(|| params.first().map(|(_, v)| v.as_str()));
+            (device.clone(), v.unwrap_or(value).to_string())
+        }
+        "V" | "I" => (device.clone(), source(sim_type.as_deref(), &params, value)?),
+        "D" => ("D".to_string(), model.to_string()),
+        "NPN" | "PNP" => ("Q".to_string(), model.to_string()),
+        "NJFET" | "PJFET" => ("J".to_string(), model.to_string()),
+        "NMES" | "PMES" => ("Z".to_string(), model.to_string()),
+        "NMOS" | "PMOS" => ("M".to_string(), model.to_string()),
+        "E" | "G" | "F" | "H" => (device.clone(), param("gain").unwrap_or(value).to_string()),
+        "SUBCKT" => ("X".to_string(), model.to_string()),
+        d => return Err(format!("Unsupported Sim.Device: {}", d)),
+    };
+    if text.is_empty() {
+        return Err("No value or model given".to_string());
+    }
+    let mut text = text;
+    for (key, v) in &params {
+        // Instance parameters of models and subcircuits, e.g. `w=1u l=180n`.
+        if !matches!(
+            prefix.as_str(),
+            "R" | "C" | "L" | "V" | "I" | "E" | "G" | "F" | "H"
+        ) {
+            text.push_str(&format!(" {}={}", key, v));
+        }
+    }
+
+    let name = if reference
+        .chars()
+        .next()
+        .is_some_and(|c| prefix.starts_with(c.to_ascii_uppercase()))
+    {
+        reference.to_string()
+    } else {
+        format!("{}{}", prefix, reference)
+    };
+
+    let nodes = node_order(&device, field("Sim.Pins"), pins)?
+        .into_iter()
+        .map(Node::new)
+        .collect();
+    Ok(Some(Component::new(name, nodes, text)))
+}
+
+/// Nets in netlist order, following `Sim.Pins` (`"1=C 2=B 3=E"`) or the
+/// symbol pin numbers when it is absent.
+fn node_order(
+    device: &str,
+    mapping: Option<&str>,
+    pins: &BTreeMap<String, String>,
+) -> Result<Vec<String>, String> {
+    let Some(mapping) = mapping else {
+        let mut numbers: Vec<&String> = pins.keys().collect();
+        numbers.sort_by(|a, b| pin_number_order(a, b));
+        return Ok(numbers.into_iter().map(|n| pins[n].clone()).collect());
+    };
+
+    let canonical = PIN_ORDERS
+        .iter()
+        .find(|(d, _)| *d == device)
+        .map(|(_, order)| *order)
+        .unwrap_or(&[]);
+    let mut entries: Vec<(usize, &str, &str)> = Vec::new();
+    for (i, entry) in mapping.split_whitespace().enumerate() {
+        let Some((symbol_pin, model_pin)) = entry.split_once('=') else {
+            return Err(format!("Invalid Sim.Pins entry: {}", entry));
+        };
+        let rank = canonical
+            .iter()
+            .position(|p| p.eq_ignore_ascii_case(model_pin))
+            .or_else(|| model_pin.parse::<usize>().ok().map(|n| n.saturating_sub(1)))
+            .unwrap_or(canonical.len() + i);
+        entries.push((rank, symbol_pin, model_pin));
+    }
+    entries.sort_by_key(|(rank, ..)| *rank);
+    entries
+        .into_iter()
+        .map(|(_, symbol_pin, _)| {
+            pins.get(symbol_pin)
+                .cloned()
+                .ok_or_else(|| format!("Sim.Pins refers to missing pin {}", symbol_pin))
+        })
+        .collect()
+}
+
+/// The value part of an independent source line.
+fn source(
+    sim_type: Option<&str>,
+    params: &[(String, String)],
+    value: &str,
+) -> Result<String, String> {
+    let param = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.clone())
+    };
+    let mut text = match sim_type {
+        None if params.is_empty() => value.to_string(),
+        None | Some("DC") => param("dc").unwrap_or_else(|| value.to_string()),
+        Some("PWL") => format!("PWL({})", param("pwl").unwrap_or_default()),
+        Some(t) => {
+            let Some((_, name, keys)) = WAVEFORMS.iter().find(|(k, ..)| *k == t) else {
+                return Err(format!("Unsupported Sim.Type: {}", t));
+            };
+            // Trailing arguments that are not given keep their defaults.
+            let mut args: Vec<String> = keys
+                .iter()
+                .map(|k| param(k).unwrap_or_else(|| "0".to_string()))
+                .collect();
+            while args.len() > 1
+                && !params
+                    .iter()
+                    .any(|(k, _)| keys[args.len() - 1].eq_ignore_ascii_case(k))
+            {
+                args.pop();
+            }
+            format!("{}({})", name, args.join(" "))
+        }
+    };
+    if let Some(ac) = param("ac") {
+        text.push_str(&format!(" AC {}", ac));
+    }
+    Ok(text)
+}
+
+/// Split `key=value key2="quoted value"` into pairs. A bare value has an
+/// empty key.
+fn parse_params(s: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if chars.peek() != Some(&'=') {
+            params.push((String::new(), key));
+            continue;
+        }
+        chars.next();
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                value.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+        params.push((key, value));
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let params = parse_params("dc=1 ampl=\"2 m\" 5");
+        assert_eq!(params[1], ("ampl".to_string(), "2 m".to_string()));
+        assert_eq!(params[2], (String::new(), "5".to_string()));
+    }
+
+    #[test]
+    fn test_components() {
+        let pins = fields(&[("1", "c"), ("2", "b"), ("3", "e")]);
+        let q = component(
+            "Q1",
+            &fields(&[
+                ("Sim.Device", "NPN"),
+                ("Sim.Name", "2N3904"),
+                ("Sim.Pins", "1=E 2=B 3=C"),
+            ]),
+            &pins,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(q.to_string(), "Q1 e b c 2N3904");
+
+        let v = component(
+            "V1",
+            &fields(&[
+                ("Sim.Device", "V"),
+                ("Sim.Type", "SIN"),
+                ("Sim.Params", "ampl=1 f=1k ac=1"),
+            ]),
+            &pins,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(v.value, "SINE(0 1 1k) AC 1");
+
+        let r = component("R3", &fields(&[("Value", "4k7")]), &pins)
+            .unwrap()
+            .unwrap();
+        assert_eq!(r.to_string(), "R3 c b e 4k7");
+        assert!(component("J1", &fields(&[("Value", "Conn")]), &pins)
+            .unwrap()
+            .is_none());
+        assert!(component("U1", &fields(&[("Sim.Device", "XYZ")]), &pins).is_err());
+    }
+}
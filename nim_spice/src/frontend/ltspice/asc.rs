@@ -0,0 +1,195 @@
+use crate::frontend::ltspice::{fields, parse_int};
+use crate::frontend::schematic::{
+    Flag, FlagKind, Orientation, Point, PortDirection, Schematic, SymbolInstance, Text, TextKind,
+    Wire,
+};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::collections::BTreeMap;
+
+/// Parse the contents of an LTspice `.asc` schematic.
+///
+/// Only the sheet itself is read; symbol definitions and child sheets are
+/// resolved by the importer. Drawing-only records (`LINE`, `RECTANGLE`,
+/// `WINDOW`, ...) and unknown keywords are skipped.
+pub(crate) fn parse_asc(content: &str) -> Result<Schematic, ErrorHandler> {
+    let mut sheet = Schematic::new();
+    let mut error_handler = ErrorHandler::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line_no = number + 1;
+        let line = line.trim_end();
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim_start()),
+            None => (line, ""),
+        };
+        let mut error = |message: String| {
+            error_handler.add_error(Error::new(ErrorType::Syntax, message, line_no, 1));
+        };
+
+        match keyword.to_ascii_uppercase().as_str() {
+            "SHEET" => match fields::<3>(rest) {
+                Some([_, w, h]) => match (parse_int(w), parse_int(h)) {
+                    (Some(w), Some(h)) => sheet.size = (w, h),
+                    _ => error(format!("Invalid sheet size: {}", rest)),
+                },
+                None => error("SHEET expects a number, width and height".to_string()),
+            },
+            "WIRE" => match fields::<4>(rest).map(|f| f.map(parse_int)) {
+                Some([Some(x1), Some(y1), Some(x2), Some(y2)]) => sheet.wires.push(Wire {
+                    start: Point::new(x1, y1),
+                    end: Point::new(x2, y2),
+                }),
+                _ => error(format!("Invalid wire: {}", rest)),
+            },
+            "FLAG" => match fields::<3>(rest) {
+                Some([x, y, name]) => match (parse_int(x), parse_int(y)) {
+                    (Some(x), Some(y)) => sheet.flags.push(Flag {
+                        position: Point::new(x, y),
+                        name: name.to_string(),
+                        kind: if name == "0" {
+                            FlagKind::Ground
+                        } else {
+                            FlagKind::Label
+                        },
+                    }),
+                    _ => error(format!("Invalid flag position: {}", rest)),
+                },
+                None => error("FLAG expects a position and a net name".to_string()),
+            },
+            "IOPIN" => {
+                let direction = match fields::<3>(rest).map(|[_, _, d]| d.to_ascii_uppercase()) {
+                    Some(d) if d == "IN" => PortDirection::Input,
+                    Some(d) if d == "OUT" => PortDirection::Output,
+                    Some(d) if d == "BIDIR" => PortDirection::Bidirectional,
+                    _ => {
+                        error(format!("Invalid IOPIN: {}", rest));
+                        continue;
+                    }
+                };
+                match sheet.flags.last_mut() {
+                    Some(flag) => flag.kind = FlagKind::Port(direction),
+                    None => error("IOPIN without a preceding FLAG".to_string()),
+                }
+            }
+            "SYMBOL" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                if parts.len() < 3 {
+                    error("SYMBOL expects a name and a position".to_string());
+                    continue;
+                }
+                let orientation = match parts.get(3) {
+                    Some(o) => Orientation::parse(o),
+                    None => Some(Orientation::R0),
+                };
+                match (parse_int(parts[1]), parse_int(parts[2]), orientation) {
+                    (Some(x), Some(y), Some(orientation)) => sheet.symbols.push(SymbolInstance {
+                        symbol: normalise_symbol_name(parts[0]),
+                        position: Point::new(x, y),
+                        orientation,
+                        attributes: BTreeMap::new(),
+                    }),
+                    _ => error(format!("Invalid symbol placement: {}", rest)),
+                }
+            }
+            "SYMATTR" => {
+                let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                match sheet.symbols.last_mut() {
+                    Some(symbol) => {
+                        symbol.attributes.insert(key.to_string(), value.to_string());
+                    }
+                    None => error("SYMATTR without a preceding SYMBOL".to_string()),
+                }
+            }
+            "TEXT" => match parse_text(rest) {
+                Some((position, text)) => {
+                    // Multi-line text is stored with literal "\n" separators.
+                    let text = text.replace("\\n", "\n");
+                    let (kind, content) = match text.strip_prefix('!') {
+                        Some(directive) => (TextKind::Directive, directive.to_string()),
+                        None => (
+                            TextKind::Comment,
+                            text.strip_prefix(';').unwrap_or(&text).to_string(),
+                        ),
+                    };
+                    sheet.texts.push(Text {
+                        position,
+                        content,
+                        kind,
+                    });
+                }
+                None => error(format!("Invalid text: {}", rest)),
+            },
+            _ => {}
+        }
+    }
+
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(sheet)
+    }
+}
+
+/// Split `x y alignment size text` into the position and the text, which may
+/// itself contain spaces.
+pub(crate) fn parse_text(rest: &str) -> Option<(Point, &str)> {
+    let mut remaining = rest;
+    let mut head = [""; 4];
+    for field in head.iter_mut() {
+        let (value, tail) = remaining.split_once(char::is_whitespace)?;
+        *field = value;
+        remaining = tail.trim_start();
+    }
+    Some((
+        Point::new(parse_int(head[0])?, parse_int(head[1])?),
+        remaining,
+    ))
+}
+
+/// Symbol names use Windows path separators for sub-libraries,
+/// e.g. `Misc\\NE555`.
+fn normalise_symbol_name(name: &str) -> String {
+    name.replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_asc() {
+        let source = "Version 4\n\
+            SHEET 1 880 680\n\
+            WIRE 112 96 48 96\n\
+            FLAG 48 176 0\n\
+            FLAG 112 96 out\n\
+            IOPIN 112 96 Out\n\
+            SYMBOL res 96 80 R0\n\
+            WINDOW 0 36 40 Left 2\n\
+            SYMATTR InstName R1\n\
+            SYMATTR Value 1k\n\
+            SYMBOL voltage 48 80 M90\n\
+            SYMATTR InstName V1\n\
+            SYMATTR Value SINE(0 1 1k)\n\
+            TEXT -40 200 Left 2 !.tran 10m\\n.op\n\
+            TEXT -40 230 Left 2 ;a comment\n";
+        let sheet = parse_asc(source).unwrap();
+        assert_eq!(sheet.size, (880, 680));
+        assert_eq!(sheet.wires.len(), 1);
+        assert_eq!(sheet.flags[0].kind, FlagKind::Ground);
+        assert_eq!(sheet.flags[1].kind, FlagKind::Port(PortDirection::Output));
+        assert_eq!(sheet.symbols[1].orientation, Orientation::M90);
+        assert_eq!(sheet.symbols[1].attributes["Value"], "SINE(0 1 1k)");
+        assert_eq!(sheet.texts[0].kind, TextKind::Directive);
+        assert_eq!(sheet.texts[0].content, ".tran 10m\n.op");
+        assert_eq!(sheet.texts[1].content, "a comment");
+    }
+
+    #[test]
+    fn test_parse_asc_errors() {
+        let source = "WIRE 1 2 three 4\nSYMATTR InstName R1\n";
+        let errors = parse_asc(source).unwrap_err();
+        assert_eq!(errors.errors.len(), 2);
+        assert_eq!(errors.errors[1].line, 2);
+    }
+}
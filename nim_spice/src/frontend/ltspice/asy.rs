@@ -0,0 +1,147 @@
+use crate::frontend::ltspice::asc::parse_text;
+use crate::frontend::ltspice::{fields, parse_int};
+use crate::frontend::schematic::{Pin, Point, Shape, Symbol, SymbolKind};
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+
+/// Parse the contents of an LTspice `.asy` symbol.
+///
+/// Pins without a `SpiceOrder` attribute are netlisted in the order they are
+/// declared.
+pub(crate) fn parse_asy(name: &str, content: &str) -> Result<Symbol, ErrorHandler> {
+    let mut symbol = Symbol {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let mut error_handler = ErrorHandler::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line_no = number + 1;
+        let line = line.trim_end();
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim_start()),
+            None => (line, ""),
+        };
+        let mut error = |message: String| {
+            error_handler.add_error(Error::new(ErrorType::Syntax, message, line_no, 1));
+        };
+
+        match keyword.to_ascii_uppercase().as_str() {
+            "SYMBOLTYPE" => match rest.to_ascii_uppercase().as_str() {
+                "CELL" => symbol.kind = SymbolKind::Cell,
+                "BLOCK" => symbol.kind = SymbolKind::Block,
+                "GRAPHIC" => symbol.kind = SymbolKind::Graphic,
+                t => error(format!("Unknown symbol type: {}", t)),
+            },
+            "LINE" | "RECTANGLE" | "CIRCLE" => {
+                // The first field is the line style, e.g. `Normal`.
+                match fields::<5>(rest).map(|[_, x1, y1, x2, y2]| [x1, y1, x2, y2].map(parse_int)) {
+                    Some([Some(x1), Some(y1), Some(x2), Some(y2)]) => {
+                        let (a, b) = (Point::new(x1, y1), Point::new(x2, y2));
+                        symbol
+                            .shapes
+                            .push(match keyword.to_ascii_uppercase().as_str() {
+                                "LINE" => Shape::Line(a, b),
+                                "RECTANGLE" => Shape::Rectangle(a, b),
+                                _ => Shape::Circle(a, b),
+                            });
+                    }
+                    _ => error(format!("Invalid {}: {}", keyword, rest)),
+                }
+            }
+            "ARC" => match fields::<9>(rest) {
+                Some(f) => {
+                    let v: Vec<i32> = f[1..].iter().filter_map(|s| parse_int(s)).collect();
+                    if v.len() == 8 {
+                        symbol.shapes.push(Shape::Arc(
+                            Point::new(v[0], v[1]),
+                            Point::new(v[2], v[3]),
+                            Point::new(v[4], v[5]),
+                            Point::new(v[6], v[7]),
+                        ));
+                    } else {
+                        error(format!("Invalid ARC: {}", rest));
+                    }
+                }
+                None => error(format!("Invalid ARC: {}", rest)),
+            },
+            "TEXT" => match parse_text(rest) {
+                Some((position, text)) => {
+                    symbol.shapes.push(Shape::Text(position, text.to_string()))
+                }
+                None => error(format!("Invalid text: {}", rest)),
+            },
+            "SYMATTR" => {
+                let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                symbol.attributes.insert(key.to_string(), value.to_string());
+            }
+            "PIN" => match fields::<2>(rest).map(|f| f.map(parse_int)) {
+                Some([Some(x), Some(y)]) => {
+                    let order = symbol.pins.len() + 1;
+                    symbol.pins.push(Pin {
+                        position: Point::new(x, y),
+                        name: String::new(),
+                        number: String::new(),
+                        order,
+                    });
+                }
+                _ => error(format!("Invalid pin: {}", rest)),
+            },
+            "PINATTR" => {
+                let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                let pin = match symbol.pins.last_mut() {
+                    Some(pin) => pin,
+                    None => {
+                        error("PINATTR without a preceding PIN".to_string());
+                        continue;
+                    }
+                };
+                match key {
+                    "PinName" => pin.name = value.to_string(),
+                    "SpiceOrder" => match value.trim().parse() {
+                        Ok(order) => pin.order = order,
+                        Err(_) => error(format!("Invalid SpiceOrder: {}", value)),
+                    },
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_asy() {
+        let source = "Version 4\n\
+            SymbolType BLOCK\n\
+            RECTANGLE Normal -32 -32 32 32\n\
+            ARC Normal 0 0 32 32 0 16 32 16\n\
+            SYMATTR Prefix X\n\
+            SYMATTR Description Two port block\n\
+            PIN -32 0 LEFT 8\n\
+            PINATTR PinName in\n\
+            PINATTR SpiceOrder 2\n\
+            PIN 32 0 RIGHT 8\n\
+            PINATTR PinName out\n\
+            PINATTR SpiceOrder 1\n";
+        let symbol = parse_asy("block", source).unwrap();
+        assert_eq!(symbol.kind, SymbolKind::Block);
+        assert_eq!(symbol.shapes.len(), 2);
+        assert_eq!(symbol.attributes["Description"], "Two port block");
+        let ordered: Vec<&str> = symbol
+            .ordered_pins()
+            .iter()
+            .map(|(_, pin)| pin.name.as_str())
+            .collect();
+        assert_eq!(ordered, ["out", "in"]);
+    }
+}
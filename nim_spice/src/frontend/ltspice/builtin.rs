@@ -0,0 +1,162 @@
+//! Minimal versions of the stock LTspice symbols, so that schematics which
+//! only use the standard library import without an LTspice installation.
+//! Pin positions and netlist order match the originals; the drawings are
+//! simplified.
+
+pub(crate) fn builtin_symbol(name: &str) -> Option<&'static str> {
+    let source = match name.to_ascii_lowercase().as_str() {
+        "res" => RES,
+        "cap" => CAP,
+        "ind" => IND,
+        "voltage" => VOLTAGE,
+        "current" => CURRENT,
+        "diode" => DIODE,
+        "npn" => NPN,
+        "nmos" => NMOS,
+        _ => return None,
+    };
+    Some(source)
+}
+
+const RES: &str = "Version 4
+SymbolType CELL
+LINE Normal 16 16 16 32
+RECTANGLE Normal 0 32 32 80
+LINE Normal 16 80 16 96
+SYMATTR Value R
+SYMATTR Prefix R
+SYMATTR Description A resistor
+PIN 16 16 NONE 0
+PINATTR PinName A
+PINATTR SpiceOrder 1
+PIN 16 96 NONE 0
+PINATTR PinName B
+PINATTR SpiceOrder 2
+";
+
+const CAP: &str = "Version 4
+SymbolType CELL
+LINE Normal 16 0 16 24
+LINE Normal 0 24 32 24
+LINE Normal 0 40 32 40
+LINE Normal 16 40 16 64
+SYMATTR Value C
+SYMATTR Prefix C
+SYMATTR Description Capacitor
+PIN 16 0 NONE 0
+PINATTR PinName A
+PINATTR SpiceOrder 1
+PIN 16 64 NONE 0
+PINATTR PinName B
+PINATTR SpiceOrder 2
+";
+
+const IND: &str = "Version 4
+SymbolType CELL
+LINE Normal 16 16 16 24
+ARC Normal 0 24 32 48 16 48 16 24
+ARC Normal 0 48 32 72 16 72 16 48
+LINE Normal 16 72 16 96
+SYMATTR Value L
+SYMATTR Prefix L
+SYMATTR Description Inductor
+PIN 16 16 NONE 0
+PINATTR PinName A
+PINATTR SpiceOrder 1
+PIN 16 96 NONE 0
+PINATTR PinName B
+PINATTR SpiceOrder 2
+";
+
+const VOLTAGE: &str = "Version 4
+SymbolType CELL
+LINE Normal 0 16 0 24
+CIRCLE Normal -32 24 32 88
+LINE Normal 0 88 0 96
+SYMATTR Value V
+SYMATTR Prefix V
+SYMATTR Description Voltage Source, either DC, AC, PULSE, SINE, PWL, EXP, or SFFM
+PIN 0 16 NONE 0
+PINATTR PinName +
+PINATTR SpiceOrder 1
+PIN 0 96 NONE 0
+PINATTR PinName -
+PINATTR SpiceOrder 2
+";
+
+const CURRENT: &str = "Version 4
+SymbolType CELL
+CIRCLE Normal -32 8 32 72
+LINE Normal 0 0 0 8
+LINE Normal 0 72 0 80
+SYMATTR Value I
+SYMATTR Prefix I
+SYMATTR Description Current Source
+PIN 0 0 NONE 0
+PINATTR PinName +
+PINATTR SpiceOrder 1
+PIN 0 80 NONE 0
+PINATTR PinName -
+PINATTR SpiceOrder 2
+";
+
+const DIODE: &str = "Version 4
+SymbolType CELL
+LINE Normal 0 44 32 44
+LINE Normal 0 20 32 20
+LINE Normal 0 20 16 44
+LINE Normal 32 20 16 44
+LINE Normal 16 0 16 64
+SYMATTR Value D
+SYMATTR Prefix D
+SYMATTR Description Diode
+PIN 16 0 NONE 0
+PINATTR PinName +
+PINATTR SpiceOrder 1
+PIN 16 64 NONE 0
+PINATTR PinName -
+PINATTR SpiceOrder 2
+";
+
+const NPN: &str = "Version 4
+SymbolType CELL
+LINE Normal 0 48 48 48
+LINE Normal 48 16 48 80
+LINE Normal 48 32 64 0
+LINE Normal 48 64 64 96
+SYMATTR Value NPN
+SYMATTR Prefix QN
+SYMATTR Description Bipolar NPN transistor
+PIN 64 0 NONE 0
+PINATTR PinName C
+PINATTR SpiceOrder 1
+PIN 0 48 NONE 0
+PINATTR PinName B
+PINATTR SpiceOrder 2
+PIN 64 96 NONE 0
+PINATTR PinName E
+PINATTR SpiceOrder 3
+";
+
+const NMOS: &str = "Version 4
+SymbolType CELL
+LINE Normal 0 80 24 80
+LINE Normal 24 16 24 80
+LINE Normal 32 16 32 80
+LINE Normal 32 16 48 16
+LINE Normal 48 0 48 16
+LINE Normal 32 80 48 80
+LINE Normal 48 80 48 96
+SYMATTR Value NMOS
+SYMATTR Prefix MN
+SYMATTR Description N-Channel MOSFET transistor
+PIN 48 0 NONE 0
+PINATTR PinName D
+PINATTR SpiceOrder 1
+PIN 0 80 NONE 0
+PINATTR PinName G
+PINATTR SpiceOrder 2
+PIN 48 96 NONE 0
+PINATTR PinName S
+PINATTR SpiceOrder 3
+";
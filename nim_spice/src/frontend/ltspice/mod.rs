@@ -0,0 +1,339 @@
+//! Import of LTspice `.asc` schematics and `.asy` symbols.
+//!
+//! An imported schematic is turned into two things: a [`Schematic`] document
+//! for the editor, and a [`Netlist`] built the way LTspice netlists a sheet.
+//! Symbols are looked up next to the schematic first, then in the search
+//! paths, then among a handful of built-in stock symbols. A block symbol
+//! with a `.asc` of the same name beside it is a hierarchical sheet and is
+//! imported recursively as a subcircuit.
+
+mod asc;
+mod asy;
+mod builtin;
+
+use crate::frontend::parser::netlist::{Component, Netlist, Node, Subcircuit};
+use crate::frontend::schematic::{Schematic, Symbol, SymbolKind, TextKind};
+use crate::frontend::Import;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use std::path::{Path, PathBuf};
+
+pub struct Importer {
+    search_paths: Vec<PathBuf>,
+    error_handler: ErrorHandler,
+}
+
+impl Default for Importer {
+    fn default() -> Self {
+        Importer {
+            search_paths: Vec::new(),
+            error_handler: ErrorHandler::new(),
+        }
+    }
+}
+
+impl Importer {
+    pub fn new() -> Self {
+        Importer::default()
+    }
+
+    /// Add a directory to look in for `.asy` symbols, such as LTspice's
+    /// `lib/sym`.
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        self.search_paths.push(path.into());
+    }
+
+    pub fn import(mut self, path: &Path) -> Result<Import, ErrorHandler> {
+        let mut stack = Vec::new();
+        let schematic = self.load_sheet(path, &mut stack);
+        if self.error_handler.has_errors() {
+            return Err(self.error_handler);
+        }
+        let schematic = schematic.unwrap();
+        let netlist = netlist(&schematic);
+        Ok(Import { schematic, netlist })
+    }
+
+    fn load_sheet(&mut self, path: &Path, stack: &mut Vec<PathBuf>) -> Option<Schematic> {
+        let content = self.read(path)?;
+        let mut sheet = match asc::parse_asc(&content) {
+            Ok(sheet) => sheet,
+            Err(errors) => {
+                self.merge(path, errors);
+                return None;
+            }
+        };
+        sheet.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        stack.push(canonical);
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let names: Vec<String> = sheet.symbols.iter().map(|s| s.symbol.clone()).collect();
+        for name in names {
+            if sheet.library.contains_key(&name) {
+                continue;
+            }
+            let Some(symbol) = self.load_symbol(&directory, &name) else {
+                continue;
+            };
+
+            let child = directory.join(format!("{}.asc", name));
+            if symbol.kind == SymbolKind::Block && child.is_file() {
+                let canonical = child.canonicalize().unwrap_or_else(|_| child.clone());
+                if stack.contains(&canonical) {
+                    self.error_handler.add_error(Error::new(
+                        ErrorType::Semantic,
+                        format!("{}: sheet {} instantiates itself", path.display(), name),
+                        0,
+                        0,
+                    ));
+                } else if let Some(child) = self.load_sheet(&child, stack) {
+                    sheet.sheets.insert(name.clone(), child);
+                }
+            }
+            sheet.library.insert(name, symbol);
+        }
+        stack.pop();
+        Some(sheet)
+    }
+
+    fn load_symbol(&mut self, directory: &Path, name: &str) -> Option<Symbol> {
+        let file = format!("{}.asy", name);
+        let found = std::iter::once(directory.to_path_buf())
+            .chain(self.search_paths.iter().cloned())
+            .map(|dir| dir.join(&file))
+            .find(|candidate| candidate.is_file());
+
+        let result = match found {
+            Some(path) => {
+                let content = self.read(&path)?;
+                asy::parse_asy(name, &content).map_err(|errors| (path, errors))
+            }
+            None => match builtin::builtin_symbol(name) {
+                Some(source) => asy::parse_asy(name, source).map_err(|e| (PathBuf::new(), e)),
+                None => {
+                    self.error_handler.add_error(Error::new(
+                        ErrorType::IOError,
+                        format!("Could not find symbol: {}", name),
+                        0,
+                        0,
+                    ));
+                    return None;
+                }
+            },
+        };
+        match result {
+            Ok(symbol) => Some(symbol),
+            Err((path, errors)) => {
+                self.merge(&path, errors);
+                None
+            }
+        }
+    }
+
+    fn read(&mut self, path: &Path) -> Option<String> {
+        match std::fs::read(path) {
+            Ok(bytes) => Some(decode(&bytes)),
+            Err(e) => {
+                self.error_handler.add_error(Error::new(
+                    ErrorType::IOError,
+                    format!("{}: {}", path.display(), e),
+                    0,
+                    0,
+                ));
+                None
+            }
+        }
+    }
+
+    /// Keep errors from a nested file, prefixed with its path.
+    fn merge(&mut self, path: &Path, errors: ErrorHandler) {
+        for mut error in errors.errors {
+            error.message = format!("{}: {}", path.display(), error.message);
+            self.error_handler.add_error(error);
+        }
+    }
+}
+
+/// Build the netlist of a sheet, with every child sheet as a subcircuit named
+/// after its block symbol.
+pub fn netlist(sheet: &Schematic) -> Netlist {
+    let mut out = Netlist::new();
+    out.title = sheet.name.clone();
+    let connectivity = sheet.connectivity();
+
+    for (i, instance) in sheet.symbols.iter().enumerate() {
+        let Some(symbol) = sheet.symbol_of(instance) else {
+            continue;
+        };
+        if symbol.kind == SymbolKind::Graphic || symbol.pins.is_empty() {
+            continue;
+        }
+        let attribute = |key: &str| {
+            instance
+                .attributes
+                .get(key)
+                .or_else(|| symbol.attributes.get(key))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+
+        let prefix = attribute("Prefix")
+            .and_then(|p| p.chars().next())
+            .unwrap_or('X');
+        let instance_name = instance.name();
+        let name = if instance_name
+            .chars()
+            .next()
+            .is_some_and(|c| c.eq_ignore_ascii_case(&prefix))
+        {
+            instance_name.to_string()
+        } else {
+            format!("{}{}", prefix, instance_name)
+        };
+
+        let nodes = symbol
+            .ordered_pins()
+            .into_iter()
+            .map(|(j, _)| {
+                let net = connectivity.net_of(i, j).map(|n| n.name.clone());
+                Node::new(net.unwrap_or_default())
+            })
+            .collect();
+
+        let mut value: Vec<&str> = Vec::new();
+        if sheet.sheets.contains_key(&instance.symbol) {
+            value.push(&instance.symbol);
+        } else if let Some(model) = attribute("SpiceModel") {
+            value.push(model);
+        }
+        for key in ["Value", "Value2", "SpiceLine", "SpiceLine2"] {
+            if let Some(v) = attribute(key) {
+                value.push(v);
+            }
+        }
+        out.add_component(Component::new(name, nodes, value.join(" ")));
+    }
+
+    for (name, child) in &sheet.sheets {
+        let ports = sheet.library[name]
+            .ordered_pins()
+            .into_iter()
+            .map(|(_, pin)| Node::new(pin.name.clone()))
+            .collect();
+        out.subcircuits.push(Subcircuit {
+            name: name.clone(),
+            ports,
+            body: netlist(child),
+        });
+    }
+
+    for text in &sheet.texts {
+        if text.kind == TextKind::Directive {
+            for line in text.content.lines().filter(|l| !l.trim().is_empty()) {
+                out.add_directive(line.trim().to_string());
+            }
+        }
+    }
+    out
+}
+
+/// LTspice writes UTF-16LE (with or without a byte order mark) or a single
+/// byte encoding depending on the version.
+fn decode(bytes: &[u8]) -> String {
+    let utf16 =
+        bytes.starts_with(&[0xFF, 0xFE]) || (bytes.len() >= 2 && bytes[1] == 0 && bytes[0] != 0);
+    if utf16 {
+        let body = bytes.strip_prefix(&[0xFF, 0xFE]).unwrap_or(bytes);
+        let units: Vec<u16> = body
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.trim_start_matches('\u{feff}').to_string(),
+        // Latin-1: every byte is its own code point.
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+fn parse_int(s: &str) -> Option<i32> {
+    s.parse().ok()
+}
+
+/// The first `N` whitespace separated fields of `s`.
+fn fields<const N: usize>(s: &str) -> Option<[&str; N]> {
+    let mut out = [""; N];
+    let mut iter = s.split_whitespace();
+    for field in out.iter_mut() {
+        *field = iter.next()?;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_import_hierarchy() {
+        let dir = std::env::temp_dir().join("nimbus_ltspice_import");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // A block wrapping a single resistor between its two ports.
+        fs::write(
+            dir.join("divider.asy"),
+            "Version 4\nSymbolType BLOCK\nRECTANGLE Normal -16 -16 16 16\n\
+             PIN -16 0 LEFT 8\nPINATTR PinName a\nPINATTR SpiceOrder 1\n\
+             PIN 16 0 RIGHT 8\nPINATTR PinName b\nPINATTR SpiceOrder 2\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("divider.asc"),
+            "Version 4\nSHEET 1 100 100\nFLAG 16 16 a\nIOPIN 16 16 In\n\
+             FLAG 16 96 b\nIOPIN 16 96 Out\nSYMBOL res 0 0 R0\nSYMATTR InstName R1\n\
+             SYMATTR Value 10k\n",
+        )
+        .unwrap();
+        // UTF-16LE top level sheet, as written by LTspice XVII.
+        let top = "Version 4\nSHEET 1 880 680\nWIRE 0 16 -16 16\nFLAG 0 96 0\nFLAG 16 16 out\n\
+                   SYMBOL voltage 0 0 R0\nSYMATTR InstName V1\nSYMATTR Value 5\n\
+                   SYMBOL divider 0 16 R0\nSYMATTR InstName X1\n\
+                   TEXT 0 200 Left 2 !.op\n";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(top.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        fs::write(dir.join("top.asc"), bytes).unwrap();
+
+        let import = Importer::new().import(&dir.join("top.asc")).unwrap();
+        assert!(import.schematic.sheets.contains_key("divider"));
+
+        let netlist = import.netlist;
+        let v1 = netlist.component("V1").unwrap();
+        assert_eq!(v1.nodes, [Node::new("N001"), Node::new("0")]);
+        let x1 = netlist.component("X1").unwrap();
+        assert_eq!(x1.nodes, [Node::new("N001"), Node::new("out")]);
+        assert_eq!(x1.value, "divider");
+        let divider = &netlist.subcircuits[0];
+        assert_eq!(divider.ports, [Node::new("a"), Node::new("b")]);
+        assert_eq!(divider.body.components[0].to_string(), "R1 a b 10k");
+        assert_eq!(netlist.directives, [".op"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_symbol() {
+        let dir = std::env::temp_dir().join("nimbus_ltspice_missing");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("top.asc"), "SYMBOL nowhere 0 0 R0\n").unwrap();
+        let errors = Importer::new().import(&dir.join("top.asc")).err().unwrap();
+        assert!(errors.errors[0].message.contains("nowhere"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
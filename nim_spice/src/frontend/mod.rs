@@ -1 +1,14 @@
-mod parser;
+use crate::frontend::parser::netlist::Netlist;
+use crate::frontend::schematic::Schematic;
+
+pub mod kicad;
+pub mod ltspice;
+pub mod parser;
+pub mod schematic;
+
+/// A schematic read from another tool's format, with the netlist that
+/// describes it for simulation.
+pub struct Import {
+    pub schematic: Schematic,
+    pub netlist: Netlist,
+}
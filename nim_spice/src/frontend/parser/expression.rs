@@ -0,0 +1,554 @@
+use crate::frontend::parser::pwl::Table;
+use crate::maths::dual::Real;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+use std::f64::consts::PI;
+
+/// An arithmetic expression as written in `.meas`, `.param` and behavioural
+/// sources, e.g. `2*V(out,in)/I(R1) + sqrt(abs(x))`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Variable(String),
+    /// A function call. For `V(...)` and `I(...)` style probes the
+    /// arguments are kept as raw node or element names in `Variable`s.
+    Call(String, Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+/// Names and functions an expression can refer to beyond the built-in
+/// maths functions, with values of type `T`: `f64`, or a dual number for
+/// the derivatives of a behavioural source.
+pub trait Scope<T = f64> {
+    fn variable(&self, name: &str) -> Option<T>;
+
+    /// Evaluate a function the scope defines itself, such as `V(out)`.
+    /// `None` falls back to the built-in functions.
+    fn function(&self, _name: &str, _args: &[Expr]) -> Option<Result<T, String>> {
+        None
+    }
+}
+
+/// Functions whose arguments are node or element names, not expressions.
+const PROBES: [&str; 8] = ["V", "I", "IB", "IC", "IE", "ID", "IG", "IS"];
+
+/// Whether `name(...)` reads a node voltage or element current.
+pub(crate) fn is_probe(name: &str) -> bool {
+    PROBES.iter().any(|p| p.eq_ignore_ascii_case(name))
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, ErrorHandler> {
+        let mut parser = ExprParser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        let result = parser.logical().and_then(|expr| {
+            parser.skip_whitespace();
+            match parser.peek() {
+                None => Ok(expr),
+                Some(c) => Err(format!("Unexpected '{}'", c)),
+            }
+        });
+        result.map_err(|message| {
+            let mut error_handler = ErrorHandler::new();
+            error_handler.add_error(Error::new(
+                ErrorType::Syntax,
+                format!("{} in expression {}", message, text),
+                0,
+                parser.position + 1,
+            ));
+            error_handler
+        })
+    }
+
+    /// Evaluate in `scope`. With dual numbers the derivatives follow the
+    /// value; comparisons, rounding and the sign functions are flat.
+    pub fn eval<T: Real>(&self, scope: &dyn Scope<T>) -> Result<T, String> {
+        match self {
+            Expr::Number(value) => Ok(T::constant(*value)),
+            Expr::Variable(name) => scope
+                .variable(name)
+                .or_else(|| name.eq_ignore_ascii_case("pi").then(|| T::constant(PI)))
+                .ok_or_else(|| format!("Unknown name: {}", name)),
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(scope)?;
+                Ok(match op {
+                    UnaryOp::Negate => -value,
+                    UnaryOp::Not => truth(value.value() == 0.0),
+                })
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(scope)?, rhs.eval(scope)?);
+                let (x, y) = (a.value(), b.value());
+                Ok(match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Subtract => a - b,
+                    BinaryOp::Multiply => a * b,
+                    BinaryOp::Divide => a / b,
+                    BinaryOp::Power => a.powf(b),
+                    BinaryOp::Less => truth(x < y),
+                    BinaryOp::LessEqual => truth(x <= y),
+                    BinaryOp::Greater => truth(x > y),
+                    BinaryOp::GreaterEqual => truth(x >= y),
+                    BinaryOp::Equal => truth(x == y),
+                    BinaryOp::NotEqual => truth(x != y),
+                    BinaryOp::And => truth(x != 0.0 && y != 0.0),
+                    BinaryOp::Or => truth(x != 0.0 || y != 0.0),
+                })
+            }
+            Expr::Call(name, args) => {
+                if let Some(result) = scope.function(name, args) {
+                    return result;
+                }
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(scope))
+                    .collect::<Result<Vec<T>, String>>()?;
+                builtin(name, &values)
+            }
+        }
+    }
+}
+
+fn truth<T: Real>(condition: bool) -> T {
+    T::constant(if condition { 1.0 } else { 0.0 })
+}
+
+fn builtin<T: Real>(name: &str, args: &[T]) -> Result<T, String> {
+    let lower = name.to_ascii_lowercase();
+    if lower == "table" {
+        return table(args);
+    }
+    let arity = match lower.as_str() {
+        "if" | "limit" => 3,
+        "atan2" | "pow" | "pwr" | "pwrs" | "min" | "max" | "hypot" => 2,
+        _ => 1,
+    };
+    if args.len() != arity {
+        return Err(format!(
+            "{}() takes {} argument{}, found {}",
+            name,
+            arity,
+            if arity == 1 { "" } else { "s" },
+            args.len()
+        ));
+    }
+    let x = args[0];
+    let flat = |f: fn(f64) -> f64| T::constant(f(x.value()));
+    let sign = || {
+        if x.value() == 0.0 {
+            0.0
+        } else {
+            x.value().signum()
+        }
+    };
+    Ok(match lower.as_str() {
+        "abs" => x.abs(),
+        "sqrt" => x.sqrt(),
+        "exp" => x.exp(),
+        "ln" | "log" => x.ln(),
+        "log10" => x.log10(),
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        "sinh" => x.sinh(),
+        "cosh" => x.cosh(),
+        "tanh" => x.tanh(),
+        "floor" => flat(f64::floor),
+        "ceil" => flat(f64::ceil),
+        "round" => flat(f64::round),
+        "int" => flat(f64::trunc),
+        "sgn" | "sign" => T::constant(sign()),
+        "u" => truth(x.value() > 0.0),
+        "atan2" => x.atan2(args[1]),
+        "pow" => x.powf(args[1]),
+        "pwr" => x.abs().powf(args[1]),
+        "pwrs" => x.abs().powf(args[1]) * x.value().signum(),
+        "min" => x.min(args[1]),
+        "max" => x.max(args[1]),
+        "hypot" => x.hypot(args[1]),
+        "if" => {
+            if x.value() != 0.0 {
+                args[1]
+            } else {
+                args[2]
+            }
+        }
+        "limit" => x.max(args[1].min(args[2])).min(args[1].max(args[2])),
+        _ => return Err(format!("Unknown function: {}", name)),
+    })
+}
+
+/// `table(x, x1, y1, x2, y2, ...)`, interpolated linearly between constant
+/// points, so that the result follows `x` along the segment it falls on.
+fn table<T: Real>(args: &[T]) -> Result<T, String> {
+    let Some((&x, pairs)) = args.split_first() else {
+        return Err("table() needs an argument and x/y pairs".to_string());
+    };
+    let pairs: Vec<f64> = pairs.iter().map(|p| p.value()).collect();
+    let table = Table::new(&pairs)?;
+    let slope = table.slope(x.value());
+    Ok(x * slope + (table.value(x.value()) - slope * x.value()))
+}
+
+struct ExprParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// Consume `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + token.chars().count();
+        if end <= self.chars.len()
+            && self.chars[self.position..end]
+                .iter()
+                .copied()
+                .eq(token.chars())
+        {
+            self.position = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn logical(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.comparison()?;
+        loop {
+            let op = if self.eat("&&") || self.eat("&") {
+                BinaryOp::And
+            } else if self.eat("||") || self.eat("|") {
+                BinaryOp::Or
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.comparison()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.additive()?;
+        loop {
+            let op = if self.eat("<=") {
+                BinaryOp::LessEqual
+            } else if self.eat(">=") {
+                BinaryOp::GreaterEqual
+            } else if self.eat("==") {
+                BinaryOp::Equal
+            } else if self.eat("!=") {
+                BinaryOp::NotEqual
+            } else if self.eat("<") {
+                BinaryOp::Less
+            } else if self.eat(">") {
+                BinaryOp::Greater
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.additive()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Subtract
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.multiplicative()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            // `**` is a power, handled further down.
+            let op = if self.chars.get(self.position + 1) != Some(&'*') && self.eat("*") {
+                BinaryOp::Multiply
+            } else if self.eat("/") {
+                BinaryOp::Divide
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Unary(UnaryOp::Negate, Box::new(self.unary()?)))
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("!") {
+            Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.primary()?;
+        if self.eat("**") || self.eat("^") {
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(
+                BinaryOp::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err("Unexpected end".to_string()),
+            Some('(') | Some('{') => {
+                let close = if self.peek() == Some('(') { ")" } else { "}" };
+                self.position += 1;
+                let expr = self.logical()?;
+                if !self.eat(close) {
+                    return Err(format!("Expected '{}'", close));
+                }
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.position;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '$')
+                {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                self.skip_whitespace();
+                if self.peek() != Some('(') {
+                    return Ok(Expr::Variable(name));
+                }
+                self.position += 1;
+                let args = if is_probe(&name) {
+                    self.probe_arguments()?
+                } else {
+                    self.arguments()?
+                };
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.position += 1;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let mut end = self.position + 1;
+            if matches!(self.chars.get(end), Some('+' | '-')) {
+                end += 1;
+            }
+            if self.chars.get(end).is_some_and(char::is_ascii_digit) {
+                self.position = end;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.position += 1;
+                }
+            }
+        }
+        // Scale suffix and unit, e.g. `10meg` or `5uF`.
+        while self.peek().is_some_and(char::is_alphabetic) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        parse_number(&text)
+            .map(Expr::Number)
+            .ok_or_else(|| format!("Invalid number {}", text))
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.logical()?);
+            if self.eat(")") {
+                return Ok(args);
+            }
+            if !self.eat(",") {
+                return Err("Expected ',' or ')'".to_string());
+            }
+        }
+    }
+
+    fn probe_arguments(&mut self) -> Result<Vec<Expr>, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != ')') {
+            self.position += 1;
+        }
+        if self.peek().is_none() {
+            return Err("Expected ')'".to_string());
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        self.position += 1;
+        Ok(text
+            .split(',')
+            .map(|name| Expr::Variable(name.trim().to_string()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::dual::Dual;
+    use std::collections::HashMap;
+
+    struct Values(HashMap<&'static str, f64>);
+
+    impl Scope for Values {
+        fn variable(&self, name: &str) -> Option<f64> {
+            self.0.get(name).copied()
+        }
+
+        fn function(&self, name: &str, args: &[Expr]) -> Option<Result<f64, String>> {
+            if name != "V" {
+                return None;
+            }
+            let node = |arg: &Expr| match arg {
+                Expr::Variable(node) => self.0.get(node.as_str()).copied().unwrap_or(0.0),
+                _ => 0.0,
+            };
+            Some(Ok(args.iter().map(node).fold(0.0, |acc, v| {
+                if acc == 0.0 {
+                    v
+                } else {
+                    acc - v
+                }
+            })))
+        }
+    }
+
+    fn eval(text: &str) -> f64 {
+        let scope = Values(HashMap::from([("x", 2.0), ("out", 3.0), ("in+", 1.0)]));
+        Expr::parse(text).unwrap().eval(&scope).unwrap()
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("2**3**2"), 512.0);
+        assert_eq!(eval("{x * 1k}"), 2000.0);
+        assert_eq!(eval("2.5e-3meg"), 2500.0);
+        assert_eq!(eval("V(out) / V(in+)"), 3.0);
+        assert_eq!(eval("V(out, in+)"), 2.0);
+        assert_eq!(eval("max(x, 5) - min(x, 5)"), 3.0);
+        assert_eq!(eval("if(x > 1 & x < 3, 10, 20)"), 10.0);
+        assert_eq!(eval("!(x == 2)"), 0.0);
+        assert!((eval("sin(pi/2)") - 1.0).abs() < 1e-12);
+        assert_eq!(eval("table(x, 3, 30, 1, 10)"), 20.0);
+        assert_eq!(eval("table(-x, 3, 30, 1, 10)"), 10.0);
+        assert_eq!(eval("table(x, 0, 7)"), 7.0);
+    }
+
+    /// Node voltages as inputs of a dual number, the way a behavioural
+    /// source gets its conductances.
+    struct Nodes(f64, f64);
+
+    impl Scope<Dual<2>> for Nodes {
+        fn variable(&self, _name: &str) -> Option<Dual<2>> {
+            None
+        }
+
+        fn function(&self, name: &str, args: &[Expr]) -> Option<Result<Dual<2>, String>> {
+            let node = |arg: &Expr| match arg {
+                Expr::Variable(node) if node == "a" => Dual::variable(self.0, 0),
+                Expr::Variable(node) if node == "b" => Dual::variable(self.1, 1),
+                _ => Dual::constant(0.0),
+            };
+            (name == "V").then(|| Ok(node(&args[0])))
+        }
+    }
+
+    #[test]
+    fn test_derivatives() {
+        let expr = Expr::parse(
+            "1m*tanh(V(a)/V(b)) + V(a)**2*exp(-V(b)) + limit(V(a), 0, 1) + u(V(b))
+                + table(V(a)*V(b), 0, 0, 1, 2, 2, 1)",
+        )
+        .unwrap();
+        let value = |a: f64, b: f64| {
+            let scope = Values(HashMap::from([("a", a), ("b", b)]));
+            expr.eval(&scope).unwrap()
+        };
+        let exact = expr.eval(&Nodes(0.4, 2.0)).unwrap();
+        assert!((exact.value - value(0.4, 2.0)).abs() < 1e-15);
+        let h = 1e-6;
+        let da = (value(0.4 + h, 2.0) - value(0.4 - h, 2.0)) / (2.0 * h);
+        let db = (value(0.4, 2.0 + h) - value(0.4, 2.0 - h)) / (2.0 * h);
+        assert!((exact.derivative(0) - da).abs() < 1e-8);
+        assert!((exact.derivative(1) - db).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("(1 + 2").is_err());
+        assert!(Expr::parse("3 4").is_err());
+        let errors = Expr::parse("V(out").err().unwrap();
+        assert_eq!(errors.errors[0].column, 6);
+        let scope = Values(HashMap::new());
+        assert!(Expr::parse("y").unwrap().eval(&scope).is_err());
+        assert!(Expr::parse("sqrt(1, 2)").unwrap().eval(&scope).is_err());
+        assert!(Expr::parse("table(1, 2)").unwrap().eval(&scope).is_err());
+    }
+}
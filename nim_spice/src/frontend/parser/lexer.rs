@@ -249,6 +249,8 @@ impl Lexer {
             ".dc" => self.add_token(TokenType::Dc, command),
             ".ac" => self.add_token(TokenType::Ac, command),
             ".op" => self.add_token(TokenType::Op, command),
+            ".step" => self.add_token(TokenType::Step, command),
+            ".meas" | ".measure" => self.add_token(TokenType::Meas, command),
             ".subckt" => self.add_token(TokenType::Subckt, command),
             ".plot" => self.add_token(TokenType::Plot, command),
             ".wave" => self.add_token(TokenType::Wave, command),
@@ -351,6 +353,16 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_step() {
+        let source = ".STEP param R 1 10 1\n.end\n";
+        let lexer = Lexer::new(source.into());
+        let tokens = lexer.scan_tokens().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::Step);
+        assert_eq!(tokens[2].content, "R");
+        assert_eq!(tokens.len(), 8);
+    }
+
     #[test]
     fn test_number() {
         let source = "-1.2E+3.4m";
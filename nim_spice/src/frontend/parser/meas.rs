@@ -0,0 +1,393 @@
+use crate::frontend::parser::expression::Expr;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+
+/// A `.meas` (or `.measure`) directive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// The analysis the measurement applies to; `None` applies it to all.
+    pub analysis: Option<AnalysisKind>,
+    pub name: String,
+    pub kind: MeasureKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnalysisKind {
+    Tran,
+    Ac,
+    Dc,
+    Op,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeasureKind {
+    /// `FIND expr AT=x` or `FIND expr WHEN ...`. Without a point the
+    /// expression is read from a single point result such as `.op`.
+    Find { expr: Expr, at: Option<Point> },
+    /// `DERIV expr AT=x` or `DERIV expr WHEN ...`.
+    Deriv { expr: Expr, at: Point },
+    /// `WHEN lhs=rhs`: the axis value of the crossing.
+    When(Condition),
+    /// `TRIG ... TARG ...`: the axis distance between two points.
+    TrigTarg { trig: Point, targ: Point },
+    /// `AVG`, `RMS`, `MIN`, `MAX`, `PP` or `INTEG` of `expr` over
+    /// `FROM=`/`TO=`, by default the whole run.
+    Statistic {
+        function: Statistic,
+        expr: Expr,
+        from: Option<Expr>,
+        to: Option<Expr>,
+    },
+    /// `PARAM expr`, combining earlier measurements and parameters.
+    Param(Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    Avg,
+    Rms,
+    Min,
+    Max,
+    Pp,
+    Integ,
+}
+
+/// A location on the sweep axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Point {
+    At(Expr),
+    When(Condition),
+}
+
+/// The `count`th time, after `delay`, that `lhs` crosses `rhs` on the
+/// given edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub lhs: Expr,
+    pub rhs: Expr,
+    pub edge: Edge,
+    pub count: Count,
+    pub delay: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rise,
+    Fall,
+    Cross,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Count {
+    Nth(usize),
+    Last,
+}
+
+const KEYWORDS: [&str; 20] = [
+    "FIND", "DERIV", "WHEN", "AT", "TRIG", "TARG", "VAL", "TD", "RISE", "FALL", "CROSS", "FROM",
+    "TO", "AVG", "RMS", "MIN", "MAX", "PP", "INTEG", "PARAM",
+];
+
+/// One keyword of a `.meas` line and the text that follows it up to the
+/// next keyword.
+struct Clause {
+    keyword: String,
+    text: String,
+}
+
+/// Parse a `.meas` line, for example
+///
+/// ```text
+/// .meas TRAN tr TRIG V(out) VAL=0.1 RISE=1 TARG V(out) VAL=0.9 RISE=1
+/// .meas TRAN vmax MAX V(out) FROM=1m TO=2m
+/// .meas AC gain FIND db(V(out)) AT=1k
+/// .meas overshoot PARAM (vmax - 1) / 1
+/// ```
+pub fn parse_meas(text: &str) -> Result<Measurement, ErrorHandler> {
+    let fail = |message: String| {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+        error_handler
+    };
+    let expr = |text: &str| {
+        if text.trim().is_empty() {
+            Err(fail("Missing expression".to_string()))
+        } else {
+            Expr::parse(text)
+        }
+    };
+
+    let mut words = text.split_whitespace().peekable();
+    if words
+        .peek()
+        .is_some_and(|w| w.eq_ignore_ascii_case(".meas") || w.eq_ignore_ascii_case(".measure"))
+    {
+        words.next();
+    }
+    let analysis = match words.peek().map(|w| w.to_ascii_uppercase()).as_deref() {
+        Some("TRAN") => Some(AnalysisKind::Tran),
+        Some("AC") => Some(AnalysisKind::Ac),
+        Some("DC") => Some(AnalysisKind::Dc),
+        Some("OP") => Some(AnalysisKind::Op),
+        _ => None,
+    };
+    if analysis.is_some() {
+        words.next();
+    }
+    let Some(name) = words.next() else {
+        return Err(fail("Missing measurement name".to_string()));
+    };
+    let clauses = clauses(words);
+    let Some(first) = clauses.first() else {
+        return Err(fail(format!("Measurement {} has no body", name)));
+    };
+
+    let find = |keyword: &str| clauses.iter().position(|c| c.keyword == keyword);
+    let value = |keyword: &str| -> Result<Option<Expr>, ErrorHandler> {
+        match find(keyword) {
+            Some(i) => Ok(Some(expr(&clauses[i].text)?)),
+            None => Ok(None),
+        }
+    };
+
+    let kind = match first.keyword.as_str() {
+        "FIND" | "DERIV" => {
+            let target = expr(&first.text)?;
+            let at = match (find("AT"), find("WHEN")) {
+                (Some(i), None) => Some(Point::At(expr(&clauses[i].text)?)),
+                (None, Some(i)) => Some(Point::When(condition(&clauses[i..], None)?)),
+                (None, None) => None,
+                (Some(_), Some(_)) => return Err(fail("Use either AT or WHEN".to_string())),
+            };
+            if first.keyword == "FIND" {
+                MeasureKind::Find { expr: target, at }
+            } else {
+                let Some(at) = at else {
+                    return Err(fail("DERIV needs AT or WHEN".to_string()));
+                };
+                MeasureKind::Deriv { expr: target, at }
+            }
+        }
+        "WHEN" => MeasureKind::When(condition(&clauses, None)?),
+        "TRIG" => {
+            let Some(targ) = find("TARG") else {
+                return Err(fail("TRIG needs a TARG".to_string()));
+            };
+            MeasureKind::TrigTarg {
+                trig: point(&clauses[..targ])?,
+                targ: point(&clauses[targ..])?,
+            }
+        }
+        "AVG" | "RMS" | "MIN" | "MAX" | "PP" | "INTEG" => MeasureKind::Statistic {
+            function: match first.keyword.as_str() {
+                "AVG" => Statistic::Avg,
+                "RMS" => Statistic::Rms,
+                "MIN" => Statistic::Min,
+                "MAX" => Statistic::Max,
+                "PP" => Statistic::Pp,
+                _ => Statistic::Integ,
+            },
+            expr: expr(&first.text)?,
+            from: value("FROM")?,
+            to: value("TO")?,
+        },
+        "PARAM" => MeasureKind::Param(expr(&first.text)?),
+        keyword => {
+            return Err(fail(format!(
+                "Unexpected {} in measurement {}",
+                keyword, name
+            )))
+        }
+    };
+    Ok(Measurement {
+        analysis,
+        name: name.to_string(),
+        kind,
+    })
+}
+
+/// Group the words of the measurement body by keyword. A keyword may be
+/// written on its own, as `KEY=value` or as `KEY = value`.
+fn clauses<'a>(words: impl Iterator<Item = &'a str>) -> Vec<Clause> {
+    let mut clauses: Vec<Clause> = Vec::new();
+    for word in words {
+        let (head, tail) = match word.split_once('=') {
+            Some((head, tail)) => (head, tail),
+            None => (word, ""),
+        };
+        let upper = head.to_ascii_uppercase();
+        if KEYWORDS.contains(&upper.as_str()) {
+            clauses.push(Clause {
+                keyword: upper,
+                text: tail.to_string(),
+            });
+        } else if let Some(last) = clauses.last_mut() {
+            // Drop the `=` of `KEY = value`.
+            let word = if last.text.is_empty() && word.starts_with('=') {
+                &word[1..]
+            } else {
+                word
+            };
+            if !last.text.is_empty() {
+                last.text.push(' ');
+            }
+            last.text.push_str(word);
+        } else {
+            clauses.push(Clause {
+                keyword: String::new(),
+                text: word.to_string(),
+            });
+        }
+    }
+    clauses
+}
+
+/// The point given by a `TRIG` or `TARG` clause and the options after it.
+fn point(clauses: &[Clause]) -> Result<Point, ErrorHandler> {
+    if let Some(at) = clauses.iter().find(|c| c.keyword == "AT") {
+        return Ok(Point::At(Expr::parse(&at.text)?));
+    }
+    let value = clauses.iter().find(|c| c.keyword == "VAL");
+    Ok(Point::When(condition(clauses, value)?))
+}
+
+/// A crossing from `clauses[0]`, which holds either `lhs=rhs` or just `lhs`
+/// with the level given by `value`, and the RISE, FALL, CROSS and TD
+/// options after it.
+fn condition(clauses: &[Clause], value: Option<&Clause>) -> Result<Condition, ErrorHandler> {
+    let fail = |message: String| {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+        error_handler
+    };
+    let text = &clauses[0].text;
+    let (lhs, rhs) = match value {
+        Some(value) => (text.as_str(), value.text.as_str()),
+        None => split_equation(text)
+            .ok_or_else(|| fail(format!("Expected lhs=rhs after {}", clauses[0].keyword)))?,
+    };
+
+    let mut edge = Edge::Cross;
+    let mut count = Count::Nth(1);
+    let mut delay = None;
+    // Options belong to this condition until the next TRIG or TARG.
+    for clause in clauses[1..]
+        .iter()
+        .take_while(|c| c.keyword != "TRIG" && c.keyword != "TARG")
+    {
+        let clause_edge = match clause.keyword.as_str() {
+            "RISE" => Edge::Rise,
+            "FALL" => Edge::Fall,
+            "CROSS" => Edge::Cross,
+            "TD" => {
+                delay = Some(Expr::parse(&clause.text)?);
+                continue;
+            }
+            _ => continue,
+        };
+        edge = clause_edge;
+        count = if clause.text.eq_ignore_ascii_case("LAST") {
+            Count::Last
+        } else {
+            match clause.text.trim().parse::<usize>() {
+                Ok(n) if n > 0 => Count::Nth(n),
+                _ => {
+                    return Err(fail(format!(
+                        "{} needs a positive count or LAST, found {}",
+                        clause.keyword, clause.text
+                    )))
+                }
+            }
+        };
+    }
+    Ok(Condition {
+        lhs: Expr::parse(lhs)?,
+        rhs: Expr::parse(rhs)?,
+        edge,
+        count,
+        delay,
+    })
+}
+
+/// Split `lhs=rhs` at an `=` that is not part of `==`, `<=`, `>=` or `!=`.
+fn split_equation(text: &str) -> Option<(&str, &str)> {
+    let bytes = text.as_bytes();
+    let position = (0..bytes.len()).find(|&i| {
+        bytes[i] == b'='
+            && !matches!(bytes.get(i + 1), Some(b'='))
+            && (i == 0 || !matches!(bytes[i - 1], b'=' | b'<' | b'>' | b'!'))
+    })?;
+    Some((&text[..position], &text[position + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_meas() {
+        let meas = parse_meas(".meas TRAN vout FIND V(out) AT=5m").unwrap();
+        assert_eq!(meas.analysis, Some(AnalysisKind::Tran));
+        assert_eq!(meas.name, "vout");
+        assert_eq!(
+            meas.kind,
+            MeasureKind::Find {
+                expr: Expr::parse("V(out)").unwrap(),
+                at: Some(Point::At(Expr::Number(5e-3))),
+            }
+        );
+
+        let meas = parse_meas(
+            ".meas TRAN tr TRIG V(out) VAL=0.1 RISE=2 TARG V(out) val = 0.9 FALL=LAST TD=1u",
+        )
+        .unwrap();
+        let MeasureKind::TrigTarg {
+            trig: Point::When(trig),
+            targ: Point::When(targ),
+        } = meas.kind
+        else {
+            panic!("{:?}", meas.kind);
+        };
+        assert_eq!((trig.edge, trig.count), (Edge::Rise, Count::Nth(2)));
+        assert_eq!(trig.rhs, Expr::Number(0.1));
+        assert_eq!(trig.delay, None);
+        assert_eq!((targ.edge, targ.count), (Edge::Fall, Count::Last));
+        assert_eq!(targ.rhs, Expr::Number(0.9));
+        assert_eq!(targ.delay, Some(Expr::Number(1e-6)));
+
+        let meas = parse_meas(".measure t50 WHEN V(a)=V(b)/2 CROSS=3").unwrap();
+        assert_eq!(meas.analysis, None);
+        let MeasureKind::When(condition) = meas.kind else {
+            panic!();
+        };
+        assert_eq!(condition.lhs, Expr::parse("V(a)").unwrap());
+        assert_eq!(condition.count, Count::Nth(3));
+
+        let meas = parse_meas(".meas TRAN p PP V(out) FROM=1m").unwrap();
+        assert!(matches!(
+            meas.kind,
+            MeasureKind::Statistic {
+                function: Statistic::Pp,
+                from: Some(_),
+                to: None,
+                ..
+            }
+        ));
+
+        let meas = parse_meas(".meas ratio PARAM vout / 2").unwrap();
+        assert_eq!(
+            meas.kind,
+            MeasureKind::Param(Expr::parse("vout/2").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_meas_errors() {
+        assert!(parse_meas(".meas TRAN").is_err());
+        assert!(parse_meas(".meas TRAN x").is_err());
+        assert!(parse_meas(".meas TRAN x DERIV V(out)").is_err());
+        assert!(parse_meas(".meas TRAN x TRIG V(out) VAL=1").is_err());
+        assert!(parse_meas(".meas TRAN x WHEN V(out)").is_err());
+        assert!(parse_meas(".meas TRAN x WHEN V(out)=1 RISE=0").is_err());
+        assert!(parse_meas(".meas TRAN x FIND V(out) AT=1 WHEN V(a)=1").is_err());
+    }
+}
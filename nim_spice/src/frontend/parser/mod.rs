@@ -1,4 +1,10 @@
+pub mod expression;
 mod lexer;
-mod netlist;
+pub mod meas;
+pub mod netlist;
 mod parser;
+pub mod pwl;
+pub mod source;
+pub mod step;
 mod token;
+pub mod tran;
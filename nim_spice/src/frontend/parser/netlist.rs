@@ -1,11 +1,140 @@
-pub struct Netlist {}
+use std::fmt::Display;
+
+/// A circuit description as a list of element lines, subcircuit definitions
+/// and simulation directives, in the form LTspice writes into a `.net` file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Netlist {
+    pub title: String,
+    pub components: Vec<Component>,
+    pub subcircuits: Vec<Subcircuit>,
+    pub directives: Vec<String>,
+}
 
 impl Netlist {
-    pub(crate) fn new() -> Self {
-        todo!()
+    pub fn new() -> Self {
+        Netlist::default()
+    }
+
+    pub fn add_component(&mut self, component: Component) {
+        self.components.push(component);
+    }
+
+    pub fn add_directive(&mut self, directive: String) {
+        self.directives.push(directive);
+    }
+
+    pub fn component(&self, name: &str) -> Option<&Component> {
+        self.components
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Names of every node referenced by a component, in order of first use.
+    pub fn nodes(&self) -> Vec<Node> {
+        let mut nodes: Vec<Node> = Vec::new();
+        for component in &self.components {
+            for node in &component.nodes {
+                if !nodes.contains(node) {
+                    nodes.push(node.clone());
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Render the netlist as SPICE source text.
+    pub fn to_spice(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("* {}\n", self.title));
+        self.write_body(&mut out);
+        out.push_str(".end\n");
+        out
+    }
+
+    fn write_body(&self, out: &mut String) {
+        for component in &self.components {
+            out.push_str(&format!("{}\n", component));
+        }
+        for subcircuit in &self.subcircuits {
+            out.push_str(&format!("\n.subckt {}", subcircuit.name));
+            for port in &subcircuit.ports {
+                out.push_str(&format!(" {}", port.name));
+            }
+            out.push('\n');
+            subcircuit.body.write_body(out);
+            out.push_str(&format!(".ends {}\n", subcircuit.name));
+        }
+        for directive in &self.directives {
+            out.push_str(&format!("{}\n", directive));
+        }
+    }
+}
+
+impl Display for Netlist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_spice())
     }
 }
 
-pub struct Node {}
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Node {
+    pub name: String,
+}
+
+impl Node {
+    pub fn new(name: impl Into<String>) -> Self {
+        Node { name: name.into() }
+    }
 
-pub struct Component {}
+    pub fn is_ground(&self) -> bool {
+        self.name == "0"
+    }
+}
+
+/// One element line: the instance name, its nodes in SPICE order and the
+/// remainder of the line (value, model name and instance parameters).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub nodes: Vec<Node>,
+    pub value: String,
+}
+
+impl Component {
+    pub fn new(name: impl Into<String>, nodes: Vec<Node>, value: impl Into<String>) -> Self {
+        Component {
+            name: name.into(),
+            nodes,
+            value: value.into(),
+        }
+    }
+
+    /// The leading letter of the instance name, which selects the element type.
+    pub fn prefix(&self) -> char {
+        self.name
+            .chars()
+            .next()
+            .map(|c| c.to_ascii_uppercase())
+            .unwrap_or(' ')
+    }
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        for node in &self.nodes {
+            write!(f, " {}", node.name)?;
+        }
+        if !self.value.is_empty() {
+            write!(f, " {}", self.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subcircuit {
+    pub name: String,
+    pub ports: Vec<Node>,
+    pub body: Netlist,
+}
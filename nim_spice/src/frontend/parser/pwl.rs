@@ -0,0 +1,576 @@
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+use std::cell::{Cell, OnceCell};
+use std::path::{Path, PathBuf};
+
+/// A piece-wise linear waveform, `PWL(...)` on an independent source.
+///
+/// The points either come from the netlist or from a data file given with
+/// `file=`. Files are only read the first time the waveform is needed, so
+/// parsing a netlist that refers to large bench captures stays cheap.
+#[derive(Debug, Clone)]
+pub struct Pwl {
+    data: PwlData,
+    time_scale: f64,
+    value_scale: f64,
+    repeat: Repeat,
+    points: OnceCell<Vec<(f64, f64)>>,
+    /// Segment of the last lookup; transient analysis mostly moves forward
+    /// by small steps, so this usually saves the binary search.
+    cursor: Cell<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PwlData {
+    Inline(Vec<(f64, f64)>),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    Once,
+    Times(usize),
+    Forever,
+}
+
+impl Pwl {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        Pwl::with_data(PwlData::Inline(points))
+    }
+
+    pub fn from_file(path: impl Into<PathBuf>) -> Self {
+        Pwl::with_data(PwlData::File(path.into()))
+    }
+
+    fn with_data(data: PwlData) -> Self {
+        Pwl {
+            data,
+            time_scale: 1.0,
+            value_scale: 1.0,
+            repeat: Repeat::Once,
+            points: OnceCell::new(),
+            cursor: Cell::new(0),
+        }
+    }
+
+    /// Parse the arguments following `PWL`, e.g.
+    /// `TIME_SCALE_FACTOR=1m file="run 3.csv"` or
+    /// `REPEAT FOR 3 (0 0 1u 1 2u 0) ENDREPEAT`.
+    ///
+    /// Times prefixed with `+` are relative to the previous point. Relative
+    /// file names are resolved against `directory`.
+    pub fn parse(args: &str, directory: &Path) -> Result<Pwl, ErrorHandler> {
+        let mut error_handler = ErrorHandler::new();
+        let mut error = |message: String| {
+            error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+        };
+
+        let words = split_words(args);
+        let mut values: Vec<&str> = Vec::new();
+        let mut file: Option<PathBuf> = None;
+        let (mut time_scale, mut value_scale) = (1.0, 1.0);
+        let mut repeat = Repeat::Once;
+        let mut in_repeat = false;
+        let mut i = 0;
+        while i < words.len() {
+            let word = words[i].as_str();
+            let upper = word.to_ascii_uppercase();
+            if let Some((key, value)) = word.split_once('=') {
+                let number = parse_number(value);
+                match key.to_ascii_uppercase().as_str() {
+                    "FILE" => file = Some(directory.join(value)),
+                    "TIME_SCALE_FACTOR" => match number {
+                        Some(n) => time_scale = n,
+                        None => error(format!("Invalid time scale factor: {}", value)),
+                    },
+                    "VALUE_SCALE_FACTOR" => match number {
+                        Some(n) => value_scale = n,
+                        None => error(format!("Invalid value scale factor: {}", value)),
+                    },
+                    _ => error(format!("Unknown PWL option: {}", key)),
+                }
+            } else if upper == "REPEAT" {
+                if !values.is_empty() || file.is_some() {
+                    error("REPEAT must enclose the whole waveform".to_string());
+                }
+                match words.get(i + 1).map(|w| w.to_ascii_uppercase()).as_deref() {
+                    Some("FOREVER") => repeat = Repeat::Forever,
+                    Some("FOR") => {
+                        match words.get(i + 2).and_then(|w| w.parse::<usize>().ok()) {
+                            Some(n) => repeat = Repeat::Times(n),
+                            None => error("REPEAT FOR needs a count".to_string()),
+                        }
+                        i += 1;
+                    }
+                    _ => error("Expected FOREVER or FOR after REPEAT".to_string()),
+                }
+                in_repeat = true;
+                i += 1;
+            } else if upper == "ENDREPEAT" {
+                if !in_repeat {
+                    error("ENDREPEAT without REPEAT".to_string());
+                }
+                in_repeat = false;
+                if i + 1 < words.len() {
+                    error("REPEAT must enclose the whole waveform".to_string());
+                }
+            } else {
+                values.push(word);
+            }
+            i += 1;
+        }
+        if in_repeat {
+            error("REPEAT without ENDREPEAT".to_string());
+        }
+
+        let data = match file {
+            Some(path) if values.is_empty() => PwlData::File(path),
+            Some(_) => {
+                error("PWL takes either points or a file, not both".to_string());
+                PwlData::Inline(Vec::new())
+            }
+            None => {
+                if !values.len().is_multiple_of(2) {
+                    error("PWL points must come in time/value pairs".to_string());
+                }
+                let mut points = Vec::new();
+                let mut last = 0.0;
+                for pair in values.chunks_exact(2) {
+                    let (relative, time) = match pair[0].strip_prefix('+') {
+                        Some(t) => (true, t),
+                        None => (false, pair[0]),
+                    };
+                    match (parse_number(time), parse_number(pair[1])) {
+                        (Some(t), Some(v)) => {
+                            let t = if relative { last + t } else { t };
+                            if t < last && !points.is_empty() {
+                                error(format!("PWL time {} goes backwards", pair[0]));
+                            }
+                            last = t;
+                            points.push((t, v));
+                        }
+                        _ => error(format!("Invalid PWL point: {} {}", pair[0], pair[1])),
+                    }
+                }
+                PwlData::Inline(points)
+            }
+        };
+
+        if error_handler.has_errors() {
+            return Err(error_handler);
+        }
+        let mut pwl = Pwl::with_data(data);
+        pwl.time_scale = time_scale;
+        pwl.value_scale = value_scale;
+        pwl.repeat = repeat;
+        Ok(pwl)
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    /// The points of the waveform after scaling, reading the data file the
+    /// first time it is called.
+    pub fn load(&self) -> Result<&[(f64, f64)], ErrorHandler> {
+        if let Some(points) = self.points.get() {
+            return Ok(points);
+        }
+        let raw = match &self.data {
+            PwlData::Inline(points) => points.clone(),
+            PwlData::File(path) => read_points(path, true)?,
+        };
+        let scaled = raw
+            .into_iter()
+            .map(|(t, v)| (t * self.time_scale, v * self.value_scale))
+            .collect();
+        Ok(self.points.get_or_init(|| scaled))
+    }
+
+    /// Length of one repetition: the time of the last point.
+    fn period(&self, points: &[(f64, f64)]) -> f64 {
+        points.last().map(|p| p.0).unwrap_or(0.0)
+    }
+
+    /// Value at time `t`. Before the first point the first value holds, after
+    /// the last point (and after the last repetition) the last value holds.
+    ///
+    /// A file that cannot be read evaluates to zero; call [`Pwl::load`]
+    /// beforehand to report the problem.
+    pub fn value(&self, t: f64) -> f64 {
+        let Ok(points) = self.load() else {
+            return 0.0;
+        };
+        let Some(&(_, last_value)) = points.last() else {
+            return 0.0;
+        };
+        let period = self.period(points);
+        let t = match self.repeat {
+            Repeat::Forever if period > 0.0 && t > period => t.rem_euclid(period),
+            Repeat::Times(n) if period > 0.0 && t > period => {
+                if t >= period * n as f64 {
+                    return last_value;
+                }
+                t.rem_euclid(period)
+            }
+            _ => t,
+        };
+        self.interpolate(points, t)
+    }
+
+    fn interpolate(&self, points: &[(f64, f64)], t: f64) -> f64 {
+        if t <= points[0].0 {
+            return points[0].1;
+        }
+        let n = points.len();
+        if t >= points[n - 1].0 {
+            return points[n - 1].1;
+        }
+        // Find `i` with points[i - 1].0 <= t < points[i].0.
+        let hint = self.cursor.get();
+        let i = if hint > 0 && hint < n && points[hint - 1].0 <= t && t < points[hint].0 {
+            hint
+        } else if hint + 1 < n && points[hint].0 <= t && t < points[hint + 1].0 {
+            hint + 1
+        } else {
+            points.partition_point(|p| p.0 <= t)
+        };
+        self.cursor.set(i);
+        let ((t0, v0), (t1, v1)) = (points[i - 1], points[i]);
+        v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+    }
+
+    /// Times in `(from, to]` where the waveform has a corner, including
+    /// those of later repetitions. Transient analysis places a time point on
+    /// each.
+    pub fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        let Ok(points) = self.load() else {
+            return Vec::new();
+        };
+        let period = self.period(points);
+        let repeats = match self.repeat {
+            Repeat::Once => 1,
+            Repeat::Times(n) => n.max(1),
+            Repeat::Forever if period > 0.0 => (to / period).ceil().max(1.0) as usize,
+            Repeat::Forever => 1,
+        };
+        // The first repetition, ending at `(k + 1) * period`, that can
+        // have points after `from`.
+        let first = if period > 0.0 {
+            (from / period - 1.0).floor().clamp(0.0, repeats as f64) as usize
+        } else {
+            0
+        };
+        let mut times = Vec::new();
+        for k in first..repeats {
+            let offset = k as f64 * period;
+            if offset > to {
+                break;
+            }
+            let start = points.partition_point(|p| p.0 + offset <= from);
+            for &(t, _) in &points[start..] {
+                let t = t + offset;
+                if t > to {
+                    break;
+                }
+                if times.last() != Some(&t) {
+                    times.push(t);
+                }
+            }
+        }
+        times
+    }
+}
+
+impl PartialEq for Pwl {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+            && self.time_scale == other.time_scale
+            && self.value_scale == other.value_scale
+            && self.repeat == other.repeat
+    }
+}
+
+/// A lookup table as used by `table()` in behavioural expressions. The
+/// output is interpolated linearly and held constant outside the range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    points: Vec<(f64, f64)>,
+}
+
+impl Table {
+    /// Build a table from `x1, y1, x2, y2, ...`, in any order of x.
+    pub fn new(pairs: &[f64]) -> Result<Table, String> {
+        if pairs.len() < 2 || !pairs.len().is_multiple_of(2) {
+            return Err("table() needs x/y pairs".to_string());
+        }
+        let points = pairs.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+        Ok(Table::sorted(points))
+    }
+
+    /// Read a table from a CSV or whitespace separated file of x/y pairs.
+    pub fn load(path: &Path) -> Result<Table, ErrorHandler> {
+        Ok(Table::sorted(read_points(path, false)?))
+    }
+
+    fn sorted(mut points: Vec<(f64, f64)>) -> Table {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Table { points }
+    }
+
+    pub fn value(&self, x: f64) -> f64 {
+        let points = &self.points;
+        let n = points.len();
+        if n == 0 {
+            return 0.0;
+        }
+        if x <= points[0].0 {
+            return points[0].1;
+        }
+        if x >= points[n - 1].0 {
+            return points[n - 1].1;
+        }
+        let i = points.partition_point(|p| p.0 <= x);
+        let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+
+    /// Slope of the segment containing `x`, zero outside the table.
+    pub fn slope(&self, x: f64) -> f64 {
+        let points = &self.points;
+        let n = points.len();
+        if n < 2 || x < points[0].0 || x >= points[n - 1].0 {
+            return 0.0;
+        }
+        let i = points.partition_point(|p| p.0 <= x);
+        let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+        if x1 > x0 {
+            (y1 - y0) / (x1 - x0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Read pairs of numbers from a data file.
+pub(crate) fn read_points(path: &Path, monotonic: bool) -> Result<Vec<(f64, f64)>, ErrorHandler> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => parse_points(&content, &path.display().to_string(), monotonic),
+        Err(e) => {
+            let mut error_handler = ErrorHandler::new();
+            error_handler.add_error(Error::new(
+                ErrorType::IOError,
+                format!("{}: {}", path.display(), e),
+                0,
+                0,
+            ));
+            Err(error_handler)
+        }
+    }
+}
+
+/// Parse data in CSV (`,` or `;` separated) or whitespace separated form.
+///
+/// The first two columns are used and any further columns are ignored.
+/// Lines starting with `#`, `*` or `;` are comments, and non-numeric lines
+/// before the first data line are taken as headers. Errors carry the line
+/// and column of the offending field. With `monotonic`, time must not go
+/// backwards.
+pub(crate) fn parse_points(
+    content: &str,
+    source: &str,
+    monotonic: bool,
+) -> Result<Vec<(f64, f64)>, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line_no = number + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', '*', ';']) {
+            continue;
+        }
+
+        let mut fields = fields(line);
+        let first = fields.next();
+        let second = fields.next();
+        let parsed = (
+            first.and_then(|(_, f)| parse_number(f)),
+            second.and_then(|(_, f)| parse_number(f)),
+        );
+        match parsed {
+            (Some(x), Some(y)) => {
+                if monotonic && points.last().is_some_and(|p| x < p.0) {
+                    error_handler.add_error(Error::new(
+                        ErrorType::Syntax,
+                        format!("{}: time {} is before the previous point", source, x),
+                        line_no,
+                        first.map(|(c, _)| c + 1).unwrap_or(1),
+                    ));
+                }
+                points.push((x, y));
+            }
+            // Column titles before the data starts.
+            _ if points.is_empty() && first.is_some_and(|(_, f)| parse_number(f).is_none()) => {}
+            (x, _) => {
+                let (column, field) = match (x, first, second) {
+                    (None, Some(f), _) => f,
+                    (_, _, Some(f)) => f,
+                    _ => (line.len(), ""),
+                };
+                let message = if field.is_empty() {
+                    format!("{}: expected two columns", source)
+                } else {
+                    format!("{}: invalid number '{}'", source, field)
+                };
+                error_handler.add_error(Error::new(
+                    ErrorType::Syntax,
+                    message,
+                    line_no,
+                    column + 1,
+                ));
+            }
+        }
+    }
+
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(points)
+    }
+}
+
+/// Non-empty fields of a data line with their byte offsets.
+fn fields(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|field| !field.is_empty())
+        .map(move |field| (field.as_ptr() as usize - line.as_ptr() as usize, field))
+}
+
+/// Split PWL arguments into words, treating parentheses and commas as
+/// separators, keeping quoted strings whole and joining `key = value`.
+fn split_words(args: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if quoted => current.push(c),
+            c if c.is_whitespace() || c == ',' || c == '(' || c == ')' => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    let mut joined: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = &words[i];
+        if word == "=" && !joined.is_empty() && i + 1 < words.len() {
+            let key = joined.pop().unwrap();
+            joined.push(format!("{}={}", key, words[i + 1]));
+            i += 2;
+        } else if i + 1 < words.len()
+            && (word.ends_with('=') || words[i + 1].starts_with('=') && words[i + 1].len() > 1)
+        {
+            joined.push(format!("{}{}", word, words[i + 1]));
+            i += 2;
+        } else {
+            joined.push(word.clone());
+            i += 1;
+        }
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_inline() {
+        let pwl = Pwl::parse("(0 0 1m 1 +1m 1 3m 0)", Path::new(".")).unwrap();
+        assert_eq!(pwl.value(-1.0), 0.0);
+        assert!((pwl.value(0.5e-3) - 0.5).abs() < 1e-12);
+        assert_eq!(pwl.value(1.5e-3), 1.0);
+        assert!((pwl.value(2.5e-3) - 0.5).abs() < 1e-12);
+        assert_eq!(pwl.value(10.0), 0.0);
+        // Going backwards in time still finds the right segment.
+        assert!((pwl.value(0.25e-3) - 0.25).abs() < 1e-12);
+        assert!(Pwl::parse("0 0 1m", Path::new(".")).is_err());
+        assert!(Pwl::parse("0 0 2m 1 1m 0", Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_repeat() {
+        let pwl = Pwl::parse("REPEAT FOR 2 (0 0 1 1 2 0) ENDREPEAT", Path::new(".")).unwrap();
+        assert_eq!(pwl.repeat(), Repeat::Times(2));
+        assert!((pwl.value(2.5) - 0.5).abs() < 1e-12);
+        assert_eq!(pwl.value(4.5), 0.0);
+        assert_eq!(pwl.breakpoints(0.5, 10.0), [1.0, 2.0, 3.0, 4.0]);
+
+        let forever = Pwl::parse("REPEAT FOREVER (0 0 1 1) ENDREPEAT", Path::new(".")).unwrap();
+        assert!((forever.value(7.25) - 0.25).abs() < 1e-12);
+        assert_eq!(
+            forever.breakpoints(1e6 + 0.5, 1e6 + 2.0),
+            [1e6 + 1.0, 1e6 + 2.0]
+        );
+        assert!(Pwl::parse("0 0 REPEAT FOREVER (1 1) ENDREPEAT", Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_file() {
+        let dir = std::env::temp_dir().join("nimbus_pwl");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("capture.csv"),
+            "Time,CH1,CH2\n# scope export\n0,0,9\n1e-3,2,9\n2e-3,4,9\n",
+        )
+        .unwrap();
+        fs::write(dir.join("spaces.txt"), "0 1\n\t1u   2\n").unwrap();
+        fs::write(dir.join("bad.csv"), "t,v\n0,0\n1m,oops\n").unwrap();
+
+        let pwl = Pwl::parse(
+            "TIME_SCALE_FACTOR = 2 VALUE_SCALE_FACTOR=0.5 file=capture.csv",
+            &dir,
+        )
+        .unwrap();
+        // Nothing is read until the waveform is needed.
+        assert!(pwl.points.get().is_none());
+        assert_eq!(pwl.load().unwrap(), [(0.0, 0.0), (2e-3, 1.0), (4e-3, 2.0)]);
+        assert!((pwl.value(3e-3) - 1.5).abs() < 1e-12);
+
+        let spaces = Pwl::parse("file=\"spaces.txt\"", &dir).unwrap();
+        assert_eq!(spaces.load().unwrap(), [(0.0, 1.0), (1e-6, 2.0)]);
+
+        let errors = Pwl::parse("file=bad.csv", &dir)
+            .unwrap()
+            .load()
+            .unwrap_err();
+        let error = &errors.errors[0];
+        assert_eq!((error.line, error.column), (3, 4));
+        assert!(error.message.contains("oops"));
+
+        let table = Table::load(&dir.join("spaces.txt")).unwrap();
+        assert!((table.value(0.5e-6) - 1.5).abs() < 1e-12);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_table() {
+        let table = Table::new(&[1.0, 10.0, 0.0, 0.0, 2.0, 10.0]).unwrap();
+        assert_eq!(table.value(-5.0), 0.0);
+        assert_eq!(table.value(0.5), 5.0);
+        assert_eq!(table.value(1.5), 10.0);
+        assert_eq!(table.slope(0.5), 10.0);
+        assert_eq!(table.slope(3.0), 0.0);
+        assert!(Table::new(&[1.0]).is_err());
+    }
+}
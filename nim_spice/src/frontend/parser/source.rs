@@ -0,0 +1,418 @@
+use crate::frontend::parser::pwl::Pwl;
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// The value part of an independent V or I source line, e.g.
+/// `DC 1 AC 1 0 SINE(0 1 1k)` or `PWL file=capture.csv`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSpec {
+    pub dc: f64,
+    /// Whether the line gave a DC value, which the operating point and DC
+    /// sweeps then use in place of the waveform's value at time zero.
+    pub dc_given: bool,
+    /// AC magnitude and phase in degrees.
+    pub ac: Option<(f64, f64)>,
+    pub waveform: Option<Waveform>,
+}
+
+/// Time dependent source functions. Arguments that are not given take the
+/// SPICE defaults, with rise and fall times of zero meaning an ideal edge.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waveform {
+    Pulse {
+        v1: f64,
+        v2: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: f64,
+        cycles: Option<usize>,
+    },
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        frequency: f64,
+        delay: f64,
+        damping: f64,
+        phase: f64,
+        cycles: Option<usize>,
+    },
+    Exp {
+        v1: f64,
+        v2: f64,
+        delay1: f64,
+        tau1: f64,
+        delay2: f64,
+        tau2: f64,
+    },
+    Pwl(Pwl),
+}
+
+impl Waveform {
+    /// Value at time `t`.
+    pub fn value(&self, t: f64) -> f64 {
+        match self {
+            Waveform::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+                cycles,
+            } => {
+                if t < *delay {
+                    return *v1;
+                }
+                let mut local = t - delay;
+                if *period > 0.0 {
+                    let cycle = (local / period).floor();
+                    if cycles.is_none_or(|n| cycle < n as f64) {
+                        local -= cycle * period;
+                    } else {
+                        return *v1;
+                    }
+                }
+                if local < *rise {
+                    v1 + (v2 - v1) * local / rise
+                } else if local < rise + width {
+                    *v2
+                } else if local < rise + width + fall {
+                    v2 + (v1 - v2) * (local - rise - width) / fall
+                } else {
+                    *v1
+                }
+            }
+            Waveform::Sine {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+                phase,
+                cycles,
+            } => {
+                let phase = phase.to_radians();
+                let local = t - delay;
+                let finished =
+                    cycles.is_some_and(|n| *frequency > 0.0 && local * frequency >= n as f64);
+                if local <= 0.0 || finished {
+                    offset + amplitude * phase.sin()
+                } else {
+                    offset
+                        + amplitude
+                            * (-local * damping).exp()
+                            * (2.0 * PI * frequency * local + phase).sin()
+                }
+            }
+            Waveform::Exp {
+                v1,
+                v2,
+                delay1,
+                tau1,
+                delay2,
+                tau2,
+            } => {
+                let mut v = *v1;
+                if t > *delay1 {
+                    v += (v2 - v1) * (1.0 - (-(t - delay1) / tau1).exp());
+                }
+                if t > *delay2 {
+                    v += (v1 - v2) * (1.0 - (-(t - delay2) / tau2).exp());
+                }
+                v
+            }
+            Waveform::Pwl(pwl) => pwl.value(t),
+        }
+    }
+
+    /// Times in `(from, to]` where the waveform has a corner, so that
+    /// transient analysis can place a time point on it.
+    pub fn breakpoints(&self, from: f64, to: f64) -> Vec<f64> {
+        match self {
+            Waveform::Pulse {
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+                cycles,
+                ..
+            } => {
+                let corners = [0.0, *rise, rise + width, rise + width + fall];
+                let mut times = Vec::new();
+                // The first cycle whose last corner can lie after `from`.
+                let mut cycle = if *period > 0.0 {
+                    ((from - delay - corners[3]) / period).floor().max(0.0) as usize
+                } else {
+                    0
+                };
+                loop {
+                    let start = delay + cycle as f64 * period;
+                    if start > to || cycles.is_some_and(|n| cycle >= n) {
+                        break;
+                    }
+                    times.extend(
+                        corners
+                            .iter()
+                            .map(|c| start + c)
+                            .filter(|t| *t > from && *t <= to),
+                    );
+                    if *period <= 0.0 {
+                        break;
+                    }
+                    cycle += 1;
+                }
+                times.dedup();
+                times
+            }
+            Waveform::Sine { delay, .. } => [*delay]
+                .into_iter()
+                .filter(|t| *t > from && *t <= to)
+                .collect(),
+            Waveform::Exp { delay1, delay2, .. } => [*delay1, *delay2]
+                .into_iter()
+                .filter(|t| *t > from && *t <= to)
+                .collect(),
+            Waveform::Pwl(pwl) => pwl.breakpoints(from, to),
+        }
+    }
+}
+
+/// Parse the value of an independent source. Relative `file=` paths in
+/// a PWL are resolved against `directory`, normally the netlist's folder.
+pub fn parse_source(text: &str, directory: &Path) -> Result<SourceSpec, ErrorHandler> {
+    let mut error_handler = ErrorHandler::new();
+    let mut spec = SourceSpec {
+        dc: 0.0,
+        dc_given: false,
+        ac: None,
+        waveform: None,
+    };
+    let mut error = |message: String| {
+        error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+    };
+
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let (word, tail) = split_word(rest);
+        let upper = word.to_ascii_uppercase();
+        rest = tail;
+        match upper.as_str() {
+            "DC" => {
+                let (value, tail) = split_word(rest);
+                match parse_number(value) {
+                    Some(v) => (spec.dc, spec.dc_given) = (v, true),
+                    None => error(format!("Invalid DC value: {}", value)),
+                }
+                rest = tail;
+            }
+            "AC" => {
+                let (magnitude, tail) = split_word(rest);
+                let Some(magnitude) = parse_number(magnitude) else {
+                    error(format!("Invalid AC magnitude: {}", magnitude));
+                    rest = tail;
+                    continue;
+                };
+                rest = tail;
+                let (phase, tail) = split_word(rest);
+                let phase = match parse_number(phase) {
+                    Some(p) => {
+                        rest = tail;
+                        p
+                    }
+                    None => 0.0,
+                };
+                spec.ac = Some((magnitude, phase));
+            }
+            "PULSE" | "SINE" | "SIN" | "EXP" | "PWL" => {
+                let (args, tail) = arguments(rest);
+                rest = tail;
+                let waveform = if upper == "PWL" {
+                    match Pwl::parse(args, directory) {
+                        Ok(pwl) => Waveform::Pwl(pwl),
+                        Err(errors) => {
+                            for e in errors.errors {
+                                error(e.message);
+                            }
+                            continue;
+                        }
+                    }
+                } else {
+                    let values: Option<Vec<f64>> = args
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .filter(|s| !s.is_empty())
+                        .map(parse_number)
+                        .collect();
+                    let Some(values) = values else {
+                        error(format!("Invalid {} arguments: {}", upper, args));
+                        continue;
+                    };
+                    match waveform(&upper, &values) {
+                        Ok(w) => w,
+                        Err(message) => {
+                            error(message);
+                            continue;
+                        }
+                    }
+                };
+                if spec.waveform.is_some() {
+                    error("A source can only have one waveform".to_string());
+                }
+                spec.waveform = Some(waveform);
+            }
+            _ => match parse_number(word) {
+                // A bare value is the DC value.
+                Some(v) => (spec.dc, spec.dc_given) = (v, true),
+                None => error(format!("Unexpected source parameter: {}", word)),
+            },
+        }
+    }
+
+    if error_handler.has_errors() {
+        Err(error_handler)
+    } else {
+        Ok(spec)
+    }
+}
+
+fn waveform(kind: &str, v: &[f64]) -> Result<Waveform, String> {
+    let arg = |i: usize, default: f64| v.get(i).copied().unwrap_or(default);
+    let (required, maximum) = match kind {
+        "PULSE" => (2, 8),
+        "EXP" => (2, 6),
+        _ => (3, 7),
+    };
+    if v.len() < required || v.len() > maximum {
+        return Err(format!(
+            "{} takes {} to {} arguments, found {}",
+            kind,
+            required,
+            maximum,
+            v.len()
+        ));
+    }
+    let cycles = |i: usize| v.get(i).map(|n| *n as usize);
+    Ok(match kind {
+        "PULSE" => Waveform::Pulse {
+            v1: v[0],
+            v2: v[1],
+            delay: arg(2, 0.0),
+            rise: arg(3, 0.0),
+            fall: arg(4, 0.0),
+            width: arg(5, f64::INFINITY),
+            period: arg(6, 0.0),
+            cycles: cycles(7),
+        },
+        "EXP" => {
+            let delay1 = arg(2, 0.0);
+            let tau1 = arg(3, 1.0);
+            Waveform::Exp {
+                v1: v[0],
+                v2: v[1],
+                delay1,
+                tau1,
+                delay2: arg(4, delay1 + tau1),
+                tau2: arg(5, tau1),
+            }
+        }
+        _ => Waveform::Sine {
+            offset: v[0],
+            amplitude: v[1],
+            frequency: v[2],
+            delay: arg(3, 0.0),
+            damping: arg(4, 0.0),
+            phase: arg(5, 0.0),
+            cycles: cycles(6),
+        },
+    })
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+/// The function arguments at the start of `text`: either a parenthesised
+/// group or, as SPICE also allows, everything up to the next keyword.
+fn arguments(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    if let Some(inner) = text.strip_prefix('(') {
+        let mut depth = 1;
+        let mut quoted = false;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '(' if !quoted => depth += 1,
+                ')' if !quoted => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return (&inner[..i], inner[i + 1..].trim_start());
+                    }
+                }
+                _ => {}
+            }
+        }
+        return (inner, "");
+    }
+    let end = ["AC ", "DC "]
+        .iter()
+        .filter_map(|k| text.to_ascii_uppercase().find(k))
+        .min()
+        .unwrap_or(text.len());
+    (&text[..end], &text[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        let spec = parse_source("DC 1.5 AC 1 90 SINE(0 1 1k)", Path::new(".")).unwrap();
+        assert_eq!((spec.dc, spec.dc_given), (1.5, true));
+        assert_eq!(spec.ac, Some((1.0, 90.0)));
+        let sine = spec.waveform.unwrap();
+        assert!((sine.value(0.25e-3) - 1.0).abs() < 1e-12);
+
+        let spec = parse_source("5", Path::new(".")).unwrap();
+        assert_eq!((spec.dc, spec.waveform), (5.0, None));
+
+        let spec = parse_source("PWL(0 0 1m 2) AC 1", Path::new(".")).unwrap();
+        assert!(!spec.dc_given);
+        assert_eq!(spec.ac, Some((1.0, 0.0)));
+        assert_eq!(spec.waveform.unwrap().value(0.5e-3), 1.0);
+
+        assert!(parse_source("PULSE(0)", Path::new(".")).is_err());
+        assert!(parse_source("SINE(0 1 abc)", Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_pulse() {
+        let pulse = waveform("PULSE", &[0.0, 1.0, 1.0, 1.0, 1.0, 2.0, 10.0, 2.0]).unwrap();
+        assert_eq!(pulse.value(0.5), 0.0);
+        assert_eq!(pulse.value(1.5), 0.5);
+        assert_eq!(pulse.value(3.0), 1.0);
+        assert_eq!(pulse.value(4.5), 0.5);
+        assert_eq!(pulse.value(13.0), 1.0);
+        assert_eq!(pulse.value(23.0), 0.0);
+        assert_eq!(
+            pulse.breakpoints(0.0, 12.5),
+            [1.0, 2.0, 4.0, 5.0, 11.0, 12.0]
+        );
+        assert_eq!(pulse.breakpoints(11.5, 30.0), [12.0, 14.0, 15.0]);
+        // Far into an endless train only the cycles in the window count.
+        let endless = waveform("PULSE", &[0.0, 1.0, 0.0, 1.0, 1.0, 2.0, 10.0]).unwrap();
+        assert_eq!(
+            endless.breakpoints(1e6 + 0.5, 1e6 + 10.0),
+            [1e6 + 1.0, 1e6 + 3.0, 1e6 + 4.0, 1e6 + 10.0]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+use std::fmt::Display;
+
+/// The most values one `.step` may sweep, so that a tiny increment is an
+/// error rather than an allocation without bound.
+pub const MAX_POINTS: usize = 100_000;
+
+/// A `.step` directive: what is swept and over which values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub target: StepTarget,
+    pub sweep: Sweep,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepTarget {
+    /// `.step param X ...`, a user parameter defined with `.param`.
+    Param(String),
+    /// `.step temp ...`, the circuit temperature.
+    Temp,
+    /// `.step V1 ...`, the value of an element, the DC value for sources.
+    Component(String),
+    /// `.step NPN 2N2222(VAF) ...`, one parameter of a `.model`.
+    Model {
+        kind: String,
+        model: String,
+        parameter: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sweep {
+    Linear {
+        start: f64,
+        stop: f64,
+        increment: f64,
+    },
+    /// Points per octave.
+    Octave {
+        start: f64,
+        stop: f64,
+        points: f64,
+    },
+    /// Points per decade.
+    Decade {
+        start: f64,
+        stop: f64,
+        points: f64,
+    },
+    List(Vec<f64>),
+}
+
+impl Sweep {
+    /// Every value of the sweep in order, including `stop` when the
+    /// increment lands on it.
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            Sweep::Linear {
+                start,
+                stop,
+                increment,
+            } => {
+                let count = ((stop - start) / increment + 1e-9).floor() as usize;
+                (0..=count.min(MAX_POINTS - 1))
+                    .map(|i| start + i as f64 * increment)
+                    .collect()
+            }
+            Sweep::Octave {
+                start,
+                stop,
+                points,
+            } => logarithmic(*start, *stop, 2.0, *points),
+            Sweep::Decade {
+                start,
+                stop,
+                points,
+            } => logarithmic(*start, *stop, 10.0, *points),
+            Sweep::List(values) => values.clone(),
+        }
+    }
+
+    /// How many values the sweep has, worked out without listing them.
+    fn count(&self) -> f64 {
+        match self {
+            Sweep::Linear {
+                start,
+                stop,
+                increment,
+            } => ((stop - start) / increment + 1e-9).floor() + 1.0,
+            Sweep::Octave {
+                start,
+                stop,
+                points,
+            } => ((stop / start).log2() * points + 1e-9).floor() + 1.0,
+            Sweep::Decade {
+                start,
+                stop,
+                points,
+            } => ((stop / start).log10() * points + 1e-9).floor() + 1.0,
+            Sweep::List(values) => values.len() as f64,
+        }
+    }
+}
+
+fn logarithmic(start: f64, stop: f64, base: f64, points: f64) -> Vec<f64> {
+    let mut values = Vec::new();
+    let mut k = 0;
+    loop {
+        let value = start * base.powf(k as f64 / points);
+        if value > stop * (1.0 + 1e-9) {
+            break values;
+        }
+        values.push(value);
+        k += 1;
+    }
+}
+
+impl Display for StepTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepTarget::Param(name) | StepTarget::Component(name) => write!(f, "{}", name),
+            StepTarget::Temp => write!(f, "temp"),
+            StepTarget::Model {
+                model, parameter, ..
+            } => write!(f, "{}({})", model, parameter),
+        }
+    }
+}
+
+/// Parse a `.step` line, with or without the leading `.step`:
+///
+/// ```text
+/// .step param R 1k 10k 1k
+/// .step dec param F 10 100k 5
+/// .step oct V1 1 16 2
+/// .step temp list -40 25 85
+/// .step NPN 2N2222(VAF) 50 150 25
+/// ```
+pub fn parse_step(text: &str) -> Result<Step, ErrorHandler> {
+    let fail = |message: String| {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+        error_handler
+    };
+
+    let words = split_words(text);
+    let keyword = |i: usize| words.get(i).map(|w| w.to_ascii_uppercase());
+    let mut i = 0;
+    if keyword(0).as_deref() == Some(".STEP") {
+        i += 1;
+    }
+    let scale = match keyword(i).as_deref() {
+        Some(kind @ ("LIN" | "OCT" | "DEC")) => {
+            i += 1;
+            Some(kind.to_string())
+        }
+        _ => None,
+    };
+
+    let target = match keyword(i).as_deref() {
+        None => return Err(fail("Missing .step target".to_string())),
+        Some("PARAM") => {
+            let Some(name) = words.get(i + 1) else {
+                return Err(fail("Missing parameter name after PARAM".to_string()));
+            };
+            i += 2;
+            StepTarget::Param(name.clone())
+        }
+        Some("TEMP") => {
+            i += 1;
+            StepTarget::Temp
+        }
+        Some(_) => match words.get(i + 1).and_then(|w| model_parameter(w)) {
+            Some((model, parameter)) => {
+                i += 2;
+                StepTarget::Model {
+                    kind: words[i - 2].to_ascii_uppercase(),
+                    model: model.to_string(),
+                    parameter: parameter.to_string(),
+                }
+            }
+            None => {
+                i += 1;
+                StepTarget::Component(words[i - 1].clone())
+            }
+        },
+    };
+
+    let is_list = keyword(i).as_deref() == Some("LIST");
+    if is_list {
+        i += 1;
+    }
+    let mut values = Vec::new();
+    for word in &words[i..] {
+        match parse_number(word) {
+            Some(value) => values.push(value),
+            None => return Err(fail(format!("Invalid .step value: {}", word))),
+        }
+    }
+
+    let sweep = if is_list {
+        if scale.is_some() {
+            return Err(fail(
+                "LIST cannot be combined with LIN, OCT or DEC".to_string(),
+            ));
+        }
+        if values.is_empty() {
+            return Err(fail("LIST needs at least one value".to_string()));
+        }
+        Sweep::List(values)
+    } else {
+        let [start, stop, third] = values[..] else {
+            return Err(fail(format!(
+                "Expected start, stop and increment, found {} values",
+                values.len()
+            )));
+        };
+        match scale.as_deref() {
+            Some(kind @ ("OCT" | "DEC")) => {
+                if start <= 0.0 || stop < start || third <= 0.0 {
+                    return Err(fail(format!(
+                        "{} sweeps need 0 < start <= stop and a positive point count",
+                        kind
+                    )));
+                }
+                if kind == "OCT" {
+                    Sweep::Octave {
+                        start,
+                        stop,
+                        points: third,
+                    }
+                } else {
+                    Sweep::Decade {
+                        start,
+                        stop,
+                        points: third,
+                    }
+                }
+            }
+            _ => {
+                if third == 0.0 || (stop - start) / third < 0.0 {
+                    return Err(fail(format!(
+                        "Increment {} never reaches {} from {}",
+                        third, stop, start
+                    )));
+                }
+                Sweep::Linear {
+                    start,
+                    stop,
+                    increment: third,
+                }
+            }
+        }
+    };
+    if sweep.count() > MAX_POINTS as f64 {
+        return Err(fail(format!(
+            "The sweep has more than {} points",
+            MAX_POINTS
+        )));
+    }
+    Ok(Step { target, sweep })
+}
+
+/// Split `MODEL(PARAM)` into its two names.
+fn model_parameter(word: &str) -> Option<(&str, &str)> {
+    let (model, rest) = word.split_once('(')?;
+    let parameter = rest.strip_suffix(')')?.trim();
+    if model.is_empty() || parameter.is_empty() {
+        return None;
+    }
+    Some((model, parameter))
+}
+
+/// Split on whitespace and commas outside parentheses, so that
+/// `2N2222( VAF )` stays one word.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                current.push(c);
+            }
+            c if depth == 0 && (c.is_whitespace() || c == ',') => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        let step = parse_step(".step param R 1k 10k 1k").unwrap();
+        assert_eq!(step.target, StepTarget::Param("R".to_string()));
+        assert_eq!(step.sweep.values().len(), 10);
+        assert_eq!(step.sweep.values()[9], 10e3);
+
+        let step = parse_step(".step dec param F 10 1k 2").unwrap();
+        let values = step.sweep.values();
+        assert_eq!(values.len(), 5);
+        assert!((values[1] - 10.0 * 10f64.sqrt()).abs() < 1e-9);
+
+        let step = parse_step(".step oct V1 1 8 1").unwrap();
+        assert_eq!(step.target, StepTarget::Component("V1".to_string()));
+        assert_eq!(step.sweep.values(), [1.0, 2.0, 4.0, 8.0]);
+
+        let step = parse_step(".step temp list -40, 25, 85").unwrap();
+        assert_eq!(step.target, StepTarget::Temp);
+        assert_eq!(step.sweep, Sweep::List(vec![-40.0, 25.0, 85.0]));
+
+        let step = parse_step(".step NPN 2N2222( VAF ) 100 50 -25").unwrap();
+        assert_eq!(
+            step.target,
+            StepTarget::Model {
+                kind: "NPN".to_string(),
+                model: "2N2222".to_string(),
+                parameter: "VAF".to_string(),
+            }
+        );
+        assert_eq!(step.target.to_string(), "2N2222(VAF)");
+        assert_eq!(step.sweep.values(), [100.0, 75.0, 50.0]);
+    }
+
+    #[test]
+    fn test_parse_step_errors() {
+        assert!(parse_step(".step").is_err());
+        assert!(parse_step(".step param R 1 10").is_err());
+        assert!(parse_step(".step param R 1 10 -1").is_err());
+        assert!(parse_step(".step dec param R 0 10 5").is_err());
+        assert!(parse_step(".step dec param R list 1 2").is_err());
+        assert!(parse_step(".step param R 1 x 1").is_err());
+        let errors = parse_step(".step param R 0 1 1e-9").err().unwrap();
+        assert_eq!(
+            errors.errors[0].message,
+            "The sweep has more than 100000 points"
+        );
+        assert!(parse_step(".step dec param R 1 1e9 1e5").is_err());
+        assert!(parse_step(".step param R 0 1 2e-5").is_ok());
+    }
+}
@@ -14,6 +14,8 @@ pub enum TokenType {
     Dc,
     Ac,
     Op,
+    Step,
+    Meas,
 
     Subckt,
 
@@ -0,0 +1,141 @@
+use crate::utils::error::{Error, ErrorHandler, ErrorType};
+use crate::utils::number::parse_number;
+
+/// A `.tran` directive.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tran {
+    /// Interval of the printed output, zero when only the stop time is
+    /// given.
+    pub print_step: f64,
+    pub stop: f64,
+    /// Time from which results are kept; the simulation still starts at
+    /// zero.
+    pub start: f64,
+    /// Largest internal time step, if limited.
+    pub max_step: Option<f64>,
+    /// Skip the operating point and start from the initial conditions.
+    pub uic: bool,
+    /// Solve the operating point with the independent sources off and
+    /// ramp them up at the start of the simulation.
+    pub startup: bool,
+    /// Stop once the circuit has reached a steady state.
+    pub steady: bool,
+    /// Keep the results before `start` as well.
+    pub nodiscard: bool,
+}
+
+/// Parse a `.tran` line, with or without the leading `.tran`, in either
+/// LTspice form:
+///
+/// ```text
+/// .tran 10m
+/// .tran 1u 10m
+/// .tran 0 10m 1m 10n uic
+/// .tran 5m startup
+/// ```
+pub fn parse_tran(text: &str) -> Result<Tran, ErrorHandler> {
+    let fail = |message: String| {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.add_error(Error::new(ErrorType::Syntax, message, 0, 0));
+        error_handler
+    };
+
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    if words
+        .first()
+        .is_some_and(|w| w.eq_ignore_ascii_case(".tran"))
+    {
+        words.remove(0);
+    }
+
+    let mut tran = Tran::default();
+    let mut values = Vec::new();
+    for word in words {
+        match word.to_ascii_lowercase().as_str() {
+            "uic" => tran.uic = true,
+            "startup" => tran.startup = true,
+            "steady" => tran.steady = true,
+            "nodiscard" => tran.nodiscard = true,
+            _ => match parse_number(word) {
+                Some(value) if tran.uic || tran.startup || tran.steady || tran.nodiscard => {
+                    return Err(fail(format!(
+                        "Time value {} after the .tran options",
+                        value
+                    )));
+                }
+                Some(value) => values.push(value),
+                None => return Err(fail(format!("Invalid .tran argument: {}", word))),
+            },
+        }
+    }
+
+    match values[..] {
+        [stop] => tran.stop = stop,
+        [print_step, stop, ref rest @ ..] if rest.len() <= 2 => {
+            tran.print_step = print_step;
+            tran.stop = stop;
+            tran.start = rest.first().copied().unwrap_or(0.0);
+            tran.max_step = rest.get(1).copied().filter(|max| *max > 0.0);
+        }
+        [] => return Err(fail("Missing .tran stop time".to_string())),
+        _ => {
+            return Err(fail(format!(
+                "Too many .tran values: {}, at most 4",
+                values.len()
+            )))
+        }
+    }
+    if tran.stop <= 0.0 {
+        return Err(fail(format!(
+            "The .tran stop time must be positive, found {}",
+            tran.stop
+        )));
+    }
+    if tran.print_step < 0.0 || tran.start < 0.0 || tran.max_step.is_some_and(|max| max < 0.0) {
+        return Err(fail("The .tran times cannot be negative".to_string()));
+    }
+    if tran.start >= tran.stop {
+        return Err(fail(format!(
+            "The .tran start time {} is not before the stop time {}",
+            tran.start, tran.stop
+        )));
+    }
+    Ok(tran)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tran() {
+        let tran = parse_tran(".tran 10m").unwrap();
+        assert_eq!(tran.stop, 10e-3);
+        assert_eq!(tran.print_step, 0.0);
+        assert_eq!(tran.max_step, None);
+
+        let tran = parse_tran(".TRAN 1u 10m").unwrap();
+        assert_eq!(tran.print_step, 1e-6);
+        assert_eq!(tran.stop, 10e-3);
+
+        let tran = parse_tran(".tran 0 10m 1m 10n UIC").unwrap();
+        assert_eq!(tran.start, 1e-3);
+        assert_eq!(tran.max_step, Some(10e-9));
+        assert!(tran.uic);
+        assert!(!tran.startup);
+
+        let tran = parse_tran("5m startup nodiscard").unwrap();
+        assert_eq!(tran.stop, 5e-3);
+        assert!(tran.startup && tran.nodiscard);
+    }
+
+    #[test]
+    fn test_parse_tran_errors() {
+        assert!(parse_tran(".tran").is_err());
+        assert!(parse_tran(".tran 0").is_err());
+        assert!(parse_tran(".tran 1u 10m 20m").is_err());
+        assert!(parse_tran(".tran 1u 10m 0 1n 5").is_err());
+        assert!(parse_tran(".tran 10m uic 1u").is_err());
+        assert!(parse_tran(".tran 10m fast").is_err());
+    }
+}
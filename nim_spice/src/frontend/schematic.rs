@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// A schematic sheet in the form the `nim_ui` schematic editor works with:
+/// wires, net labels, placed symbols and free text, plus the symbol
+/// definitions and child sheets the placed symbols refer to.
+///
+/// Coordinates follow the LTspice convention: integer grid units with the
+/// y axis pointing down.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schematic {
+    pub name: String,
+    pub size: (i32, i32),
+    pub wires: Vec<Wire>,
+    pub flags: Vec<Flag>,
+    pub symbols: Vec<SymbolInstance>,
+    pub texts: Vec<Text>,
+    /// Symbol definitions, keyed by the name the instances use.
+    pub library: BTreeMap<String, Symbol>,
+    /// Hierarchical child sheets, keyed by the block symbol name.
+    pub sheets: BTreeMap<String, Schematic>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Self {
+        Point { x, y }
+    }
+
+    /// Whether `self` lies on the segment `a`-`b`, end points included.
+    pub fn on_segment(&self, a: Point, b: Point) -> bool {
+        let cross =
+            (b.x - a.x) as i64 * (self.y - a.y) as i64 - (b.y - a.y) as i64 * (self.x - a.x) as i64;
+        cross == 0
+            && self.x >= a.x.min(b.x)
+            && self.x <= a.x.max(b.x)
+            && self.y >= a.y.min(b.y)
+            && self.y <= a.y.max(b.y)
+    }
+}
+
+impl std::ops::Add for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Point) -> Point {
+        Point::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+/// Placement of a symbol: a rotation in 90° steps, optionally preceded by a
+/// mirror about the y axis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+    M0,
+    M90,
+    M180,
+    M270,
+}
+
+impl Orientation {
+    pub fn parse(s: &str) -> Option<Orientation> {
+        match s.to_ascii_uppercase().as_str() {
+            "R0" => Some(Orientation::R0),
+            "R90" => Some(Orientation::R90),
+            "R180" => Some(Orientation::R180),
+            "R270" => Some(Orientation::R270),
+            "M0" => Some(Orientation::M0),
+            "M90" => Some(Orientation::M90),
+            "M180" => Some(Orientation::M180),
+            "M270" => Some(Orientation::M270),
+            _ => None,
+        }
+    }
+
+    /// Map a point in symbol coordinates to sheet coordinates relative to the
+    /// symbol origin.
+    pub fn transform(&self, p: Point) -> Point {
+        let (x, y) = match self {
+            Orientation::M0 | Orientation::M90 | Orientation::M180 | Orientation::M270 => {
+                (-p.x, p.y)
+            }
+            _ => (p.x, p.y),
+        };
+        match self {
+            Orientation::R0 | Orientation::M0 => Point::new(x, y),
+            Orientation::R90 | Orientation::M90 => Point::new(-y, x),
+            Orientation::R180 | Orientation::M180 => Point::new(-x, -y),
+            Orientation::R270 | Orientation::M270 => Point::new(y, -x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wire {
+    pub start: Point,
+    pub end: Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagKind {
+    Label,
+    Ground,
+    Port(PortDirection),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDirection {
+    Input,
+    Output,
+    Bidirectional,
+}
+
+/// A net label attached to a point on the sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag {
+    pub position: Point,
+    pub name: String,
+    pub kind: FlagKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    Comment,
+    Directive,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub position: Point,
+    pub content: String,
+    pub kind: TextKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A primitive element or subcircuit call.
+    #[default]
+    Cell,
+    /// A hierarchical block backed by a child sheet of the same name.
+    Block,
+    /// Drawing only, never netlisted.
+    Graphic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Line(Point, Point),
+    Rectangle(Point, Point),
+    Circle(Point, Point),
+    /// Bounding box followed by the start and end points of the arc.
+    Arc(Point, Point, Point, Point),
+    Text(Point, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+    pub position: Point,
+    pub name: String,
+    /// Pin number on the physical part, empty when the source format has none.
+    pub number: String,
+    /// 1-based position of the pin on the generated element line.
+    pub order: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub shapes: Vec<Shape>,
+    pub pins: Vec<Pin>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Symbol {
+    /// Pins sorted by their netlist order.
+    pub fn ordered_pins(&self) -> Vec<(usize, &Pin)> {
+        let mut pins: Vec<(usize, &Pin)> = self.pins.iter().enumerate().collect();
+        pins.sort_by_key(|(_, pin)| pin.order);
+        pins
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInstance {
+    pub symbol: String,
+    pub position: Point,
+    pub orientation: Orientation,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl SymbolInstance {
+    pub fn name(&self) -> &str {
+        self.attributes
+            .get("InstName")
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+
+    /// Sheet position of a pin of this instance.
+    pub fn pin_position(&self, pin: &Pin) -> Point {
+        self.position + self.orientation.transform(pin.position)
+    }
+}
+
+/// A set of electrically connected pins, wires and labels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Net {
+    pub name: String,
+    /// Whether a flag names this net, rather than it being numbered.
+    pub labelled: bool,
+    /// `(symbol instance index, pin index)` pairs on this net.
+    pub pins: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Connectivity {
+    pub nets: Vec<Net>,
+    pin_nets: HashMap<(usize, usize), usize>,
+}
+
+impl Connectivity {
+    /// The net a pin of a symbol instance is connected to.
+    pub fn net_of(&self, instance: usize, pin: usize) -> Option<&Net> {
+        self.net_index(instance, pin).map(|index| &self.nets[index])
+    }
+
+    /// The index in `nets` of the net a pin is connected to.
+    pub fn net_index(&self, instance: usize, pin: usize) -> Option<usize> {
+        self.pin_nets.get(&(instance, pin)).copied()
+    }
+}
+
+impl Schematic {
+    pub fn new() -> Self {
+        Schematic::default()
+    }
+
+    pub fn symbol_of(&self, instance: &SymbolInstance) -> Option<&Symbol> {
+        self.library.get(&instance.symbol)
+    }
+
+    /// Resolve the sheet into nets.
+    ///
+    /// Wires join at their end points and at any labelled point, pin or wire
+    /// end that lies on them, and flags of the same name join their nets.
+    /// Ground flags name their net `0`, other flags name the net after
+    /// themselves, and remaining nets are numbered `N001`, `N002`, ... in
+    /// the order their first pin appears, skipping names a flag already
+    /// uses. A pin that touches nothing gets an `NC_nn` net of its own.
+    pub fn connectivity(&self) -> Connectivity {
+        let mut points: Vec<Point> = Vec::new();
+        let mut index: HashMap<Point, usize> = HashMap::new();
+        let mut intern = |p: Point, points: &mut Vec<Point>| -> usize {
+            *index.entry(p).or_insert_with(|| {
+                points.push(p);
+                points.len() - 1
+            })
+        };
+
+        let wires: Vec<(usize, usize)> = self
+            .wires
+            .iter()
+            .map(|w| (intern(w.start, &mut points), intern(w.end, &mut points)))
+            .collect();
+        let flags: Vec<usize> = self
+            .flags
+            .iter()
+            .map(|f| intern(f.position, &mut points))
+            .collect();
+        let mut pins: Vec<((usize, usize), usize)> = Vec::new();
+        for (i, instance) in self.symbols.iter().enumerate() {
+            if let Some(symbol) = self.symbol_of(instance) {
+                for (j, pin) in symbol.pins.iter().enumerate() {
+                    pins.push(((i, j), intern(instance.pin_position(pin), &mut points)));
+                }
+            }
+        }
+
+        let mut sets = DisjointSet::new(points.len());
+        for (wire, (a, b)) in self.wires.iter().zip(&wires) {
+            sets.union(*a, *b);
+            for (k, p) in points.iter().enumerate() {
+                if p.on_segment(wire.start, wire.end) {
+                    sets.union(*a, k);
+                }
+            }
+        }
+
+        let mut named: HashMap<&str, usize> = HashMap::new();
+        for (flag, point) in self.flags.iter().zip(&flags) {
+            let name = match flag.kind {
+                FlagKind::Ground => "0",
+                _ => flag.name.as_str(),
+            };
+            let first = *named.entry(name).or_insert(*point);
+            sets.union(first, *point);
+        }
+
+        let mut labels: HashMap<usize, &Flag> = HashMap::new();
+        for (flag, point) in self.flags.iter().zip(&flags) {
+            let root = sets.find(*point);
+            let replace = match labels.get(&root) {
+                None => true,
+                Some(existing) => {
+                    existing.kind != FlagKind::Ground
+                        && (flag.kind == FlagKind::Ground || flag.name < existing.name)
+                }
+            };
+            if replace {
+                labels.insert(root, flag);
+            }
+        }
+
+        let mut connectivity = Connectivity::default();
+        let mut root_nets: HashMap<usize, usize> = HashMap::new();
+        let mut pin_count: HashMap<usize, usize> = HashMap::new();
+        for (_, point) in &pins {
+            *pin_count.entry(sets.find(*point)).or_default() += 1;
+        }
+        let (mut unnamed, mut unconnected) = (0, 0);
+        let next_free = |count: &mut usize, format: fn(usize) -> String| loop {
+            *count += 1;
+            let name = format(*count);
+            if !named.contains_key(name.as_str()) {
+                break name;
+            }
+        };
+        for (pin, point) in &pins {
+            let root = sets.find(*point);
+            let net = *root_nets.entry(root).or_insert_with(|| {
+                let name = match labels.get(&root) {
+                    Some(flag) if flag.kind == FlagKind::Ground => "0".to_string(),
+                    Some(flag) => flag.name.clone(),
+                    None if pin_count[&root] == 1
+                        && !wires.iter().any(|(a, _)| sets.find(*a) == root) =>
+                    {
+                        next_free(&mut unconnected, |n| format!("NC_{:02}", n))
+                    }
+                    None => next_free(&mut unnamed, |n| format!("N{:03}", n)),
+                };
+                connectivity.nets.push(Net {
+                    name,
+                    labelled: labels.contains_key(&root),
+                    pins: Vec::new(),
+                });
+                connectivity.nets.len() - 1
+            });
+            connectivity.nets[net].pins.push(*pin);
+            connectivity.pin_nets.insert(*pin, net);
+        }
+        connectivity
+    }
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        DisjointSet {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_pin() -> Symbol {
+        Symbol {
+            name: "res".to_string(),
+            pins: vec![
+                Pin {
+                    position: Point::new(16, 16),
+                    name: "A".to_string(),
+                    number: String::new(),
+                    order: 1,
+                },
+                Pin {
+                    position: Point::new(16, 96),
+                    name: "B".to_string(),
+                    number: String::new(),
+                    order: 2,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_orientation() {
+        let p = Point::new(16, 96);
+        assert_eq!(Orientation::R90.transform(p), Point::new(-96, 16));
+        assert_eq!(Orientation::R180.transform(p), Point::new(-16, -96));
+        assert_eq!(Orientation::M0.transform(p), Point::new(-16, 96));
+        assert_eq!(Orientation::M90.transform(p), Point::new(-96, -16));
+    }
+
+    #[test]
+    fn test_connectivity() {
+        let mut sheet = Schematic::new();
+        sheet.library.insert("res".to_string(), two_pin());
+        for (x, name) in [(0, "R1"), (100, "R2")] {
+            let mut attributes = BTreeMap::new();
+            attributes.insert("InstName".to_string(), name.to_string());
+            sheet.symbols.push(SymbolInstance {
+                symbol: "res".to_string(),
+                position: Point::new(x, 0),
+                orientation: Orientation::R0,
+                attributes,
+            });
+        }
+        // R1.B and R2.B tied by a wire, R2.A tapped in the middle of a wire
+        // running to a label, R1.A grounded.
+        sheet.wires.push(Wire {
+            start: Point::new(16, 96),
+            end: Point::new(116, 96),
+        });
+        sheet.wires.push(Wire {
+            start: Point::new(116, -50),
+            end: Point::new(116, 50),
+        });
+        sheet.flags.push(Flag {
+            position: Point::new(116, -50),
+            name: "out".to_string(),
+            kind: FlagKind::Label,
+        });
+        sheet.flags.push(Flag {
+            position: Point::new(16, 16),
+            name: "0".to_string(),
+            kind: FlagKind::Ground,
+        });
+
+        let nets = sheet.connectivity();
+        assert_eq!(nets.net_of(0, 0).unwrap().name, "0");
+        assert_eq!(nets.net_of(0, 1).unwrap().name, "N001");
+        assert_eq!(nets.net_of(1, 1).unwrap().name, "N001");
+        assert_eq!(nets.net_of(1, 0).unwrap().name, "out");
+    }
+}
@@ -1,8 +1,8 @@
-mod analysis;
-mod devices;
-mod frontend;
-mod maths;
-mod utils;
+pub mod analysis;
+pub mod devices;
+pub mod frontend;
+pub mod maths;
+pub mod utils;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -0,0 +1,265 @@
+use crate::maths::complex::Complex;
+use std::f64::consts::PI;
+
+/// Prime factors up to this size get their own butterfly; a size with a
+/// larger one is transformed by Bluestein's algorithm instead.
+const LARGEST_BUTTERFLY: usize = 64;
+
+/// A discrete Fourier transform of one size, with its factorisation and
+/// twiddle factors worked out once so that many transforms can reuse them.
+///
+/// Sizes made of small primes use mixed-radix decimation in time; other
+/// sizes are turned into a convolution of power-of-two size (Bluestein's
+/// chirp z-transform), so every size is `O(n log n)`.
+#[derive(Debug, Clone)]
+pub struct Fft {
+    size: usize,
+    /// `e^(-2πik/n)` for `k < n`.
+    twiddles: Vec<Complex>,
+    factors: Vec<usize>,
+    bluestein: Option<Box<Bluestein>>,
+}
+
+impl Fft {
+    pub fn new(size: usize) -> Self {
+        let factors = factorise(size);
+        let direct = factors.iter().all(|p| *p <= LARGEST_BUTTERFLY);
+        Fft {
+            size,
+            twiddles: if direct { twiddles(size) } else { Vec::new() },
+            factors,
+            bluestein: (!direct).then(|| Box::new(Bluestein::new(size))),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// `X[k] = Σ x[j] e^(-2πijk/n)`, in place.
+    pub fn forward(&self, data: &mut [Complex]) {
+        assert_eq!(data.len(), self.size, "FFT of the wrong size");
+        if self.size <= 1 {
+            return;
+        }
+        if let Some(bluestein) = &self.bluestein {
+            bluestein.transform(data);
+            return;
+        }
+        let input = data.to_vec();
+        self.recurse(&input, 0, 1, data, &self.factors, 1);
+    }
+
+    /// The inverse of [`Self::forward`], including the `1/n`.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        for value in data.iter_mut() {
+            *value = value.conj();
+        }
+        self.forward(data);
+        let scale = 1.0 / self.size as f64;
+        for value in data.iter_mut() {
+            *value = value.conj().scale(scale);
+        }
+    }
+
+    /// Transform the `output.len()` samples of `input` at `offset`, spaced
+    /// `stride`, into `output`. The first factor splits them into that
+    /// many interleaved sub-sequences, which are transformed recursively
+    /// and combined by butterflies.
+    fn recurse(
+        &self,
+        input: &[Complex],
+        offset: usize,
+        stride: usize,
+        output: &mut [Complex],
+        factors: &[usize],
+        twiddle_stride: usize,
+    ) {
+        let n = output.len();
+        if n == 1 {
+            output[0] = input[offset];
+            return;
+        }
+        let p = factors[0];
+        let m = n / p;
+        for (q, part) in output.chunks_mut(m).enumerate() {
+            self.recurse(
+                input,
+                offset + q * stride,
+                stride * p,
+                part,
+                &factors[1..],
+                twiddle_stride * p,
+            );
+        }
+        let twiddle = |k: usize| self.twiddles[(k * twiddle_stride) % self.size];
+        let mut scratch = vec![Complex::default(); p];
+        for k in 0..m {
+            for (q, value) in scratch.iter_mut().enumerate() {
+                *value = output[q * m + k] * twiddle(q * k);
+            }
+            for s in 0..p {
+                output[k + s * m] = scratch
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (q, value)| {
+                        sum + *value * twiddle((q * s % p) * m)
+                    });
+            }
+        }
+    }
+}
+
+/// Transform of arbitrary size as a convolution with the chirp
+/// `e^(iπk²/n)`, computed by power-of-two transforms.
+#[derive(Debug, Clone)]
+struct Bluestein {
+    size: usize,
+    /// `e^(-iπk²/n)`.
+    chirp: Vec<Complex>,
+    /// Transform of the conjugate chirp, wrapped around the convolution.
+    kernel: Vec<Complex>,
+    inner: Fft,
+}
+
+impl Bluestein {
+    fn new(size: usize) -> Self {
+        let length = (2 * size - 1).next_power_of_two();
+        let inner = Fft::new(length);
+        // k² taken modulo 2n keeps the phase accurate for large k.
+        let chirp: Vec<Complex> = (0..size)
+            .map(|k| {
+                let phase = ((k * k) % (2 * size)) as f64 * PI / size as f64;
+                Complex::from_polar(1.0, -phase)
+            })
+            .collect();
+        let mut kernel = vec![Complex::default(); length];
+        kernel[0] = chirp[0].conj();
+        for k in 1..size {
+            kernel[k] = chirp[k].conj();
+            kernel[length - k] = chirp[k].conj();
+        }
+        inner.forward(&mut kernel);
+        Bluestein {
+            size,
+            chirp,
+            kernel,
+            inner,
+        }
+    }
+
+    fn transform(&self, data: &mut [Complex]) {
+        let mut work = vec![Complex::default(); self.inner.size()];
+        for (k, value) in data.iter().enumerate() {
+            work[k] = *value * self.chirp[k];
+        }
+        self.inner.forward(&mut work);
+        for (value, kernel) in work.iter_mut().zip(&self.kernel) {
+            *value *= *kernel;
+        }
+        self.inner.inverse(&mut work);
+        for k in 0..self.size {
+            data[k] = work[k] * self.chirp[k];
+        }
+    }
+}
+
+/// Radix-4 first, then 2, 3, 5 and whatever primes remain.
+fn factorise(mut n: usize) -> Vec<usize> {
+    let mut factors = Vec::new();
+    for p in [4, 2, 3, 5] {
+        while n > 1 && n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+    }
+    let mut p = 7;
+    while n > 1 {
+        if p * p > n {
+            factors.push(n);
+            break;
+        }
+        while n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+        p += 2;
+    }
+    factors
+}
+
+fn twiddles(n: usize) -> Vec<Complex> {
+    (0..n)
+        .map(|k| Complex::from_polar(1.0, -2.0 * PI * k as f64 / n as f64))
+        .collect()
+}
+
+/// Spectrum of real samples: bins `0..=n/2` of their transform, the rest
+/// being the conjugates of these.
+pub fn real_spectrum(samples: &[f64]) -> Vec<Complex> {
+    let mut data: Vec<Complex> = samples.iter().map(|x| Complex::from(*x)).collect();
+    Fft::new(samples.len()).forward(&mut data);
+    data.truncate(samples.len() / 2 + 1);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dft(data: &[Complex]) -> Vec<Complex> {
+        let n = data.len();
+        (0..n)
+            .map(|k| {
+                data.iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (j, x)| {
+                        let phase = -2.0 * PI * ((j * k) % n) as f64 / n as f64;
+                        sum + *x * Complex::from_polar(1.0, phase)
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_against_dft() {
+        // Powers of two, mixed radix, a prime butterfly and Bluestein.
+        for n in [1, 2, 8, 64, 12, 90, 7, 49, 101, 202, 1000] {
+            let data: Vec<Complex> = (0..n)
+                .map(|j| Complex::new((j as f64 * 0.7).sin(), (j as f64 * 1.3).cos() - 0.2))
+                .collect();
+            let expected = dft(&data);
+            let fft = Fft::new(n);
+            let mut actual = data.clone();
+            fft.forward(&mut actual);
+            for (a, e) in actual.iter().zip(&expected) {
+                assert!((*a - *e).norm() < 1e-9 * n as f64, "n = {}", n);
+            }
+            fft.inverse(&mut actual);
+            for (a, x) in actual.iter().zip(&data) {
+                assert!((*a - *x).norm() < 1e-12 * n as f64, "n = {}", n);
+            }
+        }
+        assert_eq!(factorise(360), [4, 2, 3, 3, 5]);
+        assert_eq!(factorise(2 * 67), [2, 67]);
+    }
+
+    #[test]
+    fn test_real_spectrum() {
+        let n = 48;
+        let samples: Vec<f64> = (0..n)
+            .map(|j| 0.5 + 2.0 * (2.0 * PI * 5.0 * j as f64 / n as f64).cos())
+            .collect();
+        let spectrum = real_spectrum(&samples);
+        assert_eq!(spectrum.len(), 25);
+        assert!((spectrum[0].re - 0.5 * n as f64).abs() < 1e-9);
+        assert!((spectrum[5].norm() - n as f64).abs() < 1e-9);
+        let rest: f64 = spectrum
+            .iter()
+            .enumerate()
+            .filter(|(k, _)| *k != 0 && *k != 5)
+            .map(|(_, x)| x.norm())
+            .sum();
+        assert!(rest < 1e-9);
+    }
+}
@@ -0,0 +1,16 @@
+//! Signal processing for spectra of transient results.
+//!
+//! An [`Fft`] of any size, the usual [`Window`]s, [`resample`] to bring
+//! the uneven time points of a transient onto an even grid, and the
+//! measurements built on them: the harmonics of a `.four` analysis and the
+//! distortion and noise figures of a tone.
+
+mod fft;
+mod resample;
+mod spectrum;
+mod window;
+
+pub use fft::{real_spectrum, Fft};
+pub use resample::resample;
+pub use spectrum::{distortion, fourier, Distortion, Fourier, FourierError, Harmonic};
+pub use window::{coherent_gain, noise_bandwidth, Window};
@@ -0,0 +1,52 @@
+/// Interpolate a waveform sampled at increasing, uneven `times`, as the
+/// transient analysis leaves it, onto `points` evenly spaced times from
+/// `start` to `stop`, `stop` left out so that the record is one period of
+/// a periodic signal. Outside the samples the end values are held.
+pub fn resample(times: &[f64], values: &[f64], start: f64, stop: f64, points: usize) -> Vec<f64> {
+    assert_eq!(times.len(), values.len(), "a value for every time");
+    if times.is_empty() {
+        return vec![0.0; points];
+    }
+    let step = (stop - start) / points as f64;
+    // The grid times increase, so the interval only moves forward.
+    let mut i = 0;
+    (0..points)
+        .map(|k| {
+            let t = start + k as f64 * step;
+            while i + 1 < times.len() && times[i + 1] <= t {
+                i += 1;
+            }
+            if t <= times[0] {
+                return values[0];
+            }
+            if i + 1 == times.len() {
+                return values[i];
+            }
+            let span = times[i + 1] - times[i];
+            if span <= 0.0 {
+                return values[i + 1];
+            }
+            let x = (t - times[i]) / span;
+            values[i] + x * (values[i + 1] - values[i])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample() {
+        let times = [0.0, 0.1, 0.15, 0.4, 1.0];
+        let values: Vec<f64> = times.iter().map(|t| 3.0 * t - 1.0).collect();
+        let uniform = resample(&times, &values, 0.0, 1.0, 10);
+        assert_eq!(uniform.len(), 10);
+        for (k, value) in uniform.iter().enumerate() {
+            assert!((value - (0.3 * k as f64 - 1.0)).abs() < 1e-12);
+        }
+        // Held before the first sample and after the last.
+        assert_eq!(resample(&times, &values, -1.0, 3.0, 2), [-1.0, 2.0]);
+        assert_eq!(resample(&[], &[], 0.0, 1.0, 3), [0.0; 3]);
+    }
+}
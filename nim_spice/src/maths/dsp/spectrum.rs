@@ -0,0 +1,300 @@
+use crate::maths::complex::Complex;
+use crate::maths::dsp::fft::{real_spectrum, Fft};
+use crate::maths::dsp::resample::resample;
+use crate::maths::dsp::window::Window;
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+
+/// One harmonic of a `.four` analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Harmonic {
+    pub frequency: f64,
+    /// Peak amplitude.
+    pub magnitude: f64,
+    /// Phase in degrees, measured against a sine as SPICE's `.four` does:
+    /// `sin(ωt)` has phase 0 and `cos(ωt)` phase 90.
+    pub phase: f64,
+}
+
+/// Fourier components of a waveform over one period of a fundamental.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fourier {
+    pub dc: f64,
+    /// The fundamental first.
+    pub harmonics: Vec<Harmonic>,
+    /// Total harmonic distortion in percent of the fundamental.
+    pub thd: f64,
+}
+
+/// A `.four` analysis asked for more harmonics than its points can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FourierError {
+    pub points: usize,
+    pub harmonics: usize,
+}
+
+impl Display for FourierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} points cannot resolve {} harmonics, at least {} are needed",
+            self.points,
+            self.harmonics,
+            2 * self.harmonics + 1
+        )
+    }
+}
+
+impl From<FourierError> for Error {
+    fn from(error: FourierError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// The `.four` analysis: the last period of `frequency` before the end of
+/// the transient, interpolated onto `points` even samples and decomposed
+/// into the fundamental and the harmonics up to `count`.
+pub fn fourier(
+    times: &[f64],
+    values: &[f64],
+    frequency: f64,
+    count: usize,
+    points: usize,
+) -> Result<Fourier, FourierError> {
+    if points <= 2 * count {
+        return Err(FourierError {
+            points,
+            harmonics: count,
+        });
+    }
+    let stop = times.last().copied().unwrap_or(0.0);
+    let samples = resample(times, values, stop - 1.0 / frequency, stop, points);
+    let mut data: Vec<Complex> = samples.iter().map(|x| Complex::from(*x)).collect();
+    Fft::new(points).forward(&mut data);
+    let n = points as f64;
+    let harmonics: Vec<Harmonic> = (1..=count)
+        .map(|k| Harmonic {
+            frequency: k as f64 * frequency,
+            magnitude: 2.0 * data[k].norm() / n,
+            phase: sine_phase(data[k].arg().to_degrees()),
+        })
+        .collect();
+    let fundamental = harmonics.first().map_or(0.0, |h| h.magnitude);
+    let distortion: f64 = harmonics.iter().skip(1).map(|h| h.magnitude.powi(2)).sum();
+    Ok(Fourier {
+        dc: data[0].re / n,
+        thd: if fundamental > 0.0 {
+            100.0 * distortion.sqrt() / fundamental
+        } else {
+            0.0
+        },
+        harmonics,
+    })
+}
+
+/// The phase against a sine of a component whose phase against a cosine
+/// is `cosine`, in (-180, 180] degrees.
+fn sine_phase(cosine: f64) -> f64 {
+    let phase = cosine + 90.0;
+    if phase > 180.0 {
+        phase - 360.0
+    } else {
+        phase
+    }
+}
+
+/// Figures of merit of a record holding one tone with its distortion and
+/// noise, as an ADC or amplifier test reports them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distortion {
+    /// Frequency of the tone, interpolated between bins.
+    pub fundamental: f64,
+    /// Peak amplitude of the tone.
+    pub amplitude: f64,
+    /// Total harmonic distortion in percent, the rms of the harmonics
+    /// over that of the tone as for [`Fourier::thd`].
+    pub thd: f64,
+    /// Signal to noise ratio in dB, harmonics left out.
+    pub snr: f64,
+    /// Signal to noise and distortion in dB.
+    pub sinad: f64,
+    /// Spurious-free dynamic range in dB: the tone over the largest other
+    /// peak.
+    pub sfdr: f64,
+}
+
+/// Measure the tone of evenly spaced `samples` through `window`, counting
+/// harmonics up to `count`, folded back below the Nyquist frequency.
+/// `None` if there is no tone above DC.
+pub fn distortion(
+    samples: &[f64],
+    sample_rate: f64,
+    window: Window,
+    count: usize,
+) -> Option<Distortion> {
+    let n = samples.len();
+    let weights = window.coefficients(n);
+    let windowed: Vec<f64> = samples.iter().zip(&weights).map(|(x, w)| x * w).collect();
+    let power: Vec<f64> = real_spectrum(&windowed)
+        .iter()
+        .map(|x| x.norm().powi(2))
+        .collect();
+    let last = power.len() - 1;
+    let lobe = window.lobe_bins();
+    if last <= lobe {
+        return None;
+    }
+    // Bins already counted: DC, the tone and the harmonics.
+    let mut used = vec![false; power.len()];
+    used[..=lobe].fill(true);
+    let take = |centre: usize, used: &mut [bool]| {
+        let range = centre.saturating_sub(lobe)..=(centre + lobe).min(last);
+        let mut sum = 0.0;
+        let mut moment = 0.0;
+        for k in range {
+            if !used[k] {
+                used[k] = true;
+                sum += power[k];
+                moment += k as f64 * power[k];
+            }
+        }
+        (sum, moment)
+    };
+    let peak = (lobe + 1..=last).max_by(|a, b| power[*a].total_cmp(&power[*b]))?;
+    // Rounding leaves a floor some 300 dB below the signal; a DC level
+    // alone is not a tone.
+    let total: f64 = power.iter().sum();
+    if power[peak] <= 1e-20 * total {
+        return None;
+    }
+    let (signal, moment) = take(peak, &mut used);
+
+    let mut harmonics = 0.0;
+    for h in 2..=count {
+        let mut bin = (h * peak) % n;
+        if bin > n / 2 {
+            bin = n - bin;
+        }
+        // The harmonic's own peak, within a lobe of where it should be.
+        let centre = (bin.saturating_sub(lobe)..=(bin + lobe).min(last))
+            .filter(|k| !used[*k])
+            .max_by(|a, b| power[*a].total_cmp(&power[*b]));
+        if let Some(centre) = centre {
+            harmonics += take(centre, &mut used).0;
+        }
+    }
+
+    let spur = (lobe + 1..=last)
+        .filter(|k| (*k as isize - peak as isize).unsigned_abs() > lobe)
+        .map(|k| power[k])
+        .fold(0.0, f64::max);
+    let noise: f64 = (0..=last).filter(|k| !used[*k]).map(|k| power[k]).sum();
+    let squares: f64 = weights.iter().map(|w| w * w).sum();
+    let db = |ratio: f64| 10.0 * ratio.log10();
+    Some(Distortion {
+        fundamental: moment / signal * sample_rate / n as f64,
+        amplitude: 2.0 * (signal / (n as f64 * squares)).sqrt(),
+        thd: 100.0 * (harmonics / signal).sqrt(),
+        snr: db(signal / noise),
+        sinad: db(signal / (noise + harmonics)),
+        sfdr: db(power[peak] / spur),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// A tone with 1 % second and 0.1 % third harmonic and a little
+    /// deterministic "noise".
+    fn tone(n: usize, cycles: f64) -> Vec<f64> {
+        let mut state = 12345u64;
+        (0..n)
+            .map(|j| {
+                let phase = 2.0 * PI * cycles * j as f64 / n as f64;
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let noise = ((state >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 2e-3;
+                2.0 * phase.sin() + 0.02 * (2.0 * phase).sin() + 0.002 * (3.0 * phase).cos() + noise
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fourier() {
+        // Unevenly sampled, three periods of 1 kHz; only the last counts.
+        let mut times = Vec::new();
+        let mut t = 0.0;
+        let mut k = 0;
+        while t < 3e-3 {
+            times.push(t);
+            t += if k % 3 == 0 { 1e-7 } else { 2.5e-7 };
+            k += 1;
+        }
+        times.push(3e-3);
+        let values: Vec<f64> = times
+            .iter()
+            .map(|t| {
+                let phase = 2.0 * PI * 1e3 * t;
+                0.3 + phase.cos() + 0.1 * (3.0 * phase + 0.5).cos()
+            })
+            .collect();
+        let four = fourier(&times, &values, 1e3, 9, 1024).unwrap();
+        assert!((four.dc - 0.3).abs() < 1e-6);
+        assert_eq!(four.harmonics.len(), 9);
+        assert!((four.harmonics[0].magnitude - 1.0).abs() < 1e-6);
+        // A cosine leads a sine by 90 degrees.
+        assert!((four.harmonics[0].phase - 90.0).abs() < 1e-3);
+        assert!((four.harmonics[2].magnitude - 0.1).abs() < 1e-6);
+        assert!((four.harmonics[2].phase - 90.0 - 0.5f64.to_degrees()).abs() < 1e-2);
+        assert_eq!(four.harmonics[2].frequency, 3e3);
+        assert!(four.harmonics[1].magnitude < 1e-6);
+        assert!((four.thd - 10.0).abs() < 1e-3);
+
+        let sine: Vec<f64> = times.iter().map(|t| (2.0 * PI * 1e3 * t).sin()).collect();
+        let four = fourier(&times, &sine, 1e3, 2, 64).unwrap();
+        assert!(four.harmonics[0].phase.abs() < 1e-3);
+        let inverted: Vec<f64> = values.iter().map(|v| -v).collect();
+        let four = fourier(&times, &inverted, 1e3, 1, 64).unwrap();
+        assert!((four.harmonics[0].phase + 90.0).abs() < 1e-3);
+
+        let error = fourier(&times[..3], &values[..3], 1e3, 9, 16).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "16 points cannot resolve 9 harmonics, at least 19 are needed"
+        );
+    }
+
+    #[test]
+    fn test_distortion() {
+        let n = 4096;
+        let rate = 1e6;
+        // Off-bin, so leakage matters.
+        let samples = tone(n, 101.37);
+        let expected_thd = 100.0 * (0.02f64.powi(2) + 0.002f64.powi(2)).sqrt() / 2.0;
+        // Rms noise 2e-3 / √12 over a tone of rms √2, well above what
+        // leaks through the sidelobes of the windows.
+        let expected_snr = 10.0 * (2.0f64 / (4e-6 / 12.0)).log10();
+        for window in [
+            Window::BlackmanHarris,
+            Window::Kaiser(12.0),
+            Window::FlatTop,
+        ] {
+            let d = distortion(&samples, rate, window, 5).unwrap();
+            assert!(
+                (d.fundamental - 101.37 * rate / n as f64).abs() < 5.0,
+                "{}",
+                window
+            );
+            assert!((d.amplitude - 2.0).abs() < 1e-3, "{}", window);
+            assert!((d.thd / expected_thd - 1.0).abs() < 1e-2, "{}", window);
+            assert!((d.snr - expected_snr).abs() < 0.5, "{} {}", window, d.snr);
+            assert!(d.sinad < d.snr);
+            // The second harmonic is the largest spur, 40 dB down.
+            assert!((d.sfdr - 40.0).abs() < 0.5, "{} {}", window, d.sfdr);
+        }
+        assert_eq!(distortion(&[1.0; 64], rate, Window::Hann, 5), None);
+    }
+}
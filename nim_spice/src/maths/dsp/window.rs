@@ -0,0 +1,186 @@
+use std::f64::consts::PI;
+use std::fmt::Display;
+
+/// Tapers applied to a record before its transform, trading frequency
+/// resolution for leakage.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Window {
+    #[default]
+    Rectangular,
+    Hann,
+    Hamming,
+    /// Four-term Blackman-Harris, sidelobes below -92 dB.
+    BlackmanHarris,
+    /// Five-term flat top, for amplitudes accurate to a few thousandths of
+    /// a dB wherever the tone falls between bins.
+    FlatTop,
+    /// Kaiser with the shape parameter β; larger β means lower sidelobes
+    /// and a wider main lobe.
+    Kaiser(f64),
+}
+
+impl Window {
+    /// The window by name, as in `.four` and the FFT view. A Kaiser window
+    /// takes its β in parentheses, `kaiser(8.6)`, and 6 otherwise.
+    pub fn parse(name: &str) -> Option<Window> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(rest) = name.strip_prefix("kaiser") {
+            let rest = rest.trim();
+            if rest.is_empty() {
+                return Some(Window::Kaiser(6.0));
+            }
+            let beta = rest
+                .strip_prefix('(')?
+                .strip_suffix(')')?
+                .trim()
+                .parse()
+                .ok()?;
+            return Some(Window::Kaiser(beta));
+        }
+        match name.as_str() {
+            "none" | "rectangular" | "rect" => Some(Window::Rectangular),
+            "hann" | "hanning" => Some(Window::Hann),
+            "hamming" => Some(Window::Hamming),
+            "blackmanharris" | "blackman-harris" => Some(Window::BlackmanHarris),
+            "flattop" | "flat-top" => Some(Window::FlatTop),
+            _ => None,
+        }
+    }
+
+    /// The `n` weights of the window. They are periodic, the first point
+    /// of the next period left out, which is what a DFT of the record
+    /// expects.
+    pub fn coefficients(self, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|j| {
+                let x = j as f64 / n as f64;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => cosine_sum(&[0.5, 0.5], x),
+                    Window::Hamming => cosine_sum(&[0.54, 0.46], x),
+                    Window::BlackmanHarris => cosine_sum(&[0.35875, 0.48829, 0.14128, 0.01168], x),
+                    Window::FlatTop => cosine_sum(
+                        &[
+                            0.21557895,
+                            0.41663158,
+                            0.277263158,
+                            0.083578947,
+                            0.006947368,
+                        ],
+                        x,
+                    ),
+                    Window::Kaiser(beta) => {
+                        let r = 2.0 * x - 1.0;
+                        bessel_i0(beta * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(beta)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Bins on each side of a tone's peak that its main lobe spreads over,
+    /// one more than the half width since the tone may fall between bins.
+    /// This is how far from a peak its power is summed.
+    pub fn lobe_bins(self) -> usize {
+        1 + match self {
+            Window::Rectangular => 1,
+            Window::Hann | Window::Hamming => 2,
+            Window::BlackmanHarris => 4,
+            Window::FlatTop => 5,
+            Window::Kaiser(beta) => (1.0 + (beta / PI).powi(2)).sqrt().ceil() as usize,
+        }
+    }
+}
+
+impl Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Window::Rectangular => write!(f, "rectangular"),
+            Window::Hann => write!(f, "hann"),
+            Window::Hamming => write!(f, "hamming"),
+            Window::BlackmanHarris => write!(f, "blackmanharris"),
+            Window::FlatTop => write!(f, "flattop"),
+            Window::Kaiser(beta) => write!(f, "kaiser({})", beta),
+        }
+    }
+}
+
+/// Mean of the weights: the factor by which the window scales a tone.
+pub fn coherent_gain(weights: &[f64]) -> f64 {
+    weights.iter().sum::<f64>() / weights.len() as f64
+}
+
+/// Equivalent noise bandwidth in bins: the width of the rectangular
+/// filter passing as much white noise as the window.
+pub fn noise_bandwidth(weights: &[f64]) -> f64 {
+    let sum: f64 = weights.iter().sum();
+    let squares: f64 = weights.iter().map(|w| w * w).sum();
+    weights.len() as f64 * squares / (sum * sum)
+}
+
+/// `a0 - a1 cos 2πx + a2 cos 4πx - ...`
+fn cosine_sum(terms: &[f64], x: f64) -> f64 {
+    terms
+        .iter()
+        .enumerate()
+        .map(|(k, a)| {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            sign * a * (2.0 * PI * k as f64 * x).cos()
+        })
+        .sum()
+}
+
+/// Modified Bessel function of the first kind and order zero, by its
+/// power series.
+fn bessel_i0(x: f64) -> f64 {
+    let quarter = x * x / 4.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..500 {
+        term *= quarter / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows() {
+        let n = 1024;
+        let bandwidth = |window: Window| noise_bandwidth(&window.coefficients(n));
+        let gain = |window: Window| coherent_gain(&window.coefficients(n));
+        // Textbook values of the noise bandwidth and coherent gain.
+        assert!((bandwidth(Window::Rectangular) - 1.0).abs() < 1e-12);
+        assert!((bandwidth(Window::Hann) - 1.5).abs() < 1e-9);
+        assert!((bandwidth(Window::Hamming) - 1.3628).abs() < 1e-3);
+        assert!((bandwidth(Window::BlackmanHarris) - 2.0044).abs() < 1e-3);
+        assert!((bandwidth(Window::FlatTop) - 3.77).abs() < 1e-2);
+        assert!((gain(Window::Hann) - 0.5).abs() < 1e-12);
+        assert!((gain(Window::Hamming) - 0.54).abs() < 1e-12);
+
+        let kaiser = Window::Kaiser(8.0).coefficients(n);
+        assert!((kaiser[n / 2] - 1.0).abs() < 1e-12);
+        assert!((kaiser[0] - 1.0 / bessel_i0(8.0)).abs() < 1e-15);
+        assert!((bessel_i0(1.0) - 1.2660658777520082).abs() < 1e-14);
+        let hann = Window::Hann.coefficients(n);
+        assert_eq!(hann[0], 0.0);
+        assert!((hann[n / 4] - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Window::parse("Hanning"), Some(Window::Hann));
+        assert_eq!(Window::parse("kaiser( 8.6 )"), Some(Window::Kaiser(8.6)));
+        assert_eq!(Window::parse("kaiser"), Some(Window::Kaiser(6.0)));
+        assert_eq!(Window::parse("kaiser(x)"), None);
+        assert_eq!(Window::parse("triangle"), None);
+        let name = Window::Kaiser(8.6).to_string();
+        assert_eq!(Window::parse(&name), Some(Window::Kaiser(8.6)));
+    }
+}
@@ -0,0 +1,491 @@
+//! Forward-mode automatic differentiation.
+//!
+//! A [`Dual`] carries a value together with its partial derivatives with
+//! respect to `N` chosen inputs, and every operation applies the chain rule
+//! as it goes. Device equations and expressions written once against
+//! [`Real`] then give plain values when run on `f64`, and values with
+//! exact conductances and capacitances when run on duals seeded with the
+//! controlling voltages.
+
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A number device models and expressions can be written against, either
+/// `f64` or a [`Dual`].
+pub trait Real:
+    Copy
+    + Debug
+    + PartialEq
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+{
+    /// A value that does not depend on any input.
+    fn constant(value: f64) -> Self;
+
+    /// The value, without derivatives. Comparisons and branches go by it.
+    fn value(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn log10(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+    fn tanh(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn atan2(self, x: Self) -> Self;
+
+    fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+
+    /// The smaller by value, derivatives and all.
+    fn min(self, other: Self) -> Self {
+        if other.value() < self.value() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other.value() > self.value() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl Real for f64 {
+    fn constant(value: f64) -> Self {
+        value
+    }
+
+    fn value(self) -> f64 {
+        self
+    }
+
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+
+    fn log10(self) -> Self {
+        f64::log10(self)
+    }
+
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+
+    fn tan(self) -> Self {
+        f64::tan(self)
+    }
+
+    fn asin(self) -> Self {
+        f64::asin(self)
+    }
+
+    fn acos(self) -> Self {
+        f64::acos(self)
+    }
+
+    fn atan(self) -> Self {
+        f64::atan(self)
+    }
+
+    fn sinh(self) -> Self {
+        f64::sinh(self)
+    }
+
+    fn cosh(self) -> Self {
+        f64::cosh(self)
+    }
+
+    fn tanh(self) -> Self {
+        f64::tanh(self)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        f64::powi(self, n)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        f64::powf(self, exponent)
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        f64::atan2(self, x)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        f64::hypot(self, other)
+    }
+}
+
+/// A value and its derivatives with respect to `N` inputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual<const N: usize> {
+    pub value: f64,
+    pub gradient: [f64; N],
+}
+
+impl<const N: usize> Dual<N> {
+    pub fn constant(value: f64) -> Self {
+        Dual {
+            value,
+            gradient: [0.0; N],
+        }
+    }
+
+    /// Input number `index`, whose derivative with respect to itself is
+    /// one.
+    pub fn variable(value: f64, index: usize) -> Self {
+        let mut gradient = [0.0; N];
+        gradient[index] = 1.0;
+        Dual { value, gradient }
+    }
+
+    /// Derivative with respect to input number `index`.
+    pub fn derivative(&self, index: usize) -> f64 {
+        self.gradient[index]
+    }
+
+    /// `f(self)` where `f` has the value `value` and the derivative
+    /// `slope` here.
+    fn chain(self, value: f64, slope: f64) -> Self {
+        Dual {
+            value,
+            gradient: self.gradient.map(|g| g * slope),
+        }
+    }
+
+    /// `self * a + other * b` on the derivatives, with `value`.
+    fn combine(value: f64, lhs: Self, a: f64, rhs: Self, b: f64) -> Self {
+        let mut gradient = [0.0; N];
+        for (i, g) in gradient.iter_mut().enumerate() {
+            *g = lhs.gradient[i] * a + rhs.gradient[i] * b;
+        }
+        Dual { value, gradient }
+    }
+}
+
+impl<const N: usize> Add for Dual<N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Dual::combine(self.value + rhs.value, self, 1.0, rhs, 1.0)
+    }
+}
+
+impl<const N: usize> Sub for Dual<N> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Dual::combine(self.value - rhs.value, self, 1.0, rhs, -1.0)
+    }
+}
+
+impl<const N: usize> Mul for Dual<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Dual::combine(self.value * rhs.value, self, rhs.value, rhs, self.value)
+    }
+}
+
+impl<const N: usize> Div for Dual<N> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let quotient = self.value / rhs.value;
+        Dual::combine(quotient, self, 1.0 / rhs.value, rhs, -quotient / rhs.value)
+    }
+}
+
+impl<const N: usize> Neg for Dual<N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.chain(-self.value, -1.0)
+    }
+}
+
+impl<const N: usize> Add<f64> for Dual<N> {
+    type Output = Self;
+
+    fn add(self, rhs: f64) -> Self {
+        Dual {
+            value: self.value + rhs,
+            gradient: self.gradient,
+        }
+    }
+}
+
+impl<const N: usize> Sub<f64> for Dual<N> {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self {
+        self + -rhs
+    }
+}
+
+impl<const N: usize> Mul<f64> for Dual<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        self.chain(self.value * rhs, rhs)
+    }
+}
+
+impl<const N: usize> Div<f64> for Dual<N> {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self {
+        self * (1.0 / rhs)
+    }
+}
+
+impl<const N: usize> Mul<Dual<N>> for f64 {
+    type Output = Dual<N>;
+
+    fn mul(self, rhs: Dual<N>) -> Dual<N> {
+        rhs * self
+    }
+}
+
+impl<const N: usize> From<f64> for Dual<N> {
+    fn from(value: f64) -> Self {
+        Dual::constant(value)
+    }
+}
+
+impl<const N: usize> Real for Dual<N> {
+    fn constant(value: f64) -> Self {
+        Dual::constant(value)
+    }
+
+    fn value(self) -> f64 {
+        self.value
+    }
+
+    fn abs(self) -> Self {
+        if self.value < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        let root = self.value.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    fn exp(self) -> Self {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    fn log10(self) -> Self {
+        self.chain(
+            self.value.log10(),
+            1.0 / (self.value * std::f64::consts::LN_10),
+        )
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    fn tan(self) -> Self {
+        let value = self.value.tan();
+        self.chain(value, 1.0 + value * value)
+    }
+
+    fn asin(self) -> Self {
+        self.chain(
+            self.value.asin(),
+            1.0 / (1.0 - self.value * self.value).sqrt(),
+        )
+    }
+
+    fn acos(self) -> Self {
+        self.chain(
+            self.value.acos(),
+            -1.0 / (1.0 - self.value * self.value).sqrt(),
+        )
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.value.atan(), 1.0 / (1.0 + self.value * self.value))
+    }
+
+    fn sinh(self) -> Self {
+        self.chain(self.value.sinh(), self.value.cosh())
+    }
+
+    fn cosh(self) -> Self {
+        self.chain(self.value.cosh(), self.value.sinh())
+    }
+
+    fn tanh(self) -> Self {
+        let value = self.value.tanh();
+        self.chain(value, 1.0 - value * value)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        if n == 0 {
+            return Dual::constant(1.0);
+        }
+        self.chain(self.value.powi(n), n as f64 * self.value.powi(n - 1))
+    }
+
+    /// `x^y = e^(y ln x)`, except that a constant exponent does not need
+    /// the logarithm, so negative and zero bases work as they do for `f64`.
+    fn powf(self, exponent: Self) -> Self {
+        let value = self.value.powf(exponent.value);
+        let slope = if exponent.value == 0.0 {
+            0.0
+        } else {
+            exponent.value * self.value.powf(exponent.value - 1.0)
+        };
+        if exponent.gradient.iter().all(|g| *g == 0.0) {
+            return self.chain(value, slope);
+        }
+        Dual::combine(value, self, slope, exponent, value * self.value.ln())
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        let radius = self.value * self.value + x.value * x.value;
+        Dual::combine(
+            self.value.atan2(x.value),
+            self,
+            x.value / radius,
+            x,
+            -self.value / radius,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Diode current with series resistance folded in, written once for
+    /// both number types: `I = Is (e^(Vd/(n Vt)) - 1) + Vd gmin`.
+    fn diode<T: Real>(vd: T, temperature: T) -> T {
+        let vt = temperature * (1.380649e-23 / 1.602176634e-19);
+        let is = (temperature / 300.0).powf(T::constant(3.0)) * 1e-14;
+        is * ((vd / (vt * 1.5)).exp() - 1.0) + vd * 1e-12
+    }
+
+    /// Central difference of `f` along input `index`.
+    fn finite_difference(f: impl Fn(&[f64]) -> f64, at: &[f64], index: usize) -> f64 {
+        let h = 1e-6 * at[index].abs().max(1.0);
+        let mut up = at.to_vec();
+        let mut down = at.to_vec();
+        up[index] += h;
+        down[index] -= h;
+        (f(&up) - f(&down)) / (2.0 * h)
+    }
+
+    #[test]
+    fn test_against_finite_differences() {
+        let at = [0.65, 310.0];
+        let exact = diode(Dual::<2>::variable(at[0], 0), Dual::variable(at[1], 1));
+        assert_eq!(exact.value, diode(at[0], at[1]));
+        let f = |x: &[f64]| diode(x[0], x[1]);
+        for index in 0..2 {
+            let estimate = finite_difference(f, &at, index);
+            assert!(
+                (exact.derivative(index) / estimate - 1.0).abs() < 1e-6,
+                "{} {}",
+                exact.derivative(index),
+                estimate
+            );
+        }
+
+        // Every function, as a sum of terms in two inputs.
+        fn mixed<T: Real>(x: T, y: T) -> T {
+            x.sqrt()
+                + x.ln() * y
+                + x.log10()
+                + (x * y).sin()
+                + y.cos() / x
+                + (x / 4.0).tan()
+                + (x / 2.0).asin()
+                + (y / 3.0).acos()
+                + x.atan()
+                + y.sinh() * x.cosh()
+                + (x - y).tanh()
+                + x.powi(3) * y.powi(-2)
+                + x.powf(y)
+                + y.atan2(x)
+                + x.hypot(y)
+                + (y - x).abs()
+                + x.min(y) * 2.0
+                - x.max(y)
+        }
+        let at = [1.3, 0.7];
+        let exact = mixed(Dual::<2>::variable(at[0], 0), Dual::variable(at[1], 1));
+        let f = |v: &[f64]| mixed(v[0], v[1]);
+        assert!((exact.value - f(&at)).abs() < 1e-12);
+        for index in 0..2 {
+            let estimate = finite_difference(f, &at, index);
+            assert!((exact.derivative(index) - estimate).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_powf() {
+        // A constant exponent works on negative and zero bases.
+        let x = Dual::<1>::variable(-2.0, 0);
+        let cube = x.powf(Dual::constant(3.0));
+        assert_eq!(cube.value, -8.0);
+        assert_eq!(cube.derivative(0), 12.0);
+        let zero = Dual::<1>::variable(0.0, 0).powf(Dual::constant(2.0));
+        assert_eq!(zero.derivative(0), 0.0);
+        let one = Dual::<1>::variable(0.0, 0).powf(Dual::constant(0.0));
+        assert_eq!((one.value, one.derivative(0)), (1.0, 0.0));
+    }
+}
@@ -1 +1,6 @@
-
+pub mod complex;
+pub mod dsp;
+pub mod dual;
+pub mod polynomial;
+pub mod rational;
+pub mod sparse;
@@ -0,0 +1,558 @@
+//! Polynomials with real coefficients and their roots.
+//!
+//! Roots are the eigenvalues of the balanced companion matrix, found by the
+//! Francis double-shift QR iteration and then polished by a few Newton
+//! steps on the polynomial itself.
+
+use crate::maths::complex::Complex;
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// QR iterations allowed per eigenvalue before giving up.
+const QR_ITERATIONS: usize = 60;
+
+/// Newton steps polishing each root.
+const POLISH_STEPS: usize = 4;
+
+/// The QR iteration did not settle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootsError;
+
+impl Display for RootsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Polynomial roots did not converge")
+    }
+}
+
+impl From<RootsError> for Error {
+    fn from(error: RootsError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// `c[0] + c[1] s + c[2] s² + ...`, kept without zero leading
+/// coefficients; the zero polynomial has none at all.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polynomial {
+    coefficients: Vec<f64>,
+}
+
+impl Polynomial {
+    /// From coefficients in ascending powers.
+    pub fn new(mut coefficients: Vec<f64>) -> Self {
+        while coefficients.last() == Some(&0.0) {
+            coefficients.pop();
+        }
+        Polynomial { coefficients }
+    }
+
+    pub fn constant(value: f64) -> Self {
+        Polynomial::new(vec![value])
+    }
+
+    /// The variable `s` itself.
+    pub fn s() -> Self {
+        Polynomial::new(vec![0.0, 1.0])
+    }
+
+    /// `Π (s - root)`. Complex roots must come with their conjugates; the
+    /// imaginary parts left by rounding are dropped.
+    pub fn from_roots(roots: &[Complex]) -> Self {
+        let mut product = vec![Complex::from(1.0)];
+        for root in roots {
+            product = multiply_linear(&product, *root);
+        }
+        Polynomial::new(product.iter().map(|c| c.re).collect())
+    }
+
+    /// Coefficients in ascending powers.
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// Degree, zero for the zero polynomial.
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Coefficient of the highest power.
+    pub fn leading(&self) -> f64 {
+        self.coefficients.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn scale(&self, factor: f64) -> Self {
+        Polynomial::new(self.coefficients.iter().map(|c| c * factor).collect())
+    }
+
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |sum, c| sum * x + c)
+    }
+
+    pub fn eval_complex(&self, s: Complex) -> Complex {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(Complex::default(), |sum, c| sum * s + Complex::from(*c))
+    }
+
+    pub fn derivative(&self) -> Self {
+        Polynomial::new(
+            self.coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(k, c)| k as f64 * c)
+                .collect(),
+        )
+    }
+
+    /// Quotient and remainder of long division by `divisor`, which must
+    /// not be zero.
+    pub fn div_rem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+        assert!(!divisor.is_zero(), "division by the zero polynomial");
+        let mut remainder = self.coefficients.clone();
+        let n = divisor.degree();
+        if remainder.len() <= n {
+            return (Polynomial::default(), self.clone());
+        }
+        let mut quotient = vec![0.0; remainder.len() - n];
+        for k in (0..quotient.len()).rev() {
+            let factor = remainder[k + n] / divisor.leading();
+            quotient[k] = factor;
+            for (j, d) in divisor.coefficients.iter().enumerate() {
+                remainder[k + j] -= factor * d;
+            }
+            remainder[k + n] = 0.0;
+        }
+        remainder.truncate(n);
+        (Polynomial::new(quotient), Polynomial::new(remainder))
+    }
+
+    /// Every root, with multiplicity, in no particular order. Complex
+    /// roots come in conjugate pairs.
+    pub fn roots(&self) -> Result<Vec<Complex>, RootsError> {
+        // Roots at zero are exact; take them out first.
+        let zeros = self.coefficients.iter().take_while(|c| **c == 0.0).count();
+        let mut roots = vec![Complex::default(); zeros];
+        let reduced = &self.coefficients[zeros.min(self.coefficients.len())..];
+        let n = reduced.len().saturating_sub(1);
+        if n == 0 {
+            return Ok(roots);
+        }
+        // Companion matrix, 1-based like the QR iteration below.
+        let mut a = vec![vec![0.0; n + 1]; n + 1];
+        for k in 1..=n {
+            a[1][k] = -reduced[n - k] / reduced[n];
+        }
+        for j in 2..=n {
+            a[j][j - 1] = 1.0;
+        }
+        balance(&mut a, n);
+        let eigenvalues = hessenberg_eigenvalues(&mut a, n).ok_or(RootsError)?;
+        let derivative = self.derivative();
+        roots.extend(
+            eigenvalues
+                .into_iter()
+                .map(|root| self.polish(&derivative, root)),
+        );
+        Ok(roots)
+    }
+
+    /// Newton steps from `root`, kept only while they reduce the residual.
+    fn polish(&self, derivative: &Polynomial, mut root: Complex) -> Complex {
+        let mut residual = self.eval_complex(root).norm();
+        for _ in 0..POLISH_STEPS {
+            let slope = derivative.eval_complex(root);
+            if residual == 0.0 || slope.norm() == 0.0 {
+                break;
+            }
+            let mut next = root - self.eval_complex(root) / slope;
+            if root.im == 0.0 {
+                next.im = 0.0;
+            }
+            let next_residual = self.eval_complex(next).norm();
+            if next_residual >= residual {
+                break;
+            }
+            root = next;
+            residual = next_residual;
+        }
+        root
+    }
+}
+
+impl From<f64> for Polynomial {
+    fn from(value: f64) -> Self {
+        Polynomial::constant(value)
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, rhs: &Polynomial) -> Polynomial {
+        let length = self.coefficients.len().max(rhs.coefficients.len());
+        let at = |p: &Polynomial, k: usize| p.coefficients.get(k).copied().unwrap_or(0.0);
+        Polynomial::new((0..length).map(|k| at(self, k) + at(rhs, k)).collect())
+    }
+}
+
+impl Sub for &Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, rhs: &Polynomial) -> Polynomial {
+        self + &-rhs
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, rhs: &Polynomial) -> Polynomial {
+        if self.is_zero() || rhs.is_zero() {
+            return Polynomial::default();
+        }
+        let mut product = vec![0.0; self.coefficients.len() + rhs.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in rhs.coefficients.iter().enumerate() {
+                product[i + j] += a * b;
+            }
+        }
+        Polynomial::new(product)
+    }
+}
+
+impl Neg for &Polynomial {
+    type Output = Polynomial;
+
+    fn neg(self) -> Polynomial {
+        self.scale(-1.0)
+    }
+}
+
+impl Display for Polynomial {
+    /// Highest power first, `2s^2 - 3s + 1`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut first = true;
+        for (k, c) in self.coefficients.iter().enumerate().rev() {
+            if *c == 0.0 {
+                continue;
+            }
+            let magnitude = c.abs();
+            match (first, *c < 0.0) {
+                (true, true) => write!(f, "-")?,
+                (true, false) => {}
+                (false, true) => write!(f, " - ")?,
+                (false, false) => write!(f, " + ")?,
+            }
+            if magnitude != 1.0 || k == 0 {
+                write!(f, "{}", magnitude)?;
+            }
+            match k {
+                0 => {}
+                1 => write!(f, "s")?,
+                _ => write!(f, "s^{}", k)?,
+            }
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Coefficients of `p(s) (s - root)`.
+pub(crate) fn multiply_linear(p: &[Complex], root: Complex) -> Vec<Complex> {
+    let mut product = vec![Complex::default(); p.len() + 1];
+    for (k, c) in p.iter().enumerate() {
+        product[k + 1] += *c;
+        product[k] -= *c * root;
+    }
+    product
+}
+
+/// Scale rows and columns of the 1-based `n × n` matrix `a` by powers of
+/// two until their norms are comparable, which keeps the eigenvalues and
+/// makes them better conditioned.
+#[allow(clippy::needless_range_loop)]
+fn balance(a: &mut [Vec<f64>], n: usize) {
+    const RADIX: f64 = 2.0;
+    let mut done = false;
+    while !done {
+        done = true;
+        for i in 1..=n {
+            let mut c = 0.0;
+            let mut r = 0.0;
+            for j in (1..=n).filter(|j| *j != i) {
+                c += a[j][i].abs();
+                r += a[i][j].abs();
+            }
+            if c == 0.0 || r == 0.0 {
+                continue;
+            }
+            let sum = c + r;
+            let mut f = 1.0;
+            while c < r / RADIX {
+                f *= RADIX;
+                c *= RADIX * RADIX;
+            }
+            while c > r * RADIX {
+                f /= RADIX;
+                c /= RADIX * RADIX;
+            }
+            if (c + r) / f < 0.95 * sum {
+                done = false;
+                for j in 1..=n {
+                    a[i][j] /= f;
+                    a[j][i] *= f;
+                }
+            }
+        }
+    }
+}
+
+/// Eigenvalues of the 1-based upper Hessenberg matrix `a` by the Francis
+/// double-shift QR iteration, as in EISPACK's `hqr`. `a` is destroyed.
+#[allow(clippy::many_single_char_names, clippy::needless_range_loop)]
+fn hessenberg_eigenvalues(a: &mut [Vec<f64>], n: usize) -> Option<Vec<Complex>> {
+    let mut eigenvalues = Vec::with_capacity(n);
+    let mut norm = 0.0;
+    for i in 1..=n {
+        for j in i.saturating_sub(1).max(1)..=n {
+            norm += a[i][j].abs();
+        }
+    }
+    let mut nn = n;
+    let mut t = 0.0;
+    while nn >= 1 {
+        let mut iterations = 0;
+        loop {
+            // Look for a negligible subdiagonal element splitting the
+            // matrix.
+            let mut l = nn;
+            while l >= 2 {
+                let mut s = a[l - 1][l - 1].abs() + a[l][l].abs();
+                if s == 0.0 {
+                    s = norm;
+                }
+                if a[l][l - 1].abs() + s == s {
+                    a[l][l - 1] = 0.0;
+                    break;
+                }
+                l -= 1;
+            }
+            let mut x = a[nn][nn];
+            if l == nn {
+                // One root found.
+                eigenvalues.push(Complex::from(x + t));
+                nn -= 1;
+                break;
+            }
+            let mut y = a[nn - 1][nn - 1];
+            let mut w = a[nn][nn - 1] * a[nn - 1][nn];
+            if l == nn - 1 {
+                // Two roots found.
+                let p = 0.5 * (y - x);
+                let q = p * p + w;
+                let mut z = q.abs().sqrt();
+                x += t;
+                if q >= 0.0 {
+                    z = p + z.copysign(p);
+                    let second = if z != 0.0 { x - w / z } else { x + z };
+                    eigenvalues.push(Complex::from(x + z));
+                    eigenvalues.push(Complex::from(second));
+                } else {
+                    eigenvalues.push(Complex::new(x + p, z));
+                    eigenvalues.push(Complex::new(x + p, -z));
+                }
+                nn -= 2;
+                break;
+            }
+            if iterations == QR_ITERATIONS {
+                return None;
+            }
+            if iterations == 10 || iterations == 20 {
+                // Exceptional shift.
+                t += x;
+                for i in 1..=nn {
+                    a[i][i] -= x;
+                }
+                let s = a[nn][nn - 1].abs() + a[nn - 1][nn - 2].abs();
+                x = 0.75 * s;
+                y = x;
+                w = -0.4375 * s * s;
+            }
+            iterations += 1;
+            // Look for two consecutive small subdiagonal elements.
+            let (mut p, mut q, mut r);
+            let mut m = nn - 2;
+            loop {
+                let z = a[m][m];
+                let r1 = x - z;
+                let s1 = y - z;
+                p = (r1 * s1 - w) / a[m + 1][m] + a[m][m + 1];
+                q = a[m + 1][m + 1] - z - r1 - s1;
+                r = a[m + 2][m + 1];
+                let s = p.abs() + q.abs() + r.abs();
+                p /= s;
+                q /= s;
+                r /= s;
+                if m == l {
+                    break;
+                }
+                let u = a[m][m - 1].abs() * (q.abs() + r.abs());
+                let v = p.abs() * (a[m - 1][m - 1].abs() + z.abs() + a[m + 1][m + 1].abs());
+                if u + v == v {
+                    break;
+                }
+                m -= 1;
+            }
+            for i in m + 2..=nn {
+                a[i][i - 2] = 0.0;
+                if i != m + 2 {
+                    a[i][i - 3] = 0.0;
+                }
+            }
+            // Double QR step on rows l to nn and columns m to nn.
+            for k in m..nn {
+                if k != m {
+                    p = a[k][k - 1];
+                    q = a[k + 1][k - 1];
+                    r = if k != nn - 1 { a[k + 2][k - 1] } else { 0.0 };
+                    x = p.abs() + q.abs() + r.abs();
+                    if x != 0.0 {
+                        p /= x;
+                        q /= x;
+                        r /= x;
+                    }
+                }
+                let s = (p * p + q * q + r * r).sqrt().copysign(p);
+                if s == 0.0 {
+                    continue;
+                }
+                if k == m {
+                    if l != m {
+                        a[k][k - 1] = -a[k][k - 1];
+                    }
+                } else {
+                    a[k][k - 1] = -s * x;
+                }
+                p += s;
+                x = p / s;
+                y = q / s;
+                let z = r / s;
+                q /= p;
+                r /= p;
+                for j in k..=nn {
+                    let mut p = a[k][j] + q * a[k + 1][j];
+                    if k != nn - 1 {
+                        p += r * a[k + 2][j];
+                        a[k + 2][j] -= p * z;
+                    }
+                    a[k + 1][j] -= p * y;
+                    a[k][j] -= p * x;
+                }
+                for i in l..=nn.min(k + 3) {
+                    let mut p = x * a[i][k] + y * a[i][k + 1];
+                    if k != nn - 1 {
+                        p += z * a[i][k + 2];
+                        a[i][k + 2] -= p * r;
+                    }
+                    a[i][k + 1] -= p * q;
+                    a[i][k] -= p;
+                }
+            }
+        }
+    }
+    Some(eigenvalues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every expected root matched by a distinct found one.
+    fn assert_roots(found: &[Complex], expected: &[Complex], tolerance: f64) {
+        assert_eq!(found.len(), expected.len());
+        let mut unused: Vec<Complex> = found.to_vec();
+        for e in expected {
+            let (index, distance) = unused
+                .iter()
+                .enumerate()
+                .map(|(i, f)| (i, (*f - *e).norm()))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            assert!(distance < tolerance, "{} off by {}", e, distance);
+            unused.remove(index);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let p = Polynomial::new(vec![1.0, -3.0, 2.0]);
+        let q = Polynomial::new(vec![-1.0, 1.0]);
+        assert_eq!(p.to_string(), "2s^2 - 3s + 1");
+        assert_eq!((&p * &q).coefficients(), [-1.0, 4.0, -5.0, 2.0]);
+        assert_eq!((&p - &p).coefficients(), []);
+        assert_eq!((&p + &q).degree(), 2);
+        assert_eq!(p.derivative().coefficients(), [-3.0, 4.0]);
+        assert_eq!(p.eval(2.0), 3.0);
+        let (quotient, remainder) = p.div_rem(&q);
+        assert_eq!(quotient.coefficients(), [-1.0, 2.0]);
+        assert!(remainder.is_zero());
+        let (quotient, remainder) = q.div_rem(&p);
+        assert!(quotient.is_zero());
+        assert_eq!(remainder, q);
+        let s = Complex::new(0.5, 2.0);
+        let product = (&p * &q).eval_complex(s);
+        assert!((product - p.eval_complex(s) * q.eval_complex(s)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_roots() {
+        let expected = [
+            Complex::from(-1.0),
+            Complex::from(-2.0),
+            Complex::new(-1.0, 2.0),
+            Complex::new(-1.0, -2.0),
+            Complex::from(0.0),
+        ];
+        let p = Polynomial::from_roots(&expected);
+        assert_eq!(p.coefficients()[0], 0.0);
+        assert_roots(&p.roots().unwrap(), &expected, 1e-12);
+
+        // Wilkinson's polynomial of degree 10 is badly conditioned.
+        let expected: Vec<Complex> = (1..=10).map(|k| Complex::from(k as f64)).collect();
+        let p = Polynomial::from_roots(&expected);
+        assert_roots(&p.roots().unwrap(), &expected, 1e-8);
+
+        // Widely spread poles, as in a circuit with ps and ms time
+        // constants.
+        let expected = [
+            Complex::from(-1e9),
+            Complex::from(-1e3),
+            Complex::new(-1e6, 5e6),
+            Complex::new(-1e6, -5e6),
+        ];
+        let p = Polynomial::from_roots(&expected);
+        let found = p.roots().unwrap();
+        for e in expected {
+            assert!(found.iter().any(|f| (*f - e).norm() < 1e-9 * e.norm()));
+        }
+
+        // A double root is found to about half the digits.
+        let p = Polynomial::new(vec![1.0, 2.0, 1.0]);
+        assert_roots(&p.roots().unwrap(), &[Complex::from(-1.0); 2], 1e-7);
+        assert!(Polynomial::constant(3.0).roots().unwrap().is_empty());
+        assert!(Polynomial::default().roots().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,558 @@
+//! Rational functions of `s`, as written in `Laplace=` sources and found by
+//! `.pz`, and their realisation as state equations the transient analysis
+//! can integrate.
+
+use crate::maths::complex::Complex;
+use crate::maths::polynomial::{multiply_linear, Polynomial, RootsError};
+use crate::utils::error::{Error, ErrorType};
+use std::fmt::Display;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Roots closer than this, relative to their size, are taken as one
+/// repeated root. A root of multiplicity m is only found to about 1/m of
+/// the digits, so this has to be generous.
+const REPEATED_ROOT: f64 = 1e-4;
+
+/// Why a rational function has no state-space realisation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RealisationError {
+    ZeroDenominator,
+    /// More zeros than poles: the output would differentiate the input.
+    Improper {
+        numerator: usize,
+        denominator: usize,
+    },
+}
+
+impl Display for RealisationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RealisationError::ZeroDenominator => {
+                write!(f, "Transfer function has a zero denominator")
+            }
+            RealisationError::Improper {
+                numerator,
+                denominator,
+            } => write!(
+                f,
+                "Transfer function numerator of degree {} exceeds its denominator of degree {}",
+                numerator, denominator
+            ),
+        }
+    }
+}
+
+impl From<RealisationError> for Error {
+    fn from(error: RealisationError) -> Self {
+        Error::new(ErrorType::Runtime, error.to_string(), 0, 0)
+    }
+}
+
+/// `numerator(s) / denominator(s)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rational {
+    pub numerator: Polynomial,
+    pub denominator: Polynomial,
+}
+
+/// `residue / (s - pole)^power`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoleTerm {
+    pub pole: Complex,
+    pub power: usize,
+    pub residue: Complex,
+}
+
+/// A rational function as a polynomial plus a sum of pole terms. Complex
+/// poles appear with their conjugates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialFractions {
+    pub direct: Polynomial,
+    pub terms: Vec<PoleTerm>,
+}
+
+impl PartialFractions {
+    pub fn eval(&self, s: Complex) -> Complex {
+        self.terms
+            .iter()
+            .fold(self.direct.eval_complex(s), |sum, t| {
+                let mut denominator = Complex::from(1.0);
+                for _ in 0..t.power {
+                    denominator *= s - t.pole;
+                }
+                sum + t.residue / denominator
+            })
+    }
+
+    /// Impulse response at `t > 0` of the pole terms,
+    /// `Σ r t^(k-1) e^(pt) / (k-1)!`; the direct part only acts at `t = 0`.
+    pub fn impulse(&self, t: f64) -> f64 {
+        self.terms
+            .iter()
+            .map(|term| {
+                let factorial: f64 = (1..term.power).map(|k| k as f64).product();
+                let exponential = Complex::from_polar((term.pole.re * t).exp(), term.pole.im * t);
+                (term.residue * exponential).re * t.powi(term.power as i32 - 1) / factorial
+            })
+            .sum()
+    }
+}
+
+impl Rational {
+    pub fn new(numerator: Polynomial, denominator: Polynomial) -> Self {
+        Rational {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn eval(&self, s: Complex) -> Complex {
+        self.numerator.eval_complex(s) / self.denominator.eval_complex(s)
+    }
+
+    /// No more zeros than poles, so bounded as `s` grows.
+    pub fn is_proper(&self) -> bool {
+        self.numerator.is_zero() || self.numerator.degree() <= self.denominator.degree()
+    }
+
+    pub fn zeros(&self) -> Result<Vec<Complex>, RootsError> {
+        self.numerator.roots()
+    }
+
+    pub fn poles(&self) -> Result<Vec<Complex>, RootsError> {
+        self.denominator.roots()
+    }
+
+    /// Expand into a polynomial and pole terms, grouping poles closer than
+    /// [`REPEATED_ROOT`] into one repeated pole. The denominator must not
+    /// be zero.
+    pub fn partial_fractions(&self) -> Result<PartialFractions, RootsError> {
+        let (direct, remainder) = self.numerator.div_rem(&self.denominator);
+        let mut clusters: Vec<(Complex, Vec<Complex>)> = Vec::new();
+        for pole in self.poles()? {
+            let near = clusters.iter_mut().find(|(_, members)| {
+                (members[0] - pole).norm() <= REPEATED_ROOT * members[0].norm().max(1.0)
+            });
+            match near {
+                Some((_, members)) => members.push(pole),
+                None => clusters.push((pole, vec![pole])),
+            }
+        }
+        for (centre, members) in clusters.iter_mut() {
+            let sum = members.iter().fold(Complex::default(), |a, b| a + *b);
+            *centre = sum.scale(1.0 / members.len() as f64);
+        }
+
+        let numerator: Vec<Complex> = remainder
+            .coefficients()
+            .iter()
+            .map(|c| Complex::from(*c))
+            .collect();
+        let mut terms = Vec::new();
+        for (index, (pole, members)) in clusters.iter().enumerate() {
+            let power = members.len();
+            // The denominator with this pole divided out, from the others.
+            let mut rest = vec![Complex::from(self.denominator.leading())];
+            for (_, (other, others)) in clusters.iter().enumerate().filter(|(i, _)| *i != index) {
+                for _ in 0..others.len() {
+                    rest = multiply_linear(&rest, *other);
+                }
+            }
+            // Taylor coefficients about the pole of numerator / rest give
+            // the residues, the highest power first.
+            let n = taylor(&numerator, *pole, power);
+            let d = taylor(&rest, *pole, power);
+            let mut series: Vec<Complex> = Vec::with_capacity(power);
+            for j in 0..power {
+                let mut c = n[j];
+                for i in 1..=j {
+                    c -= d[i] * series[j - i];
+                }
+                series.push(c / d[0]);
+            }
+            for (j, residue) in series.into_iter().enumerate() {
+                terms.push(PoleTerm {
+                    pole: *pole,
+                    power: power - j,
+                    residue,
+                });
+            }
+        }
+        Ok(PartialFractions { direct, terms })
+    }
+
+    /// Controllable canonical realisation `x' = A x + B u`,
+    /// `y = C x + D u`, with one state per pole.
+    pub fn state_space(&self) -> Result<StateSpace, RealisationError> {
+        if self.denominator.is_zero() {
+            return Err(RealisationError::ZeroDenominator);
+        }
+        if !self.is_proper() {
+            return Err(RealisationError::Improper {
+                numerator: self.numerator.degree(),
+                denominator: self.denominator.degree(),
+            });
+        }
+        let n = self.denominator.degree();
+        let lead = self.denominator.leading();
+        let denominator = self.denominator.coefficients();
+        let at = |k: usize| self.numerator.coefficients().get(k).copied().unwrap_or(0.0);
+        let d = at(n) / lead;
+        let mut a = vec![vec![0.0; n]; n];
+        for i in 0..n.saturating_sub(1) {
+            a[i][i + 1] = 1.0;
+        }
+        if n > 0 {
+            for (j, coefficient) in denominator.iter().take(n).enumerate() {
+                a[n - 1][j] = -coefficient / lead;
+            }
+        }
+        let mut b = vec![0.0; n];
+        if n > 0 {
+            b[n - 1] = 1.0;
+        }
+        let c = (0..n)
+            .map(|k| (at(k) - d * denominator[k]) / lead)
+            .collect();
+        Ok(StateSpace { a, b, c, d })
+    }
+}
+
+impl From<Polynomial> for Rational {
+    fn from(numerator: Polynomial) -> Self {
+        Rational::new(numerator, Polynomial::constant(1.0))
+    }
+}
+
+impl From<f64> for Rational {
+    fn from(value: f64) -> Self {
+        Rational::from(Polynomial::constant(value))
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+
+    fn add(self, rhs: &Rational) -> Rational {
+        if self.denominator == rhs.denominator {
+            return Rational::new(&self.numerator + &rhs.numerator, self.denominator.clone());
+        }
+        Rational::new(
+            &(&self.numerator * &rhs.denominator) + &(&rhs.numerator * &self.denominator),
+            &self.denominator * &rhs.denominator,
+        )
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+
+    fn sub(self, rhs: &Rational) -> Rational {
+        self + &-rhs
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+
+    fn mul(self, rhs: &Rational) -> Rational {
+        Rational::new(
+            &self.numerator * &rhs.numerator,
+            &self.denominator * &rhs.denominator,
+        )
+    }
+}
+
+impl Div for &Rational {
+    type Output = Rational;
+
+    fn div(self, rhs: &Rational) -> Rational {
+        Rational::new(
+            &self.numerator * &rhs.denominator,
+            &self.denominator * &rhs.numerator,
+        )
+    }
+}
+
+impl Neg for &Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational::new(-&self.numerator, self.denominator.clone())
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) / ({})", self.numerator, self.denominator)
+    }
+}
+
+/// The first `count` Taylor coefficients of `p` about `centre`, by
+/// repeated synthetic division.
+fn taylor(p: &[Complex], centre: Complex, count: usize) -> Vec<Complex> {
+    let mut coefficients = p.to_vec();
+    let mut result = Vec::with_capacity(count);
+    for _ in 0..count {
+        if coefficients.is_empty() {
+            result.push(Complex::default());
+            continue;
+        }
+        // Horner's scheme leaves p(centre) and the quotient.
+        let mut quotient = vec![Complex::default(); coefficients.len() - 1];
+        let mut value = Complex::default();
+        for k in (0..coefficients.len()).rev() {
+            value = value * centre + coefficients[k];
+            if k > 0 {
+                quotient[k - 1] = value;
+            }
+        }
+        result.push(value);
+        coefficients = quotient;
+    }
+    result
+}
+
+/// `x' = A x + B u`, `y = C x + D u` for a single input and output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSpace {
+    pub a: Vec<Vec<f64>>,
+    pub b: Vec<f64>,
+    pub c: Vec<f64>,
+    pub d: f64,
+}
+
+impl StateSpace {
+    /// Number of states.
+    pub fn order(&self) -> usize {
+        self.b.len()
+    }
+
+    /// `x'` for the states `x` and input `u`.
+    pub fn derivative(&self, x: &[f64], u: f64) -> Vec<f64> {
+        self.a
+            .iter()
+            .zip(&self.b)
+            .map(|(row, b)| row.iter().zip(x).map(|(a, x)| a * x).sum::<f64>() + b * u)
+            .collect()
+    }
+
+    pub fn output(&self, x: &[f64], u: f64) -> f64 {
+        self.c.iter().zip(x).map(|(c, x)| c * x).sum::<f64>() + self.d * u
+    }
+
+    /// The states at rest under a constant input, `None` when a pole at the
+    /// origin makes the system integrate it.
+    pub fn equilibrium(&self, u: f64) -> Option<Vec<f64>> {
+        let n = self.order();
+        // Solve A x = -B u by elimination with partial pivoting.
+        let mut m: Vec<Vec<f64>> = self
+            .a
+            .iter()
+            .zip(&self.b)
+            .map(|(row, b)| {
+                let mut row = row.clone();
+                row.push(-b * u);
+                row
+            })
+            .collect();
+        let scale = self
+            .a
+            .iter()
+            .flatten()
+            .fold(0.0, |s: f64, a| s.max(a.abs()));
+        for k in 0..n {
+            let pivot = (k..n).max_by(|i, j| m[*i][k].abs().total_cmp(&m[*j][k].abs()))?;
+            if m[pivot][k].abs() <= 1e-14 * scale {
+                return None;
+            }
+            m.swap(k, pivot);
+            let (upper, lower) = m.split_at_mut(k + 1);
+            let pivot = &upper[k];
+            for row in lower {
+                let factor = row[k] / pivot[k];
+                for (x, p) in row[k..].iter_mut().zip(&pivot[k..]) {
+                    *x -= factor * p;
+                }
+            }
+        }
+        let mut x = vec![0.0; n];
+        for k in (0..n).rev() {
+            let sum: f64 = (k + 1..n).map(|j| m[k][j] * x[j]).sum();
+            x[k] = (m[k][n] - sum) / m[k][k];
+        }
+        Some(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poly(coefficients: &[f64]) -> Polynomial {
+        Polynomial::new(coefficients.to_vec())
+    }
+
+    /// `C (sI - A)⁻¹ B + D` by complex elimination.
+    fn transfer(system: &StateSpace, s: Complex) -> Complex {
+        let n = system.order();
+        let mut m: Vec<Vec<Complex>> = (0..n)
+            .map(|i| {
+                let mut row: Vec<Complex> = (0..n)
+                    .map(|j| {
+                        let identity = if i == j { s } else { Complex::default() };
+                        identity - Complex::from(system.a[i][j])
+                    })
+                    .collect();
+                row.push(Complex::from(system.b[i]));
+                row
+            })
+            .collect();
+        for k in 0..n {
+            let pivot = (k..n)
+                .max_by(|i, j| m[*i][k].norm().total_cmp(&m[*j][k].norm()))
+                .unwrap();
+            m.swap(k, pivot);
+            let (upper, lower) = m.split_at_mut(k + 1);
+            let pivot = &upper[k];
+            for row in lower {
+                let factor = row[k] / pivot[k];
+                for (x, p) in row[k..].iter_mut().zip(&pivot[k..]) {
+                    *x -= factor * *p;
+                }
+            }
+        }
+        let mut x = vec![Complex::default(); n];
+        for k in (0..n).rev() {
+            let mut sum = m[k][n];
+            for j in k + 1..n {
+                sum -= m[k][j] * x[j];
+            }
+            x[k] = sum / m[k][k];
+        }
+        x.iter()
+            .zip(&system.c)
+            .fold(Complex::from(system.d), |y, (x, c)| y + x.scale(*c))
+    }
+
+    fn points() -> [Complex; 4] {
+        [
+            Complex::new(0.3, 0.7),
+            Complex::new(-0.5, 2.0),
+            Complex::new(3.0, -1.0),
+            Complex::new(0.0, 10.0),
+        ]
+    }
+
+    #[test]
+    fn test_partial_fractions() {
+        // (s + 3) / ((s + 1)(s + 2)) = 2/(s + 1) - 1/(s + 2)
+        let h = Rational::new(poly(&[3.0, 1.0]), poly(&[2.0, 3.0, 1.0]));
+        let expansion = h.partial_fractions().unwrap();
+        assert!(expansion.direct.is_zero());
+        for term in &expansion.terms {
+            let expected = if term.pole.re < -1.5 { -1.0 } else { 2.0 };
+            assert!((term.residue - Complex::from(expected)).norm() < 1e-12);
+            assert_eq!(term.power, 1);
+        }
+        // Impulse response 2 e^-t - e^-2t.
+        let t: f64 = 0.7;
+        let expected = 2.0 * (-t).exp() - (-2.0 * t).exp();
+        assert!((expansion.impulse(t) - expected).abs() < 1e-12);
+
+        // 1 / ((s + 1)² (s + 2)) = 1/(s + 1)² - 1/(s + 1) + 1/(s + 2)
+        let h = Rational::new(poly(&[1.0]), poly(&[2.0, 5.0, 4.0, 1.0]));
+        let expansion = h.partial_fractions().unwrap();
+        assert_eq!(expansion.terms.len(), 3);
+        for term in &expansion.terms {
+            let expected = match (term.pole.re < -1.5, term.power) {
+                (true, 1) => 1.0,
+                (false, 2) => 1.0,
+                (false, 1) => -1.0,
+                _ => panic!("unexpected term {:?}", term),
+            };
+            assert!((term.residue - Complex::from(expected)).norm() < 1e-6);
+        }
+
+        // Improper, with complex poles: the expansion is exact everywhere.
+        let h = Rational::new(poly(&[1.0, -2.0, 0.5, 3.0, 1.0]), poly(&[5.0, 2.0, 1.0]));
+        let expansion = h.partial_fractions().unwrap();
+        assert_eq!(expansion.direct.degree(), 2);
+        for s in points() {
+            assert!((expansion.eval(s) - h.eval(s)).norm() < 1e-10 * h.eval(s).norm());
+        }
+    }
+
+    #[test]
+    fn test_state_space() {
+        // A second order low pass with a zero, and a biproper lead network.
+        let cases = [
+            Rational::new(poly(&[4.0, 1.0]), poly(&[4.0, 0.8, 1.0])),
+            Rational::new(poly(&[1.0, 1e-3]), poly(&[1.0, 1e-4])),
+            Rational::from(2.5),
+        ];
+        for h in cases {
+            let system = h.state_space().unwrap();
+            assert_eq!(system.order(), h.denominator.degree());
+            for s in points() {
+                assert!((transfer(&system, s) - h.eval(s)).norm() < 1e-12 * h.eval(s).norm());
+            }
+            let rest = system.equilibrium(2.0).unwrap();
+            assert!(system
+                .derivative(&rest, 2.0)
+                .iter()
+                .all(|d| d.abs() < 1e-12));
+            assert!(
+                (system.output(&rest, 2.0) - 2.0 * h.eval(Complex::default()).re).abs() < 1e-12
+            );
+        }
+
+        // The step response of 4 / (s² + 0.8 s + 4) overshoots by
+        // e^(-πζ/√(1-ζ²)) with ζ = 0.2.
+        let h = Rational::new(poly(&[4.0]), poly(&[4.0, 0.8, 1.0]));
+        let system = h.state_space().unwrap();
+        let mut x = vec![0.0; 2];
+        let step = 1e-3;
+        let mut peak: f64 = 0.0;
+        for _ in 0..10000 {
+            // Midpoint rule.
+            let k1 = system.derivative(&x, 1.0);
+            let half: Vec<f64> = x.iter().zip(&k1).map(|(x, k)| x + 0.5 * step * k).collect();
+            let k2 = system.derivative(&half, 1.0);
+            x.iter_mut().zip(&k2).for_each(|(x, k)| *x += step * k);
+            peak = peak.max(system.output(&x, 1.0));
+        }
+        let zeta: f64 = 0.2;
+        let overshoot = (-std::f64::consts::PI * zeta / (1.0 - zeta * zeta).sqrt()).exp();
+        assert!((peak - 1.0 - overshoot).abs() < 1e-4);
+
+        // An integrator has no rest state; a differentiator no realisation.
+        let integrator = Rational::new(poly(&[1.0]), poly(&[0.0, 1.0]));
+        assert_eq!(integrator.state_space().unwrap().equilibrium(1.0), None);
+        let differentiator = Rational::from(Polynomial::s());
+        assert_eq!(
+            differentiator.state_space(),
+            Err(RealisationError::Improper {
+                numerator: 1,
+                denominator: 0
+            })
+        );
+        let zero = Rational::new(poly(&[1.0]), Polynomial::default());
+        assert_eq!(zero.state_space(), Err(RealisationError::ZeroDenominator));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // 1 / (1 + s/ω) composed as the parser would build it.
+        let s = Rational::from(Polynomial::s());
+        let one = Rational::from(1.0);
+        let omega = Rational::from(1e3);
+        let h = &one / &(&one + &(&s / &omega));
+        let poles = h.poles().unwrap();
+        assert_eq!(poles.len(), 1);
+        assert!((poles[0] - Complex::from(-1e3)).norm() < 1e-9);
+        let difference = &h - &h;
+        assert!(difference.numerator.is_zero());
+        assert_eq!(h.to_string(), "(1000) / (s + 1000)");
+    }
+}
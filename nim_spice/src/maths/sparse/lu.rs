@@ -0,0 +1,783 @@
+use crate::maths::sparse::matrix::{SparseMatrix, Structure};
+use crate::maths::sparse::ordering::{self, Ordering, Statistics};
+use crate::maths::sparse::Scalar;
+use crate::utils::error::{Error, ErrorType};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Pivoting tolerances, with the meaning of SPICE's `pivrel` and `pivtol`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuOptions {
+    /// A pivot must be at least this fraction of the largest entry in its
+    /// column of the remaining submatrix.
+    pub relative_threshold: f64,
+    /// Entries at or below this magnitude are never used as pivots.
+    pub absolute_threshold: f64,
+    pub ordering: Ordering,
+}
+
+impl Default for LuOptions {
+    fn default() -> Self {
+        LuOptions {
+            relative_threshold: 1e-3,
+            absolute_threshold: 1e-13,
+            ordering: Ordering::Auto,
+        }
+    }
+}
+
+/// The elimination broke down: no acceptable pivot was left for `column`.
+/// `row` is the first row that was not yet eliminated, which for an MNA
+/// matrix usually names the same floating node or loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SingularMatrix {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Display for SingularMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Singular matrix at row {}, column {}",
+            self.row, self.column
+        )
+    }
+}
+
+impl From<SingularMatrix> for Error {
+    fn from(singular: SingularMatrix) -> Self {
+        Error::new(ErrorType::Runtime, singular.to_string(), 0, 0)
+    }
+}
+
+/// The pivot order and the structure of the factors, in pivot order.
+///
+/// Row `k` of the factors holds L to the left of the diagonal and U from the
+/// diagonal on; `columns[k]` lists the positions of its entries.
+#[derive(Debug, PartialEq)]
+pub struct Symbolic {
+    structure: Arc<Structure>,
+    ordering: Ordering,
+    row_order: Vec<usize>,
+    column_order: Vec<usize>,
+    columns: Vec<Vec<usize>>,
+    diagonal: Vec<usize>,
+    /// The matrix entries of each row of the factors, as factor position
+    /// and value index.
+    scatter: Vec<Vec<(usize, usize)>>,
+}
+
+impl Symbolic {
+    pub fn size(&self) -> usize {
+        self.row_order.len()
+    }
+
+    /// The ordering that chose the pivots, never [`Ordering::Auto`].
+    pub fn ordering(&self) -> Ordering {
+        self.ordering
+    }
+
+    /// Original row and column eliminated at each step.
+    pub fn pivots(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.row_order
+            .iter()
+            .copied()
+            .zip(self.column_order.iter().copied())
+    }
+
+    /// Number of entries in L and U together.
+    pub fn factor_nnz(&self) -> usize {
+        self.columns.iter().map(Vec::len).sum()
+    }
+
+    /// Entries created by the elimination that are not in the matrix.
+    pub fn fill_in(&self) -> usize {
+        self.factor_nnz() - self.structure.row.len()
+    }
+}
+
+/// Sparse LU factorisation `P A Q = L U` with a fill-reducing ordering and
+/// threshold pivoting.
+///
+/// The first factorisation chooses the pivots and records the fill-in.
+/// Later calls to [`Lu::factor`] reuse that order and only redo the
+/// arithmetic, falling back to a new ordering when a pivot has become too
+/// small for the thresholds.
+#[derive(Debug, Clone)]
+pub struct Lu<T: Scalar = f64> {
+    options: LuOptions,
+    symbolic: Option<Arc<Symbolic>>,
+    values: Vec<Vec<T>>,
+    orderings: usize,
+}
+
+impl<T: Scalar> Default for Lu<T> {
+    fn default() -> Self {
+        Lu::new(LuOptions::default())
+    }
+}
+
+impl<T: Scalar> Lu<T> {
+    pub fn new(options: LuOptions) -> Self {
+        Lu {
+            options,
+            symbolic: None,
+            values: Vec::new(),
+            orderings: 0,
+        }
+    }
+
+    /// Start from an ordering found for another matrix with the same
+    /// structure, for instance the real Jacobian of the operating point.
+    pub fn with_symbolic(options: LuOptions, symbolic: Arc<Symbolic>) -> Self {
+        Lu {
+            symbolic: Some(symbolic),
+            ..Lu::new(options)
+        }
+    }
+
+    pub fn symbolic(&self) -> Option<&Arc<Symbolic>> {
+        self.symbolic.as_ref()
+    }
+
+    /// How many times a pivot order had to be chosen.
+    pub fn orderings(&self) -> usize {
+        self.orderings
+    }
+
+    /// Factorise `matrix`, reusing the previous pivot order when it is
+    /// still numerically acceptable.
+    pub fn factor(&mut self, matrix: &SparseMatrix<T>) -> Result<(), SingularMatrix> {
+        if self.symbolic.is_some() && self.refactor(matrix).is_ok() {
+            return Ok(());
+        }
+        self.analyze(matrix)
+    }
+
+    /// Choose a new pivot order for `matrix` and factorise it.
+    pub fn analyze(&mut self, matrix: &SparseMatrix<T>) -> Result<(), SingularMatrix> {
+        self.symbolic = None;
+        self.orderings += 1;
+        let n = matrix.size();
+        let LuOptions {
+            relative_threshold,
+            absolute_threshold,
+            ordering,
+        } = self.options;
+        let ordering = match ordering {
+            Ordering::Auto => Ordering::choose(&Statistics::of(matrix)),
+            ordering => ordering,
+        };
+        let sequence = ordering::sequence(&matrix.structure, ordering);
+
+        // The active submatrix by rows with values, and by columns.
+        let mut rows: Vec<BTreeMap<usize, T>> = vec![BTreeMap::new(); n];
+        let mut columns: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+        for (row, column, value) in matrix.entries() {
+            rows[row].insert(column, value);
+            columns[column].insert(row);
+        }
+        let mut row_done = vec![false; n];
+        let mut column_done = vec![false; n];
+        let mut row_order = Vec::with_capacity(n);
+        let mut column_order = Vec::with_capacity(n);
+        // (original row, step, value) of L and (step, original column,
+        // value) of U.
+        let mut lower: Vec<(usize, usize, T)> = Vec::new();
+        let mut upper: Vec<(usize, usize, T)> = Vec::new();
+
+        for step in 0..n {
+            if let Some(&(preferred, column)) = sequence.get(step) {
+                // The column is fixed: take the preferred row if it passes
+                // the thresholds, otherwise the shortest row that does.
+                let largest = columns[column]
+                    .iter()
+                    .map(|r| rows[*r][&column].magnitude())
+                    .fold(0.0, f64::max);
+                let acceptable = |row: usize| {
+                    let magnitude = rows[row][&column].magnitude();
+                    magnitude > absolute_threshold && magnitude >= relative_threshold * largest
+                };
+                let pivot_row = if columns[column].contains(&preferred) && acceptable(preferred) {
+                    Some(preferred)
+                } else {
+                    columns[column]
+                        .iter()
+                        .copied()
+                        .filter(|r| acceptable(*r))
+                        .min_by(|a, b| {
+                            rows[*a].len().cmp(&rows[*b].len()).then(
+                                rows[*b][&column]
+                                    .magnitude()
+                                    .total_cmp(&rows[*a][&column].magnitude()),
+                            )
+                        })
+                };
+                let Some(pivot_row) = pivot_row else {
+                    let row = (0..n).find(|r| !row_done[*r]).unwrap_or(step);
+                    return Err(SingularMatrix { row, column });
+                };
+                Self::eliminate(
+                    pivot_row,
+                    column,
+                    step,
+                    &mut rows,
+                    &mut columns,
+                    &mut lower,
+                    &mut upper,
+                );
+                row_done[pivot_row] = true;
+                row_order.push(pivot_row);
+                column_order.push(column);
+                continue;
+            }
+
+            // Smallest Markowitz product (r - 1)(c - 1) among entries that
+            // pass the threshold test, preferring larger relative size.
+            let mut best: Option<(usize, f64, usize, usize)> = None;
+            'search: for column in (0..n).filter(|c| !column_done[*c]) {
+                let largest = columns[column]
+                    .iter()
+                    .map(|r| rows[*r][&column].magnitude())
+                    .fold(0.0, f64::max);
+                if largest <= absolute_threshold {
+                    continue;
+                }
+                let column_count = columns[column].len() - 1;
+                for &row in &columns[column] {
+                    let magnitude = rows[row][&column].magnitude();
+                    if magnitude <= absolute_threshold || magnitude < relative_threshold * largest {
+                        continue;
+                    }
+                    let markowitz = (rows[row].len() - 1) * column_count;
+                    let ratio = magnitude / largest;
+                    let better = match best {
+                        None => true,
+                        Some((m, r, _, _)) => markowitz < m || (markowitz == m && ratio > r),
+                    };
+                    if better {
+                        best = Some((markowitz, ratio, row, column));
+                        if markowitz == 0 && ratio == 1.0 {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+            let Some((_, _, pivot_row, pivot_column)) = best else {
+                let column = (0..n).find(|c| !column_done[*c]).unwrap_or(step);
+                let row = (0..n).find(|r| !row_done[*r]).unwrap_or(step);
+                return Err(SingularMatrix { row, column });
+            };
+
+            row_done[pivot_row] = true;
+            column_done[pivot_column] = true;
+            row_order.push(pivot_row);
+            column_order.push(pivot_column);
+            Self::eliminate(
+                pivot_row,
+                pivot_column,
+                step,
+                &mut rows,
+                &mut columns,
+                &mut lower,
+                &mut upper,
+            );
+        }
+
+        let mut row_step = vec![0; n];
+        let mut column_step = vec![0; n];
+        for step in 0..n {
+            row_step[row_order[step]] = step;
+            column_step[column_order[step]] = step;
+        }
+        let mut factor_rows: Vec<Vec<(usize, T)>> = vec![Vec::new(); n];
+        for (row, step, value) in lower {
+            factor_rows[row_step[row]].push((step, value));
+        }
+        for (step, column, value) in upper {
+            factor_rows[step].push((column_step[column], value));
+        }
+        for row in &mut factor_rows {
+            row.sort_by_key(|(column, _)| *column);
+        }
+        let mut scatter: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n];
+        let s = &matrix.structure;
+        for (column, step) in column_step.iter().enumerate() {
+            for k in s.column_start[column]..s.column_start[column + 1] {
+                scatter[row_step[s.row[k]]].push((*step, s.handle[k]));
+            }
+        }
+
+        self.symbolic = Some(Arc::new(Symbolic {
+            structure: matrix.structure.clone(),
+            ordering,
+            row_order,
+            column_order,
+            diagonal: factor_rows
+                .iter()
+                .enumerate()
+                .map(|(k, row)| row.iter().position(|(c, _)| *c == k).unwrap())
+                .collect(),
+            columns: factor_rows
+                .iter()
+                .map(|row| row.iter().map(|(c, _)| *c).collect())
+                .collect(),
+            scatter,
+        }));
+        self.values = factor_rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|(_, v)| v).collect())
+            .collect();
+        Ok(())
+    }
+
+    /// One elimination step of [`Lu::analyze`] on the active submatrix,
+    /// recording the pivot row in U and the multipliers in L.
+    fn eliminate(
+        pivot_row: usize,
+        pivot_column: usize,
+        step: usize,
+        rows: &mut [BTreeMap<usize, T>],
+        columns: &mut [BTreeSet<usize>],
+        lower: &mut Vec<(usize, usize, T)>,
+        upper: &mut Vec<(usize, usize, T)>,
+    ) {
+        let pivot = rows[pivot_row][&pivot_column];
+        let pivot_entries: Vec<(usize, T)> =
+            rows[pivot_row].iter().map(|(c, v)| (*c, *v)).collect();
+        for &(column, value) in &pivot_entries {
+            upper.push((step, column, value));
+            columns[column].remove(&pivot_row);
+        }
+        let eliminated: Vec<usize> = columns[pivot_column].iter().copied().collect();
+        for row in eliminated {
+            let factor = rows[row][&pivot_column] / pivot;
+            lower.push((row, step, factor));
+            rows[row].remove(&pivot_column);
+            for &(column, value) in &pivot_entries {
+                if column == pivot_column {
+                    continue;
+                }
+                // Fill-in stays in the structure even if it cancels.
+                let entry = rows[row].entry(column).or_insert_with(|| {
+                    columns[column].insert(row);
+                    T::zero()
+                });
+                *entry -= factor * value;
+            }
+        }
+        columns[pivot_column].clear();
+        rows[pivot_row].clear();
+    }
+
+    /// Factorise `matrix` with the existing pivot order. Fails when there is
+    /// no order yet, the structure differs, or a pivot no longer passes
+    /// the thresholds.
+    pub fn refactor(&mut self, matrix: &SparseMatrix<T>) -> Result<(), SingularMatrix> {
+        let Some(symbolic) = self.symbolic.clone() else {
+            return Err(SingularMatrix { row: 0, column: 0 });
+        };
+        if !Arc::ptr_eq(&symbolic.structure, &matrix.structure)
+            && *symbolic.structure != *matrix.structure
+        {
+            return Err(SingularMatrix { row: 0, column: 0 });
+        }
+        let n = symbolic.size();
+        self.values.resize_with(n, Vec::new);
+        for (values, columns) in self.values.iter_mut().zip(&symbolic.columns) {
+            values.resize(columns.len(), T::zero());
+        }
+        let largest_factor = 1.0 / self.options.relative_threshold;
+
+        // Row by row (IKJ) elimination into a dense work row.
+        let mut work = vec![T::zero(); n];
+        for i in 0..n {
+            for &(column, handle) in &symbolic.scatter[i] {
+                work[column] += matrix.values[handle];
+            }
+            let columns = &symbolic.columns[i];
+            for &k in &columns[..symbolic.diagonal[i]] {
+                let factor = work[k] / self.values[k][symbolic.diagonal[k]];
+                // The column threshold test of the ordering, seen from L.
+                if factor.magnitude() > largest_factor {
+                    self.clear_work(&mut work, i);
+                    return Err(self.singular_at(i));
+                }
+                work[k] = factor;
+                let upper = symbolic.diagonal[k] + 1..symbolic.columns[k].len();
+                for p in upper {
+                    let j = symbolic.columns[k][p];
+                    work[j] -= factor * self.values[k][p];
+                }
+            }
+            for (p, &column) in columns.iter().enumerate() {
+                self.values[i][p] = work[column];
+                work[column] = T::zero();
+            }
+            if self.values[i][symbolic.diagonal[i]].magnitude() <= self.options.absolute_threshold {
+                return Err(self.singular_at(i));
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_work(&self, work: &mut [T], i: usize) {
+        if let Some(symbolic) = &self.symbolic {
+            for &column in &symbolic.columns[i] {
+                work[column] = T::zero();
+            }
+        }
+    }
+
+    fn singular_at(&self, step: usize) -> SingularMatrix {
+        let symbolic = self.symbolic.as_ref().unwrap();
+        SingularMatrix {
+            row: symbolic.row_order[step],
+            column: symbolic.column_order[step],
+        }
+    }
+
+    /// Solve `A x = b` in place: `rhs` holds `b` on entry and `x` on
+    /// return.
+    pub fn solve(&self, rhs: &mut [T]) {
+        let symbolic = self.symbolic.as_ref().expect("solve before factor");
+        let n = symbolic.size();
+        let mut x: Vec<T> = symbolic.row_order.iter().map(|&r| rhs[r]).collect();
+        for i in 0..n {
+            let columns = &symbolic.columns[i];
+            for p in 0..symbolic.diagonal[i] {
+                let value = x[columns[p]];
+                x[i] -= self.values[i][p] * value;
+            }
+        }
+        for i in (0..n).rev() {
+            let columns = &symbolic.columns[i];
+            let diagonal = symbolic.diagonal[i];
+            for p in diagonal + 1..columns.len() {
+                let value = x[columns[p]];
+                x[i] -= self.values[i][p] * value;
+            }
+            x[i] = x[i] / self.values[i][diagonal];
+        }
+        for (k, &column) in symbolic.column_order.iter().enumerate() {
+            rhs[column] = x[k];
+        }
+    }
+
+    /// Solve `Aᵀ x = b` in place with the same factors, as needed by adjoint
+    /// noise and sensitivity analysis. The transpose is not conjugated.
+    pub fn solve_transpose(&self, rhs: &mut [T]) {
+        let symbolic = self.symbolic.as_ref().expect("solve before factor");
+        let n = symbolic.size();
+        let mut x: Vec<T> = symbolic.column_order.iter().map(|&c| rhs[c]).collect();
+        // Uᵀ is lower triangular: the U part of factor row `i` is column `i`
+        // of Uᵀ, so each solved unknown is pushed forward along it.
+        for i in 0..n {
+            let columns = &symbolic.columns[i];
+            let diagonal = symbolic.diagonal[i];
+            x[i] = x[i] / self.values[i][diagonal];
+            let value = x[i];
+            for p in diagonal + 1..columns.len() {
+                x[columns[p]] -= self.values[i][p] * value;
+            }
+        }
+        // Lᵀ is unit upper triangular, handled the same way backwards.
+        for i in (0..n).rev() {
+            let columns = &symbolic.columns[i];
+            let value = x[i];
+            for p in 0..symbolic.diagonal[i] {
+                x[columns[p]] -= self.values[i][p] * value;
+            }
+        }
+        for (k, &row) in symbolic.row_order.iter().enumerate() {
+            rhs[row] = x[k];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::complex::Complex;
+    use crate::maths::sparse::Pattern;
+
+    fn build(size: usize, entries: &[(usize, usize, f64)]) -> (Pattern, SparseMatrix) {
+        let mut pattern = Pattern::new(size);
+        let handles: Vec<_> = entries
+            .iter()
+            .map(|(r, c, _)| pattern.handle(*r, *c))
+            .collect();
+        let mut matrix = pattern.matrix();
+        for (handle, (_, _, value)) in handles.iter().zip(entries) {
+            matrix.add(*handle, *value);
+        }
+        (pattern, matrix)
+    }
+
+    fn residual(matrix: &SparseMatrix, x: &[f64], b: &[f64]) -> f64 {
+        matrix
+            .multiply(x)
+            .iter()
+            .zip(b)
+            .map(|(ax, b)| (ax - b).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_voltage_divider() {
+        // Nodes 1 and 2 with R1 = 1k between them, R2 = 2k to ground and a
+        // 3 V source on node 1 with its branch current as unknown 2. The
+        // zero on the diagonal of the branch row needs pivoting.
+        let g1 = 1e-3;
+        let g2 = 0.5e-3;
+        let (_, matrix) = build(
+            3,
+            &[
+                (0, 0, g1),
+                (0, 1, -g1),
+                (1, 0, -g1),
+                (1, 1, g1 + g2),
+                (0, 2, 1.0),
+                (2, 0, 1.0),
+            ],
+        );
+        let mut lu = Lu::default();
+        lu.factor(&matrix).unwrap();
+        let mut x = vec![0.0, 0.0, 3.0];
+        lu.solve(&mut x);
+        assert!((x[0] - 3.0).abs() < 1e-12);
+        assert!((x[1] - 2.0).abs() < 1e-12);
+        assert!((x[2] + 1e-3).abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_reuse_symbolic() {
+        let (pattern, mut matrix) = build(
+            3,
+            &[
+                (0, 0, 4.0),
+                (0, 1, 1.0),
+                (1, 0, 1.0),
+                (1, 1, 3.0),
+                (1, 2, 1.0),
+                (2, 1, 1.0),
+                (2, 2, 2.0),
+            ],
+        );
+        let mut lu = Lu::default();
+        lu.factor(&matrix).unwrap();
+        let fill = lu.symbolic().unwrap().fill_in();
+
+        // New values, same structure: only a numeric refactorisation.
+        for step in 1..5 {
+            let scale = step as f64;
+            for (k, (r, c, _)) in matrix.clone().entries().enumerate() {
+                let handle = pattern.find(r, c).unwrap();
+                matrix.set(
+                    handle,
+                    if r == c {
+                        10.0 * scale
+                    } else {
+                        scale + k as f64
+                    },
+                );
+            }
+            lu.factor(&matrix).unwrap();
+            let b = [1.0, 2.0, 3.0];
+            let mut x = b.to_vec();
+            lu.solve(&mut x);
+            assert!(residual(&matrix, &x, &b) < 1e-12);
+        }
+        assert_eq!(lu.orderings(), 1);
+        assert_eq!(lu.symbolic().unwrap().fill_in(), fill);
+
+        // A pivot that collapses forces a new ordering.
+        let pivot = lu.symbolic().unwrap().pivots().next().unwrap();
+        matrix.set(pattern.find(pivot.0, pivot.1).unwrap(), 0.0);
+        lu.factor(&matrix).unwrap();
+        assert_eq!(lu.orderings(), 2);
+        let b = [1.0, -1.0, 0.5];
+        let mut x = b.to_vec();
+        lu.solve(&mut x);
+        assert!(residual(&matrix, &x, &b) < 1e-12);
+    }
+
+    #[test]
+    fn test_singular() {
+        // Node 2 floats: its row and column are empty.
+        let (_, matrix) = build(3, &[(0, 0, 1.0), (0, 1, -1.0), (1, 0, -1.0), (1, 1, 2.0)]);
+        let mut lu = Lu::default();
+        assert_eq!(
+            lu.factor(&matrix),
+            Err(SingularMatrix { row: 2, column: 2 })
+        );
+    }
+
+    #[test]
+    fn test_random() {
+        // A diagonally weighted random sparse matrix from a fixed LCG.
+        let mut seed = 12345u64;
+        let mut random = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as f64 / (1u64 << 31) as f64
+        };
+        let n = 60;
+        let mut entries = Vec::new();
+        for i in 0..n {
+            entries.push((i, i, 1.0 + random()));
+            for _ in 0..3 {
+                let j = (random() * n as f64) as usize % n;
+                if j != i {
+                    entries.push((i, j, random() - 0.5));
+                }
+            }
+        }
+        let (_, matrix) = build(n, &entries);
+        let transpose: Vec<_> = entries.iter().map(|&(r, c, v)| (c, r, v)).collect();
+        let (_, transpose) = build(n, &transpose);
+        let b: Vec<f64> = (0..n).map(|i| i as f64).collect();
+        for ordering in [
+            Ordering::Auto,
+            Ordering::Markowitz,
+            Ordering::Natural,
+            Ordering::Amd,
+            Ordering::Colamd,
+            Ordering::Btf,
+        ] {
+            let mut lu = Lu::new(LuOptions {
+                ordering,
+                ..LuOptions::default()
+            });
+            lu.factor(&matrix).unwrap();
+            let mut x = b.clone();
+            lu.solve(&mut x);
+            assert!(residual(&matrix, &x, &b) < 1e-10, "{:?}", ordering);
+
+            let mut x = b.clone();
+            lu.solve_transpose(&mut x);
+            assert!(residual(&transpose, &x, &b) < 1e-10, "{:?}", ordering);
+        }
+    }
+
+    #[test]
+    fn test_zero_diagonal() {
+        // The divider again, through the fixed orderings: the branch row
+        // of the source has no diagonal, so the transversal has to move
+        // its pivot off the diagonal.
+        let g1 = 1e-3;
+        let g2 = 0.5e-3;
+        let (_, matrix) = build(
+            3,
+            &[
+                (0, 0, g1),
+                (0, 1, -g1),
+                (1, 0, -g1),
+                (1, 1, g1 + g2),
+                (0, 2, 1.0),
+                (2, 0, 1.0),
+            ],
+        );
+        for ordering in [
+            Ordering::Natural,
+            Ordering::Amd,
+            Ordering::Colamd,
+            Ordering::Btf,
+        ] {
+            let mut lu = Lu::new(LuOptions {
+                ordering,
+                ..LuOptions::default()
+            });
+            lu.factor(&matrix).unwrap();
+            assert_eq!(lu.symbolic().unwrap().ordering(), ordering);
+            let mut x = vec![0.0, 0.0, 3.0];
+            lu.solve(&mut x);
+            assert!((x[1] - 2.0).abs() < 1e-12, "{:?}", ordering);
+        }
+    }
+
+    #[test]
+    fn test_complex_sweep() {
+        // RC ladder driven by a 1 V source: R between nodes, C to ground at
+        // each node. The real pattern is ordered once at DC and the complex
+        // matrix reuses the ordering at every frequency.
+        let n = 6;
+        let mut pattern = Pattern::new(n + 1);
+        let mut stamps = Vec::new();
+        for i in 0..n {
+            stamps.push((pattern.handle(i, i), i));
+            if i + 1 < n {
+                pattern.handle(i, i + 1);
+                pattern.handle(i + 1, i);
+            }
+        }
+        let source = (pattern.handle(0, n), pattern.handle(n, 0));
+        let g = 1e-3;
+        let c = 1e-9;
+        let stamp = |matrix: &mut SparseMatrix<Complex>, pattern: &Pattern, omega: f64| {
+            matrix.clear();
+            for i in 0..n - 1 {
+                for (r, s) in [(i, i), (i + 1, i + 1)] {
+                    matrix.add(pattern.find(r, s).unwrap(), Complex::from(g));
+                }
+                for (r, s) in [(i, i + 1), (i + 1, i)] {
+                    matrix.add(pattern.find(r, s).unwrap(), Complex::from(-g));
+                }
+            }
+            for &(handle, _) in &stamps {
+                matrix.add(handle, Complex::new(0.0, omega * c));
+            }
+            matrix.set(source.0, Complex::from(1.0));
+            matrix.set(source.1, Complex::from(1.0));
+        };
+
+        let mut real: SparseMatrix = pattern.matrix();
+        for (r, s, _) in real.clone().entries() {
+            let value = match (r, s) {
+                _ if r == n || s == n => 1.0,
+                _ if r == s => 2.0 * g,
+                _ => -g,
+            };
+            real.set(pattern.find(r, s).unwrap(), value);
+        }
+        let mut dc = Lu::default();
+        dc.factor(&real).unwrap();
+
+        let mut matrix: SparseMatrix<Complex> = pattern.matrix();
+        assert!(matrix.same_structure(&real));
+        let mut lu = Lu::with_symbolic(LuOptions::default(), dc.symbolic().unwrap().clone());
+        for decade in 0..8 {
+            let omega = 2.0 * std::f64::consts::PI * 10f64.powi(decade);
+            stamp(&mut matrix, &pattern, omega);
+            lu.factor(&matrix).unwrap();
+            let mut b = vec![Complex::zero(); n + 1];
+            b[n] = Complex::from(1.0);
+            let mut x = b.clone();
+            lu.solve(&mut x);
+            let error = matrix
+                .multiply(&x)
+                .iter()
+                .zip(&b)
+                .map(|(ax, b)| (*ax - *b).norm())
+                .fold(0.0, f64::max);
+            assert!(error < 1e-12);
+            assert!((x[0] - Complex::from(1.0)).norm() < 1e-12);
+
+            // Adjoint: Aᵀ y = e_k gives row k of the inverse, so y · b
+            // reproduces x[k].
+            let k = n - 1;
+            let mut y = vec![Complex::zero(); n + 1];
+            y[k] = Complex::from(1.0);
+            lu.solve_transpose(&mut y);
+            let dot = y
+                .iter()
+                .zip(&b)
+                .fold(Complex::zero(), |sum, (y, b)| sum + *y * *b);
+            assert!((dot - x[k]).norm() < 1e-12);
+        }
+        assert_eq!(lu.orderings(), 0);
+    }
+}
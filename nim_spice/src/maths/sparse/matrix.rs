@@ -0,0 +1,216 @@
+use crate::maths::sparse::Scalar;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A structural entry of a matrix, resolved once at setup so that stamping
+/// is a single indexed add.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle(pub(crate) usize);
+
+/// The set of structural entries of a square matrix, built up while the
+/// devices of a circuit are set up.
+#[derive(Debug, Clone, Default)]
+pub struct Pattern {
+    size: usize,
+    entries: Vec<(usize, usize)>,
+    lookup: HashMap<(usize, usize), Handle>,
+    structure: Option<Arc<Structure>>,
+}
+
+/// Compressed sparse column layout of a pattern. Values are stored in
+/// handle order, so each column lists its rows and the handles to read.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Structure {
+    pub(crate) size: usize,
+    pub(crate) column_start: Vec<usize>,
+    pub(crate) row: Vec<usize>,
+    pub(crate) handle: Vec<usize>,
+}
+
+impl Pattern {
+    pub fn new(size: usize) -> Self {
+        Pattern {
+            size,
+            ..Pattern::default()
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of structural entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The handle of entry `(row, column)`, adding it to the pattern the
+    /// first time it is asked for.
+    pub fn handle(&mut self, row: usize, column: usize) -> Handle {
+        assert!(
+            row < self.size && column < self.size,
+            "entry ({}, {}) is outside a {}x{} matrix",
+            row,
+            column,
+            self.size,
+            self.size
+        );
+        if let Some(handle) = self.lookup.get(&(row, column)) {
+            return *handle;
+        }
+        let handle = Handle(self.entries.len());
+        self.entries.push((row, column));
+        self.lookup.insert((row, column), handle);
+        self.structure = None;
+        handle
+    }
+
+    /// The handle of an entry already in the pattern.
+    pub fn find(&self, row: usize, column: usize) -> Option<Handle> {
+        self.lookup.get(&(row, column)).copied()
+    }
+
+    /// A zero matrix with this pattern. Matrices made from the same pattern,
+    /// real or complex, share one copy of the structure.
+    pub fn matrix<T: Scalar>(&mut self) -> SparseMatrix<T> {
+        let structure = self
+            .structure
+            .get_or_insert_with(|| Arc::new(compress(self.size, &self.entries)))
+            .clone();
+        SparseMatrix {
+            values: vec![T::zero(); self.entries.len()],
+            structure,
+        }
+    }
+}
+
+fn compress(size: usize, entries: &[(usize, usize)]) -> Structure {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|&h| (entries[h].1, entries[h].0));
+    let mut column_start = vec![0; size + 1];
+    for &(_, column) in entries {
+        column_start[column + 1] += 1;
+    }
+    for c in 0..size {
+        column_start[c + 1] += column_start[c];
+    }
+    Structure {
+        size,
+        column_start,
+        row: order.iter().map(|&h| entries[h].0).collect(),
+        handle: order,
+    }
+}
+
+/// A square sparse matrix with a fixed structure.
+#[derive(Debug, Clone)]
+pub struct SparseMatrix<T: Scalar = f64> {
+    pub(crate) structure: Arc<Structure>,
+    pub(crate) values: Vec<T>,
+}
+
+impl<T: Scalar> SparseMatrix<T> {
+    pub fn size(&self) -> usize {
+        self.structure.size
+    }
+
+    /// Number of structural entries.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Reset every entry to zero, keeping the structure, before the next
+    /// round of stamping.
+    pub fn clear(&mut self) {
+        self.values.fill(T::zero());
+    }
+
+    pub fn add(&mut self, handle: Handle, value: T) {
+        self.values[handle.0] += value;
+    }
+
+    pub fn set(&mut self, handle: Handle, value: T) {
+        self.values[handle.0] = value;
+    }
+
+    pub fn value(&self, handle: Handle) -> T {
+        self.values[handle.0]
+    }
+
+    /// The entry at `(row, column)`, zero outside the structure.
+    pub fn get(&self, row: usize, column: usize) -> T {
+        let s = &self.structure;
+        (s.column_start[column]..s.column_start[column + 1])
+            .find(|&k| s.row[k] == row)
+            .map_or(T::zero(), |k| self.values[s.handle[k]])
+    }
+
+    /// `(row, column, value)` for every structural entry, column by column.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, usize, T)> + '_ {
+        let s = &self.structure;
+        (0..s.size).flat_map(move |c| {
+            (s.column_start[c]..s.column_start[c + 1])
+                .map(move |k| (s.row[k], c, self.values[s.handle[k]]))
+        })
+    }
+
+    /// The product `A x`.
+    pub fn multiply(&self, x: &[T]) -> Vec<T> {
+        let mut y = vec![T::zero(); self.size()];
+        for (row, column, value) in self.entries() {
+            y[row] += value * x[column];
+        }
+        y
+    }
+
+    /// Whether both matrices were made from the same pattern.
+    pub fn same_structure<U: Scalar>(&self, other: &SparseMatrix<U>) -> bool {
+        Arc::ptr_eq(&self.structure, &other.structure) || self.structure == other.structure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamping() {
+        let mut pattern = Pattern::new(3);
+        let a = pattern.handle(0, 0);
+        let b = pattern.handle(2, 1);
+        let c = pattern.handle(1, 2);
+        assert_eq!(pattern.handle(0, 0), a);
+        assert_eq!(pattern.find(1, 2), Some(c));
+        assert_eq!(pattern.find(2, 2), None);
+        assert_eq!(pattern.len(), 3);
+
+        let mut matrix: SparseMatrix = pattern.matrix();
+        matrix.add(a, 1.0);
+        matrix.add(a, 2.0);
+        matrix.add(b, -1.0);
+        matrix.set(c, 4.0);
+        assert_eq!(matrix.get(0, 0), 3.0);
+        assert_eq!(matrix.get(2, 1), -1.0);
+        assert_eq!(matrix.get(1, 1), 0.0);
+        assert_eq!(matrix.multiply(&[1.0, 2.0, 3.0]), [3.0, 12.0, -2.0]);
+        assert_eq!(
+            matrix.entries().collect::<Vec<_>>(),
+            [(0, 0, 3.0), (2, 1, -1.0), (1, 2, 4.0)]
+        );
+
+        let other: SparseMatrix = pattern.matrix();
+        assert!(matrix.same_structure(&other));
+        matrix.clear();
+        assert_eq!(matrix.value(a), 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_out_of_range() {
+        Pattern::new(2).handle(2, 0);
+    }
+}
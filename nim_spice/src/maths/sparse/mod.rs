@@ -0,0 +1,50 @@
+//! Sparse linear algebra for modified nodal analysis.
+//!
+//! The structure of an MNA matrix is fixed by the circuit topology, while its
+//! values change at every Newton iteration, time step or frequency point.
+//! The types here keep the two apart: a [`Pattern`] collects the structural
+//! entries once at setup and hands out [`Handle`]s that devices keep for
+//! stamping, and an [`Lu`] keeps the pivot order and fill-in of its first
+//! factorisation so that later factorisations are purely numeric. Large
+//! matrices are ordered up front by one of the [`Ordering`]s, picked from
+//! the [`Statistics`] of the pattern.
+
+mod lu;
+mod matrix;
+mod ordering;
+
+pub use lu::{Lu, LuOptions, SingularMatrix, Symbolic};
+pub use matrix::{Handle, Pattern, SparseMatrix};
+pub use ordering::{Ordering, Statistics};
+
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Element type of a sparse matrix.
+pub trait Scalar:
+    Copy
+    + Debug
+    + PartialEq
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+{
+    fn zero() -> Self;
+
+    /// Absolute value, used for pivot selection.
+    fn magnitude(self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn magnitude(self) -> f64 {
+        self.abs()
+    }
+}
@@ -0,0 +1,546 @@
+use crate::maths::sparse::matrix::{SparseMatrix, Structure};
+use crate::maths::sparse::Scalar;
+use std::collections::BTreeSet;
+
+/// Below this size a full Markowitz search is cheap and gives the least
+/// fill-in, so [`Ordering::Auto`] does not bother with the others.
+const MARKOWITZ_SIZE: usize = 100;
+
+/// Share of structurally symmetric off-diagonal entries above which AMD on
+/// `A + Aᵀ` predicts the fill of `L U` well.
+const SYMMETRIC: f64 = 0.5;
+
+/// How [`Lu::analyze`](crate::maths::sparse::Lu::analyze) chooses its pivot
+/// order.
+///
+/// Every ordering but Markowitz fixes the column order up front and then
+/// only picks the pivot row within each column, preferring the row the
+/// ordering had in mind when it passes the pivoting thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ordering {
+    /// Pick one of the others from the [`Statistics`] of the matrix.
+    #[default]
+    Auto,
+    /// Smallest Markowitz product over the whole active submatrix at every
+    /// step. Least fill-in, but quadratic in the size of the matrix.
+    Markowitz,
+    /// Columns in their original order.
+    Natural,
+    /// Approximate minimum degree on the pattern of `A + Aᵀ`, after a
+    /// maximum transversal has moved nonzeros onto the diagonal.
+    Amd,
+    /// Column approximate minimum degree, which orders for the pattern of
+    /// `AᵀA` without forming it.
+    Colamd,
+    /// Block triangular form, with AMD inside each diagonal block. Fill-in
+    /// never crosses from one block into another.
+    Btf,
+}
+
+impl Ordering {
+    /// The ordering [`Ordering::Auto`] stands for on a matrix with these
+    /// statistics.
+    pub fn choose(statistics: &Statistics) -> Ordering {
+        if statistics.size <= MARKOWITZ_SIZE {
+            Ordering::Markowitz
+        } else if statistics.blocks > 1 && 2 * statistics.largest_block <= statistics.size {
+            Ordering::Btf
+        } else if statistics.symmetry >= SYMMETRIC {
+            Ordering::Amd
+        } else {
+            Ordering::Colamd
+        }
+    }
+}
+
+/// Structural statistics of a matrix, as used to choose an ordering.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub size: usize,
+    pub nnz: usize,
+    /// Fraction of off-diagonal entries whose transpose is an entry too.
+    pub symmetry: f64,
+    /// Structurally zero diagonal entries, such as the branch rows of
+    /// voltage sources and inductors.
+    pub zero_diagonal: usize,
+    /// Number of diagonal blocks in the block triangular form.
+    pub blocks: usize,
+    pub largest_block: usize,
+}
+
+impl Statistics {
+    pub fn of<T: Scalar>(matrix: &SparseMatrix<T>) -> Self {
+        let s = &matrix.structure;
+        let rows = rows(s);
+        let mut off_diagonal = 0;
+        let mut symmetric = 0;
+        let mut zero_diagonal = 0;
+        for (row, columns) in rows.iter().enumerate() {
+            if columns.binary_search(&row).is_err() {
+                zero_diagonal += 1;
+            }
+            for &column in columns.iter().filter(|c| **c != row) {
+                off_diagonal += 1;
+                if rows[column].binary_search(&row).is_ok() {
+                    symmetric += 1;
+                }
+            }
+        }
+        let blocks = blocks(s, &rows, &transversal(s));
+        Statistics {
+            size: s.size,
+            nnz: s.row.len(),
+            symmetry: if off_diagonal == 0 {
+                1.0
+            } else {
+                symmetric as f64 / off_diagonal as f64
+            },
+            zero_diagonal,
+            blocks: blocks.len(),
+            largest_block: blocks.iter().map(Vec::len).max().unwrap_or(0),
+        }
+    }
+}
+
+/// The pivot sequence of a fixed ordering, as `(preferred row, column)` per
+/// elimination step. Markowitz and Auto have none.
+pub(crate) fn sequence(s: &Structure, ordering: Ordering) -> Vec<(usize, usize)> {
+    let n = s.size;
+    match ordering {
+        Ordering::Auto | Ordering::Markowitz => Vec::new(),
+        Ordering::Natural => (0..n).map(|c| (c, c)).collect(),
+        Ordering::Amd => {
+            let matched = transversal(s);
+            let nodes: Vec<usize> = (0..n).collect();
+            let graph = symmetric_graph(s, &rows(s), &matched);
+            amd(&nodes, &graph)
+                .into_iter()
+                .map(|row| (row, matched.column_of[row]))
+                .collect()
+        }
+        Ordering::Colamd => {
+            let matched = transversal(s);
+            colamd(s)
+                .into_iter()
+                .map(|column| (matched.row_of[column], column))
+                .collect()
+        }
+        Ordering::Btf => {
+            let matched = transversal(s);
+            let rows = rows(s);
+            let graph = symmetric_graph(s, &rows, &matched);
+            blocks(s, &rows, &matched)
+                .iter()
+                .flat_map(|block| amd(block, &graph))
+                .map(|row| (row, matched.column_of[row]))
+                .collect()
+        }
+    }
+}
+
+/// Column indices of each row, sorted.
+fn rows(s: &Structure) -> Vec<Vec<usize>> {
+    let mut rows = vec![Vec::new(); s.size];
+    for column in 0..s.size {
+        for k in s.column_start[column]..s.column_start[column + 1] {
+            rows[s.row[k]].push(column);
+        }
+    }
+    rows
+}
+
+/// A perfect matching of rows and columns through structural entries.
+/// When the matrix is structurally singular the unmatched rows and columns
+/// are paired up arbitrarily, leaving the numeric factorisation to report
+/// it.
+struct Transversal {
+    row_of: Vec<usize>,
+    column_of: Vec<usize>,
+}
+
+/// Maximum transversal by depth-first augmenting paths (Duff's MC21),
+/// trying a cheap direct assignment before each search.
+fn transversal(s: &Structure) -> Transversal {
+    let n = s.size;
+    let mut row_of: Vec<Option<usize>> = vec![None; n];
+    let mut column_of: Vec<Option<usize>> = vec![None; n];
+    let mut visited = vec![usize::MAX; n];
+    let entries = |column: usize| s.column_start[column]..s.column_start[column + 1];
+    for start in 0..n {
+        if let Some(row) = entries(start)
+            .map(|k| s.row[k])
+            .find(|r| column_of[*r].is_none())
+        {
+            row_of[start] = Some(row);
+            column_of[row] = Some(start);
+            continue;
+        }
+        // Each frame is a column and the next of its entries to try; the
+        // row that led into frame `d + 1` is `path[d]`.
+        let mut stack = vec![(start, s.column_start[start])];
+        let mut path: Vec<usize> = Vec::new();
+        while let Some(&(column, next)) = stack.last() {
+            if next == s.column_start[column + 1] {
+                stack.pop();
+                path.pop();
+                continue;
+            }
+            stack.last_mut().unwrap().1 += 1;
+            let row = s.row[next];
+            if visited[row] == start {
+                continue;
+            }
+            visited[row] = start;
+            path.push(row);
+            match column_of[row] {
+                Some(other) => stack.push((other, s.column_start[other])),
+                None => {
+                    for (&(column, _), &row) in stack.iter().zip(&path) {
+                        row_of[column] = Some(row);
+                        column_of[row] = Some(column);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    let free: Vec<usize> = (0..n).filter(|r| column_of[*r].is_none()).collect();
+    let mut free_rows = free.into_iter();
+    for (column, matched) in row_of.iter_mut().enumerate() {
+        if matched.is_none() {
+            let row = free_rows.next().unwrap();
+            *matched = Some(row);
+            column_of[row] = Some(column);
+        }
+    }
+    Transversal {
+        row_of: row_of.into_iter().map(Option::unwrap).collect(),
+        column_of: column_of.into_iter().map(Option::unwrap).collect(),
+    }
+}
+
+/// Diagonal blocks of the block upper triangular form, in elimination
+/// order, each listing its rows. Row `i` depends on row `j` when it has an
+/// entry in the column matched to `j`, and the blocks are the strongly
+/// connected components of that graph (Tarjan).
+fn blocks(s: &Structure, rows: &[Vec<usize>], matched: &Transversal) -> Vec<Vec<usize>> {
+    let n = s.size;
+    let successors: Vec<Vec<usize>> = rows
+        .iter()
+        .map(|columns| columns.iter().map(|c| matched.row_of[*c]).collect())
+        .collect();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut counter = 0;
+    let mut blocks = Vec::new();
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut calls = vec![(root, 0)];
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some(&(node, edge)) = calls.last() {
+            if let Some(&next) = successors[node].get(edge) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[next] == usize::MAX {
+                    index[next] = counter;
+                    low[next] = counter;
+                    counter += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    calls.push((next, 0));
+                } else if on_stack[next] {
+                    low[node] = low[node].min(index[next]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent] = low[parent].min(low[node]);
+            }
+            if low[node] == index[node] {
+                let mut block = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    block.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                block.sort_unstable();
+                blocks.push(block);
+            }
+        }
+    }
+    // Tarjan finishes a component after everything it reaches, and the
+    // rows a block depends on must come after it.
+    blocks.reverse();
+    blocks
+}
+
+/// Neighbours of each row in the pattern of `B + Bᵀ`, where `B` is the
+/// matrix with the matched entries on its diagonal.
+fn symmetric_graph(s: &Structure, rows: &[Vec<usize>], matched: &Transversal) -> Vec<Vec<usize>> {
+    let mut graph = vec![Vec::new(); s.size];
+    for (row, columns) in rows.iter().enumerate() {
+        for &column in columns {
+            let other = matched.row_of[column];
+            if other != row {
+                graph[row].push(other);
+                graph[other].push(row);
+            }
+        }
+    }
+    for neighbours in &mut graph {
+        neighbours.sort_unstable();
+        neighbours.dedup();
+    }
+    graph
+}
+
+/// AMD order of `nodes` on the subgraph of `graph` they induce.
+fn amd(nodes: &[usize], graph: &[Vec<usize>]) -> Vec<usize> {
+    let mut local = vec![usize::MAX; graph.len()];
+    for (i, &node) in nodes.iter().enumerate() {
+        local[node] = i;
+    }
+    let adjacency = nodes
+        .iter()
+        .map(|&node| {
+            graph[node]
+                .iter()
+                .map(|n| local[*n])
+                .filter(|n| *n != usize::MAX)
+                .collect()
+        })
+        .collect();
+    minimum_degree(nodes.len(), adjacency, Vec::new())
+        .into_iter()
+        .map(|i| nodes[i])
+        .collect()
+}
+
+/// COLAMD order of the columns: each row of the matrix starts out as an
+/// element over its columns, so `AᵀA` is never formed. Dense rows would
+/// tie every column together and are left out, as COLAMD does.
+fn colamd(s: &Structure) -> Vec<usize> {
+    let dense = 16.max((10.0 * (s.size as f64).sqrt()) as usize);
+    let elements = rows(s)
+        .into_iter()
+        .filter(|columns| columns.len() <= dense)
+        .collect();
+    minimum_degree(s.size, vec![Vec::new(); s.size], elements)
+}
+
+/// Approximate minimum degree elimination on a quotient graph.
+///
+/// Variables start out with their `adjacency` (symmetric, no self loops)
+/// and the `elements` they belong to, each element standing for a clique.
+/// Eliminating a variable merges it and its elements into a new element,
+/// and the degree of each variable it touched is bounded as in Amestoy,
+/// Davis and Duff's AMD, with aggressive absorption but no supervariables.
+fn minimum_degree(
+    n: usize,
+    mut adjacency: Vec<Vec<usize>>,
+    elements: Vec<Vec<usize>>,
+) -> Vec<usize> {
+    let mut variables = elements;
+    let mut alive = vec![true; variables.len()];
+    let mut elements_of: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (element, members) in variables.iter().enumerate() {
+        for &variable in members {
+            elements_of[variable].push(element);
+        }
+    }
+    let mut degree: Vec<usize> = (0..n)
+        .map(|i| {
+            let external: usize = elements_of[i].iter().map(|e| variables[*e].len() - 1).sum();
+            (adjacency[i].len() + external).min(n.saturating_sub(1))
+        })
+        .collect();
+    let mut queue: BTreeSet<(usize, usize)> = (0..n).map(|i| (degree[i], i)).collect();
+    let mut eliminated = vec![false; n];
+    let mut mark = vec![usize::MAX; n];
+    let mut weight_mark: Vec<usize> = vec![usize::MAX; variables.len()];
+    let mut weight: Vec<usize> = vec![0; variables.len()];
+    let mut order = Vec::with_capacity(n);
+
+    for step in 0..n {
+        let (_, pivot) = queue.pop_first().unwrap();
+        order.push(pivot);
+        eliminated[pivot] = true;
+        mark[pivot] = step;
+
+        // The new element: everything the pivot was connected to.
+        let mut members = Vec::new();
+        for &j in &adjacency[pivot] {
+            if !eliminated[j] && mark[j] != step {
+                mark[j] = step;
+                members.push(j);
+            }
+        }
+        for e in std::mem::take(&mut elements_of[pivot]) {
+            if !alive[e] {
+                continue;
+            }
+            alive[e] = false;
+            for j in std::mem::take(&mut variables[e]) {
+                if !eliminated[j] && mark[j] != step {
+                    mark[j] = step;
+                    members.push(j);
+                }
+            }
+        }
+        adjacency[pivot] = Vec::new();
+        let element = variables.len();
+        alive.push(true);
+        weight.push(0);
+        weight_mark.push(usize::MAX);
+
+        for &i in &members {
+            elements_of[i].retain(|e| alive[*e]);
+            elements_of[i].push(element);
+            // Edges inside the new element are implied by it.
+            adjacency[i].retain(|j| !eliminated[*j] && mark[*j] != step);
+        }
+
+        // |Le \ Lp| for every other element next to the new one.
+        for &i in &members {
+            for &e in &elements_of[i] {
+                if e == element {
+                    continue;
+                }
+                if weight_mark[e] != step {
+                    weight_mark[e] = step;
+                    weight[e] = variables[e].len();
+                }
+                weight[e] -= 1;
+            }
+        }
+
+        let remaining = n - step - 1;
+        for &i in &members {
+            let mut bound = members.len() - 1 + adjacency[i].len();
+            for &e in &elements_of[i] {
+                if e == element {
+                    continue;
+                }
+                if weight[e] == 0 {
+                    // Everything in it is in the new element too.
+                    alive[e] = false;
+                } else {
+                    bound += weight[e];
+                }
+            }
+            let bound = bound.min(remaining.saturating_sub(1));
+            queue.remove(&(degree[i], i));
+            degree[i] = bound;
+            queue.insert((bound, i));
+        }
+        variables.push(members);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::sparse::Pattern;
+
+    fn structure(size: usize, entries: &[(usize, usize)]) -> SparseMatrix {
+        let mut pattern = Pattern::new(size);
+        for &(r, c) in entries {
+            pattern.handle(r, c);
+        }
+        pattern.matrix()
+    }
+
+    #[test]
+    fn test_transversal() {
+        // A voltage source between node 0 and ground: the branch row 2 has
+        // a zero diagonal, and so does node 0 once its conductance is gone.
+        let matrix = structure(3, &[(0, 2), (2, 0), (1, 1), (0, 1), (1, 0)]);
+        let matched = transversal(&matrix.structure);
+        for row in 0..3 {
+            let column = matched.column_of[row];
+            assert_eq!(matched.row_of[column], row);
+            assert!(matrix.structure.row
+                [matrix.structure.column_start[column]..matrix.structure.column_start[column + 1]]
+                .contains(&row));
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        // Two coupled pairs {0, 1} and {2, 3}, with 1 feeding into 2.
+        let matrix = structure(
+            4,
+            &[
+                (0, 0),
+                (0, 1),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (2, 2),
+                (2, 3),
+                (3, 2),
+                (3, 3),
+            ],
+        );
+        let s = &matrix.structure;
+        let blocks = blocks(s, &rows(s), &transversal(s));
+        assert_eq!(blocks, [vec![0, 1], vec![2, 3]]);
+
+        let statistics = Statistics::of(&matrix);
+        assert_eq!(statistics.blocks, 2);
+        assert_eq!(statistics.largest_block, 2);
+        assert_eq!(statistics.zero_diagonal, 0);
+        assert!((statistics.symmetry - 4.0 / 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_amd_arrow() {
+        // Eliminating the hub of a star first fills in everything; AMD
+        // keeps it until only one leaf is left. COLAMD sees the hub row
+        // tie all columns together and has no preference here.
+        let n = 8;
+        let mut entries: Vec<_> = (0..n).map(|i| (i, i)).collect();
+        for i in 1..n {
+            entries.push((0, i));
+            entries.push((i, 0));
+        }
+        let matrix = structure(n, &entries);
+        for ordering in [Ordering::Amd, Ordering::Btf] {
+            let order = sequence(&matrix.structure, ordering);
+            let hub = order.iter().position(|(_, c)| *c == 0).unwrap();
+            assert!(hub >= n - 2, "{:?}", ordering);
+        }
+    }
+
+    #[test]
+    fn test_choose() {
+        let mut statistics = Statistics {
+            size: 50,
+            nnz: 200,
+            symmetry: 0.9,
+            zero_diagonal: 2,
+            blocks: 1,
+            largest_block: 50,
+        };
+        assert_eq!(Ordering::choose(&statistics), Ordering::Markowitz);
+        statistics.size = 5000;
+        statistics.largest_block = 5000;
+        assert_eq!(Ordering::choose(&statistics), Ordering::Amd);
+        statistics.symmetry = 0.2;
+        assert_eq!(Ordering::choose(&statistics), Ordering::Colamd);
+        statistics.blocks = 40;
+        statistics.largest_block = 300;
+        assert_eq!(Ordering::choose(&statistics), Ordering::Btf);
+    }
+}
@@ -0,0 +1,83 @@
+/// Parse a SPICE number such as `4.7k`, `10uF`, `1meg` or `-2.5e-3`.
+///
+/// Scale suffixes are case insensitive: T, G, MEG, K, M (milli), MIL, U, N,
+/// P and F. Letters after the suffix are a unit name and are ignored, as in
+/// LTspice. The `6K34` form is not supported.
+pub fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let bytes = text.as_bytes();
+    let mut end = 0;
+    if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+        end += 1;
+    }
+    let digits_start = end;
+    while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+        end += 1;
+    }
+    if end == digits_start || (end == digits_start + 1 && bytes[digits_start] == b'.') {
+        return None;
+    }
+    // An exponent is only taken if digits follow, so `1e` stays a unit.
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exponent = end + 1;
+        if exponent < bytes.len() && (bytes[exponent] == b'+' || bytes[exponent] == b'-') {
+            exponent += 1;
+        }
+        if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+            while exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+                exponent += 1;
+            }
+            end = exponent;
+        }
+    }
+    let value: f64 = text[..end].parse().ok()?;
+
+    let suffix = text[end..].to_ascii_lowercase();
+    if !suffix.chars().all(char::is_alphabetic) {
+        return None;
+    }
+    if suffix.starts_with("mil") {
+        return Some(value * 25.4e-6);
+    }
+    let exponent = if suffix.starts_with("meg") {
+        6
+    } else {
+        match suffix.chars().next() {
+            Some('t') => 12,
+            Some('g') => 9,
+            Some('k') => 3,
+            Some('m') => -3,
+            Some('u') | Some('µ') => -6,
+            Some('n') => -9,
+            Some('p') => -12,
+            Some('f') => -15,
+            _ => 0,
+        }
+    };
+    // Dividing by an exact power of ten keeps `10u` equal to `10e-6`.
+    if exponent < 0 {
+        Some(value / 10f64.powi(-exponent))
+    } else {
+        Some(value * 10f64.powi(exponent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("4.7k"), Some(4700.0));
+        assert_eq!(parse_number("10uF"), Some(10e-6));
+        assert_eq!(parse_number("1MEG"), Some(1e6));
+        assert_eq!(parse_number("2m"), Some(2e-3));
+        assert_eq!(parse_number("-2.5e-3"), Some(-2.5e-3));
+        assert_eq!(parse_number("1e3k"), Some(1e6));
+        assert_eq!(parse_number("5V"), Some(5.0));
+        assert_eq!(parse_number(".5"), Some(0.5));
+        assert_eq!(parse_number("abc"), None);
+        assert_eq!(parse_number("1.2.3"), None);
+        assert_eq!(parse_number("3k,"), None);
+    }
+}
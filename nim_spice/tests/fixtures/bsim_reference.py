@@ -0,0 +1,1094 @@
+#!/usr/bin/env python3
+"""Reference I-V and C-V curves of the BSIM3 and BSIM4 cores.
+
+A plain floating point transcription of the Berkeley reference code,
+BSIM3v3.3's b3temp.c and b3ld.c and BSIM4's b4temp.c and b4ld.c, for an
+n channel with vds >= 0: the channel current, the substrate current and the
+intrinsic gate, drain and bulk charges of each CAPMOD and XPART. It keeps
+the reference code's control flow and physical constants and shares no code
+with nim_spice, so the fixtures it writes check the Rust cores' equations,
+defaults and unit handling against the reference's.
+
+Run from this directory to rewrite the fixtures:
+
+    python3 bsim_reference.py
+"""
+
+import math
+
+EXP_THRESHOLD = 34.0
+MIN_EXP = 1.713908431e-15
+MAX_EXP = 5.834617425e14
+KBOQ = 8.617087e-5
+CHARGE_Q = 1.60219e-19
+EPS0 = 8.85418e-12
+EPSOX = 3.453133e-11
+EPSSI = 1.03594e-10
+DELTA_3 = 0.02
+DELTA_4 = 0.02
+KELVIN = 273.15
+
+
+def parse_card(card):
+    """The parameters of a `.model NAME NMOS(A=1 B=2)` card, lower case."""
+    body = card[card.index("(") + 1 : card.rindex(")")]
+    params = {}
+    for word in body.split():
+        name, value = word.split("=")
+        params[name.lower()] = float(value)
+    return params
+
+
+def sizes(p, l, w):
+    """Leff, Weff, LeffCV and WeffCV without the size terms of LL, WL..."""
+    lint = p.get("lint", 0.0)
+    wint = p.get("wint", 0.0)
+    dlc = p.get("dlc", lint)
+    dwc = p.get("dwc", wint)
+    return l - 2 * lint, w - 2 * wint, l - 2 * dlc, w - 2 * dwc
+
+
+def bsim3_temp(p, l, w, temp, tnom):
+    """The size and temperature dependent parameters of b3temp.c."""
+    q = dict(p)
+    leff, weff, leffcv, weffcv = sizes(p, l, w)
+    q.update(leff=leff, weff=weff, leffcv=leffcv, weffcv=weffcv)
+    tox = p.get("tox", 1.5e-8)
+    q["tox"] = tox
+    q["toxm"] = p.get("toxm", tox)
+    q["cox"] = EPSOX / tox
+    tratio = temp / tnom
+    q["tratio"] = tratio
+    vtm0 = KBOQ * tnom
+    q["vtm"] = KBOQ * temp
+    eg0 = 1.16 - 7.02e-4 * tnom * tnom / (tnom + 1108.0)
+    ni = (
+        1.45e10
+        * (tnom / 300.15)
+        * math.sqrt(tnom / 300.15)
+        * math.exp(21.5565981 - eg0 / (2.0 * vtm0))
+    )
+    npeak = p["nch"]
+    q["npeak"] = npeak
+    phi = 2.0 * vtm0 * math.log(npeak / ni)
+    q["phi"] = phi
+    q["sqrtphi"] = math.sqrt(phi)
+    q["phis3"] = q["sqrtphi"] * phi
+    q["xdep0"] = math.sqrt(2.0 * EPSSI / (CHARGE_Q * npeak * 1.0e6)) * q["sqrtphi"]
+    q["litl"] = math.sqrt(3.0 * p["xj"] * tox)
+    q["vbi"] = vtm0 * math.log(1.0e20 * npeak / (ni * ni))
+    q["cdep0"] = math.sqrt(CHARGE_Q * EPSSI * npeak * 1.0e6 / 2.0 / phi)
+    q["ldeb"] = math.sqrt(EPSSI * vtm0 / (CHARGE_Q * npeak * 1.0e6)) / 3.0
+
+    k1, k2 = p["k1"], p["k2"]
+    vbm = -abs(p.get("vbm", -3.0))
+    if k2 < 0.0:
+        t0 = 0.5 * k1 / k2
+        vbsc = 0.9 * (phi - t0 * t0)
+        if vbsc > -3.0:
+            vbsc = -3.0
+        elif vbsc < -30.0:
+            vbsc = -30.0
+    else:
+        vbsc = -30.0
+    if vbsc > vbm:
+        vbsc = vbm
+    q["vbsc"] = vbsc
+    vth0 = p["vth0"]
+    q["vfb"] = vth0 - phi - k1 * q["sqrtphi"]
+    q["k1ox"] = k1 * tox / q["toxm"]
+    q["k2ox"] = k2 * tox / q["toxm"]
+
+    q["factor1"] = math.sqrt(EPSSI / EPSOX * tox)
+    t0 = math.sqrt(EPSSI / EPSOX * tox * q["xdep0"])
+    drout = p.get("drout", 0.56)
+    t1 = math.exp(-0.5 * p.get("dsub", drout) * leff / t0)
+    q["theta0vb0"] = t1 * (1.0 + 2.0 * t1)
+    t1 = math.exp(-0.5 * drout * leff / t0)
+    t2 = t1 * (1.0 + 2.0 * t1)
+    q["thetarout"] = p.get("pdiblc1", 0.39) * t2 + p.get("pdiblc2", 0.0086)
+
+    tmp = math.sqrt(q["xdep0"])
+    tmp1 = q["vbi"] - phi
+    tmp2 = q["factor1"] * tmp
+    t0 = -0.5 * p.get("dvt1w", 5.3e6) * weff * leff / tmp2
+    t1 = math.exp(t0) if t0 > -EXP_THRESHOLD else MIN_EXP
+    t2 = t1 * (1.0 + 2.0 * t1)
+    t2 = p.get("dvt0w", 0.0) * t2 * tmp1
+    t0 = -0.5 * p.get("dvt1", 0.53) * leff / tmp2
+    t1 = math.exp(t0) if t0 > -EXP_THRESHOLD else MIN_EXP
+    t3 = t1 * (1.0 + 2.0 * t1)
+    t3 = p.get("dvt0", 2.2) * t3 * tmp1
+    t4 = tox * phi / (weff + p.get("w0", 2.5e-6))
+    t0 = math.sqrt(1.0 + p.get("nlx", 1.74e-7) / leff)
+    t5 = q["k1ox"] * (t0 - 1.0) * q["sqrtphi"] + (
+        p.get("kt1", -0.11) + p.get("kt1l", 0.0) / leff
+    ) * (tratio - 1.0)
+    tmp3 = vth0 - t2 - t3 + p.get("k3", 80.0) * t4 + t5
+    q["vfbzb"] = tmp3 - phi - k1 * q["sqrtphi"]
+
+    dt = tratio - 1.0
+    q["ua"] = p.get("ua", 2.25e-9) + p.get("ua1", 4.31e-9) * dt
+    q["ub"] = p.get("ub", 5.87e-19) + p.get("ub1", -7.61e-18) * dt
+    q["uc"] = p.get("uc", -4.65e-11) + p.get("uc1", -5.6e-11) * dt
+    u0 = p.get("u0", 670.0)
+    if u0 > 1.0:
+        u0 = u0 / 1.0e4
+    q["u0temp"] = u0 * math.pow(tratio, p.get("ute", -1.5))
+    q["vsattemp"] = p.get("vsat", 8.0e4) - p.get("at", 3.3e4) * dt
+    q["rds0"] = (p.get("rdsw", 0.0) + p.get("prt", 0.0) * dt) / math.pow(
+        weff * 1e6, p.get("wr", 1.0)
+    )
+    q["abulkcvfactor"] = 1.0 + math.pow(p.get("clc", 0.1e-6) / leffcv, p.get("cle", 0.6))
+    q["coxwl"] = q["cox"] * weffcv * leffcv
+    return q
+
+
+def bsim3_load(q, vgs, vds, vbs, capmod):
+    """Ids, Isub and (qgate, qdrain, qbulk) of b3ld.c for vds >= 0."""
+    g = lambda name, default: q.get(name, default)
+    leff, weff = q["leff"], q["weff"]
+    tox, cox, vtm = q["tox"], q["cox"], q["vtm"]
+    phi, sqrtphi = q["phi"], q["sqrtphi"]
+
+    t0 = vbs - q["vbsc"] - 0.001
+    t1 = math.sqrt(t0 * t0 - 0.004 * q["vbsc"])
+    vbseff = q["vbsc"] + 0.5 * (t0 + t1)
+    if vbseff < vbs:
+        vbseff = vbs
+    if vbseff > 0.0:
+        t0 = phi / (phi + vbseff)
+        phis = phi * t0
+        sqrtphis = q["phis3"] / (phi + 0.5 * vbseff)
+    else:
+        phis = phi - vbseff
+        sqrtphis = math.sqrt(phis)
+    xdep = q["xdep0"] * sqrtphis / sqrtphi
+
+    v0 = q["vbi"] - phi
+    t3 = math.sqrt(xdep)
+    t0 = g("dvt2", -0.032) * vbseff
+    t1 = 1.0 + t0 if t0 >= -0.5 else (1.0 + 3.0 * t0) / (3.0 + 8.0 * t0)
+    lt1 = q["factor1"] * t3 * t1
+    t0 = g("dvt2w", -0.032) * vbseff
+    t1 = 1.0 + t0 if t0 >= -0.5 else (1.0 + 3.0 * t0) / (3.0 + 8.0 * t0)
+    ltw = q["factor1"] * t3 * t1
+    t0 = -0.5 * g("dvt1", 0.53) * leff / lt1
+    t1 = math.exp(t0) if t0 > -EXP_THRESHOLD else MIN_EXP
+    theta0 = t1 * (1.0 + 2.0 * t1)
+    delt_vth = g("dvt0", 2.2) * theta0 * v0
+    t0 = -0.5 * g("dvt1w", 5.3e6) * weff * leff / ltw
+    t1 = math.exp(t0) if t0 > -EXP_THRESHOLD else MIN_EXP
+    t2 = g("dvt0w", 0.0) * t1 * (1.0 + 2.0 * t1) * v0
+    temp_ratio = q["tratio"] - 1.0
+    t0 = math.sqrt(1.0 + g("nlx", 1.74e-7) / leff)
+    t1 = q["k1ox"] * (t0 - 1.0) * sqrtphi + (
+        g("kt1", -0.11) + g("kt1l", 0.0) / leff + g("kt2", 0.022) * vbseff
+    ) * temp_ratio
+    tmp2 = tox * phi / (weff + g("w0", 2.5e-6))
+    t3 = g("eta0", 0.08) + g("etab", -0.07) * vbseff
+    if t3 < 1.0e-4:
+        t3 = (2.0e-4 - t3) / (3.0 - 2.0e4 * t3)
+    dibl_sft = t3 * q["theta0vb0"] * vds
+    vth = (
+        q["vth0"]
+        - q["k1"] * sqrtphi
+        + q["k1ox"] * sqrtphis
+        - q["k2ox"] * vbseff
+        - delt_vth
+        - t2
+        + (g("k3", 80.0) + g("k3b", 0.0) * vbseff) * tmp2
+        + t1
+        - dibl_sft
+    )
+
+    tmp2 = g("nfactor", 1.0) * EPSSI / xdep
+    tmp3 = g("cdsc", 2.4e-4) + g("cdscb", 0.0) * vbseff + g("cdscd", 0.0) * vds
+    tmp4 = (tmp2 + tmp3 * theta0 + g("cit", 0.0)) / cox
+    n = 1.0 + tmp4 if tmp4 >= -0.5 else (1.0 + 3.0 * tmp4) / (3.0 + 8.0 * tmp4)
+
+    ngate = g("ngate", 0.0)
+    if 1.0e18 < ngate < 1.0e25 and vgs > q["vfb"] + phi:
+        t1 = 1.0e6 * CHARGE_Q * EPSSI * ngate / (cox * cox)
+        t4 = math.sqrt(1.0 + 2.0 * (vgs - q["vfb"] - phi) / t1)
+        t2 = t1 * (t4 - 1.0)
+        t3 = 0.5 * t2 * t2 / t1
+        t7 = 1.12 - t3 - 0.05
+        t6 = math.sqrt(t7 * t7 + 0.224)
+        t5 = 1.12 - 0.5 * (t7 + t6)
+        vgs_eff = vgs - t5
+    else:
+        vgs_eff = vgs
+
+    voff = g("voff", -0.08)
+    vgst = vgs_eff - vth
+    t10 = 2.0 * n * vtm
+    vgstnvt = vgst / t10
+    exparg = (2.0 * voff - vgst) / t10
+    if vgstnvt > EXP_THRESHOLD:
+        vgsteff = vgst
+    elif exparg > EXP_THRESHOLD:
+        t0 = (vgst - voff) / (n * vtm)
+        vgsteff = vtm * q["cdep0"] / cox * math.exp(t0)
+    else:
+        t1 = t10 * math.log(1.0 + math.exp(vgstnvt))
+        t2 = 1.0 + t10 * cox / (vtm * q["cdep0"]) * math.exp(exparg)
+        vgsteff = t1 / t2
+
+    t9 = sqrtphis - sqrtphi
+    weff_b = weff - 2.0 * (g("dwg", 0.0) * vgsteff + g("dwb", 0.0) * t9)
+    if weff_b < 2.0e-8:
+        weff_b = 2.0e-8 * (4.0e-8 - weff_b) / (6.0e-8 - 2.0 * weff_b)
+    t0 = g("prwg", 0.0) * vgsteff + g("prwb", 0.0) * t9
+    if t0 >= -0.9:
+        rds = q["rds0"] * (1.0 + t0)
+    else:
+        rds = q["rds0"] * (0.8 + t0) / (17.0 + 20.0 * t0)
+
+    t1 = 0.5 * q["k1ox"] / sqrtphis
+    t9 = math.sqrt(g("xj", 1.5e-7) * xdep)
+    t5 = leff / (leff + 2.0 * t9)
+    t2 = g("a0", 1.0) * t5 + g("b0", 0.0) / (weff + g("b1", 0.0))
+    abulk0 = 1.0 + t1 * t2
+    t8 = g("ags", 0.0) * g("a0", 1.0) * t5 * t5 * t5
+    abulk = abulk0 - t1 * t8 * vgsteff
+    if abulk0 < 0.1:
+        abulk0 = (0.2 - abulk0) / (3.0 - 20.0 * abulk0)
+    if abulk < 0.1:
+        abulk = (0.2 - abulk) / (3.0 - 20.0 * abulk)
+    t2 = g("keta", -0.047) * vbseff
+    t0 = 1.0 / (1.0 + t2) if t2 >= -0.9 else (17.0 + 20.0 * t2) / (0.8 + t2)
+    abulk *= t0
+    abulk0 *= t0
+
+    t0 = vgsteff + vth + vth
+    t2 = q["ua"] + q["uc"] * vbseff
+    t3 = t0 / tox
+    t5 = t3 * (t2 + q["ub"] * t3)
+    denomi = 1.0 + t5 if t5 >= -0.8 else (0.6 + t5) / (7.0 + 10.0 * t5)
+    ueff = q["u0temp"] / denomi
+
+    vsat = q["vsattemp"]
+    wvcox = weff_b * vsat * cox
+    wvcoxrds = wvcox * rds
+    esat = 2.0 * vsat / ueff
+    esatl = esat * leff
+    a1, a2 = g("a1", 0.0), g("a2", 1.0)
+    if a1 == 0.0:
+        lam = a2
+    elif a1 > 0.0:
+        t0 = 1.0 - a2
+        t1 = t0 - a1 * vgsteff - 0.0001
+        t2 = math.sqrt(t1 * t1 + 0.0004 * t0)
+        lam = a2 + t0 - 0.5 * (t1 + t2)
+    else:
+        t1 = a2 + a1 * vgsteff - 0.0001
+        t2 = math.sqrt(t1 * t1 + 0.0004 * a2)
+        lam = 0.5 * (t1 + t2)
+    vgst2vtm = vgsteff + 2.0 * vtm
+    if rds == 0.0 and lam == 1.0:
+        vdsat = esatl * vgst2vtm / (abulk * esatl + vgst2vtm)
+    else:
+        t9 = abulk * wvcoxrds
+        t7 = vgst2vtm * t9
+        t6 = vgst2vtm * wvcoxrds
+        t0 = 2.0 * abulk * (t9 - 1.0 + 1.0 / lam)
+        t1 = vgst2vtm * (2.0 / lam - 1.0) + abulk * esatl + 3.0 * t7
+        t2 = vgst2vtm * (esatl + 2.0 * t6)
+        t3 = math.sqrt(t1 * t1 - 2.0 * t0 * t2)
+        vdsat = (t1 - t3) / t0
+    delta = g("delta", 0.01)
+    t1 = vdsat - vds - delta
+    t2 = math.sqrt(t1 * t1 + 4.0 * delta * vdsat)
+    vdseff = vdsat - 0.5 * (t1 + t2)
+    if vds == 0.0:
+        vdseff = 0.0
+    vdseff = min(vdseff, vds)
+    diffvds = vds - vdseff
+
+    tmp4 = 1.0 - 0.5 * abulk * vdsat / vgst2vtm
+    t0 = esatl + vdsat + 2.0 * wvcoxrds * vgsteff * tmp4
+    t1 = 2.0 / lam - 1.0 + wvcoxrds * abulk
+    vasat = t0 / t1
+    pclm = g("pclm", 1.3)
+    if pclm > 0.0 and diffvds > 1.0e-10:
+        t0 = 1.0 / (pclm * abulk * q["litl"])
+        vaclm = t0 * leff * (abulk + vgsteff / esatl) * diffvds
+    else:
+        vaclm = MAX_EXP
+    if q["thetarout"] > 0.0:
+        t8 = abulk * vdsat
+        vadibl = (vgst2vtm - vgst2vtm * t8 / (vgst2vtm + t8)) / q["thetarout"]
+        t7 = g("pdiblcb", 0.0) * vbseff
+        if t7 >= -0.9:
+            vadibl /= 1.0 + t7
+        else:
+            vadibl *= (17.0 + 20.0 * t7) / (0.8 + t7)
+    else:
+        vadibl = MAX_EXP
+    t8 = g("pvag", 0.0) / esatl * vgsteff
+    t0 = 1.0 + t8 if t8 > -0.9 else (0.8 + t8) / (17.0 + 20.0 * t8)
+    va = vasat + t0 * vaclm * vadibl / (vaclm + vadibl)
+    pscbe1, pscbe2 = g("pscbe1", 4.24e8), g("pscbe2", 1.0e-5)
+    if pscbe2 > 0.0:
+        if diffvds > pscbe1 * q["litl"] / EXP_THRESHOLD:
+            vascbe = leff * math.exp(pscbe1 * q["litl"] / diffvds) / pscbe2
+        else:
+            vascbe = MAX_EXP * leff / pscbe2
+    else:
+        vascbe = MAX_EXP
+
+    beta = ueff * cox * weff_b / leff
+    fgche1 = vgsteff * (1.0 - 0.5 * vdseff * abulk / vgst2vtm)
+    fgche2 = 1.0 + vdseff / esatl
+    gche = beta * fgche1 / fgche2
+    idl = gche * vdseff / (1.0 + gche * rds)
+    idsa = idl * (1.0 + diffvds / va)
+    ids = idsa * (1.0 + diffvds / vascbe)
+
+    tmp = g("alpha0", 0.0) + g("alpha1", 0.0) * leff
+    beta0 = g("beta0", 30.0)
+    if tmp <= 0.0 or beta0 <= 0.0:
+        isub = 0.0
+    elif diffvds > beta0 / EXP_THRESHOLD:
+        isub = tmp / leff * diffvds * math.exp(-beta0 / diffvds) * idsa
+    else:
+        isub = tmp / leff * MIN_EXP * diffvds * idsa
+
+    bias = dict(
+        vgs_eff=vgs_eff,
+        vbs=vbs,
+        vbseff=vbseff,
+        phis=phis,
+        sqrtphis=sqrtphis,
+        vth=vth,
+        n=n,
+        vds=vds,
+        abulk0=abulk0,
+    )
+    charges = CAPMODS3[capmod](q, bias)
+    return ids, isub, charges
+
+
+def vgsteff_cv(q, b):
+    """The gate drive of the charges with NOFF and VOFFCV."""
+    noff = b["n"] * q.get("noff", 1.0)
+    t0 = q["vtm"] * noff
+    vgst = b["vgs_eff"] - b["vth"] - q.get("voffcv", 0.0)
+    vgstnvt = vgst / t0
+    if vgstnvt > EXP_THRESHOLD:
+        return vgst
+    if vgstnvt < -EXP_THRESHOLD:
+        return t0 * math.log(1.0 + MIN_EXP)
+    return t0 * math.log(1.0 + math.exp(vgstnvt))
+
+
+def vbseff_cv(q, b):
+    if b["vbseff"] < 0.0:
+        return b["vbseff"]
+    return q["phi"] - b["phis"]
+
+
+def bsim3_capmod0(q, b):
+    coxwl = q["coxwl"]
+    phi = q["phi"]
+    vds = b["vds"]
+    vbseff = b["vbs"] if b["vbseff"] < 0.0 else phi - b["phis"]
+    vfb = q.get("vfbcv", -1.0)
+    vth = vfb + phi + q["k1ox"] * b["sqrtphis"]
+    vgst = b["vgs_eff"] - vth
+    arg1 = b["vgs_eff"] - vbseff - vfb
+    xpart = q.get("xpart", 0.0)
+    if arg1 <= 0.0:
+        qgate = coxwl * arg1
+        return qgate, 0.0, -qgate
+    if vgst <= 0.0:
+        t1 = 0.5 * q["k1ox"]
+        t2 = math.sqrt(t1 * t1 + arg1)
+        qgate = coxwl * q["k1ox"] * (t2 - t1)
+        return qgate, 0.0, -qgate
+    abulkcv = b["abulk0"] * q["abulkcvfactor"]
+    vdsat = vgst / abulkcv
+    if vds >= vdsat:
+        qgate = coxwl * (b["vgs_eff"] - vfb - phi - vdsat / 3.0)
+        t2 = -2.0 / 3.0 * coxwl * vgst
+        qbulk = -(qgate + t2)
+        if xpart > 0.5:
+            qdrn = 0.0
+        elif xpart < 0.5:
+            qdrn = 0.4 * t2
+        else:
+            qdrn = 0.5 * t2
+        return qgate, qdrn, qbulk
+    alphaz = vgst / vdsat
+    t1 = 2.0 * vdsat - vds
+    t2 = vds / (3.0 * t1)
+    t3 = t2 * vds
+    t4 = 0.25 * coxwl * alphaz
+    qgate = coxwl * (b["vgs_eff"] - vfb - phi - 0.5 * (vds - t3))
+    if xpart > 0.5:
+        t7 = 2.0 * vds - t1 - 3.0 * t3
+        t8 = t3 - t1 - 2.0 * vds
+        qdrn = t4 * t7
+        qbulk = -(qgate + qdrn + t4 * t8)
+    elif xpart < 0.5:
+        t6 = 8.0 * vdsat * vdsat - 6.0 * vdsat * vds + 1.2 * vds * vds
+        t8 = t2 / t1
+        qdrn = t4 * (vds - t1 - t8 * t6)
+        qbulk = -(qgate - t4 * 2.0 * (t1 + t3))
+    else:
+        qdrn = -t4 * (t1 + t3)
+        qbulk = -(qgate + 2.0 * qdrn)
+    return qgate, qdrn, qbulk
+
+
+def bsim3_capmod1(q, b):
+    coxwl = q["coxwl"]
+    vds = b["vds"]
+    xpart = q.get("xpart", 0.0)
+    vgsteff = vgsteff_cv(q, b)
+    arg1 = b["vgs_eff"] - vbseff_cv(q, b) - q["vfbzb"] - vgsteff
+    if arg1 <= 0.0:
+        qgate = coxwl * arg1
+    else:
+        t0 = 0.5 * q["k1ox"]
+        t1 = math.sqrt(t0 * t0 + arg1)
+        qgate = coxwl * q["k1ox"] * (t1 - t0)
+    qbulk = -qgate
+    abulkcv = b["abulk0"] * q["abulkcvfactor"]
+    vdsatcv = vgsteff / abulkcv
+    if vdsatcv < vds:
+        qgate += coxwl * (vgsteff - vdsatcv / 3.0)
+        qbulk += coxwl / 3.0 * (vdsatcv - vgsteff)
+        if xpart > 0.5:
+            t0 = -2.0 / 3.0 * coxwl
+        elif xpart < 0.5:
+            t0 = -0.4 * coxwl
+        else:
+            t0 = -coxwl / 3.0
+        qsrc = t0 * vgsteff
+    else:
+        t0 = abulkcv * vds
+        t1 = 12.0 * (vgsteff - 0.5 * t0 + 1.0e-20)
+        t2 = vds / t1
+        t3 = t0 * t2
+        qgate += coxwl * (vgsteff - 0.5 * vds + t3)
+        qbulk += coxwl * (1.0 - abulkcv) * (0.5 * vds - t3)
+        if xpart > 0.5:
+            qsrc = -coxwl * (0.5 * vgsteff + 0.25 * t0 - t0 * t0 / (t1 + t1))
+        elif xpart < 0.5:
+            t1 = t1 / 12.0
+            t2 = 0.5 * coxwl / (t1 * t1)
+            t3 = vgsteff * (2.0 * t0 * t0 / 3.0 + vgsteff * (vgsteff - 4.0 * t0 / 3.0)) - 2.0 * t0 * t0 * t0 / 15.0
+            qsrc = -t2 * t3
+        else:
+            qsrc = -0.5 * (qgate + qbulk)
+    return qgate, -(qgate + qbulk + qsrc), qbulk
+
+
+def vfbeff_of(vfbzb, vgs_eff, vbseffcv):
+    v3 = vfbzb - vgs_eff + vbseffcv - DELTA_3
+    if vfbzb <= 0.0:
+        t0 = math.sqrt(v3 * v3 - 4.0 * DELTA_3 * vfbzb)
+    else:
+        t0 = math.sqrt(v3 * v3 + 4.0 * DELTA_3 * vfbzb)
+    return vfbzb - 0.5 * (v3 + t0)
+
+
+def bsim3_capmod2(q, b):
+    coxwl = q["coxwl"]
+    vds = b["vds"]
+    xpart = q.get("xpart", 0.0)
+    vgsteff = vgsteff_cv(q, b)
+    vbseffcv = vbseff_cv(q, b)
+    vfbzb = q["vfbzb"]
+    vfbeff = vfbeff_of(vfbzb, b["vgs_eff"], vbseffcv)
+    qac0 = coxwl * (vfbeff - vfbzb)
+    t0 = 0.5 * q["k1ox"]
+    t3 = b["vgs_eff"] - vfbeff - vbseffcv - vgsteff
+    if q["k1ox"] == 0.0:
+        t1 = 0.0
+    elif t3 < 0.0:
+        t1 = t0 + t3 / q["k1ox"]
+    else:
+        t1 = math.sqrt(t0 * t0 + t3)
+    qsub0 = coxwl * q["k1ox"] * (t1 - t0)
+    abulkcv = b["abulk0"] * q["abulkcvfactor"]
+    vdsatcv = vgsteff / abulkcv
+    v4 = vdsatcv - vds - DELTA_4
+    t0 = math.sqrt(v4 * v4 + 4.0 * DELTA_4 * vdsatcv)
+    vdseffcv = vdsatcv - 0.5 * (v4 + t0)
+    t0 = abulkcv * vdseffcv
+    t1 = 12.0 * (vgsteff - 0.5 * t0 + 1e-20)
+    t2 = vdseffcv / t1
+    t3 = t0 * t2
+    qgate = coxwl * (vgsteff - 0.5 * vdseffcv + t3)
+    qbulk = coxwl * (1.0 - abulkcv) * (0.5 * vdseffcv - t3)
+    if xpart > 0.5:
+        qsrc = -coxwl * (0.5 * vgsteff + 0.25 * t0 - t0 * t0 / (t1 + t1))
+    elif xpart < 0.5:
+        t1 = t1 / 12.0
+        t2 = 0.5 * coxwl / (t1 * t1)
+        t3 = vgsteff * (2.0 * t0 * t0 / 3.0 + vgsteff * (vgsteff - 4.0 * t0 / 3.0)) - 2.0 * t0 * t0 * t0 / 15.0
+        qsrc = -t2 * t3
+    else:
+        qsrc = -0.5 * (qgate + qbulk)
+    qgate += qac0 + qsub0
+    qbulk -= qac0 + qsub0
+    return qgate, -(qgate + qbulk + qsrc), qbulk
+
+
+def charge_thickness(q, b, cox, coxp, toxp, ados, bdos, vtfbphi2):
+    """BSIM3's CAPMOD=3 and, with VTFBPHI2, ADOS, BDOS and VgDP, BSIM4's
+    CAPMOD=2."""
+    coxwl = q["coxwl"]
+    vds = b["vds"]
+    xpart = q.get("xpart", 0.0)
+    vtm = q["vtm"]
+    vgsteff = vgsteff_cv(q, b)
+    vbseffcv = vbseff_cv(q, b)
+    vfbzb = q["vfbzb"]
+    ldeb = q["ldeb"]
+    vfbeff = vfbeff_of(vfbzb, b["vgs_eff"], vbseffcv)
+
+    tox = 1.0e8 * toxp
+    t0 = (b["vgs_eff"] - vbseffcv - vfbzb) / tox
+    tmp = t0 * q.get("acde", 1.0)
+    if -EXP_THRESHOLD < tmp < EXP_THRESHOLD:
+        tcen = ldeb * math.exp(tmp)
+    elif tmp <= -EXP_THRESHOLD:
+        tcen = ldeb * MIN_EXP
+    else:
+        tcen = ldeb * MAX_EXP
+    link = 1.0e-3 * toxp
+    v3 = ldeb - tcen - link
+    v4 = math.sqrt(v3 * v3 + 4.0 * link * ldeb)
+    tcen = ldeb - 0.5 * (v3 + v4)
+    ccen = EPSSI / tcen
+    coxeff = coxp / (coxp + ccen) * ccen
+    coxwlcen = coxwl * coxeff / cox
+    qac0 = coxwlcen * (vfbeff - vfbzb)
+
+    k1ox = q["k1ox"]
+    t0 = 0.5 * k1ox
+    t3 = b["vgs_eff"] - vfbeff - vbseffcv - vgsteff
+    if k1ox == 0.0:
+        t1 = 0.0
+    elif t3 < 0.0:
+        t1 = t0 + t3 / k1ox
+    else:
+        t1 = math.sqrt(t0 * t0 + t3)
+    qsub0 = coxwlcen * k1ox * (t1 - t0)
+
+    moin = q.get("moin", 15.0)
+    if k1ox <= 0.0:
+        denomi = 0.25 * moin * vtm
+        t0 = 0.5 * q["sqrtphi"]
+    else:
+        denomi = moin * vtm * k1ox * k1ox
+        t0 = k1ox * q["sqrtphi"]
+    t1 = 2.0 * t0 + vgsteff
+    deltaphi = vtm * math.log(1.0 + t1 * vgsteff / denomi)
+    if vtfbphi2 is None:
+        vgdp = vgsteff - deltaphi
+        t3 = 4.0 * (b["vth"] - vfbzb - q["phi"])
+        tox += tox
+        t0 = (vgsteff + t3) / tox if t3 >= 0.0 else (vgsteff + 1.0e-20) / tox
+    else:
+        t0 = vgsteff - deltaphi - 0.001
+        t1 = math.sqrt(t0 * t0 + vgsteff * 0.004)
+        vgdp = 0.5 * (t0 + t1)
+        tox += tox
+        t0 = (vgsteff + vtfbphi2) / tox
+    tmp = math.exp(bdos * 0.7 * math.log(t0))
+    tcen = ados * 1.9e-9 / (1.0 + tmp)
+    ccen = EPSSI / tcen
+    coxeff = coxp / (coxp + ccen) * ccen
+    coxwlcen = coxwl * coxeff / cox
+
+    abulkcv = b["abulk0"] * q["abulkcvfactor"]
+    vdsatcv = vgdp / abulkcv
+    v4 = vdsatcv - vds - DELTA_4
+    t0 = math.sqrt(v4 * v4 + 4.0 * DELTA_4 * vdsatcv)
+    vdseffcv = vdsatcv - 0.5 * (v4 + t0)
+    t0 = abulkcv * vdseffcv
+    t1 = vgdp
+    t2 = 12.0 * (t1 - 0.5 * t0 + 1.0e-20)
+    t3 = t0 / t2
+    qgate = coxwlcen * (t1 - t0 * (0.5 - t3))
+    qbulk = coxwlcen * (1.0 - abulkcv) * (0.5 * vdseffcv - t0 * vdseffcv / t2)
+    if xpart > 0.5:
+        qsrc = -coxwlcen * (t1 / 2.0 + t0 / 4.0 - 0.5 * t0 * t0 / t2)
+    elif xpart < 0.5:
+        t2 = t2 / 12.0
+        t3 = 0.5 * coxwlcen / (t2 * t2)
+        t4 = t1 * (2.0 * t0 * t0 / 3.0 + t1 * (t1 - 4.0 * t0 / 3.0)) - 2.0 * t0 * t0 * t0 / 15.0
+        qsrc = -t3 * t4
+    else:
+        qsrc = -0.5 * qgate
+    qgate += qac0 + qsub0 - qbulk
+    qbulk -= qac0 + qsub0
+    return qgate, -(qgate + qbulk + qsrc), qbulk
+
+
+def bsim3_capmod3(q, b):
+    return charge_thickness(q, b, q["cox"], q["cox"], q["tox"], 1.0, 1.0, None)
+
+
+CAPMODS3 = {0: bsim3_capmod0, 1: bsim3_capmod1, 2: bsim3_capmod2, 3: bsim3_capmod3}
+
+
+def bsim4_temp(p, l, w, temp, tnom):
+    """The size and temperature dependent parameters of b4temp.c."""
+    q = dict(p)
+    leff, weff, leffcv, weffcv = sizes(p, l, w)
+    q.update(leff=leff, weff=weff, leffcv=leffcv, weffcv=weffcv)
+    epsrox = p.get("epsrox", 3.9)
+    toxe = p.get("toxe", 3.0e-9)
+    toxp = p.get("toxp", toxe - p.get("dtox", 0.0))
+    q.update(toxe=toxe, toxp=toxp, toxm=p.get("toxm", toxe))
+    q["coxe"] = epsrox * EPS0 / toxe
+    q["coxp"] = epsrox * EPS0 / toxp
+    coxe = q["coxe"]
+    tratio = temp / tnom
+    q["tratio"] = tratio
+    vtm0 = KBOQ * tnom
+    q["vtm"] = KBOQ * temp
+    eg0 = 1.16 - 7.02e-4 * tnom * tnom / (tnom + 1108.0)
+    ni = (
+        1.45e10
+        * (tnom / 300.15)
+        * math.sqrt(tnom / 300.15)
+        * math.exp(21.5565981 - eg0 / (2.0 * vtm0))
+    )
+    ndep = p["ndep"]
+    nsd = p.get("nsd", 1.0e20)
+    phi = vtm0 * math.log(ndep / ni) + p.get("phin", 0.0) + 0.4
+    q["phi"] = phi
+    q["sqrtphi"] = math.sqrt(phi)
+    q["phis3"] = q["sqrtphi"] * phi
+    q["xdep0"] = math.sqrt(2.0 * EPSSI / (CHARGE_Q * ndep * 1.0e6)) * q["sqrtphi"]
+    q["litl"] = math.sqrt(3.0 * 3.9 / epsrox * p.get("xj", 1.5e-7) * toxe)
+    q["vbi"] = vtm0 * math.log(nsd * ndep / (ni * ni))
+    q["cdep0"] = math.sqrt(CHARGE_Q * EPSSI * ndep * 1.0e6 / 2.0 / phi)
+    q["ldeb"] = math.sqrt(EPSSI * vtm0 / (CHARGE_Q * ndep * 1.0e6)) / 3.0
+
+    k1, k2 = p["k1"], p["k2"]
+    vbm = -abs(p.get("vbm", -3.0))
+    if k2 < 0.0:
+        t0 = 0.5 * k1 / k2
+        vbsc = 0.9 * (phi - t0 * t0)
+        if vbsc > -3.0:
+            vbsc = -3.0
+        elif vbsc < -30.0:
+            vbsc = -30.0
+    else:
+        vbsc = -30.0
+    if vbsc > vbm:
+        vbsc = vbm
+    q["vbsc"] = vbsc
+    vth0 = p["vth0"]
+    q["vfb"] = vth0 - phi - k1 * q["sqrtphi"]
+    q["k1ox"] = k1 * toxe / q["toxm"]
+    q["k2ox"] = k2 * toxe / q["toxm"]
+
+    q["factor1"] = math.sqrt(EPSSI / (epsrox * EPS0) * toxe)
+    lt0 = q["factor1"] * math.sqrt(q["xdep0"])
+    drout = p.get("drout", 0.56)
+    q["theta0vb0"] = short_channel(p.get("dsub", drout) * leff / lt0)
+    q["thetarout"] = p.get("pdiblc1", 0.39) * short_channel(drout * leff / lt0) + p.get(
+        "pdiblc2", 0.0086
+    )
+    tmp1 = q["vbi"] - phi
+    t2 = p.get("dvt0w", 0.0) * short_channel(p.get("dvt1w", 5.3e6) * weff * leff / lt0) * tmp1
+    t3 = p.get("dvt0", 2.2) * short_channel(p.get("dvt1", 0.53) * leff / lt0) * tmp1
+    t4 = toxe * phi / (weff + p.get("w0", 2.5e-6))
+    t0 = math.sqrt(1.0 + p.get("lpe0", 1.74e-7) / leff)
+    t5 = q["k1ox"] * (t0 - 1.0) * q["sqrtphi"] + (
+        p.get("kt1", -0.11) + p.get("kt1l", 0.0) / leff
+    ) * (tratio - 1.0)
+    tmp3 = vth0 - t2 - t3 + p.get("k3", 80.0) * t4 + t5
+    q["vfbzb"] = tmp3 - phi - k1 * q["sqrtphi"]
+    t3 = vth0 - q["vfb"] - phi
+    q["vtfbphi1"] = max(2.0 * t3, 0.0)
+    q["vtfbphi2"] = max(4.0 * t3, 0.0)
+
+    mobmod = int(p.get("mobmod", 0.0))
+    dt = tratio - 1.0
+    ua = 1.0e-15 if mobmod == 2 else 1.0e-9
+    uc = -0.0465 if mobmod == 1 else -0.0465e-9
+    uc1 = -0.056 if mobmod == 1 else -0.056e-9
+    q["ua"] = p.get("ua", ua) + p.get("ua1", 1.0e-9) * dt
+    q["ub"] = p.get("ub", 1.0e-19) + p.get("ub1", -1.0e-18) * dt
+    q["uc"] = p.get("uc", uc) + p.get("uc1", uc1) * dt
+    u0 = p.get("u0", 0.067)
+    if u0 > 1.0:
+        u0 = u0 / 1.0e4
+    q["u0temp"] = u0 * math.pow(tratio, p.get("ute", -1.5))
+    q["vsattemp"] = p.get("vsat", 8.0e4) - p.get("at", 3.3e4) * dt
+    prt = p.get("prt", 0.0) * dt
+    width = math.pow(weff * 1e6, p.get("wr", 1.0))
+    q["rds0"] = max(p.get("rdsw", 200.0) + prt, 0.0) / width
+    q["rdswmin"] = max(p.get("rdswmin", 0.0) + prt, 0.0) / width
+    q["mstar"] = 0.5 + math.atan(p.get("minv", 0.0)) / math.pi
+    q["voff"] = p.get("voff", -0.08) + p.get("voffl", 0.0) / leff
+    q["abulkcvfactor"] = 1.0 + math.pow(p.get("clc", 0.1e-6) / leffcv, p.get("cle", 0.6))
+    q["coxwl"] = coxe * weffcv * leffcv
+    q["mobmod"] = mobmod
+    return q
+
+
+def short_channel(x):
+    """1 / (2 (cosh x - 1)) as b4temp.c and b4ld.c keep it finite."""
+    if x < EXP_THRESHOLD:
+        t1 = math.exp(x)
+        t2 = t1 - 1.0
+        return t1 / (t2 * t2 + 2.0 * t1 * MIN_EXP)
+    return 1.0 / (MAX_EXP - 2.0)
+
+
+def bsim4_load(q, vgs, vds, vbs, capmod):
+    """Ids, Idb with GIDL, and (qgate, qdrain, qbulk) of b4ld.c, vds >= 0."""
+    g = lambda name, default: q.get(name, default)
+    leff, weff = q["leff"], q["weff"]
+    toxe, coxe, vtm = q["toxe"], q["coxe"], q["vtm"]
+    phi, sqrtphi = q["phi"], q["sqrtphi"]
+
+    t0 = vbs - q["vbsc"] - 0.001
+    t1 = math.sqrt(t0 * t0 - 0.004 * q["vbsc"])
+    vbseff = q["vbsc"] + 0.5 * (t0 + t1)
+    if vbseff < vbs:
+        vbseff = vbs
+    if vbseff > 0.0:
+        phis = phi * phi / (phi + vbseff)
+        sqrtphis = q["phis3"] / (phi + 0.5 * vbseff)
+    else:
+        phis = phi - vbseff
+        sqrtphis = math.sqrt(phis)
+    xdep = q["xdep0"] * sqrtphis / sqrtphi
+
+    v0 = q["vbi"] - phi
+    t3 = math.sqrt(xdep)
+    t0 = g("dvt2", -0.032) * vbseff
+    t1 = 1.0 + t0 if t0 >= -0.5 else (1.0 + 3.0 * t0) / (3.0 + 8.0 * t0)
+    lt1 = q["factor1"] * t3 * t1
+    t0 = g("dvt2w", -0.032) * vbseff
+    t1 = 1.0 + t0 if t0 >= -0.5 else (1.0 + 3.0 * t0) / (3.0 + 8.0 * t0)
+    ltw = q["factor1"] * t3 * t1
+    theta0 = short_channel(g("dvt1", 0.53) * leff / lt1)
+    delt_vth = g("dvt0", 2.2) * theta0 * v0
+    t2 = g("dvt0w", 0.0) * short_channel(g("dvt1w", 5.3e6) * weff * leff / ltw) * v0
+    temp_ratio = q["tratio"] - 1.0
+    t0 = math.sqrt(1.0 + g("lpe0", 1.74e-7) / leff)
+    t1 = q["k1ox"] * (t0 - 1.0) * sqrtphi + (
+        g("kt1", -0.11) + g("kt1l", 0.0) / leff + g("kt2", 0.022) * vbseff
+    ) * temp_ratio
+    tmp2 = toxe * phi / (weff + g("w0", 2.5e-6))
+    t3 = g("eta0", 0.08) + g("etab", -0.07) * vbseff
+    if t3 < 1.0e-4:
+        t3 = (2.0e-4 - t3) / (3.0 - 2.0e4 * t3)
+    dibl_sft = t3 * q["theta0vb0"] * vds
+    lpe_vb = math.sqrt(1.0 + g("lpeb", 0.0) / leff)
+    vth = (
+        (q["k1ox"] * sqrtphis - q["k1"] * sqrtphi) * lpe_vb
+        + q["vth0"]
+        - q["k2ox"] * vbseff
+        - delt_vth
+        - t2
+        + (g("k3", 80.0) + g("k3b", 0.0) * vbseff) * tmp2
+        + t1
+        - dibl_sft
+    )
+
+    tmp1 = EPSSI / xdep
+    tmp3 = g("cdsc", 2.4e-4) + g("cdscb", 0.0) * vbseff + g("cdscd", 0.0) * vds
+    tmp4 = (tmp1 * g("nfactor", 1.0) + tmp3 * theta0 + g("cit", 0.0)) / coxe
+    n = 1.0 + tmp4 if tmp4 >= -0.5 else (1.0 + 3.0 * tmp4) / (3.0 + 8.0 * tmp4)
+    dvtp0 = g("dvtp0", 0.0)
+    if dvtp0 > 0.0:
+        t0 = -g("dvtp1", 0.0) * vds
+        t2 = MIN_EXP if t0 < -EXP_THRESHOLD else math.exp(t0)
+        t3 = leff + dvtp0 * (1.0 + t2)
+        vth -= n * vtm * math.log(leff / t3)
+
+    ngate = g("ngate", 0.0)
+    if 1.0e18 < ngate < 1.0e25 and vgs > q["vfb"] + phi:
+        t1 = 1.0e6 * CHARGE_Q * EPSSI * ngate / (coxe * coxe)
+        t8 = vgs - q["vfb"] - phi
+        t4 = math.sqrt(1.0 + 2.0 * t8 / t1)
+        t2 = 2.0 * t8 / (t4 + 1.0)
+        t3 = 0.5 * t2 * t2 / t1
+        t7 = 1.12 - t3 - 0.05
+        t6 = math.sqrt(t7 * t7 + 0.224)
+        t5 = 1.12 - 0.5 * (t7 + t6)
+        vgs_eff = vgs - t5
+    else:
+        vgs_eff = vgs
+
+    mstar, voff = q["mstar"], q["voff"]
+    vgst = vgs_eff - vth
+    nvt = n * vtm
+    vgstnvt = mstar * vgst / nvt
+    exparg = (voff - (1.0 - mstar) * vgst) / nvt
+    if vgstnvt > EXP_THRESHOLD:
+        vgsteff = vgst
+    elif exparg > EXP_THRESHOLD:
+        vgsteff = vtm * q["cdep0"] / coxe * math.exp((vgst - voff) / nvt)
+    else:
+        t1 = nvt * math.log(1.0 + math.exp(vgstnvt))
+        t2 = mstar + n * coxe / q["cdep0"] * math.exp(exparg)
+        vgsteff = t1 / t2
+
+    t9 = sqrtphis - sqrtphi
+    weff_b = weff - 2.0 * (g("dwg", 0.0) * vgsteff + g("dwb", 0.0) * t9)
+    if weff_b < 2.0e-8:
+        weff_b = 2.0e-8 * (4.0e-8 - weff_b) / (6.0e-8 - 2.0 * weff_b)
+    t0 = 1.0 + g("prwg", 1.0) * vgsteff
+    t1 = g("prwb", 0.0) * t9
+    t2 = 1.0 / t0 + t1
+    t3 = t2 + math.sqrt(t2 * t2 + 0.01)
+    rds = q["rdswmin"] + 0.5 * q["rds0"] * t3
+
+    t1 = 0.5 * q["k1ox"] * lpe_vb / sqrtphis
+    t9 = math.sqrt(g("xj", 1.5e-7) * xdep)
+    t5 = leff / (leff + 2.0 * t9)
+    t2 = g("a0", 1.0) * t5 + g("b0", 0.0) / (weff + g("b1", 0.0))
+    abulk0 = 1.0 + t1 * t2
+    t8 = g("ags", 0.0) * g("a0", 1.0) * t5 * t5 * t5
+    abulk = abulk0 - t1 * t8 * vgsteff
+    if abulk0 < 0.1:
+        abulk0 = (0.2 - abulk0) / (3.0 - 20.0 * abulk0)
+    if abulk < 0.1:
+        abulk = (0.2 - abulk) / (3.0 - 20.0 * abulk)
+    t2 = g("keta", -0.047) * vbseff
+    t0 = 1.0 / (1.0 + t2) if t2 >= -0.9 else (17.0 + 20.0 * t2) / (0.8 + t2)
+    abulk *= t0
+    abulk0 *= t0
+
+    mobmod = q["mobmod"]
+    if mobmod == 0:
+        t0 = vgsteff + vth + vth
+        t2 = q["ua"] + q["uc"] * vbseff
+        t3 = t0 / toxe
+        t5 = t3 * (t2 + q["ub"] * t3)
+    elif mobmod == 1:
+        t0 = vgsteff + vth + vth
+        t2 = 1.0 + q["uc"] * vbseff
+        t3 = t0 / toxe
+        t4 = t3 * (q["ua"] + q["ub"] * t3)
+        t5 = t4 * t2
+    else:
+        t0 = (vgsteff + q["vtfbphi1"]) / toxe
+        t1 = math.exp(g("eu", 1.67) * math.log(t0))
+        t2 = q["ua"] + q["uc"] * vbseff
+        t5 = t1 * t2
+    denomi = 1.0 + t5 if t5 >= -0.8 else (0.6 + t5) / (7.0 + 10.0 * t5)
+    ueff = q["u0temp"] / denomi
+
+    vsat = q["vsattemp"]
+    wvcox = weff_b * vsat * coxe
+    wvcoxrds = wvcox * rds
+    esat = 2.0 * vsat / ueff
+    esatl = esat * leff
+    a1, a2 = g("a1", 0.0), g("a2", 1.0)
+    if a1 == 0.0:
+        lam = a2
+    elif a1 > 0.0:
+        t0 = 1.0 - a2
+        t1 = t0 - a1 * vgsteff - 0.0001
+        t2 = math.sqrt(t1 * t1 + 0.0004 * t0)
+        lam = a2 + t0 - 0.5 * (t1 + t2)
+    else:
+        t1 = a2 + a1 * vgsteff - 0.0001
+        t2 = math.sqrt(t1 * t1 + 0.0004 * a2)
+        lam = 0.5 * (t1 + t2)
+    vgst2vtm = vgsteff + 2.0 * vtm
+    if rds == 0.0 and lam == 1.0:
+        vdsat = esatl * vgst2vtm / (abulk * esatl + vgst2vtm)
+    else:
+        t9 = abulk * wvcoxrds
+        t7 = vgst2vtm * t9
+        t6 = vgst2vtm * wvcoxrds
+        t0 = 2.0 * abulk * (t9 - 1.0 + 1.0 / lam)
+        t1 = vgst2vtm * (2.0 / lam - 1.0) + abulk * esatl + 3.0 * t7
+        t2 = vgst2vtm * (esatl + 2.0 * t6)
+        t3 = math.sqrt(t1 * t1 - 2.0 * t0 * t2)
+        vdsat = (t1 - t3) / t0
+    delta = g("delta", 0.01)
+    t1 = vdsat - vds - delta
+    t2 = math.sqrt(t1 * t1 + 4.0 * delta * vdsat)
+    vdseff = vdsat - 0.5 * (t1 + t2)
+    if vds == 0.0:
+        vdseff = 0.0
+    vdseff = min(vdseff, vds)
+    diffvds = vds - vdseff
+
+    # The current takes the oxide in series with the inversion charge's
+    # centroid.
+    t0 = (vgsteff + q["vtfbphi2"]) / (2.0e8 * q["toxp"])
+    tmp3 = math.exp(g("bdos", 1.0) * 0.7 * math.log(t0))
+    tcen = g("ados", 1.0) * 1.9e-9 / (1.0 + tmp3)
+    coxeff = EPSSI * q["coxp"] / (EPSSI + q["coxp"] * tcen)
+    beta = ueff * coxeff * weff_b / leff
+    fgche1 = vgsteff * (1.0 - 0.5 * vdseff * abulk / vgst2vtm)
+    fgche2 = 1.0 + vdseff / esatl
+    gche = beta * fgche1 / fgche2
+    idl = gche * vdseff / (1.0 + gche * rds)
+
+    # Channel length modulation in its logarithmic form, DIBL and the
+    # pocket implant's DITS, each with FPROUT's and PVAG's terms.
+    tmp4 = 1.0 - 0.5 * abulk * vdsat / vgst2vtm
+    t0 = esatl + vdsat + 2.0 * wvcoxrds * vgsteff * tmp4
+    t1 = 2.0 / lam - 1.0 + wvcoxrds * abulk
+    vasat = t0 / t1
+    fprout = g("fprout", 0.0)
+    fp = 1.0 if fprout <= 0.0 else 1.0 / (1.0 + fprout * math.sqrt(leff) / vgst2vtm)
+    t9 = g("pvag", 0.0) / esatl * vgsteff
+    pvagterm = 1.0 + t9 if t9 > -0.9 else (0.8 + t9) / (17.0 + 20.0 * t9)
+    pclm = g("pclm", 1.3)
+    if pclm > MIN_EXP and diffvds > 1.0e-10:
+        cclm = fp * pvagterm * (1.0 + rds * idl) * (leff + vdsat / esat) / (pclm * q["litl"])
+        vaclm = cclm * diffvds
+    else:
+        vaclm = cclm = MAX_EXP
+    if q["thetarout"] > MIN_EXP:
+        t8 = abulk * vdsat
+        vadibl = (vgst2vtm - vgst2vtm * t8 / (vgst2vtm + t8)) / q["thetarout"]
+        t7 = g("pdiblcb", 0.0) * vbseff
+        if t7 >= -0.9:
+            vadibl /= 1.0 + t7
+        else:
+            vadibl *= (17.0 + 20.0 * t7) / (0.8 + t7)
+        vadibl *= pvagterm
+    else:
+        vadibl = MAX_EXP
+    va = vasat + vaclm
+    t0 = g("pditsd", 0.0) * vds
+    t1 = MAX_EXP if t0 > EXP_THRESHOLD else math.exp(t0)
+    pdits = g("pdits", 0.0)
+    if pdits > MIN_EXP:
+        vadits = (1.0 + (1.0 + g("pditsl", 0.0) * leff) * t1) / pdits * fp
+    else:
+        vadits = MAX_EXP
+    pscbe1, pscbe2 = g("pscbe1", 4.24e8), g("pscbe2", 1.0e-5)
+    if pscbe2 > 0.0:
+        if diffvds > pscbe1 * q["litl"] / EXP_THRESHOLD:
+            vascbe = leff * math.exp(pscbe1 * q["litl"] / diffvds) / pscbe2
+        else:
+            vascbe = MAX_EXP * leff / pscbe2
+    else:
+        vascbe = MAX_EXP
+
+    idsa = idl * (1.0 + diffvds / vadibl)
+    idsa *= 1.0 + diffvds / vadits
+    idsa *= 1.0 + math.log(va / vasat) / cclm
+    ids = idsa * (1.0 + diffvds / vascbe)
+
+    tmp = g("alpha0", 0.0) + g("alpha1", 0.0) * leff
+    beta0 = g("beta0", 30.0)
+    if tmp <= 0.0 or beta0 <= 0.0:
+        isub = 0.0
+    elif diffvds > beta0 / EXP_THRESHOLD:
+        isub = tmp / leff * diffvds * math.exp(-beta0 / diffvds) * idsa
+    else:
+        isub = tmp / leff * MIN_EXP * diffvds * idsa
+
+    agidl, bgidl, cgidl = g("agidl", 0.0), g("bgidl", 2.3e9), g("cgidl", 0.5)
+    t1 = (vds - vgs_eff - g("egidl", 0.8)) / (3.0 * toxe)
+    vbd = vbs - vds
+    if agidl <= 0.0 or bgidl <= 0.0 or cgidl <= 0.0 or t1 <= 0.0 or vbd > 0.0:
+        igidl = 0.0
+    else:
+        t2 = bgidl / t1
+        if t2 < 100.0:
+            igidl = agidl * weff * t1 * math.exp(-t2)
+        else:
+            igidl = agidl * weff * 3.720075976e-44 * t1
+        t5 = -vbd * vbd * vbd
+        igidl *= t5 / (cgidl + t5)
+
+    bias = dict(
+        vgs_eff=vgs_eff,
+        vbs=vbs,
+        vbseff=vbseff,
+        phis=phis,
+        sqrtphis=sqrtphis,
+        vth=vth,
+        n=n,
+        vds=vds,
+        abulk0=abulk0,
+    )
+    charges = CAPMODS4[capmod](q, bias)
+    return ids, isub + igidl, charges
+
+
+def bsim4_capmod0(q, b):
+    return bsim3_capmod0(dict(q, cox=q["coxe"]), b)
+
+
+def bsim4_capmod2(q, b):
+    return charge_thickness(
+        q, b, q["coxe"], q["coxp"], q["toxp"], q.get("ados", 1.0), q.get("bdos", 1.0), q["vtfbphi2"]
+    )
+
+
+CAPMODS4 = {0: bsim4_capmod0, 1: bsim3_capmod2, 2: bsim4_capmod2}
+
+
+BSIM3_CARD = (
+    ".model N018 NMOS(LEVEL=49 TOX=4.1e-9 NCH=2.3549e17 XJ=1e-7 VTH0=0.3694 K1=0.589 "
+    "K2=1.25e-3 K3=1e-3 K3B=2.4 W0=1e-7 NLX=1.798e-7 DVT0W=0.2 DVT1W=5.3e6 DVT2W=-0.032 "
+    "DVT0=1.23 DVT1=0.396 DVT2=0.0585 U0=276.8 UA=-1.39e-9 UB=2.3e-18 UC=5.4e-11 "
+    "VSAT=1.04e5 A0=1.94 AGS=0.4 B0=-1.4e-8 B1=-1e-7 KETA=-0.01 A1=6.8e-4 A2=0.75 "
+    "RDSW=105 PRWG=0.5 PRWB=-0.2 WR=1 WINT=5e-9 LINT=1.7e-8 DLC=2e-8 DWG=-4.7e-9 "
+    "DWB=-1e-9 VOFF=-0.088 NFACTOR=2.16 CIT=1e-5 CDSC=2.4e-4 CDSCD=1e-4 CDSCB=-1e-4 "
+    "ETA0=2.8e-3 ETAB=6e-6 DSUB=0.0169 PCLM=0.73 PDIBLC1=0.198 PDIBLC2=2.2e-3 "
+    "PDIBLCB=-0.1 DROUT=0.77 PSCBE1=8e8 PSCBE2=5e-6 PVAG=0.2 DELTA=0.01 NGATE=1e20 "
+    "ALPHA0=1e-7 BETA0=25 KT1=-0.11 KT2=0.022 UTE=-1.5 UA1=4.31e-9 UB1=-7.61e-18 "
+    "UC1=-5.6e-11 AT=3.3e4 PRT=20 CLC=1e-7 CLE=0.6 NOFF=1.2 VOFFCV=-0.02 ACDE=0.8 "
+    "MOIN=12 VFBCV=-0.9)"
+)
+
+BSIM4_CARD = (
+    ".model N090 NMOS(LEVEL=54 TOXE=1.85e-9 TOXP=1.45e-9 TOXM=1.85e-9 EPSROX=3.9 "
+    "NDEP=2.8e18 NSD=2e20 XJ=3e-8 VTH0=0.397 K1=0.4 K2=0.01 K3=0 K3B=0 W0=2.5e-6 "
+    "LPE0=1e-10 LPEB=5e-9 DVTP0=1e-11 DVTP1=0.5 DVT0=1 DVT1=2 DVT2=-0.032 DVT0W=0.1 "
+    "DVT1W=5.3e6 DVT2W=-0.032 MINV=0.05 VOFF=-0.13 VOFFL=1e-10 NFACTOR=1.9 CIT=0 "
+    "CDSC=2.4e-4 CDSCB=0 CDSCD=0 ETA0=0.0049 ETAB=0 DSUB=0.1 MOBMOD=0 U0=0.046 "
+    "UA=6e-10 UB=1.2e-18 UC=-1e-11 EU=1.67 VSAT=1.1e5 A0=1 AGS=0.5 B0=0 B1=0 KETA=-0.04 "
+    "A1=0 A2=1 RDSW=155 RDSWMIN=10 PRWG=0.5 PRWB=-0.1 WR=1 WINT=5e-9 LINT=3.75e-9 "
+    "DLC=5e-9 DWG=0 DWB=0 PCLM=0.04 PDIBLC1=0.001 PDIBLC2=0.001 PDIBLCB=-0.005 "
+    "DROUT=0.5 PVAG=1e-20 DELTA=0.01 FPROUT=0.2 PDITS=0.08 PDITSD=0.23 PDITSL=2.3e6 "
+    "PSCBE1=8.14e8 PSCBE2=1e-7 ALPHA0=0.074 ALPHA1=0.005 BETA0=30 AGIDL=1e-9 "
+    "BGIDL=1.4e9 CGIDL=0.25 EGIDL=0.8 NGATE=3e20 KT1=-0.11 KT2=0.022 UTE=-1.5 "
+    "UA1=4.31e-9 UB1=-7.61e-18 UC1=-5.6e-11 AT=3.3e4 PRT=0 CLC=1e-7 CLE=0.6 "
+    "NOFF=0.9 VOFFCV=0.02 ACDE=1 MOIN=15 VFBCV=-1 ADOS=1.1 BDOS=0.9)"
+)
+
+
+def write(path, version, card, l, w, temp_process, load, capmods):
+    p = parse_card(card)
+    tnom = 27.0 + KELVIN
+    with open(path, "w") as out:
+        out.write(f"# {version} n channel core, written by bsim_reference.py, a plain\n")
+        out.write("# floating point transcription of the Berkeley reference code.\n")
+        out.write(f"# l={l!r} w={w!r} tnom=27\n")
+        out.write(f"# {card}\n")
+        out.write("temp,capmod,xpart,vgs,vds,vbs,ids,idb,qg,qd,qb\n")
+        for temp in (27.0, 100.0):
+            for capmod in capmods:
+                for xpart in (0.0, 0.5, 1.0):
+                    q = temp_process(dict(p, xpart=xpart), l, w, temp + KELVIN, tnom)
+                    for vbs in (0.3, 0.0, -1.2):
+                        for vds in (0.0, 0.05, 0.4, 1.2):
+                            for vgs in (-1.2, -0.3, 0.2, 0.45, 0.7, 1.2):
+                                ids, idb, (qg, qd, qb) = load(q, vgs, vds, vbs, capmod)
+                                out.write(
+                                    f"{temp:g},{capmod},{xpart:g},{vgs:g},{vds:g},{vbs:g},"
+                                    f"{ids:.9e},{idb:.9e},{qg:.9e},{qd:.9e},{qb:.9e}\n"
+                                )
+
+
+if __name__ == "__main__":
+    write("bsim3.csv", "BSIM3v3.3", BSIM3_CARD, 0.18e-6, 2e-6, bsim3_temp, bsim3_load, range(4))
+    write("bsim4.csv", "BSIM4", BSIM4_CARD, 0.09e-6, 1e-6, bsim4_temp, bsim4_load, range(3))
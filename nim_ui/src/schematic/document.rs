@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use nim_spice::frontend::kicad;
+use nim_spice::frontend::ltspice::Importer;
+use nim_spice::frontend::parser::netlist::Netlist;
+use nim_spice::frontend::schematic::Schematic;
+use nim_spice::utils::error::ErrorHandler;
+use std::path::{Path, PathBuf};
+
+/// The schematic currently open in the editor, together with the netlist it
+/// was imported with.
+#[derive(Resource, Default)]
+pub struct SchematicDocument {
+    pub path: Option<PathBuf>,
+    pub schematic: Schematic,
+    pub netlist: Netlist,
+}
+
+impl SchematicDocument {
+    /// Open an LTspice `.asc` schematic, looking up symbols next to it and in
+    /// `symbol_paths`.
+    pub fn open_ltspice(path: &Path, symbol_paths: &[PathBuf]) -> Result<Self, ErrorHandler> {
+        let mut importer = Importer::new();
+        for dir in symbol_paths {
+            importer.add_search_path(dir);
+        }
+        let import = importer.import(path)?;
+        Ok(SchematicDocument {
+            path: Some(path.to_path_buf()),
+            schematic: import.schematic,
+            netlist: import.netlist,
+        })
+    }
+
+    /// Open a KiCad `.kicad_sch` schematic.
+    pub fn open_kicad(path: &Path) -> Result<Self, ErrorHandler> {
+        let import = kicad::import(path)?;
+        Ok(SchematicDocument {
+            path: Some(path.to_path_buf()),
+            schematic: import.schematic,
+            netlist: import.netlist,
+        })
+    }
+
+    /// Write the schematic connectivity as a KiCad netlist for board layout.
+    pub fn export_kicad_netlist(&self, path: &Path) -> std::io::Result<()> {
+        let source = self
+            .path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        std::fs::write(path, kicad::export_netlist(&self.schematic, &source))
+    }
+}
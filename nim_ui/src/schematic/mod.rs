@@ -2,3 +2,4 @@ use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 pub mod canvas;
+pub mod document;